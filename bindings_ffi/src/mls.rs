@@ -2,11 +2,9 @@ pub use crate::inbox_owner::SigningError;
 use crate::logger::init_logger;
 use crate::{FfiSubscribeError, GenericError};
 use prost::Message;
-use std::{collections::HashMap, convert::TryInto, sync::Arc};
+use std::{collections::HashMap, convert::TryInto, sync::Arc, time::Duration};
 use tokio::sync::Mutex;
 use xmtp_api_grpc::grpc_api_helper::Client as TonicApiClient;
-use xmtp_content_types::reaction::ReactionCodec;
-use xmtp_content_types::ContentCodec;
 use xmtp_id::associations::verify_signed_with_public_context;
 use xmtp_id::scw_verifier::RemoteSignatureVerifier;
 use xmtp_id::{
@@ -18,7 +16,11 @@ use xmtp_id::{
     },
     InboxId,
 };
+use xmtp_mls::codec_registry::{CodecRegistry, JsonCodec};
+use xmtp_mls::decoded_message::DecodedContent;
+use xmtp_mls::groups::attachments::AttachmentUploader;
 use xmtp_mls::groups::device_sync::preference_sync::UserPreferenceUpdate;
+use xmtp_mls::groups::link_previews::{LinkMetadata, LinkPreviewError, LinkPreviewFetcher};
 use xmtp_mls::groups::scoped_client::LocalScopedGroupClient;
 use xmtp_mls::groups::HmacKey;
 use xmtp_mls::storage::group::ConversationType;
@@ -44,13 +46,17 @@ use xmtp_mls::{
     storage::{
         consent_record::{ConsentState, ConsentType, StoredConsentRecord},
         group::GroupQueryArgs,
-        group_message::{DeliveryStatus, GroupMessageKind, StoredGroupMessage},
+        group_message::{
+            DeliveryStatus, GroupMessageKind, StoredGroupMessage, StoredGroupMessageHeader,
+        },
+        unknown_content_type::StoredUnknownContentType,
         EncryptedMessageStore, EncryptionKey, StorageOption,
     },
-    AbortHandle, GenericStreamHandle, StreamHandle,
+    subscriptions::{MessageStreamFilter, StreamAllMessagesOptions, StreamItem},
+    AbortHandle, GenericStreamHandle, StreamHandle, StreamMetrics,
 };
 use xmtp_proto::xmtp::mls::message_contents::content_types::ReactionV2;
-use xmtp_proto::xmtp::mls::message_contents::{DeviceSyncKind, EncodedContent};
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, DeviceSyncKind, EncodedContent};
 pub type RustXmtpClient = MlsClient<TonicApiClient>;
 
 #[derive(uniffi::Object, Clone)]
@@ -281,6 +287,30 @@ impl FfiXmtpClient {
         Ok(message.into())
     }
 
+    /// The text to show in place of the message if it can't be decoded: the fallback its
+    /// sender declared. `None` if the content can be decoded, or has no fallback.
+    pub fn message_fallback(&self, message_id: Vec<u8>) -> Result<Option<String>, GenericError> {
+        let message = self.inner_client.message(message_id)?;
+        Ok(self.inner_client.message_fallback(message))
+    }
+
+    /// Register a content type of the app, whose content is JSON, so its messages can be sent
+    /// with [`FfiConversation::send_content`] and aren't reported as unknown
+    pub fn register_codec(&self, content_type: FfiContentTypeId) {
+        self.inner_client
+            .register_codec(JsonCodec::new(content_type.into()));
+    }
+
+    /// The content types received that this installation can't decode, most recently seen
+    /// first
+    pub fn content_compat_report(&self) -> Result<Vec<FfiUnknownContentType>, GenericError> {
+        let report = self
+            .inner_client
+            .content_compat_report()
+            .map_err(GenericError::from_error)?;
+        Ok(report.into_iter().map(Into::into).collect())
+    }
+
     pub async fn can_message(
         &self,
         account_addresses: Vec<String>,
@@ -377,6 +407,66 @@ impl FfiXmtpClient {
         Ok(result.into())
     }
 
+    /// Allow preferences to be stored under `namespace`. Register namespaces every time the
+    /// client is built.
+    pub fn register_preference_namespace(&self, namespace: String) -> Result<(), GenericError> {
+        self.inner_client
+            .register_preference_namespace(&namespace)
+            .map_err(GenericError::from_error)
+    }
+
+    /// Store `payload` as the preference in `namespace` and sync it to the user's other
+    /// installations
+    pub fn set_custom_preference(
+        &self,
+        namespace: String,
+        payload: Vec<u8>,
+    ) -> Result<(), GenericError> {
+        self.inner_client
+            .set_custom_preference(&namespace, &payload)
+            .map_err(GenericError::from_error)
+    }
+
+    pub fn custom_preference(&self, namespace: String) -> Result<Option<Vec<u8>>, GenericError> {
+        self.inner_client
+            .custom_preference(&namespace)
+            .map_err(GenericError::from_error)
+    }
+
+    /// Block `inbox_id` on all of the user's installations. With `hide_messages`, its messages
+    /// are also hidden in groups the user shares with it.
+    pub async fn block_inbox(
+        &self,
+        inbox_id: String,
+        hide_messages: bool,
+    ) -> Result<(), GenericError> {
+        self.inner_client
+            .block_inbox(&inbox_id, hide_messages)
+            .await
+            .map_err(GenericError::from_error)
+    }
+
+    pub fn unblock_inbox(&self, inbox_id: String) -> Result<(), GenericError> {
+        self.inner_client
+            .unblock_inbox(&inbox_id)
+            .map_err(GenericError::from_error)
+    }
+
+    pub fn is_inbox_blocked(&self, inbox_id: String) -> Result<bool, GenericError> {
+        self.inner_client
+            .is_inbox_blocked(&inbox_id)
+            .map_err(GenericError::from_error)
+    }
+
+    /// The ids of the inboxes that are blocked
+    pub fn blocked_inboxes(&self) -> Result<Vec<String>, GenericError> {
+        let blocked = self
+            .inner_client
+            .blocked_inboxes()
+            .map_err(GenericError::from_error)?;
+        Ok(blocked.into_iter().map(|entry| entry.inbox_id).collect())
+    }
+
     pub fn sign_with_installation_key(&self, text: &str) -> Result<Vec<u8>, GenericError> {
         let inner = self.inner_client.as_ref();
         Ok(inner.context().sign_with_public_context(text)?)
@@ -1067,6 +1157,52 @@ impl FfiConversations {
         Ok(convo_list)
     }
 
+    /// Like [`Self::list`], with each conversation's unread count and consent state, for
+    /// showing an inbox screen in one query
+    pub fn list_overview(
+        &self,
+        opts: FfiListConversationsOptions,
+    ) -> Result<Vec<Arc<FfiConversationOverview>>, GenericError> {
+        let inner = self.inner_client.as_ref();
+        let overview = inner
+            .list_conversations_overview(opts.into())?
+            .into_iter()
+            .map(|overview| {
+                Arc::new(FfiConversationOverview {
+                    conversation: overview.group.into(),
+                    last_message: overview.last_message.map(Into::into),
+                    unread_count: overview.unread_count,
+                    consent_state: overview.consent_state.into(),
+                })
+            })
+            .collect();
+
+        Ok(overview)
+    }
+
+    /// Like [`Self::list`], but only returns ids, timestamps and the header of each
+    /// conversation's last message, without message content
+    pub fn list_headers(
+        &self,
+        opts: FfiListConversationsOptions,
+    ) -> Result<Vec<FfiConversationHeader>, GenericError> {
+        let inner = self.inner_client.as_ref();
+        let headers = inner
+            .list_conversations(opts.into())?
+            .into_iter()
+            .map(|conversation_item| FfiConversationHeader {
+                id: conversation_item.group.group_id,
+                created_at_ns: conversation_item.group.created_at_ns,
+                last_message: conversation_item
+                    .last_message
+                    .as_ref()
+                    .map(|message| StoredGroupMessageHeader::from(message).into()),
+            })
+            .collect();
+
+        Ok(headers)
+    }
+
     pub fn list_groups(
         &self,
         opts: FfiListConversationsOptions,
@@ -1156,37 +1292,134 @@ impl FfiConversations {
         FfiStreamCloser::new(handle)
     }
 
+    /// Like [`Self::stream`], but calls `idle_callback` whenever no conversation has arrived
+    /// for `idle_timeout_ms`. The timer is local, an idle stream may be quiet or disconnected.
+    pub async fn stream_with_idle_timeout(
+        &self,
+        callback: Arc<dyn FfiConversationCallback>,
+        conversation_type: Option<FfiConversationType>,
+        idle_timeout_ms: u64,
+        idle_callback: Arc<dyn FfiIdleCallback>,
+    ) -> FfiStreamCloser {
+        let handle = RustXmtpClient::stream_conversations_with_idle_timeout_callback(
+            self.inner_client.clone(),
+            conversation_type.map(Into::into),
+            Duration::from_millis(idle_timeout_ms),
+            move |item| match item {
+                StreamItem::Item(Ok(c)) => callback.on_conversation(Arc::new(c.into())),
+                StreamItem::Item(Err(e)) => callback.on_error(e.into()),
+                StreamItem::Idle => idle_callback.on_idle(),
+            },
+        );
+
+        FfiStreamCloser::new(handle)
+    }
+
+    #[uniffi::method(default(options = None))]
     pub async fn stream_all_group_messages(
         &self,
         message_callback: Arc<dyn FfiMessageCallback>,
+        options: Option<FfiStreamMessagesOptions>,
     ) -> FfiStreamCloser {
-        self.stream_messages(message_callback, Some(FfiConversationType::Group))
-            .await
+        self.stream_messages(
+            message_callback,
+            Some(FfiConversationType::Group),
+            options,
+            MessageStreamFilter::default(),
+        )
+        .await
     }
 
+    #[uniffi::method(default(options = None))]
     pub async fn stream_all_dm_messages(
         &self,
         message_callback: Arc<dyn FfiMessageCallback>,
+        options: Option<FfiStreamMessagesOptions>,
     ) -> FfiStreamCloser {
-        self.stream_messages(message_callback, Some(FfiConversationType::Dm))
-            .await
+        self.stream_messages(
+            message_callback,
+            Some(FfiConversationType::Dm),
+            options,
+            MessageStreamFilter::default(),
+        )
+        .await
     }
 
+    #[uniffi::method(default(options = None))]
     pub async fn stream_all_messages(
         &self,
         message_callback: Arc<dyn FfiMessageCallback>,
+        options: Option<FfiStreamMessagesOptions>,
     ) -> FfiStreamCloser {
-        self.stream_messages(message_callback, None).await
+        self.stream_messages(
+            message_callback,
+            None,
+            options,
+            MessageStreamFilter::default(),
+        )
+        .await
+    }
+
+    /// Stream messages from all conversations, skipping messages whose content type
+    /// doesn't match `filter`
+    #[uniffi::method(default(options = None))]
+    pub async fn stream_all_messages_filtered(
+        &self,
+        message_callback: Arc<dyn FfiMessageCallback>,
+        conversation_type: Option<FfiConversationType>,
+        filter: FfiMessageStreamFilter,
+        options: Option<FfiStreamMessagesOptions>,
+    ) -> FfiStreamCloser {
+        self.stream_messages(message_callback, conversation_type, options, filter.into())
+            .await
+    }
+
+    /// Like [`Self::stream_all_messages_filtered`], but calls `idle_callback` whenever no
+    /// message has arrived for `idle_timeout_ms`
+    #[uniffi::method(default(options = None))]
+    pub async fn stream_all_messages_with_idle_timeout(
+        &self,
+        message_callback: Arc<dyn FfiMessageCallback>,
+        conversation_type: Option<FfiConversationType>,
+        filter: FfiMessageStreamFilter,
+        idle_timeout_ms: u64,
+        idle_callback: Arc<dyn FfiIdleCallback>,
+        options: Option<FfiStreamMessagesOptions>,
+    ) -> FfiStreamCloser {
+        let handle = RustXmtpClient::stream_all_messages_with_idle_timeout_callback(
+            self.inner_client.clone(),
+            StreamAllMessagesOptions {
+                conversation_type: conversation_type.map(Into::into),
+                consent_states: options.unwrap_or_default().consent_states(),
+                filter: filter.into(),
+                ..Default::default()
+            },
+            Duration::from_millis(idle_timeout_ms),
+            move |item| match item {
+                StreamItem::Item(Ok(m)) => message_callback.on_message(m.into()),
+                StreamItem::Item(Err(e)) => message_callback.on_error(e.into()),
+                StreamItem::Idle => idle_callback.on_idle(),
+            },
+        );
+
+        FfiStreamCloser::new(handle)
     }
 
     async fn stream_messages(
         &self,
         message_callback: Arc<dyn FfiMessageCallback>,
         conversation_type: Option<FfiConversationType>,
+        options: Option<FfiStreamMessagesOptions>,
+        filter: MessageStreamFilter,
     ) -> FfiStreamCloser {
         let handle = RustXmtpClient::stream_all_messages_with_callback(
             self.inner_client.clone(),
-            conversation_type.map(Into::into),
+            StreamAllMessagesOptions {
+                conversation_type: conversation_type.map(Into::into),
+                consent_states: options.unwrap_or_default().consent_states(),
+                filter,
+                ..Default::default()
+            },
             move |msg| match msg {
                 Ok(m) => message_callback.on_message(m.into()),
                 Err(e) => message_callback.on_error(e.into()),
@@ -1267,11 +1500,35 @@ impl TryFrom<UserPreferenceUpdate> for FfiPreferenceUpdate {
     fn try_from(value: UserPreferenceUpdate) -> Result<Self, Self::Error> {
         match value {
             UserPreferenceUpdate::HmacKeyUpdate { key } => Ok(FfiPreferenceUpdate::HMAC { key }),
+            UserPreferenceUpdate::NotificationSettingsUpdate(settings) => {
+                Ok(FfiPreferenceUpdate::NotificationSettings {
+                    group_id: settings.group_id,
+                    mute_until_ns: settings.mute_until_ns,
+                    mentions_only: settings.mentions_only,
+                })
+            }
+            UserPreferenceUpdate::DraftUpdate(draft) => Ok(FfiPreferenceUpdate::Draft {
+                group_id: draft.group_id,
+                content: draft.content,
+            }),
+            UserPreferenceUpdate::CustomUpdate(preference) => Ok(FfiPreferenceUpdate::Custom {
+                namespace: preference.namespace,
+                payload: preference.payload,
+            }),
+            UserPreferenceUpdate::BlockUpdate(entry) => Ok(FfiPreferenceUpdate::Block {
+                inbox_id: entry.inbox_id,
+                blocked: entry.blocked,
+                hide_messages: entry.hide_messages,
+            }),
             // These are filtered out in the stream and should not be here
             // We're keeping preference update and consent streams separate right now.
             UserPreferenceUpdate::ConsentUpdate(_) => Err(GenericError::Generic {
                 err: "Consent updates should be filtered out.".to_string(),
             }),
+            // Invite links are only used internally, to admit members on any installation
+            UserPreferenceUpdate::InviteLinkUpdate(_) => Err(GenericError::Generic {
+                err: "Invite link updates are not streamed.".to_string(),
+            }),
         }
     }
 }
@@ -1297,6 +1554,31 @@ impl FfiConversationListItem {
     }
 }
 
+#[derive(uniffi::Object)]
+pub struct FfiConversationOverview {
+    conversation: FfiConversation,
+    last_message: Option<FfiMessage>,
+    unread_count: u64,
+    consent_state: FfiConsentState,
+}
+
+#[uniffi::export]
+impl FfiConversationOverview {
+    pub fn conversation(&self) -> Arc<FfiConversation> {
+        Arc::new(self.conversation.clone())
+    }
+    pub fn last_message(&self) -> Option<FfiMessage> {
+        self.last_message.clone()
+    }
+    /// Messages from other members that this inbox hasn't read
+    pub fn unread_count(&self) -> u64 {
+        self.unread_count
+    }
+    pub fn consent_state(&self) -> FfiConsentState {
+        self.consent_state.clone()
+    }
+}
+
 impl From<MlsGroup<RustXmtpClient>> for FfiConversation {
     fn from(mls_group: MlsGroup<RustXmtpClient>) -> FfiConversation {
         FfiConversation { inner: mls_group }
@@ -1333,7 +1615,7 @@ pub enum FfiPermissionLevel {
     SuperAdmin,
 }
 
-#[derive(uniffi::Enum, PartialEq, Debug)]
+#[derive(uniffi::Enum, Clone, PartialEq, Debug)]
 pub enum FfiConsentState {
     Unknown,
     Allowed,
@@ -1417,6 +1699,42 @@ pub struct FfiListMessagesOptions {
     pub content_types: Option<Vec<FfiContentType>>,
 }
 
+#[derive(uniffi::Record, Clone, Default)]
+pub struct FfiStreamMessagesOptions {
+    /// Only stream messages from conversations in one of these consent states. All
+    /// conversations when unset.
+    pub consent_states: Option<Vec<FfiConsentState>>,
+}
+
+impl FfiStreamMessagesOptions {
+    fn consent_states(self) -> Option<Vec<ConsentState>> {
+        self.consent_states
+            .map(|states| states.into_iter().map(Into::into).collect())
+    }
+}
+
+#[derive(uniffi::Record, Clone, Default)]
+pub struct FfiMessageStreamFilter {
+    /// Only stream messages with one of these content types. All content types when unset.
+    pub allowed_content_types: Option<Vec<FfiContentType>>,
+    /// Never stream messages with one of these content types
+    pub denied_content_types: Vec<FfiContentType>,
+    /// Only stream messages that mention this inbox
+    pub only_mentions: bool,
+}
+
+impl From<FfiMessageStreamFilter> for MessageStreamFilter {
+    fn from(filter: FfiMessageStreamFilter) -> Self {
+        let mut result = MessageStreamFilter::default();
+        if let Some(allowed) = filter.allowed_content_types {
+            result = result.allow(allowed.into_iter().map(Into::into));
+        }
+        result
+            .deny(filter.denied_content_types.into_iter().map(Into::into))
+            .only_mentions(filter.only_mentions)
+    }
+}
+
 #[derive(uniffi::Enum, Clone)]
 pub enum FfiContentType {
     Unknown,
@@ -1429,6 +1747,7 @@ pub enum FfiContentType {
     Attachment,
     RemoteAttachment,
     TransactionReference,
+    LinkPreview,
 }
 
 impl From<FfiContentType> for ContentType {
@@ -1444,6 +1763,33 @@ impl From<FfiContentType> for ContentType {
             FfiContentType::Attachment => ContentType::Attachment,
             FfiContentType::RemoteAttachment => ContentType::RemoteAttachment,
             FfiContentType::TransactionReference => ContentType::TransactionReference,
+            FfiContentType::LinkPreview => ContentType::LinkPreview,
+        }
+    }
+}
+
+impl From<ContentType> for FfiContentType {
+    fn from(value: ContentType) -> Self {
+        match value {
+            ContentType::Text => FfiContentType::Text,
+            ContentType::GroupMembershipChange => FfiContentType::GroupMembershipChange,
+            ContentType::GroupUpdated => FfiContentType::GroupUpdated,
+            ContentType::Reaction => FfiContentType::Reaction,
+            ContentType::ReadReceipt => FfiContentType::ReadReceipt,
+            ContentType::Reply => FfiContentType::Reply,
+            ContentType::Attachment => FfiContentType::Attachment,
+            ContentType::RemoteAttachment => FfiContentType::RemoteAttachment,
+            ContentType::TransactionReference => FfiContentType::TransactionReference,
+            ContentType::LinkPreview => FfiContentType::LinkPreview,
+            ContentType::Unknown
+            | ContentType::Capabilities
+            | ContentType::Profile
+            | ContentType::Edit
+            | ContentType::DeleteMessage
+            | ContentType::InviteRedemption
+            | ContentType::JoinRequest
+            | ContentType::Poll
+            | ContentType::PollVote => FfiContentType::Unknown,
         }
     }
 }
@@ -1480,6 +1826,31 @@ impl FfiConversation {
         Ok(message_id)
     }
 
+    /// Send encoded content, checking that the client has a codec for its content type so
+    /// other installations can decode it
+    pub async fn send_content(&self, content_bytes: Vec<u8>) -> Result<Vec<u8>, GenericError> {
+        let content =
+            EncodedContent::decode(content_bytes.as_slice()).map_err(GenericError::from_error)?;
+        let message_id = self.inner.send_content(content).await?;
+        Ok(message_id)
+    }
+
+    /// Send `text` with a preview of the first link in it, fetched with `fetcher`. Text without
+    /// links, or whose preview can't be fetched in time, is sent as a plain text message.
+    pub async fn send_text_with_link_preview(
+        &self,
+        text: String,
+        fetcher: Arc<dyn FfiLinkPreviewFetcher>,
+    ) -> Result<Vec<u8>, GenericError> {
+        let fetcher = FfiLinkPreviewFetcherAdapter(fetcher);
+        let no_uploader: Option<&(dyn AttachmentUploader + Send + Sync)> = None;
+        let message_id = self
+            .inner
+            .send_text_with_link_preview(&text, &fetcher, no_uploader)
+            .await?;
+        Ok(message_id)
+    }
+
     /// send a message without immediately publishing to the delivery service.
     pub fn send_optimistic(&self, content_bytes: Vec<u8>) -> Result<Vec<u8>, GenericError> {
         let id = self
@@ -1525,6 +1896,9 @@ impl FfiConversation {
                 content_types: opts
                     .content_types
                     .map(|types| types.into_iter().map(Into::into).collect()),
+                cursor: None,
+                before_cursor: None,
+                after_cursor: None,
             })?
             .into_iter()
             .map(|msg| msg.into())
@@ -1533,6 +1907,85 @@ impl FfiConversation {
         Ok(messages)
     }
 
+    /// Load a page of messages, starting at `cursor` from a previous page or at the start of
+    /// the query without one. `opts.limit` sets the page size. Unlike paging with
+    /// `sent_before_ns` or `sent_after_ns`, the cursors keep their place while messages arrive
+    /// or are deleted.
+    pub async fn find_messages_page(
+        &self,
+        opts: FfiListMessagesOptions,
+        cursor: Option<String>,
+    ) -> Result<FfiMessagePage, GenericError> {
+        let delivery_status = opts.delivery_status.map(|status| status.into());
+        let direction = opts.direction.map(|dir| dir.into());
+        let kind = match self.conversation_type().await? {
+            FfiConversationType::Group => None,
+            FfiConversationType::Dm => Some(GroupMessageKind::Application),
+            FfiConversationType::Sync => None,
+        };
+
+        let page = self.inner.find_messages_page(
+            &MsgQueryArgs {
+                sent_before_ns: opts.sent_before_ns,
+                sent_after_ns: opts.sent_after_ns,
+                limit: opts.limit,
+                kind,
+                delivery_status,
+                direction,
+                content_types: opts
+                    .content_types
+                    .map(|types| types.into_iter().map(Into::into).collect()),
+                cursor: None,
+                before_cursor: None,
+                after_cursor: None,
+            },
+            cursor.as_deref(),
+        )?;
+
+        Ok(FfiMessagePage {
+            items: page.items.into_iter().map(Into::into).collect(),
+            next_cursor: page.next_cursor,
+            prev_cursor: page.prev_cursor,
+            total_estimate: page.total_estimate,
+        })
+    }
+
+    /// Like [`Self::find_messages`], but without message content
+    pub async fn find_message_headers(
+        &self,
+        opts: FfiListMessagesOptions,
+    ) -> Result<Vec<FfiMessageHeader>, GenericError> {
+        let delivery_status = opts.delivery_status.map(|status| status.into());
+        let direction = opts.direction.map(|dir| dir.into());
+        let kind = match self.conversation_type().await? {
+            FfiConversationType::Group => None,
+            FfiConversationType::Dm => Some(GroupMessageKind::Application),
+            FfiConversationType::Sync => None,
+        };
+
+        let headers = self
+            .inner
+            .find_message_headers(&MsgQueryArgs {
+                sent_before_ns: opts.sent_before_ns,
+                sent_after_ns: opts.sent_after_ns,
+                limit: opts.limit,
+                kind,
+                delivery_status,
+                direction,
+                content_types: opts
+                    .content_types
+                    .map(|types| types.into_iter().map(Into::into).collect()),
+                cursor: None,
+                before_cursor: None,
+                after_cursor: None,
+            })?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+
+        Ok(headers)
+    }
+
     pub async fn find_messages_with_reactions(
         &self,
         opts: FfiListMessagesOptions,
@@ -1557,6 +2010,9 @@ impl FfiConversation {
                 content_types: opts
                     .content_types
                     .map(|types| types.into_iter().map(Into::into).collect()),
+                cursor: None,
+                before_cursor: None,
+                after_cursor: None,
             })?
             .into_iter()
             .map(|msg| msg.into())
@@ -1793,6 +2249,31 @@ impl FfiConversation {
         self.inner.is_active(&provider).map_err(Into::into)
     }
 
+    /// Save the unfinished message in the conversation, replacing the previous draft
+    pub fn save_draft(&self, content: Vec<u8>) -> Result<(), GenericError> {
+        self.inner.save_draft(&content).map_err(Into::into)
+    }
+
+    /// The unfinished message in the conversation, if there is one
+    pub fn load_draft(&self) -> Result<Option<Vec<u8>>, GenericError> {
+        self.inner.load_draft().map_err(Into::into)
+    }
+
+    /// Discard the draft, e.g. once the message was sent
+    pub fn clear_draft(&self) -> Result<(), GenericError> {
+        self.inner.clear_draft().map_err(Into::into)
+    }
+
+    /// Mark every message received so far as read on this installation
+    pub fn mark_as_read(&self) -> Result<(), GenericError> {
+        self.inner.mark_as_read().map_err(Into::into)
+    }
+
+    /// The number of messages from other members that haven't been read
+    pub fn unread_count(&self) -> Result<u64, GenericError> {
+        self.inner.unread_count().map_err(Into::into)
+    }
+
     pub fn consent_state(&self) -> Result<FfiConsentState, GenericError> {
         self.inner
             .consent_state()
@@ -1958,32 +2439,26 @@ impl From<ReactionV2> for FfiReaction {
 
 #[uniffi::export]
 pub fn encode_reaction(reaction: FfiReaction) -> Result<Vec<u8>, GenericError> {
-    // Convert FfiReaction to Reaction
     let reaction: ReactionV2 = reaction.into();
-
-    // Use ReactionCodec to encode the reaction
-    let encoded = ReactionCodec::encode(reaction)
-        .map_err(|e| GenericError::Generic { err: e.to_string() })?;
-
-    // Encode the EncodedContent to bytes
-    let mut buf = Vec::new();
-    encoded
-        .encode(&mut buf)
-        .map_err(|e| GenericError::Generic { err: e.to_string() })?;
-
-    Ok(buf)
+    let encoded = CodecRegistry::builtin()
+        .encode(reaction)
+        .map_err(GenericError::from_error)?;
+    Ok(encoded.encode_to_vec())
 }
 
 #[uniffi::export]
 pub fn decode_reaction(bytes: Vec<u8>) -> Result<FfiReaction, GenericError> {
-    // Decode bytes into EncodedContent
-    let encoded_content = EncodedContent::decode(bytes.as_slice())
-        .map_err(|e| GenericError::Generic { err: e.to_string() })?;
-
-    // Use ReactionCodec to decode into Reaction and convert to FfiReaction
-    ReactionCodec::decode(encoded_content)
-        .map(Into::into)
-        .map_err(|e| GenericError::Generic { err: e.to_string() })
+    let encoded_content =
+        EncodedContent::decode(bytes.as_slice()).map_err(GenericError::from_error)?;
+    match CodecRegistry::builtin()
+        .decode(encoded_content)
+        .map_err(GenericError::from_error)?
+    {
+        DecodedContent::Reaction(reaction) => Ok(reaction.into()),
+        _ => Err(GenericError::Generic {
+            err: "content is not a reaction".to_string(),
+        }),
+    }
 }
 
 #[derive(uniffi::Enum, Clone, Default, PartialEq, Debug)]
@@ -2035,6 +2510,53 @@ pub struct FfiMessage {
     pub delivery_status: FfiDeliveryStatus,
 }
 
+/// A page of messages from [`FfiConversation::find_messages_page`]
+#[derive(uniffi::Record, Clone)]
+pub struct FfiMessagePage {
+    pub items: Vec<FfiMessage>,
+    /// Pass to `find_messages_page` to load the following page. `None` once there are no more
+    /// messages.
+    pub next_cursor: Option<String>,
+    /// Pass to `find_messages_page` to load the page before this one. `None` on the first page.
+    pub prev_cursor: Option<String>,
+    /// How many messages match the query overall
+    pub total_estimate: u64,
+}
+
+/// A message without its content, for lists and badges that never display it
+#[derive(uniffi::Record, Clone)]
+pub struct FfiMessageHeader {
+    pub id: Vec<u8>,
+    pub sent_at_ns: i64,
+    pub convo_id: Vec<u8>,
+    pub sender_inbox_id: String,
+    pub kind: FfiConversationMessageKind,
+    pub delivery_status: FfiDeliveryStatus,
+    pub content_type: FfiContentType,
+}
+
+impl From<StoredGroupMessageHeader> for FfiMessageHeader {
+    fn from(header: StoredGroupMessageHeader) -> Self {
+        Self {
+            id: header.id,
+            sent_at_ns: header.sent_at_ns,
+            convo_id: header.group_id,
+            sender_inbox_id: header.sender_inbox_id,
+            kind: header.kind.into(),
+            delivery_status: header.delivery_status.into(),
+            content_type: header.content_type.into(),
+        }
+    }
+}
+
+/// A conversation and the header of its last message
+#[derive(uniffi::Record, Clone)]
+pub struct FfiConversationHeader {
+    pub id: Vec<u8>,
+    pub created_at_ns: i64,
+    pub last_message: Option<FfiMessageHeader>,
+}
+
 impl From<StoredGroupMessage> for FfiMessage {
     fn from(msg: StoredGroupMessage) -> Self {
         Self {
@@ -2049,6 +2571,53 @@ impl From<StoredGroupMessage> for FfiMessage {
     }
 }
 
+#[derive(uniffi::Record, Clone)]
+pub struct FfiContentTypeId {
+    pub authority_id: String,
+    pub type_id: String,
+    pub version_major: u32,
+    pub version_minor: u32,
+}
+
+impl From<FfiContentTypeId> for ContentTypeId {
+    fn from(content_type: FfiContentTypeId) -> Self {
+        Self {
+            authority_id: content_type.authority_id,
+            type_id: content_type.type_id,
+            version_major: content_type.version_major,
+            version_minor: content_type.version_minor,
+        }
+    }
+}
+
+/// A content type received that this installation can't decode, see
+/// [`FfiXmtpClient::content_compat_report`]
+#[derive(uniffi::Record, Clone)]
+pub struct FfiUnknownContentType {
+    pub authority_id: String,
+    pub type_id: String,
+    pub version_major: i32,
+    pub version_minor: i32,
+    pub first_seen_ns: i64,
+    pub last_seen_ns: i64,
+    /// How many messages of the content type were received
+    pub message_count: i64,
+}
+
+impl From<StoredUnknownContentType> for FfiUnknownContentType {
+    fn from(seen: StoredUnknownContentType) -> Self {
+        Self {
+            authority_id: seen.authority_id,
+            type_id: seen.type_id,
+            version_major: seen.version_major,
+            version_minor: seen.version_minor,
+            first_seen_ns: seen.first_seen_ns,
+            last_seen_ns: seen.last_seen_ns,
+            message_count: seen.message_count,
+        }
+    }
+}
+
 #[derive(uniffi::Record)]
 pub struct FfiConsent {
     pub entity_type: FfiConsentEntityType,
@@ -2058,11 +2627,11 @@ pub struct FfiConsent {
 
 impl From<FfiConsent> for StoredConsentRecord {
     fn from(consent: FfiConsent) -> Self {
-        Self {
-            entity_type: consent.entity_type.into(),
-            state: consent.state.into(),
-            entity: consent.entity,
-        }
+        Self::new(
+            consent.entity_type.into(),
+            consent.state.into(),
+            consent.entity,
+        )
     }
 }
 
@@ -2073,6 +2642,26 @@ pub struct FfiStreamCloser {
     stream_handle: Arc<Mutex<Option<FfiHandle>>>,
     // for convenience, does not require locking mutex.
     abort_handle: Arc<Box<dyn AbortHandle>>,
+    metrics: StreamMetrics,
+}
+
+#[derive(uniffi::Record, Clone, Debug)]
+pub struct FfiStreamMetrics {
+    pub items_delivered: u64,
+    pub items_dropped: u64,
+    pub cursor: Option<u64>,
+    pub last_item_ns: Option<i64>,
+}
+
+impl From<&StreamMetrics> for FfiStreamMetrics {
+    fn from(metrics: &StreamMetrics) -> Self {
+        Self {
+            items_delivered: metrics.items_delivered(),
+            items_dropped: metrics.items_dropped(),
+            cursor: metrics.cursor(),
+            last_item_ns: metrics.last_item_ns(),
+        }
+    }
 }
 
 impl FfiStreamCloser {
@@ -2081,6 +2670,7 @@ impl FfiStreamCloser {
     ) -> Self {
         Self {
             abort_handle: Arc::new(stream_handle.abort_handle()),
+            metrics: stream_handle.metrics(),
             stream_handle: Arc::new(Mutex::new(Some(Box::new(stream_handle)))),
         }
     }
@@ -2120,10 +2710,42 @@ impl FfiStreamCloser {
         }
     }
 
+    /// Stop receiving new items, let the callback finish the item it is handling,
+    /// then wait for the stream to shutdown
+    pub async fn close_gracefully(&self) -> Result<(), GenericError> {
+        use xmtp_mls::StreamHandleError::*;
+        use GenericError::Generic;
+
+        if self.abort_handle.is_finished() {
+            return Ok(());
+        }
+
+        let mut stream_handle = self.stream_handle.lock().await;
+        let stream_handle = stream_handle.take();
+        if let Some(mut h) = stream_handle {
+            match h.close_gracefully().await {
+                Err(Cancelled) => Ok(()),
+                Err(Panicked(msg)) => Err(Generic { err: msg }),
+                Err(e) => Err(Generic {
+                    err: format!("error joining task {}", e),
+                }),
+                Ok(t) => t.map_err(|e| Generic { err: e.to_string() }),
+            }
+        } else {
+            log::warn!("subscription already closed");
+            Ok(())
+        }
+    }
+
     pub fn is_closed(&self) -> bool {
         self.abort_handle.is_finished()
     }
 
+    /// Statistics about the items delivered by the stream so far
+    pub fn metrics(&self) -> FfiStreamMetrics {
+        (&self.metrics).into()
+    }
+
     pub async fn wait_for_ready(&self) {
         let mut stream_handle = self.stream_handle.lock().await;
         if let Some(ref mut h) = *stream_handle {
@@ -2144,6 +2766,11 @@ pub trait FfiConversationCallback: Send + Sync {
     fn on_error(&self, error: FfiSubscribeError);
 }
 
+#[uniffi::export(with_foreign)]
+pub trait FfiIdleCallback: Send + Sync {
+    fn on_idle(&self);
+}
+
 #[uniffi::export(with_foreign)]
 pub trait FfiConsentCallback: Send + Sync {
     fn on_consent_update(&self, consent: Vec<FfiConsent>);
@@ -2156,9 +2783,84 @@ pub trait FfiPreferenceCallback: Send + Sync {
     fn on_error(&self, error: FfiSubscribeError);
 }
 
+/// Fetches the metadata of linked pages for link previews. Called off the async runtime, so
+/// it may block, e.g. on a request through the app's proxy.
+#[uniffi::export(with_foreign)]
+pub trait FfiLinkPreviewFetcher: Send + Sync {
+    fn fetch(&self, url: String) -> Result<FfiLinkMetadata, FfiLinkPreviewError>;
+}
+
+#[derive(uniffi::Error, thiserror::Error, Debug)]
+pub enum FfiLinkPreviewError {
+    #[error("fetching the link preview failed: {message}")]
+    Fetch { message: String },
+}
+
+impl From<uniffi::UnexpectedUniFFICallbackError> for FfiLinkPreviewError {
+    fn from(err: uniffi::UnexpectedUniFFICallbackError) -> Self {
+        Self::Fetch {
+            message: err.reason,
+        }
+    }
+}
+
+/// Metadata of a linked page. Previews sent over FFI carry no image.
+#[derive(uniffi::Record, Clone, Default)]
+pub struct FfiLinkMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+}
+
+struct FfiLinkPreviewFetcherAdapter(Arc<dyn FfiLinkPreviewFetcher>);
+
+#[async_trait::async_trait]
+impl LinkPreviewFetcher for FfiLinkPreviewFetcherAdapter {
+    async fn fetch(&self, url: &str) -> Result<LinkMetadata, LinkPreviewError> {
+        let fetcher = self.0.clone();
+        let url = url.to_string();
+        let metadata = tokio::task::spawn_blocking(move || fetcher.fetch(url))
+            .await
+            .map_err(|e| LinkPreviewError::Fetch {
+                message: e.to_string(),
+                retryable: false,
+            })?
+            .map_err(|e| LinkPreviewError::Fetch {
+                message: e.to_string(),
+                retryable: false,
+            })?;
+        Ok(LinkMetadata {
+            title: metadata.title,
+            description: metadata.description,
+            site_name: metadata.site_name,
+            image: None,
+        })
+    }
+}
+
 #[derive(uniffi::Enum)]
 pub enum FfiPreferenceUpdate {
-    HMAC { key: Vec<u8> },
+    HMAC {
+        key: Vec<u8>,
+    },
+    NotificationSettings {
+        group_id: Vec<u8>,
+        mute_until_ns: Option<i64>,
+        mentions_only: bool,
+    },
+    Draft {
+        group_id: Vec<u8>,
+        content: Option<Vec<u8>>,
+    },
+    Custom {
+        namespace: String,
+        payload: Option<Vec<u8>>,
+    },
+    Block {
+        inbox_id: String,
+        blocked: bool,
+        hide_messages: bool,
+    },
 }
 
 #[derive(uniffi::Object)]
@@ -2974,7 +3676,7 @@ mod tests {
         let message_callbacks = Arc::new(RustStreamCallback::default());
         let stream_messages = bo
             .conversations()
-            .stream_all_messages(message_callbacks.clone())
+            .stream_all_messages(message_callbacks.clone(), None)
             .await;
         stream_messages.wait_for_ready().await;
 
@@ -3760,7 +4462,7 @@ mod tests {
         let message_callbacks = Arc::new(RustStreamCallback::from_client(&alix));
         let stream_messages = alix
             .conversations()
-            .stream_all_messages(message_callbacks.clone())
+            .stream_all_messages(message_callbacks.clone(), None)
             .await;
         stream_messages.wait_for_ready().await;
 
@@ -3807,7 +4509,7 @@ mod tests {
         let bo2_message_callbacks = Arc::new(RustStreamCallback::from_client(&bo2));
         let bo2_stream_messages = bo2
             .conversations()
-            .stream_all_messages(bo2_message_callbacks.clone())
+            .stream_all_messages(bo2_message_callbacks.clone(), None)
             .await;
         bo2_stream_messages.wait_for_ready().await;
 
@@ -4077,7 +4779,7 @@ mod tests {
         let message_callbacks = Arc::new(RustStreamCallback::default());
         let stream_messages = bo
             .conversations()
-            .stream_all_messages(message_callbacks.clone())
+            .stream_all_messages(message_callbacks.clone(), None)
             .await;
         stream_messages.wait_for_ready().await;
 
@@ -4211,7 +4913,7 @@ mod tests {
 
         let stream = caro
             .conversations()
-            .stream_all_messages(stream_callback.clone())
+            .stream_all_messages(stream_callback.clone(), None)
             .await;
         stream.wait_for_ready().await;
 
@@ -4306,7 +5008,7 @@ mod tests {
         let stream_callback = Arc::new(RustStreamCallback::default());
         let stream_closer = bola
             .conversations()
-            .stream_all_messages(stream_callback.clone())
+            .stream_all_messages(stream_callback.clone(), None)
             .await;
         stream_closer.wait_for_ready().await;
 
@@ -4401,7 +5103,7 @@ mod tests {
 
         let stream_messages = bo
             .conversations()
-            .stream_all_messages(message_callback.clone())
+            .stream_all_messages(message_callback.clone(), None)
             .await;
         stream_messages.wait_for_ready().await;
 
@@ -5153,7 +5855,7 @@ mod tests {
         let stream_callback = Arc::new(RustStreamCallback::default());
         let stream = bo
             .conversations()
-            .stream_all_messages(stream_callback.clone())
+            .stream_all_messages(stream_callback.clone(), None)
             .await;
         stream.wait_for_ready().await;
 
@@ -5172,7 +5874,7 @@ mod tests {
         let stream_callback = Arc::new(RustStreamCallback::default());
         let stream = bo
             .conversations()
-            .stream_all_group_messages(stream_callback.clone())
+            .stream_all_group_messages(stream_callback.clone(), None)
             .await;
         stream.wait_for_ready().await;
 
@@ -5192,7 +5894,7 @@ mod tests {
         let stream_callback = Arc::new(RustStreamCallback::default());
         let stream = bo
             .conversations()
-            .stream_all_dm_messages(stream_callback.clone())
+            .stream_all_dm_messages(stream_callback.clone(), None)
             .await;
         stream.wait_for_ready().await;
 
@@ -1,5 +1,7 @@
 use crate::conversations::Conversations;
+use crate::encoded_content::ContentTypeId;
 use crate::inbox_state::InboxState;
+use crate::message::UnknownContentType;
 use crate::signatures::SignatureRequestType;
 use crate::ErrorWrapper;
 use napi::bindgen_prelude::{Error, Result, Uint8Array};
@@ -13,6 +15,7 @@ use tracing_subscriber::{fmt, prelude::*};
 pub use xmtp_api_grpc::grpc_api_helper::Client as TonicApiClient;
 use xmtp_id::associations::builder::SignatureRequest;
 use xmtp_mls::builder::ClientBuilder;
+use xmtp_mls::codec_registry::JsonCodec;
 use xmtp_mls::groups::scoped_client::LocalScopedGroupClient;
 use xmtp_mls::identity::IdentityStrategy;
 use xmtp_mls::storage::{EncryptedMessageStore, EncryptionKey, StorageOption};
@@ -304,4 +307,88 @@ impl Client {
       .map_err(ErrorWrapper::from)?;
     Ok(state.into_iter().map(Into::into).collect())
   }
+
+  #[napi]
+  pub fn register_preference_namespace(&self, namespace: String) -> Result<()> {
+    self
+      .inner_client
+      .register_preference_namespace(&namespace)
+      .map_err(ErrorWrapper::from)?;
+    Ok(())
+  }
+
+  #[napi]
+  pub fn set_custom_preference(&self, namespace: String, payload: Uint8Array) -> Result<()> {
+    self
+      .inner_client
+      .set_custom_preference(&namespace, &payload)
+      .map_err(ErrorWrapper::from)?;
+    Ok(())
+  }
+
+  #[napi]
+  pub fn custom_preference(&self, namespace: String) -> Result<Option<Uint8Array>> {
+    let payload = self
+      .inner_client
+      .custom_preference(&namespace)
+      .map_err(ErrorWrapper::from)?;
+    Ok(payload.map(Into::into))
+  }
+
+  #[napi]
+  pub async fn block_inbox(&self, inbox_id: String, hide_messages: bool) -> Result<()> {
+    self
+      .inner_client
+      .block_inbox(&inbox_id, hide_messages)
+      .await
+      .map_err(ErrorWrapper::from)?;
+    Ok(())
+  }
+
+  #[napi]
+  pub fn unblock_inbox(&self, inbox_id: String) -> Result<()> {
+    self
+      .inner_client
+      .unblock_inbox(&inbox_id)
+      .map_err(ErrorWrapper::from)?;
+    Ok(())
+  }
+
+  #[napi]
+  pub fn is_inbox_blocked(&self, inbox_id: String) -> Result<bool> {
+    Ok(
+      self
+        .inner_client
+        .is_inbox_blocked(&inbox_id)
+        .map_err(ErrorWrapper::from)?,
+    )
+  }
+
+  #[napi]
+  pub fn blocked_inboxes(&self) -> Result<Vec<String>> {
+    let blocked = self
+      .inner_client
+      .blocked_inboxes()
+      .map_err(ErrorWrapper::from)?;
+    Ok(blocked.into_iter().map(|entry| entry.inbox_id).collect())
+  }
+
+  /// Register a content type of the app, whose content is JSON, so its messages can be sent with
+  /// `sendContent` and aren't reported as unknown
+  #[napi]
+  pub fn register_codec(&self, content_type: ContentTypeId) {
+    self
+      .inner_client
+      .register_codec(JsonCodec::new(content_type.into()));
+  }
+
+  /// The content types received that this installation can't decode, most recently seen first
+  #[napi]
+  pub fn content_compat_report(&self) -> Result<Vec<UnknownContentType>> {
+    let report = self
+      .inner_client
+      .content_compat_report()
+      .map_err(ErrorWrapper::from)?;
+    Ok(report.into_iter().map(Into::into).collect())
+  }
 }
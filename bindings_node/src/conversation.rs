@@ -119,6 +119,23 @@ impl Conversation {
     Ok(hex::encode(message_id.clone()))
   }
 
+  /// Send encoded content, checking that the client has a codec for its content type so other
+  /// installations can decode it
+  #[napi]
+  pub async fn send_content(&self, encoded_content: EncodedContent) -> Result<String> {
+    let group = MlsGroup::new(
+      self.inner_client.clone(),
+      self.group_id.clone(),
+      self.created_at_ns,
+    );
+
+    let message_id = group
+      .send_content(XmtpEncodedContent::from(encoded_content))
+      .await
+      .map_err(ErrorWrapper::from)?;
+    Ok(hex::encode(message_id))
+  }
+
   #[napi]
   pub fn send_optimistic(&self, encoded_content: EncodedContent) -> Result<String> {
     let encoded_content: XmtpEncodedContent = encoded_content.into();
@@ -595,6 +612,74 @@ impl Conversation {
     )
   }
 
+  #[napi]
+  pub fn save_draft(&self, content: Uint8Array) -> Result<()> {
+    let group = MlsGroup::new(
+      self.inner_client.clone(),
+      self.group_id.clone(),
+      self.created_at_ns,
+    );
+
+    group
+      .save_draft(content.deref())
+      .map_err(ErrorWrapper::from)?;
+
+    Ok(())
+  }
+
+  #[napi]
+  pub fn load_draft(&self) -> Result<Option<Uint8Array>> {
+    let group = MlsGroup::new(
+      self.inner_client.clone(),
+      self.group_id.clone(),
+      self.created_at_ns,
+    );
+
+    Ok(
+      group
+        .load_draft()
+        .map_err(ErrorWrapper::from)?
+        .map(Uint8Array::from),
+    )
+  }
+
+  #[napi]
+  pub fn clear_draft(&self) -> Result<()> {
+    let group = MlsGroup::new(
+      self.inner_client.clone(),
+      self.group_id.clone(),
+      self.created_at_ns,
+    );
+
+    group.clear_draft().map_err(ErrorWrapper::from)?;
+
+    Ok(())
+  }
+
+  #[napi]
+  pub fn mark_as_read(&self) -> Result<()> {
+    let group = MlsGroup::new(
+      self.inner_client.clone(),
+      self.group_id.clone(),
+      self.created_at_ns,
+    );
+
+    group.mark_as_read().map_err(ErrorWrapper::from)?;
+
+    Ok(())
+  }
+
+  #[napi]
+  pub fn unread_count(&self) -> Result<i64> {
+    let group = MlsGroup::new(
+      self.inner_client.clone(),
+      self.group_id.clone(),
+      self.created_at_ns,
+    );
+
+    Ok(group.unread_count().map_err(ErrorWrapper::from)? as i64)
+  }
+
   #[napi]
   pub fn added_by_inbox_id(&self) -> Result<String> {
     let group = MlsGroup::new(
@@ -7,11 +7,16 @@ use napi::bindgen_prelude::{BigInt, Error, Result, Uint8Array};
 use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
 use napi::JsFunction;
 use napi_derive::napi;
-use xmtp_mls::groups::{GroupMetadataOptions, HmacKey as XmtpHmacKey, PreconfiguredPolicies};
+use xmtp_mls::groups::{
+  GroupMetadataOptions, HmacKey as XmtpHmacKey, MlsGroup, PreconfiguredPolicies,
+};
 use xmtp_mls::storage::group::ConversationType as XmtpConversationType;
 use xmtp_mls::storage::group::GroupMembershipState as XmtpGroupMembershipState;
 use xmtp_mls::storage::group::GroupQueryArgs;
+use xmtp_mls::storage::group_message::StoredGroupMessage;
+use xmtp_mls::subscriptions::StreamAllMessagesOptions;
 
+use crate::consent_state::ConsentState;
 use crate::message::Message;
 use crate::permissions::{GroupPermissionsOptions, PermissionPolicySet};
 use crate::ErrorWrapper;
@@ -51,6 +56,7 @@ pub enum GroupMembershipState {
   Allowed = 0,
   Rejected = 1,
   Pending = 2,
+  Restored = 3,
 }
 
 impl From<XmtpGroupMembershipState> for GroupMembershipState {
@@ -59,6 +65,7 @@ impl From<XmtpGroupMembershipState> for GroupMembershipState {
       XmtpGroupMembershipState::Allowed => GroupMembershipState::Allowed,
       XmtpGroupMembershipState::Rejected => GroupMembershipState::Rejected,
       XmtpGroupMembershipState::Pending => GroupMembershipState::Pending,
+      XmtpGroupMembershipState::Restored => GroupMembershipState::Restored,
     }
   }
 }
@@ -69,6 +76,7 @@ impl From<GroupMembershipState> for XmtpGroupMembershipState {
       GroupMembershipState::Allowed => XmtpGroupMembershipState::Allowed,
       GroupMembershipState::Rejected => XmtpGroupMembershipState::Rejected,
       GroupMembershipState::Pending => XmtpGroupMembershipState::Pending,
+      GroupMembershipState::Restored => XmtpGroupMembershipState::Restored,
     }
   }
 }
@@ -98,6 +106,13 @@ impl From<ListConversationsOptions> for GroupQueryArgs {
   }
 }
 
+#[napi(object)]
+#[derive(Default)]
+pub struct StreamMessagesOptions {
+  /// Only stream messages from conversations in one of these consent states
+  pub consent_states: Option<Vec<ConsentState>>,
+}
+
 #[napi(object)]
 pub struct HmacKey {
   pub key: Vec<u8>,
@@ -139,6 +154,29 @@ impl CreateGroupOptions {
   }
 }
 
+/// What an inbox screen shows about a conversation
+#[napi]
+pub struct ConversationOverview {
+  group: MlsGroup<RustXmtpClient>,
+  last_message: Option<StoredGroupMessage>,
+  /// Messages from other members that this inbox hasn't read
+  pub unread_count: i64,
+  pub consent_state: ConsentState,
+}
+
+#[napi]
+impl ConversationOverview {
+  #[napi]
+  pub fn conversation(&self) -> Conversation {
+    self.group.clone().into()
+  }
+
+  #[napi]
+  pub fn last_message(&self) -> Option<Message> {
+    self.last_message.clone().map(Into::into)
+  }
+}
+
 #[napi]
 pub struct Conversations {
   inner_client: Arc<RustXmtpClient>,
@@ -266,6 +304,18 @@ impl Conversations {
     Ok(Message::from(message))
   }
 
+  /// The text to show in place of the message if it can't be decoded: the fallback its sender
+  /// declared. `None` if the content can be decoded, or has no fallback.
+  #[napi]
+  pub fn message_fallback(&self, message_id: String) -> Result<Option<String>> {
+    let message_id = hex::decode(message_id).map_err(ErrorWrapper::from)?;
+    let message = self
+      .inner_client
+      .message(message_id)
+      .map_err(ErrorWrapper::from)?;
+    Ok(self.inner_client.message_fallback(message))
+  }
+
   #[napi]
   pub async fn process_streamed_welcome_message(
     &self,
@@ -323,6 +373,29 @@ impl Conversations {
     Ok(convo_list)
   }
 
+  /// Like `list`, with each conversation's last message, unread count and consent state, for
+  /// showing an inbox screen in one query
+  #[napi]
+  pub fn list_overview(
+    &self,
+    opts: Option<ListConversationsOptions>,
+  ) -> Result<Vec<ConversationOverview>> {
+    let overview = self
+      .inner_client
+      .list_conversations_overview(opts.unwrap_or_default().into())
+      .map_err(ErrorWrapper::from)?
+      .into_iter()
+      .map(|overview| ConversationOverview {
+        group: overview.group,
+        last_message: overview.last_message,
+        unread_count: overview.unread_count as i64,
+        consent_state: overview.consent_state.into(),
+      })
+      .collect();
+
+    Ok(overview)
+  }
+
   #[napi]
   pub fn list_groups(&self, opts: Option<ListConversationsOptions>) -> Result<Vec<Conversation>> {
     self.list(Some(ListConversationsOptions {
@@ -399,11 +472,14 @@ impl Conversations {
     self.stream(callback, Some(ConversationType::Dm))
   }
 
-  #[napi(ts_args_type = "callback: (err: null | Error, result: Message | undefined) => void")]
+  #[napi(
+    ts_args_type = "callback: (err: null | Error, result: Message | undefined) => void, conversationType?: ConversationType, options?: StreamMessagesOptions"
+  )]
   pub fn stream_all_messages(
     &self,
     callback: JsFunction,
     conversation_type: Option<ConversationType>,
+    options: Option<StreamMessagesOptions>,
   ) -> Result<StreamCloser> {
     tracing::trace!(
       inbox_id = self.inner_client.inbox_id(),
@@ -414,7 +490,13 @@ impl Conversations {
     let inbox_id = self.inner_client.inbox_id().to_string();
     let stream_closer = RustXmtpClient::stream_all_messages_with_callback(
       self.inner_client.clone(),
-      conversation_type.map(Into::into),
+      StreamAllMessagesOptions {
+        conversation_type: conversation_type.map(Into::into),
+        consent_states: options
+          .and_then(|options| options.consent_states)
+          .map(|states| states.into_iter().map(Into::into).collect()),
+        ..Default::default()
+      },
       move |message| {
         tracing::trace!(
             inbox_id,
@@ -434,13 +516,25 @@ impl Conversations {
     Ok(StreamCloser::new(stream_closer))
   }
 
-  #[napi(ts_args_type = "callback: (err: null | Error, result: Message | undefined) => void")]
-  pub fn stream_all_group_messages(&self, callback: JsFunction) -> Result<StreamCloser> {
-    self.stream_all_messages(callback, Some(ConversationType::Group))
+  #[napi(
+    ts_args_type = "callback: (err: null | Error, result: Message | undefined) => void, options?: StreamMessagesOptions"
+  )]
+  pub fn stream_all_group_messages(
+    &self,
+    callback: JsFunction,
+    options: Option<StreamMessagesOptions>,
+  ) -> Result<StreamCloser> {
+    self.stream_all_messages(callback, Some(ConversationType::Group), options)
   }
 
-  #[napi(ts_args_type = "callback: (err: null | Error, result: Message | undefined) => void")]
-  pub fn stream_all_dm_messages(&self, callback: JsFunction) -> Result<StreamCloser> {
-    self.stream_all_messages(callback, Some(ConversationType::Dm))
+  #[napi(
+    ts_args_type = "callback: (err: null | Error, result: Message | undefined) => void, options?: StreamMessagesOptions"
+  )]
+  pub fn stream_all_dm_messages(
+    &self,
+    callback: JsFunction,
+    options: Option<StreamMessagesOptions>,
+  ) -> Result<StreamCloser> {
+    self.stream_all_messages(callback, Some(ConversationType::Dm), options)
   }
 }
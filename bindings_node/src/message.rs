@@ -1,8 +1,11 @@
 use napi::bindgen_prelude::Uint8Array;
 use prost::Message as ProstMessage;
-use xmtp_mls::storage::group_message::{
-  DeliveryStatus as XmtpDeliveryStatus, GroupMessageKind as XmtpGroupMessageKind, MsgQueryArgs,
-  SortDirection as XmtpSortDirection, StoredGroupMessage,
+use xmtp_mls::storage::{
+  group_message::{
+    DeliveryStatus as XmtpDeliveryStatus, GroupMessageKind as XmtpGroupMessageKind, MsgQueryArgs,
+    SortDirection as XmtpSortDirection, StoredGroupMessage,
+  },
+  unknown_content_type::StoredUnknownContentType,
 };
 
 use napi_derive::napi;
@@ -134,3 +137,29 @@ impl From<StoredGroupMessage> for Message {
     }
   }
 }
+
+/// A content type received that this installation can't decode
+#[napi(object)]
+pub struct UnknownContentType {
+  pub authority_id: String,
+  pub type_id: String,
+  pub version_major: i32,
+  pub version_minor: i32,
+  pub first_seen_ns: i64,
+  pub last_seen_ns: i64,
+  pub message_count: i64,
+}
+
+impl From<StoredUnknownContentType> for UnknownContentType {
+  fn from(seen: StoredUnknownContentType) -> Self {
+    Self {
+      authority_id: seen.authority_id,
+      type_id: seen.type_id,
+      version_major: seen.version_major,
+      version_minor: seen.version_minor,
+      first_seen_ns: seen.first_seen_ns,
+      last_seen_ns: seen.last_seen_ns,
+      message_count: seen.message_count,
+    }
+  }
+}
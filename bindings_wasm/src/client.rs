@@ -11,12 +11,15 @@ use wasm_bindgen::JsValue;
 use xmtp_api_http::XmtpHttpApiClient;
 use xmtp_id::associations::builder::SignatureRequest;
 use xmtp_mls::builder::ClientBuilder;
+use xmtp_mls::codec_registry::JsonCodec;
 use xmtp_mls::identity::IdentityStrategy;
 use xmtp_mls::storage::{EncryptedMessageStore, EncryptionKey, StorageOption};
 use xmtp_mls::Client as MlsClient;
 use xmtp_proto::xmtp::mls::message_contents::DeviceSyncKind;
 
 use crate::conversations::Conversations;
+use crate::encoded_content::ContentTypeId;
+use crate::messages::UnknownContentType;
 use crate::signatures::SignatureRequestType;
 
 pub type RustXmtpClient = MlsClient<XmtpHttpApiClient>;
@@ -289,4 +292,86 @@ impl Client {
   pub fn conversations(&self) -> Conversations {
     Conversations::new(self.inner_client.clone())
   }
+
+  #[wasm_bindgen(js_name = registerPreferenceNamespace)]
+  pub fn register_preference_namespace(&self, namespace: String) -> Result<(), JsError> {
+    self
+      .inner_client
+      .register_preference_namespace(&namespace)
+      .map_err(|e| JsError::new(format!("{}", e).as_str()))
+  }
+
+  #[wasm_bindgen(js_name = setCustomPreference)]
+  pub fn set_custom_preference(
+    &self,
+    namespace: String,
+    payload: Uint8Array,
+  ) -> Result<(), JsError> {
+    self
+      .inner_client
+      .set_custom_preference(&namespace, &payload.to_vec())
+      .map_err(|e| JsError::new(format!("{}", e).as_str()))
+  }
+
+  #[wasm_bindgen(js_name = customPreference)]
+  pub fn custom_preference(&self, namespace: String) -> Result<Option<Uint8Array>, JsError> {
+    let payload = self
+      .inner_client
+      .custom_preference(&namespace)
+      .map_err(|e| JsError::new(format!("{}", e).as_str()))?;
+    Ok(payload.map(|payload| Uint8Array::from(payload.as_slice())))
+  }
+
+  #[wasm_bindgen(js_name = blockInbox)]
+  pub async fn block_inbox(&self, inbox_id: String, hide_messages: bool) -> Result<(), JsError> {
+    self
+      .inner_client
+      .block_inbox(&inbox_id, hide_messages)
+      .await
+      .map_err(|e| JsError::new(format!("{}", e).as_str()))
+  }
+
+  #[wasm_bindgen(js_name = unblockInbox)]
+  pub fn unblock_inbox(&self, inbox_id: String) -> Result<(), JsError> {
+    self
+      .inner_client
+      .unblock_inbox(&inbox_id)
+      .map_err(|e| JsError::new(format!("{}", e).as_str()))
+  }
+
+  #[wasm_bindgen(js_name = isInboxBlocked)]
+  pub fn is_inbox_blocked(&self, inbox_id: String) -> Result<bool, JsError> {
+    self
+      .inner_client
+      .is_inbox_blocked(&inbox_id)
+      .map_err(|e| JsError::new(format!("{}", e).as_str()))
+  }
+
+  #[wasm_bindgen(js_name = blockedInboxes)]
+  pub fn blocked_inboxes(&self) -> Result<Vec<String>, JsError> {
+    let blocked = self
+      .inner_client
+      .blocked_inboxes()
+      .map_err(|e| JsError::new(format!("{}", e).as_str()))?;
+    Ok(blocked.into_iter().map(|entry| entry.inbox_id).collect())
+  }
+
+  /// Register a content type of the app, whose content is JSON, so its messages can be sent with
+  /// `sendContent` and aren't reported as unknown
+  #[wasm_bindgen(js_name = registerCodec)]
+  pub fn register_codec(&self, content_type: ContentTypeId) {
+    self
+      .inner_client
+      .register_codec(JsonCodec::new(content_type.into()));
+  }
+
+  /// The content types received that this installation can't decode, most recently seen first
+  #[wasm_bindgen(js_name = contentCompatReport)]
+  pub fn content_compat_report(&self) -> Result<Vec<UnknownContentType>, JsError> {
+    let report = self
+      .inner_client
+      .content_compat_report()
+      .map_err(|e| JsError::new(format!("{}", e).as_str()))?;
+    Ok(report.into_iter().map(Into::into).collect())
+  }
 }
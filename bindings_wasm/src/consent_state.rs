@@ -73,11 +73,11 @@ impl Consent {
 
 impl From<Consent> for StoredConsentRecord {
   fn from(consent: Consent) -> Self {
-    Self {
-      entity_type: consent.entity_type.into(),
-      state: consent.state.into(),
-      entity: consent.entity,
-    }
+    Self::new(
+      consent.entity_type.into(),
+      consent.state.into(),
+      consent.entity,
+    )
   }
 }
 
@@ -1,3 +1,4 @@
+use js_sys::Uint8Array;
 use std::sync::Arc;
 use wasm_bindgen::JsValue;
 use wasm_bindgen::{prelude::wasm_bindgen, JsError};
@@ -5,7 +6,7 @@ use xmtp_mls::storage::group::ConversationType;
 
 use crate::client::RustXmtpClient;
 use crate::encoded_content::EncodedContent;
-use crate::messages::{ListMessagesOptions, Message};
+use crate::messages::{ListMessagesOptions, Message, MessagePage};
 use crate::permissions::{MetadataField, PermissionPolicy, PermissionUpdateType};
 use crate::{consent_state::ConsentState, permissions::GroupPermissions};
 use xmtp_mls::groups::{
@@ -144,6 +145,20 @@ impl Conversation {
     Ok(hex::encode(message_id.clone()))
   }
 
+  /// Send encoded content, checking that the client has a codec for its content type so other
+  /// installations can decode it
+  #[wasm_bindgen(js_name = sendContent)]
+  pub async fn send_content(&self, encoded_content: EncodedContent) -> Result<String, JsError> {
+    let group = self.to_mls_group();
+
+    let message_id = group
+      .send_content(XmtpEncodedContent::from(encoded_content))
+      .await
+      .map_err(|e| JsError::new(&format!("{e}")))?;
+
+    Ok(hex::encode(message_id))
+  }
+
   /// send a message without immediately publishing to the delivery service.
   #[wasm_bindgen(js_name = sendOptimistic)]
   pub fn send_optimistic(&self, encoded_content: EncodedContent) -> Result<String, JsError> {
@@ -215,6 +230,45 @@ impl Conversation {
     Ok(messages)
   }
 
+  /// Load a page of messages, starting at `cursor` from a previous page or at the start of the
+  /// query without one. `opts.limit` sets the page size.
+  #[wasm_bindgen(js_name = findMessagesPage)]
+  pub async fn find_messages_page(
+    &self,
+    opts: Option<ListMessagesOptions>,
+    cursor: Option<String>,
+  ) -> Result<MessagePage, JsError> {
+    let opts = opts.unwrap_or_default();
+    let group = self.to_mls_group();
+    let provider = group
+      .mls_provider()
+      .map_err(|e| JsError::new(&format!("{e}")))?;
+    let conversation_type = group
+      .conversation_type(&provider)
+      .await
+      .map_err(|e| JsError::new(&format!("{e}")))?;
+    let kind = match conversation_type {
+      ConversationType::Group => None,
+      ConversationType::Dm => Some(XmtpGroupMessageKind::Application),
+      ConversationType::Sync => None,
+    };
+
+    let opts = MsgQueryArgs {
+      kind,
+      ..opts.into()
+    };
+    let page = group
+      .find_messages_page(&opts, cursor.as_deref())
+      .map_err(|e| JsError::new(&format!("{e}")))?;
+
+    Ok(MessagePage {
+      items: page.items.into_iter().map(Into::into).collect(),
+      next_cursor: page.next_cursor,
+      prev_cursor: page.prev_cursor,
+      total_estimate: page.total_estimate,
+    })
+  }
+
   #[wasm_bindgen(js_name = listMembers)]
   pub async fn list_members(&self) -> Result<JsValue, JsError> {
     let group = self.to_mls_group();
@@ -522,6 +576,47 @@ impl Conversation {
       .map_err(|e| JsError::new(&format!("{e}")))
   }
 
+  #[wasm_bindgen(js_name = saveDraft)]
+  pub fn save_draft(&self, content: Uint8Array) -> Result<(), JsError> {
+    self
+      .to_mls_group()
+      .save_draft(&content.to_vec())
+      .map_err(|e| JsError::new(&format!("{e}")))
+  }
+
+  #[wasm_bindgen(js_name = loadDraft)]
+  pub fn load_draft(&self) -> Result<Option<Uint8Array>, JsError> {
+    self
+      .to_mls_group()
+      .load_draft()
+      .map(|draft| draft.map(|content| Uint8Array::from(content.as_slice())))
+      .map_err(|e| JsError::new(&format!("{e}")))
+  }
+
+  #[wasm_bindgen(js_name = clearDraft)]
+  pub fn clear_draft(&self) -> Result<(), JsError> {
+    self
+      .to_mls_group()
+      .clear_draft()
+      .map_err(|e| JsError::new(&format!("{e}")))
+  }
+
+  #[wasm_bindgen(js_name = markAsRead)]
+  pub fn mark_as_read(&self) -> Result<(), JsError> {
+    self
+      .to_mls_group()
+      .mark_as_read()
+      .map_err(|e| JsError::new(&format!("{e}")))
+  }
+
+  #[wasm_bindgen(js_name = unreadCount)]
+  pub fn unread_count(&self) -> Result<u64, JsError> {
+    self
+      .to_mls_group()
+      .unread_count()
+      .map_err(|e| JsError::new(&format!("{e}")))
+  }
+
   #[wasm_bindgen(js_name = addedByInboxId)]
   pub fn added_by_inbox_id(&self) -> Result<String, JsError> {
     let group = self.to_mls_group();
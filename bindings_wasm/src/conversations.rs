@@ -2,11 +2,15 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use wasm_bindgen::prelude::wasm_bindgen;
 use wasm_bindgen::{JsError, JsValue};
-use xmtp_mls::groups::{GroupMetadataOptions, HmacKey as XmtpHmacKey, PreconfiguredPolicies};
+use xmtp_mls::groups::{
+  GroupMetadataOptions, HmacKey as XmtpHmacKey, MlsGroup, PreconfiguredPolicies,
+};
 use xmtp_mls::storage::group::ConversationType as XmtpConversationType;
 use xmtp_mls::storage::group::GroupMembershipState as XmtpGroupMembershipState;
 use xmtp_mls::storage::group::GroupQueryArgs;
+use xmtp_mls::storage::group_message::StoredGroupMessage;
 
+use crate::consent_state::ConsentState;
 use crate::messages::Message;
 use crate::permissions::{GroupPermissionsOptions, PermissionPolicySet};
 use crate::{client::RustXmtpClient, conversation::Conversation};
@@ -45,6 +49,7 @@ pub enum GroupMembershipState {
   Allowed = 0,
   Rejected = 1,
   Pending = 2,
+  Restored = 3,
 }
 
 impl From<XmtpGroupMembershipState> for GroupMembershipState {
@@ -53,6 +58,7 @@ impl From<XmtpGroupMembershipState> for GroupMembershipState {
       XmtpGroupMembershipState::Allowed => GroupMembershipState::Allowed,
       XmtpGroupMembershipState::Rejected => GroupMembershipState::Rejected,
       XmtpGroupMembershipState::Pending => GroupMembershipState::Pending,
+      XmtpGroupMembershipState::Restored => GroupMembershipState::Restored,
     }
   }
 }
@@ -63,6 +69,7 @@ impl From<GroupMembershipState> for XmtpGroupMembershipState {
       GroupMembershipState::Allowed => XmtpGroupMembershipState::Allowed,
       GroupMembershipState::Rejected => XmtpGroupMembershipState::Rejected,
       GroupMembershipState::Pending => XmtpGroupMembershipState::Pending,
+      GroupMembershipState::Restored => XmtpGroupMembershipState::Restored,
     }
   }
 }
@@ -192,6 +199,43 @@ impl From<XmtpHmacKey> for HmacKey {
   }
 }
 
+/// What an inbox screen shows about a conversation
+#[wasm_bindgen]
+pub struct ConversationOverview {
+  group: MlsGroup<RustXmtpClient>,
+  last_message: Option<StoredGroupMessage>,
+  unread_count: u64,
+  consent_state: ConsentState,
+}
+
+#[wasm_bindgen]
+impl ConversationOverview {
+  #[wasm_bindgen]
+  pub fn conversation(&self) -> Conversation {
+    Conversation::new(
+      self.group.client.clone(),
+      self.group.group_id.clone(),
+      self.group.created_at_ns,
+    )
+  }
+
+  #[wasm_bindgen(js_name = lastMessage)]
+  pub fn last_message(&self) -> Option<Message> {
+    self.last_message.clone().map(Into::into)
+  }
+
+  /// Messages from other members that this inbox hasn't read
+  #[wasm_bindgen(js_name = unreadCount)]
+  pub fn unread_count(&self) -> u64 {
+    self.unread_count
+  }
+
+  #[wasm_bindgen(js_name = consentState)]
+  pub fn consent_state(&self) -> ConsentState {
+    self.consent_state.clone()
+  }
+}
+
 #[wasm_bindgen]
 pub struct Conversations {
   inner_client: Arc<RustXmtpClient>,
@@ -323,6 +367,19 @@ impl Conversations {
     Ok(message.into())
   }
 
+  /// The text to show in place of the message if it can't be decoded: the fallback its sender
+  /// declared. `None` if the content can be decoded, or has no fallback.
+  #[wasm_bindgen(js_name = messageFallback)]
+  pub fn message_fallback(&self, message_id: String) -> Result<Option<String>, JsError> {
+    let message_id =
+      hex::decode(message_id).map_err(|e| JsError::new(format!("{}", e).as_str()))?;
+    let message = self
+      .inner_client
+      .message(message_id)
+      .map_err(|e| JsError::new(format!("{}", e).as_str()))?;
+    Ok(self.inner_client.message_fallback(message))
+  }
+
   #[wasm_bindgen]
   pub async fn sync(&self) -> Result<(), JsError> {
     let provider = self
@@ -373,6 +430,31 @@ impl Conversations {
     Ok(convo_list)
   }
 
+  /// Like `list`, with each conversation's last message, unread count and consent state, for
+  /// showing an inbox screen in one query
+  #[wasm_bindgen(js_name = listOverview)]
+  pub fn list_overview(
+    &self,
+    opts: Option<ListConversationsOptions>,
+  ) -> Result<js_sys::Array, JsError> {
+    let overview: js_sys::Array = self
+      .inner_client
+      .list_conversations_overview(opts.unwrap_or_default().into())
+      .map_err(|e| JsError::new(format!("{}", e).as_str()))?
+      .into_iter()
+      .map(|overview| {
+        JsValue::from(ConversationOverview {
+          group: overview.group,
+          last_message: overview.last_message,
+          unread_count: overview.unread_count,
+          consent_state: overview.consent_state.into(),
+        })
+      })
+      .collect();
+
+    Ok(overview)
+  }
+
   #[wasm_bindgen(js_name = listGroups)]
   pub fn list_groups(
     &self,
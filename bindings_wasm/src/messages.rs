@@ -1,9 +1,12 @@
 use js_sys::Uint8Array;
 use prost::Message as ProstMessage;
 use wasm_bindgen::prelude::wasm_bindgen;
-use xmtp_mls::storage::group_message::{
-  DeliveryStatus as XmtpDeliveryStatus, GroupMessageKind as XmtpGroupMessageKind, MsgQueryArgs,
-  SortDirection as XmtpSortDirection, StoredGroupMessage,
+use xmtp_mls::storage::{
+  group_message::{
+    DeliveryStatus as XmtpDeliveryStatus, GroupMessageKind as XmtpGroupMessageKind, MsgQueryArgs,
+    SortDirection as XmtpSortDirection, StoredGroupMessage,
+  },
+  unknown_content_type::StoredUnknownContentType,
 };
 use xmtp_proto::xmtp::mls::message_contents::EncodedContent as XmtpEncodedContent;
 
@@ -158,6 +161,23 @@ impl Message {
   }
 }
 
+/// A page of messages from `Conversation.findMessagesPage`
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone)]
+pub struct MessagePage {
+  pub items: Vec<Message>,
+  /// Pass to `findMessagesPage` to load the following page. Unset once there are no more
+  /// messages.
+  #[wasm_bindgen(js_name = nextCursor)]
+  pub next_cursor: Option<String>,
+  /// Pass to `findMessagesPage` to load the page before this one. Unset on the first page.
+  #[wasm_bindgen(js_name = prevCursor)]
+  pub prev_cursor: Option<String>,
+  /// How many messages match the query overall
+  #[wasm_bindgen(js_name = totalEstimate)]
+  pub total_estimate: u64,
+}
+
 impl From<StoredGroupMessage> for Message {
   fn from(msg: StoredGroupMessage) -> Self {
     let id = hex::encode(msg.id.clone());
@@ -188,3 +208,37 @@ impl From<StoredGroupMessage> for Message {
     }
   }
 }
+
+/// A content type received that this installation can't decode
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Clone)]
+pub struct UnknownContentType {
+  #[wasm_bindgen(js_name = authorityId)]
+  pub authority_id: String,
+  #[wasm_bindgen(js_name = typeId)]
+  pub type_id: String,
+  #[wasm_bindgen(js_name = versionMajor)]
+  pub version_major: i32,
+  #[wasm_bindgen(js_name = versionMinor)]
+  pub version_minor: i32,
+  #[wasm_bindgen(js_name = firstSeenNs)]
+  pub first_seen_ns: i64,
+  #[wasm_bindgen(js_name = lastSeenNs)]
+  pub last_seen_ns: i64,
+  #[wasm_bindgen(js_name = messageCount)]
+  pub message_count: i64,
+}
+
+impl From<StoredUnknownContentType> for UnknownContentType {
+  fn from(seen: StoredUnknownContentType) -> Self {
+    Self {
+      authority_id: seen.authority_id,
+      type_id: seen.type_id,
+      version_major: seen.version_major,
+      version_minor: seen.version_minor,
+      first_seen_ns: seen.first_seen_ns,
+      last_seen_ns: seen.last_seen_ns,
+      message_count: seen.message_count,
+    }
+  }
+}
@@ -475,6 +475,12 @@ async fn main() -> color_eyre::eyre::Result<()> {
                     Ok(DeviceSyncContent::Reply(ref reply)) => {
                         info!("Reply: {:?}", reply);
                     }
+                    Ok(DeviceSyncContent::Scope {
+                        ref request_id,
+                        ref scope,
+                    }) => {
+                        info!("Scope of {}: {:?}", request_id, scope);
+                    }
                     _ => {
                         info!("Unknown message type: {:?}", message);
                     }
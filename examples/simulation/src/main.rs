@@ -0,0 +1,374 @@
+/*
+Drives simulated traffic through many in-process XMTP clients connected to one node, and
+reports delivery latency and loss. See the README for usage.
+*/
+mod report;
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use clap::Parser;
+use color_eyre::eyre::{self, eyre};
+use futures::{stream, StreamExt, TryStreamExt};
+use parking_lot::Mutex;
+use prost::Message;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use report::Recorder;
+use tracing_subscriber::EnvFilter;
+use xmtp_api_grpc::{ChannelPool, Client as GrpcClient};
+use xmtp_common::time::now_ns;
+use xmtp_content_types::{encoded_content_to_bytes, text::TextCodec, ContentCodec};
+use xmtp_cryptography::utils::generate_local_wallet;
+use xmtp_id::associations::{
+    generate_inbox_id,
+    unverified::{UnverifiedRecoverableEcdsaSignature, UnverifiedSignature},
+};
+use xmtp_mls::{
+    builder::ClientBuilder,
+    groups::GroupMetadataOptions,
+    identity::IdentityStrategy,
+    storage::{EncryptedMessageStore, StorageOption},
+    subscriptions::StreamAllMessagesOptions,
+    InboxOwner, StreamHandle,
+};
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+type SimClient = xmtp_mls::client::Client<GrpcClient>;
+
+/// How many clients are registered, or groups created, at the same time
+const SETUP_CONCURRENCY: usize = 16;
+
+#[derive(Debug, Parser)]
+#[command(name = "xmtp_simulation")]
+#[command(about = "Simulate traffic from many XMTP clients and report delivery latency and loss")]
+struct Args {
+    /// gRPC address of the node
+    #[arg(long, default_value = "http://localhost:5556")]
+    url: String,
+    /// Connect to the node over TLS
+    #[arg(long, default_value_t = false)]
+    tls: bool,
+    /// Number of clients to simulate
+    #[arg(long, default_value_t = 100)]
+    clients: usize,
+    /// Number of groups
+    #[arg(long, default_value_t = 20)]
+    groups: usize,
+    /// Members per group, including its creator
+    #[arg(long, default_value_t = 10)]
+    group_size: usize,
+    /// Messages per second, across all groups
+    #[arg(long, default_value_t = 10.0)]
+    message_rate: f64,
+    /// Members removed from or added back to a group per minute, across all groups
+    #[arg(long, default_value_t = 0.0)]
+    churn_rate: f64,
+    /// Seconds to send traffic for
+    #[arg(long, default_value_t = 60)]
+    duration: u64,
+    /// Seconds to wait for messages still in flight once sending stops
+    #[arg(long, default_value_t = 10)]
+    drain: u64,
+    /// Seed for the traffic pattern, to compare runs
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Print the report as JSON
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+/// The simulation's view of a group's membership, used to know who should receive a message
+struct SimGroup {
+    id: Vec<u8>,
+    /// The client that created the group, which performs all membership changes
+    creator: usize,
+    members: Vec<usize>,
+    /// Members removed by churn, waiting to be added back
+    removed: Vec<usize>,
+}
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    color_eyre::install()?;
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| "warn".into()))
+        .init();
+    let args = Args::parse();
+    if args.group_size < 2 || args.group_size > args.clients {
+        return Err(eyre!(
+            "group size must be between 2 and the number of clients"
+        ));
+    }
+    let seed = args.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let mut rng = StdRng::seed_from_u64(seed);
+    tracing::info!(seed, "starting simulation");
+
+    let db_dir = std::env::temp_dir().join(format!("xmtp-simulation-{seed}-{}", now_ns()));
+    std::fs::create_dir_all(&db_dir)?;
+    let result = run(&args, &mut rng, &db_dir).await;
+    if let Err(e) = std::fs::remove_dir_all(&db_dir) {
+        tracing::warn!("failed to remove {}: {e}", db_dir.display());
+    }
+    let report = result?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("seed:                {seed}\n{report}");
+    }
+    Ok(())
+}
+
+async fn run(args: &Args, rng: &mut StdRng, db_dir: &Path) -> eyre::Result<report::Report> {
+    println!("registering {} clients", args.clients);
+    let clients: Vec<Arc<SimClient>> = stream::iter(0..args.clients)
+        .map(|i| new_client(args, db_dir.join(format!("{i}.db3"))))
+        .buffered(SETUP_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    println!("creating {} groups of {}", args.groups, args.group_size);
+    let memberships: Vec<(usize, Vec<usize>)> = (0..args.groups)
+        .map(|g| {
+            let creator = g % clients.len();
+            let mut others: Vec<usize> = (0..clients.len()).filter(|i| *i != creator).collect();
+            others.shuffle(rng);
+            others.truncate(args.group_size - 1);
+            (creator, others)
+        })
+        .collect();
+    let groups: Vec<Arc<Mutex<SimGroup>>> = stream::iter(memberships)
+        .map(|(creator, others)| {
+            let clients = &clients;
+            async move {
+                let group = clients[creator].create_group(None, GroupMetadataOptions::default())?;
+                let inbox_ids: Vec<&str> = others.iter().map(|i| clients[*i].inbox_id()).collect();
+                group.add_members_by_inbox_id(&inbox_ids).await?;
+                let mut members = others;
+                members.push(creator);
+                Ok::<_, eyre::Report>(Arc::new(Mutex::new(SimGroup {
+                    id: group.group_id.clone(),
+                    creator,
+                    members,
+                    removed: vec![],
+                })))
+            }
+        })
+        .buffered(SETUP_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    let recorder = Arc::new(Recorder::default());
+    let mut streams = vec![];
+    for (i, client) in clients.iter().enumerate() {
+        client.sync_welcomes(&client.mls_provider()?).await?;
+        let recorder = recorder.clone();
+        let inbox_id = client.inbox_id().to_string();
+        let mut handle = SimClient::stream_all_messages_with_callback(
+            client.clone(),
+            StreamAllMessagesOptions::default(),
+            move |message| {
+                let Ok(message) = message else { return };
+                if message.sender_inbox_id == inbox_id {
+                    return;
+                }
+                if let Some((sequence, sent_at_ns)) =
+                    parse_message(&message.decrypted_message_bytes)
+                {
+                    let latency = Duration::from_nanos(now_ns().saturating_sub(sent_at_ns) as u64);
+                    recorder.delivered(sequence, i, latency);
+                }
+            },
+        );
+        handle.wait_for_ready().await;
+        streams.push(handle);
+    }
+
+    println!(
+        "sending {} messages/s for {}s{}",
+        args.message_rate,
+        args.duration,
+        if args.churn_rate > 0.0 {
+            format!(" with {} membership changes/min", args.churn_rate)
+        } else {
+            String::new()
+        }
+    );
+    let churn_task = (args.churn_rate > 0.0).then(|| {
+        tokio::spawn(churn(
+            clients.clone(),
+            groups.clone(),
+            recorder.clone(),
+            StdRng::seed_from_u64(rng.gen()),
+            Duration::from_secs_f64(60.0 / args.churn_rate),
+            Duration::from_secs(args.duration),
+        ))
+    });
+
+    let mut sends = vec![];
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / args.message_rate));
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(args.duration);
+    let mut sequence = 0;
+    while tokio::time::Instant::now() < deadline {
+        interval.tick().await;
+        let group = &groups[rng.gen_range(0..groups.len())];
+        let (group_id, sender, expected) = {
+            let group = group.lock();
+            let sender = *group
+                .members
+                .choose(rng)
+                .expect("groups keep their creator");
+            let expected: Vec<usize> = group
+                .members
+                .iter()
+                .copied()
+                .filter(|m| *m != sender)
+                .collect();
+            (group.id.clone(), sender, expected)
+        };
+        sequence += 1;
+        let client = clients[sender].clone();
+        let recorder = recorder.clone();
+        sends.push(tokio::spawn(async move {
+            let content = TextCodec::encode(format!("sim:{sequence}:{}", now_ns()))?;
+            let group = client.group(group_id)?;
+            match group.send_message(&encoded_content_to_bytes(content)).await {
+                Ok(_) => recorder.sent(sequence, expected),
+                Err(e) => {
+                    tracing::warn!("send failed: {e}");
+                    recorder.send_failed();
+                }
+            }
+            Ok::<_, eyre::Report>(())
+        }));
+    }
+    for send in sends {
+        send.await??;
+    }
+    if let Some(churn_task) = churn_task {
+        churn_task.await?;
+    }
+
+    println!("waiting {}s for deliveries", args.drain);
+    tokio::time::sleep(Duration::from_secs(args.drain)).await;
+    for stream in &streams {
+        stream.end();
+    }
+    Ok(recorder.report(clients.len(), groups.len()))
+}
+
+/// Register a new inbox with a random wallet
+async fn new_client(args: &Args, db_path: PathBuf) -> eyre::Result<Arc<SimClient>> {
+    let wallet = generate_local_wallet();
+    let nonce = 0;
+    let inbox_id = generate_inbox_id(&wallet.get_address(), &nonce)?;
+    let api = GrpcClient::create_with_pool(&args.url, args.tls, ChannelPool::shared()).await?;
+    let store = EncryptedMessageStore::new_unencrypted(StorageOption::Persistent(
+        db_path.to_string_lossy().to_string(),
+    ))
+    .await?;
+    let client = ClientBuilder::new(IdentityStrategy::new(
+        inbox_id,
+        wallet.get_address(),
+        nonce,
+        None,
+    ))
+    .api_client(api)
+    .store(store)
+    .build()
+    .await?;
+
+    let mut signature_request = client
+        .identity()
+        .signature_request()
+        .ok_or_else(|| eyre!("new identity has no signature request"))?;
+    let signature = wallet.sign(&signature_request.signature_text())?;
+    signature_request
+        .add_signature(
+            UnverifiedSignature::RecoverableEcdsa(UnverifiedRecoverableEcdsaSignature::new(
+                signature.into(),
+            )),
+            client.scw_verifier(),
+        )
+        .await?;
+    client.register_identity(signature_request).await?;
+    Ok(Arc::new(client))
+}
+
+/// Alternately remove a random member from a random group and add a removed one back, every
+/// `every` until `duration` has passed. Membership is updated before a removal and after an
+/// addition, so messages sent during the change are never expected by the member changing.
+async fn churn(
+    clients: Vec<Arc<SimClient>>,
+    groups: Vec<Arc<Mutex<SimGroup>>>,
+    recorder: Arc<Recorder>,
+    mut rng: StdRng,
+    every: Duration,
+    duration: Duration,
+) {
+    let deadline = tokio::time::Instant::now() + duration;
+    let mut interval = tokio::time::interval(every);
+    interval.tick().await;
+    while tokio::time::Instant::now() < deadline {
+        interval.tick().await;
+        let group = groups[rng.gen_range(0..groups.len())].clone();
+        let add_back = rng.gen_bool(0.5);
+        let change = {
+            let mut group = group.lock();
+            let creator = group.creator;
+            if add_back && !group.removed.is_empty() {
+                let index = rng.gen_range(0..group.removed.len());
+                Some((group.id.clone(), creator, group.removed.remove(index), true))
+            } else {
+                let candidates: Vec<usize> = group
+                    .members
+                    .iter()
+                    .copied()
+                    .filter(|m| *m != creator)
+                    .collect();
+                candidates.choose(&mut rng).copied().map(|member| {
+                    group.members.retain(|m| *m != member);
+                    (group.id.clone(), creator, member, false)
+                })
+            }
+        };
+        let Some((group_id, creator, member, adding)) = change else {
+            continue;
+        };
+
+        let inbox_id = clients[member].inbox_id();
+        let result = match clients[creator].group(group_id) {
+            Ok(mls_group) if adding => mls_group.add_members_by_inbox_id(&[inbox_id]).await,
+            Ok(mls_group) => mls_group.remove_members_by_inbox_id(&[inbox_id]).await,
+            Err(e) => {
+                tracing::warn!("membership change failed: {e}");
+                continue;
+            }
+        };
+        let mut group = group.lock();
+        match (result, adding) {
+            (Ok(()), true) => group.members.push(member),
+            (Ok(()), false) => group.removed.push(member),
+            (Err(e), _) => {
+                tracing::warn!("membership change failed: {e}");
+                // Assume the member is out of the group, and try adding them back later
+                group.removed.push(member);
+                continue;
+            }
+        }
+        recorder.membership_changed();
+    }
+}
+
+/// The sequence number and send time in a message sent by the simulation
+fn parse_message(bytes: &[u8]) -> Option<(u64, i64)> {
+    let content = EncodedContent::decode(bytes).ok()?;
+    let text = TextCodec::decode(content).ok()?;
+    let mut parts = text.strip_prefix("sim:")?.split(':');
+    let sequence = parts.next()?.parse().ok()?;
+    let sent_at_ns = parts.next()?.parse().ok()?;
+    Some((sequence, sent_at_ns))
+}
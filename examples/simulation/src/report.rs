@@ -0,0 +1,152 @@
+//! Recording sends and deliveries, and summarizing them once the run ends
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    time::Duration,
+};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+/// Everything sent and delivered during a run, shared by all tasks
+#[derive(Default)]
+pub struct Recorder {
+    /// The clients expected to receive each message, by sequence number
+    sent: Mutex<HashMap<u64, Vec<usize>>>,
+    send_failures: Mutex<usize>,
+    membership_changes: Mutex<usize>,
+    /// The recipients and latency of each delivery, by sequence number
+    deliveries: Mutex<HashMap<u64, Vec<(usize, Duration)>>>,
+}
+
+impl Recorder {
+    pub fn sent(&self, sequence: u64, expected_recipients: Vec<usize>) {
+        self.sent.lock().insert(sequence, expected_recipients);
+    }
+
+    pub fn send_failed(&self) {
+        *self.send_failures.lock() += 1;
+    }
+
+    pub fn membership_changed(&self) {
+        *self.membership_changes.lock() += 1;
+    }
+
+    pub fn delivered(&self, sequence: u64, recipient: usize, latency: Duration) {
+        self.deliveries
+            .lock()
+            .entry(sequence)
+            .or_default()
+            .push((recipient, latency));
+    }
+
+    /// Summarize the run. Deliveries to clients that weren't expected to receive a message,
+    /// e.g. a member removed while the message was in flight, don't count towards latency or
+    /// loss.
+    pub fn report(&self, clients: usize, groups: usize) -> Report {
+        let sent = self.sent.lock();
+        let deliveries = self.deliveries.lock();
+        let mut expected_deliveries = 0;
+        let mut delivered = 0;
+        let mut latencies = vec![];
+        for (sequence, expected) in sent.iter() {
+            expected_deliveries += expected.len();
+            let mut seen = HashSet::new();
+            for (recipient, latency) in deliveries.get(sequence).into_iter().flatten() {
+                if expected.contains(recipient) && seen.insert(*recipient) {
+                    delivered += 1;
+                    latencies.push(*latency);
+                }
+            }
+        }
+        let lost = expected_deliveries - delivered;
+
+        Report {
+            clients,
+            groups,
+            sent: sent.len(),
+            send_failures: *self.send_failures.lock(),
+            membership_changes: *self.membership_changes.lock(),
+            expected_deliveries,
+            delivered,
+            lost,
+            loss_percent: if expected_deliveries == 0 {
+                0.0
+            } else {
+                lost as f64 * 100.0 / expected_deliveries as f64
+            },
+            latency: LatencySummary::from_latencies(latencies),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub clients: usize,
+    pub groups: usize,
+    pub sent: usize,
+    pub send_failures: usize,
+    pub membership_changes: usize,
+    pub expected_deliveries: usize,
+    pub delivered: usize,
+    pub lost: usize,
+    pub loss_percent: f64,
+    /// `None` if nothing was delivered
+    pub latency: Option<LatencySummary>,
+}
+
+/// Delivery latency, from just before the send to the recipient's stream yielding the message
+#[derive(Debug, Serialize)]
+pub struct LatencySummary {
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl LatencySummary {
+    fn from_latencies(mut latencies: Vec<Duration>) -> Option<Self> {
+        if latencies.is_empty() {
+            return None;
+        }
+        latencies.sort();
+        let ms = |latency: Duration| latency.as_secs_f64() * 1000.0;
+        let percentile = |p: f64| {
+            let index = ((latencies.len() - 1) as f64 * p).round() as usize;
+            ms(latencies[index])
+        };
+        let total: Duration = latencies.iter().sum();
+
+        Some(Self {
+            mean_ms: ms(total) / latencies.len() as f64,
+            p50_ms: percentile(0.5),
+            p90_ms: percentile(0.9),
+            p99_ms: percentile(0.99),
+            max_ms: ms(latencies[latencies.len() - 1]),
+        })
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "clients:             {}", self.clients)?;
+        writeln!(f, "groups:              {}", self.groups)?;
+        writeln!(f, "messages sent:       {}", self.sent)?;
+        writeln!(f, "send failures:       {}", self.send_failures)?;
+        writeln!(f, "membership changes:  {}", self.membership_changes)?;
+        writeln!(
+            f,
+            "delivered:           {} of {} ({} lost, {:.2}%)",
+            self.delivered, self.expected_deliveries, self.lost, self.loss_percent
+        )?;
+        match &self.latency {
+            Some(latency) => write!(
+                f,
+                "latency (ms):        mean {:.1}, p50 {:.1}, p90 {:.1}, p99 {:.1}, max {:.1}",
+                latency.mean_ms, latency.p50_ms, latency.p90_ms, latency.p99_ms, latency.max_ms
+            ),
+            None => write!(f, "latency (ms):        n/a"),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Channels shared between clients, for processes running several clients (e.g. one per
+//! account) against the same backend. A [`tonic`] channel multiplexes every request over one
+//! HTTP/2 connection, so clients created from the same pool reuse connections and TLS
+//! sessions instead of each opening their own.
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+use tokio::sync::Mutex;
+use tonic::transport::Channel;
+use xmtp_proto::{Error, ErrorKind};
+
+use crate::grpc_api_helper::create_tls_channel;
+
+/// Connected channels, keyed by endpoint
+#[derive(Debug, Clone, Default)]
+pub struct ChannelPool(Arc<Mutex<HashMap<(String, bool), Channel>>>);
+
+impl ChannelPool {
+    /// The pool shared by the whole process
+    pub fn shared() -> &'static ChannelPool {
+        static SHARED: OnceLock<ChannelPool> = OnceLock::new();
+        SHARED.get_or_init(ChannelPool::default)
+    }
+
+    /// The channel to `host`, connecting it if this is the first client to use it
+    pub async fn channel(&self, host: &str, is_secure: bool) -> Result<Channel, Error> {
+        let mut channels = self.0.lock().await;
+        let key = (host.to_string(), is_secure);
+        if let Some(channel) = channels.get(&key) {
+            return Ok(channel.clone());
+        }
+        let channel = connect(host.to_string(), is_secure).await?;
+        channels.insert(key, channel.clone());
+        Ok(channel)
+    }
+
+    /// Number of endpoints with a connected channel
+    pub async fn len(&self) -> usize {
+        self.0.lock().await.len()
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.len().await == 0
+    }
+
+    /// Drop the channel to `host`. Clients already using it keep it open until they are
+    /// dropped; clients created afterwards connect again.
+    pub async fn evict(&self, host: &str, is_secure: bool) {
+        self.0.lock().await.remove(&(host.to_string(), is_secure));
+    }
+}
+
+pub(crate) async fn connect(host: String, is_secure: bool) -> Result<Channel, Error> {
+    match is_secure {
+        true => create_tls_channel(host).await,
+        false => Channel::from_shared(host)
+            .map_err(|e| Error::new(ErrorKind::SetupCreateChannelError).with(e))?
+            .connect()
+            .await
+            .map_err(|e| Error::new(ErrorKind::SetupConnectionError).with(e)),
+    }
+}
+
+/// Counters for the requests of a single client, kept separately from the other clients
+/// sharing its channel. Cloning is cheap; every clone reads the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct ClientMetrics(Arc<ClientMetricsInner>);
+
+#[derive(Debug, Default)]
+struct ClientMetricsInner {
+    requests: AtomicU64,
+}
+
+impl ClientMetrics {
+    /// Requests and subscriptions started by the client
+    pub fn requests(&self) -> u64 {
+        self.0.requests.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_request(&self) {
+        self.0.requests.fetch_add(1, Ordering::Relaxed);
+    }
+}
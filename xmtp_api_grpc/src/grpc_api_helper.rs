@@ -10,6 +10,8 @@ use tonic::transport::ClientTlsConfig;
 use tonic::{metadata::MetadataValue, transport::Channel, Request, Streaming};
 use tracing::Instrument;
 
+use crate::channel_pool::{connect, ChannelPool, ClientMetrics};
+
 use xmtp_proto::api_client::{ClientWithMetadata, XmtpMlsStreams};
 use xmtp_proto::xmtp::mls::api::v1::{GroupMessage, WelcomeMessage};
 use xmtp_proto::{
@@ -75,26 +77,34 @@ pub struct Client {
     pub(crate) identity_client: ProtoIdentityApiClient<Channel>,
     pub(crate) app_version: MetadataValue<tonic::metadata::Ascii>,
     pub(crate) libxmtp_version: MetadataValue<tonic::metadata::Ascii>,
+    pub(crate) metrics: ClientMetrics,
 }
 
 impl Client {
     #[tracing::instrument(level = "trace", skip_all)]
     pub async fn create(host: impl ToString, is_secure: bool) -> Result<Self, Error> {
-        let host = host.to_string();
+        let channel = connect(host.to_string(), is_secure).await?;
+        Self::from_channel(channel)
+    }
+
+    /// Like [`Client::create`], but reuses the connection to `host` held by `pool`, if any.
+    /// Metrics and metadata stay specific to the returned client.
+    #[tracing::instrument(level = "trace", skip_all)]
+    pub async fn create_with_pool(
+        host: impl ToString,
+        is_secure: bool,
+        pool: &ChannelPool,
+    ) -> Result<Self, Error> {
+        let channel = pool.channel(&host.to_string(), is_secure).await?;
+        Self::from_channel(channel)
+    }
+
+    fn from_channel(channel: Channel) -> Result<Self, Error> {
         let app_version = MetadataValue::try_from(&String::from("0.0.0"))
             .map_err(|e| Error::new(ErrorKind::MetadataError).with(e))?;
         let libxmtp_version = MetadataValue::try_from(&String::from("0.0.0"))
             .map_err(|e| Error::new(ErrorKind::MetadataError).with(e))?;
 
-        let channel = match is_secure {
-            true => create_tls_channel(host).await?,
-            false => Channel::from_shared(host)
-                .map_err(|e| Error::new(ErrorKind::SetupCreateChannelError).with(e))?
-                .connect()
-                .await
-                .map_err(|e| Error::new(ErrorKind::SetupConnectionError).with(e))?,
-        };
-
         let client = MessageApiClient::new(channel.clone());
         let mls_client = ProtoMlsApiClient::new(channel.clone());
         let identity_client = ProtoIdentityApiClient::new(channel);
@@ -105,10 +115,12 @@ impl Client {
             app_version,
             libxmtp_version,
             identity_client,
+            metrics: ClientMetrics::default(),
         })
     }
 
     pub fn build_request<RequestType>(&self, request: RequestType) -> Request<RequestType> {
+        self.metrics.record_request();
         let mut req = Request::new(request);
         req.metadata_mut()
             .insert("x-app-version", self.app_version.clone());
@@ -121,6 +133,10 @@ impl Client {
     pub fn identity_client(&self) -> &ProtoIdentityApiClient<Channel> {
         &self.identity_client
     }
+
+    pub fn metrics(&self) -> &ClientMetrics {
+        &self.metrics
+    }
 }
 
 impl ClientWithMetadata for Client {
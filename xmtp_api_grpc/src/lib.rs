@@ -1,4 +1,5 @@
 pub mod auth_token;
+pub mod channel_pool;
 pub mod grpc_api_helper;
 mod identity;
 pub mod replication_client;
@@ -6,6 +7,7 @@ pub mod replication_client;
 pub const LOCALHOST_ADDRESS: &str = "http://localhost:5556";
 pub const DEV_ADDRESS: &str = "https://grpc.dev.xmtp.network:443";
 
+pub use channel_pool::{ChannelPool, ClientMetrics};
 pub use grpc_api_helper::{Client, GroupMessageStream, WelcomeMessageStream};
 
 mod utils {
@@ -328,6 +330,30 @@ pub mod tests {
         .expect("Timed out");
     }
 
+    #[tokio::test]
+    async fn channel_pool_test() {
+        let pool = ChannelPool::default();
+        let alix = Client::create_with_pool(LOCALHOST_ADDRESS, false, &pool)
+            .await
+            .unwrap();
+        let bo = Client::create_with_pool(LOCALHOST_ADDRESS, false, &pool)
+            .await
+            .unwrap();
+        assert_eq!(pool.len().await, 1);
+
+        alix.query(QueryRequest {
+            content_topics: vec!["test-pool".to_string()],
+            ..QueryRequest::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(alix.metrics().requests(), 1);
+        assert_eq!(bo.metrics().requests(), 0);
+
+        pool.evict(LOCALHOST_ADDRESS, false).await;
+        assert!(pool.is_empty().await);
+    }
+
     #[tokio::test]
     async fn metadata_test() {
         let mut client = Client::create(DEV_ADDRESS.to_string(), true).await.unwrap();
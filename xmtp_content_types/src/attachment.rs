@@ -1,6 +1,65 @@
+use std::collections::HashMap;
+
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{CodecError, ContentCodec};
+
+/// A file sent inline with the message. Larger files are sent as remote attachments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub filename: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
 pub struct AttachmentCodec {}
 
 //. Legacy content type id at https://github.com/xmtp/xmtp-js/blob/main/content-types/content-type-remote-attachment/src/Attachment.ts
 impl AttachmentCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
     pub const TYPE_ID: &'static str = "attachment";
+    const FILENAME_KEY: &'static str = "filename";
+    const MIME_TYPE_KEY: &'static str = "mimeType";
+}
+
+impl ContentCodec<Attachment> for AttachmentCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: AttachmentCodec::AUTHORITY_ID.to_string(),
+            type_id: AttachmentCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(data: Attachment) -> Result<EncodedContent, CodecError> {
+        Ok(EncodedContent {
+            r#type: Some(AttachmentCodec::content_type()),
+            parameters: HashMap::from([
+                (AttachmentCodec::FILENAME_KEY.to_string(), data.filename),
+                (AttachmentCodec::MIME_TYPE_KEY.to_string(), data.mime_type),
+            ]),
+            fallback: None,
+            compression: None,
+            content: data.data,
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<Attachment, CodecError> {
+        let parameter = |key: &str| {
+            content
+                .parameters
+                .get(key)
+                .cloned()
+                .ok_or_else(|| CodecError::Decode(format!("attachment has no {key}")))
+        };
+        let filename = parameter(AttachmentCodec::FILENAME_KEY)?;
+        let mime_type = parameter(AttachmentCodec::MIME_TYPE_KEY)?;
+
+        Ok(Attachment {
+            filename,
+            mime_type,
+            data: content.content,
+        })
+    }
 }
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use super::{CodecError, ContentCodec};
+
+/// The content types an installation can render and the optional features it supports,
+/// advertised to the other members of a conversation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Content type ids in `authority/type:major.minor` form, see [`content_type_key`]
+    pub content_types: Vec<String>,
+    pub features: Vec<String>,
+}
+
+/// Formats a content type id the way capabilities refer to it, e.g. `xmtp.org/text:1.0`
+pub fn content_type_key(content_type: &ContentTypeId) -> String {
+    format!(
+        "{}/{}:{}.{}",
+        content_type.authority_id,
+        content_type.type_id,
+        content_type.version_major,
+        content_type.version_minor
+    )
+}
+
+pub struct CapabilitiesCodec {}
+
+impl CapabilitiesCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
+    pub const TYPE_ID: &'static str = "capabilities";
+    const CONTENT_TYPES_KEY: &'static str = "contentTypes";
+    const FEATURES_KEY: &'static str = "features";
+    const SEPARATOR: char = ',';
+
+    fn join(values: Vec<String>) -> Result<String, CodecError> {
+        if let Some(value) = values.iter().find(|v| v.contains(Self::SEPARATOR)) {
+            return Err(CodecError::Encode(format!(
+                "capability `{value}` contains `{}`",
+                Self::SEPARATOR
+            )));
+        }
+        Ok(values.join(&Self::SEPARATOR.to_string()))
+    }
+
+    fn split(parameters: &HashMap<String, String>, key: &str) -> Vec<String> {
+        parameters
+            .get(key)
+            .map(|values| {
+                values
+                    .split(Self::SEPARATOR)
+                    .filter(|v| !v.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl ContentCodec<Capabilities> for CapabilitiesCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: CapabilitiesCodec::AUTHORITY_ID.to_string(),
+            type_id: CapabilitiesCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(data: Capabilities) -> Result<EncodedContent, CodecError> {
+        Ok(EncodedContent {
+            r#type: Some(CapabilitiesCodec::content_type()),
+            parameters: HashMap::from([
+                (
+                    CapabilitiesCodec::CONTENT_TYPES_KEY.to_string(),
+                    CapabilitiesCodec::join(data.content_types)?,
+                ),
+                (
+                    CapabilitiesCodec::FEATURES_KEY.to_string(),
+                    CapabilitiesCodec::join(data.features)?,
+                ),
+            ]),
+            fallback: None,
+            compression: None,
+            content: vec![],
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<Capabilities, CodecError> {
+        Ok(Capabilities {
+            content_types: CapabilitiesCodec::split(
+                &content.parameters,
+                CapabilitiesCodec::CONTENT_TYPES_KEY,
+            ),
+            features: CapabilitiesCodec::split(
+                &content.parameters,
+                CapabilitiesCodec::FEATURES_KEY,
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::text::TextCodec;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let capabilities = Capabilities {
+            content_types: vec![content_type_key(&TextCodec::content_type())],
+            features: vec!["editing".to_string(), "polls".to_string()],
+        };
+
+        let encoded = CapabilitiesCodec::encode(capabilities.clone()).unwrap();
+        assert_eq!(encoded.r#type.as_ref().unwrap().type_id, "capabilities");
+
+        let decoded = CapabilitiesCodec::decode(encoded).unwrap();
+        assert_eq!(decoded, capabilities);
+        assert_eq!(decoded.content_types[0], "xmtp.org/text:1.0");
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_rejects_separator_in_capability() {
+        let capabilities = Capabilities {
+            content_types: vec![],
+            features: vec!["a,b".to_string()],
+        };
+        assert!(CapabilitiesCodec::encode(capabilities).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{CodecError, ContentCodec};
+
+/// A tombstone asking every member to delete an earlier message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeleteMessage {
+    /// Hex encoded id of the deleted message
+    pub reference: String,
+}
+
+pub struct DeleteMessageCodec {}
+
+impl DeleteMessageCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
+    pub const TYPE_ID: &'static str = "deleteMessage";
+    const REFERENCE_KEY: &'static str = "reference";
+}
+
+impl ContentCodec<DeleteMessage> for DeleteMessageCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: DeleteMessageCodec::AUTHORITY_ID.to_string(),
+            type_id: DeleteMessageCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(data: DeleteMessage) -> Result<EncodedContent, CodecError> {
+        Ok(EncodedContent {
+            r#type: Some(DeleteMessageCodec::content_type()),
+            parameters: HashMap::from([(
+                DeleteMessageCodec::REFERENCE_KEY.to_string(),
+                data.reference,
+            )]),
+            fallback: None,
+            compression: None,
+            content: vec![],
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<DeleteMessage, CodecError> {
+        let reference = content
+            .parameters
+            .get(DeleteMessageCodec::REFERENCE_KEY)
+            .ok_or_else(|| CodecError::Decode("deletion has no reference".to_string()))?
+            .clone();
+
+        Ok(DeleteMessage { reference })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let deletion = DeleteMessage {
+            reference: "0a0b".to_string(),
+        };
+
+        let encoded = DeleteMessageCodec::encode(deletion.clone()).unwrap();
+        assert_eq!(encoded.r#type.as_ref().unwrap().type_id, "deleteMessage");
+        assert!(encoded.content.is_empty());
+        assert_eq!(DeleteMessageCodec::decode(encoded).unwrap(), deletion);
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use prost::Message;
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{CodecError, ContentCodec};
+
+/// Replaces the content of an earlier message sent by the same inbox
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edit {
+    /// Hex encoded id of the edited message
+    pub reference: String,
+    /// The new content of the message, of any content type
+    pub content: EncodedContent,
+}
+
+pub struct EditCodec {}
+
+impl EditCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
+    pub const TYPE_ID: &'static str = "edit";
+    const REFERENCE_KEY: &'static str = "reference";
+}
+
+impl ContentCodec<Edit> for EditCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: EditCodec::AUTHORITY_ID.to_string(),
+            type_id: EditCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(data: Edit) -> Result<EncodedContent, CodecError> {
+        Ok(EncodedContent {
+            r#type: Some(EditCodec::content_type()),
+            parameters: HashMap::from([(EditCodec::REFERENCE_KEY.to_string(), data.reference)]),
+            fallback: None,
+            compression: None,
+            content: data.content.encode_to_vec(),
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<Edit, CodecError> {
+        let reference = content
+            .parameters
+            .get(EditCodec::REFERENCE_KEY)
+            .ok_or_else(|| CodecError::Decode("edit has no reference".to_string()))?
+            .clone();
+        let inner = EncodedContent::decode(content.content.as_slice())
+            .map_err(|e| CodecError::Decode(e.to_string()))?;
+
+        Ok(Edit {
+            reference,
+            content: inner,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::text::TextCodec;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let edit = Edit {
+            reference: "0a0b".to_string(),
+            content: TextCodec::encode("fixed typo".to_string()).unwrap(),
+        };
+
+        let encoded = EditCodec::encode(edit.clone()).unwrap();
+        assert_eq!(encoded.r#type.as_ref().unwrap().type_id, "edit");
+
+        let decoded = EditCodec::decode(encoded).unwrap();
+        assert_eq!(decoded, edit);
+        assert_eq!(TextCodec::decode(decoded.content).unwrap(), "fixed typo");
+    }
+}
@@ -0,0 +1,122 @@
+//! Provenance of forwarded messages. It is carried in the parameters of the forwarded
+//! [`EncodedContent`], so any content type can be forwarded and clients that don't know about
+//! forwarding still render the content itself.
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use crate::CodecError;
+
+const ORIGINAL_SENDER_INBOX_ID: &str = "forwardedFromSenderInboxId";
+const ORIGINAL_CONVERSATION: &str = "forwardedFromConversation";
+const ORIGINAL_SENT_AT_NS: &str = "forwardedFromSentAtNs";
+const HOP_COUNT: &str = "forwardedHopCount";
+
+/// Where a forwarded message originally came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardProvenance {
+    pub original_sender_inbox_id: String,
+    /// An opaque reference to the original conversation. Members of that conversation can
+    /// recognize it, but it doesn't disclose the conversation's id to anyone else.
+    pub original_conversation: Vec<u8>,
+    pub original_sent_at_ns: i64,
+    /// How many times the message was forwarded, starting at 1
+    pub hop_count: u32,
+}
+
+impl ForwardProvenance {
+    /// The provenance of `content`, if it was forwarded
+    pub fn read(content: &EncodedContent) -> Result<Option<Self>, CodecError> {
+        let Some(hop_count) = content.parameters.get(HOP_COUNT) else {
+            return Ok(None);
+        };
+        let param = |key: &str| {
+            content
+                .parameters
+                .get(key)
+                .ok_or_else(|| CodecError::Decode(format!("missing forward parameter {key}")))
+        };
+        let invalid = |key: &str| CodecError::Decode(format!("invalid forward parameter {key}"));
+
+        Ok(Some(Self {
+            original_sender_inbox_id: param(ORIGINAL_SENDER_INBOX_ID)?.clone(),
+            original_conversation: hex::decode(param(ORIGINAL_CONVERSATION)?)
+                .map_err(|_| invalid(ORIGINAL_CONVERSATION))?,
+            original_sent_at_ns: param(ORIGINAL_SENT_AT_NS)?
+                .parse()
+                .map_err(|_| invalid(ORIGINAL_SENT_AT_NS))?,
+            hop_count: hop_count.parse().map_err(|_| invalid(HOP_COUNT))?,
+        }))
+    }
+
+    /// Record the provenance in `content`, replacing any earlier provenance
+    pub fn write(&self, content: &mut EncodedContent) {
+        let parameters = &mut content.parameters;
+        parameters.insert(
+            ORIGINAL_SENDER_INBOX_ID.to_string(),
+            self.original_sender_inbox_id.clone(),
+        );
+        parameters.insert(
+            ORIGINAL_CONVERSATION.to_string(),
+            hex::encode(&self.original_conversation),
+        );
+        parameters.insert(
+            ORIGINAL_SENT_AT_NS.to_string(),
+            self.original_sent_at_ns.to_string(),
+        );
+        parameters.insert(HOP_COUNT.to_string(), self.hop_count.to_string());
+    }
+
+    /// The provenance of `content` forwarded once more. Content that was already forwarded
+    /// keeps its original source; otherwise the source is the message being forwarded, sent in
+    /// the conversation referred to by `conversation`.
+    pub fn next_hop(
+        content: &EncodedContent,
+        sender_inbox_id: &str,
+        conversation: &[u8],
+        sent_at_ns: i64,
+    ) -> Result<Self, CodecError> {
+        Ok(match Self::read(content)? {
+            Some(provenance) => Self {
+                hop_count: provenance.hop_count.saturating_add(1),
+                ..provenance
+            },
+            None => Self {
+                original_sender_inbox_id: sender_inbox_id.to_string(),
+                original_conversation: conversation.to_vec(),
+                original_sent_at_ns: sent_at_ns,
+                hop_count: 1,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{text::TextCodec, ContentCodec};
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn forwarding_again_keeps_the_original_source() {
+        let mut content = TextCodec::encode("hello".to_string()).unwrap();
+        assert_eq!(ForwardProvenance::read(&content).unwrap(), None);
+
+        let first = ForwardProvenance::next_hop(&content, "alix", &[0xab, 0x01], 10).unwrap();
+        first.write(&mut content);
+        assert_eq!(ForwardProvenance::read(&content).unwrap(), Some(first));
+
+        let second = ForwardProvenance::next_hop(&content, "bo", &[0xcd], 20).unwrap();
+        assert_eq!(
+            second,
+            ForwardProvenance {
+                original_sender_inbox_id: "alix".to_string(),
+                original_conversation: vec![0xab, 0x01],
+                original_sent_at_ns: 10,
+                hop_count: 2,
+            }
+        );
+        assert_eq!(TextCodec::decode(content).unwrap(), "hello");
+    }
+}
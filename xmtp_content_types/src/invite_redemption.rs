@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{CodecError, ContentCodec};
+
+/// Asks the creator of an invite link to add the sender to the link's group. Sent in a DM with
+/// the creator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InviteRedemption {
+    /// Hex encoded id of the invite link
+    pub link_id: String,
+}
+
+pub struct InviteRedemptionCodec {}
+
+impl InviteRedemptionCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
+    pub const TYPE_ID: &'static str = "inviteRedemption";
+    const LINK_ID_KEY: &'static str = "linkId";
+}
+
+impl ContentCodec<InviteRedemption> for InviteRedemptionCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: InviteRedemptionCodec::AUTHORITY_ID.to_string(),
+            type_id: InviteRedemptionCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(data: InviteRedemption) -> Result<EncodedContent, CodecError> {
+        Ok(EncodedContent {
+            r#type: Some(InviteRedemptionCodec::content_type()),
+            parameters: HashMap::from([(
+                InviteRedemptionCodec::LINK_ID_KEY.to_string(),
+                data.link_id,
+            )]),
+            fallback: None,
+            compression: None,
+            content: vec![],
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<InviteRedemption, CodecError> {
+        let link_id = content
+            .parameters
+            .get(InviteRedemptionCodec::LINK_ID_KEY)
+            .ok_or_else(|| CodecError::Decode("invite redemption has no link id".to_string()))?
+            .clone();
+
+        Ok(InviteRedemption { link_id })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let redemption = InviteRedemption {
+            link_id: "0a0b".to_string(),
+        };
+        let encoded = InviteRedemptionCodec::encode(redemption.clone()).unwrap();
+        assert_eq!(encoded.r#type.as_ref().unwrap().type_id, "inviteRedemption");
+        assert_eq!(InviteRedemptionCodec::decode(encoded).unwrap(), redemption);
+    }
+}
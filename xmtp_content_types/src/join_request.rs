@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{CodecError, ContentCodec};
+
+/// Asks an admin of a group to add the sender to it. Sent in a DM with the admin. A member whose
+/// copy of the group forked sends a rejoin request instead, asking to be removed and added back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinRequest {
+    /// Hex encoded id of the group to join
+    pub group_id: String,
+    /// An optional note for the admin, e.g. who the sender is
+    pub note: Option<String>,
+    /// The sender is already a member and asks to be added back
+    pub rejoin: bool,
+    /// For a rejoin, the state of the sender's copy of the group
+    pub fork_evidence: Option<ForkEvidence>,
+}
+
+/// The epoch a member's copy of a group is stuck in, and its epoch authenticator. An admin
+/// compares them with its own copy to tell whether the member's copy forked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForkEvidence {
+    pub epoch: u64,
+    pub epoch_authenticator: Vec<u8>,
+}
+
+pub struct JoinRequestCodec {}
+
+impl JoinRequestCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
+    pub const TYPE_ID: &'static str = "joinRequest";
+    const GROUP_ID_KEY: &'static str = "groupId";
+    const REJOIN_KEY: &'static str = "rejoin";
+    const EPOCH_KEY: &'static str = "epoch";
+    const EPOCH_AUTHENTICATOR_KEY: &'static str = "epochAuthenticator";
+}
+
+impl ContentCodec<JoinRequest> for JoinRequestCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: JoinRequestCodec::AUTHORITY_ID.to_string(),
+            type_id: JoinRequestCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(data: JoinRequest) -> Result<EncodedContent, CodecError> {
+        let mut parameters =
+            HashMap::from([(JoinRequestCodec::GROUP_ID_KEY.to_string(), data.group_id)]);
+        if data.rejoin {
+            parameters.insert(JoinRequestCodec::REJOIN_KEY.to_string(), "true".to_string());
+        }
+        if let Some(evidence) = data.fork_evidence {
+            parameters.insert(
+                JoinRequestCodec::EPOCH_KEY.to_string(),
+                evidence.epoch.to_string(),
+            );
+            parameters.insert(
+                JoinRequestCodec::EPOCH_AUTHENTICATOR_KEY.to_string(),
+                hex::encode(evidence.epoch_authenticator),
+            );
+        }
+        Ok(EncodedContent {
+            r#type: Some(JoinRequestCodec::content_type()),
+            parameters,
+            fallback: None,
+            compression: None,
+            content: data.note.map(String::into_bytes).unwrap_or_default(),
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<JoinRequest, CodecError> {
+        let group_id = content
+            .parameters
+            .get(JoinRequestCodec::GROUP_ID_KEY)
+            .ok_or_else(|| CodecError::Decode("join request has no group id".to_string()))?
+            .clone();
+        let rejoin = content
+            .parameters
+            .get(JoinRequestCodec::REJOIN_KEY)
+            .is_some_and(|rejoin| rejoin == "true");
+        let fork_evidence = match (
+            content.parameters.get(JoinRequestCodec::EPOCH_KEY),
+            content
+                .parameters
+                .get(JoinRequestCodec::EPOCH_AUTHENTICATOR_KEY),
+        ) {
+            (Some(epoch), Some(epoch_authenticator)) => Some(ForkEvidence {
+                epoch: epoch
+                    .parse()
+                    .map_err(|_| CodecError::Decode("join request epoch is invalid".to_string()))?,
+                epoch_authenticator: hex::decode(epoch_authenticator)
+                    .map_err(|e| CodecError::Decode(e.to_string()))?,
+            }),
+            _ => None,
+        };
+        let note = if content.content.is_empty() {
+            None
+        } else {
+            Some(
+                String::from_utf8(content.content)
+                    .map_err(|e| CodecError::Decode(e.to_string()))?,
+            )
+        };
+
+        Ok(JoinRequest {
+            group_id,
+            note,
+            rejoin,
+            fork_evidence,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let request = JoinRequest {
+            group_id: "0a0b".to_string(),
+            note: Some("it's bo from the meetup".to_string()),
+            rejoin: false,
+            fork_evidence: None,
+        };
+        let encoded = JoinRequestCodec::encode(request.clone()).unwrap();
+        assert_eq!(encoded.r#type.as_ref().unwrap().type_id, "joinRequest");
+        assert_eq!(JoinRequestCodec::decode(encoded).unwrap(), request);
+
+        let without_note = JoinRequest {
+            note: None,
+            ..request
+        };
+        let encoded = JoinRequestCodec::encode(without_note.clone()).unwrap();
+        assert_eq!(JoinRequestCodec::decode(encoded).unwrap(), without_note);
+
+        let rejoin = JoinRequest {
+            rejoin: true,
+            fork_evidence: Some(ForkEvidence {
+                epoch: 7,
+                epoch_authenticator: vec![1, 2, 3],
+            }),
+            ..without_note
+        };
+        let encoded = JoinRequestCodec::encode(rejoin.clone()).unwrap();
+        assert_eq!(JoinRequestCodec::decode(encoded).unwrap(), rejoin);
+    }
+}
@@ -1,6 +1,15 @@
 pub mod attachment;
+pub mod capabilities;
+pub mod delete_message;
+pub mod edit;
+pub mod forward;
 pub mod group_updated;
+pub mod invite_redemption;
+pub mod join_request;
+pub mod link_preview;
 pub mod membership_change;
+pub mod poll;
+pub mod profile;
 pub mod reaction;
 pub mod read_receipt;
 pub mod remote_attachment;
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{
+    remote_attachment::{RemoteAttachment, RemoteAttachmentCodec},
+    CodecError, ContentCodec,
+};
+
+/// Metadata of a linked page, shown as a card below the message linking to it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    /// The preview image, encrypted and uploaded like any remote attachment
+    pub image: Option<RemoteAttachment>,
+}
+
+/// A text message along with a preview of a link in it. Clients that don't know the content
+/// type show the text, which is the fallback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextWithLinkPreview {
+    pub text: String,
+    pub preview: LinkPreview,
+}
+
+pub struct LinkPreviewCodec {}
+
+impl LinkPreviewCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
+    pub const TYPE_ID: &'static str = "linkPreview";
+    const URL_KEY: &'static str = "url";
+    const TITLE_KEY: &'static str = "title";
+    const DESCRIPTION_KEY: &'static str = "description";
+    const SITE_NAME_KEY: &'static str = "siteName";
+    /// Prefix of the parameters of the remote attachment of the image
+    const IMAGE_KEY_PREFIX: &'static str = "image.";
+    /// Key of the image URL, which remote attachments carry as their content
+    const IMAGE_URL_KEY: &'static str = "image.url";
+}
+
+impl ContentCodec<TextWithLinkPreview> for LinkPreviewCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: LinkPreviewCodec::AUTHORITY_ID.to_string(),
+            type_id: LinkPreviewCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(data: TextWithLinkPreview) -> Result<EncodedContent, CodecError> {
+        if !data.text.contains(&data.preview.url) {
+            return Err(CodecError::Encode(
+                "link preview url isn't in the text".to_string(),
+            ));
+        }
+        let preview = data.preview;
+        let mut parameters = HashMap::from([(LinkPreviewCodec::URL_KEY.to_string(), preview.url)]);
+        for (key, value) in [
+            (LinkPreviewCodec::TITLE_KEY, preview.title),
+            (LinkPreviewCodec::DESCRIPTION_KEY, preview.description),
+            (LinkPreviewCodec::SITE_NAME_KEY, preview.site_name),
+        ] {
+            if let Some(value) = value {
+                parameters.insert(key.to_string(), value);
+            }
+        }
+        if let Some(image) = preview.image {
+            let image = RemoteAttachmentCodec::encode(image)?;
+            parameters.extend(image.parameters.into_iter().map(|(key, value)| {
+                (
+                    format!("{}{key}", LinkPreviewCodec::IMAGE_KEY_PREFIX),
+                    value,
+                )
+            }));
+            let url =
+                String::from_utf8(image.content).map_err(|e| CodecError::Encode(e.to_string()))?;
+            parameters.insert(LinkPreviewCodec::IMAGE_URL_KEY.to_string(), url);
+        }
+
+        Ok(EncodedContent {
+            r#type: Some(LinkPreviewCodec::content_type()),
+            parameters,
+            fallback: Some(data.text.clone()),
+            compression: None,
+            content: data.text.into_bytes(),
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<TextWithLinkPreview, CodecError> {
+        let parameter = |key: &str| content.parameters.get(key).cloned();
+        let url = parameter(LinkPreviewCodec::URL_KEY)
+            .ok_or_else(|| CodecError::Decode("link preview has no url".to_string()))?;
+        let image = match parameter(LinkPreviewCodec::IMAGE_URL_KEY) {
+            Some(image_url) => {
+                let parameters = content
+                    .parameters
+                    .iter()
+                    .filter(|(key, _)| key.as_str() != LinkPreviewCodec::IMAGE_URL_KEY)
+                    .filter_map(|(key, value)| {
+                        key.strip_prefix(LinkPreviewCodec::IMAGE_KEY_PREFIX)
+                            .map(|key| (key.to_string(), value.clone()))
+                    })
+                    .collect();
+                Some(RemoteAttachmentCodec::decode(EncodedContent {
+                    r#type: Some(RemoteAttachmentCodec::content_type()),
+                    parameters,
+                    fallback: None,
+                    compression: None,
+                    content: image_url.into_bytes(),
+                })?)
+            }
+            None => None,
+        };
+        let preview = LinkPreview {
+            url,
+            title: parameter(LinkPreviewCodec::TITLE_KEY),
+            description: parameter(LinkPreviewCodec::DESCRIPTION_KEY),
+            site_name: parameter(LinkPreviewCodec::SITE_NAME_KEY),
+            image,
+        };
+        let text =
+            String::from_utf8(content.content).map_err(|e| CodecError::Decode(e.to_string()))?;
+        // a preview of a link the text doesn't show could pass a page off as another
+        if !text.contains(&preview.url) {
+            return Err(CodecError::Decode(
+                "link preview url isn't in the text".to_string(),
+            ));
+        }
+
+        Ok(TextWithLinkPreview { text, preview })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let message = TextWithLinkPreview {
+            text: "have you seen https://example.com/cats".to_string(),
+            preview: LinkPreview {
+                url: "https://example.com/cats".to_string(),
+                title: Some("Cats".to_string()),
+                description: None,
+                site_name: Some("Example".to_string()),
+                image: Some(RemoteAttachment {
+                    url: "https://cdn.example.com/abc".to_string(),
+                    content_digest: "0a0b".to_string(),
+                    secret: vec![1, 2],
+                    salt: vec![3, 4],
+                    nonce: vec![5, 6],
+                    scheme: "https://".to_string(),
+                    content_length: Some(100),
+                    filename: Some("cats.png".to_string()),
+                }),
+            },
+        };
+        let encoded = LinkPreviewCodec::encode(message.clone()).unwrap();
+        assert_eq!(encoded.r#type.as_ref().unwrap().type_id, "linkPreview");
+        // clients without the codec show the text
+        assert_eq!(encoded.fallback.as_deref(), Some(message.text.as_str()));
+        assert_eq!(LinkPreviewCodec::decode(encoded).unwrap(), message);
+
+        let without_image = TextWithLinkPreview {
+            text: "https://example.com".to_string(),
+            preview: LinkPreview {
+                url: "https://example.com".to_string(),
+                ..Default::default()
+            },
+        };
+        let encoded = LinkPreviewCodec::encode(without_image.clone()).unwrap();
+        assert_eq!(LinkPreviewCodec::decode(encoded).unwrap(), without_image);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_preview_of_another_url_is_rejected() {
+        let mismatched = TextWithLinkPreview {
+            text: "log in at https://bank.example.com".to_string(),
+            preview: LinkPreview {
+                url: "https://phishing.example.com".to_string(),
+                ..Default::default()
+            },
+        };
+        assert!(LinkPreviewCodec::encode(mismatched.clone()).is_err());
+
+        let mut encoded = LinkPreviewCodec::encode(TextWithLinkPreview {
+            text: "https://phishing.example.com".to_string(),
+            ..mismatched
+        })
+        .unwrap();
+        encoded.content = b"log in at https://bank.example.com".to_vec();
+        assert!(LinkPreviewCodec::decode(encoded).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{CodecError, ContentCodec};
+
+/// A question for the members of a group, with a fixed list of options to vote for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Poll {
+    pub question: String,
+    pub options: Vec<String>,
+    pub settings: PollSettings,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PollSettings {
+    /// Voters can pick more than one option
+    pub multiple_choice: bool,
+    /// Votes sent after this time aren't counted
+    pub closes_at_ns: Option<i64>,
+}
+
+pub struct PollCodec {}
+
+impl PollCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
+    pub const TYPE_ID: &'static str = "poll";
+    const OPTION_KEY_PREFIX: &'static str = "option.";
+    const MULTIPLE_CHOICE_KEY: &'static str = "multipleChoice";
+    const CLOSES_AT_KEY: &'static str = "closesAtNs";
+}
+
+impl ContentCodec<Poll> for PollCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: PollCodec::AUTHORITY_ID.to_string(),
+            type_id: PollCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(data: Poll) -> Result<EncodedContent, CodecError> {
+        if data.options.is_empty() {
+            return Err(CodecError::Encode("poll has no options".to_string()));
+        }
+        let mut parameters: HashMap<String, String> = data
+            .options
+            .into_iter()
+            .enumerate()
+            .map(|(index, option)| (format!("{}{index}", PollCodec::OPTION_KEY_PREFIX), option))
+            .collect();
+        parameters.insert(
+            PollCodec::MULTIPLE_CHOICE_KEY.to_string(),
+            data.settings.multiple_choice.to_string(),
+        );
+        if let Some(closes_at_ns) = data.settings.closes_at_ns {
+            parameters.insert(
+                PollCodec::CLOSES_AT_KEY.to_string(),
+                closes_at_ns.to_string(),
+            );
+        }
+
+        Ok(EncodedContent {
+            r#type: Some(PollCodec::content_type()),
+            parameters,
+            fallback: None,
+            compression: None,
+            content: data.question.into_bytes(),
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<Poll, CodecError> {
+        let options: Vec<String> = (0..)
+            .map_while(|index| {
+                content
+                    .parameters
+                    .get(&format!("{}{index}", PollCodec::OPTION_KEY_PREFIX))
+                    .cloned()
+            })
+            .collect();
+        if options.is_empty() {
+            return Err(CodecError::Decode("poll has no options".to_string()));
+        }
+        let multiple_choice = content
+            .parameters
+            .get(PollCodec::MULTIPLE_CHOICE_KEY)
+            .is_some_and(|multiple_choice| multiple_choice == "true");
+        let closes_at_ns = content
+            .parameters
+            .get(PollCodec::CLOSES_AT_KEY)
+            .map(|closes_at_ns| closes_at_ns.parse::<i64>())
+            .transpose()
+            .map_err(|e| CodecError::Decode(e.to_string()))?;
+        let question =
+            String::from_utf8(content.content).map_err(|e| CodecError::Decode(e.to_string()))?;
+
+        Ok(Poll {
+            question,
+            options,
+            settings: PollSettings {
+                multiple_choice,
+                closes_at_ns,
+            },
+        })
+    }
+}
+
+/// A vote in a poll. A later vote from the same inbox replaces its earlier one, and a vote for
+/// no options retracts it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PollVote {
+    /// Hex encoded id of the poll message
+    pub reference: String,
+    /// Indices of the chosen options
+    pub options: Vec<u32>,
+}
+
+pub struct PollVoteCodec {}
+
+impl PollVoteCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
+    pub const TYPE_ID: &'static str = "pollVote";
+    const REFERENCE_KEY: &'static str = "reference";
+}
+
+impl ContentCodec<PollVote> for PollVoteCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: PollVoteCodec::AUTHORITY_ID.to_string(),
+            type_id: PollVoteCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(data: PollVote) -> Result<EncodedContent, CodecError> {
+        let options = data
+            .options
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+
+        Ok(EncodedContent {
+            r#type: Some(PollVoteCodec::content_type()),
+            parameters: HashMap::from([(PollVoteCodec::REFERENCE_KEY.to_string(), data.reference)]),
+            fallback: None,
+            compression: None,
+            content: options.into_bytes(),
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<PollVote, CodecError> {
+        let reference = content
+            .parameters
+            .get(PollVoteCodec::REFERENCE_KEY)
+            .ok_or_else(|| CodecError::Decode("poll vote has no reference".to_string()))?
+            .clone();
+        let options =
+            String::from_utf8(content.content).map_err(|e| CodecError::Decode(e.to_string()))?;
+        let options = options
+            .split(',')
+            .filter(|option| !option.is_empty())
+            .map(str::parse::<u32>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| CodecError::Decode(e.to_string()))?;
+
+        Ok(PollVote { reference, options })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let poll = Poll {
+            question: "Where should we eat?".to_string(),
+            options: vec![
+                "Tacos".to_string(),
+                "Ramen".to_string(),
+                "Pizza".to_string(),
+            ],
+            settings: PollSettings {
+                multiple_choice: true,
+                closes_at_ns: Some(1_000),
+            },
+        };
+        let encoded = PollCodec::encode(poll.clone()).unwrap();
+        assert_eq!(encoded.r#type.as_ref().unwrap().type_id, "poll");
+        assert_eq!(PollCodec::decode(encoded).unwrap(), poll);
+
+        let vote = PollVote {
+            reference: "0a0b".to_string(),
+            options: vec![0, 2],
+        };
+        let encoded = PollVoteCodec::encode(vote.clone()).unwrap();
+        assert_eq!(encoded.r#type.as_ref().unwrap().type_id, "pollVote");
+        assert_eq!(PollVoteCodec::decode(encoded).unwrap(), vote);
+
+        let retraction = PollVote {
+            options: vec![],
+            ..vote
+        };
+        let encoded = PollVoteCodec::encode(retraction.clone()).unwrap();
+        assert_eq!(PollVoteCodec::decode(encoded).unwrap(), retraction);
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use super::{CodecError, ContentCodec};
+
+/// What an inbox says about itself. Every claim is optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileClaims {
+    pub display_name: Option<String>,
+    /// Where the avatar can be fetched from, e.g. an https or ipfs URL
+    pub avatar_url: Option<String>,
+    pub ens_name: Option<String>,
+}
+
+/// [`ProfileClaims`] signed by one of the inbox's installations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedProfile {
+    pub inbox_id: String,
+    /// The public key of the installation that signed the claims
+    pub installation_id: Vec<u8>,
+    pub claims: ProfileClaims,
+    pub signed_at_ns: i64,
+    pub signature: Vec<u8>,
+}
+
+impl SignedProfile {
+    /// The text the installation signs. Every field is included so none can be changed or
+    /// replayed for another inbox without invalidating the signature.
+    pub fn signature_text(
+        inbox_id: &str,
+        installation_id: &[u8],
+        claims: &ProfileClaims,
+        signed_at_ns: i64,
+    ) -> String {
+        let claim = |value: &Option<String>| value.as_deref().unwrap_or_default();
+        format!(
+            "XMTP : Profile\n\nInbox ID: {inbox_id}\nInstallation ID: {}\nDisplay name: {}\nAvatar URL: {}\nENS name: {}\nSigned at: {signed_at_ns}",
+            hex::encode(installation_id),
+            claim(&claims.display_name),
+            claim(&claims.avatar_url),
+            claim(&claims.ens_name),
+        )
+    }
+}
+
+pub struct ProfileCodec {}
+
+impl ProfileCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
+    pub const TYPE_ID: &'static str = "profile";
+    const INBOX_ID_KEY: &'static str = "inboxId";
+    const INSTALLATION_ID_KEY: &'static str = "installationId";
+    const DISPLAY_NAME_KEY: &'static str = "displayName";
+    const AVATAR_URL_KEY: &'static str = "avatarUrl";
+    const ENS_NAME_KEY: &'static str = "ensName";
+    const SIGNED_AT_NS_KEY: &'static str = "signedAtNs";
+    const SIGNATURE_KEY: &'static str = "signature";
+
+    fn required<'a>(
+        parameters: &'a HashMap<String, String>,
+        key: &str,
+    ) -> Result<&'a String, CodecError> {
+        parameters
+            .get(key)
+            .ok_or_else(|| CodecError::Decode(format!("profile is missing {key}")))
+    }
+
+    fn required_hex(
+        parameters: &HashMap<String, String>,
+        key: &str,
+    ) -> Result<Vec<u8>, CodecError> {
+        hex::decode(Self::required(parameters, key)?)
+            .map_err(|e| CodecError::Decode(format!("invalid profile {key}: {e}")))
+    }
+}
+
+impl ContentCodec<SignedProfile> for ProfileCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: ProfileCodec::AUTHORITY_ID.to_string(),
+            type_id: ProfileCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(data: SignedProfile) -> Result<EncodedContent, CodecError> {
+        let mut parameters = HashMap::from([
+            (ProfileCodec::INBOX_ID_KEY.to_string(), data.inbox_id),
+            (
+                ProfileCodec::INSTALLATION_ID_KEY.to_string(),
+                hex::encode(data.installation_id),
+            ),
+            (
+                ProfileCodec::SIGNED_AT_NS_KEY.to_string(),
+                data.signed_at_ns.to_string(),
+            ),
+            (
+                ProfileCodec::SIGNATURE_KEY.to_string(),
+                hex::encode(data.signature),
+            ),
+        ]);
+        // Claims that aren't made are left out rather than sent empty
+        parameters.extend(
+            [
+                (ProfileCodec::DISPLAY_NAME_KEY, data.claims.display_name),
+                (ProfileCodec::AVATAR_URL_KEY, data.claims.avatar_url),
+                (ProfileCodec::ENS_NAME_KEY, data.claims.ens_name),
+            ]
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.to_string(), value?))),
+        );
+
+        Ok(EncodedContent {
+            r#type: Some(ProfileCodec::content_type()),
+            parameters,
+            fallback: None,
+            compression: None,
+            content: vec![],
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<SignedProfile, CodecError> {
+        let parameters = &content.parameters;
+        Ok(SignedProfile {
+            inbox_id: ProfileCodec::required(parameters, ProfileCodec::INBOX_ID_KEY)?.clone(),
+            installation_id: ProfileCodec::required_hex(
+                parameters,
+                ProfileCodec::INSTALLATION_ID_KEY,
+            )?,
+            claims: ProfileClaims {
+                display_name: parameters.get(ProfileCodec::DISPLAY_NAME_KEY).cloned(),
+                avatar_url: parameters.get(ProfileCodec::AVATAR_URL_KEY).cloned(),
+                ens_name: parameters.get(ProfileCodec::ENS_NAME_KEY).cloned(),
+            },
+            signed_at_ns: ProfileCodec::required(parameters, ProfileCodec::SIGNED_AT_NS_KEY)?
+                .parse()
+                .map_err(|_| CodecError::Decode("invalid profile signedAtNs".to_string()))?,
+            signature: ProfileCodec::required_hex(parameters, ProfileCodec::SIGNATURE_KEY)?,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let profile = SignedProfile {
+            inbox_id: "alix".to_string(),
+            installation_id: vec![1, 2, 3],
+            claims: ProfileClaims {
+                display_name: Some("Alix".to_string()),
+                avatar_url: None,
+                ens_name: Some("alix.eth".to_string()),
+            },
+            signed_at_ns: 42,
+            signature: vec![4, 5, 6],
+        };
+
+        let encoded = ProfileCodec::encode(profile.clone()).unwrap();
+        assert_eq!(encoded.r#type.as_ref().unwrap().type_id, "profile");
+        assert!(!encoded.parameters.contains_key("avatarUrl"));
+        assert_eq!(ProfileCodec::decode(encoded).unwrap(), profile);
+    }
+}
@@ -1,6 +1,87 @@
+use std::collections::HashMap;
+
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{CodecError, ContentCodec};
+
+/// Marks the messages of a conversation as read by the sender
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadReceipt {
+    /// Hex encoded id of the last message read. Legacy receipts have none and mark everything
+    /// sent before the receipt as read.
+    pub reference: Option<String>,
+}
+
 pub struct ReadReceiptCodec {}
 
 /// Legacy content type id at https://github.com/xmtp/xmtp-js/blob/main/content-types/content-type-read-receipt/src/ReadReceipt.ts
 impl ReadReceiptCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
     pub const TYPE_ID: &'static str = "readReceipt";
+    const REFERENCE_KEY: &'static str = "reference";
+}
+
+impl ContentCodec<ReadReceipt> for ReadReceiptCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: ReadReceiptCodec::AUTHORITY_ID.to_string(),
+            type_id: ReadReceiptCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(data: ReadReceipt) -> Result<EncodedContent, CodecError> {
+        let parameters = data
+            .reference
+            .map(|reference| (ReadReceiptCodec::REFERENCE_KEY.to_string(), reference))
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+
+        Ok(EncodedContent {
+            r#type: Some(ReadReceiptCodec::content_type()),
+            parameters,
+            fallback: None,
+            compression: None,
+            content: vec![],
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<ReadReceipt, CodecError> {
+        Ok(ReadReceipt {
+            reference: content
+                .parameters
+                .get(ReadReceiptCodec::REFERENCE_KEY)
+                .cloned(),
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let receipt = ReadReceipt {
+            reference: Some("0a0b".to_string()),
+        };
+        let encoded = ReadReceiptCodec::encode(receipt.clone()).unwrap();
+        assert_eq!(encoded.r#type.as_ref().unwrap().type_id, "readReceipt");
+        assert_eq!(ReadReceiptCodec::decode(encoded).unwrap(), receipt);
+
+        // legacy receipts are empty
+        let legacy = EncodedContent {
+            r#type: Some(ReadReceiptCodec::content_type()),
+            ..Default::default()
+        };
+        assert_eq!(
+            ReadReceiptCodec::decode(legacy).unwrap(),
+            ReadReceipt::default()
+        );
+    }
 }
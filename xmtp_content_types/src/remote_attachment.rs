@@ -1,6 +1,158 @@
+use std::collections::HashMap;
+
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{CodecError, ContentCodec};
+
+/// An encrypted attachment stored outside the network. The message carries where to download it
+/// from and the key material to decrypt it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteAttachment {
+    pub url: String,
+    /// Hex encoded SHA-256 digest of the encrypted payload
+    pub content_digest: String,
+    pub secret: Vec<u8>,
+    pub salt: Vec<u8>,
+    pub nonce: Vec<u8>,
+    /// The URL scheme, e.g. `https://`
+    pub scheme: String,
+    /// Size of the encrypted payload, if known
+    pub content_length: Option<u64>,
+    pub filename: Option<String>,
+}
+
 pub struct RemoteAttachmentCodec {}
 
 //. Legacy content type id at https://github.com/xmtp/xmtp-js/blob/main/content-types/content-type-remote-attachment/src/RemoteAttachment.ts
 impl RemoteAttachmentCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
     pub const TYPE_ID: &'static str = "remoteStaticAttachment";
+    const CONTENT_DIGEST_KEY: &'static str = "contentDigest";
+    const SECRET_KEY: &'static str = "secret";
+    const SALT_KEY: &'static str = "salt";
+    const NONCE_KEY: &'static str = "nonce";
+    const SCHEME_KEY: &'static str = "scheme";
+    const CONTENT_LENGTH_KEY: &'static str = "contentLength";
+    const FILENAME_KEY: &'static str = "filename";
+}
+
+impl ContentCodec<RemoteAttachment> for RemoteAttachmentCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: RemoteAttachmentCodec::AUTHORITY_ID.to_string(),
+            type_id: RemoteAttachmentCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(data: RemoteAttachment) -> Result<EncodedContent, CodecError> {
+        let mut parameters = HashMap::from([
+            (
+                RemoteAttachmentCodec::CONTENT_DIGEST_KEY.to_string(),
+                data.content_digest,
+            ),
+            (
+                RemoteAttachmentCodec::SECRET_KEY.to_string(),
+                hex::encode(data.secret),
+            ),
+            (
+                RemoteAttachmentCodec::SALT_KEY.to_string(),
+                hex::encode(data.salt),
+            ),
+            (
+                RemoteAttachmentCodec::NONCE_KEY.to_string(),
+                hex::encode(data.nonce),
+            ),
+            (RemoteAttachmentCodec::SCHEME_KEY.to_string(), data.scheme),
+        ]);
+        if let Some(content_length) = data.content_length {
+            parameters.insert(
+                RemoteAttachmentCodec::CONTENT_LENGTH_KEY.to_string(),
+                content_length.to_string(),
+            );
+        }
+        if let Some(filename) = data.filename {
+            parameters.insert(RemoteAttachmentCodec::FILENAME_KEY.to_string(), filename);
+        }
+
+        Ok(EncodedContent {
+            r#type: Some(RemoteAttachmentCodec::content_type()),
+            parameters,
+            fallback: None,
+            compression: None,
+            content: data.url.into_bytes(),
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<RemoteAttachment, CodecError> {
+        let parameter = |key: &str| {
+            content
+                .parameters
+                .get(key)
+                .cloned()
+                .ok_or_else(|| CodecError::Decode(format!("remote attachment has no {key}")))
+        };
+        let bytes_parameter =
+            |key: &str| hex::decode(parameter(key)?).map_err(|e| CodecError::Decode(e.to_string()));
+        let content_digest = parameter(RemoteAttachmentCodec::CONTENT_DIGEST_KEY)?;
+        let secret = bytes_parameter(RemoteAttachmentCodec::SECRET_KEY)?;
+        let salt = bytes_parameter(RemoteAttachmentCodec::SALT_KEY)?;
+        let nonce = bytes_parameter(RemoteAttachmentCodec::NONCE_KEY)?;
+        let scheme = parameter(RemoteAttachmentCodec::SCHEME_KEY)?;
+        let content_length = content
+            .parameters
+            .get(RemoteAttachmentCodec::CONTENT_LENGTH_KEY)
+            .map(|content_length| content_length.parse::<u64>())
+            .transpose()
+            .map_err(|e| CodecError::Decode(e.to_string()))?;
+        let filename = content
+            .parameters
+            .get(RemoteAttachmentCodec::FILENAME_KEY)
+            .cloned();
+        let url =
+            String::from_utf8(content.content).map_err(|e| CodecError::Decode(e.to_string()))?;
+
+        Ok(RemoteAttachment {
+            url,
+            content_digest,
+            secret,
+            salt,
+            nonce,
+            scheme,
+            content_length,
+            filename,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let attachment = RemoteAttachment {
+            url: "https://example.com/abc".to_string(),
+            content_digest: "0a0b".to_string(),
+            secret: vec![1; 32],
+            salt: vec![2; 32],
+            nonce: vec![3; 12],
+            scheme: "https://".to_string(),
+            content_length: Some(1024),
+            filename: Some("cat.png".to_string()),
+        };
+        let encoded = RemoteAttachmentCodec::encode(attachment.clone()).unwrap();
+        assert_eq!(
+            encoded.r#type.as_ref().unwrap().type_id,
+            "remoteStaticAttachment"
+        );
+        // clients that only read the URL, like thumbnail warming, find it in the content
+        assert_eq!(encoded.content, b"https://example.com/abc");
+        assert_eq!(RemoteAttachmentCodec::decode(encoded).unwrap(), attachment);
+    }
 }
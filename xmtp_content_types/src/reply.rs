@@ -1,6 +1,113 @@
+use std::collections::HashMap;
+
+use prost::Message;
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{capabilities::content_type_key, CodecError, ContentCodec};
+
+/// A message sent in reply to an earlier message of the conversation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reply {
+    /// Hex encoded id of the message replied to
+    pub reference: String,
+    /// Inbox id of the sender of the message replied to
+    pub reference_inbox_id: Option<String>,
+    /// The content of the reply, of any content type
+    pub content: EncodedContent,
+}
+
 pub struct ReplyCodec {}
 
 /// Legacy content type id at https://github.com/xmtp/xmtp-js/blob/main/content-types/content-type-reply/src/Reply.ts
 impl ReplyCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
     pub const TYPE_ID: &'static str = "reply";
+    pub const REFERENCE_KEY: &'static str = "reference";
+    const REFERENCE_INBOX_ID_KEY: &'static str = "referenceInboxId";
+    const CONTENT_TYPE_KEY: &'static str = "contentType";
+}
+
+impl ContentCodec<Reply> for ReplyCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: ReplyCodec::AUTHORITY_ID.to_string(),
+            type_id: ReplyCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(data: Reply) -> Result<EncodedContent, CodecError> {
+        let inner_type =
+            data.content.r#type.as_ref().ok_or_else(|| {
+                CodecError::Encode("reply content has no content type".to_string())
+            })?;
+        let mut parameters = HashMap::from([
+            (ReplyCodec::REFERENCE_KEY.to_string(), data.reference),
+            (
+                ReplyCodec::CONTENT_TYPE_KEY.to_string(),
+                content_type_key(inner_type),
+            ),
+        ]);
+        if let Some(reference_inbox_id) = data.reference_inbox_id {
+            parameters.insert(
+                ReplyCodec::REFERENCE_INBOX_ID_KEY.to_string(),
+                reference_inbox_id,
+            );
+        }
+
+        Ok(EncodedContent {
+            r#type: Some(ReplyCodec::content_type()),
+            parameters,
+            fallback: None,
+            compression: None,
+            content: data.content.encode_to_vec(),
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<Reply, CodecError> {
+        let reference = content
+            .parameters
+            .get(ReplyCodec::REFERENCE_KEY)
+            .ok_or_else(|| CodecError::Decode("reply has no reference".to_string()))?
+            .clone();
+        let inner = EncodedContent::decode(content.content.as_slice())
+            .map_err(|e| CodecError::Decode(e.to_string()))?;
+
+        Ok(Reply {
+            reference,
+            reference_inbox_id: content
+                .parameters
+                .get(ReplyCodec::REFERENCE_INBOX_ID_KEY)
+                .cloned(),
+            content: inner,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::text::TextCodec;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let reply = Reply {
+            reference: "0a0b".to_string(),
+            reference_inbox_id: Some("alix".to_string()),
+            content: TextCodec::encode("hi back".to_string()).unwrap(),
+        };
+
+        let encoded = ReplyCodec::encode(reply.clone()).unwrap();
+        assert_eq!(encoded.r#type.as_ref().unwrap().type_id, "reply");
+        assert_eq!(encoded.parameters["contentType"], "xmtp.org/text:1.0");
+
+        let decoded = ReplyCodec::decode(encoded).unwrap();
+        assert_eq!(decoded, reply);
+        assert_eq!(TextCodec::decode(decoded.content).unwrap(), "hi back");
+    }
 }
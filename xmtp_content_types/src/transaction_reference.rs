@@ -1,6 +1,134 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use crate::{CodecError, ContentCodec};
+
+/// A reference to an onchain transaction, e.g. a payment sent to another member
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionReference {
+    /// The namespace of the network, e.g. `eip155`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// The id of the network, e.g. the chain id. Legacy senders send it as a number.
+    #[serde(deserialize_with = "string_or_number")]
+    pub network_id: String,
+    /// The transaction hash
+    pub reference: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<TransactionMetadata>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionMetadata {
+    pub transaction_type: String,
+    pub currency: String,
+    pub amount: f64,
+    pub decimals: u32,
+    pub from_address: String,
+    pub to_address: String,
+}
+
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(serde_json::Number),
+    }
+    Ok(match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(value) => value,
+        StringOrNumber::Number(value) => value.to_string(),
+    })
+}
+
 pub struct TransactionReferenceCodec {}
 
 /// Legacy content type id at https://github.com/xmtp/xmtp-js/blob/main/content-types/content-type-transaction-reference/src/TransactionReference.ts
 impl TransactionReferenceCodec {
+    const AUTHORITY_ID: &'static str = "xmtp.org";
     pub const TYPE_ID: &'static str = "transactionReference";
 }
+
+impl ContentCodec<TransactionReference> for TransactionReferenceCodec {
+    fn content_type() -> ContentTypeId {
+        ContentTypeId {
+            authority_id: TransactionReferenceCodec::AUTHORITY_ID.to_string(),
+            type_id: TransactionReferenceCodec::TYPE_ID.to_string(),
+            version_major: 1,
+            version_minor: 0,
+        }
+    }
+
+    fn encode(data: TransactionReference) -> Result<EncodedContent, CodecError> {
+        let content = serde_json::to_vec(&data).map_err(|e| CodecError::Encode(e.to_string()))?;
+
+        Ok(EncodedContent {
+            r#type: Some(TransactionReferenceCodec::content_type()),
+            parameters: Default::default(),
+            fallback: Some(format!(
+                "[Crypto transaction] Use a blockchain explorer to learn more using the \
+                 transaction hash: {}",
+                data.reference
+            )),
+            compression: None,
+            content,
+        })
+    }
+
+    fn decode(content: EncodedContent) -> Result<TransactionReference, CodecError> {
+        serde_json::from_slice(&content.content).map_err(|e| CodecError::Decode(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_encode_decode() {
+        let transaction = TransactionReference {
+            namespace: Some("eip155".to_string()),
+            network_id: "8453".to_string(),
+            reference: "0xabc".to_string(),
+            metadata: Some(TransactionMetadata {
+                transaction_type: "transfer".to_string(),
+                currency: "USDC".to_string(),
+                amount: 1.5,
+                decimals: 6,
+                from_address: "0x1".to_string(),
+                to_address: "0x2".to_string(),
+            }),
+        };
+        let encoded = TransactionReferenceCodec::encode(transaction.clone()).unwrap();
+        assert_eq!(
+            encoded.r#type.as_ref().unwrap().type_id,
+            "transactionReference"
+        );
+        assert!(encoded.fallback.as_ref().unwrap().contains("0xabc"));
+        assert_eq!(
+            TransactionReferenceCodec::decode(encoded).unwrap(),
+            transaction
+        );
+
+        // legacy senders send the network id as a number
+        let legacy = EncodedContent {
+            r#type: Some(TransactionReferenceCodec::content_type()),
+            content: br#"{"networkId":1,"reference":"0xdef"}"#.to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(
+            TransactionReferenceCodec::decode(legacy).unwrap(),
+            TransactionReference {
+                network_id: "1".to_string(),
+                reference: "0xdef".to_string(),
+                ..Default::default()
+            }
+        );
+    }
+}
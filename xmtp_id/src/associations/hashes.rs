@@ -20,12 +20,130 @@ fn is_valid_address(account_address: &str) -> bool {
 
 /// Generates an inbox ID if the account address is valid.
 pub fn generate_inbox_id(account_address: &str, nonce: &u64) -> Result<String, AssociationError> {
-    if !is_valid_address(account_address) {
-        return Err(AssociationError::InvalidAccountAddress);
+    InboxIdVersion::V1.derive(account_address, nonce)
+}
+
+/// A scheme for deriving an inbox ID from an account address and nonce.
+///
+/// New schemes are added as new versions, so that inbox IDs derived with an older scheme stay
+/// resolvable while clients migrate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum InboxIdVersion {
+    /// The lowercased address followed by the nonce, hashed with SHA256
+    #[default]
+    V1,
+}
+
+impl InboxIdVersion {
+    /// Derives the inbox ID for `account_address` and `nonce` with this scheme
+    pub fn derive(&self, account_address: &str, nonce: &u64) -> Result<String, AssociationError> {
+        if !is_valid_address(account_address) {
+            return Err(AssociationError::InvalidAccountAddress);
+        }
+        match self {
+            Self::V1 => Ok(sha256_string(format!(
+                "{}{}",
+                account_address.to_lowercase(),
+                nonce
+            ))),
+        }
+    }
+}
+
+/// Which inbox ID schemes a client derives new inbox IDs with and which it accepts.
+///
+/// During a migration window clients accept both the old and new schemes, and only switch
+/// `derive` to the new scheme once enough clients accept it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InboxIdCompatibility {
+    pub derive: InboxIdVersion,
+    pub accept: Vec<InboxIdVersion>,
+}
+
+impl Default for InboxIdCompatibility {
+    fn default() -> Self {
+        Self {
+            derive: InboxIdVersion::V1,
+            accept: vec![InboxIdVersion::V1],
+        }
+    }
+}
+
+impl InboxIdCompatibility {
+    /// The inbox IDs `account_address` and `nonce` resolve to, the one derived with
+    /// [`Self::derive`] first
+    pub fn candidate_inbox_ids(
+        &self,
+        account_address: &str,
+        nonce: &u64,
+    ) -> Result<Vec<String>, AssociationError> {
+        let mut inbox_ids = vec![self.derive.derive(account_address, nonce)?];
+        for version in &self.accept {
+            let inbox_id = version.derive(account_address, nonce)?;
+            if !inbox_ids.contains(&inbox_id) {
+                inbox_ids.push(inbox_id);
+            }
+        }
+        Ok(inbox_ids)
+    }
+
+    /// Whether `inbox_id` is one of the forms `account_address` and `nonce` resolve to
+    pub fn accepts(
+        &self,
+        inbox_id: &str,
+        account_address: &str,
+        nonce: &u64,
+    ) -> Result<bool, AssociationError> {
+        Ok(self
+            .candidate_inbox_ids(account_address, nonce)?
+            .iter()
+            .any(|candidate| candidate == inbox_id))
+    }
+
+    /// Whether `a` and `b` are the same inbox, possibly derived with different schemes
+    pub fn same_inbox(
+        &self,
+        a: &str,
+        b: &str,
+        account_address: &str,
+        nonce: &u64,
+    ) -> Result<bool, AssociationError> {
+        Ok(a == b
+            || (self.accepts(a, account_address, nonce)?
+                && self.accepts(b, account_address, nonce)?))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn default_compatibility_accepts_generated_inbox_id() {
+        let account_address = "0x1234567890abcdef1234567890abcdef12345678";
+        let compatibility = InboxIdCompatibility::default();
+        let inbox_id = generate_inbox_id(account_address, &0).unwrap();
+
+        assert_eq!(
+            compatibility
+                .candidate_inbox_ids(account_address, &0)
+                .unwrap(),
+            vec![inbox_id.clone()]
+        );
+        assert!(compatibility
+            .accepts(&inbox_id, account_address, &0)
+            .unwrap());
+        assert!(!compatibility
+            .accepts(&inbox_id, account_address, &1)
+            .unwrap());
+        assert!(!compatibility
+            .same_inbox(&inbox_id, "other", account_address, &0)
+            .unwrap());
+        assert!(compatibility.accepts(&inbox_id, "0x1234", &0).is_err());
     }
-    Ok(sha256_string(format!(
-        "{}{}",
-        account_address.to_lowercase(),
-        nonce
-    )))
 }
@@ -0,0 +1,2 @@
+DROP INDEX idx_attachment_uploads_state;
+DROP TABLE attachment_uploads;
@@ -0,0 +1,17 @@
+CREATE TABLE attachment_uploads (
+    "id" BINARY PRIMARY KEY NOT NULL,
+    "group_id" BINARY NOT NULL,
+    "content_digest" BINARY NOT NULL,
+    "total_size" BIGINT NOT NULL,
+    "chunk_size" BIGINT NOT NULL,
+    "bytes_uploaded" BIGINT NOT NULL DEFAULT 0,
+    "upload_session" TEXT,
+    "url" TEXT,
+    "state" INTEGER NOT NULL,
+    "message_id" BINARY,
+    "created_at_ns" BIGINT NOT NULL,
+    "updated_at_ns" BIGINT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id)
+);
+
+CREATE INDEX idx_attachment_uploads_state ON attachment_uploads(state);
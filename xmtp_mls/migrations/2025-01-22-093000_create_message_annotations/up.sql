@@ -0,0 +1,9 @@
+CREATE TABLE message_annotations (
+    "message_id" BINARY NOT NULL,
+    "processor_id" TEXT NOT NULL,
+    "key" TEXT NOT NULL,
+    "value" TEXT NOT NULL,
+    "created_at_ns" BIGINT NOT NULL,
+    PRIMARY KEY (message_id, processor_id, key),
+    FOREIGN KEY (message_id) REFERENCES group_messages(id) ON DELETE CASCADE
+);
@@ -0,0 +1,11 @@
+CREATE TABLE installation_capabilities (
+    -- The installation that advertised the capabilities
+    "installation_id" BINARY PRIMARY KEY NOT NULL,
+    "inbox_id" TEXT NOT NULL,
+    -- JSON array of content type ids
+    "content_types" TEXT NOT NULL,
+    -- JSON array of feature names
+    "features" TEXT NOT NULL,
+    -- When the advertisement was sent, only newer advertisements replace a stored one
+    "advertised_at_ns" BIGINT NOT NULL
+);
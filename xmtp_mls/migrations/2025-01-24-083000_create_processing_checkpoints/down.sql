@@ -0,0 +1 @@
+DROP TABLE processing_checkpoints;
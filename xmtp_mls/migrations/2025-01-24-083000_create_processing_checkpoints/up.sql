@@ -0,0 +1,11 @@
+-- A row exists while an envelope is being processed for a group. A row left behind on startup
+-- means processing was interrupted and must be resolved before the group is synced again.
+CREATE TABLE processing_checkpoints (
+    "group_id" BINARY PRIMARY KEY NOT NULL,
+    -- Cursor of the envelope being processed
+    "cursor" BIGINT NOT NULL,
+    -- Timestamp of the envelope being processed, used to find a message it may have produced
+    "envelope_timestamp_ns" BIGINT NOT NULL,
+    "started_at_ns" BIGINT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
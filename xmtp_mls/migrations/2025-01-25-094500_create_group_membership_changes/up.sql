@@ -0,0 +1,11 @@
+-- Inboxes added to and removed from a group, by the epoch the change took effect in.
+-- Used to compute membership deltas without loading the full member list.
+CREATE TABLE group_membership_changes (
+    "group_id" BINARY NOT NULL,
+    "epoch" BIGINT NOT NULL,
+    "inbox_id" TEXT NOT NULL,
+    -- Enum, 1 = Added, 2 = Removed
+    "kind" INTEGER NOT NULL,
+    PRIMARY KEY (group_id, epoch, inbox_id),
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
@@ -0,0 +1 @@
+DROP TABLE reconsent_prompts;
@@ -0,0 +1,7 @@
+-- When each inactive conversation was last flagged for re-consent,
+-- so the same conversation is not flagged again too soon.
+CREATE TABLE reconsent_prompts (
+    "group_id" BINARY PRIMARY KEY NOT NULL,
+    "prompted_at_ns" BIGINT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
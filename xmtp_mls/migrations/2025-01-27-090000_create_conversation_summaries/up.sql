@@ -0,0 +1,15 @@
+-- Summaries of a range of a conversation's messages, generated and stored by apps.
+CREATE TABLE conversation_summaries (
+    "group_id" BINARY NOT NULL,
+    -- Set by the app to tell summaries of different kinds apart
+    "key" TEXT NOT NULL,
+    -- sent_at_ns of the first and last messages summarized
+    "start_ns" BIGINT NOT NULL,
+    "end_ns" BIGINT NOT NULL,
+    "content" BLOB NOT NULL,
+    -- Number of messages in the range when the summary was stored
+    "message_count" BIGINT NOT NULL,
+    "created_at_ns" BIGINT NOT NULL,
+    PRIMARY KEY (group_id, key, start_ns, end_ns),
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
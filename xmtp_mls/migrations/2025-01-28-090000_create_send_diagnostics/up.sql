@@ -0,0 +1,16 @@
+-- Fan-out diagnostics for messages sent by this installation, recorded only while diagnostics
+-- are enabled.
+CREATE TABLE send_diagnostics (
+    "message_id" BINARY PRIMARY KEY NOT NULL,
+    -- Epoch the message was encrypted in
+    "epoch" BIGINT NOT NULL,
+    -- Number of member installations when the message was encrypted
+    "installation_count" INTEGER NOT NULL,
+    "publish_attempts" INTEGER NOT NULL,
+    -- When the API accepted the message
+    "published_at_ns" BIGINT,
+    -- Cursor and server timestamp of the message when it was read back from the network
+    "receipt_cursor" BIGINT,
+    "receipt_timestamp_ns" BIGINT,
+    FOREIGN KEY (message_id) REFERENCES group_messages(id) ON DELETE CASCADE
+);
@@ -0,0 +1,2 @@
+DROP TRIGGER delete_conversation_scratch;
+DROP TABLE conversation_scratch;
@@ -0,0 +1,17 @@
+-- Small per-conversation key-value state kept by apps on this installation only
+-- (e.g. scroll anchors or feature flags). Never synced to other installations.
+CREATE TABLE conversation_scratch (
+    "group_id" BINARY NOT NULL,
+    "key" TEXT NOT NULL,
+    "value" BLOB NOT NULL,
+    "updated_at_ns" BIGINT NOT NULL,
+    PRIMARY KEY (group_id, key),
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+-- Foreign keys aren't enforced on every connection, so clean up explicitly as well
+CREATE TRIGGER delete_conversation_scratch
+AFTER DELETE ON groups
+BEGIN
+    DELETE FROM conversation_scratch WHERE group_id = OLD.id;
+END;
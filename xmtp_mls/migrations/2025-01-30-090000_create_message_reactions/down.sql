@@ -0,0 +1,3 @@
+DROP TRIGGER delete_message_reactions;
+DROP INDEX message_reactions_group_id;
+DROP TABLE message_reactions;
@@ -0,0 +1,21 @@
+-- Current reactions to each message, one row per reacting inbox and emoji.
+-- Maintained while processing reaction messages so clients don't aggregate them themselves.
+CREATE TABLE message_reactions (
+    "message_id" BINARY NOT NULL,
+    "sender_inbox_id" TEXT NOT NULL,
+    "content" TEXT NOT NULL,
+    "group_id" BINARY NOT NULL,
+    "reaction_message_id" BINARY NOT NULL,
+    "reacted_at_ns" BIGINT NOT NULL,
+    PRIMARY KEY (message_id, sender_inbox_id, content),
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+CREATE INDEX message_reactions_group_id ON message_reactions(group_id);
+
+-- Foreign keys aren't enforced on every connection, so clean up explicitly as well
+CREATE TRIGGER delete_message_reactions
+AFTER DELETE ON groups
+BEGIN
+    DELETE FROM message_reactions WHERE group_id = OLD.id;
+END;
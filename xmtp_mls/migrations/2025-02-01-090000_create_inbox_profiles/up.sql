@@ -0,0 +1,10 @@
+-- The most recent verified profile of each inbox, including our own
+CREATE TABLE inbox_profiles (
+    "inbox_id" TEXT PRIMARY KEY NOT NULL,
+    "installation_id" BINARY NOT NULL,
+    "display_name" TEXT,
+    "avatar_url" TEXT,
+    "ens_name" TEXT,
+    "signed_at_ns" BIGINT NOT NULL,
+    "signature" BINARY NOT NULL
+);
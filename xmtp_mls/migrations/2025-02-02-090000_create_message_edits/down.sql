@@ -0,0 +1,2 @@
+DROP TRIGGER delete_message_edits;
+DROP TABLE message_edits;
@@ -0,0 +1,20 @@
+-- Earlier versions of edited messages. The current version stays in group_messages;
+-- version 0 is the content the message was originally sent with.
+CREATE TABLE message_edits (
+    "message_id" BINARY NOT NULL,
+    "version" INTEGER NOT NULL,
+    "group_id" BINARY NOT NULL,
+    "decrypted_message_bytes" BLOB NOT NULL,
+    -- The edit that replaced this version
+    "edit_message_id" BINARY NOT NULL,
+    "replaced_at_ns" BIGINT NOT NULL,
+    PRIMARY KEY (message_id, version),
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+-- Foreign keys aren't enforced on every connection, so clean up explicitly as well
+CREATE TRIGGER delete_message_edits
+AFTER DELETE ON groups
+BEGIN
+    DELETE FROM message_edits WHERE group_id = OLD.id;
+END;
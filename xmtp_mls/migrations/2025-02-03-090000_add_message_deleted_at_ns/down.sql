@@ -0,0 +1 @@
+ALTER TABLE group_messages DROP COLUMN deleted_at_ns;
@@ -0,0 +1,2 @@
+-- Set when a message is deleted by its sender or an admin; the content is erased at the same time
+ALTER TABLE group_messages ADD COLUMN deleted_at_ns BIGINT;
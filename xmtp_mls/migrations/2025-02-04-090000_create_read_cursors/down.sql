@@ -0,0 +1,2 @@
+DROP TRIGGER delete_read_cursors;
+DROP TABLE read_cursors;
@@ -0,0 +1,18 @@
+-- How far each member has read in each group, as announced by their read receipts.
+-- A cursor only moves forward, so receipts processed out of order can't move it back.
+CREATE TABLE read_cursors (
+    "group_id" BINARY NOT NULL,
+    "inbox_id" TEXT NOT NULL,
+    "read_until_ns" BIGINT NOT NULL,
+    "message_id" BINARY,
+    "receipt_message_id" BINARY NOT NULL,
+    PRIMARY KEY (group_id, inbox_id),
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+-- Foreign keys aren't enforced on every connection, so clean up explicitly as well
+CREATE TRIGGER delete_read_cursors
+AFTER DELETE ON groups
+BEGIN
+    DELETE FROM read_cursors WHERE group_id = OLD.id;
+END;
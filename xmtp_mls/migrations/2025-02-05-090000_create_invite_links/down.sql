@@ -0,0 +1,4 @@
+DROP TRIGGER delete_invite_redemptions;
+DROP TRIGGER delete_invite_links;
+DROP TABLE invite_redemptions;
+DROP TABLE invite_links;
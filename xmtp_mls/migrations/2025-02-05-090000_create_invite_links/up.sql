@@ -0,0 +1,35 @@
+-- Invite links created by this installation, and the redemptions received for them that are
+-- waiting for the redeeming inbox to be added.
+CREATE TABLE invite_links (
+    "id" BINARY PRIMARY KEY NOT NULL,
+    "group_id" BINARY NOT NULL,
+    "created_at_ns" BIGINT NOT NULL,
+    "expires_at_ns" BIGINT,
+    "max_uses" INTEGER,
+    "uses" INTEGER NOT NULL DEFAULT 0,
+    "revoked_at_ns" BIGINT,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+CREATE INDEX invite_links_group_id_idx ON invite_links(group_id);
+
+CREATE TABLE invite_redemptions (
+    "message_id" BINARY PRIMARY KEY NOT NULL,
+    "link_id" BINARY NOT NULL,
+    "inbox_id" TEXT NOT NULL,
+    "received_at_ns" BIGINT NOT NULL,
+    FOREIGN KEY (link_id) REFERENCES invite_links(id) ON DELETE CASCADE
+);
+
+-- Foreign keys aren't enforced on every connection, so clean up explicitly as well
+CREATE TRIGGER delete_invite_links
+AFTER DELETE ON groups
+BEGIN
+    DELETE FROM invite_links WHERE group_id = OLD.id;
+END;
+
+CREATE TRIGGER delete_invite_redemptions
+AFTER DELETE ON invite_links
+BEGIN
+    DELETE FROM invite_redemptions WHERE link_id = OLD.id;
+END;
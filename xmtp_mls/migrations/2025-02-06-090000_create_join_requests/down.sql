@@ -0,0 +1,2 @@
+DROP TRIGGER delete_join_requests;
+DROP TABLE join_requests;
@@ -0,0 +1,18 @@
+-- Requests to join groups this installation administers, waiting for an admin's decision.
+CREATE TABLE join_requests (
+    "message_id" BINARY PRIMARY KEY NOT NULL,
+    "group_id" BINARY NOT NULL,
+    "inbox_id" TEXT NOT NULL,
+    "note" TEXT,
+    "requested_at_ns" BIGINT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+CREATE INDEX join_requests_group_id_idx ON join_requests(group_id);
+
+-- Foreign keys aren't enforced on every connection, so clean up explicitly as well
+CREATE TRIGGER delete_join_requests
+AFTER DELETE ON groups
+BEGIN
+    DELETE FROM join_requests WHERE group_id = OLD.id;
+END;
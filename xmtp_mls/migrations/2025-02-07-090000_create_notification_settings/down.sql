@@ -0,0 +1,2 @@
+DROP TRIGGER delete_notification_settings;
+DROP TABLE notification_settings;
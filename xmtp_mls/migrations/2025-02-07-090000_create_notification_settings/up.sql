@@ -0,0 +1,16 @@
+-- The user's notification settings for each conversation, synced across their installations.
+-- Updates from other installations only apply if they're newer than the stored settings.
+CREATE TABLE notification_settings (
+    "group_id" BINARY PRIMARY KEY NOT NULL,
+    "mute_until_ns" BIGINT,
+    "mentions_only" BOOLEAN NOT NULL DEFAULT FALSE,
+    "updated_at_ns" BIGINT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+-- Foreign keys aren't enforced on every connection, so clean up explicitly as well
+CREATE TRIGGER delete_notification_settings
+AFTER DELETE ON groups
+BEGIN
+    DELETE FROM notification_settings WHERE group_id = OLD.id;
+END;
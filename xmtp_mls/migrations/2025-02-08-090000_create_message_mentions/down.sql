@@ -0,0 +1,3 @@
+DROP TRIGGER delete_message_mentions;
+DROP INDEX message_mentions_inbox_id_sent_at_ns;
+DROP TABLE message_mentions;
@@ -0,0 +1,18 @@
+-- The inboxes mentioned by each message, taken from the message's metadata as it's processed
+CREATE TABLE message_mentions (
+    "message_id" BINARY NOT NULL,
+    "inbox_id" TEXT NOT NULL,
+    "group_id" BINARY NOT NULL,
+    "sent_at_ns" BIGINT NOT NULL,
+    PRIMARY KEY (message_id, inbox_id),
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+CREATE INDEX message_mentions_inbox_id_sent_at_ns ON message_mentions(inbox_id, sent_at_ns);
+
+-- Foreign keys aren't enforced on every connection, so clean up explicitly as well
+CREATE TRIGGER delete_message_mentions
+AFTER DELETE ON groups
+BEGIN
+    DELETE FROM message_mentions WHERE group_id = OLD.id;
+END;
@@ -0,0 +1,2 @@
+DROP TRIGGER delete_poll_votes;
+DROP TABLE poll_votes;
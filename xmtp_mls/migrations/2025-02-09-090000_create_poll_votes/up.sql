@@ -0,0 +1,20 @@
+-- The current vote of each inbox in each poll. A later vote replaces the earlier one, and a vote
+-- for no options is kept, with empty options, so that older votes can't take its place.
+CREATE TABLE poll_votes (
+    "poll_id" BINARY NOT NULL,
+    "inbox_id" TEXT NOT NULL,
+    "group_id" BINARY NOT NULL,
+    -- Indices of the chosen options, separated by commas
+    "options" TEXT NOT NULL,
+    "vote_message_id" BINARY NOT NULL,
+    "voted_at_ns" BIGINT NOT NULL,
+    PRIMARY KEY (poll_id, inbox_id),
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+-- Foreign keys aren't enforced on every connection, so clean up explicitly as well
+CREATE TRIGGER delete_poll_votes
+AFTER DELETE ON groups
+BEGIN
+    DELETE FROM poll_votes WHERE group_id = OLD.id;
+END;
@@ -0,0 +1,2 @@
+DROP TRIGGER delete_unread_counts;
+DROP TABLE unread_counts;
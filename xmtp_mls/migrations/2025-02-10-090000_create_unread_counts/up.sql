@@ -0,0 +1,28 @@
+-- How many messages of each group this inbox hasn't read, updated as messages arrive so badges
+-- don't have to count messages
+CREATE TABLE unread_counts (
+    "group_id" BINARY PRIMARY KEY NOT NULL,
+    -- Messages sent at or before this time have been read
+    "last_read_ns" BIGINT NOT NULL,
+    "unread_count" BIGINT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+-- Foreign keys aren't enforced on every connection, so clean up explicitly as well
+CREATE TRIGGER delete_unread_counts
+AFTER DELETE ON groups
+BEGIN
+    DELETE FROM unread_counts WHERE group_id = OLD.id;
+END;
+
+-- Existing groups start out read up to their latest message, rather than counting their whole
+-- history as unread on the next recount
+INSERT OR IGNORE INTO unread_counts (group_id, last_read_ns, unread_count)
+SELECT
+    id,
+    COALESCE(
+        (SELECT MAX(sent_at_ns) FROM group_messages WHERE group_messages.group_id = groups.id),
+        0
+    ),
+    0
+FROM groups;
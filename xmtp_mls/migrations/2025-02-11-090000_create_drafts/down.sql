@@ -0,0 +1,2 @@
+DROP TRIGGER delete_drafts;
+DROP TABLE drafts;
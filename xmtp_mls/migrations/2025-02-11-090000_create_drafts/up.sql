@@ -0,0 +1,18 @@
+-- The user's unfinished message in each conversation, synced across their installations.
+-- A cleared draft is kept without content, so that clearing it syncs like any other change.
+CREATE TABLE drafts (
+    "group_id" BINARY PRIMARY KEY NOT NULL,
+    "content" BLOB,
+    -- Hybrid logical clock timestamp of the write, ordering writes from different installations
+    "hlc_wall_ns" BIGINT NOT NULL,
+    "hlc_counter" INTEGER NOT NULL,
+    "hlc_node" BLOB NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+-- Foreign keys aren't enforced on every connection, so clean up explicitly as well
+CREATE TRIGGER delete_drafts
+AFTER DELETE ON groups
+BEGIN
+    DELETE FROM drafts WHERE group_id = OLD.id;
+END;
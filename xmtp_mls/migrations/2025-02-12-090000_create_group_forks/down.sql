@@ -0,0 +1,3 @@
+ALTER TABLE join_requests DROP COLUMN rejoin;
+DROP TRIGGER delete_group_forks;
+DROP TABLE group_forks;
@@ -0,0 +1,22 @@
+-- Groups whose latest messages couldn't be decrypted. Once enough of them in a row fail, the
+-- group is considered forked and this installation asks an admin to add it back.
+CREATE TABLE group_forks (
+    "group_id" BINARY PRIMARY KEY NOT NULL,
+    "undecryptable_messages" INTEGER NOT NULL,
+    -- the cursor of the latest undecryptable message, so a message that's retried isn't counted twice
+    "last_failed_cursor" BIGINT NOT NULL,
+    "first_failed_at_ns" BIGINT NOT NULL,
+    "forked_at_ns" BIGINT,
+    "rejoin_requested_at_ns" BIGINT,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+-- Foreign keys aren't enforced on every connection, so clean up explicitly as well
+CREATE TRIGGER delete_group_forks
+AFTER DELETE ON groups
+BEGIN
+    DELETE FROM group_forks WHERE group_id = OLD.id;
+END;
+
+-- Set when a member asks to be removed and added back because their copy of the group forked
+ALTER TABLE join_requests ADD COLUMN rejoin BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+DROP TRIGGER delete_imported_archives;
+DROP TABLE imported_archives;
@@ -0,0 +1,17 @@
+-- Groups restored from an archive exported on another installation, with the metadata and
+-- members the group had when it was exported. The group's messages are stored as usual.
+CREATE TABLE imported_archives (
+    "group_id" BINARY PRIMARY KEY NOT NULL,
+    -- JSON encoded metadata, admins and members
+    "metadata" BLOB NOT NULL,
+    "exported_at_ns" BIGINT NOT NULL,
+    "imported_at_ns" BIGINT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+-- Foreign keys aren't enforced on every connection, so clean up explicitly as well
+CREATE TRIGGER delete_imported_archives
+AFTER DELETE ON groups
+BEGIN
+    DELETE FROM imported_archives WHERE group_id = OLD.id;
+END;
@@ -0,0 +1,6 @@
+ALTER TABLE consent_records
+DROP COLUMN hlc_wall_ns;
+ALTER TABLE consent_records
+DROP COLUMN hlc_counter;
+ALTER TABLE consent_records
+DROP COLUMN hlc_node;
@@ -0,0 +1,10 @@
+-- Hybrid logical clock timestamp of the write that set the record, used to resolve concurrent
+-- writes from different installations. Records written before this have the zero timestamp.
+ALTER TABLE consent_records
+    ADD COLUMN hlc_wall_ns BIGINT NOT NULL DEFAULT 0;
+
+ALTER TABLE consent_records
+    ADD COLUMN hlc_counter INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE consent_records
+    ADD COLUMN hlc_node BLOB NOT NULL DEFAULT x'';
@@ -0,0 +1,9 @@
+-- App-defined preferences, synced across the user's installations. A removed preference is kept
+-- without a payload, so that removing it syncs like any other change.
+CREATE TABLE custom_preferences (
+    "namespace" TEXT PRIMARY KEY NOT NULL,
+    "payload" BLOB,
+    "hlc_wall_ns" BIGINT NOT NULL,
+    "hlc_counter" INTEGER NOT NULL,
+    "hlc_node" BLOB NOT NULL
+);
@@ -0,0 +1,10 @@
+-- Inboxes the user blocked, synced across their installations. An unblocked inbox is kept with
+-- `blocked` unset, so that unblocking syncs like any other change.
+CREATE TABLE blocked_inboxes (
+    "inbox_id" TEXT PRIMARY KEY NOT NULL,
+    "blocked" BOOLEAN NOT NULL,
+    "hide_messages" BOOLEAN NOT NULL,
+    "hlc_wall_ns" BIGINT NOT NULL,
+    "hlc_counter" INTEGER NOT NULL,
+    "hlc_node" BLOB NOT NULL
+);
@@ -0,0 +1,12 @@
+-- Content types of received messages that this installation couldn't decode, so apps can see
+-- which content types their users are missing out on.
+CREATE TABLE unknown_content_types (
+    "authority_id" TEXT NOT NULL,
+    "type_id" TEXT NOT NULL,
+    "version_major" INTEGER NOT NULL,
+    "version_minor" INTEGER NOT NULL,
+    "first_seen_ns" BIGINT NOT NULL,
+    "last_seen_ns" BIGINT NOT NULL,
+    "message_count" BIGINT NOT NULL,
+    PRIMARY KEY (authority_id, type_id, version_major, version_minor)
+);
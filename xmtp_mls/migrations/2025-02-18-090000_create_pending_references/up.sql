@@ -0,0 +1,19 @@
+-- Messages that reference a message this installation hasn't received yet, such as an edit that
+-- arrived before the message it edits. They're applied once the referenced message arrives.
+CREATE TABLE pending_references (
+    -- The message waiting, which is stored in group_messages
+    "message_id" BINARY PRIMARY KEY NOT NULL,
+    "group_id" BINARY NOT NULL,
+    -- The message it references
+    "reference_id" BINARY NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+CREATE INDEX pending_references_reference_id ON pending_references(reference_id);
+
+-- Foreign keys aren't enforced on every connection, so clean up explicitly as well
+CREATE TRIGGER delete_pending_references
+AFTER DELETE ON groups
+BEGIN
+    DELETE FROM pending_references WHERE group_id = OLD.id;
+END;
@@ -0,0 +1,14 @@
+-- When each group last moved to a new epoch, by the server timestamp of the commit
+CREATE TABLE group_epochs (
+    "group_id" BINARY PRIMARY KEY NOT NULL,
+    "epoch" BIGINT NOT NULL,
+    "changed_at_ns" BIGINT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE
+);
+
+-- Foreign keys aren't enforced on every connection, so clean up explicitly as well
+CREATE TRIGGER delete_group_epochs
+AFTER DELETE ON groups
+BEGIN
+    DELETE FROM group_epochs WHERE group_id = OLD.id;
+END;
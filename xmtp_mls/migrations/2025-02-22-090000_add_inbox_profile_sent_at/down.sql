@@ -0,0 +1 @@
+ALTER TABLE inbox_profiles DROP COLUMN sent_at_ns;
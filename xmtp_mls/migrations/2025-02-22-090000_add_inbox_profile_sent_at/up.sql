@@ -0,0 +1,5 @@
+-- When each profile was signed as far as we can tell: the time it was signed at, but no later
+-- than the server timestamp of the message that carried it. Profiles are ordered by it, so one
+-- signed with a clock set in the future can't win over the profiles sent after it.
+ALTER TABLE inbox_profiles ADD COLUMN sent_at_ns BIGINT NOT NULL DEFAULT 0;
+UPDATE inbox_profiles SET sent_at_ns = signed_at_ns;
@@ -0,0 +1,6 @@
+ALTER TABLE notification_settings
+DROP COLUMN hlc_node;
+ALTER TABLE notification_settings
+DROP COLUMN hlc_counter;
+ALTER TABLE notification_settings
+RENAME COLUMN hlc_wall_ns TO updated_at_ns;
@@ -0,0 +1,11 @@
+-- Hybrid logical clock timestamp of the write that set the settings, used to resolve concurrent
+-- writes from different installations. The time settings were changed becomes its wall clock
+-- time.
+ALTER TABLE notification_settings
+    RENAME COLUMN updated_at_ns TO hlc_wall_ns;
+
+ALTER TABLE notification_settings
+    ADD COLUMN hlc_counter INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE notification_settings
+    ADD COLUMN hlc_node BLOB NOT NULL DEFAULT x'';
@@ -0,0 +1,673 @@
+//! Full backups of a client's local data: groups, messages, consent records and preferences, so
+//! history can be restored on a new installation without an old one online to sync from.
+//!
+//! A backup is a header followed by a stream of frames, each holding one protobuf encoded
+//! `BackupElement` sealed with AES-256-GCM. The key is derived from the caller's key and a
+//! random salt with HKDF, and frame nonces count up from a random prefix, so frames can't be
+//! reordered or dropped without failing to decrypt. The last frame marks the end of the backup,
+//! so a truncated backup is detected too. Elements a reader doesn't know are skipped, so older
+//! clients can restore what they understand of newer backups. Rows are serialized the same way
+//! history sync serializes them.
+//!
+//! Header layout: `magic | version | salt | nonce prefix`. Each frame is the ciphertext length as
+//! a big endian u32, then the ciphertext.
+use std::io::{ErrorKind, Read, Write};
+
+use aes_gcm::{
+    aead::{generic_array::GenericArray, Aead, KeyInit, Payload},
+    Aes256Gcm,
+};
+use hkdf::Hkdf;
+use prost::Message;
+use sha2::Sha256;
+use thiserror::Error;
+use xmtp_common::{time::now_ns, RetryableError};
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use zeroize::Zeroizing;
+
+use crate::{
+    configuration::DEFAULT_MESSAGE_PAGE_SIZE,
+    groups::device_sync::preference_sync::UserPreferenceUpdate,
+    storage::{
+        group::{ConversationType, GroupMembershipState, GroupQueryArgs, StoredGroup},
+        group_message::{MessageCursor, MsgQueryArgs, SortDirection, StoredGroupMessage},
+        user_preferences::StoredUserPreferences,
+        DbConnection, ProviderTransactions, StorageError,
+    },
+    subscriptions::LocalEvents,
+    Client, StoreOrIgnore, XmtpApi,
+};
+
+/// The backup version written by [`Client::create_backup`]
+pub const BACKUP_VERSION: u8 = 1;
+/// The size of the key backups are encrypted with
+pub const BACKUP_KEY_SIZE: usize = 32;
+
+const MAGIC: &[u8; 8] = b"XMTPBKUP";
+const SALT_SIZE: usize = 16;
+const NONCE_PREFIX_SIZE: usize = 8;
+const HEADER_SIZE: usize = MAGIC.len() + 1 + SALT_SIZE + NONCE_PREFIX_SIZE;
+const KEY_INFO: &[u8] = b"XMTP backup";
+/// Frames larger than this are rejected rather than read into memory, 64 MiB
+const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("backup key must be {BACKUP_KEY_SIZE} bytes")]
+    InvalidKey,
+    #[error("data is not a backup")]
+    NotABackup,
+    #[error("unsupported backup version {0}")]
+    UnsupportedVersion(u8),
+    #[error("backup belongs to inbox {0}")]
+    WrongInbox(String),
+    #[error("backup is missing its metadata")]
+    MissingMetadata,
+    #[error("backup is truncated")]
+    Truncated,
+    #[error("backup is too large")]
+    TooLarge,
+    /// The key is wrong, or the backup was modified
+    #[error("backup could not be decrypted")]
+    Decrypt,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Decode(#[from] prost::DecodeError),
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+    #[error(transparent)]
+    Diesel(#[from] diesel::result::Error),
+}
+
+impl RetryableError for BackupError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            Self::Storage(err) => err.is_retryable(),
+            Self::Diesel(err) => err.is_retryable(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct BackupElement {
+    #[prost(oneof = "backup_element::Element", tags = "1, 2, 3, 4, 5, 6")]
+    element: Option<backup_element::Element>,
+}
+
+mod backup_element {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub(super) enum Element {
+        #[prost(message, tag = "1")]
+        Metadata(super::BackupMetadata),
+        /// A JSON encoded `StoredGroup`
+        #[prost(bytes, tag = "2")]
+        Group(Vec<u8>),
+        /// A JSON encoded `StoredGroupMessage`
+        #[prost(bytes, tag = "3")]
+        GroupMessage(Vec<u8>),
+        /// A JSON encoded `StoredConsentRecord`
+        #[prost(bytes, tag = "4")]
+        ConsentRecord(Vec<u8>),
+        /// A JSON encoded `UserPreferenceUpdate`, other than consent
+        #[prost(bytes, tag = "5")]
+        Preference(Vec<u8>),
+        #[prost(message, tag = "6")]
+        End(super::BackupEnd),
+    }
+}
+use backup_element::Element;
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct BackupMetadata {
+    #[prost(string, tag = "1")]
+    inbox_id: String,
+    #[prost(int64, tag = "2")]
+    created_at_ns: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct BackupEnd {
+    /// The number of elements before this one
+    #[prost(uint64, tag = "1")]
+    elements: u64,
+}
+
+/// What a backup held, or what of it was restored
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackupSummary {
+    pub groups: usize,
+    pub messages: usize,
+    pub consent_records: usize,
+    pub preferences: usize,
+}
+
+struct FrameCipher {
+    cipher: Aes256Gcm,
+    /// The header, authenticated along with every frame
+    header: Vec<u8>,
+    nonce_prefix: [u8; NONCE_PREFIX_SIZE],
+    counter: u32,
+}
+
+impl FrameCipher {
+    fn new(key: &[u8], header: Vec<u8>) -> Result<Self, BackupError> {
+        if key.len() != BACKUP_KEY_SIZE {
+            return Err(BackupError::InvalidKey);
+        }
+        let salt = &header[MAGIC.len() + 1..MAGIC.len() + 1 + SALT_SIZE];
+        let nonce_prefix = header[HEADER_SIZE - NONCE_PREFIX_SIZE..HEADER_SIZE]
+            .try_into()
+            .expect("8 bytes");
+        let mut derived = Zeroizing::new([0u8; BACKUP_KEY_SIZE]);
+        Hkdf::<Sha256>::new(Some(salt), key)
+            .expand(KEY_INFO, derived.as_mut())
+            .map_err(|_| BackupError::InvalidKey)?;
+
+        Ok(Self {
+            cipher: Aes256Gcm::new(GenericArray::from_slice(derived.as_ref())),
+            header,
+            nonce_prefix,
+            counter: 0,
+        })
+    }
+
+    fn next_nonce(&mut self) -> Result<[u8; 12], BackupError> {
+        let mut nonce = [0u8; 12];
+        nonce[..NONCE_PREFIX_SIZE].copy_from_slice(&self.nonce_prefix);
+        nonce[NONCE_PREFIX_SIZE..].copy_from_slice(&self.counter.to_be_bytes());
+        self.counter = self.counter.checked_add(1).ok_or(BackupError::TooLarge)?;
+        Ok(nonce)
+    }
+
+    fn seal(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, BackupError> {
+        let nonce = self.next_nonce()?;
+        self.cipher
+            .encrypt(
+                GenericArray::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: &self.header,
+                },
+            )
+            .map_err(|_| BackupError::Decrypt)
+    }
+
+    fn open(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, BackupError> {
+        let nonce = self.next_nonce()?;
+        self.cipher
+            .decrypt(
+                GenericArray::from_slice(&nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &self.header,
+                },
+            )
+            .map_err(|_| BackupError::Decrypt)
+    }
+}
+
+struct BackupWriter<W: Write> {
+    writer: W,
+    cipher: FrameCipher,
+    elements: u64,
+}
+
+impl<W: Write> BackupWriter<W> {
+    fn new(mut writer: W, key: &[u8]) -> Result<Self, BackupError> {
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend_from_slice(MAGIC);
+        header.push(BACKUP_VERSION);
+        header.extend_from_slice(&xmtp_common::rand_array::<SALT_SIZE>());
+        header.extend_from_slice(&xmtp_common::rand_array::<NONCE_PREFIX_SIZE>());
+        let cipher = FrameCipher::new(key, header)?;
+        writer.write_all(&cipher.header)?;
+
+        Ok(Self {
+            writer,
+            cipher,
+            elements: 0,
+        })
+    }
+
+    fn write(&mut self, element: Element) -> Result<(), BackupError> {
+        let plaintext = BackupElement {
+            element: Some(element),
+        }
+        .encode_to_vec();
+        let frame = self.cipher.seal(&plaintext)?;
+        let len = u32::try_from(frame.len()).map_err(|_| BackupError::TooLarge)?;
+        self.writer.write_all(&len.to_be_bytes())?;
+        self.writer.write_all(&frame)?;
+        self.elements += 1;
+        Ok(())
+    }
+
+    fn write_json<T: serde::Serialize>(
+        &mut self,
+        element: fn(Vec<u8>) -> Element,
+        value: &T,
+    ) -> Result<(), BackupError> {
+        self.write(element(serde_json::to_vec(value)?))
+    }
+
+    fn finish(mut self) -> Result<(), BackupError> {
+        let elements = self.elements;
+        self.write(Element::End(BackupEnd { elements }))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+struct BackupReader<R: Read> {
+    reader: R,
+    cipher: FrameCipher,
+    elements: u64,
+}
+
+impl<R: Read> BackupReader<R> {
+    fn new(mut reader: R, key: &[u8]) -> Result<Self, BackupError> {
+        let mut header = vec![0u8; HEADER_SIZE];
+        read_exact(&mut reader, &mut header).map_err(|e| match e {
+            BackupError::Truncated => BackupError::NotABackup,
+            e => e,
+        })?;
+        if !header.starts_with(MAGIC) {
+            return Err(BackupError::NotABackup);
+        }
+        let version = header[MAGIC.len()];
+        if version != BACKUP_VERSION {
+            return Err(BackupError::UnsupportedVersion(version));
+        }
+
+        Ok(Self {
+            reader,
+            cipher: FrameCipher::new(key, header)?,
+            elements: 0,
+        })
+    }
+
+    /// The next element, or `None` once the end of the backup is reached. Elements this version
+    /// doesn't know are skipped.
+    fn next_element(&mut self) -> Result<Option<Element>, BackupError> {
+        loop {
+            let mut len = [0u8; 4];
+            read_exact(&mut self.reader, &mut len)?;
+            let len = u32::from_be_bytes(len) as usize;
+            if len > MAX_FRAME_SIZE {
+                return Err(BackupError::TooLarge);
+            }
+            let mut frame = vec![0u8; len];
+            read_exact(&mut self.reader, &mut frame)?;
+            let plaintext = self.cipher.open(&frame)?;
+
+            match BackupElement::decode(plaintext.as_slice())?.element {
+                Some(Element::End(end)) if end.elements == self.elements => return Ok(None),
+                Some(Element::End(_)) => return Err(BackupError::Truncated),
+                Some(element) => {
+                    self.elements += 1;
+                    return Ok(Some(element));
+                }
+                None => self.elements += 1,
+            }
+        }
+    }
+}
+
+fn read_exact(reader: &mut impl Read, buf: &mut [u8]) -> Result<(), BackupError> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => BackupError::Truncated,
+        _ => e.into(),
+    })
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Write a backup of this client's groups, messages, consent records and preferences to
+    /// `writer`, encrypted with `key`. Device sync groups are left out, they are specific to the
+    /// installation. Returns what was written.
+    pub fn create_backup<W: Write>(
+        &self,
+        writer: W,
+        key: &[u8],
+    ) -> Result<BackupSummary, BackupError> {
+        let conn = self.store().conn()?;
+        let mut backup = BackupWriter::new(writer, key)?;
+        let mut summary = BackupSummary::default();
+        backup.write(Element::Metadata(BackupMetadata {
+            inbox_id: self.inbox_id().to_string(),
+            created_at_ns: now_ns(),
+        }))?;
+
+        let groups: Vec<StoredGroup> = conn
+            .find_groups(GroupQueryArgs {
+                include_sync_groups: false,
+                ..Default::default()
+            })?
+            .into_iter()
+            .filter(|group| group.conversation_type != ConversationType::Sync)
+            .collect();
+        for group in &groups {
+            backup.write_json(Element::Group, group)?;
+            summary.groups += 1;
+        }
+        for group in &groups {
+            summary.messages += write_messages(&conn, &mut backup, &group.id)?;
+        }
+        for record in conn.consent_records()? {
+            backup.write_json(Element::ConsentRecord, &record)?;
+            summary.consent_records += 1;
+        }
+        for update in preference_updates(&conn)? {
+            backup.write_json(Element::Preference, &update)?;
+            summary.preferences += 1;
+        }
+
+        backup.finish()?;
+        tracing::info!(?summary, "created backup");
+        Ok(summary)
+    }
+
+    /// Restore a backup from [`Client::create_backup`] of this inbox. Records that are already
+    /// stored, or that are newer locally, are kept, and rows that fail to decode are skipped.
+    /// Groups that don't exist locally are restored as read-only history, in the
+    /// [`Restored`](GroupMembershipState::Restored) state, until this installation is added back.
+    /// The restore runs in a single transaction, so nothing is restored from a backup that turns
+    /// out to be truncated or modified. Returns what was restored.
+    pub async fn restore_backup<R: Read>(
+        &self,
+        reader: R,
+        key: &[u8],
+    ) -> Result<BackupSummary, BackupError> {
+        let mut backup = BackupReader::new(reader, key)?;
+        match backup.next_element()? {
+            Some(Element::Metadata(metadata)) if metadata.inbox_id == self.inbox_id() => {}
+            Some(Element::Metadata(metadata)) => {
+                return Err(BackupError::WrongInbox(metadata.inbox_id))
+            }
+            _ => return Err(BackupError::MissingMetadata),
+        }
+
+        let provider = self.mls_provider()?;
+        let (summary, preferences) =
+            provider.transaction(|provider| restore_elements(provider.conn_ref(), &mut backup))?;
+
+        tracing::info!(?summary, "restored backup");
+        if !preferences.is_empty() {
+            self.publish_local_event(LocalEvents::IncomingPreferenceUpdate(preferences))
+                .await;
+        }
+        Ok(summary)
+    }
+}
+
+/// Restore the elements left in `backup`, returning what was restored along with the preference
+/// updates that were applied
+fn restore_elements<R: Read>(
+    conn: &DbConnection,
+    backup: &mut BackupReader<R>,
+) -> Result<(BackupSummary, Vec<UserPreferenceUpdate>), BackupError> {
+    let mut summary = BackupSummary::default();
+    let mut preferences = vec![];
+    while let Some(element) = backup.next_element()? {
+        match element {
+            Element::Group(group) => {
+                let Some(group) = decode_json::<StoredGroup>("group", &group) else {
+                    continue;
+                };
+                if conn.find_group(group.id.clone())?.is_none() {
+                    conn.insert_or_replace_group(StoredGroup {
+                        membership_state: GroupMembershipState::Restored,
+                        ..group
+                    })?;
+                    summary.groups += 1;
+                }
+            }
+            Element::GroupMessage(message) => {
+                let Some(message) = decode_json::<StoredGroupMessage>("message", &message) else {
+                    continue;
+                };
+                if conn.get_group_message(&message.id)?.is_none() {
+                    message.store_or_ignore(conn)?;
+                    summary.messages += 1;
+                }
+            }
+            Element::ConsentRecord(record) => {
+                let Some(record) = decode_json("consent record", &record) else {
+                    continue;
+                };
+                let update = UserPreferenceUpdate::ConsentUpdate(record);
+                let applied = UserPreferenceUpdate::apply_imported(conn, vec![update])?;
+                summary.consent_records += applied.len();
+                preferences.extend(applied);
+            }
+            Element::Preference(update) => {
+                let Some(update) = decode_json::<UserPreferenceUpdate>("preference", &update)
+                else {
+                    continue;
+                };
+                let applied = UserPreferenceUpdate::apply_imported(conn, vec![update])?;
+                summary.preferences += applied.len();
+                preferences.extend(applied);
+            }
+            Element::Metadata(_) | Element::End(_) => {}
+        }
+    }
+    Ok((summary, preferences))
+}
+
+/// Decode an element's JSON encoded row, or `None` if it can't be decoded, e.g. because it was
+/// written by a newer version. The element is skipped, the rest of the backup is still restored.
+fn decode_json<T: serde::de::DeserializeOwned>(kind: &str, bytes: &[u8]) -> Option<T> {
+    match serde_json::from_slice(bytes) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            tracing::warn!("skipping backed up {kind} that failed to decode: {e}");
+            None
+        }
+    }
+}
+
+/// Write the messages of `group_id` a page at a time, returning how many were written
+fn write_messages<W: Write>(
+    conn: &DbConnection,
+    backup: &mut BackupWriter<W>,
+    group_id: &[u8],
+) -> Result<usize, BackupError> {
+    let mut written = 0;
+    let mut cursor = None;
+    loop {
+        let page = conn.get_group_messages(
+            group_id,
+            &MsgQueryArgs {
+                direction: Some(SortDirection::Ascending),
+                limit: Some(DEFAULT_MESSAGE_PAGE_SIZE),
+                cursor,
+                ..Default::default()
+            },
+        )?;
+        for message in &page {
+            backup.write_json(Element::GroupMessage, message)?;
+        }
+        written += page.len();
+        match page.last() {
+            Some(last) if page.len() as i64 == DEFAULT_MESSAGE_PAGE_SIZE => {
+                cursor = Some(MessageCursor::at(last))
+            }
+            _ => return Ok(written),
+        }
+    }
+}
+
+/// The preferences that aren't consent records, which are backed up on their own
+fn preference_updates(conn: &DbConnection) -> Result<Vec<UserPreferenceUpdate>, StorageError> {
+    let mut updates = vec![];
+    if let Some(key) = StoredUserPreferences::load(conn)?.hmac_key {
+        updates.push(UserPreferenceUpdate::HmacKeyUpdate { key });
+    }
+    updates.extend(
+        conn.all_notification_settings()?
+            .into_iter()
+            .map(UserPreferenceUpdate::NotificationSettingsUpdate),
+    );
+    updates.extend(
+        conn.all_drafts()?
+            .into_iter()
+            .map(UserPreferenceUpdate::DraftUpdate),
+    );
+    updates.extend(
+        conn.all_custom_preferences()?
+            .into_iter()
+            .map(UserPreferenceUpdate::CustomUpdate),
+    );
+    updates.extend(
+        conn.all_blocked_inboxes()?
+            .into_iter()
+            .map(UserPreferenceUpdate::BlockUpdate),
+    );
+    Ok(updates)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::GroupMetadataOptions,
+        storage::consent_record::{ConsentState, ConsentType, StoredConsentRecord},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_backup_round_trip() {
+        let wallet = generate_local_wallet();
+        let amal_a = ClientBuilder::new_test_client(&wallet).await;
+        let group = amal_a
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        for i in 0..(DEFAULT_MESSAGE_PAGE_SIZE + 1) {
+            group.send_message(format!("{i}").as_bytes()).await.unwrap();
+        }
+        group.save_draft(b"unsent").unwrap();
+        let record = StoredConsentRecord::new(
+            ConsentType::InboxId,
+            ConsentState::Denied,
+            "spammer".to_string(),
+        );
+        amal_a.set_consent_states(&[record.clone()]).await.unwrap();
+
+        let key = xmtp_common::rand_array::<BACKUP_KEY_SIZE>();
+        let mut backup = vec![];
+        let written = amal_a.create_backup(&mut backup, &key).unwrap();
+        assert_eq!(written.groups, 1);
+        assert!(written.messages > DEFAULT_MESSAGE_PAGE_SIZE as usize);
+
+        let amal_b = ClientBuilder::new_test_client(&wallet).await;
+        let wrong_key = xmtp_common::rand_array::<BACKUP_KEY_SIZE>();
+        assert!(matches!(
+            amal_b.restore_backup(backup.as_slice(), &wrong_key).await,
+            Err(BackupError::Decrypt)
+        ));
+        assert!(matches!(
+            amal_b
+                .restore_backup(&backup[..backup.len() - 1], &key)
+                .await,
+            Err(BackupError::Truncated)
+        ));
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        assert!(matches!(
+            bola.restore_backup(backup.as_slice(), &key).await,
+            Err(BackupError::WrongInbox(_))
+        ));
+
+        // nothing was restored from the truncated backup
+        assert!(amal_b.group(group.group_id.clone()).is_err());
+
+        let restored = amal_b
+            .restore_backup(backup.as_slice(), &key)
+            .await
+            .unwrap();
+        assert_eq!(restored.groups, 1);
+        assert_eq!(restored.messages, written.messages);
+        let conn = amal_b.store().conn().unwrap();
+        assert_eq!(
+            conn.get_consent_record("spammer".to_string(), ConsentType::InboxId)
+                .unwrap(),
+            Some(record)
+        );
+        // there's no MLS state for the group until amal_b is added to it
+        assert_eq!(
+            conn.find_group(group.group_id.clone())
+                .unwrap()
+                .unwrap()
+                .membership_state,
+            GroupMembershipState::Restored
+        );
+        amal_b
+            .sync_all_welcomes_and_groups(&amal_b.mls_provider().unwrap(), None)
+            .await
+            .unwrap();
+        let restored_group = amal_b.group(group.group_id.clone()).unwrap();
+        assert_eq!(
+            restored_group
+                .find_messages(&MsgQueryArgs::default())
+                .unwrap(),
+            group.find_messages(&MsgQueryArgs::default()).unwrap()
+        );
+        assert_eq!(
+            restored_group.load_draft().unwrap(),
+            Some(b"unsent".to_vec())
+        );
+
+        // restoring again keeps what's already there
+        let again = amal_b
+            .restore_backup(backup.as_slice(), &key)
+            .await
+            .unwrap();
+        assert_eq!(again.groups, 0);
+        assert_eq!(again.messages, 0);
+        assert_eq!(again.consent_records, 0);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_undecodable_elements_are_skipped() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group.send_message(b"hello").await.unwrap();
+        let conn = amal.store().conn().unwrap();
+        let messages = conn
+            .get_group_messages(&group.group_id, &MsgQueryArgs::default())
+            .unwrap();
+
+        let key = xmtp_common::rand_array::<BACKUP_KEY_SIZE>();
+        let mut bytes = vec![];
+        let mut backup = BackupWriter::new(&mut bytes, &key).unwrap();
+        backup
+            .write(Element::Metadata(BackupMetadata {
+                inbox_id: amal.inbox_id().to_string(),
+                created_at_ns: now_ns(),
+            }))
+            .unwrap();
+        backup.write(Element::Group(b"not json".to_vec())).unwrap();
+        for message in &messages {
+            backup.write_json(Element::GroupMessage, message).unwrap();
+        }
+        backup.finish().unwrap();
+
+        // the messages are already stored, only the bad group is left and it's skipped
+        let restored = amal.restore_backup(bytes.as_slice(), &key).await.unwrap();
+        assert_eq!(restored, BackupSummary::default());
+    }
+}
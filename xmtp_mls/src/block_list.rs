@@ -0,0 +1,196 @@
+//! Blocking other inboxes. The block list is stored locally and synced to the user's other
+//! installations through the sync group.
+//!
+//! Welcomes sent by a blocked inbox are dropped, so it can't add the user to new conversations.
+//! Blocking also denies consent for the inbox and for an existing DM with it, which new DMs
+//! inherit. The blocked inbox's messages in groups the user shares with it can be hidden as
+//! well: they're left out of message queries, streams, unread counts and the last message of
+//! conversation lists. They're kept, and show up again once it's unblocked.
+use thiserror::Error;
+use xmtp_common::RetryableError;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use crate::{
+    client::ClientError,
+    groups::device_sync::preference_sync::UserPreferenceUpdate,
+    storage::{
+        blocked_inbox::StoredBlockedInbox,
+        consent_record::{ConsentState, ConsentType, StoredConsentRecord},
+        db_connection::DbConnection,
+        StorageError,
+    },
+    subscriptions::{publish_without_waiting, LocalEvents, UnreadCountUpdate},
+    Client, XmtpApi,
+};
+
+#[derive(Debug, Error)]
+pub enum BlockError {
+    #[error("inbox {0} is blocked")]
+    Blocked(String),
+}
+
+impl RetryableError for BlockError {
+    fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Block `inbox_id` on all of the user's installations. With `hide_messages`, its messages
+    /// are also hidden in groups the user shares with it.
+    pub async fn block_inbox(
+        &self,
+        inbox_id: &str,
+        hide_messages: bool,
+    ) -> Result<(), ClientError> {
+        self.write_block_list(inbox_id, true, hide_messages)?;
+        self.set_consent_states(&[StoredConsentRecord::new(
+            ConsentType::InboxId,
+            ConsentState::Denied,
+            inbox_id.to_string(),
+        )])
+        .await?;
+        if let Ok(dm) = self.dm_group_from_target_inbox(inbox_id.to_string()) {
+            dm.update_consent_state(ConsentState::Denied)?;
+        }
+        Ok(())
+    }
+
+    /// Take `inbox_id` off the block list. Consent stays denied until the user allows the inbox
+    /// or a conversation with it.
+    pub fn unblock_inbox(&self, inbox_id: &str) -> Result<(), ClientError> {
+        self.write_block_list(inbox_id, false, false)
+    }
+
+    pub fn is_inbox_blocked(&self, inbox_id: &str) -> Result<bool, ClientError> {
+        Ok(self.store().conn()?.is_inbox_blocked(inbox_id)?)
+    }
+
+    /// The inboxes that are blocked
+    pub fn blocked_inboxes(&self) -> Result<Vec<StoredBlockedInbox>, ClientError> {
+        Ok(self.store().conn()?.blocked_inboxes()?)
+    }
+
+    fn write_block_list(
+        &self,
+        inbox_id: &str,
+        blocked: bool,
+        hide_messages: bool,
+    ) -> Result<(), ClientError> {
+        let conn = self.store().conn()?;
+        let entry = StoredBlockedInbox::new(
+            inbox_id.to_string(),
+            blocked,
+            hide_messages,
+            self.context.hlc.now(),
+        );
+        conn.set_blocked_inbox(&entry)?;
+        for event in recount_unread_counts(&conn, self.inbox_id())? {
+            publish_without_waiting(&self.local_events, event);
+        }
+
+        if self.history_sync_url().is_some() {
+            // Dispatch an update event so it can be synced across devices
+            publish_without_waiting(
+                &self.local_events,
+                LocalEvents::OutgoingPreferenceUpdates(vec![UserPreferenceUpdate::BlockUpdate(
+                    entry,
+                )]),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Recount the unread messages of every group of `inbox_id` once the inboxes whose messages
+/// are hidden changed. Returns an event for each count that changed.
+pub(crate) fn recount_unread_counts<C>(
+    conn: &DbConnection,
+    inbox_id: &str,
+) -> Result<Vec<LocalEvents<C>>, StorageError> {
+    Ok(conn
+        .recount_unread_counts(inbox_id)?
+        .into_iter()
+        .map(|(group_id, unread_count)| {
+            LocalEvents::UnreadCountChanged(UnreadCountUpdate {
+                group_id,
+                unread_count: unread_count as u64,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::{GroupMetadataOptions, MlsGroup},
+        storage::group_message::MsgQueryArgs,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_blocked_inboxes_are_kept_out() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_provider = alix.mls_provider().unwrap();
+
+        let group = caro
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[alix.inbox_id(), bo.inbox_id()])
+            .await
+            .unwrap();
+        alix.sync_welcomes(&alix_provider).await.unwrap();
+        let alix_group = alix.group(group.group_id.clone()).unwrap();
+        bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        let bo_group = bo.group(group.group_id.clone()).unwrap();
+        bo_group.send_message(b"hi").await.unwrap();
+
+        alix.block_inbox(bo.inbox_id(), true).await.unwrap();
+        assert!(alix.is_inbox_blocked(bo.inbox_id()).unwrap());
+        assert_eq!(
+            alix.get_consent_state(ConsentType::InboxId, bo.inbox_id().to_string())
+                .await
+                .unwrap(),
+            ConsentState::Denied
+        );
+
+        // a DM from a blocked inbox never shows up
+        bo.create_dm_by_inbox_id(alix.inbox_id().to_string())
+            .await
+            .unwrap();
+        assert!(alix.sync_welcomes(&alix_provider).await.unwrap().is_empty());
+        assert!(alix
+            .dm_group_from_target_inbox(bo.inbox_id().to_string())
+            .is_err());
+
+        alix_group.sync().await.unwrap();
+        let from_bo = |group: &MlsGroup<_>| {
+            group
+                .find_messages(&MsgQueryArgs::default())
+                .unwrap()
+                .into_iter()
+                .filter(|msg| msg.sender_inbox_id == bo.inbox_id())
+                .count()
+        };
+        assert_eq!(from_bo(&alix_group), 0);
+        assert_eq!(alix_group.unread_count().unwrap(), 0);
+
+        alix.unblock_inbox(bo.inbox_id()).unwrap();
+        assert!(alix.blocked_inboxes().unwrap().is_empty());
+        assert_eq!(from_bo(&alix_group), 1);
+        assert_eq!(alix_group.unread_count().unwrap(), 1);
+    }
+}
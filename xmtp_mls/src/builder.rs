@@ -9,9 +9,13 @@ use xmtp_id::scw_verifier::{RemoteSignatureVerifier, SmartContractSignatureVerif
 use crate::{
     api::ApiClientWrapper,
     client::Client,
+    deferred_startup::{is_offline_error, DEFERRED_STARTUP_RETRY_INTERVAL},
+    failover::{Failover, FailoverLease, FailoverOptions},
+    groups::device_sync::history_scope::HistorySyncScope,
     identity::{Identity, IdentityStrategy},
     identity_updates::load_identity_updates,
     storage::EncryptedMessageStore,
+    subscriptions::LocalEventQueueOptions,
     StorageError, XmtpApi, XmtpOpenMlsProvider,
 };
 use xmtp_common::Retry;
@@ -23,6 +27,11 @@ pub enum ClientBuilderError {
 
     #[error("Missing parameter: {parameter}")]
     MissingParameter { parameter: &'static str },
+    #[error("Invalid parameter {parameter}: {reason}")]
+    InvalidParameter {
+        parameter: &'static str,
+        reason: &'static str,
+    },
     #[error(transparent)]
     ClientError(#[from] crate::client::ClientError),
 
@@ -53,8 +62,12 @@ pub struct ClientBuilder<ApiClient, V = RemoteSignatureVerifier<ApiClient>> {
     store: Option<EncryptedMessageStore>,
     identity_strategy: IdentityStrategy,
     history_sync_url: Option<String>,
+    history_sync_scope: Option<HistorySyncScope>,
     app_version: Option<String>,
     scw_verifier: Option<V>,
+    local_event_queue: LocalEventQueueOptions,
+    failover: Failover,
+    offline_startup: bool,
 }
 
 impl<ApiClient, V> Client<ApiClient, V> {
@@ -73,8 +86,12 @@ impl<ApiClient, V> ClientBuilder<ApiClient, V> {
             store: None,
             identity_strategy: strategy,
             history_sync_url: None,
+            history_sync_scope: None,
             app_version: None,
             scw_verifier: None,
+            local_event_queue: LocalEventQueueOptions::default(),
+            failover: Failover::default(),
+            offline_startup: false,
         }
     }
 
@@ -98,6 +115,13 @@ impl<ApiClient, V> ClientBuilder<ApiClient, V> {
         self
     }
 
+    /// Only ask for this part of the message history when the installation first syncs, e.g.
+    /// [`HistorySyncScope::last_days`]. Defaults to the whole history.
+    pub fn history_sync_scope(mut self, scope: HistorySyncScope) -> Self {
+        self.history_sync_scope = Some(scope);
+        self
+    }
+
     pub fn app_version(mut self, version: String) -> Self {
         self.app_version = Some(version);
         self
@@ -107,6 +131,28 @@ impl<ApiClient, V> ClientBuilder<ApiClient, V> {
         self.scw_verifier = Some(verifier);
         self
     }
+
+    /// Configure the queue local events (new groups, consent and preference updates) are
+    /// streamed through
+    pub fn local_event_queue(mut self, options: LocalEventQueueOptions) -> Self {
+        self.local_event_queue = options;
+        self
+    }
+
+    /// Run as one of several installations sharing `lease`, only one of which publishes at
+    /// a time. The client starts in standby; see [`Client::try_promote`].
+    pub fn failover(mut self, lease: Arc<dyn FailoverLease>, options: FailoverOptions) -> Self {
+        self.failover = Failover::new(lease, options);
+        self
+    }
+
+    /// Start from a stored identity even if the API is unreachable. Local conversations and
+    /// messages are available right away; the identity refresh normally done at startup runs
+    /// in the background once the network is back. See [`Client::startup_complete`].
+    pub fn offline_startup(mut self, enabled: bool) -> Self {
+        self.offline_startup = enabled;
+        self
+    }
 }
 
 impl<ApiClient, V> ClientBuilder<ApiClient, V>
@@ -173,12 +219,24 @@ where
         mut store,
         identity_strategy,
         history_sync_url,
+        history_sync_scope,
         mut scw_verifier,
+        local_event_queue,
+        failover,
+        offline_startup,
         ..
     } = client;
 
     debug!("Building client");
 
+    // a broadcast channel can't be empty
+    if local_event_queue.capacity == 0 {
+        return Err(ClientBuilderError::InvalidParameter {
+            parameter: "local_event_queue",
+            reason: "capacity must be at least 1",
+        });
+    }
+
     let scw_verifier = scw_verifier
         .take()
         .ok_or(ClientBuilderError::MissingParameter {
@@ -201,12 +259,20 @@ where
         "Initialized identity"
     );
     // get sequence_id from identity updates and loaded into the DB
-    load_identity_updates(
+    let identity_updates_deferred = match load_identity_updates(
         &api_client_wrapper,
         provider.conn_ref(),
         vec![identity.inbox_id.as_str()].as_slice(),
     )
-    .await?;
+    .await
+    {
+        Ok(_) => false,
+        Err(e) if offline_startup && is_offline_error(&e) => {
+            tracing::warn!("starting offline, identity updates will load once online: {e}");
+            true
+        }
+        Err(e) => return Err(e.into()),
+    };
 
     let client = Client::new(
         api_client_wrapper,
@@ -214,8 +280,22 @@ where
         store,
         scw_verifier,
         history_sync_url.clone(),
+        local_event_queue,
+        failover,
     );
 
+    if let Some(scope) = history_sync_scope {
+        let _ = client.context.history_sync_scope.set(scope);
+    }
+
+    // Resolve anything left in flight by a crash before the client starts syncing
+    client.recover_interrupted_processing()?;
+
+    if identity_updates_deferred {
+        client.context.deferred_startup.defer_identity_updates();
+        client.resume_deferred_startup(DEFERRED_STARTUP_RETRY_INTERVAL);
+    }
+
     if history_sync_url.is_some() {
         client.start_sync_worker();
     }
@@ -263,6 +343,7 @@ pub(crate) mod tests {
     use super::{ClientBuilder, IdentityStrategy};
     use crate::{
         storage::{EncryptedMessageStore, StorageOption},
+        subscriptions::LocalEventQueueOptions,
         Client, InboxOwner,
     };
 
@@ -524,6 +605,100 @@ pub(crate) mod tests {
         assert!(client1.installation_public_key() != client4.installation_public_key());
     }
 
+    // A client with a stored identity starts with the API unreachable, leaving the identity
+    // refresh for later
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_offline_startup() {
+        let store = EncryptedMessageStore::new(
+            StorageOption::Persistent(tmp_path()),
+            EncryptedMessageStore::generate_enc_key(),
+        )
+        .await
+        .unwrap();
+        let wallet = generate_local_wallet();
+        let address = wallet.get_address();
+        let client1 = ClientBuilder::new(IdentityStrategy::new(
+            generate_inbox_id(&address, &0).unwrap(),
+            address,
+            0,
+            None,
+        ))
+        .store(store.clone())
+        .api_client(<TestClient as XmtpTestClient>::create_local().await)
+        .scw_signature_verifier(MockSmartContractSignatureVerifier::new(true))
+        .build_with_verifier()
+        .await
+        .unwrap();
+        register_client(&client1, &wallet).await;
+
+        let failing_api = |kind: fn() -> xmtp_proto::ErrorKind| {
+            let mut mock_api = MockApiClient::new();
+            mock_api.expect_set_libxmtp_version().returning(|_| Ok(()));
+            mock_api
+                .expect_get_identity_updates_v2()
+                .returning(move |_| Err(xmtp_proto::Error::new(kind())));
+            mock_api
+        };
+        let offline_api = || failing_api(|| xmtp_proto::ErrorKind::SetupConnectionError);
+
+        let result = ClientBuilder::new(IdentityStrategy::CachedOnly)
+            .store(store.clone())
+            .api_client(offline_api())
+            .scw_signature_verifier(MockSmartContractSignatureVerifier::new(true))
+            .build_with_verifier()
+            .await;
+        assert!(result.is_err());
+
+        // the API rejecting the request isn't being offline
+        let result = ClientBuilder::new(IdentityStrategy::CachedOnly)
+            .store(store.clone())
+            .api_client(failing_api(|| xmtp_proto::ErrorKind::IdentityError))
+            .scw_signature_verifier(MockSmartContractSignatureVerifier::new(true))
+            .offline_startup(true)
+            .build_with_verifier()
+            .await;
+        assert!(result.is_err());
+
+        let client2 = ClientBuilder::new(IdentityStrategy::CachedOnly)
+            .store(store)
+            .api_client(offline_api())
+            .scw_signature_verifier(MockSmartContractSignatureVerifier::new(true))
+            .offline_startup(true)
+            .build_with_verifier()
+            .await
+            .unwrap();
+        assert_eq!(client1.inbox_id(), client2.inbox_id());
+        assert!(!client2.startup_complete());
+        assert!(client2.find_groups(Default::default()).unwrap().is_empty());
+        assert!(client2.complete_deferred_startup().await.is_err());
+        assert!(!client2.startup_complete());
+        client2.stop_deferred_startup_retry();
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn test_empty_local_event_queue_is_rejected() {
+        let mut mock_api = MockApiClient::new();
+        mock_api.expect_set_libxmtp_version().returning(|_| Ok(()));
+        let result = ClientBuilder::new(IdentityStrategy::CachedOnly)
+            .api_client(mock_api)
+            .scw_signature_verifier(MockSmartContractSignatureVerifier::new(true))
+            .local_event_queue(LocalEventQueueOptions {
+                capacity: 0,
+                backpressure: None,
+            })
+            .build_with_verifier()
+            .await;
+        assert!(matches!(
+            result,
+            Err(ClientBuilderError::InvalidParameter {
+                parameter: "local_event_queue",
+                ..
+            })
+        ));
+    }
+
     // Should return error if inbox associated with given account_address doesn't match the provided one.
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
     #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
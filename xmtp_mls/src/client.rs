@@ -1,8 +1,8 @@
 use std::{
     collections::HashMap,
     sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, OnceLock,
     },
 };
 
@@ -35,30 +35,56 @@ use crate::groups::device_sync::WorkerHandle;
 
 use crate::{
     api::ApiClientWrapper,
+    codec_registry::CodecRegistry,
+    commit_scheduling::CommitScheduler,
+    connectivity::Connectivity,
+    deferred_startup::DeferredStartup,
+    failover::{Failover, FailoverError},
     groups::{
-        device_sync::preference_sync::UserPreferenceUpdate, group_metadata::DmMembers,
-        group_permissions::PolicySet, GroupError, GroupMetadataOptions, MlsGroup,
+        commit_validator::CommitHook,
+        device_sync::{
+            custom_preferences::PreferenceNamespaces, history_scope::HistorySyncScope,
+            preference_sync::UserPreferenceUpdate, progress::SyncControl,
+        },
+        group_metadata::DmMembers,
+        group_permissions::PolicySet,
+        open::OpenConversations,
+        post_processors::PostProcessors,
+        GroupError, GroupMetadataOptions, MlsGroup,
     },
+    hlc::HybridClock,
     identity::{parse_credential, Identity, IdentityError},
     identity_updates::{load_identity_updates, IdentityUpdateError},
     intents::ProcessIntentError,
+    key_package_cache::KeyPackageCache,
+    multiplexer::SubscriptionMultiplexer,
     mutex_registry::MutexRegistry,
+    outbox::Outbox,
+    rate_limiting::InboundRateLimiter,
     storage::{
         consent_record::{ConsentState, ConsentType, StoredConsentRecord},
         db_connection::DbConnection,
         group::{GroupMembershipState, GroupQueryArgs, StoredGroup},
         group_message::StoredGroupMessage,
+        processing_checkpoint::CheckpointResolution,
         refresh_state::EntityKind,
         wallet_addresses::WalletEntry,
         xmtp_openmls_provider::XmtpOpenMlsProvider,
         EncryptedMessageStore, NotFound, StorageError,
     },
-    subscriptions::{LocalEventError, LocalEvents},
+    subscriptions::{
+        publish_without_waiting, LocalEventError, LocalEventQueueOptions, LocalEvents,
+    },
     types::InstallationId,
     verified_key_package_v2::{KeyPackageVerificationError, VerifiedKeyPackageV2},
     Fetch, Store, XmtpApi,
 };
-use crate::{groups::ConversationListItem, storage::ProviderTransactions};
+use crate::{
+    groups::{ConversationListItem, ConversationOverview},
+    storage::{
+        conversation_list::ConversationListItem as StoredConversationListItem, ProviderTransactions,
+    },
+};
 use xmtp_common::{retry_async, retryable, Retry};
 
 /// Enum representing the network the Client is connected to
@@ -105,6 +131,8 @@ pub enum ClientError {
     Group(Box<GroupError>),
     #[error(transparent)]
     LocalEvent(#[from] LocalEventError),
+    #[error(transparent)]
+    Failover(#[from] FailoverError),
     #[error("generic:{0}")]
     Generic(String),
 }
@@ -128,6 +156,7 @@ impl xmtp_common::RetryableError for ClientError {
             ClientError::Diesel(diesel_error) => retryable!(diesel_error),
             ClientError::Api(api_error) => retryable!(api_error),
             ClientError::Storage(storage_error) => retryable!(storage_error),
+            ClientError::Failover(failover_error) => retryable!(failover_error),
             ClientError::Generic(err) => err.contains("database is locked"),
             _ => false,
         }
@@ -154,6 +183,8 @@ pub struct Client<ApiClient, V = RemoteSignatureVerifier<ApiClient>> {
     pub(crate) local_events: broadcast::Sender<LocalEvents<Self>>,
     /// The method of verifying smart contract wallet signatures for this Client
     pub(crate) scw_verifier: Arc<V>,
+    /// Shares one group message subscription between multiplexed group streams
+    pub(crate) multiplexer: Arc<SubscriptionMultiplexer<ApiClient>>,
 
     #[cfg(any(test, feature = "test-utils"))]
     pub(crate) sync_worker_handle: Arc<parking_lot::Mutex<Option<Arc<WorkerHandle>>>>,
@@ -168,6 +199,7 @@ impl<ApiClient, V> Clone for Client<ApiClient, V> {
             history_sync_url: self.history_sync_url.clone(),
             local_events: self.local_events.clone(),
             scw_verifier: self.scw_verifier.clone(),
+            multiplexer: self.multiplexer.clone(),
 
             #[cfg(any(test, feature = "test-utils"))]
             sync_worker_handle: self.sync_worker_handle.clone(),
@@ -184,6 +216,41 @@ pub struct XmtpMlsLocalContext {
     /// XMTP Local Storage
     store: EncryptedMessageStore,
     pub(crate) mutexes: MutexRegistry,
+    /// Plugins run on messages after they are decrypted
+    pub(crate) post_processors: PostProcessors,
+    pub(crate) local_event_queue: LocalEventQueueOptions,
+    /// Whether this installation may publish, when running with a standby
+    pub(crate) failover: Failover,
+    /// The membership policy and app rules that commits this installation publishes must pass
+    pub(crate) commit_hook: CommitHook,
+    /// Key packages prefetched for likely add targets
+    pub(crate) key_package_cache: KeyPackageCache,
+    /// Startup work waiting on the network
+    pub(crate) deferred_startup: DeferredStartup,
+    /// Whether fan-out diagnostics are recorded for sent messages
+    pub(crate) send_diagnostics: AtomicBool,
+    /// Spreads out commit work around the host's UI interaction
+    pub(crate) commit_scheduler: CommitScheduler,
+    /// Conversations open in the UI, which are synced first
+    pub(crate) open_conversations: OpenConversations,
+    /// Wakes the outbox publisher when messages are sent optimistically
+    pub(crate) outbox: Outbox,
+    /// Whether the API is reachable, as far as the client knows
+    pub(crate) connectivity: Connectivity,
+    /// Pauses or cancels the device sync in progress
+    pub(crate) sync_control: SyncControl,
+    /// The part of the history this installation asks for when it first syncs
+    pub(crate) history_sync_scope: OnceLock<HistorySyncScope>,
+    /// Orders this installation's preference writes against those of its other installations
+    pub(crate) hlc: HybridClock,
+    /// The namespaces the app stores its own synced preferences under
+    pub(crate) preference_namespaces: PreferenceNamespaces,
+    /// Keeps a flooding group from holding up the sync of the others
+    pub(crate) inbound_rate_limiter: InboundRateLimiter,
+    /// Encodes and decodes message content
+    pub(crate) codecs: CodecRegistry,
+    #[cfg(any(test, feature = "test-utils"))]
+    pub(crate) processing_crash: std::sync::atomic::AtomicBool,
 }
 
 impl XmtpMlsLocalContext {
@@ -239,20 +306,51 @@ where
         store: EncryptedMessageStore,
         scw_verifier: V,
         history_sync_url: Option<String>,
+        local_event_queue: LocalEventQueueOptions,
+        failover: Failover,
     ) -> Self
     where
         V: SmartContractSignatureVerifier,
     {
         api_client.attach_inbox_id(Some(identity.inbox_id().to_string()));
+        let hlc = HybridClock::new(identity.installation_keys.public_bytes().to_vec());
+        // writes made before a restart stay ordered before new ones, even if the wall clock
+        // moved back in between
+        match store.conn().and_then(|conn| conn.latest_consent_hlc()) {
+            Ok(Some(latest)) => hlc.observe(&latest),
+            Ok(None) => {}
+            Err(e) => tracing::warn!("failed to seed the hybrid clock from storage: {e}"),
+        }
         let context = Arc::new(XmtpMlsLocalContext {
             identity,
             store,
             mutexes: MutexRegistry::new(),
+            post_processors: PostProcessors::default(),
+            local_event_queue,
+            failover,
+            commit_hook: CommitHook::default(),
+            key_package_cache: KeyPackageCache::default(),
+            deferred_startup: DeferredStartup::default(),
+            send_diagnostics: AtomicBool::new(false),
+            commit_scheduler: CommitScheduler::default(),
+            open_conversations: OpenConversations::default(),
+            outbox: Outbox::default(),
+            connectivity: Connectivity::default(),
+            sync_control: SyncControl::default(),
+            history_sync_scope: OnceLock::new(),
+            hlc,
+            preference_namespaces: PreferenceNamespaces::default(),
+            inbound_rate_limiter: InboundRateLimiter::default(),
+            codecs: CodecRegistry::default(),
+            #[cfg(any(test, feature = "test-utils"))]
+            processing_crash: Default::default(),
         });
-        let (tx, _) = broadcast::channel(32);
+        let (tx, _) = broadcast::channel(local_event_queue.capacity);
+        let api_client = Arc::new(api_client);
 
         Self {
-            api_client: api_client.into(),
+            multiplexer: Arc::new(SubscriptionMultiplexer::new(api_client.clone())),
+            api_client,
             context,
             history_sync_url,
             local_events: tx,
@@ -412,6 +510,9 @@ where
 
     /// Set a consent record in the local database.
     /// If the consent record is an address set the consent state for both the address and `inbox_id`
+    ///
+    /// Any number of records can be set at once. They're written in a single transaction and
+    /// synced to the user's other installations as one update.
     pub async fn set_consent_states(
         &self,
         records: &[StoredConsentRecord],
@@ -445,6 +546,10 @@ where
         }
 
         new_records.extend_from_slice(records);
+        let new_records: Vec<_> = new_records
+            .into_iter()
+            .map(|record| record.with_hlc(self.context.hlc.now()))
+            .collect();
         let changed_records = conn.insert_or_replace_consent_records(&new_records)?;
 
         if self.history_sync_url.is_some() && !changed_records.is_empty() {
@@ -452,9 +557,8 @@ where
                 .into_iter()
                 .map(UserPreferenceUpdate::ConsentUpdate)
                 .collect();
-            let _ = self
-                .local_events
-                .send(LocalEvents::OutgoingPreferenceUpdates(records));
+            self.publish_local_event(LocalEvents::OutgoingPreferenceUpdates(records))
+                .await;
         }
 
         Ok(())
@@ -526,7 +630,7 @@ where
         )?;
 
         // notify streams of our new group
-        let _ = self.local_events.send(LocalEvents::NewGroup(group.clone()));
+        publish_without_waiting(&self.local_events, LocalEvents::NewGroup(group.clone()));
 
         Ok(group)
     }
@@ -600,7 +704,8 @@ where
             .await?;
 
         // notify any streams of the new group
-        let _ = self.local_events.send(LocalEvents::NewGroup(group.clone()));
+        self.publish_local_event(LocalEvents::NewGroup(group.clone()))
+            .await;
 
         Ok(group)
     }
@@ -660,6 +765,19 @@ where
         Ok(MlsGroup::new(self.clone(), group.id, group.created_at_ns))
     }
 
+    /// Look up the DM with `target_inbox_id`, creating it if there is none yet
+    pub(crate) async fn find_or_create_dm_by_inbox_id(
+        &self,
+        target_inbox_id: String,
+    ) -> Result<MlsGroup<Self>, ClientError> {
+        match self.dm_group_from_target_inbox(target_inbox_id.clone()) {
+            Err(ClientError::Storage(StorageError::NotFound(_))) => {
+                self.create_dm_by_inbox_id(target_inbox_id).await
+            }
+            result => result,
+        }
+    }
+
     /// Look up a message by its ID
     /// Returns a [`StoredGroupMessage`] if the message exists, or an error if it does not
     pub fn message(&self, message_id: Vec<u8>) -> Result<StoredGroupMessage, ClientError> {
@@ -696,38 +814,71 @@ where
             .conn()?
             .fetch_conversation_list(args)?
             .into_iter()
-            .map(|conversation_item| {
-                let message = conversation_item.message_id.and_then(|message_id| {
-                    // Only construct StoredGroupMessage if all fields are Some
-                    Some(StoredGroupMessage {
-                        id: message_id,
-                        group_id: conversation_item.id.clone(),
-                        decrypted_message_bytes: conversation_item.decrypted_message_bytes?,
-                        sent_at_ns: conversation_item.sent_at_ns?,
-                        sender_installation_id: conversation_item.sender_installation_id?,
-                        sender_inbox_id: conversation_item.sender_inbox_id?,
-                        kind: conversation_item.kind?,
-                        delivery_status: conversation_item.delivery_status?,
-                        content_type: conversation_item.content_type?,
-                        version_major: conversation_item.version_major?,
-                        version_minor: conversation_item.version_minor?,
-                        authority_id: conversation_item.authority_id?,
-                        reference_id: None, // conversation_item does not use message reference_id
-                    })
-                });
-
-                ConversationListItem {
-                    group: MlsGroup::new(
-                        self.clone(),
-                        conversation_item.id,
-                        conversation_item.created_at_ns,
-                    ),
-                    last_message: message,
+            .map(|conversation_item| self.conversation_list_item(conversation_item))
+            .collect())
+    }
+
+    /// The conversations matching `args` with their last message, unread count and consent
+    /// state, most recently active first. Everything is loaded in a single query, so this is
+    /// the way to build an inbox screen.
+    pub fn list_conversations_overview(
+        &self,
+        args: GroupQueryArgs,
+    ) -> Result<Vec<ConversationOverview<Self>>, ClientError> {
+        Ok(self
+            .store()
+            .conn()?
+            .fetch_conversation_overview(args)?
+            .into_iter()
+            .map(|overview| {
+                let ConversationListItem {
+                    group,
+                    last_message,
+                } = self.conversation_list_item(overview.conversation);
+                ConversationOverview {
+                    group,
+                    last_message,
+                    unread_count: overview.unread_count as u64,
+                    consent_state: overview.consent_state.unwrap_or(ConsentState::Unknown),
                 }
             })
             .collect())
     }
 
+    fn conversation_list_item(
+        &self,
+        conversation_item: StoredConversationListItem,
+    ) -> ConversationListItem<Self> {
+        let message = conversation_item.message_id.and_then(|message_id| {
+            // Only construct StoredGroupMessage if all fields are Some
+            Some(StoredGroupMessage {
+                id: message_id,
+                group_id: conversation_item.id.clone(),
+                decrypted_message_bytes: conversation_item.decrypted_message_bytes?,
+                sent_at_ns: conversation_item.sent_at_ns?,
+                sender_installation_id: conversation_item.sender_installation_id?,
+                sender_inbox_id: conversation_item.sender_inbox_id?,
+                kind: conversation_item.kind?,
+                delivery_status: conversation_item.delivery_status?,
+                content_type: conversation_item.content_type?,
+                version_major: conversation_item.version_major?,
+                version_minor: conversation_item.version_minor?,
+                authority_id: conversation_item.authority_id?,
+                reference_id: None, // conversation_item does not use message reference_id
+                deleted_at_ns: None,
+            })
+        });
+
+        ConversationListItem {
+            group: MlsGroup::new(
+                self.clone(),
+                conversation_item.id,
+                conversation_item.created_at_ns,
+            ),
+            last_message: message,
+        }
+    }
+
     /// Upload a Key Package to the network and publish the signed identity update
     /// from the provided SignatureRequest
     pub async fn register_identity(
@@ -802,11 +953,28 @@ where
         Ok(welcomes)
     }
 
-    /// Fetches the current key package from the network for each of the `installation_id`s specified
+    /// Gets the current key package for each of the `installation_id`s specified, using
+    /// prefetched key packages where available and fetching the rest from the network
     #[tracing::instrument(level = "trace", skip_all)]
     pub(crate) async fn get_key_packages_for_installation_ids(
         &self,
         installation_ids: Vec<Vec<u8>>,
+    ) -> Result<Vec<VerifiedKeyPackageV2>, ClientError> {
+        let mut key_packages = self.context.key_package_cache.take(&installation_ids);
+        let missing: Vec<Vec<u8>> = installation_ids
+            .into_iter()
+            .filter(|id| !key_packages.iter().any(|kp| kp.installation_id() == *id))
+            .collect();
+        if !missing.is_empty() {
+            key_packages.extend(self.fetch_key_packages(missing).await?);
+        }
+        Ok(key_packages)
+    }
+
+    /// Fetches the current key package from the network for each of the `installation_id`s specified
+    pub(crate) async fn fetch_key_packages(
+        &self,
+        installation_ids: Vec<Vec<u8>>,
     ) -> Result<Vec<VerifiedKeyPackageV2>, ClientError> {
         let key_package_results = self.api_client.fetch_key_packages(installation_ids).await?;
 
@@ -841,6 +1009,7 @@ where
                     (async { self.process_new_welcome(provider, &welcome_v1).await })
                 )
                 .ok()
+                .flatten()
             })
             .collect()
             .await;
@@ -856,11 +1025,12 @@ where
     /// Internal API to process a unread welcome message and convert to a group.
     /// In a database transaction, increments the cursor for a given installation and
     /// applies the update after the welcome processed succesfully.
+    /// Returns `None` for a welcome from a blocked inbox, which is dropped.
     async fn process_new_welcome(
         &self,
         provider: &XmtpOpenMlsProvider,
         welcome: &WelcomeMessageV1,
-    ) -> Result<MlsGroup<Self>, GroupError> {
+    ) -> Result<Option<MlsGroup<Self>>, GroupError> {
         provider
             .transaction_async(|provider| async move {
                 let cursor = welcome.id;
@@ -882,7 +1052,12 @@ where
                 .await;
 
                 match result {
-                    Ok(mls_group) => Ok(mls_group),
+                    Ok(mls_group) => Ok(Some(mls_group)),
+                    // the cursor still moves past the welcome, so it isn't fetched again
+                    Err(GroupError::Block(err)) => {
+                        tracing::info!("dropping welcome: {err}");
+                        Ok(None)
+                    }
                     Err(err) => {
                         use crate::DuplicateItem::*;
                         use crate::StorageError::*;
@@ -904,53 +1079,92 @@ where
     }
 
     /// Sync all groups for the current installation and return the number of groups that were synced.
-    /// Only active groups will be synced.
+    /// Only active groups will be synced, restored groups are skipped.
     pub async fn sync_all_groups(
         &self,
         groups: Vec<MlsGroup<Self>>,
         provider: &XmtpOpenMlsProvider,
     ) -> Result<usize, GroupError> {
         let active_group_count = Arc::new(AtomicUsize::new(0));
-
-        let sync_futures = groups
+        // Groups restored from an archive or backup have no MLS state to sync
+        let conn = provider.conn_ref();
+        let is_restored = |group: &MlsGroup<Self>| {
+            matches!(
+                conn.find_group(group.group_id.clone()),
+                Ok(Some(StoredGroup {
+                    membership_state: GroupMembershipState::Restored,
+                    ..
+                }))
+            )
+        };
+        // Conversations open in the UI go first
+        let (open, rest): (Vec<_>, Vec<_>) = groups
             .into_iter()
-            .map(|group| {
-                let active_group_count = Arc::clone(&active_group_count);
-                async move {
-                    tracing::info!(
-                        inbox_id = self.inbox_id(),
-                        "[{}] syncing group",
-                        self.inbox_id()
-                    );
-                    tracing::info!(
-                        inbox_id = self.inbox_id(),
-                        "[{}] syncing group",
-                        self.inbox_id()
-                    );
-                    let is_active = group
-                        .load_mls_group_with_lock_async(provider, |mls_group| async move {
-                            Ok::<bool, GroupError>(mls_group.is_active())
-                        })
-                        .await?;
-                    if is_active {
-                        group.maybe_update_installations(provider, None).await?;
+            .filter(|group| !is_restored(group))
+            .partition(|group| self.context.open_conversations.is_open(&group.group_id));
 
-                        group.sync_with_conn(provider).await?;
-                        active_group_count.fetch_add(1, Ordering::SeqCst);
+        let sync_batch = |groups: Vec<MlsGroup<Self>>| {
+            groups
+                .into_iter()
+                .map(|group| {
+                    let active_group_count = Arc::clone(&active_group_count);
+                    async move {
+                        tracing::info!(
+                            inbox_id = self.inbox_id(),
+                            "[{}] syncing group",
+                            self.inbox_id()
+                        );
+                        tracing::info!(
+                            inbox_id = self.inbox_id(),
+                            "[{}] syncing group",
+                            self.inbox_id()
+                        );
+                        let result = async {
+                            let is_active = group
+                                .load_mls_group_with_lock_async(provider, |mls_group| async move {
+                                    Ok::<bool, GroupError>(mls_group.is_active())
+                                })
+                                .await?;
+                            if is_active {
+                                group.maybe_update_installations(provider, None).await?;
+
+                                group.sync_with_conn(provider).await?;
+                                group.keep_super_admin_heartbeat(provider).await;
+                                active_group_count.fetch_add(1, Ordering::SeqCst);
+                            }
+                            Ok::<(), GroupError>(())
+                        }
+                        .await;
+                        (group.group_id, result)
                     }
+                })
+                .collect::<FuturesUnordered<_>>()
+        };
 
-                    Ok::<(), GroupError>(())
+        // A group that fails to sync doesn't hold up the others, the first error is returned
+        // once every group was tried
+        let mut first_error = None;
+        for batch in [open, rest] {
+            for (group_id, result) in sync_batch(batch).collect::<Vec<_>>().await {
+                if let Err(e) = result {
+                    tracing::warn!(
+                        group_id = hex::encode(&group_id),
+                        "failed to sync group: {e}"
+                    );
+                    first_error.get_or_insert(e);
                 }
-            })
-            .collect::<FuturesUnordered<_>>();
+            }
+        }
 
-        sync_futures
-            .collect::<Vec<Result<_, _>>>()
-            .await
-            .into_iter()
-            .collect::<Result<Vec<_>, _>>()?;
+        // Redemptions arrive in DMs synced above
+        if let Err(e) = self.admit_invite_redemptions().await {
+            tracing::warn!("failed to admit invite redemptions: {e}");
+        }
 
-        Ok(active_group_count.load(Ordering::SeqCst))
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(active_group_count.load(Ordering::SeqCst)),
+        }
     }
 
     /// Sync all unread welcome messages and then sync all groups.
@@ -974,10 +1188,39 @@ where
             .map(|g| MlsGroup::new(self.clone(), g.id, g.created_at_ns))
             .collect();
         let active_groups_count = self.sync_all_groups(groups, provider).await?;
+        if let Err(e) = self.recover_forked_groups().await {
+            tracing::warn!("failed to recover forked groups: {e}");
+        }
 
         Ok(active_groups_count)
     }
 
+    /// Resolve message processing that was interrupted, e.g. by the app being killed mid-sync.
+    /// Returns the ids of groups whose interrupted envelope was rolled back and will be
+    /// processed again on the next sync.
+    pub fn recover_interrupted_processing(&self) -> Result<Vec<Vec<u8>>, ClientError> {
+        let provider = self.mls_provider()?;
+        let mut rolled_back = vec![];
+        for checkpoint in provider.conn_ref().processing_checkpoints()? {
+            let resolution = provider.transaction(|provider| {
+                provider
+                    .conn_ref()
+                    .resolve_processing_checkpoint(&checkpoint)
+            })?;
+            tracing::warn!(
+                inbox_id = self.inbox_id(),
+                group_id = hex::encode(&checkpoint.group_id),
+                cursor = checkpoint.cursor,
+                "recovered interrupted message processing: {resolution:?}"
+            );
+            if resolution == CheckpointResolution::RolledBack {
+                rolled_back.push(checkpoint.group_id);
+            }
+        }
+
+        Ok(rolled_back)
+    }
+
     /**
      * Validates a credential against the given installation public key
      *
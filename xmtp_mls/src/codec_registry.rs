@@ -0,0 +1,418 @@
+//! The codecs the client encodes and decodes message content with.
+//!
+//! Each client has a [`CodecRegistry`], which starts out with codecs for the standard content
+//! types. Apps register codecs for their own content types on top with
+//! [`Client::register_codec`], after which messages of those types are decoded like any other,
+//! instead of every binding carrying its own codec layer. Codecs are looked up by authority,
+//! type and major version, so a codec decodes every minor version of its content type.
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{Arc, LazyLock},
+};
+
+use parking_lot::RwLock;
+use xmtp_content_types::{
+    attachment::{Attachment, AttachmentCodec},
+    capabilities::CapabilitiesCodec,
+    group_updated::GroupUpdatedCodec,
+    link_preview::{LinkPreviewCodec, TextWithLinkPreview},
+    membership_change::GroupMembershipChangeCodec,
+    reaction::ReactionCodec,
+    read_receipt::{ReadReceipt, ReadReceiptCodec},
+    remote_attachment::{RemoteAttachment, RemoteAttachmentCodec},
+    reply::{Reply, ReplyCodec},
+    text::TextCodec,
+    transaction_reference::{TransactionReference, TransactionReferenceCodec},
+    CodecError, ContentCodec,
+};
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::xmtp::mls::message_contents::{
+    content_types::ReactionV2, ContentTypeId, EncodedContent,
+};
+
+use crate::{
+    decoded_message::{CustomContent, DecodedContent},
+    Client, XmtpApi,
+};
+
+/// The codecs of the standard content types, for encoding and decoding without a client
+static BUILTIN_CODECS: LazyLock<CodecRegistry> = LazyLock::new(CodecRegistry::default);
+
+/// Decodes the content of one content type
+pub trait Codec: Send + Sync {
+    fn content_type(&self) -> ContentTypeId;
+    fn decode(&self, content: EncodedContent) -> Result<DecodedContent, CodecError>;
+}
+
+/// Content that can be sent with [`MlsGroup::send_content`]. Content of a custom content type is
+/// sent already encoded, as an [`EncodedContent`].
+///
+/// [`MlsGroup::send_content`]: crate::groups::MlsGroup::send_content
+pub trait EncodableContent {
+    fn encode(self) -> Result<EncodedContent, CodecError>;
+}
+
+impl EncodableContent for String {
+    fn encode(self) -> Result<EncodedContent, CodecError> {
+        TextCodec::encode(self)
+    }
+}
+
+impl EncodableContent for &str {
+    fn encode(self) -> Result<EncodedContent, CodecError> {
+        TextCodec::encode(self.to_string())
+    }
+}
+
+impl EncodableContent for ReactionV2 {
+    fn encode(self) -> Result<EncodedContent, CodecError> {
+        ReactionCodec::encode(self)
+    }
+}
+
+impl EncodableContent for Reply {
+    fn encode(self) -> Result<EncodedContent, CodecError> {
+        ReplyCodec::encode(self)
+    }
+}
+
+impl EncodableContent for Attachment {
+    fn encode(self) -> Result<EncodedContent, CodecError> {
+        AttachmentCodec::encode(self)
+    }
+}
+
+impl EncodableContent for RemoteAttachment {
+    fn encode(self) -> Result<EncodedContent, CodecError> {
+        RemoteAttachmentCodec::encode(self)
+    }
+}
+
+impl EncodableContent for ReadReceipt {
+    fn encode(self) -> Result<EncodedContent, CodecError> {
+        ReadReceiptCodec::encode(self)
+    }
+}
+
+impl EncodableContent for TransactionReference {
+    fn encode(self) -> Result<EncodedContent, CodecError> {
+        TransactionReferenceCodec::encode(self)
+    }
+}
+
+impl EncodableContent for TextWithLinkPreview {
+    fn encode(self) -> Result<EncodedContent, CodecError> {
+        LinkPreviewCodec::encode(self)
+    }
+}
+
+impl EncodableContent for EncodedContent {
+    fn encode(self) -> Result<EncodedContent, CodecError> {
+        Ok(self)
+    }
+}
+
+/// A [`ContentCodec`] of the content types crate, decoding into a [`DecodedContent`] variant
+struct BuiltinCodec<C, T> {
+    wrap: fn(T) -> DecodedContent,
+    codec: PhantomData<fn() -> C>,
+}
+
+impl<C, T> BuiltinCodec<C, T> {
+    fn new(wrap: fn(T) -> DecodedContent) -> Arc<Self> {
+        Arc::new(Self {
+            wrap,
+            codec: PhantomData,
+        })
+    }
+}
+
+impl<C: ContentCodec<T>, T> Codec for BuiltinCodec<C, T> {
+    fn content_type(&self) -> ContentTypeId {
+        C::content_type()
+    }
+
+    fn decode(&self, content: EncodedContent) -> Result<DecodedContent, CodecError> {
+        C::decode(content).map(self.wrap)
+    }
+}
+
+/// Codec of an app's content type whose content is JSON, decoding into
+/// [`DecodedContent::Custom`]. Lets apps whose codecs live outside of Rust, like those of the
+/// bindings, register their content types.
+pub struct JsonCodec {
+    content_type: ContentTypeId,
+}
+
+impl JsonCodec {
+    pub fn new(content_type: ContentTypeId) -> Arc<Self> {
+        Arc::new(Self { content_type })
+    }
+}
+
+impl Codec for JsonCodec {
+    fn content_type(&self) -> ContentTypeId {
+        self.content_type.clone()
+    }
+
+    fn decode(&self, content: EncodedContent) -> Result<DecodedContent, CodecError> {
+        let value = serde_json::from_slice(&content.content)
+            .map_err(|e| CodecError::Decode(e.to_string()))?;
+        Ok(DecodedContent::Custom(CustomContent {
+            content_type: content.r#type.unwrap_or_else(|| self.content_type()),
+            value,
+        }))
+    }
+}
+
+/// The key codecs are registered under, e.g. `xmtp.org/text:1`
+fn codec_key(content_type: &ContentTypeId) -> String {
+    format!(
+        "{}/{}:{}",
+        content_type.authority_id, content_type.type_id, content_type.version_major
+    )
+}
+
+pub struct CodecRegistry {
+    codecs: RwLock<HashMap<String, Arc<dyn Codec>>>,
+}
+
+impl Default for CodecRegistry {
+    fn default() -> Self {
+        let registry = Self {
+            codecs: RwLock::default(),
+        };
+        registry.register(BuiltinCodec::<TextCodec, _>::new(DecodedContent::Text));
+        // legacy JSON encoded reactions are version 1, and left undecoded
+        registry.register(BuiltinCodec::<ReactionCodec, _>::new(
+            DecodedContent::Reaction,
+        ));
+        registry.register(BuiltinCodec::<ReplyCodec, _>::new(DecodedContent::Reply));
+        registry.register(BuiltinCodec::<AttachmentCodec, _>::new(
+            DecodedContent::Attachment,
+        ));
+        registry.register(BuiltinCodec::<RemoteAttachmentCodec, _>::new(
+            DecodedContent::RemoteAttachment,
+        ));
+        registry.register(BuiltinCodec::<ReadReceiptCodec, _>::new(
+            DecodedContent::ReadReceipt,
+        ));
+        registry.register(BuiltinCodec::<TransactionReferenceCodec, _>::new(
+            DecodedContent::TransactionReference,
+        ));
+        registry.register(BuiltinCodec::<LinkPreviewCodec, _>::new(
+            DecodedContent::LinkPreview,
+        ));
+        registry.register(BuiltinCodec::<GroupUpdatedCodec, _>::new(
+            DecodedContent::GroupUpdated,
+        ));
+        registry.register(BuiltinCodec::<GroupMembershipChangeCodec, _>::new(
+            DecodedContent::GroupMembershipChange,
+        ));
+        registry.register(BuiltinCodec::<CapabilitiesCodec, _>::new(
+            DecodedContent::Capabilities,
+        ));
+        registry
+    }
+}
+
+impl CodecRegistry {
+    /// The codecs of the standard content types, for use without a client
+    pub fn builtin() -> &'static CodecRegistry {
+        &BUILTIN_CODECS
+    }
+
+    /// Register `codec`, replacing the codec registered for the same content type
+    pub fn register(&self, codec: Arc<dyn Codec>) {
+        let key = codec_key(&codec.content_type());
+        self.codecs.write().insert(key, codec);
+    }
+
+    pub fn is_registered(&self, content_type: &ContentTypeId) -> bool {
+        self.codecs.read().contains_key(&codec_key(content_type))
+    }
+
+    /// Whether a codec is registered for a major version of the content type other than the
+    /// one of `content_type`
+    pub fn has_other_version(&self, content_type: &ContentTypeId) -> bool {
+        self.codecs.read().values().any(|codec| {
+            let registered = codec.content_type();
+            registered.authority_id == content_type.authority_id
+                && registered.type_id == content_type.type_id
+                && registered.version_major != content_type.version_major
+        })
+    }
+
+    /// Decode `content` with the codec for its content type, or leave it
+    /// [`DecodedContent::Unknown`] if there is none
+    pub fn decode(&self, content: EncodedContent) -> Result<DecodedContent, CodecError> {
+        let codec = content
+            .r#type
+            .as_ref()
+            .and_then(|content_type| self.codecs.read().get(&codec_key(content_type)).cloned());
+        match codec {
+            Some(codec) => codec.decode(content),
+            None => Ok(DecodedContent::Unknown(content)),
+        }
+    }
+
+    /// Encode `content`, checking that there's a codec registered for its content type so
+    /// other installations of the app can decode it
+    pub fn encode(&self, content: impl EncodableContent) -> Result<EncodedContent, CodecError> {
+        let encoded = content.encode()?;
+        let content_type = encoded
+            .r#type
+            .as_ref()
+            .ok_or_else(|| CodecError::Encode("content has no content type".to_string()))?;
+        if !self.is_registered(content_type) {
+            return Err(CodecError::Encode(format!(
+                "no codec registered for {}",
+                codec_key(content_type)
+            )));
+        }
+        Ok(encoded)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Register `codec` to encode and decode its content type, replacing a codec registered for
+    /// the same content type, including the built-in ones
+    pub fn register_codec(&self, codec: Arc<dyn Codec>) {
+        self.context.codecs.register(codec)
+    }
+
+    pub fn codecs(&self) -> &CodecRegistry {
+        &self.context.codecs
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        decoded_message::CustomContent,
+        groups::{GroupMetadataOptions, MlsGroup},
+        storage::group_message::{GroupMessageKind, MsgQueryArgs},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    /// A content type of the app, with JSON content
+    struct StickerCodec;
+
+    impl Codec for StickerCodec {
+        fn content_type(&self) -> ContentTypeId {
+            ContentTypeId {
+                authority_id: "example.com".to_string(),
+                type_id: "sticker".to_string(),
+                version_major: 1,
+                version_minor: 0,
+            }
+        }
+
+        fn decode(&self, content: EncodedContent) -> Result<DecodedContent, CodecError> {
+            let value = serde_json::from_slice(&content.content)
+                .map_err(|e| CodecError::Decode(e.to_string()))?;
+            Ok(DecodedContent::Custom(CustomContent {
+                content_type: self.content_type(),
+                value,
+            }))
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_registered_codecs_decode_messages() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+
+        let sticker = EncodedContent {
+            r#type: Some(StickerCodec.content_type()),
+            content: br#"{"pack":"cats","id":7}"#.to_vec(),
+            ..Default::default()
+        };
+        // can't send a content type without a codec
+        assert!(group.send_content(sticker.clone()).await.is_err());
+        alix.register_codec(Arc::new(StickerCodec));
+        group.send_content("hello").await.unwrap();
+        group.send_content(sticker).await.unwrap();
+        // not even encoded content
+        group.send_message(&[0xff]).await.unwrap();
+
+        bo_group.sync().await.unwrap();
+        let args = MsgQueryArgs {
+            kind: Some(GroupMessageKind::Application),
+            ..Default::default()
+        };
+        let contents = |group: &MlsGroup<_>| {
+            group
+                .find_decoded_messages(&args)
+                .unwrap()
+                .into_iter()
+                .map(|message| message.content)
+                .collect::<Vec<_>>()
+        };
+        // bo has no codec for stickers yet
+        let decoded = contents(&bo_group);
+        assert_eq!(decoded[0], DecodedContent::Text("hello".to_string()));
+        assert!(matches!(decoded[1], DecodedContent::Unknown(_)));
+        // messages that can't be decoded at all are kept as their raw bytes
+        let DecodedContent::Unknown(raw) = &decoded[2] else {
+            panic!("undecodable message wasn't kept");
+        };
+        assert_eq!(raw.content, vec![0xff]);
+        assert!(raw.r#type.is_none());
+
+        // apps with codecs outside of Rust register their JSON content types
+        bo.register_codec(JsonCodec::new(StickerCodec.content_type()));
+        let DecodedContent::Custom(sticker) = contents(&bo_group).remove(1) else {
+            panic!("sticker wasn't decoded");
+        };
+        assert_eq!(sticker.value["pack"], "cats");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_builtin_codecs() {
+        let receipt = ReadReceipt {
+            reference: Some("0a0b".to_string()),
+        };
+        let encoded = CodecRegistry::builtin().encode(receipt.clone()).unwrap();
+        assert_eq!(
+            CodecRegistry::builtin().decode(encoded).unwrap(),
+            DecodedContent::ReadReceipt(receipt)
+        );
+        let transaction = TransactionReference {
+            network_id: "1".to_string(),
+            reference: "0xabc".to_string(),
+            ..Default::default()
+        };
+        let encoded = CodecRegistry::builtin()
+            .encode(transaction.clone())
+            .unwrap();
+        assert_eq!(
+            CodecRegistry::builtin().decode(encoded).unwrap(),
+            DecodedContent::TransactionReference(transaction)
+        );
+    }
+}
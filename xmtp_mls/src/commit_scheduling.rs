@@ -0,0 +1,249 @@
+//! Cooperative scheduling of commit work, to keep the host's threads responsive.
+//!
+//! Syncing a busy group can apply or publish many commits back to back. Between commits the
+//! sync periodically yields to the runtime, and while the host reports active UI interaction
+//! through [`Client::set_ui_interaction_active`] each commit waits for the interaction to end,
+//! for at most [`CommitSchedulingPolicy::max_deferral`], before it is applied or published.
+//! Application messages don't wait on the interaction themselves, but a batch is processed in
+//! order, so those that come after a deferred commit are delayed along with it. A commit to
+//! publish waits without holding the group's lock, once the intents queued before it are sent.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use openmls::{
+    framing::ContentType as MlsContentType,
+    prelude::{tls_codec::Deserialize, MlsMessageBodyIn, MlsMessageIn},
+};
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+use xmtp_common::time::Duration;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::xmtp::mls::api::v1::{group_message::Version as GroupMessageVersion, GroupMessage};
+
+use crate::{
+    storage::group_intent::{IntentKind, StoredGroupIntent},
+    Client, XmtpApi,
+};
+
+/// How commit work is spread out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitSchedulingPolicy {
+    /// Yield to the runtime after this many messages or intents in a row
+    pub yield_every: usize,
+    /// The longest a commit waits for UI interaction to end
+    pub max_deferral: Duration,
+}
+
+impl Default for CommitSchedulingPolicy {
+    fn default() -> Self {
+        Self {
+            yield_every: 10,
+            max_deferral: Duration::from_secs(2),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct CommitScheduler {
+    policy: Mutex<CommitSchedulingPolicy>,
+    interacting: AtomicBool,
+    interaction_ended: Notify,
+}
+
+impl CommitScheduler {
+    pub fn is_interacting(&self) -> bool {
+        self.interacting.load(Ordering::SeqCst)
+    }
+
+    fn set_interacting(&self, active: bool) {
+        let was_interacting = self.interacting.swap(active, Ordering::SeqCst);
+        if was_interacting && !active {
+            self.interaction_ended.notify_waiters();
+        }
+    }
+
+    /// Called before processing the `index`th message of a batch
+    pub(crate) async fn before_message(&self, index: usize, message: &GroupMessage) {
+        self.checkpoint(index, || is_commit(message)).await
+    }
+
+    /// Called before publishing the `index`th intent of a batch. Returns whether the intent is
+    /// a commit to hold back: the caller releases the group's lock and calls
+    /// [`Self::wait_for_interaction`] before publishing it.
+    pub(crate) async fn before_intent(&self, index: usize, intent: &StoredGroupIntent) -> bool {
+        self.yield_periodically(index).await;
+        intent.kind != IntentKind::SendMessage && self.is_interacting()
+    }
+
+    async fn checkpoint(&self, index: usize, is_commit: impl FnOnce() -> bool) {
+        self.yield_periodically(index).await;
+        if is_commit() {
+            self.wait_for_interaction().await;
+        }
+    }
+
+    async fn yield_periodically(&self, index: usize) {
+        let yield_every = self.policy.lock().yield_every;
+        if index > 0 && index % yield_every.max(1) == 0 {
+            xmtp_common::yield_().await;
+        }
+    }
+
+    /// Wait for the UI interaction to end, for at most the policy's `max_deferral`
+    pub(crate) async fn wait_for_interaction(&self) {
+        let max_deferral = self.policy.lock().max_deferral;
+        // created before checking the flag, so the end of the interaction can't be missed
+        let interaction_ended = self.interaction_ended.notified();
+        if !self.is_interacting() {
+            return;
+        }
+        tracing::debug!("deferring commit while the UI is active");
+        if xmtp_common::time::timeout(max_deferral, interaction_ended)
+            .await
+            .is_err()
+        {
+            tracing::debug!("UI still active, applying deferred commit");
+        }
+    }
+}
+
+/// Whether `message` is a commit. Messages that can't be parsed aren't held back, since they
+/// will fail processing regardless.
+fn is_commit(message: &GroupMessage) -> bool {
+    let Some(GroupMessageVersion::V1(message)) = &message.version else {
+        return false;
+    };
+    match MlsMessageIn::tls_deserialize_exact(&message.data).map(MlsMessageIn::extract) {
+        Ok(MlsMessageBodyIn::PrivateMessage(message)) => {
+            message.content_type() == MlsContentType::Commit
+        }
+        _ => false,
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Report whether the user is interacting with the app. While active, commits wait for
+    /// the interaction to end, up to the scheduling policy's `max_deferral`.
+    pub fn set_ui_interaction_active(&self, active: bool) {
+        self.context.commit_scheduler.set_interacting(active)
+    }
+
+    pub fn set_commit_scheduling_policy(&self, policy: CommitSchedulingPolicy) {
+        *self.context.commit_scheduler.policy.lock() = policy;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_common::time::Instant;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 2))]
+    async fn test_commits_deferred_during_ui_interaction() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+        group
+            .add_members_by_inbox_id(&[caro.inbox_id()])
+            .await
+            .unwrap();
+
+        let max_deferral = Duration::from_millis(500);
+        bo.set_commit_scheduling_policy(CommitSchedulingPolicy {
+            yield_every: 1,
+            max_deferral,
+        });
+        bo.set_ui_interaction_active(true);
+
+        // the commit waits out the interaction, then is applied anyway
+        let started = Instant::now();
+        bo_group.sync().await.unwrap();
+        assert!(started.elapsed() >= max_deferral);
+        assert_eq!(bo_group.members().await.unwrap().len(), 3);
+
+        // ending the interaction releases waiting commits right away
+        group.update_group_name("renamed".into()).await.unwrap();
+        let bo_sync = bo_group.sync();
+        futures::pin_mut!(bo_sync);
+        assert!(
+            xmtp_common::time::timeout(Duration::from_millis(50), &mut bo_sync)
+                .await
+                .is_err()
+        );
+        bo.set_ui_interaction_active(false);
+        bo_sync.await.unwrap();
+        assert_eq!(
+            bo_group.group_name(&bo.mls_provider().unwrap()).unwrap(),
+            "renamed"
+        );
+    }
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 2))]
+    async fn test_deferred_commit_does_not_hold_the_group() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+
+        alix.set_commit_scheduling_policy(CommitSchedulingPolicy {
+            yield_every: 1,
+            max_deferral: Duration::from_secs(5),
+        });
+        alix.set_ui_interaction_active(true);
+
+        // the message queued before the commit is published while the commit waits
+        group.send_message_optimistic(b"before").unwrap();
+        let rename = group.update_group_name("renamed".into());
+        futures::pin_mut!(rename);
+        assert!(
+            xmtp_common::time::timeout(Duration::from_millis(200), &mut rename)
+                .await
+                .is_err()
+        );
+
+        // and the group isn't locked in the meantime
+        let started = Instant::now();
+        bo_group.sync().await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(bo_group
+            .find_messages(&Default::default())
+            .unwrap()
+            .iter()
+            .any(|m| m.decrypted_message_bytes == b"before"));
+
+        alix.set_ui_interaction_active(false);
+        rename.await.unwrap();
+    }
+}
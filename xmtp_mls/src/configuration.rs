@@ -12,11 +12,16 @@ pub const MAX_GROUP_SYNC_RETRIES: usize = 3;
 
 pub const MAX_INTENT_PUBLISH_ATTEMPTS: usize = 3;
 
-const NS_IN_SEC: i64 = 1_000_000_000;
+/// The most messages of a group published in a single request
+pub const MAX_PUBLISH_BATCH_SIZE: usize = 50;
+
+pub const NS_IN_MS: i64 = 1_000_000;
+
+pub const NS_IN_SEC: i64 = 1_000_000_000;
 
 pub const NS_IN_HOUR: i64 = NS_IN_SEC * 60 * 60;
 
-const NS_IN_DAY: i64 = NS_IN_HOUR * 24;
+pub const NS_IN_DAY: i64 = NS_IN_HOUR * 24;
 
 pub const GROUP_KEY_ROTATION_INTERVAL_NS: i64 = 30 * NS_IN_DAY;
 
@@ -24,12 +29,34 @@ pub const SYNC_UPDATE_INSTALLATIONS_INTERVAL_NS: i64 = NS_IN_HOUR / 2; // 30 min
 
 pub const SEND_MESSAGE_UPDATE_INSTALLATIONS_INTERVAL_NS: i64 = 5 * NS_IN_SEC;
 
+pub const INACTIVE_CONVERSATION_THRESHOLD_NS: i64 = 180 * NS_IN_DAY; // ~6 months
+
+pub const RECONSENT_PROMPT_INTERVAL_NS: i64 = 30 * NS_IN_DAY;
+
+pub const MAX_RECONSENT_PROMPTS: usize = 10;
+
+pub const MAX_PINNED_MESSAGES: usize = 50;
+
+pub const DEFAULT_MESSAGE_PAGE_SIZE: i64 = 50;
+
+/// The most pages of messages prefetched at once for an open conversation
+pub const MAX_PREFETCH_PAGES: i64 = 4;
+
+pub const STALE_INSTALLATION_THRESHOLD_NS: i64 = 90 * NS_IN_DAY; // ~3 months
+
 pub const MAX_GROUP_SIZE: usize = 400;
 
 pub const MAX_PAST_EPOCHS: usize = 3;
 
+/// Messages in a row from the group's current or later epochs that must fail to decrypt before
+/// the group is considered forked
+pub const FORK_DETECTION_THRESHOLD: u32 = 3;
+
 pub const MAX_DB_POOL_SIZE: u32 = 25;
 
+/// The most recent slow statements kept by the slow query log
+pub const SLOW_QUERY_LOG_CAPACITY: usize = 100;
+
 /// the max amount of data that can be sent in one gRPC call
 /// we leave 5 * 1024 * 1024 as extra buffer room
 pub const GRPC_DATA_LIMIT: usize = 45 * 1024 * 1024;
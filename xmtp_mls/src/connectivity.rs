@@ -0,0 +1,249 @@
+//! Offline mode.
+//!
+//! The client goes offline when a network call fails because the API can't be reached, or when
+//! the host reports it through [`Client::set_online`], e.g. from the OS's reachability callbacks.
+//! While offline, sends are queued in the outbox instead of waiting on the network, and the
+//! connectivity monitor started with [`Client::start_connectivity_monitor`] probes the API. Once
+//! it's reachable again the client syncs its welcomes and flushes the outbox. Each change is
+//! emitted as a [`LocalEvents::ConnectivityChanged`] event.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tokio::sync::Notify;
+use xmtp_common::time::Duration;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use crate::{
+    api::WrappedApiError,
+    client::ClientError,
+    deferred_startup::is_offline_error,
+    groups::{scoped_client::ScopedGroupClient, GroupError, MlsGroup},
+    subscriptions::LocalEvents,
+    CancellationToken, Client, StreamHandle, StreamMetrics, XmtpApi,
+};
+
+/// How often the connectivity monitor probes the API while offline
+pub const CONNECTIVITY_PROBE_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectivityState {
+    Online,
+    Offline,
+}
+
+/// Whether the client can reach the API, as far as it knows
+#[derive(Default)]
+pub struct Connectivity {
+    offline: AtomicBool,
+    went_offline: Notify,
+}
+
+impl Connectivity {
+    pub fn is_online(&self) -> bool {
+        !self.offline.load(Ordering::SeqCst)
+    }
+
+    /// Record the connectivity state. Returns whether it changed.
+    pub(crate) fn set_online(&self, online: bool) -> bool {
+        let was_offline = self.offline.swap(!online, Ordering::SeqCst);
+        let changed = was_offline == online;
+        if changed && !online {
+            self.went_offline.notify_one();
+        }
+        changed
+    }
+}
+
+/// Whether `err` means the API couldn't be reached, rather than that it rejected the request.
+/// A sync failed by the network only if all of its errors are connection errors.
+pub(crate) fn is_offline_group_error(err: &GroupError) -> bool {
+    match err {
+        GroupError::Api(err) | GroupError::WrappedApi(WrappedApiError::Api(err)) => {
+            err.is_connection_error()
+        }
+        GroupError::Client(err) => is_offline_error(err),
+        GroupError::Sync(errors) => !errors.is_empty() && errors.iter().all(is_offline_group_error),
+        _ => false,
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Take the client offline if `err` means the API couldn't be reached. Returns whether it
+    /// did.
+    pub(crate) fn note_offline_error(&self, err: &GroupError) -> bool {
+        if !is_offline_group_error(err) {
+            return false;
+        }
+        if self.context().connectivity.set_online(false) {
+            tracing::info!("going offline: {err}");
+            let _ = self
+                .client
+                .local_events()
+                .send(LocalEvents::ConnectivityChanged(ConnectivityState::Offline));
+        }
+        true
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    pub fn is_online(&self) -> bool {
+        self.context.connectivity.is_online()
+    }
+
+    /// Report whether the network is available. Going online wakes the outbox publisher, if
+    /// it's running.
+    pub fn set_online(&self, online: bool) {
+        if !self.context.connectivity.set_online(online) {
+            return;
+        }
+        let state = if online {
+            tracing::info!("connectivity restored");
+            // let the outbox publisher retry right away
+            self.context.outbox.wake();
+            ConnectivityState::Online
+        } else {
+            tracing::info!("going offline");
+            ConnectivityState::Offline
+        };
+        let _ = self
+            .local_events()
+            .send(LocalEvents::ConnectivityChanged(state));
+    }
+
+    /// Probe the API by syncing welcomes. If it's reachable, go online and publish the messages
+    /// queued in the outbox. Returns whether the client is online.
+    pub async fn reconnect(&self) -> Result<bool, ClientError> {
+        let provider = self.mls_provider()?;
+        match self.sync_welcomes(&provider).await {
+            Ok(_) => {}
+            Err(e) if is_offline_error(&e) => {
+                tracing::debug!("API still unreachable: {e}");
+                return Ok(false);
+            }
+            Err(e) => return Err(e),
+        }
+        self.set_online(true);
+        self.publish_outbox().await?;
+        Ok(true)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Wait for the client to go offline, then [`Client::reconnect`] every `probe_interval` until
+    /// it's back online. Runs until the returned handle is ended.
+    pub fn start_connectivity_monitor(
+        &self,
+        probe_interval: Duration,
+    ) -> impl StreamHandle<StreamOutput = Result<(), ClientError>> {
+        let client = self.clone();
+        let cancel = CancellationToken::new();
+        let stopped = cancel.clone();
+
+        crate::spawn_cancellable(None, StreamMetrics::default(), cancel, async move {
+            while !stopped.is_cancelled() {
+                if client.is_online() {
+                    tokio::select! {
+                        _ = client.context.connectivity.went_offline.notified() => {}
+                        _ = stopped.cancelled() => {}
+                    }
+                    continue;
+                }
+                let _ = xmtp_common::time::timeout(probe_interval, stopped.cancelled()).await;
+                if let Err(e) = client.reconnect().await {
+                    tracing::warn!("failed to reconnect: {e}");
+                }
+            }
+            Ok::<_, ClientError>(())
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions,
+        storage::group_message::DeliveryStatus, subscriptions::StreamMessages,
+    };
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_offline_sends_flush_on_reconnect() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let changes = alix
+            .local_events()
+            .subscribe()
+            .stream_connectivity_changes();
+        futures::pin_mut!(changes);
+        let mut monitor = alix.start_connectivity_monitor(Duration::from_millis(500));
+
+        alix.set_online(false);
+        assert_eq!(
+            changes.next().await.unwrap().unwrap(),
+            ConnectivityState::Offline
+        );
+        // sends are queued instead of failing
+        let message_id = group.send_message(b"while offline").await.unwrap();
+        let conn = alix.store().conn().unwrap();
+        assert_eq!(
+            conn.get_group_message(&message_id)
+                .unwrap()
+                .unwrap()
+                .delivery_status,
+            DeliveryStatus::Unpublished
+        );
+
+        // the local node is reachable, so the monitor reconnects and flushes the outbox
+        assert_eq!(
+            changes.next().await.unwrap().unwrap(),
+            ConnectivityState::Online
+        );
+        xmtp_common::time::timeout(Duration::from_secs(10), async {
+            while !conn.groups_with_unpublished_messages().unwrap().is_empty() {
+                xmtp_common::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            conn.get_group_message(&message_id)
+                .unwrap()
+                .unwrap()
+                .delivery_status,
+            DeliveryStatus::Published
+        );
+        let _ = monitor.end_and_wait().await;
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_rejections_are_not_offline() {
+        use xmtp_proto::{Error as ApiError, ErrorKind};
+
+        let unreachable = || GroupError::Api(ApiError::new(ErrorKind::SetupConnectionError));
+        let rejected = || GroupError::Api(ApiError::new(ErrorKind::MlsError));
+        assert!(is_offline_group_error(&unreachable()));
+        assert!(!is_offline_group_error(&rejected()));
+        assert!(is_offline_group_error(&GroupError::Sync(vec![
+            unreachable()
+        ])));
+        assert!(!is_offline_group_error(&GroupError::Sync(vec![
+            unreachable(),
+            rejected()
+        ])));
+    }
+}
@@ -0,0 +1,221 @@
+//! Message streams sharded by consent state, so that the main inbox can stream in realtime
+//! while message requests are only caught up on when the user looks at them.
+//!
+//! Each shard subscribes only to the conversations in its consent states and keeps its own
+//! per-conversation position, so reopening a shard resumes where it left off without
+//! affecting the other one.
+use std::collections::HashMap;
+
+use futures::{Stream, StreamExt};
+use xmtp_common::time::now_ns;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::api_client::XmtpMlsStreams;
+
+use crate::{
+    client::ClientError,
+    storage::{
+        consent_record::ConsentState,
+        db_connection::DbConnection,
+        group::{ConversationType, GroupQueryArgs},
+        group_message::{MsgQueryArgs, StoredGroupMessage},
+        refresh_state::EntityKind,
+        StorageError,
+    },
+    subscriptions::{
+        MessageStreamFilter, MessagesStreamInfo, StreamAllMessagesOptions, SubscribeError,
+    },
+    Client, XmtpApi,
+};
+
+/// A part of the inbox streamed independently of the rest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsentShard {
+    /// Conversations the user has allowed
+    Allowed,
+    /// Conversations the user hasn't decided on yet
+    Requests,
+}
+
+impl ConsentShard {
+    pub fn consent_states(&self) -> Vec<ConsentState> {
+        match self {
+            Self::Allowed => vec![ConsentState::Allowed],
+            Self::Requests => vec![ConsentState::Unknown],
+        }
+    }
+
+    fn entity_kind(&self) -> EntityKind {
+        match self {
+            Self::Allowed => EntityKind::AllowedShard,
+            Self::Requests => EntityKind::RequestsShard,
+        }
+    }
+
+    /// Record that the shard has yielded everything in `group_id` up to `sent_at_ns`
+    fn advance(
+        &self,
+        conn: &DbConnection,
+        group_id: &[u8],
+        sent_at_ns: i64,
+    ) -> Result<(), StorageError> {
+        // creates the position if the conversation joined the shard after it was opened
+        conn.get_last_cursor_for_id(group_id, self.entity_kind())?;
+        conn.update_cursor(group_id, self.entity_kind(), sent_at_ns)?;
+        Ok(())
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi + XmtpMlsStreams + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Stream messages from the conversations in `shard`. The first time a conversation is
+    /// streamed in a shard only new messages are yielded; after that, reopening the shard
+    /// first yields the stored messages sent since the shard last yielded from it.
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn stream_consent_shard(
+        &self,
+        shard: ConsentShard,
+        conversation_type: Option<ConversationType>,
+        filter: MessageStreamFilter,
+    ) -> Result<impl Stream<Item = Result<StoredGroupMessage, SubscribeError>> + '_, ClientError>
+    {
+        tracing::debug!(
+            inbox_id = self.inbox_id(),
+            shard = ?shard,
+            conversation_type = ?conversation_type,
+            "stream consent shard"
+        );
+        let provider = self.mls_provider()?;
+        self.sync_welcomes(&provider).await?;
+        let conn = provider.conn_ref();
+
+        let groups = conn.find_groups(
+            GroupQueryArgs::default()
+                .maybe_conversation_type(conversation_type)
+                .consent_states(shard.consent_states()),
+        )?;
+        let mut group_id_to_info = HashMap::new();
+        let mut stored = vec![];
+        for group in groups {
+            let position = conn.get_last_cursor_for_id(&group.id, shard.entity_kind())?;
+            if position == 0 {
+                conn.update_cursor(&group.id, shard.entity_kind(), now_ns())?;
+            } else {
+                let query = MsgQueryArgs {
+                    sent_after_ns: Some(position),
+                    ..Default::default()
+                };
+                stored.extend(
+                    conn.get_group_messages(&group.id, &query)?
+                        .into_iter()
+                        .filter(|message| filter.matches(message, self.inbox_id())),
+                );
+            }
+            let cursor = conn.get_last_cursor_for_id(&group.id, EntityKind::Group)?;
+            group_id_to_info.insert(
+                group.id,
+                MessagesStreamInfo {
+                    convo_created_at_ns: group.created_at_ns,
+                    cursor: cursor as u64,
+                },
+            );
+        }
+        stored.sort_by_key(|message| message.sent_at_ns);
+
+        let opts = StreamAllMessagesOptions {
+            conversation_type,
+            consent_states: Some(shard.consent_states()),
+            filter,
+            ..Default::default()
+        };
+        let stream = self
+            .stream_messages_from(stored, group_id_to_info, opts, None)
+            .inspect(move |message| {
+                if let Ok(message) = message {
+                    self.advance_consent_shard(shard, message);
+                }
+            });
+
+        Ok(stream)
+    }
+
+    fn advance_consent_shard(&self, shard: ConsentShard, message: &StoredGroupMessage) {
+        let result = self
+            .store()
+            .conn()
+            .and_then(|conn| shard.advance(&conn, &message.group_id, message.sent_at_ns));
+        if let Err(e) = result {
+            tracing::warn!(shard = ?shard, "failed to advance consent shard position: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_consent_shards_stream_independently() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        // alix created this group, so it is allowed
+        let allowed_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        allowed_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        // bo invited alix to this one, so it is a request
+        let request_group = bo
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        request_group
+            .add_members_by_inbox_id(&[alix.inbox_id()])
+            .await
+            .unwrap();
+        let bo_allowed_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+
+        // opening the requests shard starts its position at the current time
+        let requests = alix
+            .stream_consent_shard(ConsentShard::Requests, None, MessageStreamFilter::default())
+            .await
+            .unwrap();
+        drop(requests);
+
+        let allowed = alix
+            .stream_consent_shard(ConsentShard::Allowed, None, MessageStreamFilter::default())
+            .await
+            .unwrap();
+        futures::pin_mut!(allowed);
+        request_group.send_message(b"request").await.unwrap();
+        bo_allowed_group.send_message(b"hi").await.unwrap();
+
+        let message = allowed.next().await.unwrap().unwrap();
+        assert_eq!(message.decrypted_message_bytes, b"hi");
+        assert_eq!(message.group_id, allowed_group.group_id);
+
+        // the requests shard catches up on what it missed while closed
+        let requests = alix
+            .stream_consent_shard(ConsentShard::Requests, None, MessageStreamFilter::default())
+            .await
+            .unwrap();
+        futures::pin_mut!(requests);
+        let message = requests.next().await.unwrap().unwrap();
+        assert_eq!(message.decrypted_message_bytes, b"request");
+        assert_eq!(message.group_id, request_group.group_id);
+    }
+}
@@ -0,0 +1,279 @@
+//! Messages decoded into structured content in the core crate, so that bindings don't each
+//! carry their own copy of the codec layer.
+use futures::{Stream, StreamExt};
+use prost::Message;
+use xmtp_content_types::{
+    attachment::Attachment, capabilities::Capabilities, link_preview::TextWithLinkPreview,
+    read_receipt::ReadReceipt, remote_attachment::RemoteAttachment, reply::Reply,
+    transaction_reference::TransactionReference, ContentCodec,
+};
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::{
+    api_client::XmtpMlsStreams,
+    xmtp::mls::message_contents::{
+        content_types::ReactionV2, ContentTypeId, EncodedContent, GroupMembershipChanges,
+        GroupUpdated,
+    },
+};
+
+use crate::{
+    client::ClientError,
+    codec_registry::CodecRegistry,
+    groups::{scoped_client::ScopedGroupClient, GroupError, MlsGroup},
+    storage::{
+        consent_record::ConsentState,
+        group::ConversationType,
+        group_message::{MsgQueryArgs, StoredGroupMessage},
+    },
+    subscriptions::{StreamAllMessagesOptions, SubscribeError},
+    Client, XmtpApi,
+};
+
+/// A stored message along with its decoded content
+#[derive(Debug, Clone)]
+pub struct DecodedMessage<T> {
+    pub message: StoredGroupMessage,
+    pub content: T,
+}
+
+impl DecodedMessage<DecodedContent> {
+    /// The text the sender declared for clients that can't decode the content, to show in its
+    /// place. `None` if the content was decoded, or the sender declared no fallback.
+    pub fn fallback(&self) -> Option<&str> {
+        match &self.content {
+            DecodedContent::Unknown(encoded) => encoded.fallback.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// The content of a message of any content type the client has a codec for. Codecs for more
+/// standard content types are added over time, so match with a wildcard arm.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum DecodedContent {
+    Text(String),
+    Reaction(ReactionV2),
+    Reply(Reply),
+    Attachment(Attachment),
+    RemoteAttachment(RemoteAttachment),
+    ReadReceipt(ReadReceipt),
+    TransactionReference(TransactionReference),
+    LinkPreview(TextWithLinkPreview),
+    GroupUpdated(GroupUpdated),
+    GroupMembershipChange(GroupMembershipChanges),
+    Capabilities(Capabilities),
+    /// Content of an app's content type, decoded by the codec it registered
+    Custom(CustomContent),
+    /// Content types without a registered codec, or content that failed to decode, left for the
+    /// app to decode
+    Unknown(EncodedContent),
+}
+
+/// Content decoded by a codec registered by the app
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomContent {
+    pub content_type: ContentTypeId,
+    pub value: serde_json::Value,
+}
+
+impl StoredGroupMessage {
+    /// The message's content as sent, before decoding
+    pub fn encoded_content(&self) -> Result<EncodedContent, prost::DecodeError> {
+        EncodedContent::decode(self.decrypted_message_bytes.as_slice())
+    }
+
+    /// Decode the content with the built-in codec for its content type. Use
+    /// [`Client::decode_message`] to also decode the content types the app registered codecs
+    /// for.
+    pub fn decoded_content(&self) -> Result<DecodedContent, SubscribeError> {
+        self.decoded_content_with(CodecRegistry::builtin())
+    }
+
+    /// Decode the content with the codec in `codecs` for its content type
+    pub fn decoded_content_with(
+        &self,
+        codecs: &CodecRegistry,
+    ) -> Result<DecodedContent, SubscribeError> {
+        Ok(codecs.decode(self.encoded_content()?)?)
+    }
+
+    /// Decode the content with `C`, or return `None` if the message has another content type
+    pub fn decode_as<C, T>(&self) -> Result<Option<T>, SubscribeError>
+    where
+        C: ContentCodec<T>,
+    {
+        let encoded = self.encoded_content()?;
+        let expected = C::content_type();
+        let matches = encoded.r#type.as_ref().is_some_and(|content_type| {
+            content_type.authority_id == expected.authority_id
+                && content_type.type_id == expected.type_id
+                && content_type.version_major == expected.version_major
+        });
+        if !matches {
+            return Ok(None);
+        }
+        Ok(Some(C::decode(encoded)?))
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Like [`Self::find_messages`], but with each message decoded with the client's codecs.
+    /// Content that fails to decode is left [`DecodedContent::Unknown`]; content that isn't
+    /// even an [`EncodedContent`] is left there as its raw bytes, without a content type.
+    pub fn find_decoded_messages(
+        &self,
+        args: &MsgQueryArgs,
+    ) -> Result<Vec<DecodedMessage<DecodedContent>>, GroupError> {
+        let codecs = &self.client.context_ref().codecs;
+        let messages = self
+            .find_messages(args)?
+            .into_iter()
+            .map(|message| {
+                let encoded = message
+                    .encoded_content()
+                    .unwrap_or_else(|_| EncodedContent {
+                        content: message.decrypted_message_bytes.clone(),
+                        ..Default::default()
+                    });
+                let content = codecs.decode(encoded.clone()).unwrap_or_else(|err| {
+                    tracing::warn!(
+                        message_id = hex::encode(&message.id),
+                        "failed to decode message content: {err}"
+                    );
+                    DecodedContent::Unknown(encoded)
+                });
+                DecodedMessage { message, content }
+            })
+            .collect();
+        Ok(messages)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Decode the content of `message` with the client's codecs
+    pub fn decode_message(
+        &self,
+        message: &StoredGroupMessage,
+    ) -> Result<DecodedContent, SubscribeError> {
+        message.decoded_content_with(&self.context.codecs)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi + XmtpMlsStreams + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Like [`Client::stream_all_messages`], but only yields the messages `C` decodes, already
+    /// decoded. Use e.g. `stream_all_decoded_messages::<TextCodec, String>` for text messages.
+    pub async fn stream_all_decoded_messages<C, T>(
+        &self,
+        conversation_type: Option<ConversationType>,
+        consent_states: Option<Vec<ConsentState>>,
+    ) -> Result<impl Stream<Item = Result<DecodedMessage<T>, SubscribeError>> + '_, ClientError>
+    where
+        C: ContentCodec<T>,
+        T: 'static,
+    {
+        let stream = self
+            .stream_all_messages(StreamAllMessagesOptions {
+                conversation_type,
+                consent_states,
+                ..Default::default()
+            })
+            .await?;
+        Ok(stream.filter_map(|message| async move {
+            let message = match message {
+                Ok(message) => message,
+                Err(e) => return Some(Err(e)),
+            };
+            message
+                .decode_as::<C, T>()
+                .transpose()
+                .map(|content| content.map(|content| DecodedMessage { message, content }))
+        }))
+    }
+
+    /// Like [`Client::stream_all_messages`], but yields every message decoded into
+    /// [`DecodedContent`]
+    pub async fn stream_all_decoded_content(
+        &self,
+        opts: StreamAllMessagesOptions,
+    ) -> Result<
+        impl Stream<Item = Result<DecodedMessage<DecodedContent>, SubscribeError>> + '_,
+        ClientError,
+    > {
+        let stream = self.stream_all_messages(opts).await?;
+        Ok(stream.map(|message| {
+            let message = message?;
+            let content = self.decode_message(&message)?;
+            Ok(DecodedMessage { message, content })
+        }))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::{encoded_content_to_bytes, reaction::ReactionCodec, text::TextCodec};
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_stream_decoded_text_messages() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+
+        let stream = bo
+            .stream_all_decoded_messages::<TextCodec, String>(None, None)
+            .await
+            .unwrap();
+        futures::pin_mut!(stream);
+
+        let reaction = ReactionV2 {
+            reference: hex::encode(&group.group_id),
+            action: 1,
+            content: "👍".to_string(),
+            ..Default::default()
+        };
+        group
+            .send_message(&encoded_content_to_bytes(
+                ReactionCodec::encode(reaction).unwrap(),
+            ))
+            .await
+            .unwrap();
+        group
+            .send_message(&encoded_content_to_bytes(
+                TextCodec::encode("hello".to_string()).unwrap(),
+            ))
+            .await
+            .unwrap();
+
+        // the reaction is skipped
+        let message = stream.next().await.unwrap().unwrap();
+        assert_eq!(message.content, "hello");
+        assert_eq!(message.message.sender_inbox_id, alix.inbox_id());
+        assert_eq!(
+            message.message.decoded_content().unwrap(),
+            DecodedContent::Text("hello".to_string())
+        );
+    }
+}
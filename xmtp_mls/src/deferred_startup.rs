@@ -0,0 +1,155 @@
+//! Startup work that needs the network, deferred while the API is unreachable.
+//!
+//! A client built with [`ClientBuilder::offline_startup`](crate::builder::ClientBuilder::offline_startup)
+//! from a stored identity starts without refreshing its identity updates when the network is
+//! down, so local conversations and messages can be served right away. Registering an identity
+//! can also be deferred with [`Client::register_identity_or_defer`]. A background task finishes
+//! the deferred work once the API is reachable again.
+//!
+//! Creating a brand new identity always needs the network, since it has to look up the inbox.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use parking_lot::Mutex;
+use xmtp_common::time::Duration;
+use xmtp_id::{
+    associations::builder::SignatureRequest, scw_verifier::SmartContractSignatureVerifier,
+};
+
+use crate::{
+    api::WrappedApiError, client::ClientError, identity::IdentityError,
+    identity_updates::load_identity_updates, AbortHandle, CancellationToken, Client, StreamHandle,
+    StreamMetrics, XmtpApi,
+};
+
+/// How often deferred startup work is retried while offline
+pub const DEFERRED_STARTUP_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Network-dependent startup work that hasn't completed yet
+#[derive(Default)]
+pub struct DeferredStartup {
+    identity_updates: AtomicBool,
+    registration: Mutex<Option<SignatureRequest>>,
+    /// Held while registering or finishing the deferred work, so a registration is only ever
+    /// sent by one caller at a time
+    completing: tokio::sync::Mutex<()>,
+    /// The task retrying the deferred work in the background, if one was started
+    retry: Mutex<Option<Box<dyn AbortHandle>>>,
+}
+
+impl DeferredStartup {
+    pub(crate) fn defer_identity_updates(&self) {
+        self.identity_updates.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether no startup work is waiting on the network
+    pub fn is_complete(&self) -> bool {
+        !self.identity_updates.load(Ordering::SeqCst) && self.registration.lock().is_none()
+    }
+}
+
+/// Whether `err` means the API couldn't be reached, rather than that it rejected the request
+pub(crate) fn is_offline_error(err: &ClientError) -> bool {
+    match err {
+        ClientError::Api(WrappedApiError::Api(err))
+        | ClientError::QueryError(err)
+        | ClientError::Identity(
+            IdentityError::Api(err) | IdentityError::WrappedApi(WrappedApiError::Api(err)),
+        ) => err.is_connection_error(),
+        _ => false,
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Whether startup work deferred while offline has completed
+    pub fn startup_complete(&self) -> bool {
+        self.context.deferred_startup.is_complete()
+    }
+
+    /// Run the deferred startup work. Returns whether all of it has completed; work that still
+    /// can't reach the network stays deferred.
+    pub async fn complete_deferred_startup(&self) -> Result<bool, ClientError> {
+        let deferred = &self.context.deferred_startup;
+        let _completing = deferred.completing.lock().await;
+        if deferred.identity_updates.load(Ordering::SeqCst) {
+            let conn = self.store().conn()?;
+            load_identity_updates(&self.api_client, &conn, &[self.inbox_id()]).await?;
+            deferred.identity_updates.store(false, Ordering::SeqCst);
+            tracing::info!("loaded identity updates deferred at startup");
+        }
+
+        let registration = deferred.registration.lock().clone();
+        if let Some(signature_request) = registration {
+            self.register_identity(signature_request).await?;
+            *deferred.registration.lock() = None;
+            tracing::info!("completed identity registration deferred while offline");
+        }
+        Ok(deferred.is_complete())
+    }
+
+    /// Stop retrying the deferred startup work in the background. It can still be completed
+    /// with [`Client::complete_deferred_startup`].
+    pub fn stop_deferred_startup_retry(&self) {
+        if let Some(retry) = self.context.deferred_startup.retry.lock().take() {
+            retry.end();
+        }
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Like [`Client::register_identity`], but if the network is unreachable the signed request
+    /// is kept and registration completes in the background once it is back.
+    /// Returns whether the identity was registered right away.
+    pub async fn register_identity_or_defer(
+        &self,
+        signature_request: SignatureRequest,
+    ) -> Result<bool, ClientError> {
+        let deferred = &self.context.deferred_startup;
+        let _completing = deferred.completing.lock().await;
+        match self.register_identity(signature_request.clone()).await {
+            Ok(()) => {
+                *deferred.registration.lock() = None;
+                Ok(true)
+            }
+            Err(e) if is_offline_error(&e) => {
+                tracing::warn!("deferring identity registration until online: {e}");
+                *deferred.registration.lock() = Some(signature_request);
+                self.resume_deferred_startup(DEFERRED_STARTUP_RETRY_INTERVAL);
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Retry the deferred startup work every `retry_interval` until it completes, unless a
+    /// retry is already running
+    pub(crate) fn resume_deferred_startup(&self, retry_interval: Duration) {
+        let mut retry = self.context.deferred_startup.retry.lock();
+        if retry.as_ref().is_some_and(|retry| !retry.is_finished()) {
+            return;
+        }
+        let client = self.clone();
+        let cancel = CancellationToken::new();
+        let stopped = cancel.clone();
+
+        let handle = crate::spawn_cancellable(None, StreamMetrics::default(), cancel, async move {
+            while !stopped.is_cancelled() {
+                match client.complete_deferred_startup().await {
+                    Ok(true) => break,
+                    Ok(false) => {}
+                    Err(e) => tracing::debug!("deferred startup still pending: {e}"),
+                }
+                let _ = xmtp_common::time::timeout(retry_interval, stopped.cancelled()).await;
+            }
+            Ok::<_, ClientError>(())
+        });
+        *retry = Some(handle.abort_handle());
+    }
+}
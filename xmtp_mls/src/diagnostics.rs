@@ -0,0 +1,82 @@
+//! A snapshot of client state for apps to attach to bug reports from the field
+use serde::Serialize;
+use xmtp_common::time::now_ns;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+#[cfg(all(feature = "slow-query-log", not(target_arch = "wasm32")))]
+use crate::storage::slow_query_log::SlowQuery;
+use crate::{client::ClientError, Client, XmtpApi};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsBundle {
+    pub libxmtp_version: String,
+    pub inbox_id: String,
+    /// Hex encoded
+    pub installation_id: String,
+    pub generated_at_ns: i64,
+    /// Statements that exceeded the slow query threshold, with their query plans
+    #[cfg(all(feature = "slow-query-log", not(target_arch = "wasm32")))]
+    pub slow_queries: Vec<SlowQuery>,
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Collect a [`DiagnosticsBundle`] for this client
+    pub fn diagnostics_bundle(&self) -> Result<DiagnosticsBundle, ClientError> {
+        Ok(DiagnosticsBundle {
+            libxmtp_version: env!("CARGO_PKG_VERSION").to_string(),
+            inbox_id: self.inbox_id().to_string(),
+            installation_id: hex::encode(self.installation_public_key()),
+            generated_at_ns: now_ns(),
+            #[cfg(all(feature = "slow-query-log", not(target_arch = "wasm32")))]
+            slow_queries: self
+                .store()
+                .conn()?
+                .slow_queries_with_plans(self.store().slow_query_log()),
+        })
+    }
+
+    /// Record statements that take longer than `threshold` in the diagnostics bundle, or stop
+    /// recording them with `None`
+    #[cfg(all(feature = "slow-query-log", not(target_arch = "wasm32")))]
+    pub fn set_slow_query_threshold(&self, threshold: Option<std::time::Duration>) {
+        let log = self.store().slow_query_log();
+        match threshold {
+            Some(threshold) => log.enable(threshold),
+            None => log.disable(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::builder::ClientBuilder;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_diagnostics_bundle() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        #[cfg(all(feature = "slow-query-log", not(target_arch = "wasm32")))]
+        alix.set_slow_query_threshold(Some(std::time::Duration::from_nanos(1)));
+        alix.find_groups(Default::default()).unwrap();
+
+        let bundle = alix.diagnostics_bundle().unwrap();
+        assert_eq!(bundle.inbox_id, alix.inbox_id());
+        assert_eq!(
+            bundle.installation_id,
+            hex::encode(alix.installation_public_key())
+        );
+        #[cfg(all(feature = "slow-query-log", not(target_arch = "wasm32")))]
+        assert!(bundle
+            .slow_queries
+            .iter()
+            .any(|query| !query.query_plan.is_empty()));
+    }
+}
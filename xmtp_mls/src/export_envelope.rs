@@ -0,0 +1,303 @@
+//! A passphrase protected envelope for anything a user exports from the client, like preferences
+//! or a diagnostics bundle, so every export shares one format.
+//!
+//! The key is derived from the passphrase with Argon2id and the payload is sealed with
+//! AES-256-GCM. The header is authenticated along with the payload and holds the format version,
+//! what kind of export the envelope holds, when it was created and the key derivation parameters,
+//! so the parameters can be raised later without breaking older envelopes.
+//!
+//! Layout: `magic | version | kind | created_at_ns | m_cost | t_cost | p_cost | salt | nonce |
+//! ciphertext`, with integers in big endian.
+use aes_gcm::{
+    aead::{generic_array::GenericArray, Aead, KeyInit, Payload},
+    Aes256Gcm,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use thiserror::Error;
+use xmtp_common::time::now_ns;
+use zeroize::Zeroizing;
+
+/// The envelope version written by [`seal_export`]
+pub const EXPORT_ENVELOPE_VERSION: u8 = 1;
+
+const MAGIC: &[u8; 8] = b"XMTPEXPT";
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+const KEY_SIZE: usize = 32;
+const HEADER_SIZE: usize = MAGIC.len() + 2 + 8 + 3 * 4 + SALT_SIZE + NONCE_SIZE;
+
+/// Argon2id parameters for new envelopes, following the OWASP recommendation
+const DEFAULT_KDF_PARAMS: KdfParams = KdfParams {
+    m_cost: 19 * 1024,
+    t_cost: 2,
+    p_cost: 1,
+};
+/// Envelopes asking for more memory than this are rejected rather than opened, 1 GiB
+const MAX_M_COST: u32 = 1024 * 1024;
+const MAX_T_COST: u32 = 16;
+const MAX_P_COST: u32 = 16;
+
+#[derive(Debug, Error)]
+pub enum ExportEnvelopeError {
+    #[error("data is not an export envelope")]
+    NotAnEnvelope,
+    #[error("unsupported export envelope version {0}")]
+    UnsupportedVersion(u8),
+    #[error("unknown export kind {0}")]
+    UnknownKind(u8),
+    #[error("expected a {expected:?} export, found a {found:?} export")]
+    WrongKind {
+        expected: ExportKind,
+        found: ExportKind,
+    },
+    #[error("export envelope key derivation parameters are out of range")]
+    InvalidParams,
+    #[error("passphrase must not be empty")]
+    EmptyPassphrase,
+    #[error("key derivation failed: {0}")]
+    KeyDerivation(String),
+    /// The passphrase is wrong, or the envelope was modified
+    #[error("export envelope could not be decrypted")]
+    Decrypt,
+}
+
+/// What an export envelope holds
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportKind {
+    Preferences = 1,
+    Diagnostics = 2,
+    MessageHistory = 3,
+    GroupArchive = 4,
+}
+
+impl TryFrom<u8> for ExportKind {
+    type Error = ExportEnvelopeError;
+
+    fn try_from(kind: u8) -> Result<Self, Self::Error> {
+        match kind {
+            1 => Ok(Self::Preferences),
+            2 => Ok(Self::Diagnostics),
+            3 => Ok(Self::MessageHistory),
+            4 => Ok(Self::GroupArchive),
+            kind => Err(ExportEnvelopeError::UnknownKind(kind)),
+        }
+    }
+}
+
+/// The unencrypted header of an export envelope
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportMetadata {
+    pub version: u8,
+    pub kind: ExportKind,
+    pub created_at_ns: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KdfParams {
+    /// Memory in KiB
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl KdfParams {
+    fn derive_key(
+        &self,
+        passphrase: &str,
+        salt: &[u8],
+    ) -> Result<Zeroizing<[u8; KEY_SIZE]>, ExportEnvelopeError> {
+        if self.m_cost > MAX_M_COST || self.t_cost > MAX_T_COST || self.p_cost > MAX_P_COST {
+            return Err(ExportEnvelopeError::InvalidParams);
+        }
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(KEY_SIZE))
+            .map_err(|_| ExportEnvelopeError::InvalidParams)?;
+        let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+            .map_err(|e| ExportEnvelopeError::KeyDerivation(e.to_string()))?;
+        Ok(key)
+    }
+}
+
+struct Header<'a> {
+    metadata: ExportMetadata,
+    kdf: KdfParams,
+    salt: &'a [u8],
+    nonce: &'a [u8],
+}
+
+/// Split `envelope` into its header, the raw header bytes and the ciphertext
+fn parse(envelope: &[u8]) -> Result<(Header<'_>, &[u8], &[u8]), ExportEnvelopeError> {
+    if envelope.len() < HEADER_SIZE || !envelope.starts_with(MAGIC) {
+        return Err(ExportEnvelopeError::NotAnEnvelope);
+    }
+    let (header_bytes, ciphertext) = envelope.split_at(HEADER_SIZE);
+    let rest = &header_bytes[MAGIC.len()..];
+    let version = rest[0];
+    if version != EXPORT_ENVELOPE_VERSION {
+        return Err(ExportEnvelopeError::UnsupportedVersion(version));
+    }
+    let kind = ExportKind::try_from(rest[1])?;
+    let (created_at_ns, rest) = rest[2..].split_at(8);
+    let (kdf, rest) = rest.split_at(12);
+    let u32_at = |i: usize| u32::from_be_bytes(kdf[i..i + 4].try_into().expect("4 bytes"));
+    let (salt, nonce) = rest.split_at(SALT_SIZE);
+
+    let header = Header {
+        metadata: ExportMetadata {
+            version,
+            kind,
+            created_at_ns: i64::from_be_bytes(created_at_ns.try_into().expect("8 bytes")),
+        },
+        kdf: KdfParams {
+            m_cost: u32_at(0),
+            t_cost: u32_at(4),
+            p_cost: u32_at(8),
+        },
+        salt,
+        nonce,
+    };
+    Ok((header, header_bytes, ciphertext))
+}
+
+/// Encrypt `payload` with a key derived from `passphrase`
+pub fn seal_export(
+    kind: ExportKind,
+    passphrase: &str,
+    payload: &[u8],
+) -> Result<Vec<u8>, ExportEnvelopeError> {
+    seal_with_params(kind, passphrase, payload, DEFAULT_KDF_PARAMS)
+}
+
+fn seal_with_params(
+    kind: ExportKind,
+    passphrase: &str,
+    payload: &[u8],
+    kdf: KdfParams,
+) -> Result<Vec<u8>, ExportEnvelopeError> {
+    if passphrase.is_empty() {
+        return Err(ExportEnvelopeError::EmptyPassphrase);
+    }
+    let salt = xmtp_common::rand_array::<SALT_SIZE>();
+    let nonce = xmtp_common::rand_array::<NONCE_SIZE>();
+
+    let mut envelope = Vec::with_capacity(HEADER_SIZE + payload.len() + 16);
+    envelope.extend_from_slice(MAGIC);
+    envelope.push(EXPORT_ENVELOPE_VERSION);
+    envelope.push(kind as u8);
+    envelope.extend_from_slice(&now_ns().to_be_bytes());
+    envelope.extend_from_slice(&kdf.m_cost.to_be_bytes());
+    envelope.extend_from_slice(&kdf.t_cost.to_be_bytes());
+    envelope.extend_from_slice(&kdf.p_cost.to_be_bytes());
+    envelope.extend_from_slice(&salt);
+    envelope.extend_from_slice(&nonce);
+
+    let key = kdf.derive_key(passphrase, &salt)?;
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(key.as_ref()));
+    let ciphertext = cipher
+        .encrypt(
+            GenericArray::from_slice(&nonce),
+            Payload {
+                msg: payload,
+                aad: &envelope,
+            },
+        )
+        .map_err(|_| ExportEnvelopeError::Decrypt)?;
+    envelope.extend_from_slice(&ciphertext);
+    Ok(envelope)
+}
+
+/// Read the header of `envelope` without decrypting it, e.g. to tell the user what a file holds
+/// before asking for the passphrase. The header is only authenticated by [`open_export`].
+pub fn inspect_export(envelope: &[u8]) -> Result<ExportMetadata, ExportEnvelopeError> {
+    Ok(parse(envelope)?.0.metadata)
+}
+
+/// Decrypt an envelope from [`seal_export`] holding a `kind` export
+pub fn open_export(
+    kind: ExportKind,
+    passphrase: &str,
+    envelope: &[u8],
+) -> Result<(ExportMetadata, Vec<u8>), ExportEnvelopeError> {
+    let (header, header_bytes, ciphertext) = parse(envelope)?;
+    if header.metadata.kind != kind {
+        return Err(ExportEnvelopeError::WrongKind {
+            expected: kind,
+            found: header.metadata.kind,
+        });
+    }
+
+    let key = header.kdf.derive_key(passphrase, header.salt)?;
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(key.as_ref()));
+    let payload = cipher
+        .decrypt(
+            GenericArray::from_slice(header.nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header_bytes,
+            },
+        )
+        .map_err(|_| ExportEnvelopeError::Decrypt)?;
+    Ok((header.metadata, payload))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+
+    // Keeps the tests fast, the format is the same
+    const TEST_KDF_PARAMS: KdfParams = KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_seal_and_open() {
+        let envelope = seal_with_params(
+            ExportKind::Diagnostics,
+            "correct horse",
+            b"bundle",
+            TEST_KDF_PARAMS,
+        )
+        .unwrap();
+        let metadata = inspect_export(&envelope).unwrap();
+        assert_eq!(metadata.version, EXPORT_ENVELOPE_VERSION);
+        assert_eq!(metadata.kind, ExportKind::Diagnostics);
+
+        let (opened, payload) =
+            open_export(ExportKind::Diagnostics, "correct horse", &envelope).unwrap();
+        assert_eq!(opened, metadata);
+        assert_eq!(payload, b"bundle");
+
+        assert!(matches!(
+            open_export(ExportKind::Diagnostics, "wrong horse", &envelope),
+            Err(ExportEnvelopeError::Decrypt)
+        ));
+        assert!(matches!(
+            open_export(ExportKind::Preferences, "correct horse", &envelope),
+            Err(ExportEnvelopeError::WrongKind { .. })
+        ));
+
+        // the header is authenticated
+        let mut tampered = envelope.clone();
+        tampered[MAGIC.len() + 2] ^= 1;
+        assert!(matches!(
+            open_export(ExportKind::Diagnostics, "correct horse", &tampered),
+            Err(ExportEnvelopeError::Decrypt)
+        ));
+        assert!(matches!(
+            inspect_export(b"not an envelope"),
+            Err(ExportEnvelopeError::NotAnEnvelope)
+        ));
+        assert!(matches!(
+            seal_export(ExportKind::Diagnostics, "", b"bundle"),
+            Err(ExportEnvelopeError::EmptyPassphrase)
+        ));
+    }
+}
@@ -0,0 +1,378 @@
+//! Hot standby for server-side bots. Installations of the same inbox share a
+//! [`FailoverLease`]: the installation holding the lease is active and publishes, while the
+//! others stay in standby, keeping their state current through device sync and group syncs
+//! until the lease is released or expires and one of them takes over.
+//!
+//! Every new lease holder receives a larger fencing token. Right before each request that
+//! publishes to a conversation, an installation checks that its token is still current and
+//! refuses to publish with a stale one, so a primary that was replaced while paused or
+//! partitioned can't commit alongside its successor. A standby only becomes active once it
+//! caught up on the conversations after taking the lease.
+//!
+//! The network itself doesn't know about the lease, so the lease TTL should leave a wide
+//! margin over how long a publish request takes. Services a bot writes to can fence its side
+//! effects with the same token, see [`Client::fencing_token`].
+use std::{sync::Arc, time::Duration};
+
+use thiserror::Error;
+use xmtp_common::{time::now_ns, RetryableError};
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use crate::{client::ClientError, Client, XmtpApi};
+
+#[derive(Debug, Clone, Error)]
+pub enum FailoverError {
+    #[error("installation is in standby")]
+    Standby,
+    #[error("fencing token {0} was superseded by another installation")]
+    Fenced(u64),
+    #[error("failover lease error: {0}")]
+    Lease(String),
+}
+
+impl RetryableError for FailoverError {
+    fn is_retryable(&self) -> bool {
+        // the lease backend being unreachable is transient, losing the lease is not
+        matches!(self, Self::Lease(_))
+    }
+}
+
+/// A lease that at most one installation holds at a time, backed by storage all
+/// installations can reach (e.g. a database or a coordination service).
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+pub trait FailoverLease: Send + Sync {
+    /// Take or renew the lease for `installation_id` for `ttl`. Succeeds when the lease is
+    /// free, expired, or already held by `installation_id`, returning its fencing token.
+    /// A new holder must always receive a larger token than every earlier holder.
+    async fn acquire(
+        &self,
+        installation_id: &[u8],
+        ttl: Duration,
+    ) -> Result<Option<u64>, FailoverError>;
+
+    /// Whether `fencing_token` belongs to the current, unexpired lease
+    async fn is_current(&self, fencing_token: u64) -> Result<bool, FailoverError>;
+
+    /// Give up the lease if `installation_id` holds it
+    async fn release(&self, installation_id: &[u8]) -> Result<(), FailoverError>;
+}
+
+/// A [`FailoverLease`] kept in memory, for installations running in the same process
+#[derive(Debug, Default)]
+pub struct LocalLease(parking_lot::Mutex<LocalLeaseState>);
+
+#[derive(Debug, Default)]
+struct LocalLeaseState {
+    holder: Option<Vec<u8>>,
+    fencing_token: u64,
+    expires_at_ns: i64,
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+impl FailoverLease for LocalLease {
+    async fn acquire(
+        &self,
+        installation_id: &[u8],
+        ttl: Duration,
+    ) -> Result<Option<u64>, FailoverError> {
+        let mut lease = self.0.lock();
+        let now = now_ns();
+        let held = lease.holder.as_deref() == Some(installation_id);
+        if !held && lease.holder.is_some() && lease.expires_at_ns > now {
+            return Ok(None);
+        }
+        if !held {
+            lease.holder = Some(installation_id.to_vec());
+            lease.fencing_token += 1;
+        }
+        lease.expires_at_ns = now.saturating_add(ttl.as_nanos() as i64);
+        Ok(Some(lease.fencing_token))
+    }
+
+    async fn is_current(&self, fencing_token: u64) -> Result<bool, FailoverError> {
+        let lease = self.0.lock();
+        Ok(lease.holder.is_some()
+            && lease.fencing_token == fencing_token
+            && lease.expires_at_ns > now_ns())
+    }
+
+    async fn release(&self, installation_id: &[u8]) -> Result<(), FailoverError> {
+        let mut lease = self.0.lock();
+        if lease.holder.as_deref() == Some(installation_id) {
+            lease.holder = None;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FailoverOptions {
+    /// How long a lease lasts without being renewed. A standby can take over this long
+    /// after the active installation dies.
+    pub lease_ttl: Duration,
+    /// How often the active installation renews the lease, and a standby syncs and
+    /// tries to take it over
+    pub poll_interval: Duration,
+}
+
+impl Default for FailoverOptions {
+    fn default() -> Self {
+        Self {
+            lease_ttl: Duration::from_secs(10),
+            poll_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallationRole {
+    Active,
+    Standby,
+}
+
+/// Failover state of a client. Clients built without a lease are always active.
+#[derive(Default)]
+pub(crate) struct Failover {
+    lease: Option<Arc<dyn FailoverLease>>,
+    options: FailoverOptions,
+    /// The token of the lease this installation holds, `None` while in standby
+    fencing_token: parking_lot::Mutex<Option<u64>>,
+}
+
+impl Failover {
+    pub(crate) fn new(lease: Arc<dyn FailoverLease>, options: FailoverOptions) -> Self {
+        Self {
+            lease: Some(lease),
+            options,
+            fencing_token: Default::default(),
+        }
+    }
+
+    fn role(&self) -> InstallationRole {
+        if self.lease.is_none() || self.fencing_token.lock().is_some() {
+            InstallationRole::Active
+        } else {
+            InstallationRole::Standby
+        }
+    }
+
+    /// Fails unless this installation holds the current lease. Losing the lease moves the
+    /// installation to standby.
+    pub(crate) async fn check_active(&self) -> Result<(), FailoverError> {
+        let Some(lease) = &self.lease else {
+            return Ok(());
+        };
+        let Some(token) = *self.fencing_token.lock() else {
+            return Err(FailoverError::Standby);
+        };
+        if lease.is_current(token).await? {
+            return Ok(());
+        }
+        let mut current = self.fencing_token.lock();
+        if *current == Some(token) {
+            *current = None;
+        }
+        Err(FailoverError::Fenced(token))
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Whether this installation may publish to conversations
+    pub fn installation_role(&self) -> InstallationRole {
+        self.context.failover.role()
+    }
+
+    /// The fencing token of the lease this installation holds
+    pub fn fencing_token(&self) -> Option<u64> {
+        *self.context.failover.fencing_token.lock()
+    }
+
+    /// Take or renew the failover lease. Returns whether this installation is active.
+    /// The active installation must call this more often than the lease TTL, or run
+    /// [`Self::hold_lease`]. A standby that takes the lease only becomes active once it
+    /// caught up on its conversations; if that fails the lease is released again.
+    pub async fn try_promote(&self) -> Result<bool, ClientError> {
+        let failover = &self.context.failover;
+        let Some(lease) = &failover.lease else {
+            return Ok(true);
+        };
+        let installation_id = self.installation_public_key();
+        let acquired = lease
+            .acquire(installation_id.as_ref(), failover.options.lease_ttl)
+            .await?;
+        let previous = *failover.fencing_token.lock();
+        match (previous, acquired) {
+            (None, Some(token)) => {
+                // catch up on anything the standby has not mirrored yet before publishing
+                let provider = self.mls_provider()?;
+                if let Err(e) = self.sync_all_welcomes_and_groups(&provider, None).await {
+                    if let Err(release) = lease.release(installation_id.as_ref()).await {
+                        tracing::warn!("failed to release the failover lease: {release}");
+                    }
+                    return Err(e);
+                }
+                *failover.fencing_token.lock() = Some(token);
+                tracing::info!(
+                    inbox_id = self.inbox_id(),
+                    fencing_token = token,
+                    "installation promoted to active"
+                );
+            }
+            (Some(token), None) => {
+                *failover.fencing_token.lock() = None;
+                tracing::warn!(
+                    inbox_id = self.inbox_id(),
+                    fencing_token = token,
+                    "installation lost the failover lease"
+                )
+            }
+            _ => *failover.fencing_token.lock() = acquired,
+        }
+        Ok(acquired.is_some())
+    }
+
+    /// Give up the failover lease, letting a standby take over without waiting for it to expire
+    pub async fn demote(&self) -> Result<(), ClientError> {
+        let failover = &self.context.failover;
+        if let Some(lease) = &failover.lease {
+            failover.fencing_token.lock().take();
+            lease
+                .release(self.installation_public_key().as_ref())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Stay in standby, syncing conversations so their state stays current, until this
+    /// installation takes over the failover lease. Returns the new fencing token.
+    pub async fn run_standby(&self) -> Result<u64, ClientError> {
+        loop {
+            if self.try_promote().await? {
+                return Ok(self.fencing_token().unwrap_or_default());
+            }
+            let provider = self.mls_provider()?;
+            if let Err(err) = self.sync_all_welcomes_and_groups(&provider, None).await {
+                tracing::warn!(error = %err, "standby sync failed");
+            }
+            xmtp_common::time::sleep(self.context.failover.options.poll_interval).await;
+        }
+    }
+
+    /// Keep renewing the failover lease. Returns once the lease is lost, after which
+    /// publishing fails until the installation is promoted again.
+    pub async fn hold_lease(&self) -> Result<(), ClientError> {
+        while self.try_promote().await? {
+            xmtp_common::time::sleep(self.context.failover.options.poll_interval).await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::GroupError,
+        identity::IdentityStrategy,
+        utils::test::{register_client, TestClient},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+    use xmtp_id::{
+        associations::{generate_inbox_id, test_utils::MockSmartContractSignatureVerifier},
+        InboxOwner,
+    };
+    use xmtp_proto::api_client::XmtpTestClient;
+
+    async fn failover_client(
+        owner: &impl InboxOwner,
+        lease: Arc<dyn FailoverLease>,
+    ) -> Client<TestClient, MockSmartContractSignatureVerifier> {
+        let inbox_id = generate_inbox_id(&owner.get_address(), &1).unwrap();
+        let client = ClientBuilder::new(IdentityStrategy::new(
+            inbox_id,
+            owner.get_address(),
+            1,
+            None,
+        ))
+        .temp_store()
+        .await
+        .api_client(<TestClient as XmtpTestClient>::create_local().await)
+        .scw_signature_verifier(MockSmartContractSignatureVerifier::new(true))
+        .failover(lease, FailoverOptions::default())
+        .build_with_verifier()
+        .await
+        .unwrap();
+        register_client(&client, owner).await;
+        client
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_local_lease_hands_out_increasing_tokens() {
+        let lease = LocalLease::default();
+        let ttl = Duration::from_secs(60);
+        assert_eq!(lease.acquire(b"primary", ttl).await.unwrap(), Some(1));
+        // renewing keeps the token
+        assert_eq!(lease.acquire(b"primary", ttl).await.unwrap(), Some(1));
+        assert_eq!(lease.acquire(b"standby", ttl).await.unwrap(), None);
+
+        lease.release(b"primary").await.unwrap();
+        assert!(!lease.is_current(1).await.unwrap());
+        assert_eq!(lease.acquire(b"standby", ttl).await.unwrap(), Some(2));
+
+        // an expired lease can be taken over
+        lease.acquire(b"standby", Duration::ZERO).await.unwrap();
+        assert_eq!(lease.acquire(b"primary", ttl).await.unwrap(), Some(3));
+        assert!(!lease.is_current(2).await.unwrap());
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread"))]
+    async fn test_standby_takes_over_and_fences_primary() {
+        let wallet = generate_local_wallet();
+        let lease: Arc<dyn FailoverLease> = Arc::new(LocalLease::default());
+        let primary = failover_client(&wallet, lease.clone()).await;
+        let standby = failover_client(&wallet, lease.clone()).await;
+
+        assert_eq!(primary.installation_role(), InstallationRole::Standby);
+        assert!(primary.try_promote().await.unwrap());
+        assert!(!standby.try_promote().await.unwrap());
+        assert_eq!(standby.installation_role(), InstallationRole::Standby);
+
+        let group = primary.create_group(None, Default::default()).unwrap();
+        group.send_message(b"from primary").await.unwrap();
+
+        // a standby cannot publish to conversations
+        let standby_group = standby.create_group(None, Default::default()).unwrap();
+        let err = standby_group.send_message(b"too early").await.unwrap_err();
+        assert!(matches!(err, GroupError::Failover(FailoverError::Standby)));
+
+        // the primary stops renewing, and the lease is handed over
+        lease
+            .release(primary.installation_public_key().as_ref())
+            .await
+            .unwrap();
+        let token = standby.run_standby().await.unwrap();
+        assert_eq!(Some(token), standby.fencing_token());
+        assert_eq!(standby.installation_role(), InstallationRole::Active);
+        standby_group.send_message(b"from standby").await.unwrap();
+
+        // the old primary still believes it is active, but is fenced off
+        assert_eq!(primary.installation_role(), InstallationRole::Active);
+        let err = group.send_message(b"stale primary").await.unwrap_err();
+        assert!(matches!(
+            err,
+            GroupError::Failover(FailoverError::Fenced(1))
+        ));
+        assert_eq!(primary.installation_role(), InstallationRole::Standby);
+    }
+}
@@ -0,0 +1,18 @@
+//! Activity statistics for community dashboards, aggregated from the messages stored locally
+//! rather than exported and computed by the app.
+use std::time::Duration;
+
+use xmtp_common::time::now_ns;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::storage::message_activity::MessageActivity;
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Message counts per member per day, the active hours histogram and attachment totals
+    /// for the messages sent within the last `window`
+    pub fn activity_stats(&self, window: Duration) -> Result<MessageActivity, GroupError> {
+        let conn = self.context().store().conn()?;
+        let since_ns = now_ns().saturating_sub(window.as_nanos() as i64);
+        Ok(conn.get_message_activity(&self.group_id, since_ns)?)
+    }
+}
@@ -0,0 +1,123 @@
+//! Announcement groups, where only admins can send messages. The mode is the group's send message
+//! policy, so it's enforced by every member: sending fails locally for members the policy doesn't
+//! allow, and messages from them are dropped on receipt instead of being stored, in case they
+//! come from a client that skips the local check.
+use super::{
+    group_permissions::is_allowed_to_send,
+    intents::{PermissionPolicyOption, PermissionUpdateType},
+    validated_commit::CommitParticipant,
+    GroupError, MlsGroup, ScopedGroupClient,
+};
+use crate::storage::xmtp_openmls_provider::XmtpOpenMlsProvider;
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Let only admins and super admins send messages, or every member again with `false`.
+    /// Requires permission to update the group's permissions.
+    pub async fn set_announcement_only(&self, announcement_only: bool) -> Result<(), GroupError> {
+        let policy = if announcement_only {
+            PermissionPolicyOption::AdminOnly
+        } else {
+            PermissionPolicyOption::Allow
+        };
+        self.update_permission_policy(PermissionUpdateType::SendMessage, policy, None)
+            .await
+    }
+
+    /// Whether members who aren't admins are barred from sending messages, as of the most
+    /// recently synced epoch
+    pub fn is_announcement_only(&self) -> Result<bool, GroupError> {
+        let permissions = self.permissions()?;
+        Ok(!permissions.policies.evaluate_send(&CommitParticipant {
+            inbox_id: String::new(),
+            installation_id: vec![],
+            is_creator: false,
+            is_admin: false,
+            is_super_admin: false,
+        }))
+    }
+
+    /// Fail with [`GroupError::SendNotAllowed`] if the group's send message policy doesn't let
+    /// this inbox send
+    pub(super) fn ensure_allowed_to_send(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<(), GroupError> {
+        let allowed = self.load_mls_group_with_lock(provider, |mls_group| {
+            Ok(is_allowed_to_send(&mls_group, self.client.inbox_id()))
+        })?;
+        if !allowed {
+            return Err(GroupError::SendNotAllowed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::GroupMetadataOptions,
+        storage::group_message::{GroupMessageKind, MsgQueryArgs},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_announcement_only_group() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        let bo_group = bo.group(alix_group.group_id.clone()).unwrap();
+        assert!(!bo_group.is_announcement_only().unwrap());
+
+        alix_group.set_announcement_only(true).await.unwrap();
+        assert!(alix_group.is_announcement_only().unwrap());
+
+        // bo hasn't seen the change yet, so its client doesn't stop it, like a malicious client
+        bo_group.send_message(b"sneaky").await.unwrap();
+        alix_group.sync().await.unwrap();
+        let received = alix_group
+            .find_messages(&MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(received
+            .iter()
+            .all(|message| message.sender_inbox_id != bo.inbox_id()));
+
+        bo_group.sync().await.unwrap();
+        assert!(bo_group.is_announcement_only().unwrap());
+        assert!(matches!(
+            bo_group.send_message(b"hello").await,
+            Err(GroupError::SendNotAllowed)
+        ));
+
+        alix_group.send_message(b"announcement").await.unwrap();
+        bo_group.sync().await.unwrap();
+        let received = bo_group
+            .find_messages(&MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(received
+            .iter()
+            .any(|message| message.decrypted_message_bytes == b"announcement"));
+
+        alix_group.set_announcement_only(false).await.unwrap();
+        bo_group.sync().await.unwrap();
+        bo_group.send_message(b"hello").await.unwrap();
+    }
+}
@@ -0,0 +1,351 @@
+//! Exporting a single conversation as an encrypted archive, for compliance exports or to move a
+//! conversation to another device. The archive holds the group's messages, its metadata and
+//! members, and its membership history, sealed in an [export envelope](crate::export_envelope).
+//!
+//! Importing an archive restores the messages and membership history into local storage. A group
+//! that doesn't exist locally is restored as read-only history in the
+//! [`Restored`](GroupMembershipState::Restored) state: its messages can be read, but it has no MLS
+//! state, so it isn't synced and nothing can be sent to it.
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use xmtp_common::{time::now_ns, RetryableError};
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    export_envelope::{open_export, seal_export, ExportEnvelopeError, ExportKind},
+    storage::{
+        group::{GroupMembershipState, StoredGroup},
+        group_membership_change::MembershipChangeKind,
+        group_message::{MsgQueryArgs, StoredGroupMessage},
+        imported_archive::StoredImportedArchive,
+        ProviderTransactions, StorageError,
+    },
+    Client, StoreOrIgnore, XmtpApi,
+};
+
+/// The archive version written by [`MlsGroup::export_archive_bytes`]
+pub const GROUP_ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("unsupported group archive version {0}")]
+    UnsupportedVersion(u32),
+    #[error(transparent)]
+    Envelope(#[from] ExportEnvelopeError),
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+    #[error("the group is active locally, archives are only imported into restored groups")]
+    ActiveGroup,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl From<diesel::result::Error> for ArchiveError {
+    fn from(err: diesel::result::Error) -> Self {
+        Self::Storage(err.into())
+    }
+}
+
+impl RetryableError for ArchiveError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            Self::Storage(err) => err.is_retryable(),
+            _ => false,
+        }
+    }
+}
+
+/// The group's metadata and members when it was exported
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedGroupMetadata {
+    /// The group's mutable metadata, keyed by
+    /// [`MetadataField`](super::group_mutable_metadata::MetadataField)
+    pub attributes: HashMap<String, String>,
+    pub admins: Vec<String>,
+    pub super_admins: Vec<String>,
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ArchivedMembershipChange {
+    epoch: i64,
+    inbox_id: String,
+    added: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GroupArchive {
+    version: u32,
+    group: StoredGroup,
+    metadata: ArchivedGroupMetadata,
+    membership_changes: Vec<ArchivedMembershipChange>,
+    messages: Vec<StoredGroupMessage>,
+}
+
+/// What [`Client::import_archive_bytes`] restored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedArchive {
+    pub group_id: Vec<u8>,
+    /// Whether the group didn't exist locally and was restored as read-only history by this
+    /// import
+    pub restored_group: bool,
+    /// Messages that weren't stored locally yet
+    pub messages_imported: usize,
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Export the group's messages, metadata and membership history as an archive encrypted with
+    /// `passphrase`
+    pub async fn export_archive_bytes(&self, passphrase: &str) -> Result<Vec<u8>, GroupError> {
+        let provider = self.mls_provider()?;
+        let conn = provider.conn_ref();
+        let group = conn
+            .find_group(self.group_id.clone())?
+            .ok_or(GroupError::GroupNotFound)?;
+        let metadata = ArchivedGroupMetadata {
+            attributes: self.mutable_metadata(&provider)?.attributes,
+            admins: self.admin_list(&provider)?,
+            super_admins: self.super_admin_list(&provider)?,
+            members: self
+                .members_with_provider(&provider)
+                .await?
+                .into_iter()
+                .map(|member| member.inbox_id)
+                .collect(),
+        };
+        let membership_changes = conn
+            .get_membership_changes(&self.group_id)?
+            .into_iter()
+            .map(|change| ArchivedMembershipChange {
+                epoch: change.epoch,
+                inbox_id: change.inbox_id,
+                added: change.kind == MembershipChangeKind::Added,
+            })
+            .collect();
+        let archive = GroupArchive {
+            version: GROUP_ARCHIVE_VERSION,
+            group,
+            metadata,
+            membership_changes,
+            messages: conn.get_group_messages(&self.group_id, &MsgQueryArgs::default())?,
+        };
+
+        let payload = serde_json::to_vec(&archive).map_err(ArchiveError::from)?;
+        Ok(seal_export(ExportKind::GroupArchive, passphrase, &payload)
+            .map_err(ArchiveError::from)?)
+    }
+
+    /// [`Self::export_archive_bytes`], written to the file at `path`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn export_archive(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        passphrase: &str,
+    ) -> Result<(), GroupError> {
+        let archive = self.export_archive_bytes(passphrase).await?;
+        std::fs::write(path, archive).map_err(ArchiveError::from)?;
+        Ok(())
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Decrypt an archive from [`MlsGroup::export_archive_bytes`] and restore it into local
+    /// storage, in a single transaction. Messages and membership changes that are already stored
+    /// are kept. Archives are only imported into groups an import created: a group that's active
+    /// locally is never written to, and [`ArchiveError::ActiveGroup`] is returned.
+    pub fn import_archive_bytes(
+        &self,
+        envelope: &[u8],
+        passphrase: &str,
+    ) -> Result<ImportedArchive, ArchiveError> {
+        let (header, payload) = open_export(ExportKind::GroupArchive, passphrase, envelope)?;
+        let archive: GroupArchive = serde_json::from_slice(&payload)?;
+        if archive.version != GROUP_ARCHIVE_VERSION {
+            return Err(ArchiveError::UnsupportedVersion(archive.version));
+        }
+
+        let group_id = archive.group.id.clone();
+        let metadata = serde_json::to_vec(&archive.metadata)?;
+        let provider = self.mls_provider()?;
+        let (restored_group, messages_imported) =
+            provider.transaction(|provider| -> Result<_, ArchiveError> {
+                let conn = provider.conn_ref();
+                let restored_group = match conn.find_group(group_id.clone())? {
+                    None => {
+                        conn.insert_or_replace_group(StoredGroup {
+                            membership_state: GroupMembershipState::Restored,
+                            ..archive.group
+                        })?;
+                        true
+                    }
+                    Some(group) if group.membership_state == GroupMembershipState::Restored => {
+                        false
+                    }
+                    Some(_) => return Err(ArchiveError::ActiveGroup),
+                };
+
+                let mut messages_imported = 0;
+                for message in archive.messages {
+                    if conn.get_group_message(&message.id)?.is_none() {
+                        message.store_or_ignore(conn)?;
+                        messages_imported += 1;
+                    }
+                }
+                for change in archive.membership_changes {
+                    let kind = if change.added {
+                        MembershipChangeKind::Added
+                    } else {
+                        MembershipChangeKind::Removed
+                    };
+                    conn.record_membership_changes(
+                        &group_id,
+                        change.epoch,
+                        [(change.inbox_id, kind)],
+                    )?;
+                }
+                conn.set_imported_archive(&StoredImportedArchive {
+                    group_id: group_id.clone(),
+                    metadata,
+                    exported_at_ns: header.created_at_ns,
+                    imported_at_ns: now_ns(),
+                })?;
+                Ok((restored_group, messages_imported))
+            })?;
+
+        tracing::info!(
+            group_id = hex::encode(&group_id),
+            restored_group,
+            messages_imported,
+            "imported group archive"
+        );
+        Ok(ImportedArchive {
+            group_id,
+            restored_group,
+            messages_imported,
+        })
+    }
+
+    /// [`Self::import_archive_bytes`], reading the archive from the file at `path`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn import_archive(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        passphrase: &str,
+    ) -> Result<ImportedArchive, ArchiveError> {
+        let envelope = std::fs::read(path)?;
+        self.import_archive_bytes(&envelope, passphrase)
+    }
+
+    /// The metadata and members of `group_id` when the latest archive of it imported was
+    /// exported
+    pub fn archived_group_metadata(
+        &self,
+        group_id: &[u8],
+    ) -> Result<Option<ArchivedGroupMetadata>, ArchiveError> {
+        let conn = self.store().conn()?;
+        conn.get_imported_archive(group_id)?
+            .map(|archive| serde_json::from_slice(&archive.metadata))
+            .transpose()
+            .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::{group_mutable_metadata::MetadataField, GroupMetadataOptions},
+        storage::group_message::GroupMessageKind,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_archive_round_trip() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        group.update_group_name("audit".to_string()).await.unwrap();
+        group.send_message(b"first").await.unwrap();
+        group.send_message(b"second").await.unwrap();
+
+        let archive = group.export_archive_bytes("correct horse").await.unwrap();
+
+        // caro was never a member, the group is restored as read-only history
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        assert!(matches!(
+            caro.import_archive_bytes(&archive, "wrong horse"),
+            Err(ArchiveError::Envelope(ExportEnvelopeError::Decrypt))
+        ));
+        let imported = caro
+            .import_archive_bytes(&archive, "correct horse")
+            .unwrap();
+        assert_eq!(imported.group_id, group.group_id);
+        assert!(imported.restored_group);
+
+        let restored = caro.group(group.group_id.clone()).unwrap();
+        let messages = restored
+            .find_messages(&MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            })
+            .unwrap();
+        let texts: Vec<&[u8]> = messages
+            .iter()
+            .map(|m| m.decrypted_message_bytes.as_slice())
+            .collect();
+        assert_eq!(texts, vec![&b"first"[..], &b"second"[..]]);
+        assert!(restored.send_message(b"hello").await.is_err());
+        // the restored group has no MLS state and isn't synced
+        let synced = caro
+            .sync_all_welcomes_and_groups(&caro.mls_provider().unwrap(), None)
+            .await
+            .unwrap();
+        assert_eq!(synced, 0);
+
+        let metadata = caro
+            .archived_group_metadata(&group.group_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            metadata.attributes.get(MetadataField::GroupName.as_str()),
+            Some(&"audit".to_string())
+        );
+        assert_eq!(metadata.members.len(), 2);
+        let conn = caro.store().conn().unwrap();
+        assert!(conn.has_membership_changes(&group.group_id).unwrap());
+
+        // importing again only adds what's missing
+        let again = caro
+            .import_archive_bytes(&archive, "correct horse")
+            .unwrap();
+        assert!(!again.restored_group);
+        assert_eq!(again.messages_imported, 0);
+
+        // groups that are active locally are never written to
+        assert!(matches!(
+            alix.import_archive_bytes(&archive, "correct horse"),
+            Err(ArchiveError::ActiveGroup)
+        ));
+    }
+}
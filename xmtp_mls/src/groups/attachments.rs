@@ -0,0 +1,625 @@
+//! Resumable, chunked attachment uploads, and remote attachments built on them.
+//!
+//! Upload progress is persisted after every acknowledged chunk, so an upload interrupted by a
+//! lost connection (or an app restart) picks up where it left off the next time
+//! [`MlsGroup::send_attachment`] is called with the same payload. The message referencing the
+//! attachment is only sent once the upload has completed.
+//!
+//! Remote attachments are encrypted before upload with a key derived from a random secret, and
+//! the message carries the secret along with the URL. Recipients check the digest of what they
+//! download before decrypting it. The format is the one of the `remoteStaticAttachment` content
+//! type used by the other XMTP SDKs.
+use aes_gcm::{
+    aead::{generic_array::GenericArray, Aead, KeyInit},
+    Aes256Gcm,
+};
+use hkdf::Hkdf;
+use prost::Message;
+use sha2::Sha256;
+use thiserror::Error;
+use xmtp_common::retry::RetryableError;
+use xmtp_content_types::{
+    attachment::{Attachment, AttachmentCodec},
+    encoded_content_to_bytes,
+    remote_attachment::{RemoteAttachment, RemoteAttachmentCodec},
+    CodecError, ContentCodec,
+};
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    storage::{
+        attachment_upload::StoredAttachmentUpload, group_message::ContentType, NotFound,
+        StorageError,
+    },
+    utils::hash::sha256,
+    StoreOrIgnore,
+};
+
+/// Default size of each uploaded chunk (1 MiB)
+pub const DEFAULT_ATTACHMENT_CHUNK_SIZE: usize = 1024 * 1024;
+
+const SECRET_SIZE: usize = 32;
+const SALT_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+
+#[derive(Debug, Error)]
+pub enum AttachmentError {
+    #[error("upload failed: {message}")]
+    Upload { message: String, retryable: bool },
+    #[error("uploader acknowledged offset {0}, which is beyond the payload size {1}")]
+    InvalidOffset(u64, u64),
+    #[error("chunk size must be greater than zero")]
+    InvalidChunkSize,
+    #[error("download failed: {message}")]
+    Download { message: String, retryable: bool },
+    #[error("downloaded attachment doesn't match its digest")]
+    DigestMismatch,
+    #[error("attachment could not be decrypted")]
+    Decrypt,
+    #[error("message is not an attachment")]
+    NotAnAttachment,
+    #[error(transparent)]
+    Codec(#[from] CodecError),
+    #[error("failed to read attachment: {0}")]
+    Read(#[from] std::io::Error),
+}
+
+impl AttachmentError {
+    /// A transient failure (e.g. connectivity loss). The upload can be resumed.
+    pub fn transient(message: impl Into<String>) -> Self {
+        Self::Upload {
+            message: message.into(),
+            retryable: true,
+        }
+    }
+
+    /// A failure that will not succeed on retry
+    pub fn permanent(message: impl Into<String>) -> Self {
+        Self::Upload {
+            message: message.into(),
+            retryable: false,
+        }
+    }
+}
+
+impl RetryableError for AttachmentError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            Self::Upload { retryable, .. } => *retryable,
+            Self::Download { retryable, .. } => *retryable,
+            Self::InvalidOffset(_, _)
+            | Self::InvalidChunkSize
+            | Self::DigestMismatch
+            | Self::Decrypt
+            | Self::NotAnAttachment
+            | Self::Codec(_)
+            | Self::Read(_) => false,
+        }
+    }
+}
+
+/// Transport used to upload attachment payloads to remote storage.
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+pub trait AttachmentUploader {
+    /// Begin a new upload of `total_size` bytes, returning a session identifier
+    /// that can be used to resume the upload later.
+    async fn start_upload(&self, total_size: u64) -> Result<String, AttachmentError>;
+
+    /// The number of bytes the remote has durably received for `session`, if the
+    /// uploader is able to tell. Used to reconcile local progress when resuming.
+    async fn uploaded_bytes(&self, _session: &str) -> Result<Option<u64>, AttachmentError> {
+        Ok(None)
+    }
+
+    /// Upload a single chunk starting at `offset`
+    async fn upload_chunk(
+        &self,
+        session: &str,
+        offset: u64,
+        chunk: &[u8],
+    ) -> Result<(), AttachmentError>;
+
+    /// Finalize the upload, returning the URL the attachment can be fetched from
+    async fn complete_upload(&self, session: &str) -> Result<String, AttachmentError>;
+}
+
+/// Transport used to download remote attachment payloads
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+pub trait AttachmentDownloader {
+    /// Fetch the payload stored at `url`
+    async fn download(&self, url: &str) -> Result<Vec<u8>, AttachmentError>;
+}
+
+/// The app's local store of downloaded attachments, e.g. a disk cache, used to have attachments
+/// ready by the time they're scrolled into view
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+pub trait BlobStore: Send + Sync {
+    /// Fetch the thumbnail of the attachment at `url` into the store, unless it's there already
+    async fn warm_thumbnail(&self, url: &str) -> Result<(), AttachmentError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct AttachmentUploadOptions {
+    /// Size of each chunk handed to the uploader. Smaller chunks lose less progress on
+    /// flaky connections, larger chunks have less per-request overhead.
+    pub chunk_size: usize,
+}
+
+impl Default for AttachmentUploadOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_ATTACHMENT_CHUNK_SIZE,
+        }
+    }
+}
+
+/// An attachment encrypted by [`encrypt_attachment`], ready to be uploaded. Keep it to retry a
+/// failed [`MlsGroup::send_remote_attachment`]: encrypting the attachment again produces a
+/// different payload, which starts a new upload instead of resuming the interrupted one.
+#[derive(Debug, Clone)]
+pub struct EncryptedAttachment {
+    pub payload: Vec<u8>,
+    /// Hex encoded SHA-256 digest of `payload`
+    pub content_digest: String,
+    pub secret: Vec<u8>,
+    pub salt: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub filename: String,
+}
+
+impl EncryptedAttachment {
+    /// The content referencing this attachment once it's been uploaded to `url`
+    pub fn remote_attachment(&self, url: &str) -> RemoteAttachment {
+        RemoteAttachment {
+            url: url.to_string(),
+            content_digest: self.content_digest.clone(),
+            secret: self.secret.clone(),
+            salt: self.salt.clone(),
+            nonce: self.nonce.clone(),
+            scheme: url
+                .split_once("://")
+                .map(|(scheme, _)| format!("{scheme}://"))
+                .unwrap_or_default(),
+            content_length: Some(self.payload.len() as u64),
+            filename: Some(self.filename.clone()),
+        }
+    }
+}
+
+fn attachment_cipher(secret: &[u8], salt: &[u8]) -> Aes256Gcm {
+    let hkdf = Hkdf::<Sha256>::new(Some(salt), secret);
+    let mut key = [0; 32];
+    hkdf.expand(&[], &mut key).expect("Length is correct");
+    Aes256Gcm::new(GenericArray::from_slice(&key))
+}
+
+/// Encrypt `attachment` with a new random secret
+pub fn encrypt_attachment(attachment: Attachment) -> Result<EncryptedAttachment, AttachmentError> {
+    let filename = attachment.filename.clone();
+    let plaintext = encoded_content_to_bytes(AttachmentCodec::encode(attachment)?);
+    let secret = xmtp_common::rand_array::<SECRET_SIZE>().to_vec();
+    let salt = xmtp_common::rand_array::<SALT_SIZE>().to_vec();
+    let nonce = xmtp_common::rand_array::<NONCE_SIZE>().to_vec();
+    let payload = attachment_cipher(&secret, &salt)
+        .encrypt(GenericArray::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| AttachmentError::Decrypt)?;
+
+    Ok(EncryptedAttachment {
+        content_digest: hex::encode(sha256(&payload)),
+        payload,
+        secret,
+        salt,
+        nonce,
+        filename,
+    })
+}
+
+/// Verify `payload`, downloaded from `remote.url`, against the digest in `remote` and decrypt it
+pub fn decrypt_attachment(
+    remote: &RemoteAttachment,
+    payload: &[u8],
+) -> Result<Attachment, AttachmentError> {
+    if hex::encode(sha256(payload)) != remote.content_digest {
+        return Err(AttachmentError::DigestMismatch);
+    }
+    if remote.nonce.len() != NONCE_SIZE {
+        return Err(AttachmentError::Decrypt);
+    }
+    let plaintext = attachment_cipher(&remote.secret, &remote.salt)
+        .decrypt(GenericArray::from_slice(&remote.nonce), payload)
+        .map_err(|_| AttachmentError::Decrypt)?;
+    let content = EncodedContent::decode(plaintext.as_slice())
+        .map_err(|e| CodecError::Decode(e.to_string()))?;
+
+    Ok(AttachmentCodec::decode(content)?)
+}
+
+/// Read the file at `path` into an attachment of type `mime_type`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_attachment(
+    path: &std::path::Path,
+    mime_type: &str,
+) -> Result<Attachment, AttachmentError> {
+    let data = std::fs::read(path)?;
+    let filename = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    Ok(Attachment {
+        filename,
+        mime_type: mime_type.to_string(),
+        data,
+    })
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Upload `attachment`, encrypted by [`encrypt_attachment`], with `uploader` and send a
+    /// remote attachment message referencing it. Like [`Self::send_attachment`], calling this
+    /// again with the same attachment after a failure resumes the upload.
+    pub async fn send_remote_attachment<U>(
+        &self,
+        uploader: &U,
+        attachment: &EncryptedAttachment,
+        opts: AttachmentUploadOptions,
+    ) -> Result<Vec<u8>, GroupError>
+    where
+        U: AttachmentUploader + ?Sized,
+    {
+        self.send_attachment(uploader, &attachment.payload, opts, |url| {
+            let content = RemoteAttachmentCodec::encode(attachment.remote_attachment(url))
+                .expect("remote attachments always encode");
+            encoded_content_to_bytes(content)
+        })
+        .await
+    }
+
+    /// The attachment sent in `message_id`. Remote attachments are downloaded with `downloader`,
+    /// verified and decrypted.
+    pub async fn download_attachment<D>(
+        &self,
+        downloader: &D,
+        message_id: &[u8],
+    ) -> Result<Attachment, GroupError>
+    where
+        D: AttachmentDownloader + ?Sized,
+    {
+        let conn = self.context().store().conn()?;
+        let message = conn
+            .get_group_message(message_id)?
+            .filter(|message| message.group_id == self.group_id)
+            .ok_or_else(|| StorageError::from(NotFound::MessageById(message_id.to_vec())))?;
+        let content = message
+            .encoded_content()
+            .map_err(|e| CodecError::Decode(e.to_string()))?;
+
+        match message.content_type {
+            ContentType::Attachment => Ok(AttachmentCodec::decode(content)?),
+            ContentType::RemoteAttachment => {
+                let remote = RemoteAttachmentCodec::decode(content)?;
+                let payload = downloader.download(&remote.url).await?;
+                Ok(decrypt_attachment(&remote, &payload)?)
+            }
+            _ => Err(AttachmentError::NotAnAttachment.into()),
+        }
+    }
+
+    /// Upload `payload` in chunks with `uploader` and, once the upload has completed, send the
+    /// message built by `encode_message` from the URL of the uploaded attachment.
+    ///
+    /// Calling this again with the same payload after a failure resumes the upload from the last
+    /// persisted chunk. Every call sends its own message, so sending a payload that was sent
+    /// before sends it again, reusing the completed upload.
+    pub async fn send_attachment<U, F>(
+        &self,
+        uploader: &U,
+        payload: &[u8],
+        opts: AttachmentUploadOptions,
+        encode_message: F,
+    ) -> Result<Vec<u8>, GroupError>
+    where
+        U: AttachmentUploader + ?Sized,
+        F: FnOnce(&str) -> Vec<u8>,
+    {
+        let provider = self.mls_provider()?;
+        let conn = provider.conn_ref();
+        let upload = self.load_or_create_upload(payload, &opts)?;
+        let upload_id = upload.id.clone();
+        let url = match upload.url.clone() {
+            Some(url) => url,
+            None => self.upload_remaining(uploader, payload, upload).await?,
+        };
+
+        let message_id = self
+            .send_message_with_provider(&encode_message(&url), &provider)
+            .await?;
+        conn.set_attachment_upload_sent(&upload_id, &message_id)?;
+
+        Ok(message_id)
+    }
+
+    /// Upload `payload` with `uploader` without sending a message, resuming an interrupted
+    /// upload of the same payload. Returns the URL of the uploaded attachment.
+    pub(super) async fn upload_attachment<U>(
+        &self,
+        uploader: &U,
+        payload: &[u8],
+        opts: &AttachmentUploadOptions,
+    ) -> Result<String, GroupError>
+    where
+        U: AttachmentUploader + ?Sized,
+    {
+        let upload = self.load_or_create_upload(payload, opts)?;
+        match upload.url.clone() {
+            Some(url) => Ok(url),
+            None => self.upload_remaining(uploader, payload, upload).await,
+        }
+    }
+
+    fn load_or_create_upload(
+        &self,
+        payload: &[u8],
+        opts: &AttachmentUploadOptions,
+    ) -> Result<StoredAttachmentUpload, GroupError> {
+        if opts.chunk_size == 0 {
+            return Err(AttachmentError::InvalidChunkSize.into());
+        }
+        let conn = self.context().store().conn()?;
+        let id = attachment_upload_id(&self.group_id, payload);
+        StoredAttachmentUpload::new(
+            id.clone(),
+            self.group_id.clone(),
+            sha256(payload),
+            payload.len() as i64,
+            opts.chunk_size as i64,
+        )
+        .store_or_ignore(&conn)?;
+
+        Ok(conn
+            .find_attachment_upload(&id)?
+            .ok_or(NotFound::AttachmentUpload(id))?)
+    }
+
+    async fn upload_remaining<U>(
+        &self,
+        uploader: &U,
+        payload: &[u8],
+        upload: StoredAttachmentUpload,
+    ) -> Result<String, GroupError>
+    where
+        U: AttachmentUploader + ?Sized,
+    {
+        let conn = self.context().store().conn()?;
+        let total = payload.len() as u64;
+
+        let session = match upload.upload_session {
+            Some(session) => session,
+            None => {
+                let session = uploader.start_upload(total).await?;
+                conn.set_attachment_upload_session(&upload.id, &session)?;
+                session
+            }
+        };
+
+        // Trust the remote over local progress: a chunk may have been written but not
+        // acknowledged before the connection dropped.
+        let mut offset = match uploader.uploaded_bytes(&session).await? {
+            Some(remote) => remote,
+            None => upload.bytes_uploaded as u64,
+        };
+        if offset > total {
+            return Err(AttachmentError::InvalidOffset(offset, total).into());
+        }
+        if offset > 0 {
+            tracing::info!(
+                "resuming attachment upload {} at {}/{} bytes",
+                hex::encode(&upload.id),
+                offset,
+                total
+            );
+        }
+
+        let chunk_size = upload.chunk_size as u64;
+        while offset < total {
+            let end = std::cmp::min(offset + chunk_size, total);
+            uploader
+                .upload_chunk(&session, offset, &payload[offset as usize..end as usize])
+                .await?;
+            offset = end;
+            conn.update_attachment_upload_progress(&upload.id, offset as i64)?;
+        }
+
+        let url = uploader.complete_upload(&session).await?;
+        conn.set_attachment_upload_complete(&upload.id, &url)?;
+
+        Ok(url)
+    }
+}
+
+/// Uploads are keyed by group and payload, so retrying the same send resumes the same upload,
+/// and sending the payload again reuses it once complete
+fn attachment_upload_id(group_id: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut id = group_id.to_vec();
+    id.extend(sha256(payload));
+    sha256(&id)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use std::sync::Mutex;
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::GroupMetadataOptions,
+        storage::{attachment_upload::AttachmentUploadState, group_message::MsgQueryArgs},
+    };
+
+    /// Uploader that drops the connection after `fail_after` chunks, once.
+    #[derive(Default)]
+    struct FlakyUploader {
+        fail_after: Option<usize>,
+        chunks: Mutex<Vec<(u64, Vec<u8>)>>,
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    impl AttachmentUploader for FlakyUploader {
+        async fn start_upload(&self, _total_size: u64) -> Result<String, AttachmentError> {
+            Ok("session".to_string())
+        }
+
+        async fn upload_chunk(
+            &self,
+            _session: &str,
+            offset: u64,
+            chunk: &[u8],
+        ) -> Result<(), AttachmentError> {
+            let mut chunks = self.chunks.lock().unwrap();
+            if Some(chunks.len()) == self.fail_after {
+                return Err(AttachmentError::transient("connection lost"));
+            }
+            chunks.push((offset, chunk.to_vec()));
+            Ok(())
+        }
+
+        async fn complete_upload(&self, _session: &str) -> Result<String, AttachmentError> {
+            Ok("https://example.com/attachment".to_string())
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_resumes_interrupted_upload() {
+        let client = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = client
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let payload = vec![7u8; 10];
+        let opts = AttachmentUploadOptions { chunk_size: 3 };
+
+        let flaky = FlakyUploader {
+            fail_after: Some(2),
+            ..Default::default()
+        };
+        let result = group
+            .send_attachment(&flaky, &payload, opts.clone(), |url| {
+                url.as_bytes().to_vec()
+            })
+            .await;
+        assert!(matches!(
+            result,
+            Err(GroupError::Attachment(AttachmentError::Upload { .. }))
+        ));
+        // Nothing is sent until the upload completes
+        assert!(group
+            .find_messages(&MsgQueryArgs::default())
+            .unwrap()
+            .is_empty());
+
+        let uploader = FlakyUploader::default();
+        let message_id = group
+            .send_attachment(&uploader, &payload, opts.clone(), |url| {
+                url.as_bytes().to_vec()
+            })
+            .await
+            .unwrap();
+
+        // Only the remaining chunks are uploaded on resume
+        let offsets: Vec<u64> = uploader
+            .chunks
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(offset, _)| *offset)
+            .collect();
+        assert_eq!(offsets, vec![6, 9]);
+
+        let messages = group.find_messages(&MsgQueryArgs::default()).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(
+            messages[0].decrypted_message_bytes,
+            b"https://example.com/attachment"
+        );
+
+        // Sending the same payload again sends another message, without uploading it again
+        let again = group
+            .send_attachment(&uploader, &payload, opts, |url| url.as_bytes().to_vec())
+            .await
+            .unwrap();
+        assert_ne!(again, message_id);
+        assert_eq!(
+            group.find_messages(&MsgQueryArgs::default()).unwrap().len(),
+            2
+        );
+        assert_eq!(uploader.chunks.lock().unwrap().len(), 2);
+
+        let conn = client.store().conn().unwrap();
+        let upload = conn
+            .find_attachment_upload(attachment_upload_id(&group.group_id, &payload))
+            .unwrap()
+            .unwrap();
+        assert_eq!(upload.state, AttachmentUploadState::Sent);
+        assert_eq!(upload.message_id, Some(again));
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    impl AttachmentDownloader for FlakyUploader {
+        async fn download(&self, _url: &str) -> Result<Vec<u8>, AttachmentError> {
+            Ok(self
+                .chunks
+                .lock()
+                .unwrap()
+                .iter()
+                .flat_map(|(_, chunk)| chunk.clone())
+                .collect())
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_remote_attachment_round_trip() {
+        let client = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = client
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let attachment = Attachment {
+            filename: "cat.png".to_string(),
+            mime_type: "image/png".to_string(),
+            data: vec![9u8; 100],
+        };
+        let encrypted = encrypt_attachment(attachment.clone()).unwrap();
+        assert_ne!(encrypted.payload, attachment.data);
+
+        let storage = FlakyUploader::default();
+        let message_id = group
+            .send_remote_attachment(
+                &storage,
+                &encrypted,
+                AttachmentUploadOptions { chunk_size: 32 },
+            )
+            .await
+            .unwrap();
+        let downloaded = group
+            .download_attachment(&storage, &message_id)
+            .await
+            .unwrap();
+        assert_eq!(downloaded, attachment);
+
+        // a payload that was tampered with is rejected before decrypting
+        storage.chunks.lock().unwrap()[0].1[0] ^= 1;
+        assert!(matches!(
+            group.download_attachment(&storage, &message_id).await,
+            Err(GroupError::Attachment(AttachmentError::DigestMismatch))
+        ));
+    }
+}
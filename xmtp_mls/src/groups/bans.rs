@@ -0,0 +1,236 @@
+//! Banning members. A ban removes the member and keeps them out: the group's banned inboxes live
+//! in its mutable metadata, so every member knows about them. Adding a banned inbox fails locally,
+//! and commits that add one anyway are rejected by every member when validating them.
+//!
+//! The field has no entry in the default metadata policies, so only admins can ban or unban. It
+//! holds the banned inbox ids separated by commas. Each ban or unban adds or removes its own
+//! entry of the list as it is when the change is published, so concurrent bans are all kept.
+use thiserror::Error;
+use xmtp_common::RetryableError;
+
+use super::{
+    group_metadata::ConversationType,
+    group_mutable_metadata::{GroupMutableMetadata, MetadataField},
+    intents::{MetadataListAction, UpdateMetadataListIntentData},
+    GroupError, MlsGroup, ScopedGroupClient,
+};
+use crate::storage::{group_intent::IntentKind, xmtp_openmls_provider::XmtpOpenMlsProvider};
+
+#[derive(Debug, Error)]
+pub enum BanError {
+    #[error("inbox {0} is banned from the group")]
+    Banned(String),
+    #[error("inbox is already banned")]
+    AlreadyBanned,
+    #[error("inbox is not banned")]
+    NotBanned,
+    #[error("members can't be banned from a DM")]
+    Dm,
+}
+
+impl RetryableError for BanError {
+    fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+/// The inboxes banned from the group with `metadata`
+pub fn banned_inbox_ids(metadata: &GroupMutableMetadata) -> Vec<String> {
+    metadata
+        .attributes
+        .get(MetadataField::BannedInboxes.as_str())
+        .map(|banned| {
+            banned
+                .split(',')
+                .filter(|inbox_id| !inbox_id.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Remove `inbox_id` from the group and keep them from being added back until they're
+    /// unbanned. Requires being an admin, and permission to remove members if they're still in
+    /// the group.
+    pub async fn ban_member(&self, inbox_id: &str) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        if self.metadata(&provider).await?.conversation_type == ConversationType::Dm {
+            return Err(BanError::Dm.into());
+        }
+        let banned = self.banned_members_with_provider(&provider)?;
+        if banned.iter().any(|banned| banned == inbox_id) {
+            return Err(BanError::AlreadyBanned.into());
+        }
+        // ban first, so that nobody can add them back in between
+        self.update_banned(&provider, inbox_id, MetadataListAction::AddLast)
+            .await?;
+
+        let is_member = self
+            .members()
+            .await?
+            .iter()
+            .any(|member| member.inbox_id == inbox_id);
+        if is_member {
+            self.remove_members_by_inbox_id(&[inbox_id]).await?;
+        }
+        Ok(())
+    }
+
+    /// Let `inbox_id` be added to the group again. Requires being an admin.
+    pub async fn unban_member(&self, inbox_id: &str) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        let banned = self.banned_members_with_provider(&provider)?;
+        if !banned.iter().any(|banned| banned == inbox_id) {
+            return Err(BanError::NotBanned.into());
+        }
+        self.update_banned(&provider, inbox_id, MetadataListAction::Remove)
+            .await
+    }
+
+    /// The inboxes banned from the group, as of its last sync, in the order they were banned
+    pub fn banned_members(&self) -> Result<Vec<String>, GroupError> {
+        self.banned_members_with_provider(&self.mls_provider()?)
+    }
+
+    fn banned_members_with_provider(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<Vec<String>, GroupError> {
+        Ok(banned_inbox_ids(&self.mutable_metadata(provider)?))
+    }
+
+    /// Fail with [`BanError::Banned`] if any of `inbox_ids` is banned from the group
+    pub(super) fn ensure_not_banned(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        inbox_ids: &[&str],
+    ) -> Result<(), GroupError> {
+        let banned = self.banned_members_with_provider(provider)?;
+        if let Some(inbox_id) = inbox_ids
+            .iter()
+            .find(|inbox_id| banned.iter().any(|banned| banned == *inbox_id))
+        {
+            return Err(BanError::Banned(inbox_id.to_string()).into());
+        }
+        Ok(())
+    }
+
+    async fn update_banned(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        inbox_id: &str,
+        action: MetadataListAction,
+    ) -> Result<(), GroupError> {
+        let intent_data: Vec<u8> = UpdateMetadataListIntentData::new(
+            MetadataField::BannedInboxes,
+            inbox_id.to_string(),
+            action,
+            None,
+        )
+        .into();
+        let intent = self.queue_intent(provider, IntentKind::UpdateMetadataList, intent_data)?;
+
+        self.sync_until_intent_resolved(provider, intent.id).await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::{GroupMetadataOptions, UpdateAdminListType},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_banned_member_cannot_be_added_back() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id(), caro.inbox_id()])
+            .await
+            .unwrap();
+        bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        let bo_group = bo.group(alix_group.group_id.clone()).unwrap();
+
+        alix_group.ban_member(caro.inbox_id()).await.unwrap();
+        assert_eq!(alix_group.banned_members().unwrap(), vec![caro.inbox_id()]);
+        assert!(!alix_group
+            .members()
+            .await
+            .unwrap()
+            .iter()
+            .any(|member| member.inbox_id == caro.inbox_id()));
+        assert!(matches!(
+            alix_group.ban_member(caro.inbox_id()).await,
+            Err(GroupError::Ban(BanError::AlreadyBanned))
+        ));
+
+        // bo can add members, but not banned ones
+        bo_group.sync().await.unwrap();
+        assert!(matches!(
+            bo_group.add_members_by_inbox_id(&[caro.inbox_id()]).await,
+            Err(GroupError::Ban(BanError::Banned(inbox_id))) if inbox_id == caro.inbox_id()
+        ));
+
+        // a client that skips the local check gets its commit rejected by everyone else
+        let bo_provider = bo.mls_provider().unwrap();
+        let intent_data = bo_group
+            .get_membership_update_intent(&bo_provider, &[caro.inbox_id()], &[])
+            .await
+            .unwrap();
+        let intent = bo_group
+            .queue_intent(
+                &bo_provider,
+                IntentKind::UpdateGroupMembership,
+                intent_data.into(),
+            )
+            .unwrap();
+        let _ = bo_group
+            .sync_until_intent_resolved(&bo_provider, intent.id)
+            .await;
+        alix_group.sync().await.unwrap();
+        assert!(!alix_group
+            .members()
+            .await
+            .unwrap()
+            .iter()
+            .any(|member| member.inbox_id == caro.inbox_id()));
+
+        // bans made at the same time are all kept
+        let dave = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let eri = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        alix_group
+            .update_admin_list(UpdateAdminListType::Add, bo.inbox_id().to_string())
+            .await
+            .unwrap();
+        bo_group.sync().await.unwrap();
+        alix_group.ban_member(dave.inbox_id()).await.unwrap();
+        bo_group.ban_member(eri.inbox_id()).await.unwrap();
+        alix_group.sync().await.unwrap();
+        assert_eq!(
+            alix_group.banned_members().unwrap(),
+            vec![caro.inbox_id(), dave.inbox_id(), eri.inbox_id()]
+        );
+
+        alix_group.unban_member(caro.inbox_id()).await.unwrap();
+        assert_eq!(
+            alix_group.banned_members().unwrap(),
+            vec![dave.inbox_id(), eri.inbox_id()]
+        );
+        alix_group
+            .add_members_by_inbox_id(&[caro.inbox_id()])
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+//! Sending several messages at once, e.g. a text along with an attachment and a link preview.
+//!
+//! The messages of a batch are queued in a single transaction, so either all of them are sent
+//! or none is, and are published in order. Consecutive messages are published in a single
+//! request, so a batch takes one round-trip instead of one per message.
+use xmtp_content_types::encoded_content_to_bytes;
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    configuration::SEND_MESSAGE_UPDATE_INSTALLATIONS_INTERVAL_NS,
+    storage::{
+        consent_record::ConsentState, xmtp_openmls_provider::XmtpOpenMlsProvider,
+        ProviderTransactions,
+    },
+};
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Send `contents` as consecutive messages, in order, encoding each with the client's
+    /// codecs. Returns the ids of the messages, in the same order.
+    pub async fn send_batch(
+        &self,
+        contents: Vec<EncodedContent>,
+    ) -> Result<Vec<Vec<u8>>, GroupError> {
+        if contents.is_empty() {
+            return Ok(vec![]);
+        }
+        let codecs = &self.client.context_ref().codecs;
+        let messages = contents
+            .into_iter()
+            .map(|content| codecs.encode(content).map(encoded_content_to_bytes))
+            .collect::<Result<Vec<_>, _>>()?;
+        let provider = self.mls_provider()?;
+
+        if !self.context().connectivity.is_online() {
+            return self.queue_batch(&messages, &provider);
+        }
+        let update_interval_ns = Some(SEND_MESSAGE_UPDATE_INSTALLATIONS_INTERVAL_NS);
+        match self
+            .maybe_update_installations(&provider, update_interval_ns)
+            .await
+        {
+            Err(e) if self.note_offline_error(&e) => return self.queue_batch(&messages, &provider),
+            result => result?,
+        }
+
+        let message_ids = self.prepare_batch(&messages, &provider)?;
+        if let Err(e) = self.sync_until_last_intent_resolved(&provider).await {
+            if !self.note_offline_error(&e) {
+                return Err(e);
+            }
+            // published by the outbox once the client is back online
+            self.context().outbox.wake();
+            return Ok(message_ids);
+        }
+
+        // implicitly set group consent state to allowed
+        self.update_consent_state(ConsentState::Allowed)?;
+
+        Ok(message_ids)
+    }
+
+    /// Store the batch as unpublished and leave it to the outbox to publish
+    fn queue_batch(
+        &self,
+        messages: &[Vec<u8>],
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<Vec<Vec<u8>>, GroupError> {
+        let message_ids = self.prepare_batch(messages, provider)?;
+        self.context().outbox.wake();
+        Ok(message_ids)
+    }
+
+    fn prepare_batch(
+        &self,
+        messages: &[Vec<u8>],
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<Vec<Vec<u8>>, GroupError> {
+        provider.transaction(|provider| {
+            messages
+                .iter()
+                .map(|message| {
+                    self.prepare_message(message, provider, |now| Self::into_envelope(message, now))
+                })
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        codec_registry::EncodableContent,
+        decoded_message::DecodedContent,
+        groups::GroupMetadataOptions,
+        storage::group_message::{GroupMessageKind, MsgQueryArgs},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::attachment::Attachment;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_send_batch_in_order() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+
+        let attachment = Attachment {
+            filename: "cat.png".to_string(),
+            mime_type: "image/png".to_string(),
+            data: vec![1, 2, 3],
+        };
+        let batch = vec![
+            "look at this".encode().unwrap(),
+            attachment.clone().encode().unwrap(),
+            "cute right?".encode().unwrap(),
+        ];
+        let message_ids = group.send_batch(batch).await.unwrap();
+        assert_eq!(message_ids.len(), 3);
+
+        bo_group.sync().await.unwrap();
+        let received = bo_group
+            .find_decoded_messages(&MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            })
+            .unwrap()
+            .into_iter()
+            .map(|message| message.content)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            received,
+            vec![
+                DecodedContent::Text("look at this".to_string()),
+                DecodedContent::Attachment(attachment),
+                DecodedContent::Text("cute right?".to_string()),
+            ]
+        );
+
+        // a batch with content that can't be encoded sends nothing
+        let unregistered = EncodedContent::default();
+        assert!(group
+            .send_batch(vec!["hi".encode().unwrap(), unregistered])
+            .await
+            .is_err());
+        assert_eq!(
+            group
+                .find_messages(&MsgQueryArgs {
+                    kind: Some(GroupMessageKind::Application),
+                    ..Default::default()
+                })
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+}
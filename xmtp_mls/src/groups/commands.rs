@@ -0,0 +1,255 @@
+//! Recognizes bot commands, e.g. `/weather "new york" tomorrow`, in incoming text messages so
+//! bots built on this crate don't each need their own parsing layer.
+use std::sync::Arc;
+
+use futures::{Stream, StreamExt};
+use prost::Message;
+use xmtp_content_types::{text::TextCodec, ContentCodec};
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::{api_client::XmtpMlsStreams, xmtp::mls::message_contents::EncodedContent};
+
+use crate::{
+    client::ClientError,
+    storage::group_message::{ContentType, StoredGroupMessage},
+    stream_handles::spawn_stream_task,
+    subscriptions::{StreamAllMessagesOptions, SubscribeError},
+    Client, XmtpApi,
+};
+
+/// Recognizes text messages starting with a prefix as commands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandParser {
+    prefix: String,
+    suppress_messages: bool,
+}
+
+impl CommandParser {
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            suppress_messages: false,
+        }
+    }
+
+    /// Don't also yield messages containing a command as regular messages
+    pub fn suppress_messages(mut self, suppress_messages: bool) -> Self {
+        self.suppress_messages = suppress_messages;
+        self
+    }
+
+    /// Parse the command in `message`, if it is a text message starting with the prefix
+    pub fn parse(&self, message: &StoredGroupMessage) -> Option<Command> {
+        if message.content_type != ContentType::Text {
+            return None;
+        }
+        let content = EncodedContent::decode(message.decrypted_message_bytes.as_slice()).ok()?;
+        let text = TextCodec::decode(content).ok()?;
+        let (name, args) = self.parse_text(&text)?;
+        Some(Command {
+            name,
+            args,
+            sender_inbox_id: message.sender_inbox_id.clone(),
+            group_id: message.group_id.clone(),
+            message_id: message.id.clone(),
+        })
+    }
+
+    fn parse_text(&self, text: &str) -> Option<(String, Vec<String>)> {
+        let rest = text.trim_start().strip_prefix(&self.prefix)?;
+        let mut words = split_args(rest).into_iter();
+        let name = words.next().filter(|name| !name.is_empty())?;
+        Some((name, words.collect()))
+    }
+}
+
+/// Split on whitespace, keeping double-quoted arguments together
+fn split_args(text: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_arg = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_arg = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if has_arg {
+                    args.push(std::mem::take(&mut current));
+                    has_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_arg = true;
+            }
+        }
+    }
+    if has_arg {
+        args.push(current);
+    }
+    args
+}
+
+/// A command sent to the bot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    /// The command without its prefix
+    pub name: String,
+    pub args: Vec<String>,
+    pub sender_inbox_id: String,
+    pub group_id: Vec<u8>,
+    pub message_id: Vec<u8>,
+}
+
+/// An item of [`Client::stream_all_messages_with_commands`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BotEvent {
+    Message(StoredGroupMessage),
+    CommandReceived(Command),
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi + XmtpMlsStreams + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Like [`Client::stream_all_messages`], but yields a [`BotEvent::CommandReceived`] for
+    /// each message `parser` recognizes as a command. Messages sent by this inbox are never
+    /// treated as commands.
+    pub async fn stream_all_messages_with_commands(
+        &self,
+        opts: StreamAllMessagesOptions,
+        parser: CommandParser,
+    ) -> Result<impl Stream<Item = Result<BotEvent, SubscribeError>> + '_, ClientError> {
+        let stream = self.stream_all_messages(opts).await?;
+        let inbox_id = self.inbox_id().to_string();
+
+        Ok(stream.flat_map(move |message| {
+            let events = match message {
+                Err(e) => vec![Err(e)],
+                Ok(message) => {
+                    let command = (message.sender_inbox_id != inbox_id)
+                        .then(|| parser.parse(&message))
+                        .flatten();
+                    match command {
+                        Some(command) if parser.suppress_messages => {
+                            vec![Ok(BotEvent::CommandReceived(command))]
+                        }
+                        Some(command) => vec![
+                            Ok(BotEvent::Message(message)),
+                            Ok(BotEvent::CommandReceived(command)),
+                        ],
+                        None => vec![Ok(BotEvent::Message(message))],
+                    }
+                }
+            };
+            futures::stream::iter(events)
+        }))
+    }
+
+    pub fn stream_all_messages_with_commands_callback(
+        client: Arc<Client<ApiClient, V>>,
+        opts: StreamAllMessagesOptions,
+        parser: CommandParser,
+        mut callback: impl FnMut(Result<BotEvent, SubscribeError>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
+        spawn_stream_task(move |task| async move {
+            let stream = client
+                .stream_all_messages_with_commands(opts, parser)
+                .await?;
+            let stream = task.until_closed(stream);
+            task.run(stream, |metrics, event| {
+                metrics.record(&event);
+                futures::future::ready(callback(event))
+            })
+            .await;
+            tracing::debug!("`stream_all_messages_with_commands` stream ended, dropping stream");
+            Ok::<_, ClientError>(())
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions, subscriptions::MessageStreamFilter,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::encoded_content_to_bytes;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_parses_commands() {
+        let parser = CommandParser::new("/");
+        assert_eq!(
+            parser.parse_text(r#"/weather "new york"  tomorrow"#),
+            Some((
+                "weather".to_string(),
+                vec!["new york".to_string(), "tomorrow".to_string()]
+            ))
+        );
+        assert_eq!(
+            parser.parse_text("  /help"),
+            Some(("help".to_string(), vec![]))
+        );
+        assert_eq!(parser.parse_text("/"), None);
+        assert_eq!(parser.parse_text("hello /help"), None);
+        assert_eq!(
+            parser.parse_text(r#"/say """#),
+            Some(("say".to_string(), vec![String::new()]))
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_stream_all_messages_with_commands() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bot = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bot.inbox_id()])
+            .await
+            .unwrap();
+        bot.sync_welcomes(&bot.mls_provider().unwrap())
+            .await
+            .unwrap();
+
+        let stream = bot
+            .stream_all_messages_with_commands(
+                StreamAllMessagesOptions {
+                    filter: MessageStreamFilter::default().allow([ContentType::Text]),
+                    ..Default::default()
+                },
+                CommandParser::new("!").suppress_messages(true),
+            )
+            .await
+            .unwrap();
+        futures::pin_mut!(stream);
+
+        let text =
+            |text: &str| encoded_content_to_bytes(TextCodec::encode(text.to_string()).unwrap());
+        group.send_message(&text("hello")).await.unwrap();
+        group.send_message(&text("!roll 2d6")).await.unwrap();
+
+        match stream.next().await.unwrap().unwrap() {
+            BotEvent::Message(message) => assert_eq!(message.sender_inbox_id, alix.inbox_id()),
+            event => panic!("expected a message, got {event:?}"),
+        }
+        match stream.next().await.unwrap().unwrap() {
+            BotEvent::CommandReceived(command) => {
+                assert_eq!(command.name, "roll");
+                assert_eq!(command.args, vec!["2d6"]);
+                assert_eq!(command.sender_inbox_id, alix.inbox_id());
+                assert_eq!(command.group_id, group.group_id);
+            }
+            event => panic!("expected a command, got {event:?}"),
+        }
+    }
+}
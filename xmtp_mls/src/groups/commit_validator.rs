@@ -0,0 +1,299 @@
+//! Hooks that let apps enforce their own rules on group changes, e.g. "only corporate inboxes may
+//! be added". The [membership policy](super::membership_policy) and the commit validators make
+//! up a single [`CommitHook`] that runs on every commit this installation publishes, after the
+//! commit is created and before it is sent.
+//!
+//! Commits from other installations are never checked. Every member has to merge the same commits
+//! to stay in the same epoch, so rejecting one locally would fork this installation out of the
+//! group.
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use thiserror::Error;
+use xmtp_common::RetryableError;
+
+use super::{
+    membership_policy::{MembershipPolicyError, MembershipPolicyHook},
+    validated_commit::{Inbox, ValidatedCommit},
+    MlsGroup, ScopedGroupClient,
+};
+use crate::{storage::group::ConversationType, Client};
+
+#[derive(Debug, Error)]
+pub enum CommitValidatorError {
+    #[error("commit rejected by validator {validator}: {reason}")]
+    Denied { validator: String, reason: String },
+    #[error(transparent)]
+    MembershipPolicy(#[from] MembershipPolicyError),
+}
+
+impl RetryableError for CommitValidatorError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            Self::Denied { .. } => false,
+            Self::MembershipPolicy(err) => err.is_retryable(),
+        }
+    }
+}
+
+/// A change to a metadata field made by a commit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataChange {
+    pub field_name: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+/// What a commit changes in a group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitSummary {
+    pub group_id: Vec<u8>,
+    pub actor_inbox_id: String,
+    pub actor_installation_id: Vec<u8>,
+    pub added_inboxes: Vec<String>,
+    pub removed_inboxes: Vec<String>,
+    pub metadata_changes: Vec<MetadataChange>,
+    pub admins_added: Vec<String>,
+    pub admins_removed: Vec<String>,
+    pub super_admins_added: Vec<String>,
+    pub super_admins_removed: Vec<String>,
+    pub permissions_changed: bool,
+}
+
+impl CommitSummary {
+    pub(crate) fn new(group_id: &[u8], commit: &ValidatedCommit) -> Self {
+        let inbox_ids = |inboxes: &[Inbox]| -> Vec<String> {
+            inboxes.iter().map(|inbox| inbox.inbox_id.clone()).collect()
+        };
+        let changes = &commit.metadata_changes;
+        Self {
+            group_id: group_id.to_vec(),
+            actor_inbox_id: commit.actor.inbox_id.clone(),
+            actor_installation_id: commit.actor.installation_id.clone(),
+            added_inboxes: inbox_ids(&commit.added_inboxes),
+            removed_inboxes: inbox_ids(&commit.removed_inboxes),
+            metadata_changes: changes
+                .metadata_field_changes
+                .iter()
+                .map(|change| MetadataChange {
+                    field_name: change.field_name.clone(),
+                    old_value: change.old_value.clone(),
+                    new_value: change.new_value.clone(),
+                })
+                .collect(),
+            admins_added: inbox_ids(&changes.admins_added),
+            admins_removed: inbox_ids(&changes.admins_removed),
+            super_admins_added: inbox_ids(&changes.super_admins_added),
+            super_admins_removed: inbox_ids(&changes.super_admins_removed),
+            permissions_changed: commit.permissions_changed,
+        }
+    }
+}
+
+/// Whether a commit may be published
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommitDecision {
+    Allow,
+    /// Reject the commit, with a reason for the logs
+    Deny(String),
+}
+
+/// Runs on every commit this installation publishes, before it is sent.
+///
+/// Validators must be cheap: they run inline with publishing.
+pub trait CommitValidator: Send + Sync {
+    /// Identifies the validator in errors and logs
+    fn id(&self) -> &str;
+
+    fn validate(&self, commit: &CommitSummary) -> CommitDecision;
+}
+
+/// The membership policy and commit validators registered on a client
+#[derive(Default)]
+pub struct CommitHook {
+    pub(crate) membership_policy: MembershipPolicyHook,
+    validators: RwLock<Vec<Arc<dyn CommitValidator>>>,
+}
+
+impl CommitHook {
+    pub fn register(&self, validator: Arc<dyn CommitValidator>) {
+        let mut validators = self.validators.write();
+        validators.retain(|v| v.id() != validator.id());
+        validators.push(validator);
+    }
+
+    pub fn unregister(&self, id: &str) {
+        self.validators.write().retain(|v| v.id() != id);
+    }
+
+    /// Check an outgoing commit against the membership policy, for regular groups, and then
+    /// every registered validator, failing on the first rejection
+    pub(crate) async fn check(
+        &self,
+        own_inbox_id: &str,
+        conversation_type: ConversationType,
+        commit: &CommitSummary,
+    ) -> Result<(), CommitValidatorError> {
+        if conversation_type == ConversationType::Group {
+            let joining: Vec<&str> = commit
+                .added_inboxes
+                .iter()
+                .map(String::as_str)
+                .filter(|inbox_id| *inbox_id != own_inbox_id)
+                .collect();
+            self.membership_policy.check(&joining).await?;
+        }
+
+        // clone out of the lock so a slow validator doesn't block registration
+        let validators = self.validators.read().clone();
+        for validator in validators {
+            if let CommitDecision::Deny(reason) = validator.validate(commit) {
+                return Err(CommitValidatorError::Denied {
+                    validator: validator.id().to_string(),
+                    reason,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V> {
+    /// Register a validator to run on every commit published from now on.
+    /// Registering a validator with the same id replaces the previous one.
+    pub fn register_commit_validator(&self, validator: impl CommitValidator + 'static) {
+        self.context.commit_hook.register(Arc::new(validator));
+    }
+
+    pub fn unregister_commit_validator(&self, id: &str) {
+        self.context.commit_hook.unregister(id);
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Check a commit this installation is about to publish against the commit hook
+    pub(super) async fn check_commit_hook(
+        &self,
+        conversation_type: ConversationType,
+        commit: &ValidatedCommit,
+    ) -> Result<(), CommitValidatorError> {
+        let context = self.context();
+        context
+            .commit_hook
+            .check(
+                context.inbox_id(),
+                conversation_type,
+                &CommitSummary::new(&self.group_id, commit),
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use std::collections::HashSet;
+
+    use parking_lot::Mutex;
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::{GroupError, GroupMetadataOptions},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    /// Only lets inboxes on the corporate directory be added
+    struct CorporateInboxes {
+        directory: HashSet<String>,
+        seen: Arc<Mutex<Vec<CommitSummary>>>,
+    }
+
+    impl CommitValidator for CorporateInboxes {
+        fn id(&self) -> &str {
+            "corporate"
+        }
+
+        fn validate(&self, commit: &CommitSummary) -> CommitDecision {
+            self.seen.lock().push(commit.clone());
+            match commit
+                .added_inboxes
+                .iter()
+                .find(|inbox_id| !self.directory.contains(*inbox_id))
+            {
+                Some(inbox_id) => CommitDecision::Deny(format!("{inbox_id} is not corporate")),
+                None => CommitDecision::Allow,
+            }
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_commit_validator_gates_outgoing_commits() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        alix.register_commit_validator(CorporateInboxes {
+            directory: HashSet::from([bo.inbox_id().to_string()]),
+            seen: seen.clone(),
+        });
+        // commits from other installations are never checked
+        bo.register_commit_validator(CorporateInboxes {
+            directory: HashSet::new(),
+            seen: Arc::new(Mutex::new(vec![])),
+        });
+
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        let bo_group = bo.group(alix_group.group_id.clone()).unwrap();
+
+        alix_group
+            .update_group_name("corp".to_string())
+            .await
+            .unwrap();
+        {
+            let seen = seen.lock();
+            let summary = seen.last().unwrap();
+            assert_eq!(summary.actor_inbox_id, alix.inbox_id());
+            assert!(summary.added_inboxes.is_empty());
+            assert_eq!(
+                summary.metadata_changes[0].new_value.as_deref(),
+                Some("corp")
+            );
+        }
+        bo_group.sync().await.unwrap();
+        assert_eq!(
+            bo_group.group_name(&bo.mls_provider().unwrap()).unwrap(),
+            "corp"
+        );
+
+        let Err(GroupError::Sync(errors)) =
+            alix_group.add_members_by_inbox_id(&[caro.inbox_id()]).await
+        else {
+            panic!("adding caro should fail");
+        };
+        assert!(errors.iter().any(|e| matches!(
+            e,
+            GroupError::CommitValidator(CommitValidatorError::Denied { validator, .. })
+                if validator == "corporate"
+        )));
+        assert_eq!(
+            seen.lock().last().unwrap().added_inboxes,
+            vec![caro.inbox_id().to_string()]
+        );
+        // the rejected commit was never sent
+        bo_group.sync().await.unwrap();
+        assert_eq!(alix_group.members().await.unwrap().len(), 2);
+        assert_eq!(bo_group.members().await.unwrap().len(), 2);
+    }
+}
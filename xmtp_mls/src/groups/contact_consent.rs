@@ -0,0 +1,99 @@
+//! Consent given to a contact before any conversation with them exists. Consent records keyed by
+//! an inbox id or a wallet address can be set with [`Client::set_consent_states`] at any time,
+//! even for an address that isn't on the network yet, and are synced like any other record. When
+//! a DM with the contact arrives, it starts out with that consent, so e.g. blocking an address
+//! keeps its DMs out of the user's requests once it joins.
+//!
+//! [`Client::set_consent_states`]: crate::Client::set_consent_states
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::storage::{
+    consent_record::{ConsentType, StoredConsentRecord},
+    xmtp_openmls_provider::XmtpOpenMlsProvider,
+};
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Give a new DM the consent stored for `peer_inbox_id` or one of its addresses. A consent
+    /// state already set for the conversation is kept.
+    pub(super) async fn inherit_contact_consent(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        peer_inbox_id: &str,
+    ) -> Result<(), GroupError> {
+        let conn = provider.conn_ref();
+        let addresses = self
+            .client
+            .get_association_state(conn, peer_inbox_id, None)
+            .await?
+            .account_addresses();
+        let Some(state) = conn.contact_consent_state(peer_inbox_id, &addresses)? else {
+            return Ok(());
+        };
+
+        // Not stamped, so a decision the user makes about the conversation itself, on any
+        // installation, takes precedence
+        let record = StoredConsentRecord::new(
+            ConsentType::ConversationId,
+            state,
+            hex::encode(&self.group_id),
+        );
+        conn.maybe_insert_consent_record_return_existing(&record)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{
+        builder::ClientBuilder,
+        storage::consent_record::{ConsentState, ConsentType, StoredConsentRecord},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+    use xmtp_id::InboxOwner;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_new_dms_inherit_contact_consent() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo_wallet = generate_local_wallet();
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        // bo isn't on the network yet when alix blocks their address
+        alix.set_consent_states(&[StoredConsentRecord::new(
+            ConsentType::Address,
+            ConsentState::Denied,
+            bo_wallet.get_address(),
+        )])
+        .await
+        .unwrap();
+        alix.set_consent_states(&[StoredConsentRecord::new(
+            ConsentType::InboxId,
+            ConsentState::Allowed,
+            caro.inbox_id().to_string(),
+        )])
+        .await
+        .unwrap();
+
+        let bo = ClientBuilder::new_test_client(&bo_wallet).await;
+        bo.create_dm_by_inbox_id(alix.inbox_id().to_string())
+            .await
+            .unwrap();
+        caro.create_dm_by_inbox_id(alix.inbox_id().to_string())
+            .await
+            .unwrap();
+
+        alix.sync_welcomes(&alix.mls_provider().unwrap())
+            .await
+            .unwrap();
+        let bo_dm = alix
+            .dm_group_from_target_inbox(bo.inbox_id().to_string())
+            .unwrap();
+        let caro_dm = alix
+            .dm_group_from_target_inbox(caro.inbox_id().to_string())
+            .unwrap();
+        assert_eq!(bo_dm.consent_state().unwrap(), ConsentState::Denied);
+        assert_eq!(caro_dm.consent_state().unwrap(), ConsentState::Allowed);
+    }
+}
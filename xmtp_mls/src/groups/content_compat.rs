@@ -0,0 +1,202 @@
+//! Messages of content types this installation doesn't understand, e.g. a content type added in
+//! a newer version of the app, or a new major version of a standard one.
+//!
+//! Such messages are stored as received, and decode to [`DecodedContent::Unknown`] with the
+//! fallback text the sender declared, for apps to show in their place. Their content types are
+//! also recorded, and [`Client::content_compat_report`] lists the ones that still can't be
+//! decoded, so apps can tell which content types their users are missing out on.
+//!
+//! [`DecodedContent::Unknown`]: crate::decoded_message::DecodedContent::Unknown
+use prost::Message;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use super::{MlsGroup, ScopedGroupClient};
+use crate::{
+    client::ClientError,
+    codec_registry::CodecRegistry,
+    decoded_message::{DecodedContent, DecodedMessage},
+    storage::{
+        db_connection::DbConnection,
+        group_message::{ContentType, StoredGroupMessage},
+        unknown_content_type::StoredUnknownContentType,
+    },
+    Client, XmtpApi,
+};
+
+/// Whether messages of `content_type`, stored as `stored_type`, can be decoded or are processed
+/// by the core crate. A major version the registered codec doesn't decode isn't understood.
+fn is_understood(
+    codecs: &CodecRegistry,
+    content_type: &ContentTypeId,
+    stored_type: ContentType,
+) -> bool {
+    codecs.is_registered(content_type)
+        || (stored_type != ContentType::Unknown && !codecs.has_other_version(content_type))
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Record the content type of `message` if this installation doesn't understand it. Failing
+    /// to record it is logged, it must not fail message processing.
+    pub(super) fn note_unknown_content_type(
+        &self,
+        conn: &DbConnection,
+        message: &StoredGroupMessage,
+    ) {
+        let Some(content_type) = EncodedContent::decode(message.decrypted_message_bytes.as_slice())
+            .ok()
+            .and_then(|content| content.r#type)
+        else {
+            return;
+        };
+        let codecs = &self.client.context_ref().codecs;
+        if is_understood(codecs, &content_type, message.content_type) {
+            return;
+        }
+        tracing::debug!(
+            group_id = hex::encode(&self.group_id),
+            "received a message of unknown content type {}/{}:{}.{}",
+            content_type.authority_id,
+            content_type.type_id,
+            content_type.version_major,
+            content_type.version_minor
+        );
+        if let Err(e) = conn.record_unknown_content_type(
+            &content_type.authority_id,
+            &content_type.type_id,
+            content_type.version_major as i32,
+            content_type.version_minor as i32,
+            message.sent_at_ns,
+        ) {
+            tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                "failed to record unknown content type: {e}"
+            );
+        }
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// The content types received that this installation can't decode, most recently seen
+    /// first. Content types a codec was registered for since are left out.
+    pub fn content_compat_report(&self) -> Result<Vec<StoredUnknownContentType>, ClientError> {
+        let mut report = self.store().conn()?.unknown_content_types()?;
+        report.retain(|seen| {
+            let content_type = ContentTypeId {
+                authority_id: seen.authority_id.clone(),
+                type_id: seen.type_id.clone(),
+                version_major: seen.version_major as u32,
+                version_minor: seen.version_minor as u32,
+            };
+            let stored_type = ContentType::from(seen.type_id.clone());
+            !is_understood(&self.context.codecs, &content_type, stored_type)
+        });
+        Ok(report)
+    }
+
+    /// The text to show in place of `message` if the client's codecs can't decode it: the
+    /// fallback its sender declared, see [`DecodedMessage::fallback`]. `None` if the content
+    /// can be decoded, or the sender declared no fallback.
+    pub fn message_fallback(&self, message: StoredGroupMessage) -> Option<String> {
+        let encoded = message.encoded_content().ok()?;
+        let content = self
+            .decode_message(&message)
+            .unwrap_or(DecodedContent::Unknown(encoded));
+        DecodedMessage { message, content }
+            .fallback()
+            .map(str::to_string)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{
+        builder::ClientBuilder,
+        decoded_message::DecodedContent,
+        groups::GroupMetadataOptions,
+        storage::group_message::{GroupMessageKind, MsgQueryArgs},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::{encoded_content_to_bytes, text::TextCodec, ContentCodec};
+    use xmtp_cryptography::utils::generate_local_wallet;
+    use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_unknown_content_types_fall_back() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+
+        let sticker = EncodedContent {
+            r#type: Some(ContentTypeId {
+                authority_id: "example.com".to_string(),
+                type_id: "sticker".to_string(),
+                version_major: 1,
+                version_minor: 0,
+            }),
+            fallback: Some("[a cat sticker]".to_string()),
+            content: b"cats/7".to_vec(),
+            ..Default::default()
+        };
+        // a text message of a major version that doesn't exist yet
+        let text_v2 = EncodedContent {
+            r#type: Some(ContentTypeId {
+                version_major: 2,
+                ..TextCodec::content_type()
+            }),
+            ..TextCodec::encode("hello".to_string()).unwrap()
+        };
+        for content in [sticker, text_v2] {
+            group
+                .send_message(&encoded_content_to_bytes(content))
+                .await
+                .unwrap();
+        }
+
+        bo_group.sync().await.unwrap();
+        let messages = bo_group
+            .find_decoded_messages(&MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(matches!(messages[0].content, DecodedContent::Unknown(_)));
+        assert_eq!(messages[0].fallback(), Some("[a cat sticker]"));
+        assert!(matches!(messages[1].content, DecodedContent::Unknown(_)));
+        assert_eq!(messages[1].fallback(), None);
+        assert_eq!(
+            bo.message_fallback(messages[0].message.clone()).as_deref(),
+            Some("[a cat sticker]")
+        );
+
+        let report = bo.content_compat_report().unwrap();
+        assert_eq!(report.len(), 2);
+        assert!(report
+            .iter()
+            .any(|seen| seen.type_id == "text" && seen.version_major == 2));
+        assert!(report
+            .iter()
+            .any(|seen| seen.type_id == "sticker" && seen.message_count == 1));
+        // alix doesn't report the messages it sent
+        assert!(alix.content_compat_report().unwrap().is_empty());
+    }
+}
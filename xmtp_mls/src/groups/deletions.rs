@@ -0,0 +1,304 @@
+//! Deleting sent messages. A deletion is sent as a tombstone message; applying it erases the
+//! content of the stored message on every member's device and keeps only its metadata.
+//!
+//! Senders can always delete their own messages. Whether admins can also delete other members'
+//! messages is set per group in its mutable metadata. Metadata fields without a policy can only
+//! be changed by admins, so members can't grant themselves the right.
+use openmls::group::MlsGroup as OpenMlsGroup;
+use thiserror::Error;
+use xmtp_common::RetryableError;
+use xmtp_content_types::{
+    delete_message::{DeleteMessage, DeleteMessageCodec},
+    encoded_content_to_bytes, CodecError, ContentCodec,
+};
+
+use super::{
+    group_mutable_metadata::GroupMutableMetadata, intents::UpdateMetadataIntentData, GroupError,
+    MlsGroup, ScopedGroupClient,
+};
+use crate::{
+    storage::{
+        db_connection::DbConnection,
+        group::ConversationType,
+        group_intent::IntentKind,
+        group_message::{ContentType, GroupMessageKind, StoredGroupMessage},
+        NotFound, StorageError,
+    },
+    subscriptions::{LocalEvents, MessageDeletion},
+    XmtpOpenMlsProvider,
+};
+
+/// The metadata field holding the group's [`MessageDeletionPolicy`]
+pub const MESSAGE_DELETION_POLICY_METADATA_FIELD: &str = "message_deletion_policy";
+
+/// Who may delete a message
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MessageDeletionPolicy {
+    /// Only the sender of a message
+    #[default]
+    SenderOnly,
+    /// The sender of a message or any admin of the group
+    SenderOrAdmins,
+}
+
+impl MessageDeletionPolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::SenderOnly => "sender_only",
+            Self::SenderOrAdmins => "sender_or_admins",
+        }
+    }
+
+    fn from_metadata(metadata: &GroupMutableMetadata) -> Self {
+        match metadata
+            .attributes
+            .get(MESSAGE_DELETION_POLICY_METADATA_FIELD)
+            .map(String::as_str)
+        {
+            Some("sender_or_admins") => Self::SenderOrAdmins,
+            _ => Self::SenderOnly,
+        }
+    }
+
+    /// Whether `inbox_id` may delete `message` under this policy
+    fn allows(
+        &self,
+        metadata: &GroupMutableMetadata,
+        inbox_id: &str,
+        message: &StoredGroupMessage,
+    ) -> bool {
+        let inbox_id = inbox_id.to_string();
+        message.sender_inbox_id == inbox_id
+            || (*self == Self::SenderOrAdmins
+                && (metadata.is_admin(&inbox_id) || metadata.is_super_admin(&inbox_id)))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DeletionError {
+    #[error("inbox {0} is not allowed to delete this message")]
+    NotAllowed(String),
+    #[error("{0} messages can't be deleted")]
+    NotDeletable(ContentType),
+    #[error("message was already deleted")]
+    AlreadyDeleted,
+}
+
+impl RetryableError for DeletionError {
+    fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+/// Whether `message` can be deleted at all, regardless of who deletes it
+fn check_deletable(message: &StoredGroupMessage) -> Result<(), DeletionError> {
+    if message.deleted_at_ns.is_some() {
+        return Err(DeletionError::AlreadyDeleted);
+    }
+    match message.content_type {
+        ContentType::DeleteMessage => Err(DeletionError::NotDeletable(message.content_type)),
+        _ if message.kind != GroupMessageKind::Application => {
+            Err(DeletionError::NotDeletable(message.content_type))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Delete `message_id` for every member of the conversation. Returns the id of the
+    /// tombstone message.
+    pub async fn delete_message(&self, message_id: &[u8]) -> Result<Vec<u8>, GroupError> {
+        let provider = self.mls_provider()?;
+        let message = provider
+            .conn_ref()
+            .get_group_message(message_id)?
+            .filter(|message| message.group_id == self.group_id)
+            .ok_or_else(|| StorageError::from(NotFound::MessageById(message_id.to_vec())))?;
+        check_deletable(&message)?;
+        let metadata = self.mutable_metadata(&provider)?;
+        let inbox_id = self.context().inbox_id();
+        if !MessageDeletionPolicy::from_metadata(&metadata).allows(&metadata, inbox_id, &message) {
+            return Err(DeletionError::NotAllowed(inbox_id.to_string()).into());
+        }
+
+        let deletion = DeleteMessageCodec::encode(DeleteMessage {
+            reference: hex::encode(message_id),
+        })?;
+        self.send_message(&encoded_content_to_bytes(deletion)).await
+    }
+
+    /// Who may delete messages in this group
+    pub fn message_deletion_policy(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<MessageDeletionPolicy, GroupError> {
+        Ok(MessageDeletionPolicy::from_metadata(
+            &self.mutable_metadata(provider)?,
+        ))
+    }
+
+    /// Change who may delete messages in this group. Requires admin permissions.
+    pub async fn set_message_deletion_policy(
+        &self,
+        policy: MessageDeletionPolicy,
+    ) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        if self.metadata(&provider).await?.conversation_type == ConversationType::Dm {
+            return Err(GroupError::DmGroupMetadataForbidden);
+        }
+        let intent_data: Vec<u8> = UpdateMetadataIntentData::new(
+            MESSAGE_DELETION_POLICY_METADATA_FIELD.to_string(),
+            policy.as_str().to_string(),
+        )
+        .into();
+        let intent = self.queue_intent(&provider, IntentKind::MetadataUpdate, intent_data)?;
+
+        self.sync_until_intent_resolved(&provider, intent.id).await
+    }
+
+    /// Apply the deletion in `message` to the message it references, checking it against the
+    /// group's deletion policy as of `mls_group`. A deletion that is malformed or not allowed is
+    /// logged and ignored, it must not fail message processing.
+    pub(super) fn process_deletion(
+        &self,
+        conn: &DbConnection,
+        mls_group: &OpenMlsGroup,
+        message: &StoredGroupMessage,
+    ) {
+        match apply_deletion(conn, mls_group, message) {
+            Ok(Some(deletion)) => {
+                self.recount_unread(conn);
+                let _ = self
+                    .client
+                    .local_events()
+                    .send(LocalEvents::MessageDeleted(deletion));
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                message_id = hex::encode(&message.id),
+                "ignoring deletion: {e}"
+            ),
+        }
+    }
+}
+
+fn apply_deletion(
+    conn: &DbConnection,
+    mls_group: &OpenMlsGroup,
+    message: &StoredGroupMessage,
+) -> Result<Option<MessageDeletion>, GroupError> {
+    let content = message
+        .encoded_content()
+        .map_err(|e| CodecError::Decode(e.to_string()))?;
+    let deletion = DeleteMessageCodec::decode(content)?;
+    let deleted_id =
+        hex::decode(&deletion.reference).map_err(|e| CodecError::Decode(e.to_string()))?;
+    let original = conn
+        .get_group_message(&deleted_id)?
+        .filter(|original| original.group_id == message.group_id)
+        .ok_or_else(|| StorageError::from(NotFound::MessageById(deleted_id.clone())))?;
+    match check_deletable(&original) {
+        // a deletion sent by several installations of the same inbox
+        Err(DeletionError::AlreadyDeleted) => return Ok(None),
+        result => result?,
+    }
+    let metadata = GroupMutableMetadata::try_from(mls_group)?;
+    if !MessageDeletionPolicy::from_metadata(&metadata).allows(
+        &metadata,
+        &message.sender_inbox_id,
+        &original,
+    ) {
+        return Err(DeletionError::NotAllowed(message.sender_inbox_id.clone()).into());
+    }
+
+    if !conn.mark_message_deleted(&original.id, message.sent_at_ns)? {
+        return Ok(None);
+    }
+    Ok(Some(MessageDeletion {
+        group_id: message.group_id.clone(),
+        message_id: original.id,
+        delete_message_id: message.id.clone(),
+        deleted_by_inbox_id: message.sender_inbox_id.clone(),
+    }))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::text::TextCodec;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_delete_message() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+        let bo_message_id = bo_group.send_message(b"oops").await.unwrap();
+        let edit_id = bo_group
+            .edit_message(
+                &bo_message_id,
+                TextCodec::encode("oops!".to_string()).unwrap(),
+            )
+            .await
+            .unwrap();
+        alix_group.sync().await.unwrap();
+
+        // only the sender may delete until admins are allowed to
+        assert!(matches!(
+            alix_group.delete_message(&bo_message_id).await,
+            Err(GroupError::Deletion(DeletionError::NotAllowed(_)))
+        ));
+        alix_group
+            .set_message_deletion_policy(MessageDeletionPolicy::SenderOrAdmins)
+            .await
+            .unwrap();
+        assert_eq!(
+            alix_group
+                .message_deletion_policy(&alix.mls_provider().unwrap())
+                .unwrap(),
+            MessageDeletionPolicy::SenderOrAdmins
+        );
+
+        let deletions = bo_group.stream_message_deletions();
+        futures::pin_mut!(deletions);
+        let tombstone_id = alix_group.delete_message(&bo_message_id).await.unwrap();
+        bo_group.sync().await.unwrap();
+
+        let deletion = deletions.next().await.unwrap().unwrap();
+        assert_eq!(deletion.message_id, bo_message_id);
+        assert_eq!(deletion.delete_message_id, tombstone_id);
+        assert_eq!(deletion.deleted_by_inbox_id, alix.inbox_id());
+
+        for group in [&alix_group, &bo_group] {
+            let conn = group.context().store().conn().unwrap();
+            // the edit's content is erased along with the message it changed
+            for id in [&bo_message_id, &edit_id] {
+                let message = conn.get_group_message(id).unwrap().unwrap();
+                assert!(message.deleted_at_ns.is_some());
+                assert!(message.decrypted_message_bytes.is_empty());
+            }
+        }
+        assert!(matches!(
+            bo_group.delete_message(&bo_message_id).await,
+            Err(GroupError::Deletion(DeletionError::AlreadyDeleted))
+        ));
+    }
+}
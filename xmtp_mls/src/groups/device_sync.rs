@@ -20,7 +20,9 @@ use aes_gcm::{
     Aes256Gcm,
 };
 use futures::{Stream, StreamExt};
+use history_scope::HistorySyncScope;
 use preference_sync::UserPreferenceUpdate;
+use progress::{SyncPhase, SyncProgress, SYNC_PROGRESS_INTERVAL};
 use rand::{Rng, RngCore};
 use serde::{Deserialize, Serialize};
 use std::pin::Pin;
@@ -43,8 +45,12 @@ use xmtp_proto::xmtp::mls::message_contents::{
 };
 
 pub mod consent_sync;
+pub mod custom_preferences;
+pub mod history_scope;
 pub mod message_sync;
+pub mod preference_export;
 pub mod preference_sync;
+pub mod progress;
 
 pub const ENC_KEY_SIZE: usize = 32; // 256-bit key
 pub const NONCE_SIZE: usize = 12; // 96-bit nonce
@@ -105,11 +111,15 @@ pub enum DeviceSyncError {
     Subscribe(#[from] SubscribeError),
     #[error(transparent)]
     Bincode(#[from] bincode::Error),
+    #[error("device sync was cancelled")]
+    Cancelled,
+    #[error("device sync is paused")]
+    Paused,
 }
 
 impl RetryableError for DeviceSyncError {
     fn is_retryable(&self) -> bool {
-        true
+        !matches!(self, Self::Cancelled | Self::Paused)
     }
 }
 
@@ -168,18 +178,32 @@ where
         self.sync_init().await?;
 
         while let Some(event) = self.stream.next().await {
-            let event = event?;
+            let event = match event {
+                Err(SubscribeError::LaggedEvents(missed)) => {
+                    tracing::warn!("Sync worker missed {missed} local events");
+                    continue;
+                }
+                event => event?,
+            };
             match event {
-                LocalEvents::SyncMessage(msg) => match msg {
-                    SyncMessage::Reply { message_id } => {
-                        let provider = self.client.mls_provider()?;
-                        self.on_reply(message_id, &provider).await?
-                    }
-                    SyncMessage::Request { message_id } => {
-                        let provider = self.client.mls_provider()?;
-                        self.on_request(message_id, &provider).await?
+                LocalEvents::SyncMessage(msg) => {
+                    self.client.context.sync_control.begin(&msg);
+                    let provider = self.client.mls_provider()?;
+                    let result = match msg {
+                        SyncMessage::Reply { message_id } => {
+                            self.on_reply(message_id, &provider).await
+                        }
+                        SyncMessage::Request { message_id } => {
+                            self.on_request(message_id, &provider).await
+                        }
+                    };
+                    self.client.context.sync_control.finish();
+                    match result {
+                        Err(DeviceSyncError::Cancelled) => tracing::info!("device sync cancelled"),
+                        Err(DeviceSyncError::Paused) => tracing::info!("device sync paused"),
+                        result => result?,
                     }
-                },
+                }
                 LocalEvents::OutgoingPreferenceUpdates(preference_updates) => {
                     tracing::error!("Outgoing preference update {preference_updates:?}");
                     retry_async!(
@@ -291,8 +315,9 @@ where
                 client
                     .send_sync_request(&provider, DeviceSyncKind::Consent)
                     .await?;
+                let scope = client.context.history_sync_scope.get().cloned();
                 client
-                    .send_sync_request(&provider, DeviceSyncKind::MessageHistory)
+                    .request_message_history(&provider, scope.unwrap_or_default())
                     .await?;
             }
             tracing::info!(
@@ -388,11 +413,21 @@ where
         &self,
         provider: &XmtpOpenMlsProvider,
         kind: DeviceSyncKind,
+    ) -> Result<DeviceSyncRequestProto, DeviceSyncError> {
+        self.send_scoped_sync_request(provider, kind, HistorySyncScope::default())
+            .await
+    }
+
+    async fn send_scoped_sync_request(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        kind: DeviceSyncKind,
+        scope: HistorySyncScope,
     ) -> Result<DeviceSyncRequestProto, DeviceSyncError> {
         tracing::info!(
             inbox_id = self.inbox_id(),
             installation_id = hex::encode(self.installation_public_key()),
-            "Sending a sync request for {kind:?}"
+            "Sending a sync request for {kind:?} with scope {scope:?}"
         );
         let request = DeviceSyncRequest::new(kind);
 
@@ -410,6 +445,11 @@ where
         // build the request
         let request: DeviceSyncRequestProto = request.into();
 
+        // the scope goes first, so it's stored by the time the request is handled
+        if !scope.is_everything() {
+            self.send_sync_request_scope(provider, &request.request_id, scope)?;
+        }
+
         let content = DeviceSyncContent::Request(request.clone());
         let content_bytes = serde_json::to_vec(&content)?;
 
@@ -425,6 +465,8 @@ where
 
         // publish the intent
         sync_group.publish_intents(provider).await?;
+        self.report_sync_progress(SyncProgress::new(kind, SyncPhase::Requesting))
+            .await;
 
         Ok(request)
     }
@@ -439,15 +481,31 @@ where
         let records = match request.kind() {
             DeviceSyncKind::Consent => vec![self.syncable_consent_records(conn)?],
             DeviceSyncKind::MessageHistory => {
-                vec![self.syncable_groups(conn)?, self.syncable_messages(conn)?]
+                let scope = self.get_sync_request_scope(provider, &request.request_id)?;
+                vec![
+                    self.syncable_groups(conn, &scope)?,
+                    self.syncable_messages(conn, &scope)?,
+                ]
             }
             DeviceSyncKind::Unspecified => return Err(DeviceSyncError::UnspecifiedDeviceSyncKind),
         };
+        let items_total = records.iter().map(|records| records.len() as u64).sum();
+        let progress = SyncProgress {
+            items_total,
+            ..SyncProgress::new(request.kind(), SyncPhase::Exporting)
+        };
+        self.sync_checkpoint(progress).await?;
 
         let reply = self
             .create_sync_reply(&request.request_id, &records, request.kind())
             .await?;
         self.send_sync_reply(provider, reply.clone()).await?;
+        self.report_sync_progress(SyncProgress {
+            phase: SyncPhase::Done,
+            items_done: items_total,
+            ..progress
+        })
+        .await;
 
         Ok(reply)
     }
@@ -579,8 +637,11 @@ where
             return Err(DeviceSyncError::InvalidPayload);
         };
 
+        self.sync_checkpoint(SyncProgress::new(reply.kind(), SyncPhase::Downloading))
+            .await?;
         let enc_payload = download_history_payload(&reply.url).await?;
-        self.insert_encrypted_syncables(provider, enc_payload, &enc_key.try_into()?)
+        let progress = self
+            .insert_encrypted_syncables(provider, reply.kind(), enc_payload, &enc_key.try_into()?)
             .await?;
 
         self.sync_welcomes(provider).await?;
@@ -592,6 +653,11 @@ where
             group.maybe_update_installations(provider, None).await?;
             Box::pin(group.sync_with_conn(provider)).await?;
         }
+        self.report_sync_progress(SyncProgress {
+            phase: SyncPhase::Done,
+            ..progress
+        })
+        .await;
 
         Ok(())
     }
@@ -622,6 +688,12 @@ where
         kind: DeviceSyncKind,
     ) -> Result<DeviceSyncReplyProto, DeviceSyncError> {
         let (payload, enc_key) = encrypt_syncables(syncables)?;
+        let items_total = syncables.iter().map(|records| records.len() as u64).sum();
+        self.sync_checkpoint(SyncProgress {
+            items_total,
+            ..SyncProgress::new(kind, SyncPhase::Uploading)
+        })
+        .await?;
 
         // upload the payload
         let Some(url) = &self.history_sync_url else {
@@ -668,9 +740,10 @@ where
     async fn insert_encrypted_syncables(
         &self,
         provider: &XmtpOpenMlsProvider,
+        kind: DeviceSyncKind,
         payload: Vec<u8>,
         enc_key: &DeviceSyncKeyType,
-    ) -> Result<(), DeviceSyncError> {
+    ) -> Result<SyncProgress, DeviceSyncError> {
         let conn = provider.conn_ref();
         let enc_key = enc_key.as_bytes();
 
@@ -684,8 +757,19 @@ where
         // Decrypt the ciphertext
         let payload = cipher.decrypt(nonce_array, ciphertext)?;
         let payload: Vec<Syncable> = serde_json::from_slice(&payload)?;
+        // a sync that was paused skips the items it already imported
+        let items_done = self
+            .context
+            .sync_control
+            .resumed_items(SyncPhase::Importing);
+        let mut progress = SyncProgress {
+            items_total: payload.len() as u64,
+            items_done,
+            ..SyncProgress::new(kind, SyncPhase::Importing)
+        };
+        self.sync_checkpoint(progress).await?;
 
-        for syncable in payload {
+        for syncable in payload.into_iter().skip(items_done as usize) {
             match syncable {
                 Syncable::Group(group) => {
                     conn.insert_or_replace_group(group)?;
@@ -709,18 +793,22 @@ where
                     {
                         if existing_consent_record.state != consent_record.state {
                             warn!("Existing consent record exists and does not match payload state. Streaming consent_record update to sync group.");
-                            self.local_events
-                                .send(LocalEvents::OutgoingPreferenceUpdates(vec![
-                                    UserPreferenceUpdate::ConsentUpdate(existing_consent_record),
-                                ]))
-                                .map_err(|e| DeviceSyncError::Generic(e.to_string()))?;
+                            self.publish_local_event(LocalEvents::OutgoingPreferenceUpdates(vec![
+                                UserPreferenceUpdate::ConsentUpdate(existing_consent_record),
+                            ]))
+                            .await;
                         }
                     }
                 }
             };
+
+            progress.items_done += 1;
+            if progress.items_done % SYNC_PROGRESS_INTERVAL == 0 {
+                self.sync_checkpoint(progress).await?;
+            }
         }
 
-        Ok(())
+        Ok(progress)
     }
 
     #[instrument(level = "trace", skip_all)]
@@ -739,6 +827,11 @@ where
 pub enum DeviceSyncContent {
     Request(DeviceSyncRequestProto),
     Reply(DeviceSyncReplyProto),
+    /// The part of the history a request asks for
+    Scope {
+        request_id: String,
+        scope: HistorySyncScope,
+    },
 }
 
 pub struct MessageHistoryUrls;
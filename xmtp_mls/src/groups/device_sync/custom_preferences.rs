@@ -0,0 +1,185 @@
+//! Preferences defined by the app, synced across the user's installations through the sync group
+//! like consent and the built-in preferences. An app registers a namespace for each of its
+//! settings, e.g. `com.example.theme`, and stores a small opaque payload under it; encoding the
+//! payload is up to the app.
+//!
+//! Concurrent writes from different installations are ordered by
+//! [hybrid logical clock](crate::hlc) timestamps, so every installation keeps the same one.
+//! Updates are stored even if their namespace isn't registered here, e.g. by an older version of
+//! the app, so nothing is lost once it is.
+use std::collections::HashSet;
+
+use parking_lot::RwLock;
+use thiserror::Error;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use super::preference_sync::UserPreferenceUpdate;
+use crate::{
+    storage::{custom_preference::StoredCustomPreference, StorageError},
+    subscriptions::LocalEvents,
+    Client, XmtpApi,
+};
+
+/// The largest payload a custom preference can have. Preferences are sent to every installation
+/// with each change, so they're meant for settings, not content.
+pub const MAX_CUSTOM_PREFERENCE_SIZE: usize = 16 * 1024;
+
+#[derive(Debug, Error)]
+pub enum CustomPreferenceError {
+    #[error("preference namespace {0} isn't registered")]
+    UnregisteredNamespace(String),
+    #[error("preference namespace must not be empty")]
+    EmptyNamespace,
+    #[error("preference payload is {size} bytes, more than the maximum of {max}")]
+    TooLarge { size: usize, max: usize },
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+/// The namespaces the app registered for its preferences
+#[derive(Default)]
+pub struct PreferenceNamespaces(RwLock<HashSet<String>>);
+
+impl PreferenceNamespaces {
+    pub fn register(&self, namespace: &str) -> Result<(), CustomPreferenceError> {
+        if namespace.is_empty() {
+            return Err(CustomPreferenceError::EmptyNamespace);
+        }
+        self.0.write().insert(namespace.to_string());
+        Ok(())
+    }
+
+    pub fn unregister(&self, namespace: &str) {
+        self.0.write().remove(namespace);
+    }
+
+    pub fn is_registered(&self, namespace: &str) -> bool {
+        self.0.read().contains(namespace)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Allow preferences to be stored under `namespace`. Apps should register their namespaces
+    /// every time the client is built.
+    pub fn register_preference_namespace(
+        &self,
+        namespace: &str,
+    ) -> Result<(), CustomPreferenceError> {
+        self.context.preference_namespaces.register(namespace)
+    }
+
+    /// Stop accepting writes to `namespace`. Stored and incoming preferences are kept.
+    pub fn unregister_preference_namespace(&self, namespace: &str) {
+        self.context.preference_namespaces.unregister(namespace)
+    }
+
+    /// Store `payload` as the preference in `namespace`, replacing the previous one, and sync it
+    /// to the user's other installations
+    pub fn set_custom_preference(
+        &self,
+        namespace: &str,
+        payload: &[u8],
+    ) -> Result<(), CustomPreferenceError> {
+        if payload.len() > MAX_CUSTOM_PREFERENCE_SIZE {
+            return Err(CustomPreferenceError::TooLarge {
+                size: payload.len(),
+                max: MAX_CUSTOM_PREFERENCE_SIZE,
+            });
+        }
+        self.write_custom_preference(namespace, Some(payload.to_vec()))
+    }
+
+    /// Remove the preference in `namespace`, on this and the user's other installations
+    pub fn remove_custom_preference(&self, namespace: &str) -> Result<(), CustomPreferenceError> {
+        self.write_custom_preference(namespace, None)
+    }
+
+    /// The preference in `namespace`, whether or not the namespace is registered
+    pub fn custom_preference(
+        &self,
+        namespace: &str,
+    ) -> Result<Option<Vec<u8>>, CustomPreferenceError> {
+        let conn = self.store().conn()?;
+        Ok(conn
+            .get_custom_preference(namespace)?
+            .and_then(|preference| preference.payload))
+    }
+
+    fn write_custom_preference(
+        &self,
+        namespace: &str,
+        payload: Option<Vec<u8>>,
+    ) -> Result<(), CustomPreferenceError> {
+        if !self.context.preference_namespaces.is_registered(namespace) {
+            return Err(CustomPreferenceError::UnregisteredNamespace(
+                namespace.to_string(),
+            ));
+        }
+        let conn = self.store().conn()?;
+        let preference =
+            StoredCustomPreference::new(namespace.to_string(), payload, self.context.hlc.now());
+        conn.set_custom_preference(&preference)?;
+
+        if self.history_sync_url().is_some() {
+            // Dispatch an update event so it can be synced across devices
+            let _ = self
+                .local_events
+                .send(LocalEvents::OutgoingPreferenceUpdates(vec![
+                    UserPreferenceUpdate::CustomUpdate(preference),
+                ]));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::builder::ClientBuilder;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_custom_preferences_need_a_registered_namespace() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let theme = "com.example.theme";
+
+        assert!(matches!(
+            alix.set_custom_preference(theme, b"dark"),
+            Err(CustomPreferenceError::UnregisteredNamespace(_))
+        ));
+        alix.register_preference_namespace(theme).unwrap();
+        alix.set_custom_preference(theme, b"dark").unwrap();
+        alix.set_custom_preference(theme, b"light").unwrap();
+        assert_eq!(
+            alix.custom_preference(theme).unwrap(),
+            Some(b"light".to_vec())
+        );
+
+        let too_large = vec![0; MAX_CUSTOM_PREFERENCE_SIZE + 1];
+        assert!(matches!(
+            alix.set_custom_preference(theme, &too_large),
+            Err(CustomPreferenceError::TooLarge { .. })
+        ));
+
+        alix.remove_custom_preference(theme).unwrap();
+        assert_eq!(alix.custom_preference(theme).unwrap(), None);
+
+        // stored preferences stay readable after the namespace is unregistered
+        alix.register_preference_namespace(theme).unwrap();
+        alix.set_custom_preference(theme, b"dark").unwrap();
+        alix.unregister_preference_namespace(theme);
+        assert_eq!(
+            alix.custom_preference(theme).unwrap(),
+            Some(b"dark".to_vec())
+        );
+        assert!(alix.remove_custom_preference(theme).is_err());
+    }
+}
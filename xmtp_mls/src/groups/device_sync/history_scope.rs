@@ -0,0 +1,195 @@
+//! Selective history sync. An installation can ask for only recent messages, or only some
+//! conversations, instead of its whole history, which makes restoring a large account much
+//! faster.
+//!
+//! The scope is sent to the sync group as its own message, just before the request it applies
+//! to, and is matched to the request by id. Installations that don't know about scopes ignore
+//! the message and reply with the full history.
+use serde::{Deserialize, Serialize};
+use xmtp_common::time::now_ns;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::xmtp::mls::message_contents::{
+    plaintext_envelope::{Content, V1},
+    DeviceSyncKind, DeviceSyncRequest as DeviceSyncRequestProto, PlaintextEnvelope,
+};
+
+use super::{DeviceSyncContent, DeviceSyncError};
+use crate::{
+    configuration::NS_IN_DAY,
+    storage::{
+        group_message::{GroupMessageKind, MsgQueryArgs},
+        xmtp_openmls_provider::XmtpOpenMlsProvider,
+    },
+    Client, XmtpApi,
+};
+
+/// Which part of the message history a sync request asks for. The default asks for all of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistorySyncScope {
+    /// Only messages sent after this time
+    pub since_ns: Option<i64>,
+    /// Only these conversations
+    pub group_ids: Option<Vec<Vec<u8>>>,
+}
+
+impl HistorySyncScope {
+    /// Only messages sent in the last `days` days
+    pub fn last_days(days: u32) -> Self {
+        Self {
+            since_ns: Some(now_ns() - days as i64 * NS_IN_DAY),
+            group_ids: None,
+        }
+    }
+
+    /// Only the history of `group_ids`
+    pub fn conversations(group_ids: Vec<Vec<u8>>) -> Self {
+        Self {
+            since_ns: None,
+            group_ids: Some(group_ids),
+        }
+    }
+
+    pub fn is_everything(&self) -> bool {
+        self == &Self::default()
+    }
+
+    pub(crate) fn includes_group(&self, group_id: &[u8]) -> bool {
+        self.group_ids
+            .as_ref()
+            .is_none_or(|ids| ids.iter().any(|id| id == group_id))
+    }
+
+    pub(crate) fn message_query(&self) -> MsgQueryArgs {
+        MsgQueryArgs {
+            sent_after_ns: self.since_ns,
+            ..Default::default()
+        }
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Ask the other installations for the part of the message history in `scope`.
+    /// If a message history request is already pending, it's returned as is.
+    pub async fn request_message_history(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        scope: HistorySyncScope,
+    ) -> Result<DeviceSyncRequestProto, DeviceSyncError> {
+        self.send_scoped_sync_request(provider, DeviceSyncKind::MessageHistory, scope)
+            .await
+    }
+
+    /// Send `scope` to the sync group, ahead of the request it applies to
+    pub(super) fn send_sync_request_scope(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        request_id: &str,
+        scope: HistorySyncScope,
+    ) -> Result<(), DeviceSyncError> {
+        let sync_group = self.get_sync_group(provider.conn_ref())?;
+        let content = DeviceSyncContent::Scope {
+            request_id: request_id.to_string(),
+            scope,
+        };
+        let content_bytes = serde_json::to_vec(&content)?;
+        sync_group.prepare_message(&content_bytes, provider, {
+            let content_bytes = content_bytes.clone();
+            move |now| PlaintextEnvelope {
+                content: Some(Content::V1(V1 {
+                    content: content_bytes,
+                    idempotency_key: now.to_string(),
+                })),
+            }
+        })?;
+
+        Ok(())
+    }
+
+    /// The scope sent along with the request `request_id`, or everything if there wasn't one
+    pub(super) fn get_sync_request_scope(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        request_id: &str,
+    ) -> Result<HistorySyncScope, DeviceSyncError> {
+        let sync_group = self.get_sync_group(provider.conn_ref())?;
+        let messages = provider.conn_ref().get_group_messages(
+            &sync_group.group_id,
+            &MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            },
+        )?;
+
+        let scope = messages.into_iter().rev().find_map(|msg| {
+            match serde_json::from_slice(&msg.decrypted_message_bytes) {
+                Ok(DeviceSyncContent::Scope {
+                    request_id: id,
+                    scope,
+                }) if id == request_id => Some(scope),
+                _ => None,
+            }
+        });
+        Ok(scope.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::{device_sync::Syncable, GroupMetadataOptions},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_syncables_are_limited_to_the_scope() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let first = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let second = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        first.send_message(b"old").await.unwrap();
+        let cutoff = now_ns();
+        first.send_message(b"new").await.unwrap();
+        second.send_message(b"other").await.unwrap();
+        let conn = alix.store().conn().unwrap();
+
+        let texts = |scope: &HistorySyncScope| -> Vec<Vec<u8>> {
+            alix.syncable_messages(&conn, scope)
+                .unwrap()
+                .into_iter()
+                .filter_map(|syncable| match syncable {
+                    Syncable::GroupMessage(msg) if msg.kind == GroupMessageKind::Application => {
+                        Some(msg.decrypted_message_bytes)
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+
+        assert_eq!(texts(&HistorySyncScope::default()).len(), 3);
+        let recent = HistorySyncScope {
+            since_ns: Some(cutoff),
+            group_ids: None,
+        };
+        let mut recent_texts = texts(&recent);
+        recent_texts.sort();
+        assert_eq!(recent_texts, vec![b"new".to_vec(), b"other".to_vec()]);
+
+        let only_first = HistorySyncScope::conversations(vec![first.group_id.clone()]);
+        assert_eq!(texts(&only_first), vec![b"old".to_vec(), b"new".to_vec()]);
+        assert_eq!(alix.syncable_groups(&conn, &only_first).unwrap().len(), 1);
+        assert!(!only_first.is_everything());
+    }
+}
@@ -1,6 +1,5 @@
 use super::*;
 use crate::storage::group::GroupQueryArgs;
-use crate::storage::group_message::MsgQueryArgs;
 use crate::XmtpApi;
 use crate::{storage::group::StoredGroup, Client};
 use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
@@ -13,10 +12,12 @@ where
     pub(super) fn syncable_groups(
         &self,
         conn: &DbConnection,
+        scope: &HistorySyncScope,
     ) -> Result<Vec<Syncable>, DeviceSyncError> {
         let groups = conn
             .find_groups(GroupQueryArgs::default())?
             .into_iter()
+            .filter(|group| scope.includes_group(&group.id))
             .map(Syncable::Group)
             .collect();
 
@@ -26,12 +27,16 @@ where
     pub(super) fn syncable_messages(
         &self,
         conn: &DbConnection,
+        scope: &HistorySyncScope,
     ) -> Result<Vec<Syncable>, DeviceSyncError> {
         let groups = conn.find_groups(GroupQueryArgs::default())?;
 
         let mut all_messages = vec![];
         for StoredGroup { id, .. } in groups.into_iter() {
-            let messages = conn.get_group_messages(&id, &MsgQueryArgs::default())?;
+            if !scope.includes_group(&id) {
+                continue;
+            }
+            let messages = conn.get_group_messages(&id, &scope.message_query())?;
             for msg in messages {
                 all_messages.push(Syncable::GroupMessage(msg));
             }
@@ -82,9 +87,13 @@ pub(crate) mod tests {
         group.send_message(&[1, 2, 3]).await.unwrap();
 
         // Ensure that groups and messages now exists.
-        let syncable_groups = amal_a.syncable_groups(amal_a_conn).unwrap();
+        let syncable_groups = amal_a
+            .syncable_groups(amal_a_conn, &HistorySyncScope::default())
+            .unwrap();
         assert_eq!(syncable_groups.len(), 1);
-        let syncable_messages = amal_a.syncable_messages(amal_a_conn).unwrap();
+        let syncable_messages = amal_a
+            .syncable_messages(amal_a_conn, &HistorySyncScope::default())
+            .unwrap();
         assert_eq!(syncable_messages.len(), 2); // welcome message, and message that was just sent
 
         // Create a second installation for amal.
@@ -92,7 +101,9 @@ pub(crate) mod tests {
         let amal_b_provider = amal_b.mls_provider().unwrap();
         let amal_b_conn = amal_b_provider.conn_ref();
 
-        let groups_b = amal_b.syncable_groups(amal_b_conn).unwrap();
+        let groups_b = amal_b
+            .syncable_groups(amal_b_conn, &HistorySyncScope::default())
+            .unwrap();
         assert_eq!(groups_b.len(), 0);
 
         // make sure amal's worker has time to sync
@@ -134,10 +145,22 @@ pub(crate) mod tests {
 
         xmtp_common::wait_for_eq(
             || {
-                let groups_a = amal_a.syncable_groups(amal_a_conn).unwrap().len();
-                let groups_b = amal_b.syncable_groups(amal_b_conn).unwrap().len();
-                let messages_a = amal_a.syncable_messages(amal_a_conn).unwrap().len();
-                let messages_b = amal_b.syncable_messages(amal_b_conn).unwrap().len();
+                let groups_a = amal_a
+                    .syncable_groups(amal_a_conn, &HistorySyncScope::default())
+                    .unwrap()
+                    .len();
+                let groups_b = amal_b
+                    .syncable_groups(amal_b_conn, &HistorySyncScope::default())
+                    .unwrap()
+                    .len();
+                let messages_a = amal_a
+                    .syncable_messages(amal_a_conn, &HistorySyncScope::default())
+                    .unwrap()
+                    .len();
+                let messages_b = amal_b
+                    .syncable_messages(amal_b_conn, &HistorySyncScope::default())
+                    .unwrap()
+                    .len();
                 futures::future::ready(groups_a != groups_b || messages_a != messages_b)
             },
             true,
@@ -172,7 +195,9 @@ pub(crate) mod tests {
         let amal_b_provider = amal_b.mls_provider().unwrap();
         let amal_b_conn = amal_b_provider.conn_ref();
 
-        let groups_b = amal_b.syncable_groups(amal_b_conn).unwrap();
+        let groups_b = amal_b
+            .syncable_groups(amal_b_conn, &HistorySyncScope::default())
+            .unwrap();
         assert_eq!(groups_b.len(), 0);
 
         // make sure amal's worker has time to sync
@@ -220,7 +245,10 @@ pub(crate) mod tests {
             .expect("create group");
 
         let result = amal_a
-            .syncable_groups(&amal_a.store().conn().unwrap())
+            .syncable_groups(
+                &amal_a.store().conn().unwrap(),
+                &HistorySyncScope::default(),
+            )
             .unwrap();
         assert_eq!(result.len(), 2);
     }
@@ -0,0 +1,304 @@
+//! Exporting consent and preferences as a signed document, so a new installation of the same
+//! inbox can import them right away instead of waiting for device sync.
+//!
+//! The document is signed by the installation that exported it. On import the signature is
+//! checked, and the signing installation must belong to the importing inbox, so a document can't
+//! be forged or imported into another inbox. The document contains the HMAC key, so apps should
+//! store and transfer it like any other secret.
+use super::*;
+use crate::{
+    export_envelope::{open_export, seal_export, ExportEnvelopeError, ExportKind},
+    storage::user_preferences::StoredUserPreferences,
+    XmtpApi,
+};
+use sha2::{Digest, Sha256};
+use xmtp_id::associations::{verify_signed_with_public_context, SignatureError};
+
+/// The document version written by [`Client::export_preferences_signed`]
+pub const PREFERENCES_DOCUMENT_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum PreferenceExportError {
+    #[error("unsupported preferences document version {0}")]
+    UnsupportedVersion(u32),
+    #[error("preferences document belongs to inbox {0}")]
+    WrongInbox(String),
+    #[error("preferences document was signed by an installation that isn't part of the inbox")]
+    UnknownInstallation,
+    #[error("preferences document signature or signing key is malformed")]
+    Malformed,
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+    #[error(transparent)]
+    Envelope(#[from] ExportEnvelopeError),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+    #[error(transparent)]
+    Client(#[from] ClientError),
+}
+
+/// Consent records and preferences, signed by the installation that exported them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedPreferences {
+    pub version: u32,
+    pub inbox_id: String,
+    pub installation_id: Vec<u8>,
+    pub created_at_ns: i64,
+    /// JSON encoded list of [`UserPreferenceUpdate`]s
+    pub payload: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl SignedPreferences {
+    /// The text the installation signs, committing to every field through a hash of the payload
+    fn signature_text(&self) -> String {
+        format!(
+            "XMTP : Preferences\n\nVersion: {}\nInbox ID: {}\nInstallation ID: {}\nCreated at: {}\nPayload hash: {}",
+            self.version,
+            self.inbox_id,
+            hex::encode(&self.installation_id),
+            self.created_at_ns,
+            hex::encode(Sha256::digest(&self.payload)),
+        )
+    }
+
+    fn verify_signature(&self) -> Result<(), PreferenceExportError> {
+        let signature: [u8; 64] = self
+            .signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| PreferenceExportError::Malformed)?;
+        let public_key: [u8; 32] = self
+            .installation_id
+            .as_slice()
+            .try_into()
+            .map_err(|_| PreferenceExportError::Malformed)?;
+        verify_signed_with_public_context(self.signature_text(), &signature, &public_key)?;
+        Ok(())
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Export this inbox's consent records and preferences as a signed document
+    pub fn export_preferences_signed(&self) -> Result<Vec<u8>, PreferenceExportError> {
+        let conn = self.store().conn()?;
+        let mut updates: Vec<UserPreferenceUpdate> = conn
+            .consent_records()?
+            .into_iter()
+            .map(UserPreferenceUpdate::ConsentUpdate)
+            .collect();
+        if let Some(key) = StoredUserPreferences::load(&conn)?.hmac_key {
+            updates.push(UserPreferenceUpdate::HmacKeyUpdate { key });
+        }
+        updates.extend(
+            conn.all_notification_settings()?
+                .into_iter()
+                .map(UserPreferenceUpdate::NotificationSettingsUpdate),
+        );
+        updates.extend(
+            conn.all_custom_preferences()?
+                .into_iter()
+                .map(UserPreferenceUpdate::CustomUpdate),
+        );
+        updates.extend(
+            conn.all_blocked_inboxes()?
+                .into_iter()
+                .map(UserPreferenceUpdate::BlockUpdate),
+        );
+
+        let mut document = SignedPreferences {
+            version: PREFERENCES_DOCUMENT_VERSION,
+            inbox_id: self.inbox_id().to_string(),
+            installation_id: self.installation_public_key().into(),
+            created_at_ns: now_ns(),
+            payload: serde_json::to_vec(&updates)?,
+            signature: vec![],
+        };
+        document.signature = self
+            .sign_with_public_context(document.signature_text())
+            .map_err(ClientError::from)?;
+        Ok(serde_json::to_vec(&document)?)
+    }
+
+    /// [`Client::export_preferences_signed`], encrypted with `passphrase` so the document can be
+    /// stored or sent somewhere untrusted
+    pub fn export_preferences_encrypted(
+        &self,
+        passphrase: &str,
+    ) -> Result<Vec<u8>, PreferenceExportError> {
+        let document = self.export_preferences_signed()?;
+        Ok(seal_export(ExportKind::Preferences, passphrase, &document)?)
+    }
+
+    /// Decrypt and import a document from [`Client::export_preferences_encrypted`]
+    pub async fn import_preferences_encrypted(
+        &self,
+        envelope: &[u8],
+        passphrase: &str,
+    ) -> Result<Vec<UserPreferenceUpdate>, PreferenceExportError> {
+        let (_, document) = open_export(ExportKind::Preferences, passphrase, envelope)?;
+        self.import_preferences_signed(&document).await
+    }
+
+    /// Verify and apply a document from [`Client::export_preferences_signed`]. Consent that is
+    /// already stored locally, e.g. from device sync, is newer and is kept.
+    /// Returns the updates that were applied.
+    pub async fn import_preferences_signed(
+        &self,
+        document: &[u8],
+    ) -> Result<Vec<UserPreferenceUpdate>, PreferenceExportError> {
+        let document: SignedPreferences = serde_json::from_slice(document)?;
+        if document.version != PREFERENCES_DOCUMENT_VERSION {
+            return Err(PreferenceExportError::UnsupportedVersion(document.version));
+        }
+        if document.inbox_id != self.inbox_id() {
+            return Err(PreferenceExportError::WrongInbox(document.inbox_id));
+        }
+        document.verify_signature()?;
+        let conn = self.store().conn()?;
+        let association_state = self
+            .get_latest_association_state(&conn, self.inbox_id())
+            .await?;
+        if !association_state
+            .installation_ids()
+            .contains(&document.installation_id)
+        {
+            return Err(PreferenceExportError::UnknownInstallation);
+        }
+
+        let updates: Vec<UserPreferenceUpdate> = serde_json::from_slice(&document.payload)?;
+        let applied = UserPreferenceUpdate::apply_imported(&conn, updates)?;
+
+        tracing::info!(
+            inbox_id = self.inbox_id(),
+            "imported {} preference updates",
+            applied.len()
+        );
+        if !applied.is_empty() {
+            self.publish_local_event(LocalEvents::IncomingPreferenceUpdate(applied.clone()))
+                .await;
+        }
+        Ok(applied)
+    }
+}
+
+impl UserPreferenceUpdate {
+    /// Apply updates imported from another installation. Local state that is newer, like consent
+    /// already received through device sync, is kept. Returns the updates that were applied.
+    pub(crate) fn apply_imported(
+        conn: &DbConnection,
+        updates: Vec<Self>,
+    ) -> Result<Vec<Self>, StorageError> {
+        let mut applied = vec![];
+        for update in updates {
+            match &update {
+                UserPreferenceUpdate::ConsentUpdate(record) => {
+                    if conn
+                        .maybe_insert_consent_record_return_existing(record)?
+                        .is_some()
+                    {
+                        continue;
+                    }
+                }
+                UserPreferenceUpdate::HmacKeyUpdate { key } => {
+                    let preferences = StoredUserPreferences::load(conn)?;
+                    if preferences.hmac_key.is_some() {
+                        continue;
+                    }
+                    StoredUserPreferences {
+                        hmac_key: Some(key.clone()),
+                        ..preferences
+                    }
+                    .store(conn)?;
+                }
+                UserPreferenceUpdate::NotificationSettingsUpdate(settings) => {
+                    if !conn.set_notification_settings(settings)? {
+                        continue;
+                    }
+                }
+                UserPreferenceUpdate::DraftUpdate(draft) => {
+                    if !conn.set_draft(draft)? {
+                        continue;
+                    }
+                }
+                UserPreferenceUpdate::CustomUpdate(preference) => {
+                    if !conn.set_custom_preference(preference)? {
+                        continue;
+                    }
+                }
+                UserPreferenceUpdate::BlockUpdate(entry) => {
+                    if !conn.set_blocked_inbox(entry)? {
+                        continue;
+                    }
+                }
+                UserPreferenceUpdate::InviteLinkUpdate(link) => {
+                    if !conn.merge_invite_link(link)? {
+                        continue;
+                    }
+                }
+            }
+            applied.push(update);
+        }
+        Ok(applied)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        storage::consent_record::{ConsentState, ConsentType},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_preferences_round_trip() {
+        let wallet = generate_local_wallet();
+        let amal_a = ClientBuilder::new_test_client(&wallet).await;
+        let record = StoredConsentRecord::new(
+            ConsentType::InboxId,
+            ConsentState::Denied,
+            "spammer".to_string(),
+        );
+        amal_a.set_consent_states(&[record.clone()]).await.unwrap();
+        let document = amal_a.export_preferences_signed().unwrap();
+
+        let amal_b = ClientBuilder::new_test_client(&wallet).await;
+        let applied = amal_b.import_preferences_signed(&document).await.unwrap();
+        assert!(applied
+            .iter()
+            .any(|u| matches!(u, UserPreferenceUpdate::ConsentUpdate(r) if *r == record)));
+        let conn = amal_b.store().conn().unwrap();
+        assert_eq!(
+            conn.get_consent_record("spammer".to_string(), ConsentType::InboxId)
+                .unwrap(),
+            Some(record)
+        );
+
+        // another inbox can't import it, and tampering breaks the signature
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        assert!(matches!(
+            bola.import_preferences_signed(&document).await,
+            Err(PreferenceExportError::WrongInbox(_))
+        ));
+        let mut tampered: SignedPreferences = serde_json::from_slice(&document).unwrap();
+        tampered.payload = serde_json::to_vec(&Vec::<UserPreferenceUpdate>::new()).unwrap();
+        assert!(matches!(
+            amal_b
+                .import_preferences_signed(&serde_json::to_vec(&tampered).unwrap())
+                .await,
+            Err(PreferenceExportError::Signature(_))
+        ));
+    }
+}
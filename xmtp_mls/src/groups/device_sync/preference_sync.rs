@@ -1,6 +1,15 @@
 use super::*;
 use crate::{
-    storage::{consent_record::StoredConsentRecord, user_preferences::StoredUserPreferences},
+    hlc::HybridClock,
+    storage::{
+        blocked_inbox::StoredBlockedInbox,
+        consent_record::{ConsentMerge, ConsentState, ConsentType, StoredConsentRecord},
+        custom_preference::StoredCustomPreference,
+        draft::StoredDraft,
+        invite_link::StoredInviteLink,
+        notification_settings::StoredNotificationSettings,
+        user_preferences::StoredUserPreferences,
+    },
     Client,
 };
 use serde::{Deserialize, Serialize};
@@ -13,7 +22,79 @@ use xmtp_proto::{
 #[repr(i32)]
 pub enum UserPreferenceUpdate {
     ConsentUpdate(StoredConsentRecord) = 1,
-    HmacKeyUpdate { key: Vec<u8> } = 2,
+    HmacKeyUpdate {
+        key: Vec<u8>,
+    } = 2,
+    NotificationSettingsUpdate(StoredNotificationSettings) = 3,
+    DraftUpdate(StoredDraft) = 4,
+    /// A preference in a namespace the app registered
+    CustomUpdate(StoredCustomPreference) = 5,
+    /// An inbox blocked or unblocked on another installation
+    BlockUpdate(StoredBlockedInbox) = 6,
+    /// An invite link created, used or revoked on another installation
+    InviteLinkUpdate(StoredInviteLink) = 7,
+}
+
+/// Consent updates from versions that don't stamp records with a
+/// [hybrid logical clock](crate::hlc) timestamp
+#[derive(Deserialize)]
+enum LegacyUserPreferenceUpdate {
+    ConsentUpdate(LegacyConsentRecord),
+}
+
+#[derive(Deserialize)]
+struct LegacyConsentRecord {
+    entity_type: ConsentType,
+    state: ConsentState,
+    entity: String,
+}
+
+impl From<LegacyUserPreferenceUpdate> for UserPreferenceUpdate {
+    fn from(update: LegacyUserPreferenceUpdate) -> Self {
+        let LegacyUserPreferenceUpdate::ConsentUpdate(record) = update;
+        Self::ConsentUpdate(StoredConsentRecord::new(
+            record.entity_type,
+            record.state,
+            record.entity,
+        ))
+    }
+}
+
+/// Which of two concurrent writes of a consent record won
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictWinner {
+    Incoming,
+    Local,
+}
+
+/// A consent record that another installation changed to a different state than the one stored
+/// here, and how that was resolved. The write with the later timestamp wins on every
+/// installation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsentConflict {
+    /// The record received from the other installation
+    pub incoming: StoredConsentRecord,
+    /// The record stored here when it arrived
+    pub local: StoredConsentRecord,
+    pub winner: ConflictWinner,
+}
+
+impl ConsentConflict {
+    fn from_merge(merge: ConsentMerge) -> Option<Self> {
+        let (incoming, local, winner) = match merge {
+            ConsentMerge::Applied {
+                record,
+                replaced: Some(replaced),
+            } => (record, replaced, ConflictWinner::Incoming),
+            ConsentMerge::Ignored { record, kept } => (record, kept, ConflictWinner::Local),
+            ConsentMerge::Applied { replaced: None, .. } => return None,
+        };
+        (incoming.state != local.state).then_some(Self {
+            incoming,
+            local,
+            winner,
+        })
+    }
 }
 
 impl UserPreferenceUpdate {
@@ -43,11 +124,13 @@ impl UserPreferenceUpdate {
         Ok(())
     }
 
-    /// Process and insert incoming preference updates over the sync group
+    /// Process and insert incoming preference updates over the sync group.
+    /// Returns the updates that were applied, and the consent conflicts that were resolved.
     pub(crate) fn process_incoming_preference_update(
         update_proto: UserPreferenceUpdateProto,
         provider: &XmtpOpenMlsProvider,
-    ) -> Result<Vec<Self>, StorageError> {
+        hlc: &HybridClock,
+    ) -> Result<(Vec<Self>, Vec<ConsentConflict>), StorageError> {
         let conn = provider.conn_ref();
 
         let proto_content = update_proto.contents;
@@ -56,10 +139,24 @@ impl UserPreferenceUpdate {
         let mut consent_updates = vec![];
 
         for update in proto_content {
-            if let Ok(update) = bincode::deserialize::<UserPreferenceUpdate>(&update) {
-                updates.push(update.clone());
+            let update = bincode::deserialize::<UserPreferenceUpdate>(&update).or_else(|_| {
+                bincode::deserialize::<LegacyUserPreferenceUpdate>(&update).map(Into::into)
+            });
+            if let Ok(update) = update {
+                // consent records, custom preferences, block list entries and invite links are
+                // reported once merged, if they change
+                if !matches!(
+                    update,
+                    UserPreferenceUpdate::ConsentUpdate(_)
+                        | UserPreferenceUpdate::CustomUpdate(_)
+                        | UserPreferenceUpdate::BlockUpdate(_)
+                        | UserPreferenceUpdate::InviteLinkUpdate(_)
+                ) {
+                    updates.push(update.clone());
+                }
                 match update {
                     UserPreferenceUpdate::ConsentUpdate(consent_record) => {
+                        hlc.observe(&consent_record.hlc());
                         consent_updates.push(consent_record);
                     }
                     UserPreferenceUpdate::HmacKeyUpdate { key } => {
@@ -69,6 +166,31 @@ impl UserPreferenceUpdate {
                         }
                         .store(conn)?;
                     }
+                    UserPreferenceUpdate::NotificationSettingsUpdate(settings) => {
+                        hlc.observe(&settings.hlc());
+                        conn.set_notification_settings(&settings)?;
+                    }
+                    UserPreferenceUpdate::DraftUpdate(draft) => {
+                        hlc.observe(&draft.hlc());
+                        conn.set_draft(&draft)?;
+                    }
+                    UserPreferenceUpdate::CustomUpdate(preference) => {
+                        hlc.observe(&preference.hlc());
+                        if conn.set_custom_preference(&preference)? {
+                            updates.push(UserPreferenceUpdate::CustomUpdate(preference));
+                        }
+                    }
+                    UserPreferenceUpdate::BlockUpdate(entry) => {
+                        hlc.observe(&entry.hlc());
+                        if conn.set_blocked_inbox(&entry)? {
+                            updates.push(UserPreferenceUpdate::BlockUpdate(entry));
+                        }
+                    }
+                    UserPreferenceUpdate::InviteLinkUpdate(link) => {
+                        if conn.merge_invite_link(&link)? {
+                            updates.push(UserPreferenceUpdate::InviteLinkUpdate(link));
+                        }
+                    }
                 }
             } else {
                 // Don't fail on errors since this may come from a newer version of the lib
@@ -79,12 +201,18 @@ impl UserPreferenceUpdate {
             }
         }
 
-        // Insert all of the consent records at once.
+        // Merge all of the consent records at once.
+        let mut conflicts = vec![];
         if !consent_updates.is_empty() {
-            conn.insert_or_replace_consent_records(&consent_updates)?;
+            for merge in conn.merge_consent_records(&consent_updates)? {
+                if let ConsentMerge::Applied { ref record, .. } = merge {
+                    updates.push(UserPreferenceUpdate::ConsentUpdate(record.clone()));
+                }
+                conflicts.extend(ConsentConflict::from_merge(merge));
+            }
         }
 
-        Ok(updates)
+        Ok((updates, conflicts))
     }
 }
 
@@ -108,11 +236,11 @@ mod tests {
     #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
     #[cfg_attr(target_family = "wasm", ignore)]
     async fn test_can_deserialize_between_versions() {
-        let consent_record = StoredConsentRecord {
-            entity: "hello there".to_string(),
-            entity_type: ConsentType::Address,
-            state: ConsentState::Allowed,
-        };
+        let consent_record = StoredConsentRecord::new(
+            ConsentType::Address,
+            ConsentState::Allowed,
+            "hello there".to_string(),
+        );
         let update = UserPreferenceUpdate::ConsentUpdate(consent_record);
 
         let bytes = bincode::serialize(&update).unwrap();
@@ -168,4 +296,41 @@ mod tests {
         let new_pref_a = StoredUserPreferences::load(amal_a_conn).unwrap();
         assert_ne!(pref_a.hmac_key, new_pref_a.hmac_key);
     }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_stale_custom_preferences_are_not_reported() {
+        let client = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let provider = client.mls_provider().unwrap();
+        let update = |wall_ns| {
+            let preference = StoredCustomPreference::new(
+                "com.example.theme".to_string(),
+                Some(b"dark".to_vec()),
+                crate::hlc::Hlc {
+                    wall_ns,
+                    counter: 0,
+                    node: vec![1],
+                },
+            );
+            UserPreferenceUpdateProto {
+                contents: vec![
+                    bincode::serialize(&UserPreferenceUpdate::CustomUpdate(preference)).unwrap(),
+                ],
+            }
+        };
+
+        let (applied, _) = UserPreferenceUpdate::process_incoming_preference_update(
+            update(10),
+            &provider,
+            &client.context.hlc,
+        )
+        .unwrap();
+        assert_eq!(applied.len(), 1);
+        let (applied, _) = UserPreferenceUpdate::process_incoming_preference_update(
+            update(5),
+            &provider,
+            &client.context.hlc,
+        )
+        .unwrap();
+        assert!(applied.is_empty());
+    }
 }
@@ -0,0 +1,272 @@
+//! Progress reporting and controls for device sync. Each step of replying to a sync request or
+//! importing a reply is emitted as a [`LocalEvents::SyncProgress`] event, and the sync in
+//! progress can be paused, resumed or cancelled from the [`Client`].
+//!
+//! The sync worker checks the controls at each checkpoint, between items or steps; a network
+//! call that already started runs to completion. A sync paused at a checkpoint is set aside
+//! there, so the worker goes on handling other events, and runs again once resumed: an import
+//! skips the items it already stored. Imports are idempotent, so a cancelled import leaves what
+//! it imported so far in place and a later reply fills in the rest.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use parking_lot::Mutex;
+use xmtp_proto::xmtp::mls::message_contents::DeviceSyncKind;
+
+use super::DeviceSyncError;
+use crate::{
+    subscriptions::{LocalEvents, SyncMessage},
+    Client,
+};
+
+/// Progress is reported every this many items while importing a reply
+pub const SYNC_PROGRESS_INTERVAL: u64 = 100;
+
+/// The step a device sync is at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncPhase {
+    /// A sync request was sent to the other installations
+    Requesting,
+    /// Collecting the records to reply to a request with
+    Exporting,
+    /// Uploading the encrypted reply payload
+    Uploading,
+    /// Downloading the payload of a reply
+    Downloading,
+    /// Storing the records of a reply
+    Importing,
+    Done,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncProgress {
+    pub kind: DeviceSyncKind,
+    pub phase: SyncPhase,
+    pub items_total: u64,
+    pub items_done: u64,
+}
+
+impl SyncProgress {
+    pub(crate) fn new(kind: DeviceSyncKind, phase: SyncPhase) -> Self {
+        Self {
+            kind,
+            phase,
+            items_total: 0,
+            items_done: 0,
+        }
+    }
+}
+
+/// Pause and cancel state of device sync, checked by the sync worker at each checkpoint
+#[derive(Default)]
+pub struct SyncControl {
+    paused: AtomicBool,
+    cancelled: AtomicBool,
+    /// The sync the worker is running, with the progress it made before it was paused, if it
+    /// was
+    current: Mutex<Option<(SyncMessage, Option<SyncProgress>)>>,
+    /// The syncs set aside at a checkpoint while paused, with the progress they made
+    parked: Mutex<Vec<(SyncMessage, SyncProgress)>>,
+}
+
+impl SyncControl {
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Stop pausing syncs. Returns the syncs that were set aside, for the worker to run again.
+    pub(crate) fn resume(&self) -> Vec<SyncMessage> {
+        self.paused.store(false, Ordering::SeqCst);
+        self.parked
+            .lock()
+            .iter()
+            .map(|(message, _)| message.clone())
+            .collect()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Cancel the sync in progress and the ones set aside while paused. The next sync runs as
+    /// usual. Returns the progress of the syncs that were set aside.
+    pub(crate) fn cancel(&self) -> Vec<SyncProgress> {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.parked
+            .lock()
+            .drain(..)
+            .map(|(_, progress)| progress)
+            .collect()
+    }
+
+    /// Start running the sync of `message`, forgetting cancellations of earlier ones. A sync
+    /// set aside while paused picks up its progress.
+    pub(crate) fn begin(&self, message: &SyncMessage) {
+        self.cancelled.store(false, Ordering::SeqCst);
+        let mut parked = self.parked.lock();
+        let resumed = parked
+            .iter()
+            .position(|(parked, _)| parked == message)
+            .map(|i| parked.remove(i).1);
+        *self.current.lock() = Some((message.clone(), resumed));
+    }
+
+    /// The sync being run is done, or was set aside
+    pub(crate) fn finish(&self) {
+        *self.current.lock() = None;
+    }
+
+    /// How many items the sync being run already handled in `phase` before it was paused
+    pub(crate) fn resumed_items(&self, phase: SyncPhase) -> u64 {
+        match &*self.current.lock() {
+            Some((_, Some(progress))) if progress.phase == phase => progress.items_done,
+            _ => 0,
+        }
+    }
+
+    /// Fails if the sync was cancelled, or is paused, in which case the sync being run is set
+    /// aside at `progress`
+    pub(crate) fn checkpoint(&self, progress: SyncProgress) -> Result<(), DeviceSyncError> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Err(DeviceSyncError::Cancelled);
+        }
+        if !self.is_paused() {
+            return Ok(());
+        }
+        if let Some((message, _)) = self.current.lock().take() {
+            self.parked.lock().push((message, progress));
+        }
+        Err(DeviceSyncError::Paused)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V> {
+    /// Set device sync aside at its next checkpoint until [`Self::resume_device_sync`] is
+    /// called
+    pub fn pause_device_sync(&self) {
+        self.context.sync_control.pause();
+    }
+
+    /// Run the syncs set aside while paused again, from where they stopped
+    pub async fn resume_device_sync(&self) {
+        for message in self.context.sync_control.resume() {
+            self.publish_local_event(LocalEvents::SyncMessage(message))
+                .await;
+        }
+    }
+
+    pub fn is_device_sync_paused(&self) -> bool {
+        self.context.sync_control.is_paused()
+    }
+
+    /// Abandon the device sync in progress, and the ones set aside while paused. The sync
+    /// worker keeps handling later requests and replies.
+    pub async fn cancel_device_sync(&self) {
+        for progress in self.context.sync_control.cancel() {
+            self.report_sync_progress(SyncProgress {
+                phase: SyncPhase::Cancelled,
+                ..progress
+            })
+            .await;
+        }
+    }
+
+    pub(crate) async fn report_sync_progress(&self, progress: SyncProgress) {
+        tracing::debug!(
+            kind = ?progress.kind,
+            phase = ?progress.phase,
+            items_done = progress.items_done,
+            items_total = progress.items_total,
+            "device sync progress"
+        );
+        self.publish_local_event(LocalEvents::SyncProgress(progress))
+            .await;
+    }
+
+    /// Report `progress`, then fail if sync was paused or cancelled. A cancelled sync reports
+    /// that as well.
+    pub(crate) async fn sync_checkpoint(
+        &self,
+        progress: SyncProgress,
+    ) -> Result<(), DeviceSyncError> {
+        self.report_sync_progress(progress).await;
+        let result = self.context.sync_control.checkpoint(progress);
+        if matches!(result, Err(DeviceSyncError::Cancelled)) {
+            self.report_sync_progress(SyncProgress {
+                phase: SyncPhase::Cancelled,
+                ..progress
+            })
+            .await;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, subscriptions::StreamMessages};
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_paused_sync_is_set_aside_and_can_be_cancelled() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let control = &alix.context.sync_control;
+        let progress = alix.local_events().subscribe().stream_sync_progress();
+        futures::pin_mut!(progress);
+        let sync_events = alix.local_events().subscribe().stream_sync_messages();
+        futures::pin_mut!(sync_events);
+        let reply = SyncMessage::Reply {
+            message_id: vec![1],
+        };
+        let importing = SyncProgress {
+            items_total: 250,
+            items_done: 100,
+            ..SyncProgress::new(DeviceSyncKind::MessageHistory, SyncPhase::Importing)
+        };
+
+        // a paused sync doesn't hold up the worker, it's set aside at the checkpoint
+        alix.pause_device_sync();
+        assert!(alix.is_device_sync_paused());
+        control.begin(&reply);
+        assert!(matches!(
+            alix.sync_checkpoint(importing).await,
+            Err(DeviceSyncError::Paused)
+        ));
+        assert_eq!(progress.next().await.unwrap().unwrap(), importing);
+
+        // resuming runs it again, and it skips what it already imported
+        alix.resume_device_sync().await;
+        let Some(Ok(LocalEvents::SyncMessage(resumed))) = sync_events.next().await else {
+            panic!("the paused sync wasn't run again");
+        };
+        assert_eq!(resumed, reply);
+        control.begin(&resumed);
+        assert_eq!(control.resumed_items(SyncPhase::Importing), 100);
+        assert_eq!(control.resumed_items(SyncPhase::Exporting), 0);
+        alix.sync_checkpoint(importing).await.unwrap();
+        assert_eq!(progress.next().await.unwrap().unwrap(), importing);
+        control.finish();
+
+        // cancelling drops the syncs set aside
+        alix.pause_device_sync();
+        control.begin(&reply);
+        assert!(alix.sync_checkpoint(importing).await.is_err());
+        assert_eq!(progress.next().await.unwrap().unwrap(), importing);
+        alix.cancel_device_sync().await;
+        assert_eq!(
+            progress.next().await.unwrap().unwrap().phase,
+            SyncPhase::Cancelled
+        );
+        assert!(alix.context.sync_control.resume().is_empty());
+
+        // a cancellation only applies to the sync it was made during
+        control.begin(&reply);
+        alix.sync_checkpoint(importing).await.unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+//! Disappearing messages. A group's retention settings live in its mutable metadata, so every
+//! member applies the same ones: application messages sent after the settings took effect are
+//! deleted once they are older than the configured lifetime.
+//!
+//! Expired messages are purged by [`Client::delete_expired_messages`], which apps run
+//! periodically or through [`Client::start_disappearing_messages_worker`]. Messages that have
+//! already expired when they are received are not stored at all.
+use std::{sync::Arc, time::Duration};
+
+use openmls::group::MlsGroup as OpenMlsGroup;
+use xmtp_common::time::now_ns;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use super::{
+    group_mutable_metadata::{GroupMutableMetadata, MetadataField},
+    intents::UpdateMetadataIntentData,
+    GroupError, MlsGroup, ScopedGroupClient,
+};
+use crate::{
+    client::ClientError,
+    configuration::NS_IN_MS,
+    storage::{group::GroupQueryArgs, group_intent::IntentKind, group_message::StoredGroupMessage},
+    CancellationToken, Client, StreamMetrics, XmtpApi, XmtpOpenMlsProvider,
+};
+
+/// How long messages in a group last
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageDisappearingSettings {
+    /// Messages sent at or after this time disappear. Earlier messages are kept.
+    pub from_ns: i64,
+    /// How long after being sent a message disappears
+    pub in_ns: i64,
+}
+
+impl MessageDisappearingSettings {
+    /// The settings in `metadata`, or `None` if messages in the group don't disappear.
+    /// Metadata stores both values in milliseconds.
+    pub fn from_metadata(metadata: &GroupMutableMetadata) -> Option<Self> {
+        let field = |field: MetadataField| -> Option<i64> {
+            metadata.attributes.get(field.as_str())?.parse().ok()
+        };
+        let in_ms = field(MetadataField::MessageExpirationMillis).filter(|ms| *ms > 0)?;
+        let from_ms = field(MetadataField::MessageExpirationFromMillis).unwrap_or_default();
+        Some(Self {
+            from_ns: from_ms.saturating_mul(NS_IN_MS),
+            in_ns: in_ms.saturating_mul(NS_IN_MS),
+        })
+    }
+
+    /// Whether a message sent at `sent_at_ns` has disappeared by `now_ns`
+    pub fn is_expired(&self, sent_at_ns: i64, now_ns: i64) -> bool {
+        sent_at_ns >= self.from_ns && sent_at_ns <= now_ns.saturating_sub(self.in_ns)
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Make messages sent from `settings.from_ns` on disappear `settings.in_ns` after they
+    /// were sent. Requires permission to update the message expiration metadata.
+    pub async fn update_message_disappearing_settings(
+        &self,
+        settings: MessageDisappearingSettings,
+    ) -> Result<(), GroupError> {
+        self.update_disappearing_field(
+            MetadataField::MessageExpirationFromMillis,
+            settings.from_ns / NS_IN_MS,
+        )
+        .await?;
+        self.update_disappearing_field(
+            MetadataField::MessageExpirationMillis,
+            settings.in_ns / NS_IN_MS,
+        )
+        .await
+    }
+
+    /// Stop messages in this group from disappearing. Messages that already disappeared
+    /// are not restored.
+    pub async fn remove_message_disappearing_settings(&self) -> Result<(), GroupError> {
+        self.update_disappearing_field(MetadataField::MessageExpirationMillis, 0)
+            .await
+    }
+
+    async fn update_disappearing_field(
+        &self,
+        field: MetadataField,
+        value_ms: i64,
+    ) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        let intent_data: Vec<u8> =
+            UpdateMetadataIntentData::new(field.to_string(), value_ms.to_string()).into();
+        let intent = self.queue_intent(&provider, IntentKind::MetadataUpdate, intent_data)?;
+
+        self.sync_until_intent_resolved(&provider, intent.id).await
+    }
+
+    /// The group's disappearing message settings as of its last sync
+    pub fn message_disappearing_settings(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<Option<MessageDisappearingSettings>, GroupError> {
+        Ok(MessageDisappearingSettings::from_metadata(
+            &self.mutable_metadata(provider)?,
+        ))
+    }
+
+    /// Delete this group's messages that have disappeared. Returns the number deleted.
+    pub fn delete_expired_messages(&self) -> Result<usize, GroupError> {
+        let provider = self.mls_provider()?;
+        let Some(settings) = self.message_disappearing_settings(&provider)? else {
+            return Ok(0);
+        };
+        let sent_before_ns = now_ns().saturating_sub(settings.in_ns).saturating_add(1);
+        let deleted = provider.conn_ref().delete_expired_messages(
+            &self.group_id,
+            settings.from_ns,
+            sent_before_ns,
+        )?;
+        if deleted > 0 {
+            self.recount_unread(provider.conn_ref());
+        }
+        Ok(deleted)
+    }
+
+    /// Whether `message` already disappeared under the settings of `mls_group` when received
+    pub(super) fn has_disappeared(
+        &self,
+        mls_group: &OpenMlsGroup,
+        message: &StoredGroupMessage,
+    ) -> bool {
+        let settings = GroupMutableMetadata::try_from(mls_group)
+            .ok()
+            .as_ref()
+            .and_then(MessageDisappearingSettings::from_metadata);
+        let expired = settings.is_some_and(|s| s.is_expired(message.sent_at_ns, now_ns()));
+        if expired {
+            tracing::debug!(
+                group_id = hex::encode(&self.group_id),
+                message_id = hex::encode(&message.id),
+                "not storing message that already disappeared"
+            );
+        }
+        expired
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Delete the messages that have disappeared from every group.
+    /// Returns the number of messages deleted.
+    pub fn delete_expired_messages(&self) -> Result<usize, ClientError> {
+        let mut deleted = 0;
+        for group in self.find_groups(GroupQueryArgs::default())? {
+            match group.delete_expired_messages() {
+                Ok(count) => deleted += count,
+                Err(e) => tracing::warn!(
+                    group_id = hex::encode(&group.group_id),
+                    "failed to delete disappeared messages: {e}"
+                ),
+            }
+        }
+        if deleted > 0 {
+            tracing::info!(deleted, "deleted disappeared messages");
+        }
+        Ok(deleted)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Run [`Client::delete_expired_messages`] every `interval` until the handle is closed
+    pub fn start_disappearing_messages_worker(
+        client: Arc<Client<ApiClient, V>>,
+        interval: Duration,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
+        let cancel = CancellationToken::new();
+        let stopped = cancel.clone();
+
+        crate::spawn_cancellable(None, StreamMetrics::default(), cancel, async move {
+            while !stopped.is_cancelled() {
+                if let Err(e) = client.delete_expired_messages() {
+                    tracing::warn!("disappearing messages run failed: {e}");
+                }
+                let _ = xmtp_common::time::timeout(interval, stopped.cancelled()).await;
+            }
+            Ok::<_, ClientError>(())
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_messages_disappear() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let provider = alix.mls_provider().unwrap();
+        let kept = group.send_message(b"before").await.unwrap();
+        assert_eq!(
+            group.message_disappearing_settings(&provider).unwrap(),
+            None
+        );
+
+        let settings = MessageDisappearingSettings {
+            from_ns: now_ns() / NS_IN_MS * NS_IN_MS,
+            in_ns: NS_IN_MS,
+        };
+        group
+            .update_message_disappearing_settings(settings)
+            .await
+            .unwrap();
+        assert_eq!(
+            group.message_disappearing_settings(&provider).unwrap(),
+            Some(settings)
+        );
+        let disappearing = group.send_message(b"after").await.unwrap();
+        xmtp_common::time::sleep(Duration::from_millis(10)).await;
+
+        assert!(alix.delete_expired_messages().unwrap() >= 1);
+        let conn = provider.conn_ref();
+        assert!(conn.get_group_message(&kept).unwrap().is_some());
+        assert!(conn.get_group_message(&disappearing).unwrap().is_none());
+
+        group.remove_message_disappearing_settings().await.unwrap();
+        assert_eq!(
+            group.message_disappearing_settings(&provider).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! Per-conversation drafts of the message the user is writing. They're local to the user, not
+//! shared with the group, and synced to the user's other installations through the sync group
+//! when history sync is enabled, so an unfinished message started on one device can be finished
+//! on another.
+//!
+//! Drafts change with every keystroke, so a draft is only synced once it stopped changing for
+//! [`DRAFT_SYNC_DEBOUNCE`]. Concurrent edits on different installations are ordered by their
+//! [hybrid logical clock](crate::hlc) timestamp, so every installation keeps the same draft.
+use xmtp_common::time::{sleep, Duration};
+
+use super::{
+    device_sync::preference_sync::UserPreferenceUpdate, GroupError, MlsGroup, ScopedGroupClient,
+};
+use crate::{hlc::Hlc, storage::draft::StoredDraft, subscriptions::LocalEvents};
+
+/// How long a draft has to stay unchanged before it's synced to the user's other installations
+pub const DRAFT_SYNC_DEBOUNCE: Duration = Duration::from_secs(1);
+
+impl<ScopedClient> MlsGroup<ScopedClient>
+where
+    ScopedClient: ScopedGroupClient + 'static,
+{
+    /// Save the unfinished message in the conversation, replacing the previous draft
+    pub fn save_draft(&self, content: &[u8]) -> Result<(), GroupError> {
+        self.set_draft(Some(content.to_vec()))
+    }
+
+    /// The unfinished message in the conversation, if there is one
+    pub fn load_draft(&self) -> Result<Option<Vec<u8>>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn
+            .get_draft(&self.group_id)?
+            .and_then(|draft| draft.content))
+    }
+
+    /// Discard the draft, e.g. once the message was sent
+    pub fn clear_draft(&self) -> Result<(), GroupError> {
+        self.set_draft(None)
+    }
+
+    fn set_draft(&self, content: Option<Vec<u8>>) -> Result<(), GroupError> {
+        let conn = self.context().store().conn()?;
+        let draft = StoredDraft::new(self.group_id.clone(), content, self.context().hlc.now());
+        conn.set_draft(&draft)?;
+
+        if self.client.history_sync_url().is_some() {
+            self.sync_draft_once_unchanged(draft.hlc());
+        }
+        Ok(())
+    }
+
+    /// Sync the draft written at `hlc` across devices after [`DRAFT_SYNC_DEBOUNCE`], unless it
+    /// changed again by then, in which case the later write is synced instead
+    fn sync_draft_once_unchanged(&self, hlc: Hlc) {
+        let group = self.clone();
+        crate::spawn(None, async move {
+            sleep(DRAFT_SYNC_DEBOUNCE).await;
+            let draft = group
+                .context()
+                .store()
+                .conn()
+                .and_then(|conn| conn.get_draft(&group.group_id));
+            match draft {
+                Ok(Some(draft)) if draft.hlc() == hlc => {
+                    group
+                        .publish_local_event(LocalEvents::OutgoingPreferenceUpdates(vec![
+                            UserPreferenceUpdate::DraftUpdate(draft),
+                        ]))
+                        .await;
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!(
+                    group_id = hex::encode(&group.group_id),
+                    "failed to sync the draft: {e}"
+                ),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions, utils::test::HISTORY_SYNC_URL,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_save_load_and_clear_draft() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let other = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        assert_eq!(group.load_draft().unwrap(), None);
+
+        group.save_draft(b"see you at").unwrap();
+        group.save_draft(b"see you at noon").unwrap();
+        assert_eq!(
+            group.load_draft().unwrap(),
+            Some(b"see you at noon".to_vec())
+        );
+        assert_eq!(other.load_draft().unwrap(), None);
+
+        group.clear_draft().unwrap();
+        assert_eq!(group.load_draft().unwrap(), None);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_only_the_settled_draft_is_synced() {
+        let alix =
+            ClientBuilder::new_test_client_with_history(&generate_local_wallet(), HISTORY_SYNC_URL)
+                .await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let mut events = alix.local_events().subscribe();
+
+        for draft in ["s", "se", "see you"] {
+            group.save_draft(draft.as_bytes()).unwrap();
+        }
+        sleep(DRAFT_SYNC_DEBOUNCE * 2).await;
+
+        let mut synced = vec![];
+        while let Ok(event) = events.try_recv() {
+            if let LocalEvents::OutgoingPreferenceUpdates(updates) = event {
+                synced.extend(updates.into_iter().filter_map(|update| match update {
+                    UserPreferenceUpdate::DraftUpdate(draft) => draft.content,
+                    _ => None,
+                }));
+            }
+        }
+        assert_eq!(synced, vec![b"see you".to_vec()]);
+    }
+}
@@ -0,0 +1,315 @@
+//! Starting a side conversation from a group: a new group with the same metadata and permissions
+//! and a subset of the members, optionally seeded with the latest messages of the group. The
+//! messages are forwarded, so they're rendered as quotes of the original conversation. Only
+//! messages that every member of the new group could already read in the group are forwarded,
+//! so nobody sees history from before they joined. Remote attachments are left out, since
+//! forwarding them needs an upload.
+use prost::Message;
+use thiserror::Error;
+use xmtp_common::RetryableError;
+use xmtp_content_types::{group_updated::GroupUpdatedCodec, CodecError, ContentCodec};
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use super::{
+    forward::is_forwardable, group_mutable_metadata::MetadataField, GroupError,
+    GroupMetadataOptions, MlsGroup,
+};
+use crate::{
+    configuration::DEFAULT_MESSAGE_PAGE_SIZE,
+    storage::{
+        group::ConversationType,
+        group_message::{
+            ContentType, GroupMessageKind, MessageCursor, MsgQueryArgs, SortDirection,
+        },
+        ProviderTransactions,
+    },
+    wipe::wipe_group,
+    Client, XmtpApi,
+};
+
+#[derive(Debug, Error)]
+pub enum DuplicateError {
+    #[error("only groups can be duplicated")]
+    NotAGroup,
+    #[error("inbox {0} is not a member of the group")]
+    NotAMember(String),
+}
+
+impl RetryableError for DuplicateError {
+    fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DuplicateOptions {
+    /// How many of the group's latest messages to forward into the new group as context. Only
+    /// messages sent after every member of the new group joined this one are forwarded, and
+    /// remote attachments aren't.
+    pub context_messages: usize,
+}
+
+impl<ApiClient, V> MlsGroup<Client<ApiClient, V>>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Create a group with this group's metadata and permissions and the members in
+    /// `member_subset`, who must all be members of this group. This inbox is the super admin of
+    /// the new group. If the members can't be added, the new group is deleted again.
+    pub async fn duplicate<S: AsRef<str>>(
+        &self,
+        member_subset: &[S],
+        opts: DuplicateOptions,
+    ) -> Result<MlsGroup<Client<ApiClient, V>>, GroupError> {
+        let provider = self.mls_provider()?;
+        if self.metadata(&provider).await?.conversation_type != ConversationType::Group {
+            return Err(DuplicateError::NotAGroup.into());
+        }
+        let members = self.members_with_provider(&provider).await?;
+        if let Some(stranger) = member_subset
+            .iter()
+            .map(AsRef::as_ref)
+            .find(|inbox_id| !members.iter().any(|member| member.inbox_id == *inbox_id))
+        {
+            return Err(DuplicateError::NotAMember(stranger.to_string()).into());
+        }
+
+        let metadata = self.mutable_metadata(&provider)?;
+        let field = |field: MetadataField| metadata.attributes.get(field.as_str()).cloned();
+        let millis = |field: MetadataField| metadata.attributes.get(field.as_str())?.parse().ok();
+        let group = self.client.create_group(
+            Some(self.permissions()?.policies),
+            GroupMetadataOptions {
+                name: field(MetadataField::GroupName),
+                image_url_square: field(MetadataField::GroupImageUrlSquare),
+                description: field(MetadataField::Description),
+                pinned_frame_url: field(MetadataField::GroupPinnedFrameUrl),
+                message_expiration_from_ms: millis(MetadataField::MessageExpirationFromMillis),
+                message_expiration_ms: millis(MetadataField::MessageExpirationMillis),
+            },
+        )?;
+
+        let own_inbox_id = self.context().inbox_id();
+        let inbox_ids: Vec<&str> = member_subset
+            .iter()
+            .map(AsRef::as_ref)
+            .filter(|inbox_id| *inbox_id != own_inbox_id)
+            .collect();
+        if !inbox_ids.is_empty() {
+            if let Err(e) = group
+                .add_members_by_inbox_id_with_provider(&provider, &inbox_ids)
+                .await
+            {
+                // nobody else knows about the group yet, so don't leave it behind
+                if let Err(cleanup) =
+                    provider.transaction(|provider| wipe_group(provider, &group.group_id))
+                {
+                    tracing::warn!(
+                        group_id = hex::encode(&group.group_id),
+                        "failed to delete a duplicate group whose members couldn't be added: \
+                         {cleanup}"
+                    );
+                }
+                return Err(e);
+            }
+        }
+
+        let joined_at_ns = self.last_joined_at_ns(&inbox_ids)?;
+        for message_id in self.latest_forwardable_messages(opts.context_messages, joined_at_ns)? {
+            self.forward_message(&message_id, &group.group_id).await?;
+        }
+
+        Ok(group)
+    }
+
+    /// When the last of `inbox_ids` to join the group was added, or `None` if they were all
+    /// members before the first membership change this installation knows of
+    fn last_joined_at_ns(&self, inbox_ids: &[&str]) -> Result<Option<i64>, GroupError> {
+        let conn = self.context().store().conn()?;
+        let changes = conn.get_group_messages(
+            &self.group_id,
+            &MsgQueryArgs {
+                kind: Some(GroupMessageKind::MembershipChange),
+                direction: Some(SortDirection::Descending),
+                ..Default::default()
+            },
+        )?;
+        for change in changes {
+            let content = EncodedContent::decode(change.decrypted_message_bytes.as_slice())
+                .map_err(|e| CodecError::Decode(e.to_string()))?;
+            let update = GroupUpdatedCodec::decode(content)?;
+            if update
+                .added_inboxes
+                .iter()
+                .any(|inbox| inbox_ids.contains(&inbox.inbox_id.as_str()))
+            {
+                return Ok(Some(change.sent_at_ns));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The ids of up to `count` of the latest messages sent after `sent_after_ns` that can be
+    /// forwarded, oldest first
+    fn latest_forwardable_messages(
+        &self,
+        count: usize,
+        sent_after_ns: Option<i64>,
+    ) -> Result<Vec<Vec<u8>>, GroupError> {
+        let conn = self.context().store().conn()?;
+        let mut message_ids = vec![];
+        let mut cursor = None;
+        while message_ids.len() < count {
+            let page = conn.get_group_messages(
+                &self.group_id,
+                &MsgQueryArgs {
+                    sent_after_ns,
+                    kind: Some(GroupMessageKind::Application),
+                    direction: Some(SortDirection::Descending),
+                    limit: Some(DEFAULT_MESSAGE_PAGE_SIZE),
+                    cursor,
+                    ..Default::default()
+                },
+            )?;
+            let Some(last) = page.last() else {
+                break;
+            };
+            cursor = Some(MessageCursor {
+                sent_at_ns: last.sent_at_ns,
+                message_id: last.id.clone(),
+            });
+            message_ids.extend(
+                page.iter()
+                    .filter(|message| {
+                        is_forwardable(message)
+                            && message.content_type != ContentType::RemoteAttachment
+                    })
+                    .map(|message| message.id.clone()),
+            );
+        }
+        message_ids.truncate(count);
+        message_ids.reverse();
+        Ok(message_ids)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::forward::conversation_ref};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::{
+        encoded_content_to_bytes, forward::ForwardProvenance, text::TextCodec, ContentCodec,
+    };
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_duplicate_with_member_subset_and_context() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(
+                None,
+                GroupMetadataOptions {
+                    name: Some("Trip planning".to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id(), caro.inbox_id()])
+            .await
+            .unwrap();
+        for text in ["flights", "hotels", "car"] {
+            let text = TextCodec::encode(text.to_string()).unwrap();
+            group
+                .send_message(&encoded_content_to_bytes(text))
+                .await
+                .unwrap();
+        }
+
+        let stranger = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        assert!(matches!(
+            group
+                .duplicate(&[stranger.inbox_id()], DuplicateOptions::default())
+                .await,
+            Err(GroupError::Duplicate(DuplicateError::NotAMember(_)))
+        ));
+
+        let side = group
+            .duplicate(
+                &[bo.inbox_id()],
+                DuplicateOptions {
+                    context_messages: 2,
+                },
+            )
+            .await
+            .unwrap();
+        let provider = alix.mls_provider().unwrap();
+        assert_eq!(side.group_name(&provider).unwrap(), "Trip planning");
+        let mut members: Vec<_> = side
+            .members()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|member| member.inbox_id)
+            .collect();
+        members.sort();
+        let mut expected = vec![alix.inbox_id().to_string(), bo.inbox_id().to_string()];
+        expected.sort();
+        assert_eq!(members, expected);
+
+        let messages = side
+            .find_messages(&MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(messages.len(), 2);
+        let content = messages[0].encoded_content().unwrap();
+        let provenance = ForwardProvenance::read(&content).unwrap().unwrap();
+        assert_eq!(TextCodec::decode(content).unwrap(), "hotels");
+        assert_eq!(
+            provenance.original_conversation,
+            conversation_ref(&group.group_id)
+        );
+
+        // a member who joined later doesn't get the messages sent before
+        let dave = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        group
+            .add_members_by_inbox_id(&[dave.inbox_id()])
+            .await
+            .unwrap();
+        let text = TextCodec::encode("dinner".to_string()).unwrap();
+        group
+            .send_message(&encoded_content_to_bytes(text))
+            .await
+            .unwrap();
+        let side = group
+            .duplicate(
+                &[bo.inbox_id(), dave.inbox_id()],
+                DuplicateOptions {
+                    context_messages: 5,
+                },
+            )
+            .await
+            .unwrap();
+        let messages = side
+            .find_messages(&MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(
+            TextCodec::decode(messages[0].encoded_content().unwrap()).unwrap(),
+            "dinner"
+        );
+    }
+}
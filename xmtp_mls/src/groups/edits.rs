@@ -0,0 +1,306 @@
+//! Editing sent messages. An edit is sent as its own message; applying it replaces the
+//! content of the stored message and keeps the earlier content in the edit history. An edit
+//! can't change the content type, and one that arrives before the message it edits is applied
+//! once that message arrives.
+use prost::Message;
+use thiserror::Error;
+use xmtp_common::RetryableError;
+use xmtp_content_types::{
+    edit::{Edit, EditCodec},
+    encoded_content_to_bytes, CodecError, ContentCodec,
+};
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use super::{GroupError, MlsGroup, QueryableContentFields, ScopedGroupClient};
+use crate::{
+    storage::{
+        db_connection::DbConnection,
+        group_message::{ContentType, GroupMessageKind, StoredGroupMessage},
+        message_edit::StoredMessageEdit,
+        pending_reference::StoredPendingReference,
+        NotFound, StorageError,
+    },
+    subscriptions::{LocalEvents, MessageEdit},
+    StoreOrIgnore,
+};
+
+#[derive(Debug, Error)]
+pub enum EditError {
+    #[error("only the sender of a message can edit it")]
+    NotSender,
+    #[error("{0} messages can't be edited")]
+    NotEditable(ContentType),
+    #[error("an edit can't change a {0} message to another content type")]
+    ContentTypeChanged(ContentType),
+}
+
+impl RetryableError for EditError {
+    fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+/// Whether `message` can be edited at all, regardless of who edits it
+fn check_editable(message: &StoredGroupMessage) -> Result<(), EditError> {
+    match message.content_type {
+        ContentType::Edit
+        | ContentType::DeleteMessage
+        | ContentType::Reaction
+        | ContentType::ReadReceipt
+        | ContentType::GroupMembershipChange
+        | ContentType::GroupUpdated => Err(EditError::NotEditable(message.content_type)),
+        _ if message.kind != GroupMessageKind::Application || message.deleted_at_ns.is_some() => {
+            Err(EditError::NotEditable(message.content_type))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Whether `edited`, the new content of `message`, has the same content type
+fn check_content_type(
+    message: &StoredGroupMessage,
+    edited: &QueryableContentFields,
+) -> Result<(), EditError> {
+    if edited.content_type != message.content_type || edited.authority_id != message.authority_id {
+        return Err(EditError::ContentTypeChanged(message.content_type));
+    }
+    Ok(())
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Replace the content of `message_id`, a message this inbox sent to this conversation,
+    /// with `new_content`. Returns the id of the edit message.
+    pub async fn edit_message(
+        &self,
+        message_id: &[u8],
+        new_content: EncodedContent,
+    ) -> Result<Vec<u8>, GroupError> {
+        let conn = self.context().store().conn()?;
+        let message = conn
+            .get_group_message(message_id)?
+            .filter(|message| message.group_id == self.group_id)
+            .ok_or_else(|| StorageError::from(NotFound::MessageById(message_id.to_vec())))?;
+        if message.sender_inbox_id != self.context().inbox_id() {
+            return Err(EditError::NotSender.into());
+        }
+        check_editable(&message)?;
+        let edited = QueryableContentFields::try_from(new_content.clone())
+            .map_err(|e| CodecError::Encode(e.to_string()))?;
+        check_content_type(&message, &edited)?;
+
+        let edit = EditCodec::encode(Edit {
+            reference: hex::encode(message_id),
+            content: new_content,
+        })?;
+        self.send_message(&encoded_content_to_bytes(edit)).await
+    }
+
+    /// Earlier versions of `message_id`, oldest first. The current version is the stored
+    /// message itself.
+    pub fn edit_history(&self, message_id: &[u8]) -> Result<Vec<StoredMessageEdit>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn.get_message_edits(message_id)?)
+    }
+
+    /// Apply the edit in `message` to the message it references, or keep it until that message
+    /// arrives. An edit that is malformed or not allowed is logged and ignored, it must not fail
+    /// message processing.
+    pub(super) fn process_edit(&self, conn: &DbConnection, message: &StoredGroupMessage) {
+        match apply_edit(conn, message) {
+            Ok(Some(edit)) => self.publish_after_commit(conn, LocalEvents::MessageEdited(edit)),
+            Ok(None) => tracing::debug!(
+                group_id = hex::encode(&self.group_id),
+                message_id = hex::encode(&message.id),
+                "keeping edit until the message it edits arrives"
+            ),
+            Err(e) => tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                message_id = hex::encode(&message.id),
+                "ignoring edit: {e}"
+            ),
+        }
+    }
+}
+
+/// Apply the edit in `message`. Returns `None` if the edited message hasn't arrived yet, in
+/// which case the edit waits for it.
+fn apply_edit(
+    conn: &DbConnection,
+    message: &StoredGroupMessage,
+) -> Result<Option<MessageEdit>, GroupError> {
+    let content = message
+        .encoded_content()
+        .map_err(|e| CodecError::Decode(e.to_string()))?;
+    let edit = EditCodec::decode(content)?;
+    let edited_id = hex::decode(&edit.reference).map_err(|e| CodecError::Decode(e.to_string()))?;
+    let Some(original) = conn
+        .get_group_message(&edited_id)?
+        .filter(|original| original.group_id == message.group_id)
+    else {
+        StoredPendingReference {
+            message_id: message.id.clone(),
+            group_id: message.group_id.clone(),
+            reference_id: edited_id,
+        }
+        .store_or_ignore(conn)?;
+        return Ok(None);
+    };
+    if original.sender_inbox_id != message.sender_inbox_id {
+        return Err(EditError::NotSender.into());
+    }
+    if let Some(deleted_at_ns) = original.deleted_at_ns {
+        // an edit of a deleted message mustn't keep the content the deletion erased
+        conn.mark_message_deleted(&message.id, deleted_at_ns)?;
+    }
+    check_editable(&original)?;
+
+    let fields = QueryableContentFields::try_from(edit.content.clone())
+        .map_err(|e| CodecError::Decode(e.to_string()))?;
+    check_content_type(&original, &fields)?;
+    let edited = StoredGroupMessage {
+        decrypted_message_bytes: edit.content.encode_to_vec(),
+        content_type: fields.content_type,
+        version_major: fields.version_major,
+        version_minor: fields.version_minor,
+        authority_id: fields.authority_id,
+        reference_id: fields.reference_id,
+        ..original.clone()
+    };
+    let version = conn.apply_message_edit(&original, &edited, &message.id, message.sent_at_ns)?;
+
+    Ok(Some(MessageEdit {
+        group_id: message.group_id.clone(),
+        message_id: original.id,
+        edit_message_id: message.id.clone(),
+        version,
+    }))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions,
+        storage::group_message::tests::generate_message, Store,
+    };
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::{
+        attachment::{Attachment, AttachmentCodec},
+        text::TextCodec,
+    };
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_edit_message() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let text = |s: &str| TextCodec::encode(s.to_string()).unwrap();
+        let message_id = alix_group
+            .send_message(&encoded_content_to_bytes(text("helo")))
+            .await
+            .unwrap();
+
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+        bo_group.sync().await.unwrap();
+        assert!(matches!(
+            bo_group.edit_message(&message_id, text("bye")).await,
+            Err(GroupError::Edit(EditError::NotSender))
+        ));
+
+        let attachment = AttachmentCodec::encode(Attachment {
+            filename: "hello.txt".to_string(),
+            mime_type: "text/plain".to_string(),
+            data: b"hello".to_vec(),
+        })
+        .unwrap();
+        assert!(matches!(
+            alix_group.edit_message(&message_id, attachment).await,
+            Err(GroupError::Edit(EditError::ContentTypeChanged(
+                ContentType::Text
+            )))
+        ));
+
+        let edits = bo_group.stream_message_edits();
+        futures::pin_mut!(edits);
+        let edit_id = alix_group
+            .edit_message(&message_id, text("hello"))
+            .await
+            .unwrap();
+        bo_group.sync().await.unwrap();
+
+        let edit = edits.next().await.unwrap().unwrap();
+        assert_eq!(edit.message_id, message_id);
+        assert_eq!(edit.edit_message_id, edit_id);
+        assert_eq!(edit.version, 1);
+
+        for group in [&alix_group, &bo_group] {
+            let conn = group.context().store().conn().unwrap();
+            let message = conn.get_group_message(&message_id).unwrap().unwrap();
+            assert_eq!(
+                TextCodec::decode(message.encoded_content().unwrap()).unwrap(),
+                "hello"
+            );
+            let history = group.edit_history(&message_id).unwrap();
+            assert_eq!(history.len(), 1);
+            assert_eq!(
+                history[0].decrypted_message_bytes,
+                encoded_content_to_bytes(text("helo"))
+            );
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_edit_before_message() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let conn = group.context().store().conn().unwrap();
+        let text = |s: &str| TextCodec::encode(s.to_string()).unwrap();
+        let original = StoredGroupMessage {
+            decrypted_message_bytes: encoded_content_to_bytes(text("helo")),
+            content_type: ContentType::Text,
+            version_major: 1,
+            authority_id: "xmtp.org".to_string(),
+            ..generate_message(None, Some(&group.group_id), Some(1), None)
+        };
+        let edit = EditCodec::encode(Edit {
+            reference: hex::encode(&original.id),
+            content: text("hello"),
+        })
+        .unwrap();
+        let edit = StoredGroupMessage {
+            decrypted_message_bytes: encoded_content_to_bytes(edit),
+            content_type: ContentType::Edit,
+            ..generate_message(None, Some(&group.group_id), Some(2), None)
+        };
+
+        // the edit arrives first and waits for the message
+        edit.store(&conn).unwrap();
+        group.process_edit(&conn, &edit);
+        original.store(&conn).unwrap();
+        group.process_pending_references(&conn, &original);
+
+        let message = conn.get_group_message(&original.id).unwrap().unwrap();
+        assert_eq!(
+            TextCodec::decode(message.encoded_content().unwrap()).unwrap(),
+            "hello"
+        );
+        assert_eq!(group.edit_history(&original.id).unwrap().len(), 1);
+    }
+}
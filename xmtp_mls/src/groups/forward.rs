@@ -0,0 +1,356 @@
+//! Forwarding messages to other conversations. The forwarded copy carries a
+//! [`ForwardProvenance`] naming the original sender and conversation, so every client can
+//! render "Forwarded from…" the same way. The conversation is named by [`conversation_ref`], so
+//! its id isn't disclosed to the target conversation.
+//!
+//! Remote attachments can't be forwarded by [`MlsGroup::forward_message`], since their content
+//! carries the secret and location of the original payload. [`MlsGroup::forward_attachment`]
+//! re-encrypts and uploads the payload instead.
+use prost::Message;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use xmtp_common::RetryableError;
+use xmtp_content_types::{
+    forward::ForwardProvenance, remote_attachment::RemoteAttachmentCodec, CodecError, ContentCodec,
+};
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use super::{
+    attachments::{
+        decrypt_attachment, encrypt_attachment, AttachmentDownloader, AttachmentError,
+        AttachmentUploadOptions, AttachmentUploader,
+    },
+    GroupError, MlsGroup, ScopedGroupClient,
+};
+use crate::storage::{
+    db_connection::DbConnection,
+    group_message::{ContentType, StoredGroupMessage},
+    NotFound, StorageError,
+};
+
+#[derive(Debug, Error)]
+pub enum ForwardError {
+    #[error("{0} messages can't be forwarded")]
+    NotForwardable(ContentType),
+    #[error("can't forward a message to the conversation it was sent in")]
+    SameConversation,
+    #[error("remote attachments are forwarded with forward_attachment")]
+    RemoteAttachment,
+}
+
+impl RetryableError for ForwardError {
+    fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+const CONVERSATION_REF_LABEL: &[u8] = b"XMTP_FORWARDED_FROM";
+
+/// The reference to the conversation `group_id` recorded in the provenance of messages
+/// forwarded from it. Members of the conversation can recognize it, but the id can't be
+/// recovered from it.
+pub fn conversation_ref(group_id: &[u8]) -> Vec<u8> {
+    Sha256::new()
+        .chain_update(CONVERSATION_REF_LABEL)
+        .chain_update(group_id)
+        .finalize()
+        .to_vec()
+}
+
+fn is_remote_attachment(content: &EncodedContent) -> bool {
+    content
+        .r#type
+        .as_ref()
+        .is_some_and(|id| id.type_id == RemoteAttachmentCodec::TYPE_ID)
+}
+
+/// Whether `message` carries content of its own, rather than acting on the conversation or on
+/// other messages
+pub(super) fn is_forwardable(message: &StoredGroupMessage) -> bool {
+    let forwardable_type = !matches!(
+        message.content_type,
+        ContentType::GroupMembershipChange
+            | ContentType::GroupUpdated
+            | ContentType::Reaction
+            | ContentType::ReadReceipt
+            | ContentType::Capabilities
+            | ContentType::Profile
+            | ContentType::Edit
+            | ContentType::DeleteMessage
+            | ContentType::InviteRedemption
+            | ContentType::JoinRequest
+            | ContentType::PollVote
+    );
+    forwardable_type && message.deleted_at_ns.is_none()
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Send a copy of `message_id`, a message of this conversation, to `target_group_id`.
+    /// Returns the id of the forwarded message. Remote attachments must be forwarded with
+    /// [`Self::forward_attachment`].
+    pub async fn forward_message(
+        &self,
+        message_id: &[u8],
+        target_group_id: &[u8],
+    ) -> Result<Vec<u8>, GroupError> {
+        let conn = self.context().store().conn()?;
+        let (mut content, provenance) =
+            self.forwarded_content(&conn, message_id, target_group_id)?;
+        if is_remote_attachment(&content) {
+            return Err(ForwardError::RemoteAttachment.into());
+        }
+        provenance.write(&mut content);
+
+        let target = self.target_group(&conn, target_group_id)?;
+        target.send_message(&content.encode_to_vec()).await
+    }
+
+    /// Forward `message_id`, a remote attachment of this conversation, to `target_group_id`
+    /// under a new secret: the payload is downloaded with `downloader`, decrypted, encrypted
+    /// again and uploaded with `uploader`. Returns the id of the forwarded message.
+    pub async fn forward_attachment<D, U>(
+        &self,
+        message_id: &[u8],
+        target_group_id: &[u8],
+        downloader: &D,
+        uploader: &U,
+        opts: AttachmentUploadOptions,
+    ) -> Result<Vec<u8>, GroupError>
+    where
+        D: AttachmentDownloader + ?Sized,
+        U: AttachmentUploader + ?Sized,
+    {
+        let conn = self.context().store().conn()?;
+        let (content, provenance) = self.forwarded_content(&conn, message_id, target_group_id)?;
+        if !is_remote_attachment(&content) {
+            return Err(AttachmentError::NotAnAttachment.into());
+        }
+
+        let original = RemoteAttachmentCodec::decode(content)?;
+        let payload = downloader.download(&original.url).await?;
+        let encrypted = encrypt_attachment(decrypt_attachment(&original, &payload)?)?;
+
+        let target = self.target_group(&conn, target_group_id)?;
+        target
+            .send_attachment(uploader, &encrypted.payload, opts, |url| {
+                let mut content = RemoteAttachmentCodec::encode(encrypted.remote_attachment(url))
+                    .expect("remote attachments always encode");
+                provenance.write(&mut content);
+                content.encode_to_vec()
+            })
+            .await
+    }
+
+    /// The content of `message_id` to forward to `target_group_id`, and its provenance once
+    /// forwarded
+    fn forwarded_content(
+        &self,
+        conn: &DbConnection,
+        message_id: &[u8],
+        target_group_id: &[u8],
+    ) -> Result<(EncodedContent, ForwardProvenance), GroupError> {
+        if target_group_id == self.group_id.as_slice() {
+            return Err(ForwardError::SameConversation.into());
+        }
+        let message = conn
+            .get_group_message(message_id)?
+            .filter(|message| message.group_id == self.group_id)
+            .ok_or_else(|| StorageError::from(NotFound::MessageById(message_id.to_vec())))?;
+        if !is_forwardable(&message) {
+            return Err(ForwardError::NotForwardable(message.content_type).into());
+        }
+
+        let content = EncodedContent::decode(message.decrypted_message_bytes.as_slice())
+            .map_err(|e| CodecError::Decode(e.to_string()))?;
+        let provenance = ForwardProvenance::next_hop(
+            &content,
+            &message.sender_inbox_id,
+            &conversation_ref(&message.group_id),
+            message.sent_at_ns,
+        )?;
+        Ok((content, provenance))
+    }
+
+    fn target_group(
+        &self,
+        conn: &DbConnection,
+        target_group_id: &[u8],
+    ) -> Result<MlsGroup<ScopedClient>, GroupError> {
+        let target = conn
+            .find_group(target_group_id.to_vec())?
+            .ok_or_else(|| StorageError::from(NotFound::GroupById(target_group_id.to_vec())))?;
+        Ok(MlsGroup::new_from_arc(
+            self.client.clone(),
+            target.id,
+            target.created_at_ns,
+        ))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use std::{collections::HashMap, sync::Mutex};
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::{attachment::Attachment, encoded_content_to_bytes, text::TextCodec};
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    /// Attachment storage that keeps uploads in memory, one URL per upload session
+    #[derive(Default)]
+    struct MemoryStorage {
+        uploads: Mutex<HashMap<String, Vec<u8>>>,
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    impl AttachmentUploader for MemoryStorage {
+        async fn start_upload(&self, _total_size: u64) -> Result<String, AttachmentError> {
+            let mut uploads = self.uploads.lock().unwrap();
+            let url = format!("https://example.com/{}", uploads.len());
+            uploads.insert(url.clone(), vec![]);
+            Ok(url)
+        }
+
+        async fn upload_chunk(
+            &self,
+            session: &str,
+            _offset: u64,
+            chunk: &[u8],
+        ) -> Result<(), AttachmentError> {
+            let mut uploads = self.uploads.lock().unwrap();
+            uploads.get_mut(session).unwrap().extend_from_slice(chunk);
+            Ok(())
+        }
+
+        async fn complete_upload(&self, session: &str) -> Result<String, AttachmentError> {
+            Ok(session.to_string())
+        }
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    impl AttachmentDownloader for MemoryStorage {
+        async fn download(&self, url: &str) -> Result<Vec<u8>, AttachmentError> {
+            Ok(self.uploads.lock().unwrap()[url].clone())
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_forward_message() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let source = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let target = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+
+        let text = TextCodec::encode("hello".to_string()).unwrap();
+        let message_id = source
+            .send_message(&encoded_content_to_bytes(text))
+            .await
+            .unwrap();
+
+        let forwarded_id = source
+            .forward_message(&message_id, &target.group_id)
+            .await
+            .unwrap();
+        let conn = alix.store().conn().unwrap();
+        let forwarded = conn.get_group_message(&forwarded_id).unwrap().unwrap();
+        assert_eq!(forwarded.group_id, target.group_id);
+
+        let content = EncodedContent::decode(forwarded.decrypted_message_bytes.as_slice()).unwrap();
+        let provenance = ForwardProvenance::read(&content).unwrap().unwrap();
+        assert_eq!(provenance.original_sender_inbox_id, alix.inbox_id());
+        assert_eq!(
+            provenance.original_conversation,
+            conversation_ref(&source.group_id)
+        );
+        assert_eq!(provenance.hop_count, 1);
+        assert_eq!(TextCodec::decode(content).unwrap(), "hello");
+
+        // forwarding it back keeps the original source
+        let again_id = target
+            .forward_message(&forwarded_id, &source.group_id)
+            .await
+            .unwrap();
+        let again = conn.get_group_message(&again_id).unwrap().unwrap();
+        let content = EncodedContent::decode(again.decrypted_message_bytes.as_slice()).unwrap();
+        let provenance = ForwardProvenance::read(&content).unwrap().unwrap();
+        assert_eq!(
+            provenance.original_conversation,
+            conversation_ref(&source.group_id)
+        );
+        assert_eq!(provenance.hop_count, 2);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_forward_attachment_under_new_secret() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let source = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let target = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let storage = MemoryStorage::default();
+        let attachment = Attachment {
+            filename: "cat.png".to_string(),
+            mime_type: "image/png".to_string(),
+            data: vec![9u8; 100],
+        };
+        let message_id = source
+            .send_remote_attachment(
+                &storage,
+                &encrypt_attachment(attachment.clone()).unwrap(),
+                AttachmentUploadOptions::default(),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            source.forward_message(&message_id, &target.group_id).await,
+            Err(GroupError::Forward(ForwardError::RemoteAttachment))
+        ));
+
+        let forwarded_id = source
+            .forward_attachment(
+                &message_id,
+                &target.group_id,
+                &storage,
+                &storage,
+                AttachmentUploadOptions::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(storage.uploads.lock().unwrap().len(), 2);
+
+        let conn = alix.store().conn().unwrap();
+        let content = |message_id: &[u8]| {
+            let message = conn.get_group_message(message_id).unwrap().unwrap();
+            EncodedContent::decode(message.decrypted_message_bytes.as_slice()).unwrap()
+        };
+        let provenance = ForwardProvenance::read(&content(&forwarded_id))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            provenance.original_conversation,
+            conversation_ref(&source.group_id)
+        );
+        let original = RemoteAttachmentCodec::decode(content(&message_id)).unwrap();
+        let forwarded = RemoteAttachmentCodec::decode(content(&forwarded_id)).unwrap();
+        assert_ne!(forwarded.url, original.url);
+        assert_ne!(forwarded.secret, original.secret);
+        assert_eq!(
+            target
+                .download_attachment(&storage, &forwarded_id)
+                .await
+                .unwrap(),
+            attachment
+        );
+    }
+}
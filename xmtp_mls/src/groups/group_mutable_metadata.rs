@@ -49,6 +49,12 @@ pub enum MetadataField {
     GroupPinnedFrameUrl,
     MessageExpirationFromMillis,
     MessageExpirationMillis,
+    PinnedMessages,
+    /// Left out of [`GroupMutableMetadata::supported_fields`] so that only admins can ban
+    BannedInboxes,
+    /// Left out of [`GroupMutableMetadata::supported_fields`] so that only admins can change
+    /// the key rotation policy
+    KeyRotationPolicy,
 }
 
 impl MetadataField {
@@ -61,6 +67,9 @@ impl MetadataField {
             MetadataField::GroupPinnedFrameUrl => "group_pinned_frame_url",
             MetadataField::MessageExpirationFromMillis => "message_expiration_from_ms",
             MetadataField::MessageExpirationMillis => "message_expiration_ms",
+            MetadataField::PinnedMessages => "pinned_messages",
+            MetadataField::BannedInboxes => "banned_inboxes",
+            MetadataField::KeyRotationPolicy => "key_rotation_policy",
         }
     }
 }
@@ -187,6 +196,7 @@ impl GroupMutableMetadata {
             MetadataField::GroupImageUrlSquare,
             MetadataField::GroupPinnedFrameUrl,
             MetadataField::MessageExpirationMillis,
+            MetadataField::PinnedMessages,
         ]
     }
 
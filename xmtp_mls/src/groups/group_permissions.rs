@@ -27,12 +27,18 @@ use xmtp_proto::xmtp::mls::message_contents::{
 
 use super::{
     group_mutable_metadata::GroupMutableMetadata,
+    succession::{SuperAdminClaim, SUPER_ADMIN_TRANSFER_METADATA_FIELD},
     validated_commit::{CommitParticipant, Inbox, MetadataFieldChange, ValidatedCommit},
 };
 use crate::configuration::{GROUP_PERMISSIONS_EXTENSION_ID, SUPER_ADMIN_METADATA_PREFIX};
 use crate::groups::group_mutable_metadata::MetadataField;
 use crate::groups::group_mutable_metadata::MetadataField::MessageExpirationMillis;
 
+/// Key of the send message policy in [`PolicySet::update_metadata_policy`]. The policy set proto
+/// has no slot for it, so it's stored with the metadata policies, where clients that don't know
+/// about it ignore it.
+pub const SEND_MESSAGE_POLICY_KEY: &str = "send_message";
+
 /// Errors that can occur when working with GroupMutablePermissions.
 #[derive(Debug, Error)]
 pub enum GroupMutablePermissionsError {
@@ -923,6 +929,27 @@ impl PolicySet {
         }
     }
 
+    /// Starts a [`PolicySetBuilder`] from the "All Members" preconfigured policy.
+    pub fn builder() -> PolicySetBuilder {
+        PolicySetBuilder::new(PreconfiguredPolicies::Default)
+    }
+
+    /// The policy for sending application messages. Groups without one let every member send.
+    pub fn send_message_policy(&self) -> Option<&MetadataPolicies> {
+        self.update_metadata_policy.get(SEND_MESSAGE_POLICY_KEY)
+    }
+
+    /// Whether `sender` may send application messages to the group.
+    pub fn evaluate_send(&self, sender: &CommitParticipant) -> bool {
+        let Some(policy) = self.send_message_policy() else {
+            return true;
+        };
+        policy.evaluate(
+            sender,
+            &MetadataFieldChange::new(SEND_MESSAGE_POLICY_KEY.to_string(), None, None),
+        )
+    }
+
     /// The [evaluate_commit] function is the core function for client side verification
     /// that [ValidatedCommit](crate::groups::validated_commit::ValidatedCommit)
     /// adheres to the XMTP permission policies set in the PolicySet.
@@ -958,9 +985,24 @@ impl PolicySet {
             .iter()
             .any(|inbox| inbox.is_super_admin);
 
+        // Members accepting the super admin role may withdraw the offer they accept
+        let transfer_from = match &commit.super_admin_claim {
+            Some(SuperAdminClaim::Transfer { from_inbox_id }) => Some(from_inbox_id),
+            _ => None,
+        };
+        let withdraws_accepted_offer = |change: &MetadataFieldChange| {
+            transfer_from.is_some()
+                && change.field_name == SUPER_ADMIN_TRANSFER_METADATA_FIELD
+                && change.new_value.as_deref().unwrap_or_default().is_empty()
+        };
+
         // Verify that update metadata policy was not violated
         let metadata_changes_valid = self.evaluate_metadata_policy(
-            commit.metadata_changes.metadata_field_changes.iter(),
+            commit
+                .metadata_changes
+                .metadata_field_changes
+                .iter()
+                .filter(|change| !withdraws_accepted_offer(change)),
             &self.update_metadata_policy,
             &commit.actor,
         );
@@ -974,13 +1016,29 @@ impl PolicySet {
             || self.remove_admin_policy.evaluate(&commit.actor);
 
         // Verify that super admin add policy was not violated
-        let super_admin_add_valid =
-            commit.metadata_changes.super_admins_added.is_empty() || commit.actor.is_super_admin;
+        // Members offered the role or succeeding dormant super admins may only add themselves
+        let super_admin_add_valid = commit.metadata_changes.super_admins_added.is_empty()
+            || commit.actor.is_super_admin
+            || (commit.super_admin_claim.is_some()
+                && commit
+                    .metadata_changes
+                    .super_admins_added
+                    .iter()
+                    .all(|inbox| inbox.inbox_id == commit.actor.inbox_id));
 
         // Verify that super admin remove policy was not violated
         // You can never remove the last super admin
+        // Members accepting the super admin role may only demote the member who offered it
         let super_admin_remove_valid = commit.metadata_changes.super_admins_removed.is_empty()
-            || (commit.actor.is_super_admin && commit.metadata_changes.num_super_admins > 0);
+            || (commit.metadata_changes.num_super_admins > 0
+                && (commit.actor.is_super_admin
+                    || transfer_from.is_some_and(|from_inbox_id| {
+                        commit
+                            .metadata_changes
+                            .super_admins_removed
+                            .iter()
+                            .all(|inbox| &inbox.inbox_id == from_inbox_id)
+                    })));
 
         // Permissions can only be changed by the super admin
         let permissions_changes_valid = !commit.permissions_changed || commit.actor.is_super_admin;
@@ -1144,6 +1202,118 @@ impl PolicySet {
     }
 }
 
+/// Builds a [`PolicySet`] one action at a time, starting from a preconfigured policy.
+#[derive(Debug, Clone)]
+pub struct PolicySetBuilder {
+    policies: PolicySet,
+}
+
+impl PolicySetBuilder {
+    /// Starts from the policies of `base`.
+    pub fn new(base: PreconfiguredPolicies) -> Self {
+        Self {
+            policies: base.to_policy_set(),
+        }
+    }
+
+    /// Who can add members.
+    pub fn add_member_policy(mut self, policy: MembershipPolicies) -> Self {
+        self.policies.add_member_policy = policy;
+        self
+    }
+
+    /// Who can remove members.
+    pub fn remove_member_policy(mut self, policy: MembershipPolicies) -> Self {
+        self.policies.remove_member_policy = policy;
+        self
+    }
+
+    /// Who can update `field`.
+    pub fn metadata_policy(mut self, field: MetadataField, policy: MetadataPolicies) -> Self {
+        self.policies
+            .update_metadata_policy
+            .insert(field.to_string(), policy);
+        self
+    }
+
+    /// Who can update any of the supported metadata fields.
+    pub fn all_metadata_policies(mut self, policy: MetadataPolicies) -> Self {
+        for field in GroupMutableMetadata::supported_fields() {
+            self.policies
+                .update_metadata_policy
+                .insert(field.to_string(), policy.clone());
+        }
+        self
+    }
+
+    /// Who can make members admins.
+    pub fn add_admin_policy(mut self, policy: PermissionsPolicies) -> Self {
+        self.policies.add_admin_policy = policy;
+        self
+    }
+
+    /// Who can revoke admins.
+    pub fn remove_admin_policy(mut self, policy: PermissionsPolicies) -> Self {
+        self.policies.remove_admin_policy = policy;
+        self
+    }
+
+    /// Who can change the group's policies. Only super admins can, whatever this is set to.
+    pub fn update_permissions_policy(mut self, policy: PermissionsPolicies) -> Self {
+        self.policies.update_permissions_policy = policy;
+        self
+    }
+
+    /// Who can send messages. [`MetadataPolicies::allow_if_actor_admin`] makes the group an
+    /// announcement channel, and [`MetadataPolicies::deny`] makes it read only.
+    pub fn send_message_policy(mut self, policy: MetadataPolicies) -> Self {
+        self.policies
+            .update_metadata_policy
+            .insert(SEND_MESSAGE_POLICY_KEY.to_string(), policy);
+        self
+    }
+
+    /// Checks that members will be able to read the policies back, and returns them.
+    pub fn build(self) -> Result<PolicySet, PolicyError> {
+        PolicySet::from_proto(self.policies.to_proto()?)?;
+        Ok(self.policies)
+    }
+}
+
+/// Whether `inbox_id` may send application messages to `group` under its send message policy,
+/// as of the group's current epoch. Groups without a send message policy let everyone send.
+/// If the permissions or the admin lists can't be read, nobody may send, so that a corrupt
+/// extension doesn't lift the policy.
+pub(crate) fn is_allowed_to_send(group: &OpenMlsGroup, inbox_id: &str) -> bool {
+    let permissions = match extract_group_permissions(group) {
+        Ok(permissions) => permissions,
+        Err(e) => {
+            tracing::warn!("denying send, the group permissions can't be read: {e}");
+            return false;
+        }
+    };
+    if permissions.policies.send_message_policy().is_none() {
+        return true;
+    }
+    let metadata = match GroupMutableMetadata::try_from(group) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            tracing::warn!("denying send, the group admins can't be read: {e}");
+            return false;
+        }
+    };
+    permissions.policies.evaluate_send(&CommitParticipant {
+        inbox_id: inbox_id.to_string(),
+        installation_id: vec![],
+        is_creator: false,
+        is_admin: metadata.admin_list.iter().any(|admin| admin == inbox_id),
+        is_super_admin: metadata
+            .super_admin_list
+            .iter()
+            .any(|admin| admin == inbox_id),
+    })
+}
+
 /// Checks if a PolicySet is equivalent to the "All Members" preconfigured policy.
 ///
 /// Depending on if the client is on a newer or older version of libxmtp
@@ -1394,6 +1564,7 @@ pub(crate) mod tests {
             },
             permissions_changed,
             dm_members,
+            super_admin_claim: None,
         }
     }
 
@@ -1877,4 +2048,45 @@ pub(crate) mod tests {
         );
         assert!(permissions.evaluate_commit(&commit));
     }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn test_policy_set_builder() {
+        let permissions = PolicySet::builder()
+            .add_member_policy(MembershipPolicies::allow_if_actor_admin())
+            .metadata_policy(MetadataField::GroupName, MetadataPolicies::deny())
+            .send_message_policy(MetadataPolicies::allow_if_actor_admin())
+            .build()
+            .unwrap();
+        assert_eq!(
+            permissions.add_member_policy,
+            MembershipPolicies::allow_if_actor_admin()
+        );
+        assert!(PreconfiguredPolicies::from_policy_set(&permissions).is_err());
+
+        // the send message policy survives the trip through the group context
+        let permissions = PolicySet::from_bytes(&permissions.to_bytes().unwrap()).unwrap();
+        assert!(permissions.evaluate_send(&build_actor(None, None, true, false)));
+        assert!(!permissions.evaluate_send(&build_actor(None, None, false, false)));
+        let commit = build_validated_commit(
+            None,
+            None,
+            Some(vec![MetadataField::GroupName.to_string()]),
+            false,
+            true,
+            true,
+            None,
+        );
+        assert!(!permissions.evaluate_commit(&commit));
+
+        // groups without a send message policy let everyone send
+        assert!(PolicySet::default().evaluate_send(&build_actor(None, None, false, false)));
+
+        assert!(matches!(
+            PolicySet::builder()
+                .remove_member_policy(MembershipPolicies::any(vec![]))
+                .build(),
+            Err(PolicyError::InvalidMembershipPolicy)
+        ));
+    }
 }
@@ -0,0 +1,277 @@
+//! Guest members that are only part of a group until an expiration time, e.g. for support
+//! chats and event groups.
+//!
+//! Expirations are stored in the group's mutable metadata, one field per guest, so every member
+//! sees them. Metadata fields without a policy can only be changed by admins, which keeps guests
+//! from extending their own stay. Once a guest expires, whichever admin is online first removes
+//! them from the group.
+use std::{sync::Arc, time::Duration};
+
+use xmtp_common::time::now_ns;
+use xmtp_id::{scw_verifier::SmartContractSignatureVerifier, InboxIdRef};
+
+use super::{intents::UpdateMetadataIntentData, GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    client::ClientError,
+    storage::{
+        group::{ConversationType, GroupMembershipState, GroupQueryArgs},
+        group_intent::IntentKind,
+    },
+    subscriptions::LocalEvents,
+    CancellationToken, Client, StreamMetrics, XmtpApi, XmtpOpenMlsProvider,
+};
+
+/// Prefix of the metadata fields holding guest expirations, followed by the guest's inbox id
+pub const GUEST_EXPIRATION_METADATA_PREFIX: &str = "guest_expiration_ns:";
+
+/// When a guest's membership of a group ends
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestExpiration {
+    pub group_id: Vec<u8>,
+    pub inbox_id: String,
+    pub expires_at_ns: i64,
+}
+
+/// The outcome of a [`Client::process_guest_expirations`] run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GuestExpiryReport {
+    /// Guests this client removed
+    pub removed: Vec<GuestExpiration>,
+    /// Guests expiring within the warning window
+    pub upcoming: Vec<GuestExpiration>,
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Add members that are removed again once `expires_at_ns` has passed.
+    /// Requires admin permissions to record the expiration.
+    pub async fn add_guest_members_by_inbox_id<S: AsRef<str>>(
+        &self,
+        inbox_ids: &[S],
+        expires_at_ns: i64,
+    ) -> Result<(), GroupError> {
+        self.add_members_by_inbox_id(inbox_ids).await?;
+        for inbox_id in inbox_ids {
+            self.set_guest_expiration(inbox_id.as_ref(), Some(expires_at_ns))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Change when a guest's membership ends, or make them a regular member with `None`
+    pub async fn set_guest_expiration(
+        &self,
+        inbox_id: InboxIdRef<'_>,
+        expires_at_ns: Option<i64>,
+    ) -> Result<(), GroupError> {
+        let provider = self.client.mls_provider()?;
+        if self.metadata(&provider).await?.conversation_type == ConversationType::Dm {
+            return Err(GroupError::DmGroupMetadataForbidden);
+        }
+        let intent_data: Vec<u8> = UpdateMetadataIntentData::new(
+            format!("{GUEST_EXPIRATION_METADATA_PREFIX}{inbox_id}"),
+            expires_at_ns.map(|ns| ns.to_string()).unwrap_or_default(),
+        )
+        .into();
+        let intent = self.queue_intent(&provider, IntentKind::MetadataUpdate, intent_data)?;
+
+        self.sync_until_intent_resolved(&provider, intent.id).await
+    }
+
+    /// The guests of the group, soonest to expire first
+    pub fn guest_expirations(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<Vec<GuestExpiration>, GroupError> {
+        let mutable_metadata = self.mutable_metadata(provider)?;
+        let mut guests: Vec<GuestExpiration> = mutable_metadata
+            .attributes
+            .iter()
+            .filter_map(|(field, value)| {
+                let inbox_id = field.strip_prefix(GUEST_EXPIRATION_METADATA_PREFIX)?;
+                // cleared expirations are left behind as empty values
+                let expires_at_ns = value.parse().ok()?;
+                Some(GuestExpiration {
+                    group_id: self.group_id.clone(),
+                    inbox_id: inbox_id.to_string(),
+                    expires_at_ns,
+                })
+            })
+            .collect();
+        guests.sort_by_key(|guest| guest.expires_at_ns);
+        Ok(guests)
+    }
+
+    /// Remove guests whose membership has expired and clear their expirations.
+    /// Does nothing unless this client is an admin of the group.
+    pub async fn remove_expired_guests(&self) -> Result<Vec<GuestExpiration>, GroupError> {
+        let provider = self.client.mls_provider()?;
+        let inbox_id = self.client.inbox_id().to_string();
+        if !self.is_admin(inbox_id.clone(), &provider)?
+            && !self.is_super_admin(inbox_id, &provider)?
+        {
+            return Ok(vec![]);
+        }
+
+        let now = now_ns();
+        let expired: Vec<GuestExpiration> = self
+            .guest_expirations(&provider)?
+            .into_iter()
+            .filter(|guest| guest.expires_at_ns <= now)
+            .collect();
+        if expired.is_empty() {
+            return Ok(expired);
+        }
+
+        let members = self.members_with_provider(&provider).await?;
+        let to_remove: Vec<&str> = expired
+            .iter()
+            .map(|guest| guest.inbox_id.as_str())
+            .filter(|inbox_id| members.iter().any(|m| m.inbox_id == *inbox_id))
+            .collect();
+        if !to_remove.is_empty() {
+            self.remove_members_by_inbox_id(&to_remove).await?;
+        }
+        for guest in &expired {
+            self.set_guest_expiration(&guest.inbox_id, None).await?;
+        }
+        Ok(expired)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Remove expired guests from every group this client administers, and publish a
+    /// [`LocalEvents::GuestsExpiring`] event for guests expiring within `warn_before`.
+    /// Works from the groups' state as of their last sync.
+    pub async fn process_guest_expirations(
+        &self,
+        warn_before: Duration,
+    ) -> Result<GuestExpiryReport, ClientError> {
+        let provider = self.mls_provider()?;
+        let groups = self.find_groups(
+            GroupQueryArgs::default()
+                .conversation_type(ConversationType::Group)
+                .allowed_states(vec![GroupMembershipState::Allowed]),
+        )?;
+        let warn_until = now_ns().saturating_add(warn_before.as_nanos() as i64);
+
+        let mut report = GuestExpiryReport::default();
+        for group in groups {
+            match group.remove_expired_guests().await {
+                Ok(removed) => report.removed.extend(removed),
+                Err(e) => {
+                    tracing::warn!(
+                        group_id = hex::encode(&group.group_id),
+                        "failed to remove expired guests: {e}"
+                    );
+                    continue;
+                }
+            }
+            report.upcoming.extend(
+                group
+                    .guest_expirations(&provider)?
+                    .into_iter()
+                    .take_while(|guest| guest.expires_at_ns <= warn_until),
+            );
+        }
+
+        if !report.upcoming.is_empty() {
+            self.publish_local_event(LocalEvents::GuestsExpiring(report.upcoming.clone()))
+                .await;
+        }
+        Ok(report)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Run [`Client::process_guest_expirations`] every `interval` until the handle is closed.
+    /// Closing gracefully lets a run in progress finish first.
+    pub fn start_guest_expiry_worker(
+        client: Arc<Client<ApiClient, V>>,
+        interval: Duration,
+        warn_before: Duration,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
+        let cancel = CancellationToken::new();
+        let stopped = cancel.clone();
+
+        crate::spawn_cancellable(None, StreamMetrics::default(), cancel, async move {
+            while !stopped.is_cancelled() {
+                if let Err(e) = client.process_guest_expirations(warn_before).await {
+                    tracing::warn!("guest expiry run failed: {e}");
+                }
+                let _ = xmtp_common::time::timeout(interval, stopped.cancelled()).await;
+            }
+            Ok::<_, ClientError>(())
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions, subscriptions::StreamMessages,
+    };
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_expired_guests_are_removed() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let hour = Duration::from_secs(3600).as_nanos() as i64;
+        group
+            .add_guest_members_by_inbox_id(&[bo.inbox_id()], now_ns() - 1)
+            .await
+            .unwrap();
+        group
+            .add_guest_members_by_inbox_id(&[caro.inbox_id()], now_ns() + hour)
+            .await
+            .unwrap();
+        assert_eq!(
+            group
+                .guest_expirations(&alix.mls_provider().unwrap())
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let events = alix.local_events.subscribe().stream_guests_expiring();
+        futures::pin_mut!(events);
+        let report = alix
+            .process_guest_expirations(Duration::from_secs(2 * 3600))
+            .await
+            .unwrap();
+        assert_eq!(report.removed.len(), 1);
+        assert_eq!(report.removed[0].inbox_id, bo.inbox_id());
+        assert_eq!(report.upcoming.len(), 1);
+        assert_eq!(report.upcoming[0].inbox_id, caro.inbox_id());
+        let upcoming = events.next().await.unwrap().unwrap();
+        assert_eq!(upcoming, report.upcoming);
+
+        let members = group.members().await.unwrap();
+        assert_eq!(members.len(), 2);
+        assert!(!members.iter().any(|m| m.inbox_id == bo.inbox_id()));
+        let guests = group
+            .guest_expirations(&alix.mls_provider().unwrap())
+            .unwrap();
+        assert_eq!(guests.len(), 1);
+        assert_eq!(guests[0].inbox_id, caro.inbox_id());
+    }
+}
@@ -0,0 +1,210 @@
+//! Flags conversations that have been inactive for a long time, so the user can be asked
+//! whether to keep them. Optionally resets their consent so they stop being streamed and
+//! maintained until the user allows them again.
+use xmtp_common::time::now_ns;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use crate::{
+    client::ClientError,
+    configuration::{
+        INACTIVE_CONVERSATION_THRESHOLD_NS, MAX_RECONSENT_PROMPTS, NS_IN_DAY,
+        RECONSENT_PROMPT_INTERVAL_NS,
+    },
+    storage::{
+        consent_record::{ConsentState, ConsentType, StoredConsentRecord},
+        group::GroupQueryArgs,
+    },
+    subscriptions::LocalEvents,
+    Client, XmtpApi,
+};
+
+/// Decides which conversations [`Client::apply_inactivity_policy`] flags for re-consent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InactivityPolicy {
+    /// Allowed conversations without a message for this long are flagged
+    pub inactive_after_ns: i64,
+    /// Reset the consent of flagged conversations from allowed to unknown
+    pub downgrade_consent: bool,
+    /// The most conversations flagged by a single run, least recently active first
+    pub max_prompts: usize,
+    /// A flagged conversation is not flagged again until this long has passed
+    pub reprompt_after_ns: i64,
+}
+
+impl Default for InactivityPolicy {
+    fn default() -> Self {
+        Self {
+            inactive_after_ns: INACTIVE_CONVERSATION_THRESHOLD_NS,
+            downgrade_consent: false,
+            max_prompts: MAX_RECONSENT_PROMPTS,
+            reprompt_after_ns: RECONSENT_PROMPT_INTERVAL_NS,
+        }
+    }
+}
+
+impl InactivityPolicy {
+    /// Flag conversations inactive for `months` 30-day months
+    pub fn inactive_after_months(mut self, months: u32) -> Self {
+        self.inactive_after_ns = i64::from(months) * 30 * NS_IN_DAY;
+        self
+    }
+
+    pub fn downgrade_consent(mut self, downgrade_consent: bool) -> Self {
+        self.downgrade_consent = downgrade_consent;
+        self
+    }
+}
+
+/// A conversation flagged for re-consent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReconsentRequest {
+    pub group_id: Vec<u8>,
+    /// When the last message was sent, or the conversation was created if it has no messages
+    pub last_active_ns: i64,
+    /// Whether the conversation's consent was reset to unknown
+    pub consent_downgraded: bool,
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Flag allowed conversations that have been inactive longer than the policy allows,
+    /// publishing them as a [`LocalEvents::ReconsentRequested`] event.
+    /// Returns the flagged conversations.
+    pub async fn apply_inactivity_policy(
+        &self,
+        policy: &InactivityPolicy,
+    ) -> Result<Vec<ReconsentRequest>, ClientError> {
+        let conn = self.store().conn()?;
+        let now = now_ns();
+        let inactive_before = now.saturating_sub(policy.inactive_after_ns);
+        let reprompt_before = now.saturating_sub(policy.reprompt_after_ns);
+        let prompted_at = conn.reconsent_prompt_times()?;
+
+        let mut inactive: Vec<ReconsentRequest> = conn
+            .find_groups(GroupQueryArgs::default().consent_states(vec![ConsentState::Allowed]))?
+            .into_iter()
+            .filter(|group| {
+                prompted_at
+                    .get(&group.id)
+                    .map_or(true, |prompted_at| *prompted_at <= reprompt_before)
+            })
+            .filter_map(|group| {
+                let last_active_ns = group.last_message_ns.unwrap_or(group.created_at_ns);
+                (last_active_ns < inactive_before).then_some(ReconsentRequest {
+                    group_id: group.id,
+                    last_active_ns,
+                    consent_downgraded: policy.downgrade_consent,
+                })
+            })
+            .collect();
+        inactive.sort_by_key(|request| request.last_active_ns);
+        inactive.truncate(policy.max_prompts);
+        if inactive.is_empty() {
+            return Ok(inactive);
+        }
+
+        let group_ids: Vec<Vec<u8>> = inactive.iter().map(|r| r.group_id.clone()).collect();
+        conn.record_reconsent_prompts(&group_ids, now)?;
+        if policy.downgrade_consent {
+            let records: Vec<StoredConsentRecord> = group_ids
+                .iter()
+                .map(|group_id| {
+                    StoredConsentRecord::new(
+                        ConsentType::ConversationId,
+                        ConsentState::Unknown,
+                        hex::encode(group_id),
+                    )
+                })
+                .collect();
+            self.set_consent_states(&records).await?;
+        }
+        tracing::info!(
+            inbox_id = self.inbox_id(),
+            "flagged {} inactive conversations for re-consent",
+            inactive.len()
+        );
+
+        self.publish_local_event(LocalEvents::ReconsentRequested(inactive.clone()))
+            .await;
+        Ok(inactive)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions, subscriptions::StreamMessages,
+    };
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_inactivity_policy_is_rate_limited() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let older = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let newer = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let requests = alix.local_events.subscribe().stream_reconsent_requests();
+        futures::pin_mut!(requests);
+
+        // every conversation is inactive, but only one is flagged per run
+        let policy = InactivityPolicy {
+            inactive_after_ns: 0,
+            max_prompts: 1,
+            ..Default::default()
+        };
+        let flagged = alix.apply_inactivity_policy(&policy).await.unwrap();
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].group_id, older.group_id);
+        assert_eq!(requests.next().await.unwrap().unwrap(), flagged);
+
+        let flagged = alix.apply_inactivity_policy(&policy).await.unwrap();
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].group_id, newer.group_id);
+
+        // both were flagged recently
+        let flagged = alix.apply_inactivity_policy(&policy).await.unwrap();
+        assert!(flagged.is_empty());
+        assert_eq!(older.consent_state().unwrap(), ConsentState::Allowed);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_inactivity_policy_downgrades_consent() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+
+        let flagged = alix
+            .apply_inactivity_policy(&InactivityPolicy::default().downgrade_consent(true))
+            .await
+            .unwrap();
+        assert!(flagged.is_empty(), "a new conversation is not inactive");
+
+        let policy = InactivityPolicy {
+            inactive_after_ns: 0,
+            reprompt_after_ns: 0,
+            downgrade_consent: true,
+            ..Default::default()
+        };
+        let flagged = alix.apply_inactivity_policy(&policy).await.unwrap();
+        assert_eq!(flagged.len(), 1);
+        assert!(flagged[0].consent_downgraded);
+        assert_eq!(group.consent_state().unwrap(), ConsentState::Unknown);
+
+        // conversations that are no longer allowed are not flagged again
+        let flagged = alix.apply_inactivity_policy(&policy).await.unwrap();
+        assert!(flagged.is_empty());
+    }
+}
@@ -279,6 +279,83 @@ impl TryFrom<Vec<u8>> for UpdateMetadataIntentData {
     }
 }
 
+/// How an [`UpdateMetadataListIntentData`] changes its list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum MetadataListAction {
+    /// Add the entry at the start of the list
+    AddFirst,
+    /// Add the entry at the end of the list
+    AddLast,
+    Remove,
+}
+
+/// Add or remove one entry of a metadata field holding a comma separated list, like the pinned
+/// messages. The change is applied to the list as it is when the intent is published, not when
+/// it's queued, so entries other members changed in the meantime are kept.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct UpdateMetadataListIntentData {
+    pub field_name: String,
+    pub entry: String,
+    pub action: MetadataListAction,
+    /// How many entries the list can hold at most
+    pub max_entries: Option<usize>,
+}
+
+impl UpdateMetadataListIntentData {
+    pub fn new(
+        field: MetadataField,
+        entry: String,
+        action: MetadataListAction,
+        max_entries: Option<usize>,
+    ) -> Self {
+        Self {
+            field_name: field.to_string(),
+            entry,
+            action,
+            max_entries,
+        }
+    }
+
+    /// `list` with the change applied, or `None` if it already has, or lacks, the entry
+    pub fn apply(&self, list: &str) -> Result<Option<String>, IntentError> {
+        let mut entries: Vec<&str> = list.split(',').filter(|entry| !entry.is_empty()).collect();
+        let present = entries.contains(&self.entry.as_str());
+        match self.action {
+            MetadataListAction::Remove if !present => return Ok(None),
+            MetadataListAction::Remove => entries.retain(|entry| *entry != self.entry),
+            _ if present => return Ok(None),
+            action => {
+                if let Some(max) = self.max_entries.filter(|max| entries.len() >= *max) {
+                    return Err(IntentError::Generic(format!(
+                        "{} can hold at most {max} entries",
+                        self.field_name
+                    )));
+                }
+                if action == MetadataListAction::AddFirst {
+                    entries.insert(0, &self.entry);
+                } else {
+                    entries.push(&self.entry);
+                }
+            }
+        }
+        Ok(Some(entries.join(",")))
+    }
+}
+
+impl From<UpdateMetadataListIntentData> for Vec<u8> {
+    fn from(intent: UpdateMetadataListIntentData) -> Self {
+        bincode::serialize(&intent).expect("encode error")
+    }
+}
+
+impl TryFrom<&Vec<u8>> for UpdateMetadataListIntentData {
+    type Error = IntentError;
+
+    fn try_from(data: &Vec<u8>) -> Result<Self, Self::Error> {
+        bincode::deserialize(data).map_err(|e| IntentError::Generic(e.to_string()))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct UpdateGroupMembershipIntentData {
     pub membership_updates: HashMap<String, u64>,
@@ -359,6 +436,42 @@ impl TryFrom<&Vec<u8>> for UpdateGroupMembershipIntentData {
     }
 }
 
+/// Installations to remove and add back in a single commit, so they receive a fresh welcome.
+/// Members whose copy of the group forked are recovered this way.
+#[derive(Debug, Clone)]
+pub(crate) struct ReaddInstallationsIntentData {
+    pub installations: Vec<Vec<u8>>,
+}
+
+impl ReaddInstallationsIntentData {
+    pub fn new(installations: Vec<Vec<u8>>) -> Self {
+        Self { installations }
+    }
+}
+
+impl From<ReaddInstallationsIntentData> for Vec<u8> {
+    fn from(intent: ReaddInstallationsIntentData) -> Self {
+        let mut buf = Vec::new();
+
+        InstallationIds {
+            installation_ids: intent.installations,
+        }
+        .encode(&mut buf)
+        .expect("encode error");
+
+        buf
+    }
+}
+
+impl TryFrom<&Vec<u8>> for ReaddInstallationsIntentData {
+    type Error = IntentError;
+
+    fn try_from(data: &Vec<u8>) -> Result<Self, Self::Error> {
+        let installations = InstallationIds::decode(data.as_slice())?;
+        Ok(Self::new(installations.installation_ids))
+    }
+}
+
 #[repr(i32)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum AdminListActionType {
@@ -453,6 +566,7 @@ pub enum PermissionUpdateType {
     AddAdmin = 3,       // Matches ADD_ADMIN in Protobuf
     RemoveAdmin = 4,    // Matches REMOVE_ADMIN in Protobuf
     UpdateMetadata = 5, // Matches UPDATE_METADATA in Protobuf
+    SendMessage = 6,    // Local only, stored as a metadata policy
 }
 
 impl TryFrom<i32> for PermissionUpdateType {
@@ -465,6 +579,7 @@ impl TryFrom<i32> for PermissionUpdateType {
             3 => Ok(PermissionUpdateType::AddAdmin),
             4 => Ok(PermissionUpdateType::RemoveAdmin),
             5 => Ok(PermissionUpdateType::UpdateMetadata),
+            6 => Ok(PermissionUpdateType::SendMessage),
             _ => Err("Unknown value for PermissionUpdateType"),
         }
     }
@@ -0,0 +1,427 @@
+//! Joining groups through invite links. A link is a token signed by the installation that
+//! created it. Redeeming a link sends an [`InviteRedemption`] to the inviter in a DM, and the
+//! inviter adds the sender to the group the next time it syncs, as long as the link hasn't
+//! expired, been revoked or run out of uses. Links are synced to the inviter's other
+//! installations through the sync group when history sync is enabled, so any of them can admit
+//! members through the link, as long as the inviter may add members to the group.
+//!
+//! Installations admitting redemptions at the same time only learn of each other's uses once
+//! they sync, so a link can briefly admit more inboxes than its limit.
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::broadcast;
+use xmtp_common::{time::now_ns, RetryableError};
+use xmtp_content_types::{
+    encoded_content_to_bytes,
+    invite_redemption::{InviteRedemption, InviteRedemptionCodec},
+    CodecError, ContentCodec,
+};
+use xmtp_id::{
+    associations::{verify_signed_with_public_context, SignatureError},
+    scw_verifier::SmartContractSignatureVerifier,
+};
+
+use super::{
+    device_sync::preference_sync::UserPreferenceUpdate, GroupError, MlsGroup, ScopedGroupClient,
+};
+use crate::{
+    storage::{
+        db_connection::DbConnection,
+        group_message::StoredGroupMessage,
+        invite_link::{StoredInviteLink, StoredInviteRedemption},
+    },
+    subscriptions::LocalEvents,
+    Client, Fetch, Store, StoreOrIgnore, XmtpApi,
+};
+
+/// The token version written by [`MlsGroup::create_invite_link`]
+pub const INVITE_LINK_VERSION: u32 = 1;
+
+const INVITE_LINK_PREFIX: &str = "xmtp-invite:";
+
+#[derive(Debug, Error)]
+pub enum InviteError {
+    #[error("invite link is malformed")]
+    Malformed,
+    #[error("unsupported invite link version {0}")]
+    UnsupportedVersion(u32),
+    #[error("invite link has expired")]
+    Expired,
+    #[error("invite link was signed by an installation that isn't part of the inviter's inbox")]
+    UnknownInstallation,
+    #[error("invite link was not created by this inbox")]
+    UnknownLink,
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+}
+
+impl RetryableError for InviteError {
+    fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+/// The contents of an invite link, signed by the installation that created it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InviteLink {
+    pub version: u32,
+    pub group_id: Vec<u8>,
+    pub link_id: Vec<u8>,
+    pub inviter_inbox_id: String,
+    pub inviter_installation_id: Vec<u8>,
+    pub expires_at_ns: Option<i64>,
+    pub signature: Vec<u8>,
+}
+
+impl InviteLink {
+    /// Parse and verify the signature of a link from [`InviteLink::encode`]
+    pub fn decode(link: &str) -> Result<Self, InviteError> {
+        let bytes = link
+            .trim()
+            .strip_prefix(INVITE_LINK_PREFIX)
+            .and_then(|link| hex::decode(link).ok())
+            .ok_or(InviteError::Malformed)?;
+        let link: InviteLink = serde_json::from_slice(&bytes)?;
+        if link.version != INVITE_LINK_VERSION {
+            return Err(InviteError::UnsupportedVersion(link.version));
+        }
+        link.verify_signature()?;
+        Ok(link)
+    }
+
+    pub fn encode(&self) -> Result<String, InviteError> {
+        Ok(format!(
+            "{INVITE_LINK_PREFIX}{}",
+            hex::encode(serde_json::to_vec(self)?)
+        ))
+    }
+
+    fn signature_text(&self) -> String {
+        format!(
+            "XMTP : Group Invite\n\nVersion: {}\nGroup ID: {}\nLink ID: {}\nInbox ID: {}\nInstallation ID: {}\nExpires at: {}",
+            self.version,
+            hex::encode(&self.group_id),
+            hex::encode(&self.link_id),
+            self.inviter_inbox_id,
+            hex::encode(&self.inviter_installation_id),
+            self.expires_at_ns
+                .map(|expires_at_ns| expires_at_ns.to_string())
+                .unwrap_or_else(|| "never".to_string()),
+        )
+    }
+
+    fn verify_signature(&self) -> Result<(), InviteError> {
+        let signature: [u8; 64] = self
+            .signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| InviteError::Malformed)?;
+        let public_key: [u8; 32] = self
+            .inviter_installation_id
+            .as_slice()
+            .try_into()
+            .map_err(|_| InviteError::Malformed)?;
+        verify_signed_with_public_context(self.signature_text(), &signature, &public_key)?;
+        Ok(())
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Create a link others can use to join the group with [`Client::join_group_via_invite`].
+    /// The link stops working after `expiry`, or once `max_uses` inboxes joined through it.
+    pub fn create_invite_link(
+        &self,
+        expiry: Option<Duration>,
+        max_uses: Option<u32>,
+    ) -> Result<String, GroupError> {
+        let conn = self.context().store().conn()?;
+        let created_at_ns = now_ns();
+        let mut link = InviteLink {
+            version: INVITE_LINK_VERSION,
+            group_id: self.group_id.clone(),
+            link_id: xmtp_common::rand_array::<32>().to_vec(),
+            inviter_inbox_id: self.client.inbox_id().to_string(),
+            inviter_installation_id: self.client.installation_id().into(),
+            expires_at_ns: expiry
+                .map(|expiry| created_at_ns.saturating_add(expiry.as_nanos() as i64)),
+            signature: vec![],
+        };
+        link.signature = self
+            .context()
+            .identity
+            .sign_with_public_context(link.signature_text())?;
+
+        let stored = StoredInviteLink {
+            id: link.link_id.clone(),
+            group_id: self.group_id.clone(),
+            created_at_ns,
+            expires_at_ns: link.expires_at_ns,
+            max_uses: max_uses.map(|max_uses| max_uses.min(i32::MAX as u32) as i32),
+            uses: 0,
+            revoked_at_ns: None,
+        };
+        stored.store(&conn)?;
+        share_invite_link(
+            self.client.local_events(),
+            self.client.history_sync_url(),
+            stored,
+        );
+        Ok(link.encode()?)
+    }
+
+    /// The invite links the user created for the group, newest first
+    pub fn invite_links(&self) -> Result<Vec<StoredInviteLink>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn.get_invite_links(&self.group_id)?)
+    }
+
+    /// Stop admitting members through `link_id`. Redemptions that arrive afterwards are dropped.
+    pub fn revoke_invite_link(&self, link_id: &[u8]) -> Result<(), GroupError> {
+        let conn = self.context().store().conn()?;
+        let link: Option<StoredInviteLink> = conn.fetch(&link_id.to_vec())?;
+        if !link.is_some_and(|link| link.group_id == self.group_id) {
+            return Err(InviteError::UnknownLink.into());
+        }
+        if conn.revoke_invite_link(link_id, now_ns())? {
+            let link: Option<StoredInviteLink> = conn.fetch(&link_id.to_vec())?;
+            if let Some(link) = link {
+                share_invite_link(
+                    self.client.local_events(),
+                    self.client.history_sync_url(),
+                    link,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Queue the redemption in `message` for admission if it names a link the user created. A
+    /// malformed redemption is logged and ignored, it must not fail message processing.
+    pub(super) fn process_invite_redemption(
+        &self,
+        conn: &DbConnection,
+        message: &StoredGroupMessage,
+    ) {
+        if message.sender_inbox_id == self.client.inbox_id() {
+            return;
+        }
+        if let Err(e) = record_invite_redemption(conn, message) {
+            tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                message_id = hex::encode(&message.id),
+                "failed to record invite redemption: {e}"
+            );
+        }
+    }
+}
+
+fn record_invite_redemption(
+    conn: &DbConnection,
+    message: &StoredGroupMessage,
+) -> Result<(), GroupError> {
+    let content = message
+        .encoded_content()
+        .map_err(|e| CodecError::Decode(e.to_string()))?;
+    let redemption = InviteRedemptionCodec::decode(content)?;
+    let link_id =
+        hex::decode(&redemption.link_id).map_err(|e| CodecError::Decode(e.to_string()))?;
+
+    let link: Option<StoredInviteLink> = conn.fetch(&link_id)?;
+    if link.is_none() {
+        // Not a link of this inbox, or one that wasn't synced here
+        return Ok(());
+    }
+    StoredInviteRedemption {
+        message_id: message.id.clone(),
+        link_id,
+        inbox_id: message.sender_inbox_id.clone(),
+        received_at_ns: message.sent_at_ns,
+    }
+    .store_or_ignore(conn)?;
+    Ok(())
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Ask the inviter of `link` to add this inbox to the link's group. The group arrives as a
+    /// welcome once one of the inviter's installations admits the request, which requires it to
+    /// be online and the link to still be valid.
+    pub async fn join_group_via_invite(&self, link: &str) -> Result<(), GroupError> {
+        let link = InviteLink::decode(link)?;
+        if link
+            .expires_at_ns
+            .is_some_and(|expires_at_ns| expires_at_ns <= now_ns())
+        {
+            return Err(InviteError::Expired.into());
+        }
+
+        let conn = self.store().conn()?;
+        let association_state = self
+            .get_latest_association_state(&conn, &link.inviter_inbox_id)
+            .await?;
+        if !association_state
+            .installation_ids()
+            .contains(&link.inviter_installation_id)
+        {
+            return Err(InviteError::UnknownInstallation.into());
+        }
+
+        let dm = self
+            .find_or_create_dm_by_inbox_id(link.inviter_inbox_id.clone())
+            .await?;
+        let redemption = InviteRedemptionCodec::encode(InviteRedemption {
+            link_id: hex::encode(&link.link_id),
+        })?;
+        dm.send_message(&encoded_content_to_bytes(redemption))
+            .await?;
+        Ok(())
+    }
+
+    /// Add the senders of pending invite redemptions to the links' groups. Redemptions of links
+    /// that can no longer be redeemed are dropped, as are those from inboxes that are already
+    /// members, e.g. because another installation admitted them. A redemption that fails to be
+    /// admitted is logged and kept for the next sync if the failure is retryable, and dropped
+    /// otherwise, without holding up the others.
+    pub async fn admit_invite_redemptions(&self) -> Result<usize, GroupError> {
+        let conn = self.store().conn()?;
+        let mut admitted = 0;
+        for redemption in conn.get_pending_invite_redemptions()? {
+            match self.admit_invite_redemption(&conn, &redemption).await {
+                Ok(true) => admitted += 1,
+                Ok(false) => {}
+                Err(e) if e.is_retryable() => {
+                    tracing::warn!(
+                        message_id = hex::encode(&redemption.message_id),
+                        "failed to admit invite redemption, retrying on the next sync: {e}"
+                    );
+                    continue;
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        message_id = hex::encode(&redemption.message_id),
+                        "dropping invite redemption that can't be admitted: {e}"
+                    );
+                }
+            }
+            if let Err(e) = conn.delete_invite_redemption(&redemption.message_id) {
+                tracing::warn!(
+                    message_id = hex::encode(&redemption.message_id),
+                    "failed to delete invite redemption: {e}"
+                );
+            }
+        }
+        Ok(admitted)
+    }
+
+    /// Add the sender of `redemption` to the link's group. Returns whether it was added.
+    async fn admit_invite_redemption(
+        &self,
+        conn: &DbConnection,
+        redemption: &StoredInviteRedemption,
+    ) -> Result<bool, GroupError> {
+        let link: Option<StoredInviteLink> = conn.fetch(&redemption.link_id)?;
+        let Some(link) = link.filter(|link| link.is_redeemable(now_ns())) else {
+            return Ok(false);
+        };
+        let group = self.group_with_conn(conn, link.group_id.clone())?;
+        let is_member = group
+            .members()
+            .await?
+            .iter()
+            .any(|member| member.inbox_id == redemption.inbox_id);
+        if is_member {
+            return Ok(false);
+        }
+        group
+            .add_members_by_inbox_id(&[redemption.inbox_id.as_str()])
+            .await?;
+        conn.record_invite_link_use(&link.id)?;
+        let link: Option<StoredInviteLink> = conn.fetch(&link.id)?;
+        if let Some(link) = link {
+            share_invite_link(&self.local_events, &self.history_sync_url, link);
+        }
+        Ok(true)
+    }
+}
+
+/// Sync `link` to the user's other installations, so they can admit members through it
+fn share_invite_link<C>(
+    local_events: &broadcast::Sender<LocalEvents<C>>,
+    history_sync_url: &Option<String>,
+    link: StoredInviteLink,
+) {
+    if history_sync_url.is_some() {
+        // Dispatch an update event so it can be synced across devices
+        let _ = local_events.send(LocalEvents::OutgoingPreferenceUpdates(vec![
+            UserPreferenceUpdate::InviteLinkUpdate(link),
+        ]));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_join_group_via_invite() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let link = group.create_invite_link(None, Some(1)).unwrap();
+
+        bo.join_group_via_invite(&link).await.unwrap();
+        caro.join_group_via_invite(&link).await.unwrap();
+        alix.sync_all_welcomes_and_groups(&alix.mls_provider().unwrap(), None)
+            .await
+            .unwrap();
+
+        // the link only admits one inbox
+        let members = group.members().await.unwrap();
+        assert_eq!(members.len(), 2);
+        assert!(members
+            .iter()
+            .any(|member| member.inbox_id == bo.inbox_id()));
+        assert_eq!(group.invite_links().unwrap()[0].uses, 1);
+        assert!(alix
+            .store()
+            .conn()
+            .unwrap()
+            .get_pending_invite_redemptions()
+            .unwrap()
+            .is_empty());
+
+        bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        assert!(bo.group(group.group_id.clone()).is_ok());
+
+        let mut tampered = InviteLink::decode(&link).unwrap();
+        tampered.expires_at_ns = Some(i64::MAX);
+        assert!(matches!(
+            InviteLink::decode(&tampered.encode().unwrap()),
+            Err(InviteError::Signature(_))
+        ));
+
+        let link_id = group.invite_links().unwrap()[0].id.clone();
+        group.revoke_invite_link(&link_id).unwrap();
+        assert!(group.invite_links().unwrap()[0].revoked_at_ns.is_some());
+        assert!(matches!(
+            group.revoke_invite_link(b"unknown"),
+            Err(GroupError::Invite(InviteError::UnknownLink))
+        ));
+    }
+}
@@ -0,0 +1,282 @@
+//! Asking to join a group. A non-member sends a [`JoinRequest`] to each of the group's admins it
+//! knows of in a DM. The installations of a recipient that is an admin of the group store it as
+//! pending and publish a [`LocalEvents::JoinRequest`] event; requests sent to anyone else are
+//! dropped. Any of the admins can then approve the request, adding the sender to the group, or
+//! reject it. Senders aren't told about rejections.
+//!
+//! A member whose copy of the group forked sends a rejoin request instead, see
+//! [`fork_detection`](super::mls_sync::fork_detection). Approving it removes the member's
+//! installation and adds it back in the same commit, so it receives a fresh welcome.
+use thiserror::Error;
+use xmtp_common::RetryableError;
+use xmtp_content_types::{
+    encoded_content_to_bytes,
+    join_request::{ForkEvidence, JoinRequest, JoinRequestCodec},
+    CodecError, ContentCodec,
+};
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    storage::{
+        group::{GroupMembershipState, StoredGroup},
+        group_message::StoredGroupMessage,
+        join_request::StoredJoinRequest,
+        xmtp_openmls_provider::XmtpOpenMlsProvider,
+    },
+    subscriptions::{LocalEvents, PendingJoinRequest},
+    Client, Fetch, StoreOrIgnore, XmtpApi,
+};
+
+#[derive(Debug, Error)]
+pub enum JoinRequestError {
+    #[error("no pending join request {0}")]
+    NotFound(String),
+}
+
+impl RetryableError for JoinRequestError {
+    fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// The pending requests to join the group received by this inbox, oldest first
+    pub fn join_requests(&self) -> Result<Vec<PendingJoinRequest>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn
+            .get_join_requests(&self.group_id)?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Add the sender of `request_id` to the group. Requires permission to add members. A rejoin
+    /// request from a member removes the installation that sent it and adds it back in one
+    /// commit instead, which requires being an admin. A request from an inbox that's already a
+    /// member, e.g. because another admin approved it, is only resolved. Any other pending
+    /// requests from the same inbox are resolved as well.
+    pub async fn approve_join_request(&self, request_id: &[u8]) -> Result<(), GroupError> {
+        let request = self.pending_join_request(request_id)?;
+        let conn = self.context().store().conn()?;
+        let is_member = self
+            .members()
+            .await?
+            .iter()
+            .any(|member| member.inbox_id == request.inbox_id);
+        if request.rejoin && is_member {
+            let installation_id = conn
+                .get_group_message(&request.message_id)?
+                .map(|message| message.sender_installation_id)
+                .ok_or_else(|| JoinRequestError::NotFound(hex::encode(request_id)))?;
+            self.readd_installations(vec![installation_id]).await?;
+        } else if !is_member {
+            self.add_members_by_inbox_id(&[request.inbox_id.as_str()])
+                .await?;
+        }
+
+        conn.delete_join_requests(&self.group_id, &request.inbox_id)?;
+        Ok(())
+    }
+
+    /// Drop `request_id` and any other pending requests from the same inbox
+    pub fn reject_join_request(&self, request_id: &[u8]) -> Result<(), GroupError> {
+        let request = self.pending_join_request(request_id)?;
+        let conn = self.context().store().conn()?;
+        conn.delete_join_requests(&self.group_id, &request.inbox_id)?;
+        Ok(())
+    }
+
+    fn pending_join_request(&self, request_id: &[u8]) -> Result<StoredJoinRequest, GroupError> {
+        let conn = self.context().store().conn()?;
+        let request: Option<StoredJoinRequest> = conn.fetch(&request_id.to_vec())?;
+        request
+            .filter(|request| request.group_id == self.group_id)
+            .ok_or_else(|| JoinRequestError::NotFound(hex::encode(request_id)).into())
+    }
+
+    /// Store the join request in `message` and publish a [`LocalEvents::JoinRequest`] event if it
+    /// names a group this inbox is an admin of. A malformed request is logged and ignored, it
+    /// must not fail message processing.
+    pub(super) fn process_join_request(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        message: &StoredGroupMessage,
+    ) {
+        if message.sender_inbox_id == self.client.inbox_id() {
+            return;
+        }
+        match self.record_join_request(provider, message) {
+            Ok(Some(request)) => self.publish_after_commit(
+                provider.conn_ref(),
+                LocalEvents::JoinRequest(request.into()),
+            ),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                message_id = hex::encode(&message.id),
+                "failed to record join request: {e}"
+            ),
+        }
+    }
+
+    fn record_join_request(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        message: &StoredGroupMessage,
+    ) -> Result<Option<StoredJoinRequest>, GroupError> {
+        let conn = provider.conn_ref();
+        let content = message
+            .encoded_content()
+            .map_err(|e| CodecError::Decode(e.to_string()))?;
+        let request = JoinRequestCodec::decode(content)?;
+        let group_id =
+            hex::decode(&request.group_id).map_err(|e| CodecError::Decode(e.to_string()))?;
+
+        let group: Option<StoredGroup> = conn.fetch(&group_id)?;
+        let Some(group) =
+            group.filter(|group| group.membership_state == GroupMembershipState::Allowed)
+        else {
+            return Ok(None);
+        };
+        let group = MlsGroup::new_from_arc(self.client.clone(), group.id, group.created_at_ns);
+        let inbox_id = self.client.inbox_id().to_string();
+        if !group.is_admin(inbox_id.clone(), provider)?
+            && !group.is_super_admin(inbox_id, provider)?
+        {
+            tracing::warn!(
+                group_id = hex::encode(&group.group_id),
+                sender_inbox_id = message.sender_inbox_id,
+                "dropping join request, this inbox isn't an admin of the group"
+            );
+            return Ok(None);
+        }
+
+        let request = StoredJoinRequest {
+            message_id: message.id.clone(),
+            group_id: group.group_id,
+            inbox_id: message.sender_inbox_id.clone(),
+            note: request.note,
+            requested_at_ns: message.sent_at_ns,
+            rejoin: request.rejoin,
+        };
+        request.store_or_ignore(conn)?;
+        Ok(Some(request))
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Ask each of `admin_inbox_ids` to add this inbox to `group_id`, with an optional `note`
+    /// for the admins. Any of them can approve the request, and the group then arrives as a
+    /// welcome. Returns the id of the request sent to each admin, in order.
+    pub async fn request_to_join<S: AsRef<str>>(
+        &self,
+        group_id: &[u8],
+        admin_inbox_ids: &[S],
+        note: Option<String>,
+    ) -> Result<Vec<Vec<u8>>, GroupError> {
+        self.send_join_request(group_id, admin_inbox_ids, note, None)
+            .await
+    }
+
+    /// Send a join request, or with `fork_evidence`, a rejoin request, to each admin
+    pub(crate) async fn send_join_request<S: AsRef<str>>(
+        &self,
+        group_id: &[u8],
+        admin_inbox_ids: &[S],
+        note: Option<String>,
+        fork_evidence: Option<ForkEvidence>,
+    ) -> Result<Vec<Vec<u8>>, GroupError> {
+        let request = encoded_content_to_bytes(JoinRequestCodec::encode(JoinRequest {
+            group_id: hex::encode(group_id),
+            note,
+            rejoin: fork_evidence.is_some(),
+            fork_evidence,
+        })?);
+        let mut request_ids = Vec::with_capacity(admin_inbox_ids.len());
+        for admin_inbox_id in admin_inbox_ids {
+            let dm = self
+                .find_or_create_dm_by_inbox_id(admin_inbox_id.as_ref().to_string())
+                .await?;
+            request_ids.push(dm.send_message(&request).await?);
+        }
+        Ok(request_ids)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_approve_join_request() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+
+        let requests = group.stream_join_requests();
+        futures::pin_mut!(requests);
+        let request_id = bo
+            .request_to_join(&group.group_id, &[alix.inbox_id()], Some("hi".to_string()))
+            .await
+            .unwrap()
+            .remove(0);
+        caro.request_to_join(&group.group_id, &[alix.inbox_id()], None)
+            .await
+            .unwrap();
+        alix.sync_all_welcomes_and_groups(&alix.mls_provider().unwrap(), None)
+            .await
+            .unwrap();
+
+        let pending = group.join_requests().unwrap();
+        assert_eq!(pending.len(), 2);
+        assert!(pending.contains(&requests.next().await.unwrap().unwrap()));
+        assert_eq!(pending[0].request_id, request_id);
+        assert_eq!(pending[0].inbox_id, bo.inbox_id());
+        assert_eq!(pending[0].note.as_deref(), Some("hi"));
+
+        group.approve_join_request(&request_id).await.unwrap();
+        group.reject_join_request(&pending[1].request_id).unwrap();
+        assert!(group.join_requests().unwrap().is_empty());
+        let members = group.members().await.unwrap();
+        assert_eq!(members.len(), 2);
+        assert!(members
+            .iter()
+            .any(|member| member.inbox_id == bo.inbox_id()));
+        assert!(matches!(
+            group.approve_join_request(&request_id).await,
+            Err(GroupError::JoinRequest(JoinRequestError::NotFound(_)))
+        ));
+
+        bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        let bo_group = bo.group(group.group_id.clone()).unwrap();
+
+        // only admins keep the requests they receive
+        let dave = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        dave.request_to_join(&group.group_id, &[alix.inbox_id(), bo.inbox_id()], None)
+            .await
+            .unwrap();
+        bo.sync_all_welcomes_and_groups(&bo.mls_provider().unwrap(), None)
+            .await
+            .unwrap();
+        assert!(bo_group.join_requests().unwrap().is_empty());
+        alix.sync_all_welcomes_and_groups(&alix.mls_provider().unwrap(), None)
+            .await
+            .unwrap();
+        assert_eq!(group.join_requests().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,240 @@
+//! Policy-driven key rotation. A group's admins can cap how long the group stays in an epoch, in
+//! time or in messages sent to the group since its last commit. Once either limit is reached,
+//! the installation commits a key update, so that a compromised key exposes as little of a
+//! long-lived or long-quiet group as possible. The policy lives in the group's mutable metadata,
+//! so every member applies the same one.
+//!
+//! Due rotations are issued by [`Client::rotate_due_keys`], which apps run periodically or
+//! through [`Client::start_key_rotation_worker`].
+use std::{sync::Arc, time::Duration};
+
+use xmtp_common::time::now_ns;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use super::{
+    group_mutable_metadata::{GroupMutableMetadata, MetadataField},
+    intents::UpdateMetadataIntentData,
+    GroupError, MlsGroup, ScopedGroupClient,
+};
+use crate::{
+    client::ClientError,
+    configuration::NS_IN_MS,
+    storage::{
+        group::GroupQueryArgs,
+        group_intent::IntentKind,
+        group_message::{GroupMessageKind, MsgQueryArgs},
+    },
+    CancellationToken, Client, StreamMetrics, XmtpApi, XmtpOpenMlsProvider,
+};
+
+/// When members of a group rotate their keys
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyRotationPolicy {
+    /// Rotate once the group has been in its epoch for longer than this
+    pub max_epoch_age_ns: Option<i64>,
+    /// Rotate once this many messages were sent to the group in its epoch
+    pub max_messages: Option<u64>,
+}
+
+impl KeyRotationPolicy {
+    /// The policy in `metadata`, or `None` if keys in the group aren't rotated on a schedule.
+    /// Metadata stores both limits in one field, so they change in a single commit, as the age
+    /// in milliseconds and the message count separated by a comma, with zero for a limit that
+    /// isn't set.
+    pub fn from_metadata(metadata: &GroupMutableMetadata) -> Option<Self> {
+        let value = metadata
+            .attributes
+            .get(MetadataField::KeyRotationPolicy.as_str())?;
+        let (max_age_ms, max_messages) = value.split_once(',')?;
+        let limit = |value: &str| value.parse().ok().filter(|value: &i64| *value > 0);
+        let policy = Self {
+            max_epoch_age_ns: limit(max_age_ms).map(|ms| ms.saturating_mul(NS_IN_MS)),
+            max_messages: limit(max_messages).map(|count| count as u64),
+        };
+        (policy != Self::default()).then_some(policy)
+    }
+
+    /// The policy as stored in metadata, see [`Self::from_metadata`]
+    fn to_metadata_value(self) -> String {
+        format!(
+            "{},{}",
+            self.max_epoch_age_ns.unwrap_or_default() / NS_IN_MS,
+            self.max_messages.unwrap_or_default()
+        )
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Make members rotate their keys according to `policy`. Only admins can change it.
+    pub async fn update_key_rotation_policy(
+        &self,
+        policy: KeyRotationPolicy,
+    ) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        let intent_data: Vec<u8> = UpdateMetadataIntentData::new(
+            MetadataField::KeyRotationPolicy.to_string(),
+            policy.to_metadata_value(),
+        )
+        .into();
+        let intent = self.queue_intent(&provider, IntentKind::MetadataUpdate, intent_data)?;
+
+        self.sync_until_intent_resolved(&provider, intent.id).await
+    }
+
+    /// The group's key rotation policy as of its last sync
+    pub fn key_rotation_policy(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<Option<KeyRotationPolicy>, GroupError> {
+        Ok(KeyRotationPolicy::from_metadata(
+            &self.mutable_metadata(provider)?,
+        ))
+    }
+
+    /// Rotate this installation's key in the group right away, whatever the policy
+    pub async fn rotate_keys_now(&self) -> Result<(), GroupError> {
+        self.key_update().await
+    }
+
+    /// Whether the group's policy calls for this installation to rotate its key, because the
+    /// group has been in its epoch for too long
+    pub fn key_rotation_due(&self, provider: &XmtpOpenMlsProvider) -> Result<bool, GroupError> {
+        let Some(policy) = self.key_rotation_policy(provider)? else {
+            return Ok(false);
+        };
+        if !self.is_active(provider)? {
+            return Ok(false);
+        }
+        let conn = provider.conn_ref();
+        // groups without a commit since this installation joined them were keyed when it joined
+        let epoch_changed_at_ns = conn
+            .get_epoch_changed_at_ns(&self.group_id)?
+            .unwrap_or(self.created_at_ns);
+
+        if policy
+            .max_epoch_age_ns
+            .is_some_and(|max_age| now_ns().saturating_sub(epoch_changed_at_ns) >= max_age)
+        {
+            return Ok(true);
+        }
+        let Some(max_messages) = policy.max_messages else {
+            return Ok(false);
+        };
+        let sent = conn.count_group_messages(
+            &self.group_id,
+            &MsgQueryArgs {
+                sent_after_ns: Some(epoch_changed_at_ns),
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            },
+        )?;
+        Ok(sent as u64 >= max_messages)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Rotate this installation's key in every group whose policy calls for it.
+    /// Returns the number of groups rotated.
+    pub async fn rotate_due_keys(&self) -> Result<usize, ClientError> {
+        let provider = self.mls_provider()?;
+        let mut rotated = 0;
+        for group in self.find_groups(GroupQueryArgs::default())? {
+            let result = match group.key_rotation_due(&provider) {
+                Ok(true) => group.rotate_keys_now().await.map(|_| true),
+                other => other,
+            };
+            match result {
+                Ok(true) => rotated += 1,
+                Ok(false) => {}
+                Err(e) => tracing::warn!(
+                    group_id = hex::encode(&group.group_id),
+                    "failed to rotate group keys: {e}"
+                ),
+            }
+        }
+        if rotated > 0 {
+            tracing::info!(rotated, "rotated group keys");
+        }
+        Ok(rotated)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Run [`Client::rotate_due_keys`] every `interval` until the handle is closed
+    pub fn start_key_rotation_worker(
+        client: Arc<Client<ApiClient, V>>,
+        interval: Duration,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
+        let cancel = CancellationToken::new();
+        let stopped = cancel.clone();
+
+        crate::spawn_cancellable(None, StreamMetrics::default(), cancel, async move {
+            while !stopped.is_cancelled() {
+                if let Err(e) = client.rotate_due_keys().await {
+                    tracing::warn!("key rotation run failed: {e}");
+                }
+                let _ = xmtp_common::time::timeout(interval, stopped.cancelled()).await;
+            }
+            Ok::<_, ClientError>(())
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_keys_rotate_after_max_messages() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let provider = alix.mls_provider().unwrap();
+        let epoch = || {
+            group
+                .load_mls_group_with_lock(&provider, |mls_group| Ok(mls_group.epoch().as_u64()))
+                .unwrap()
+        };
+        assert_eq!(group.key_rotation_policy(&provider).unwrap(), None);
+        assert!(!group.key_rotation_due(&provider).unwrap());
+
+        let policy = KeyRotationPolicy {
+            max_epoch_age_ns: None,
+            max_messages: Some(2),
+        };
+        let before = epoch();
+        group.update_key_rotation_policy(policy).await.unwrap();
+        assert_eq!(group.key_rotation_policy(&provider).unwrap(), Some(policy));
+        // both limits change in a single commit
+        assert_eq!(epoch(), before + 1);
+
+        group.send_message(b"one").await.unwrap();
+        assert!(!group.key_rotation_due(&provider).unwrap());
+        group.send_message(b"two").await.unwrap();
+        assert!(group.key_rotation_due(&provider).unwrap());
+
+        let before = epoch();
+        assert_eq!(alix.rotate_due_keys().await.unwrap(), 1);
+        assert_eq!(epoch(), before + 1);
+        assert!(!group.key_rotation_due(&provider).unwrap());
+        assert_eq!(alix.rotate_due_keys().await.unwrap(), 0);
+
+        group.rotate_keys_now().await.unwrap();
+        assert_eq!(epoch(), before + 2);
+    }
+}
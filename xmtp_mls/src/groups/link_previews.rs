@@ -0,0 +1,294 @@
+//! Previews of links in outgoing text messages.
+//!
+//! This is optional: the page metadata is fetched by a [`LinkPreviewFetcher`] the app supplies,
+//! which decides how pages are fetched, e.g. through a proxy so the sender's IP address isn't
+//! revealed to the linked site, or not at all. The preview image is encrypted and uploaded like
+//! a remote attachment, so the preview reveals nothing the text doesn't. Previews are best
+//! effort: if fetching fails or takes longer than [`LINK_PREVIEW_FETCH_TIMEOUT`], the text is
+//! sent without one. Received previews of a link that isn't in the text are rejected, and the
+//! message shows as its text.
+use thiserror::Error;
+use xmtp_common::{
+    retry::RetryableError,
+    time::{timeout, Duration},
+};
+use xmtp_content_types::{
+    attachment::Attachment,
+    link_preview::{LinkPreview, TextWithLinkPreview},
+    remote_attachment::RemoteAttachment,
+};
+
+use super::{
+    attachments::{encrypt_attachment, AttachmentUploadOptions, AttachmentUploader},
+    GroupError, MlsGroup, ScopedGroupClient,
+};
+
+#[derive(Debug, Error)]
+pub enum LinkPreviewError {
+    #[error("fetching the link preview failed: {message}")]
+    Fetch { message: String, retryable: bool },
+}
+
+impl RetryableError for LinkPreviewError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            Self::Fetch { retryable, .. } => *retryable,
+        }
+    }
+}
+
+/// How long a [`LinkPreviewFetcher`] gets before the text is sent without a preview
+pub const LINK_PREVIEW_FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Metadata of a linked page, as found by a [`LinkPreviewFetcher`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    /// The preview image, downloaded by the fetcher. It's encrypted before upload.
+    pub image: Option<Attachment>,
+}
+
+/// Fetches the metadata of linked pages, e.g. their Open Graph tags
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+pub trait LinkPreviewFetcher {
+    async fn fetch(&self, url: &str) -> Result<LinkMetadata, LinkPreviewError>;
+}
+
+/// The first http(s) link in `text`, without trailing punctuation
+fn first_link(text: &str) -> Option<&str> {
+    text.split_whitespace()
+        .map(|word| word.trim_start_matches(['(', '<', '"', '\'']))
+        .filter(|word| word.starts_with("https://") || word.starts_with("http://"))
+        .map(|word| word.trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '>', '"', '\'']))
+        .find(|link| !link.ends_with("://"))
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Send `text` with a preview of the first link in it, fetched with `fetcher`. The preview
+    /// image is encrypted and uploaded with `uploader`, and left out without one. Text without
+    /// links, or whose preview can't be fetched in time, is sent as a plain text message.
+    pub async fn send_text_with_link_preview<F, U>(
+        &self,
+        text: &str,
+        fetcher: &F,
+        uploader: Option<&U>,
+    ) -> Result<Vec<u8>, GroupError>
+    where
+        F: LinkPreviewFetcher + ?Sized,
+        U: AttachmentUploader + ?Sized,
+    {
+        let Some(url) = first_link(text) else {
+            return self.send_content(text).await;
+        };
+        let metadata = match timeout(LINK_PREVIEW_FETCH_TIMEOUT, fetcher.fetch(url)).await {
+            Ok(Ok(metadata)) => metadata,
+            Ok(Err(e)) => {
+                tracing::warn!("sending without a link preview, fetching it failed: {e}");
+                return self.send_content(text).await;
+            }
+            Err(_) => {
+                tracing::warn!(
+                    "sending without a link preview, fetching it took longer than {:?}",
+                    LINK_PREVIEW_FETCH_TIMEOUT
+                );
+                return self.send_content(text).await;
+            }
+        };
+
+        let image = match metadata.image.zip(uploader) {
+            Some((image, uploader)) => match self.upload_preview_image(uploader, image).await {
+                Ok(image) => Some(image),
+                Err(e) => {
+                    tracing::warn!("sending the link preview without its image: {e}");
+                    None
+                }
+            },
+            None => None,
+        };
+        let content = TextWithLinkPreview {
+            text: text.to_string(),
+            preview: LinkPreview {
+                url: url.to_string(),
+                title: metadata.title,
+                description: metadata.description,
+                site_name: metadata.site_name,
+                image,
+            },
+        };
+        self.send_content(content).await
+    }
+
+    async fn upload_preview_image<U>(
+        &self,
+        uploader: &U,
+        image: Attachment,
+    ) -> Result<RemoteAttachment, GroupError>
+    where
+        U: AttachmentUploader + ?Sized,
+    {
+        let encrypted = encrypt_attachment(image)?;
+        let url = self
+            .upload_attachment(
+                uploader,
+                &encrypted.payload,
+                &AttachmentUploadOptions::default(),
+            )
+            .await?;
+        Ok(encrypted.remote_attachment(&url))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        decoded_message::DecodedContent,
+        groups::{attachments::AttachmentError, GroupMetadataOptions},
+        storage::group_message::{GroupMessageKind, MsgQueryArgs},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    /// Fetcher that knows a single page, and records what it was asked for
+    #[derive(Default)]
+    struct StaticFetcher {
+        fetched: Mutex<Vec<String>>,
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    impl LinkPreviewFetcher for StaticFetcher {
+        async fn fetch(&self, url: &str) -> Result<LinkMetadata, LinkPreviewError> {
+            self.fetched.lock().unwrap().push(url.to_string());
+            if url == "https://example.com/slow" {
+                futures::future::pending::<()>().await;
+            }
+            if url != "https://example.com/cats" {
+                return Err(LinkPreviewError::Fetch {
+                    message: "not found".to_string(),
+                    retryable: false,
+                });
+            }
+            Ok(LinkMetadata {
+                title: Some("Cats".to_string()),
+                site_name: Some("Example".to_string()),
+                image: Some(Attachment {
+                    filename: "cats.png".to_string(),
+                    mime_type: "image/png".to_string(),
+                    data: vec![7u8; 20],
+                }),
+                ..Default::default()
+            })
+        }
+    }
+
+    /// Uploader that keeps the uploaded payload in memory
+    #[derive(Default)]
+    struct MemoryUploader {
+        uploaded: Mutex<Vec<u8>>,
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    impl AttachmentUploader for MemoryUploader {
+        async fn start_upload(&self, _total_size: u64) -> Result<String, AttachmentError> {
+            Ok("session".to_string())
+        }
+
+        async fn upload_chunk(
+            &self,
+            _session: &str,
+            _offset: u64,
+            chunk: &[u8],
+        ) -> Result<(), AttachmentError> {
+            self.uploaded.lock().unwrap().extend_from_slice(chunk);
+            Ok(())
+        }
+
+        async fn complete_upload(&self, _session: &str) -> Result<String, AttachmentError> {
+            Ok("https://cdn.example.com/preview".to_string())
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_send_text_with_link_preview() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+
+        let fetcher = StaticFetcher::default();
+        let uploader = MemoryUploader::default();
+        for text in [
+            "look (https://example.com/cats).",
+            "no links here",
+            "broken https://example.com/missing",
+            "slow https://example.com/slow",
+        ] {
+            group
+                .send_text_with_link_preview(text, &fetcher, Some(&uploader))
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            *fetcher.fetched.lock().unwrap(),
+            vec![
+                "https://example.com/cats",
+                "https://example.com/missing",
+                "https://example.com/slow"
+            ]
+        );
+        // the image is uploaded encrypted
+        let uploaded = uploader.uploaded.lock().unwrap().clone();
+        assert!(!uploaded.is_empty());
+        assert_ne!(uploaded, vec![7u8; 20]);
+
+        bo_group.sync().await.unwrap();
+        let mut received = bo_group
+            .find_decoded_messages(&MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            })
+            .unwrap()
+            .into_iter()
+            .map(|message| message.content);
+        let Some(DecodedContent::LinkPreview(with_preview)) = received.next() else {
+            panic!("link preview wasn't decoded");
+        };
+        assert_eq!(with_preview.text, "look (https://example.com/cats).");
+        assert_eq!(with_preview.preview.url, "https://example.com/cats");
+        assert_eq!(with_preview.preview.title.as_deref(), Some("Cats"));
+        let image = with_preview.preview.image.unwrap();
+        assert_eq!(image.url, "https://cdn.example.com/preview");
+        assert_eq!(image.content_length, Some(uploaded.len() as u64));
+        // messages without a preview are sent as text
+        assert_eq!(
+            received.collect::<Vec<_>>(),
+            vec![
+                DecodedContent::Text("no links here".to_string()),
+                DecodedContent::Text("broken https://example.com/missing".to_string()),
+                DecodedContent::Text("slow https://example.com/slow".to_string()),
+            ]
+        );
+    }
+}
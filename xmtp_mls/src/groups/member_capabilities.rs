@@ -0,0 +1,201 @@
+//! Members advertise the content types and features their installation supports with an
+//! application message, so senders can avoid sending content some members can't render.
+use prost::Message;
+use xmtp_common::time::now_ns;
+use xmtp_content_types::{
+    capabilities::{content_type_key, Capabilities, CapabilitiesCodec},
+    encoded_content_to_bytes, CodecError, ContentCodec,
+};
+use xmtp_proto::xmtp::mls::message_contents::{ContentTypeId, EncodedContent};
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::storage::{
+    group_message::StoredGroupMessage, installation_capability::StoredInstallationCapabilities,
+    DbConnection,
+};
+
+/// The capabilities shared by every installation in a conversation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommonCapabilities {
+    /// Supported by every installation that has advertised its capabilities
+    pub capabilities: Capabilities,
+    /// Installations that have not advertised yet. Nothing is known about what they support.
+    pub unknown_installations: Vec<Vec<u8>>,
+}
+
+impl CommonCapabilities {
+    pub fn supports_content_type(&self, content_type: &ContentTypeId) -> bool {
+        self.capabilities
+            .content_types
+            .contains(&content_type_key(content_type))
+    }
+
+    pub fn supports_feature(&self, feature: &str) -> bool {
+        self.capabilities.features.iter().any(|f| f == feature)
+    }
+
+    /// Whether every installation in the conversation has advertised its capabilities
+    pub fn is_complete(&self) -> bool {
+        self.unknown_installations.is_empty()
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Tell the other members which content types and features this installation supports.
+    /// A later advertisement replaces an earlier one.
+    pub async fn advertise_capabilities(
+        &self,
+        capabilities: Capabilities,
+    ) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        // Our own messages are never processed as incoming, so record our capabilities directly
+        provider.conn_ref().upsert_installation_capabilities(
+            &StoredInstallationCapabilities::new(
+                self.context().installation_public_key().into(),
+                self.context().inbox_id().to_string(),
+                &capabilities,
+                now_ns(),
+            )?,
+        )?;
+
+        let encoded = encoded_content_to_bytes(CapabilitiesCodec::encode(capabilities)?);
+        self.send_message_with_provider(&encoded, &provider).await?;
+        Ok(())
+    }
+
+    /// The content types and features supported by every installation of every member,
+    /// based on the most recent advertisement from each installation.
+    pub async fn common_capabilities(&self) -> Result<CommonCapabilities, GroupError> {
+        let provider = self.mls_provider()?;
+        let installation_ids: Vec<Vec<u8>> = self
+            .members_with_provider(&provider)
+            .await?
+            .into_iter()
+            .flat_map(|m| m.installation_ids)
+            .collect();
+        let mut advertised = provider
+            .conn_ref()
+            .get_installation_capabilities_for(&installation_ids)?;
+
+        let mut common: Option<Capabilities> = None;
+        let mut unknown_installations = vec![];
+        for installation_id in installation_ids {
+            let Some(stored) = advertised.remove(&installation_id) else {
+                unknown_installations.push(installation_id);
+                continue;
+            };
+            let capabilities = stored.capabilities()?;
+            common = Some(match common {
+                None => capabilities,
+                Some(mut common) => {
+                    common
+                        .content_types
+                        .retain(|c| capabilities.content_types.contains(c));
+                    common
+                        .features
+                        .retain(|f| capabilities.features.contains(f));
+                    common
+                }
+            });
+        }
+
+        Ok(CommonCapabilities {
+            capabilities: common.unwrap_or_default(),
+            unknown_installations,
+        })
+    }
+
+    /// Record the capabilities advertised in `message`. A malformed advertisement is logged and
+    /// ignored, it must not fail message processing.
+    pub(super) fn process_capabilities_advertisement(
+        &self,
+        conn: &DbConnection,
+        message: &StoredGroupMessage,
+    ) {
+        if let Err(e) = record_advertisement(conn, message) {
+            tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                sender_installation_id = hex::encode(&message.sender_installation_id),
+                "failed to record advertised capabilities: {e}"
+            );
+        }
+    }
+}
+
+fn record_advertisement(
+    conn: &DbConnection,
+    message: &StoredGroupMessage,
+) -> Result<(), GroupError> {
+    let content = EncodedContent::decode(message.decrypted_message_bytes.as_slice())
+        .map_err(|e| CodecError::Decode(e.to_string()))?;
+    let capabilities = CapabilitiesCodec::decode(content)?;
+    conn.upsert_installation_capabilities(&StoredInstallationCapabilities::new(
+        message.sender_installation_id.clone(),
+        message.sender_inbox_id.clone(),
+        &capabilities,
+        message.sent_at_ns,
+    )?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::{reaction::ReactionCodec, text::TextCodec};
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_common_capabilities() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+
+        let text = content_type_key(&TextCodec::content_type());
+        let reaction = content_type_key(&ReactionCodec::content_type());
+        alix_group
+            .advertise_capabilities(Capabilities {
+                content_types: vec![text.clone(), reaction],
+                features: vec!["polls".to_string()],
+            })
+            .await
+            .unwrap();
+
+        // bo hasn't advertised yet
+        let common = alix_group.common_capabilities().await.unwrap();
+        assert_eq!(common.unknown_installations.len(), 1);
+        assert!(common.supports_content_type(&ReactionCodec::content_type()));
+
+        bo_group
+            .advertise_capabilities(Capabilities {
+                content_types: vec![text.clone()],
+                features: vec!["polls".to_string()],
+            })
+            .await
+            .unwrap();
+        alix_group.sync().await.unwrap();
+
+        let common = alix_group.common_capabilities().await.unwrap();
+        assert!(common.is_complete());
+        assert_eq!(common.capabilities.content_types, vec![text]);
+        assert!(!common.supports_content_type(&ReactionCodec::content_type()));
+        assert!(common.supports_feature("polls"));
+    }
+}
@@ -5,6 +5,7 @@ use super::{validated_commit::extract_group_membership, GroupError, MlsGroup, Sc
 use crate::storage::{
     association_state::StoredAssociationState,
     consent_record::{ConsentState, ConsentType},
+    group_membership_change::MembershipChangeKind,
     xmtp_openmls_provider::XmtpOpenMlsProvider,
 };
 
@@ -24,6 +25,15 @@ pub enum PermissionLevel {
     SuperAdmin,
 }
 
+/// Inboxes that joined or left a group, for incrementally updating a cached member list
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MembersDelta {
+    /// The epoch the delta is current to. Pass it to the next call to get the changes after it.
+    pub epoch: u64,
+    pub added: Vec<InboxId>,
+    pub removed: Vec<InboxId>,
+}
+
 impl<ScopedClient> MlsGroup<ScopedClient>
 where
     ScopedClient: ScopedGroupClient,
@@ -115,4 +125,36 @@ where
 
         Ok(members)
     }
+    /// The inboxes that joined or left the group in epochs after `since_epoch`.
+    /// With no `since_epoch`, every current member is returned as added.
+    pub fn members_delta(&self, since_epoch: Option<u64>) -> Result<MembersDelta, GroupError> {
+        let provider = self.mls_provider()?;
+        let conn = provider.conn_ref();
+        let (epoch, membership) = self.load_mls_group_with_lock(&provider, |mls_group| {
+            Ok((
+                mls_group.epoch().as_u64(),
+                extract_group_membership(mls_group.extensions())?,
+            ))
+        })?;
+
+        // Groups joined before membership changes were recorded start from a snapshot
+        if !conn.has_membership_changes(&self.group_id)? {
+            conn.record_membership_changes(
+                &self.group_id,
+                epoch as i64,
+                membership
+                    .inbox_ids()
+                    .into_iter()
+                    .map(|inbox_id| (inbox_id.to_string(), MembershipChangeKind::Added)),
+            )?;
+        }
+
+        let since_epoch = since_epoch.map_or(-1, |epoch| epoch as i64);
+        let delta = conn.get_membership_delta(&self.group_id, since_epoch)?;
+        Ok(MembersDelta {
+            epoch,
+            added: delta.added,
+            removed: delta.removed,
+        })
+    }
 }
@@ -0,0 +1,268 @@
+//! A hook that lets apps gate group membership on external state, e.g. requiring every member
+//! to hold a token, checked through the same RPC the smart contract wallet verifier uses.
+//!
+//! The policy is part of the [commit hook](super::commit_validator::CommitHook), so it only gates
+//! the members added by commits this installation publishes. Commits and welcomes from other
+//! members are never checked: every member must reach the same decision on a commit to stay in
+//! the same epoch, and the external state may differ between members or over time, so rejecting
+//! them would fork the group. Decisions are cached per inbox so that repeated adds don't hit the
+//! external source every time.
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::{Mutex, RwLock};
+use thiserror::Error;
+use xmtp_common::{
+    time::{Duration, Instant},
+    RetryableError,
+};
+
+use crate::Client;
+
+/// How long a decision is cached when no TTL is given
+pub const DEFAULT_MEMBERSHIP_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+pub type MembershipPolicyHookError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Error)]
+pub enum MembershipPolicyError {
+    #[error("inbox {0} is not allowed in the group by the membership policy")]
+    Rejected(String),
+    #[error("membership policy check for inbox {inbox_id} failed: {err}")]
+    Check { inbox_id: String, err: String },
+}
+
+impl RetryableError for MembershipPolicyError {
+    fn is_retryable(&self) -> bool {
+        // the external state may be temporarily unreachable; a rejection is final
+        matches!(self, Self::Check { .. })
+    }
+}
+
+/// Decides whether this installation may add an inbox to the client's groups.
+///
+/// Only regular groups are gated: DMs and device sync groups are never checked.
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+pub trait MembershipPolicy: Send + Sync {
+    async fn allows(&self, inbox_id: &str) -> Result<bool, MembershipPolicyHookError>;
+}
+
+struct CachedDecision {
+    allowed: bool,
+    checked_at: Instant,
+}
+
+/// The policy registered on a client, along with its cached decisions
+#[derive(Default)]
+pub struct MembershipPolicyHook {
+    policy: RwLock<Option<(Arc<dyn MembershipPolicy>, Duration)>>,
+    cache: Mutex<HashMap<String, CachedDecision>>,
+}
+
+impl MembershipPolicyHook {
+    pub fn set(&self, policy: Arc<dyn MembershipPolicy>, cache_ttl: Duration) {
+        *self.policy.write() = Some((policy, cache_ttl));
+        self.cache.lock().clear();
+    }
+
+    pub fn clear(&self) {
+        *self.policy.write() = None;
+        self.cache.lock().clear();
+    }
+
+    /// Forget the cached decision for `inbox_id`, or every decision if `None`
+    pub fn invalidate(&self, inbox_id: Option<&str>) {
+        let mut cache = self.cache.lock();
+        match inbox_id {
+            Some(inbox_id) => {
+                cache.remove(inbox_id);
+            }
+            None => cache.clear(),
+        }
+    }
+
+    /// Check every inbox in `inbox_ids` against the policy, failing on the first one rejected
+    pub(crate) async fn check<S: AsRef<str>>(
+        &self,
+        inbox_ids: &[S],
+    ) -> Result<(), MembershipPolicyError> {
+        // clone out of the lock so a slow policy doesn't block registration
+        let Some((policy, cache_ttl)) = self.policy.read().clone() else {
+            return Ok(());
+        };
+
+        for inbox_id in inbox_ids.iter().map(AsRef::as_ref) {
+            if !self.allows(policy.as_ref(), cache_ttl, inbox_id).await? {
+                return Err(MembershipPolicyError::Rejected(inbox_id.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    async fn allows(
+        &self,
+        policy: &dyn MembershipPolicy,
+        cache_ttl: Duration,
+        inbox_id: &str,
+    ) -> Result<bool, MembershipPolicyError> {
+        if let Some(cached) = self.cache.lock().get(inbox_id) {
+            if cached.checked_at.elapsed() < cache_ttl {
+                return Ok(cached.allowed);
+            }
+        }
+
+        let allowed = policy
+            .allows(inbox_id)
+            .await
+            .map_err(|e| MembershipPolicyError::Check {
+                inbox_id: inbox_id.to_string(),
+                err: e.to_string(),
+            })?;
+        self.cache.lock().insert(
+            inbox_id.to_string(),
+            CachedDecision {
+                allowed,
+                checked_at: Instant::now(),
+            },
+        );
+        Ok(allowed)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V> {
+    /// Gate membership of this client's groups on `policy`, caching each decision for
+    /// `cache_ttl`. Replaces any previous policy and its cached decisions.
+    pub fn set_membership_policy(
+        &self,
+        policy: impl MembershipPolicy + 'static,
+        cache_ttl: Option<Duration>,
+    ) {
+        self.context.commit_hook.membership_policy.set(
+            Arc::new(policy),
+            cache_ttl.unwrap_or(DEFAULT_MEMBERSHIP_CACHE_TTL),
+        );
+    }
+
+    pub fn clear_membership_policy(&self) {
+        self.context.commit_hook.membership_policy.clear();
+    }
+
+    /// Re-check `inbox_id` (or every inbox if `None`) the next time membership is checked,
+    /// e.g. after learning that its token balance changed
+    pub fn invalidate_membership_policy_cache(&self, inbox_id: Option<&str>) {
+        self.context
+            .commit_hook
+            .membership_policy
+            .invalidate(inbox_id);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use std::{
+        collections::HashSet,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::{commit_validator::CommitValidatorError, GroupError, GroupMetadataOptions},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    /// Stands in for an on-chain token balance lookup
+    struct TokenHolders {
+        holders: HashSet<String>,
+        lookups: Arc<AtomicUsize>,
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    impl MembershipPolicy for TokenHolders {
+        async fn allows(&self, inbox_id: &str) -> Result<bool, MembershipPolicyHookError> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            Ok(self.holders.contains(inbox_id))
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_membership_policy_gates_adds() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let lookups = Arc::new(AtomicUsize::new(0));
+        alix.set_membership_policy(
+            TokenHolders {
+                holders: HashSet::from([bo.inbox_id().to_string()]),
+                lookups: lookups.clone(),
+            },
+            None,
+        );
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let Err(GroupError::Sync(errors)) = group.add_members_by_inbox_id(&[caro.inbox_id()]).await
+        else {
+            panic!("adding caro should fail");
+        };
+        assert!(errors.iter().any(|e| matches!(
+            e,
+            GroupError::CommitValidator(CommitValidatorError::MembershipPolicy(
+                MembershipPolicyError::Rejected(inbox_id)
+            )) if inbox_id == caro.inbox_id()
+        )));
+        assert_eq!(group.members().await.unwrap().len(), 2);
+
+        // bo's decision is cached
+        let hook = &alix.context().commit_hook.membership_policy;
+        let lookups_before = lookups.load(Ordering::SeqCst);
+        hook.check(&[bo.inbox_id()]).await.unwrap();
+        assert_eq!(lookups.load(Ordering::SeqCst), lookups_before);
+        alix.invalidate_membership_policy_cache(Some(bo.inbox_id()));
+        hook.check(&[bo.inbox_id()]).await.unwrap();
+        assert_eq!(lookups.load(Ordering::SeqCst), lookups_before + 1);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_membership_policy_ignores_inbound() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        bo.set_membership_policy(
+            TokenHolders {
+                holders: HashSet::new(),
+                lookups: Arc::new(AtomicUsize::new(0)),
+            },
+            None,
+        );
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+
+        // only bo's own adds are gated, the welcome and commits from alix are accepted
+        let groups = bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        assert_eq!(groups.len(), 1);
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        group
+            .add_members_by_inbox_id(&[caro.inbox_id()])
+            .await
+            .unwrap();
+        groups[0].sync().await.unwrap();
+        assert_eq!(groups[0].members().await.unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,166 @@
+//! Mentions. A message mentions inboxes by listing them in the
+//! [`MENTIONS_PARAMETER`] of its encoded content, so any content type can carry mentions and
+//! clients that don't know about them still display the message. Mentions are indexed in
+//! `message_mentions` as messages are received, so mention badges don't have to decode the
+//! conversation's messages.
+use xmtp_content_types::encoded_content_to_bytes;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    client::ClientError,
+    storage::{
+        db_connection::DbConnection, group_message::StoredGroupMessage,
+        message_mention::StoredMessageMention,
+    },
+    Client, XmtpApi,
+};
+
+/// The content parameter holding the mentioned inbox ids, separated by commas
+pub const MENTIONS_PARAMETER: &str = "mentions";
+
+/// The inboxes `content` mentions
+pub fn mentioned_inbox_ids(content: &EncodedContent) -> Vec<String> {
+    content
+        .parameters
+        .get(MENTIONS_PARAMETER)
+        .map(|mentions| {
+            mentions
+                .split(',')
+                .filter(|inbox_id| !inbox_id.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether `message` mentions `inbox_id`
+pub fn mentions_inbox(message: &StoredGroupMessage, inbox_id: &str) -> bool {
+    message.encoded_content().is_ok_and(|content| {
+        mentioned_inbox_ids(&content)
+            .iter()
+            .any(|id| id == inbox_id)
+    })
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Send `content`, mentioning `mentioned_inbox_ids`. Returns the id of the message.
+    pub async fn send_message_with_mentions(
+        &self,
+        mut content: EncodedContent,
+        mentioned_inbox_ids: &[&str],
+    ) -> Result<Vec<u8>, GroupError> {
+        if !mentioned_inbox_ids.is_empty() {
+            content.parameters.insert(
+                MENTIONS_PARAMETER.to_string(),
+                mentioned_inbox_ids.join(","),
+            );
+        }
+        self.send_message(&encoded_content_to_bytes(content)).await
+    }
+
+    /// Index the inboxes mentioned by `message`. Messages that can't be decoded mention nobody.
+    pub(super) fn process_mentions(&self, conn: &DbConnection, message: &StoredGroupMessage) {
+        let Ok(content) = message.encoded_content() else {
+            return;
+        };
+        let mentions: Vec<StoredMessageMention> = mentioned_inbox_ids(&content)
+            .into_iter()
+            .map(|inbox_id| StoredMessageMention {
+                message_id: message.id.clone(),
+                inbox_id,
+                group_id: message.group_id.clone(),
+                sent_at_ns: message.sent_at_ns,
+            })
+            .collect();
+        if mentions.is_empty() {
+            return;
+        }
+        if let Err(e) = conn.insert_message_mentions(&mentions) {
+            tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                message_id = hex::encode(&message.id),
+                "failed to record mentions: {e}"
+            );
+        }
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// The messages mentioning this inbox that it hasn't read, across every conversation,
+    /// newest first. A conversation is read up to this inbox's last read receipt in it.
+    pub fn unread_mentions(&self) -> Result<Vec<StoredGroupMessage>, ClientError> {
+        let conn = self.store().conn()?;
+        Ok(conn.unread_mentions(self.inbox_id())?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::GroupMetadataOptions,
+        subscriptions::{MessageStreamFilter, StreamAllMessagesOptions},
+    };
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::{text::TextCodec, ContentCodec};
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_unread_mentions() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        let bo_group = bo.group(alix_group.group_id.clone()).unwrap();
+
+        let stream = bo
+            .stream_all_messages(StreamAllMessagesOptions {
+                filter: MessageStreamFilter::default().only_mentions(true),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        futures::pin_mut!(stream);
+
+        alix_group
+            .send_message(&encoded_content_to_bytes(
+                TextCodec::encode("no mention".to_string()).unwrap(),
+            ))
+            .await
+            .unwrap();
+        let mention_id = alix_group
+            .send_message_with_mentions(
+                TextCodec::encode("hi @bo".to_string()).unwrap(),
+                &[bo.inbox_id()],
+            )
+            .await
+            .unwrap();
+
+        let streamed = stream.next().await.unwrap().unwrap();
+        assert_eq!(streamed.id, mention_id);
+        let unread = bo.unread_mentions().unwrap();
+        assert_eq!(unread.len(), 1);
+        assert_eq!(unread[0].id, mention_id);
+        assert!(alix.unread_mentions().unwrap().is_empty());
+
+        bo_group.mark_read_until(&mention_id).await.unwrap();
+        assert!(bo.unread_mentions().unwrap().is_empty());
+    }
+}
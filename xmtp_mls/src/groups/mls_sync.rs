@@ -1,17 +1,21 @@
 use super::{
-    build_extensions_for_admin_lists_update, build_extensions_for_metadata_update,
-    build_extensions_for_permissions_update, build_group_membership_extension,
+    build_extensions_for_admin_lists_update, build_extensions_for_metadata_list_update,
+    build_extensions_for_metadata_update, build_extensions_for_permissions_update,
+    build_extensions_for_super_admin_transfer_acceptance, build_group_membership_extension,
+    group_permissions::is_allowed_to_send,
     intents::{
-        Installation, IntentError, PostCommitAction, SendMessageIntentData, SendWelcomesAction,
-        UpdateAdminListIntentData, UpdateGroupMembershipIntentData, UpdatePermissionIntentData,
+        Installation, IntentError, PostCommitAction, ReaddInstallationsIntentData,
+        SendMessageIntentData, SendWelcomesAction, UpdateAdminListIntentData,
+        UpdateGroupMembershipIntentData, UpdateMetadataListIntentData, UpdatePermissionIntentData,
     },
     validated_commit::{extract_group_membership, CommitValidationError},
     GroupError, HmacKey, MlsGroup, ScopedGroupClient,
 };
 use crate::{
+    block_list::recount_unread_counts,
     configuration::{
         GRPC_DATA_LIMIT, HMAC_SALT, MAX_GROUP_SIZE, MAX_INTENT_PUBLISH_ATTEMPTS, MAX_PAST_EPOCHS,
-        SYNC_UPDATE_INSTALLATIONS_INTERVAL_NS,
+        MAX_PUBLISH_BATCH_SIZE, SYNC_UPDATE_INSTALLATIONS_INTERVAL_NS,
     },
     groups::{
         device_sync::{preference_sync::UserPreferenceUpdate, DeviceSyncContent},
@@ -25,7 +29,9 @@ use crate::{
     storage::xmtp_openmls_provider::XmtpOpenMlsProvider,
     storage::{
         db_connection::DbConnection,
+        group::ConversationType,
         group_intent::{IntentKind, IntentState, StoredGroupIntent, ID},
+        group_membership_change::MembershipChangeKind,
         group_message::{ContentType, DeliveryStatus, GroupMessageKind, StoredGroupMessage},
         refresh_state::EntityKind,
         serialization::{db_deserialize, db_serialize},
@@ -33,7 +39,7 @@ use crate::{
         user_preferences::StoredUserPreferences,
         ProviderTransactions, StorageError,
     },
-    subscriptions::{LocalEvents, SyncMessage},
+    subscriptions::{publish_without_waiting, LocalEvents, SyncMessage},
     utils::{hash::sha256, id::calculate_message_id, time::hmac_epoch},
     Delete, Fetch, StoreOrIgnore,
 };
@@ -49,7 +55,7 @@ use openmls::{
     prelude::{
         tls_codec::{Deserialize, Error as TlsCodecError, Serialize},
         LeafNodeIndex, MlsGroup as OpenMlsGroup, MlsMessageBodyIn, MlsMessageIn, PrivateMessageIn,
-        ProcessedMessage, ProcessedMessageContent, Sender,
+        ProcessMessageError, ProcessedMessage, ProcessedMessageContent, Sender,
     },
     treesync::LeafNodeParameters,
 };
@@ -83,6 +89,8 @@ use xmtp_proto::xmtp::mls::{
     },
 };
 
+pub mod fork_detection;
+
 #[derive(Debug, Error)]
 pub enum GroupMessageProcessingError {
     #[error("[{0}] already processed")]
@@ -102,6 +110,14 @@ pub enum GroupMessageProcessingError {
     Identity(#[from] IdentityError),
     #[error("openmls process message error: {0}")]
     OpenMlsProcessMessage(#[from] openmls::prelude::ProcessMessageError),
+    #[error(
+        "message from epoch {message_epoch} can't be decrypted in epoch {group_epoch}: {source}"
+    )]
+    Undecryptable {
+        message_epoch: u64,
+        group_epoch: u64,
+        source: openmls::prelude::ProcessMessageError,
+    },
     #[error("merge staged commit: {0}")]
     MergeStagedCommit(#[from] openmls::group::MergeCommitError<sql_key_store::SqlKeyStoreError>),
     #[error("TLS Codec error: {0}")]
@@ -141,6 +157,7 @@ impl RetryableError for GroupMessageProcessingError {
             Self::Storage(err) => err.is_retryable(),
             Self::Identity(err) => err.is_retryable(),
             Self::OpenMlsProcessMessage(err) => err.is_retryable(),
+            Self::Undecryptable { source, .. } => source.is_retryable(),
             Self::MergeStagedCommit(err) => err.is_retryable(),
             Self::ProcessIntent(err) => err.is_retryable(),
             Self::CommitValidation(err) => err.is_retryable(),
@@ -170,6 +187,32 @@ struct PublishIntentData {
     payload_to_publish: Vec<u8>,
 }
 
+/// An intent marked published, waiting to be sent along with the rest of its batch
+struct PendingPublish {
+    intent_id: ID,
+    payload: Vec<u8>,
+    diagnosed_message_id: Option<Vec<u8>>,
+}
+
+/// Payloads published together in a single request, kept under the API's request size limit
+#[derive(Default)]
+struct PublishBatch {
+    pending: Vec<PendingPublish>,
+    /// Whether the installation's fencing token is checked before the batch is sent
+    fenced: bool,
+}
+
+impl PublishBatch {
+    fn has_room_for(&self, payload: &[u8]) -> bool {
+        let size: usize = self
+            .pending
+            .iter()
+            .map(|pending| pending.payload.len())
+            .sum();
+        self.pending.len() < MAX_PUBLISH_BATCH_SIZE && size + payload.len() <= GRPC_DATA_LIMIT
+    }
+}
+
 impl<ScopedClient> MlsGroup<ScopedClient>
 where
     ScopedClient: ScopedGroupClient,
@@ -277,6 +320,14 @@ where
         while num_attempts < crate::configuration::MAX_GROUP_SYNC_RETRIES {
             if let Err(err) = self.sync_with_conn(provider).await {
                 tracing::error!("error syncing group {:?}", err);
+                // nothing can be published until the installation is promoted
+                if let GroupError::Sync(errors) = &err {
+                    if let Some(GroupError::Failover(failover)) =
+                        errors.iter().find(|e| matches!(e, GroupError::Failover(_)))
+                    {
+                        return Err(GroupError::Failover(failover.clone()));
+                    }
+                }
                 last_err = Some(err);
             }
 
@@ -391,7 +442,10 @@ where
                 | IntentKind::UpdateGroupMembership
                 | IntentKind::UpdateAdminList
                 | IntentKind::MetadataUpdate
-                | IntentKind::UpdatePermission => {
+                | IntentKind::UpdateMetadataList
+                | IntentKind::UpdatePermission
+                | IntentKind::ReaddInstallations
+                | IntentKind::AcceptSuperAdminTransfer => {
                     if let Some(published_in_epoch) = intent.published_in_epoch {
                         let published_in_epoch_u64 = published_in_epoch as u64;
                         let group_epoch_u64 = group_epoch.as_u64();
@@ -429,6 +483,7 @@ where
                         conn,
                         &pending_commit,
                         &mls_group,
+                        envelope_timestamp_ns,
                     )
                     .await;
 
@@ -444,6 +499,12 @@ where
                     }
 
                     let validated_commit = maybe_validated_commit.expect("Checked for error");
+                    let inbox_removed = validated_commit
+                        .removed_inboxes
+                        .iter()
+                        .any(|inbox| inbox.inbox_id == self.client.inbox_id());
+                    let actor = validated_commit.actor.clone();
+                    let key_rotation = pending_commit.update_path_leaf_node().is_some();
 
                     tracing::info!(
                         "[{}] merging pending commit for intent {}",
@@ -459,7 +520,23 @@ where
                             conn,
                             validated_commit,
                             envelope_timestamp_ns,
+                            mls_group.epoch().as_u64(),
+                        )?;
+                        conn.record_epoch_change(
+                            &self.group_id,
+                            mls_group.epoch().as_u64() as i64,
+                            envelope_timestamp_ns as i64,
                         )?;
+                        self.notify_epoch_change(
+                            conn,
+                            mls_group.epoch().as_u64(),
+                            &actor,
+                            key_rotation,
+                        );
+                        // e.g. this installation left, or removed its own inbox
+                        if !mls_group.is_active() {
+                            self.notify_conversation_removed(conn, inbox_removed);
+                        }
                     }
                 }
                 IntentKind::SendMessage => {
@@ -474,6 +551,28 @@ where
                     }
                     if let Some(id) = intent.message_id()? {
                         conn.set_delivery_status_to_published(&id, envelope_timestamp_ns)?;
+                        conn.set_send_receipt(&id, *msg_id as i64, envelope_timestamp_ns as i64)?;
+                        // Our own reactions, receipts, edits, deletions and votes are applied once
+                        // published, like everyone else's
+                        match conn.get_group_message(&id)? {
+                            Some(message) if message.content_type == ContentType::Reaction => {
+                                self.process_reaction(conn, &message)
+                            }
+                            Some(message) if message.content_type == ContentType::ReadReceipt => {
+                                self.process_read_receipt(conn, &message)
+                            }
+                            Some(message) if message.content_type == ContentType::Edit => {
+                                self.process_edit(conn, &message)
+                            }
+                            Some(message) if message.content_type == ContentType::DeleteMessage => {
+                                self.process_deletion(conn, &mls_group, &message)
+                            }
+                            Some(message) if message.content_type == ContentType::PollVote => {
+                                self.process_poll_vote(conn, &message)
+                            }
+                            _ => {}
+                        }
+                        self.notify_delivery_status(conn, id, DeliveryStatus::Published);
                     }
                 }
             };
@@ -497,7 +596,24 @@ where
                 ..
             } = *envelope;
 
-            let decrypted_message = mls_group.process_message(provider, message)?;
+            let message = ProtocolMessage::from(message);
+            let (message_epoch, group_epoch) =
+                (message.epoch().as_u64(), mls_group.epoch().as_u64());
+            let decrypted_message = match mls_group.process_message(provider, message) {
+                Ok(decrypted_message) => decrypted_message,
+                // Messages from past epochs are expected to fail once their keys are gone, e.g.
+                // messages sent before this installation joined. Current and future ones aren't.
+                Err(source @ ProcessMessageError::ValidationError(_))
+                    if message_epoch >= group_epoch =>
+                {
+                    return Err(GroupMessageProcessingError::Undecryptable {
+                        message_epoch,
+                        group_epoch,
+                        source,
+                    });
+                }
+                Err(e) => return Err(e.into()),
+            };
             let (sender_inbox_id, sender_installation_id) =
                 extract_message_sender(&mut mls_group, &decrypted_message, envelope_timestamp_ns)?;
 
@@ -546,7 +662,7 @@ where
                             let message_id =
                                 calculate_message_id(&self.group_id, &content, &idempotency_key);
                             let queryable_content_fields = Self::extract_queryable_content_fields(&content);
-                            StoredGroupMessage {
+                            let message = StoredGroupMessage {
                                 id: message_id,
                                 group_id: self.group_id.clone(),
                                 decrypted_message_bytes: content,
@@ -560,8 +676,58 @@ where
                                 version_minor: queryable_content_fields.version_minor,
                                 authority_id: queryable_content_fields.authority_id,
                                 reference_id: queryable_content_fields.reference_id,
+                                deleted_at_ns: None,
+                            };
+                            if self.has_disappeared(&mls_group, &message) {
+                                return Ok(());
+                            }
+                            if !is_allowed_to_send(&mls_group, &message.sender_inbox_id) {
+                                tracing::warn!(
+                                    group_id = hex::encode(&self.group_id),
+                                    sender_inbox_id = message.sender_inbox_id,
+                                    "dropping message from a sender the send message policy doesn't allow"
+                                );
+                                return Ok(());
+                            }
+                            let is_new =
+                                self.store_received_message(provider.conn_ref(), &message)?;
+                            // redelivered or reprocessed messages were handled the first time
+                            if is_new {
+                                if message.content_type == ContentType::Capabilities {
+                                    self.process_capabilities_advertisement(
+                                        provider.conn_ref(),
+                                        &message,
+                                    );
+                                }
+                                if message.content_type == ContentType::Reaction {
+                                    self.process_reaction(provider.conn_ref(), &message);
+                                }
+                                if message.content_type == ContentType::ReadReceipt {
+                                    self.process_read_receipt(provider.conn_ref(), &message);
+                                }
+                                if message.content_type == ContentType::Profile {
+                                    self.process_profile(provider.conn_ref(), &message);
+                                }
+                                if message.content_type == ContentType::Edit {
+                                    self.process_edit(provider.conn_ref(), &message);
+                                }
+                                if message.content_type == ContentType::DeleteMessage {
+                                    self.process_deletion(provider.conn_ref(), &mls_group, &message);
+                                }
+                                if message.content_type == ContentType::InviteRedemption {
+                                    self.process_invite_redemption(provider.conn_ref(), &message);
+                                }
+                                if message.content_type == ContentType::JoinRequest {
+                                    self.process_join_request(provider, &message);
+                                }
+                                if message.content_type == ContentType::PollVote {
+                                    self.process_poll_vote(provider.conn_ref(), &message);
+                                }
+                                self.process_mentions(provider.conn_ref(), &message);
+                                self.process_pending_references(provider.conn_ref(), &message);
+                                self.process_unread_message(provider.conn_ref(), &message);
+                                self.note_unknown_content_type(provider.conn_ref(), &message);
                             }
-                                .store_or_ignore(provider.conn_ref())?
                         }
                         Some(Content::V2(V2 {
                                              idempotency_key,
@@ -579,7 +745,7 @@ where
                                     );
 
                                     // store the request message
-                                    StoredGroupMessage {
+                                    let message = StoredGroupMessage {
                                         id: message_id.clone(),
                                         group_id: self.group_id.clone(),
                                         decrypted_message_bytes: content_bytes,
@@ -593,8 +759,9 @@ where
                                         version_minor: 0,
                                         authority_id: "unknown".to_string(),
                                         reference_id: None,
-                                    }
-                                        .store_or_ignore(provider.conn_ref())?;
+                                        deleted_at_ns: None,
+                                    };
+                                    self.store_received_message(provider.conn_ref(), &message)?;
 
                                     tracing::info!("Received a history request.");
                                     let _ = self.client.local_events().send(LocalEvents::SyncMessage(
@@ -613,7 +780,7 @@ where
                                     );
 
                                     // store the reply message
-                                    StoredGroupMessage {
+                                    let message = StoredGroupMessage {
                                         id: message_id.clone(),
                                         group_id: self.group_id.clone(),
                                         decrypted_message_bytes: content_bytes,
@@ -627,8 +794,9 @@ where
                                         version_minor: 0,
                                         authority_id: "unknown".to_string(),
                                         reference_id: None,
-                                    }
-                                        .store_or_ignore(provider.conn_ref())?;
+                                        deleted_at_ns: None,
+                                    };
+                                    self.store_received_message(provider.conn_ref(), &message)?;
 
                                 tracing::info!("Received a history reply.");
                                 let _ = self.client.local_events().send(LocalEvents::SyncMessage(
@@ -638,16 +806,36 @@ where
                             Some(MessageType::UserPreferenceUpdate(update)) => {
                                 // This function inserts the updates appropriately,
                                 // and returns a copy of what was inserted
-                                let updates =
+                                let (updates, conflicts) =
                                     UserPreferenceUpdate::process_incoming_preference_update(
-                                        update, provider,
+                                        update,
+                                        provider,
+                                        &self.context().hlc,
                                     )?;
+                                // hidden senders may have changed
+                                let blocked = updates.iter().any(|update| {
+                                    matches!(update, UserPreferenceUpdate::BlockUpdate(_))
+                                });
+                                if blocked {
+                                    let conn = provider.conn_ref();
+                                    let inbox_id = self.context().inbox_id();
+                                    for event in recount_unread_counts(conn, inbox_id)? {
+                                        self.publish_after_commit(conn, event);
+                                    }
+                                }
 
                                 // Broadcast those updates for integrators to be notified of changes
                                 let _ = self
                                     .client
                                     .local_events()
                                     .send(LocalEvents::IncomingPreferenceUpdate(updates));
+                                for conflict in conflicts {
+                                    tracing::info!(
+                                        entity = conflict.incoming.entity,
+                                        winner = ?conflict.winner,
+                                        "resolved a concurrent consent change"
+                                    );
+                                }
                             }
                             _ => {
                                 return Err(GroupMessageProcessingError::InvalidPayload);
@@ -685,6 +873,7 @@ where
                         provider.conn_ref(),
                         &sc,
                         &mls_group,
+                        envelope_timestamp_ns,
                     )
                         .await?;
                     tracing::info!(
@@ -699,12 +888,33 @@ where
                         "[{}] staged commit is valid, will attempt to merge",
                         self.context().inbox_id()
                     );
+                    let inbox_removed = validated_commit
+                        .removed_inboxes
+                        .iter()
+                        .any(|inbox| inbox.inbox_id == self.client.inbox_id());
+                    let actor = validated_commit.actor.clone();
+                    let key_rotation = sc.update_path_leaf_node().is_some();
                     mls_group.merge_staged_commit(provider, sc)?;
                     self.save_transcript_message(
                         provider.conn_ref(),
                         validated_commit,
                         envelope_timestamp_ns,
+                        mls_group.epoch().as_u64(),
+                    )?;
+                    provider.conn_ref().record_epoch_change(
+                        &self.group_id,
+                        mls_group.epoch().as_u64() as i64,
+                        envelope_timestamp_ns as i64,
                     )?;
+                    self.notify_epoch_change(
+                        provider.conn_ref(),
+                        mls_group.epoch().as_u64(),
+                        &actor,
+                        key_rotation,
+                    );
+                    if !mls_group.is_active() {
+                        self.notify_conversation_removed(provider.conn_ref(), inbox_removed);
+                    }
                 }
             };
 
@@ -831,10 +1041,18 @@ where
             Err(GroupMessageProcessingError::AlreadyProcessed(msgv1.id))
         } else {
             let cursor = &msgv1.id;
+            let conn = provider.conn_ref();
+            // Written outside of the processing transaction and cleared inside it, so it is only
+            // left behind if processing is interrupted before committing, e.g. by a crash
+            conn.start_processing_checkpoint(
+                &self.group_id,
+                *cursor as i64,
+                msgv1.created_ns as i64,
+            )?;
             // Download all unread welcome messages and convert to groups.
             // In a database transaction, increment the cursor for a given entity and
             // apply the update after the provided `ProcessingFn` has completed successfully.
-            provider.transaction_async(|provider| async move {
+            let result = provider.transaction_async(|provider| async move {
                 let is_updated =
                     provider
                         .conn_ref()
@@ -843,6 +1061,9 @@ where
                     return Err(ProcessIntentError::AlreadyProcessed(*cursor).into());
                 }
                 self.process_message(provider, msgv1, true).await?;
+                provider.conn_ref().clear_processing_checkpoint(&self.group_id)?;
+                #[cfg(any(test, feature = "test-utils"))]
+                self.maybe_crash().await;
                 Ok::<_, GroupMessageProcessingError>(())
             }).await
             .inspect(|_| {
@@ -859,8 +1080,49 @@ where
                     cursor,
                     err
                 );
-            })?;
-            Ok(())
+            });
+            self.publish_committed_events(conn);
+            // A failed envelope was rolled back, not interrupted, so there is nothing to recover
+            if result.is_err() {
+                conn.clear_processing_checkpoint(&self.group_id)?;
+            }
+            result
+        }
+    }
+
+    /// Apply the messages that arrived before `message`, the message they reference
+    pub(super) fn process_pending_references(
+        &self,
+        conn: &DbConnection,
+        message: &StoredGroupMessage,
+    ) {
+        let waiting = match conn.take_pending_references(&message.id) {
+            Ok(waiting) => waiting,
+            Err(e) => {
+                tracing::warn!(
+                    group_id = hex::encode(&self.group_id),
+                    message_id = hex::encode(&message.id),
+                    "failed to load the messages referencing this one: {e}"
+                );
+                return;
+            }
+        };
+        for reference in waiting {
+            match reference.content_type {
+                ContentType::Edit => self.process_edit(conn, &reference),
+                ContentType::PollVote => self.process_poll_vote(conn, &reference),
+                _ => {}
+            }
+        }
+    }
+
+    /// Never finish processing, before the transaction commits, if a crash was injected with
+    /// [`Client::crash_next_processing`](crate::Client::crash_next_processing)
+    #[cfg(any(test, feature = "test-utils"))]
+    async fn maybe_crash(&self) {
+        let crash = &self.context().processing_crash;
+        if crash.swap(false, std::sync::atomic::Ordering::SeqCst) {
+            futures::future::pending::<()>().await;
         }
     }
 
@@ -871,11 +1133,44 @@ where
         provider: &XmtpOpenMlsProvider,
     ) -> Result<(), GroupError> {
         let mut receive_errors: Vec<GroupMessageProcessingError> = vec![];
-        for message in messages.into_iter() {
+        // Only a group with undecryptable messages on record has a fork to clear
+        let mut fork_recorded =
+            !matches!(provider.conn_ref().get_group_fork(&self.group_id), Ok(None));
+        for (index, message) in messages.into_iter().enumerate() {
+            if !self
+                .context()
+                .inbound_rate_limiter
+                .try_acquire(&self.group_id)
+            {
+                // The cursor stays put, so the rest is fetched again on a later sync
+                tracing::warn!(
+                    group_id = hex::encode(&self.group_id),
+                    "Rate limited, deferring the group's remaining messages"
+                );
+                self.publish_local_event(LocalEvents::RateLimited(self.group_id.clone()))
+                    .await;
+                break;
+            }
+            self.context()
+                .commit_scheduler
+                .before_message(index, &message)
+                .await;
             let result = retry_async!(
                 Retry::default(),
                 (async { self.consume_message(provider, &message).await })
             );
+            match &result {
+                Ok(()) if fork_recorded => {
+                    self.clear_fork(provider.conn_ref());
+                    fork_recorded = false;
+                }
+                Ok(()) => {}
+                Err(GroupMessageProcessingError::Undecryptable { .. }) => {
+                    self.note_undecryptable_message(provider, &message);
+                    fork_recorded = true;
+                }
+                Err(_) => {}
+            }
             if let Err(e) = result {
                 let is_retryable = e.is_retryable();
                 let error_message = e.to_string();
@@ -922,11 +1217,22 @@ where
         conn: &DbConnection,
         validated_commit: ValidatedCommit,
         timestamp_ns: u64,
+        epoch: u64,
     ) -> Result<Option<StoredGroupMessage>, GroupMessageProcessingError> {
         if validated_commit.is_empty() {
             return Ok(None);
         }
 
+        let added = validated_commit
+            .added_inboxes
+            .iter()
+            .map(|inbox| (inbox.inbox_id.clone(), MembershipChangeKind::Added));
+        let removed = validated_commit
+            .removed_inboxes
+            .iter()
+            .map(|inbox| (inbox.inbox_id.clone(), MembershipChangeKind::Removed));
+        conn.record_membership_changes(&self.group_id, epoch as i64, added.chain(removed))?;
+
         tracing::info!(
             "{}: Storing a transcript message with {} members added and {} members removed and {} metadata changes",
             self.context().inbox_id(),
@@ -936,6 +1242,7 @@ where
         );
         let sender_installation_id = validated_commit.actor_installation_id();
         let sender_inbox_id = validated_commit.actor_inbox_id();
+        self.process_pin_changes(&validated_commit);
 
         let payload: GroupUpdated = validated_commit.into();
         let encoded_payload = GroupUpdatedCodec::encode(payload)?;
@@ -975,6 +1282,7 @@ where
             version_minor: content_type.version_minor as i32,
             authority_id: content_type.authority_id.to_string(),
             reference_id: None,
+            deleted_at_ns: None,
         };
 
         msg.store_or_ignore(conn)?;
@@ -986,6 +1294,21 @@ where
         &self,
         provider: &XmtpOpenMlsProvider,
     ) -> Result<(), GroupError> {
+        if self.publish_intents_until_deferred(provider, true).await? {
+            // the commit waits out the UI interaction without holding the group's lock
+            self.context().commit_scheduler.wait_for_interaction().await;
+            self.publish_intents_until_deferred(provider, false).await?;
+        }
+        Ok(())
+    }
+
+    /// Publish the group's intents. With `defer_commits`, stops at a commit the UI
+    /// interaction holds back and returns true.
+    async fn publish_intents_until_deferred(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        defer_commits: bool,
+    ) -> Result<bool, GroupError> {
         self.load_mls_group_with_lock_async(provider, |mut mls_group| async move {
             let intents = provider.conn_ref().find_group_intents(
                 self.group_id.clone(),
@@ -993,101 +1316,241 @@ where
                 None,
             )?;
 
-            for intent in intents {
-                let result = retry_async!(
-                    Retry::default(),
-                    (async {
-                        self.get_publish_intent_data(provider, &mut mls_group, &intent)
-                            .await
-                    })
-                );
-
-                match result {
-                    Err(err) => {
-                        tracing::error!(error = %err, "error getting publish intent data {:?}", err);
-                        if (intent.publish_attempts + 1) as usize >= MAX_INTENT_PUBLISH_ATTEMPTS {
-                            tracing::error!(
-                                intent.id,
-                                intent.kind = %intent.kind,
-                                inbox_id = self.client.inbox_id(),
-                                installation_id = %self.client.installation_id(),group_id = hex::encode(&self.group_id),
-                                "intent {} has reached max publish attempts", intent.id);
-                            // TODO: Eventually clean up errored attempts
-                            provider
-                                .conn_ref()
-                                .set_group_intent_error_and_fail_msg(&intent)?;
-                        } else {
-                            provider
-                                .conn_ref()
-                                .increment_intent_publish_attempt_count(intent.id)?;
-                        }
+            let conversation_type = provider
+                .conn_ref()
+                .find_group(self.group_id.clone())?
+                .map(|group| group.conversation_type)
+                .unwrap_or(ConversationType::Group);
+            // device sync keeps working while the installation is in standby
+            let fenced = conversation_type != ConversationType::Sync;
+            if !intents.is_empty() && fenced {
+                self.context().failover.check_active().await?;
+            }
 
-                        return Err(err);
+            // Consecutive messages are published together, in a single request. Intents are
+            // marked published before they're sent, so if anything fails before their batch is
+            // sent they go back to be published again.
+            let mut batch = PublishBatch {
+                fenced,
+                ..Default::default()
+            };
+            let result: Result<bool, GroupError> = async {
+                for (index, intent) in intents.into_iter().enumerate() {
+                    let hold_back = self
+                        .context()
+                        .commit_scheduler
+                        .before_intent(index, &intent)
+                        .await;
+                    if defer_commits && hold_back {
+                        self.publish_batch(provider, &mut batch).await?;
+                        return Ok(true);
                     }
-                    Ok(Some(PublishIntentData {
-                                payload_to_publish,
+                    let result = retry_async!(
+                        Retry::default(),
+                        (async {
+                            let data = self
+                                .get_publish_intent_data(provider, &mut mls_group, &intent)
+                                .await?;
+                            if let Some(data) = &data {
+                                self.check_outgoing_commit(
+                                    provider,
+                                    &mls_group,
+                                    conversation_type,
+                                    data,
+                                )
+                                .await?;
+                            }
+                            Ok::<_, GroupError>(data)
+                        })
+                    );
+
+                    match result {
+                        Err(err) => {
+                            tracing::error!(error = %err, "error getting publish intent data {:?}", err);
+                            self.publish_batch(provider, &mut batch).await?;
+                            // a commit the hook rejects won't be accepted on another attempt
+                            let rejected = !err.is_retryable()
+                                && matches!(err, GroupError::CommitValidator(_));
+                            let attempts = (intent.publish_attempts + 1) as usize;
+                            if rejected || attempts >= MAX_INTENT_PUBLISH_ATTEMPTS {
+                                tracing::error!(
+                                    intent.id,
+                                    intent.kind = %intent.kind,
+                                    inbox_id = self.client.inbox_id(),
+                                    installation_id = %self.client.installation_id(),group_id = hex::encode(&self.group_id),
+                                    "intent {} has reached max publish attempts", intent.id);
+                                // TODO: Eventually clean up errored attempts
+                                provider
+                                    .conn_ref()
+                                    .set_group_intent_error_and_fail_msg(&intent)?;
+                                if let Some(id) = intent.message_id()? {
+                                    self.notify_delivery_status(
+                                        provider.conn_ref(),
+                                        id,
+                                        DeliveryStatus::Failed,
+                                    );
+                                }
+                            } else {
+                                provider
+                                    .conn_ref()
+                                    .increment_intent_publish_attempt_count(intent.id)?;
+                            }
+
+                            return Err(err);
+                        }
+                        Ok(Some(PublishIntentData {
+                                    payload_to_publish,
+                                    post_commit_action,
+                                    staged_commit,
+                                })) => {
+                            let has_staged_commit = staged_commit.is_some();
+                            if !batch.has_room_for(&payload_to_publish) {
+                                self.publish_batch(provider, &mut batch).await?;
+                            }
+                            provider.conn_ref().set_group_intent_published(
+                                intent.id,
+                                sha256(&payload_to_publish),
                                 post_commit_action,
                                 staged_commit,
-                            })) => {
-                        let payload_slice = payload_to_publish.as_slice();
-                        let has_staged_commit = staged_commit.is_some();
-                        provider.conn_ref().set_group_intent_published(
-                            intent.id,
-                            sha256(payload_slice),
-                            post_commit_action,
-                            staged_commit,
-                            mls_group.epoch().as_u64() as i64,
-                        )?;
-                        tracing::debug!(
-                            inbox_id = self.client.inbox_id(),
-                            installation_id = %self.client.installation_id(),
-                            intent.id,
-                            intent.kind = %intent.kind,
-                            group_id = hex::encode(&self.group_id),
-                            "client [{}] set stored intent [{}] to state `published`",
-                            self.client.inbox_id(),
-                            intent.id
-                        );
-
-                        let messages = self.prepare_group_messages(vec![payload_slice])?;
+                                mls_group.epoch().as_u64() as i64,
+                            )?;
+                            let diagnosed_message_id =
+                                self.record_send_attempt(provider.conn_ref(), &mls_group, &intent)?;
+                            tracing::debug!(
+                                inbox_id = self.client.inbox_id(),
+                                installation_id = %self.client.installation_id(),
+                                intent.id,
+                                intent.kind = %intent.kind,
+                                group_id = hex::encode(&self.group_id),
+                                "client [{}] set stored intent [{}] to state `published`",
+                                self.client.inbox_id(),
+                                intent.id
+                            );
 
-                        self.client
-                            .api()
-                            .send_group_messages(messages)
-                            .await?;
+                            batch.pending.push(PendingPublish {
+                                intent_id: intent.id,
+                                payload: payload_to_publish,
+                                diagnosed_message_id,
+                            });
+                            if has_staged_commit {
+                                // a commit goes out right away, after the messages queued before it
+                                self.publish_batch(provider, &mut batch).await?;
+                            }
 
-                        tracing::info!(
-                            intent.id,
-                            intent.kind = %intent.kind,
-                            inbox_id = self.client.inbox_id(),
-                            installation_id = %self.client.installation_id(),
-                            group_id = hex::encode(&self.group_id),
-                            "[{}] published intent [{}] of type [{}]",
-                            self.client.inbox_id(),
-                            intent.id,
-                            intent.kind
-                        );
-                        if has_staged_commit {
-                            tracing::info!("Commit sent. Stopping further publishes for this round");
-                            return Ok(());
+                            tracing::info!(
+                                intent.id,
+                                intent.kind = %intent.kind,
+                                inbox_id = self.client.inbox_id(),
+                                installation_id = %self.client.installation_id(),
+                                group_id = hex::encode(&self.group_id),
+                                "[{}] published intent [{}] of type [{}]",
+                                self.client.inbox_id(),
+                                intent.id,
+                                intent.kind
+                            );
+                            if has_staged_commit {
+                                tracing::info!("Commit sent. Stopping further publishes for this round");
+                                return Ok(false);
+                            }
+                        }
+                        Ok(None) => {
+                            tracing::info!(
+                                inbox_id = self.client.inbox_id(),
+                                installation_id = %self.client.installation_id(),
+                                "Skipping intent because no publish data returned"
+                            );
+                            let deleter: &dyn Delete<StoredGroupIntent, Key = i32> = provider.conn_ref();
+                            deleter.delete(intent.id)?;
                         }
-                    }
-                    Ok(None) => {
-                        tracing::info!(
-                            inbox_id = self.client.inbox_id(),
-                            installation_id = %self.client.installation_id(),
-                            "Skipping intent because no publish data returned"
-                        );
-                        let deleter: &dyn Delete<StoredGroupIntent, Key = i32> = provider.conn_ref();
-                        deleter.delete(intent.id)?;
                     }
                 }
-            }
 
-            Ok(())
+                self.publish_batch(provider, &mut batch).await?;
+                Ok(false)
+            }
+            .await;
+            if result.is_err() {
+                self.return_unsent_intents(provider, &mut batch);
+            }
+            result
         }).await
     }
 
+    /// Run the commit hook on the commit in `data`, if there is one. The pending commit was
+    /// already cleared from `mls_group`, so a rejected commit is simply never sent.
+    async fn check_outgoing_commit(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        mls_group: &OpenMlsGroup,
+        conversation_type: ConversationType,
+        data: &PublishIntentData,
+    ) -> Result<(), GroupError> {
+        let Some(staged_commit) = &data.staged_commit else {
+            return Ok(());
+        };
+        let staged_commit = decode_staged_commit(staged_commit.clone())?;
+        let validated_commit = ValidatedCommit::from_staged_commit(
+            self.client.as_ref(),
+            provider.conn_ref(),
+            &staged_commit,
+            mls_group,
+            xmtp_common::time::now_ns() as u64,
+        )
+        .await?;
+        self.check_commit_hook(conversation_type, &validated_commit)
+            .await?;
+        Ok(())
+    }
+
+    /// Publish the payloads of `batch` in a single request, in order, and mark the messages
+    /// among them with send diagnostics as published. On failure, `batch` is left as it was.
+    async fn publish_batch(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        batch: &mut PublishBatch,
+    ) -> Result<(), GroupError> {
+        if batch.pending.is_empty() {
+            return Ok(());
+        }
+        if batch.fenced {
+            // another installation may have taken over the lease since the intents were prepared
+            self.context().failover.check_active().await?;
+        }
+        let messages = self.prepare_group_messages(
+            batch
+                .pending
+                .iter()
+                .map(|pending| pending.payload.as_slice())
+                .collect(),
+        )?;
+        self.client.api().send_group_messages(messages).await?;
+        let published_at_ns = xmtp_common::time::now_ns();
+        for pending in std::mem::take(&mut batch.pending) {
+            if let Some(message_id) = pending.diagnosed_message_id {
+                provider
+                    .conn_ref()
+                    .set_send_published(&message_id, published_at_ns)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Put the intents of a batch that wasn't sent back to be published again
+    fn return_unsent_intents(&self, provider: &XmtpOpenMlsProvider, batch: &mut PublishBatch) {
+        for pending in std::mem::take(&mut batch.pending) {
+            if let Err(e) = provider
+                .conn_ref()
+                .set_group_intent_to_publish(pending.intent_id)
+            {
+                tracing::error!(
+                    intent.id = pending.intent_id,
+                    group_id = hex::encode(&self.group_id),
+                    "failed to return unsent intent to be published: {e}"
+                );
+            }
+        }
+    }
+
     // Takes a StoredGroupIntent and returns the payload and post commit data as a tuple
     // A return value of [`Option::None`] means this intent would not change the group.
     #[allow(clippy::type_complexity)]
@@ -1111,6 +1574,18 @@ where
                 )
                 .await
             }
+            IntentKind::ReaddInstallations => {
+                let intent_data = ReaddInstallationsIntentData::try_from(&intent.data)?;
+                let signer = &self.context().identity.installation_keys;
+                apply_readd_installations_intent(
+                    self.client.as_ref(),
+                    provider,
+                    openmls_group,
+                    intent_data,
+                    signer,
+                )
+                .await
+            }
             IntentKind::SendMessage => {
                 // We can safely assume all SendMessage intents have data
                 let intent_data = SendMessageIntentData::from_bytes(intent.data.as_slice())?;
@@ -1162,6 +1637,29 @@ where
                     post_commit_action: None,
                 }))
             }
+            IntentKind::UpdateMetadataList => {
+                let list_intent = UpdateMetadataListIntentData::try_from(&intent.data)?;
+                let Some(mutable_metadata_extensions) =
+                    build_extensions_for_metadata_list_update(openmls_group, &list_intent)?
+                else {
+                    // the list already is as the intent wants it
+                    return Ok(None);
+                };
+
+                let (commit, _, _) = openmls_group.update_group_context_extensions(
+                    &provider,
+                    mutable_metadata_extensions,
+                    &self.context().identity.installation_keys,
+                )?;
+
+                let commit_bytes = commit.tls_serialize_detached()?;
+
+                Ok(Some(PublishIntentData {
+                    payload_to_publish: commit_bytes,
+                    staged_commit: get_and_clear_pending_commit(openmls_group, provider)?,
+                    post_commit_action: None,
+                }))
+            }
             IntentKind::UpdateAdminList => {
                 let admin_list_update_intent =
                     UpdateAdminListIntentData::try_from(intent.data.clone())?;
@@ -1183,6 +1681,26 @@ where
                     post_commit_action: None,
                 }))
             }
+            IntentKind::AcceptSuperAdminTransfer => {
+                let mutable_metadata_extensions =
+                    build_extensions_for_super_admin_transfer_acceptance(
+                        openmls_group,
+                        self.context().inbox_id(),
+                    )?;
+
+                let (commit, _, _) = openmls_group.update_group_context_extensions(
+                    provider,
+                    mutable_metadata_extensions,
+                    &self.context().identity.installation_keys,
+                )?;
+                let commit_bytes = commit.tls_serialize_detached()?;
+
+                Ok(Some(PublishIntentData {
+                    payload_to_publish: commit_bytes,
+                    staged_commit: get_and_clear_pending_commit(openmls_group, provider)?,
+                    post_commit_action: None,
+                }))
+            }
             IntentKind::UpdatePermission => {
                 let update_permissions_intent =
                     UpdatePermissionIntentData::try_from(intent.data.clone())?;
@@ -1431,8 +1949,14 @@ where
         let mut ikm = match preferences.hmac_key {
             Some(ikm) => ikm,
             None => {
-                let local_events = self.client.local_events();
-                StoredUserPreferences::new_hmac_key(&conn, local_events)?
+                let key = StoredUserPreferences::new_hmac_key(&conn)?;
+                publish_without_waiting(
+                    self.client.local_events(),
+                    LocalEvents::OutgoingPreferenceUpdates(vec![
+                        UserPreferenceUpdate::HmacKeyUpdate { key: key.clone() },
+                    ]),
+                );
+                key
             }
         };
         ikm.extend(&self.group_id);
@@ -1602,6 +2126,66 @@ async fn apply_update_group_membership_intent(
     }))
 }
 
+// Removes the installations in ReaddInstallationsIntentData and adds them back with new key
+// packages in one commit, returning the commit and the welcomes to send
+#[tracing::instrument(level = "trace", skip_all)]
+async fn apply_readd_installations_intent(
+    client: impl ScopedGroupClient,
+    provider: &XmtpOpenMlsProvider,
+    openmls_group: &mut OpenMlsGroup,
+    intent_data: ReaddInstallationsIntentData,
+    signer: impl Signer,
+) -> Result<Option<PublishIntentData>, GroupError> {
+    let installations: HashSet<Vec<u8>> = intent_data.installations.into_iter().collect();
+    // installations that left the group in the meantime aren't added back
+    let leaf_nodes_to_remove = get_removed_leaf_nodes(openmls_group, &installations);
+    if leaf_nodes_to_remove.is_empty() {
+        return Ok(None);
+    }
+    let installation_ids = openmls_group
+        .members()
+        .filter(|member| installations.contains(&member.signature_key))
+        .map(|member| member.signature_key)
+        .collect();
+
+    let mut new_installations: Vec<Installation> = vec![];
+    let mut new_key_packages: Vec<KeyPackage> = vec![];
+    for key_package in client
+        .get_key_packages_for_installation_ids(installation_ids)
+        .await?
+    {
+        new_installations.push(Installation::from_verified_key_package(&key_package));
+        new_key_packages.push(key_package.inner);
+    }
+
+    // The membership doesn't change, only the installations' leaves
+    let extensions = openmls_group.extensions().clone();
+    let (commit, maybe_welcome_message, _) = openmls_group.update_group_membership(
+        provider,
+        &signer,
+        &new_key_packages,
+        &leaf_nodes_to_remove,
+        extensions,
+    )?;
+
+    let post_commit_action = match maybe_welcome_message {
+        Some(welcome_message) => Some(PostCommitAction::from_welcome(
+            welcome_message,
+            new_installations,
+        )?),
+        None => None,
+    };
+
+    let staged_commit = get_and_clear_pending_commit(openmls_group, provider)?
+        .ok_or_else(|| GroupError::MissingPendingCommit)?;
+
+    Ok(Some(PublishIntentData {
+        payload_to_publish: commit.tls_serialize_detached()?,
+        post_commit_action: post_commit_action.map(|action| action.to_bytes()),
+        staged_commit: Some(staged_commit),
+    }))
+}
+
 fn get_removed_leaf_nodes(
     openmls_group: &mut OpenMlsGroup,
     removed_installations: &HashSet<Vec<u8>>,
@@ -1636,7 +2220,7 @@ pub(crate) mod tests {
     wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
 
     use super::*;
-    use crate::builder::ClientBuilder;
+    use crate::{builder::ClientBuilder, storage::group_message::MsgQueryArgs};
     use std::sync::Arc;
     use xmtp_cryptography::utils::generate_local_wallet;
 
@@ -1686,6 +2270,28 @@ pub(crate) mod tests {
         }
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn publish_batches_stay_under_request_limits() {
+        let pending = |payload: Vec<u8>| PendingPublish {
+            intent_id: 1,
+            payload,
+            diagnosed_message_id: None,
+        };
+        let mut batch = PublishBatch::default();
+        assert!(batch.has_room_for(&[0; 100]));
+
+        batch.pending.push(pending(vec![0; GRPC_DATA_LIMIT - 10]));
+        assert!(batch.has_room_for(&[0; 10]));
+        assert!(!batch.has_room_for(&[0; 11]));
+
+        let mut batch = PublishBatch::default();
+        for _ in 0..MAX_PUBLISH_BATCH_SIZE {
+            batch.pending.push(pending(vec![0]));
+        }
+        assert!(!batch.has_room_for(&[0]));
+    }
+
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
     #[cfg_attr(not(target_arch = "wasm32"), tokio::test(flavor = "multi_thread"))]
     async fn hmac_keys_work_as_expected() {
@@ -1711,4 +2317,144 @@ pub(crate) mod tests {
         assert_eq!(hmac_keys[1].epoch, current_epoch);
         assert_eq!(hmac_keys[2].epoch, current_epoch + 1);
     }
+
+    #[wasm_bindgen_test::wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_recovers_interrupted_processing() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let alix_group = alix.create_group(None, Default::default()).unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_provider = bo.mls_provider().unwrap();
+        let bo_group = bo.sync_welcomes(&bo_provider).await.unwrap().remove(0);
+        bo_group.sync().await.unwrap();
+        let has_message = |text: &[u8]| {
+            bo_group
+                .find_messages(&MsgQueryArgs::default())
+                .unwrap()
+                .iter()
+                .any(|m| m.decrypted_message_bytes == text)
+        };
+
+        // Crash before the processing transaction commits: the sync never finishes and is
+        // abandoned halfway through the transaction
+        alix_group.send_message(b"first").await.unwrap();
+        bo.crash_next_processing();
+        assert!(
+            xmtp_common::time::timeout(std::time::Duration::from_secs(1), bo_group.sync())
+                .await
+                .is_err()
+        );
+        assert!(!has_message(b"first"));
+        assert_eq!(
+            bo_provider
+                .conn_ref()
+                .processing_checkpoints()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let rolled_back = bo.recover_interrupted_processing().unwrap();
+        assert_eq!(rolled_back, vec![bo_group.group_id.clone()]);
+        bo_group.sync().await.unwrap();
+        assert!(has_message(b"first"));
+
+        // Committed processing leaves nothing to recover
+        alix_group.send_message(b"second").await.unwrap();
+        bo_group.sync().await.unwrap();
+        assert!(has_message(b"second"));
+
+        assert!(bo.recover_interrupted_processing().unwrap().is_empty());
+        assert!(bo_provider
+            .conn_ref()
+            .processing_checkpoints()
+            .unwrap()
+            .is_empty());
+    }
+    #[derive(diesel::QueryableByName)]
+    struct KeyValue {
+        #[diesel(sql_type = diesel::sql_types::Binary)]
+        key_bytes: Vec<u8>,
+        #[diesel(sql_type = diesel::sql_types::Integer)]
+        version: i32,
+        #[diesel(sql_type = diesel::sql_types::Binary)]
+        value_bytes: Vec<u8>,
+    }
+
+    #[wasm_bindgen_test::wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_redelivered_message_is_applied_once() {
+        use diesel::RunQueryDsl;
+        use xmtp_proto::xmtp::mls::message_contents::content_types::ReactionAction;
+
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix.create_group(None, Default::default()).unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let message_id = alix_group.send_message(b"hi").await.unwrap();
+        let bo_provider = bo.mls_provider().unwrap();
+        let bo_group = bo.sync_welcomes(&bo_provider).await.unwrap().remove(0);
+        bo_group.sync().await.unwrap();
+
+        // keep bo's MLS state from before the reaction, so the reaction can be decrypted again
+        let key_store: Vec<KeyValue> = bo_provider
+            .conn_ref()
+            .raw_query(|conn| {
+                diesel::sql_query("SELECT key_bytes, version, value_bytes FROM openmls_key_value")
+                    .load(conn)
+            })
+            .unwrap();
+        alix_group
+            .send_reaction(&message_id, "👍", ReactionAction::Added)
+            .await
+            .unwrap();
+        let envelopes = bo
+            .api_client
+            .query_group_messages(bo_group.group_id.clone(), None)
+            .await
+            .unwrap();
+        let Some(GroupMessageVersion::V1(reaction)) = envelopes.last().unwrap().version.clone()
+        else {
+            panic!("wrong message format")
+        };
+        bo_group.sync().await.unwrap();
+        alix_group
+            .send_reaction(&message_id, "👍", ReactionAction::Removed)
+            .await
+            .unwrap();
+        bo_group.sync().await.unwrap();
+        assert!(bo_group.reactions_for(&message_id).unwrap().is_empty());
+        let unread_count = bo_group.unread_count().unwrap();
+
+        bo_provider
+            .conn_ref()
+            .raw_query(|conn| {
+                for row in &key_store {
+                    diesel::sql_query(
+                        "REPLACE INTO openmls_key_value (key_bytes, version, value_bytes) \
+                         VALUES (?, ?, ?)",
+                    )
+                    .bind::<diesel::sql_types::Binary, _>(&row.key_bytes)
+                    .bind::<diesel::sql_types::Integer, _>(row.version)
+                    .bind::<diesel::sql_types::Binary, _>(&row.value_bytes)
+                    .execute(conn)?;
+                }
+                Ok::<_, diesel::result::Error>(())
+            })
+            .unwrap();
+        bo_group
+            .process_message(&bo_provider, &reaction, false)
+            .await
+            .unwrap();
+
+        // the reaction isn't added back and nothing is counted twice
+        assert!(bo_group.reactions_for(&message_id).unwrap().is_empty());
+        assert_eq!(bo_group.unread_count().unwrap(), unread_count);
+    }
 }
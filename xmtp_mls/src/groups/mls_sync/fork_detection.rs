@@ -0,0 +1,418 @@
+//! Detecting and recovering from forked groups.
+//!
+//! A group forks on an installation that missed or rejected a commit the other members merged:
+//! it stays in an epoch nobody else uses, and can't decrypt anything sent after. Messages from
+//! past epochs are expected to fail now and then, e.g. the ones sent before the installation
+//! joined, so only messages from the group's current or later epochs count. Once
+//! [`FORK_DETECTION_THRESHOLD`] of them in a row fail, the group is considered forked and a
+//! [`LocalEvents::GroupForked`] event is emitted. Any message processed successfully resets the
+//! count.
+//!
+//! [`Client::recover_forked_groups`], which runs after
+//! [`Client::sync_all_welcomes_and_groups`], then sends a rejoin request to an admin of each
+//! forked group, with the epoch the group is stuck in and its epoch authenticator as evidence.
+//! On the admin's side, the same call approves the rejoin requests it received from members
+//! whose evidence shows a fork: their copy is in an earlier epoch than the admin's, or in the
+//! same epoch with a different authenticator. The member's installation is removed and added
+//! back in one commit, and the welcome replaces the forked copy of the group. That emits
+//! [`LocalEvents::GroupRecovered`].
+use std::cmp::Ordering;
+
+use xmtp_common::time::now_ns;
+use xmtp_content_types::{
+    join_request::{ForkEvidence, JoinRequestCodec},
+    CodecError, ContentCodec,
+};
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::xmtp::mls::api::v1::{group_message::Version as GroupMessageVersion, GroupMessage};
+
+use crate::{
+    client::ClientError,
+    configuration::FORK_DETECTION_THRESHOLD,
+    groups::{
+        intents::ReaddInstallationsIntentData, scoped_client::ScopedGroupClient, GroupError,
+        MlsGroup,
+    },
+    storage::{
+        db_connection::DbConnection, group_intent::IntentKind, join_request::StoredJoinRequest,
+        xmtp_openmls_provider::XmtpOpenMlsProvider,
+    },
+    subscriptions::{GroupFork, LocalEvents},
+    Client, XmtpApi,
+};
+
+/// What [`Client::recover_forked_groups`] did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForkRecovery {
+    /// Forked groups an admin was asked to add this installation back to
+    pub rejoins_requested: Vec<Vec<u8>>,
+    /// The groups and inboxes of the members that were added back
+    pub members_readded: Vec<(Vec<u8>, String)>,
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// The group's undecryptable messages, if it's considered forked
+    pub fn fork_status(&self) -> Result<Option<GroupFork>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn
+            .get_group_fork(&self.group_id)?
+            .filter(|fork| fork.forked_at_ns.is_some())
+            .map(Into::into))
+    }
+
+    /// Count `envelope` as undecryptable, and consider the group forked once enough of them are
+    /// in a row. Failures are logged, they must not fail message processing.
+    pub(super) fn note_undecryptable_message(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        envelope: &GroupMessage,
+    ) {
+        let Some(GroupMessageVersion::V1(envelope)) = &envelope.version else {
+            return;
+        };
+        if let Err(e) = self.record_undecryptable_message(provider, envelope.id as i64) {
+            tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                cursor = envelope.id,
+                "failed to record undecryptable message: {e}"
+            );
+        }
+    }
+
+    fn record_undecryptable_message(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        cursor: i64,
+    ) -> Result<(), GroupError> {
+        let conn = provider.conn_ref();
+        let now = now_ns();
+        let fork = conn.record_undecryptable_message(&self.group_id, cursor, now)?;
+        if fork.forked_at_ns.is_some()
+            || fork.undecryptable_messages < FORK_DETECTION_THRESHOLD as i32
+            // an installation that was removed can't decrypt the group either
+            || !self.is_active(provider)?
+        {
+            return Ok(());
+        }
+
+        conn.set_group_forked(&self.group_id, now)?;
+        tracing::warn!(
+            group_id = hex::encode(&self.group_id),
+            undecryptable_messages = fork.undecryptable_messages,
+            "group forked"
+        );
+        let _ = self
+            .client
+            .local_events()
+            .send(LocalEvents::GroupForked(GroupFork {
+                group_id: self.group_id.clone(),
+                undecryptable_messages: fork.undecryptable_messages as u32,
+                forked_at_ns: now,
+            }));
+        Ok(())
+    }
+
+    /// Forget the group's undecryptable messages once it can be processed again, and emit
+    /// [`LocalEvents::GroupRecovered`] if it was forked. Failures are logged.
+    pub(crate) fn clear_fork(&self, conn: &DbConnection) {
+        match conn.clear_group_fork(&self.group_id) {
+            Ok(Some(fork)) if fork.forked_at_ns.is_some() => {
+                tracing::info!(group_id = hex::encode(&self.group_id), "group recovered");
+                let _ = self
+                    .client
+                    .local_events()
+                    .send(LocalEvents::GroupRecovered(fork.into()));
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                "failed to clear group fork: {e}"
+            ),
+        }
+    }
+
+    /// The epoch this installation's copy of the group is in, and its epoch authenticator
+    fn fork_evidence(&self, provider: &XmtpOpenMlsProvider) -> Result<ForkEvidence, GroupError> {
+        self.load_mls_group_with_lock(provider, |mls_group| {
+            Ok(ForkEvidence {
+                epoch: mls_group.epoch().as_u64(),
+                epoch_authenticator: mls_group.epoch_authenticator().as_slice().to_vec(),
+            })
+        })
+    }
+
+    /// Whether `evidence` from a member shows that its copy of the group forked from this one.
+    /// `None` if the member is in a later epoch, this installation has to catch up first.
+    fn shows_fork(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        evidence: &ForkEvidence,
+    ) -> Result<Option<bool>, GroupError> {
+        let own = self.fork_evidence(provider)?;
+        Ok(match evidence.epoch.cmp(&own.epoch) {
+            Ordering::Less => Some(true),
+            Ordering::Equal => Some(evidence.epoch_authenticator != own.epoch_authenticator),
+            Ordering::Greater => None,
+        })
+    }
+
+    /// Remove `installation_ids` and add them back in one commit, so they receive a fresh
+    /// welcome. Requires being an admin.
+    pub(crate) async fn readd_installations(
+        &self,
+        installation_ids: Vec<Vec<u8>>,
+    ) -> Result<(), GroupError> {
+        let provider = self.client.mls_provider()?;
+        let intent = self.queue_intent(
+            &provider,
+            IntentKind::ReaddInstallations,
+            ReaddInstallationsIntentData::new(installation_ids).into(),
+        )?;
+        self.sync_until_intent_resolved(&provider, intent.id).await
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Ask an admin of each forked group to add this installation back, once per fork, and add
+    /// back the members whose rejoin requests this client received for groups it administers.
+    /// Groups that fail are logged and retried on the next call.
+    pub async fn recover_forked_groups(&self) -> Result<ForkRecovery, ClientError> {
+        let provider = self.mls_provider()?;
+        let conn = provider.conn_ref();
+        let mut recovery = ForkRecovery::default();
+
+        let forks = conn.forked_groups()?;
+        for fork in forks
+            .into_iter()
+            .filter(|fork| fork.rejoin_requested_at_ns.is_none())
+        {
+            match self.request_rejoin(&provider, &fork.group_id).await {
+                Ok(true) => recovery.rejoins_requested.push(fork.group_id),
+                Ok(false) => {}
+                Err(e) => tracing::warn!(
+                    group_id = hex::encode(&fork.group_id),
+                    "failed to request rejoining forked group: {e}"
+                ),
+            }
+        }
+
+        for request in conn.get_rejoin_requests()? {
+            match self.readd_forked_member(&provider, &request).await {
+                Ok(true) => recovery
+                    .members_readded
+                    .push((request.group_id, request.inbox_id)),
+                Ok(false) => {}
+                Err(e) => tracing::warn!(
+                    group_id = hex::encode(&request.group_id),
+                    inbox_id = request.inbox_id,
+                    "failed to add back forked member: {e}"
+                ),
+            }
+        }
+
+        Ok(recovery)
+    }
+
+    /// Add back the sender of a rejoin `request` if this inbox administers the group and the
+    /// request's evidence shows a fork. Returns whether the member was added back. Requests
+    /// showing no fork are dropped, those from non-members or without evidence are left for an
+    /// admin to decide on.
+    async fn readd_forked_member(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        request: &StoredJoinRequest,
+    ) -> Result<bool, GroupError> {
+        let group = self.group(request.group_id.clone())?;
+        let inbox_id = self.inbox_id().to_string();
+        if !group.is_admin(inbox_id.clone(), provider)?
+            && !group.is_super_admin(inbox_id, provider)?
+        {
+            return Ok(false);
+        }
+        let members = group.members().await?;
+        if !members
+            .iter()
+            .any(|member| member.inbox_id == request.inbox_id)
+        {
+            return Ok(false);
+        }
+        let Some(message) = provider.conn_ref().get_group_message(&request.message_id)? else {
+            return Ok(false);
+        };
+        let content = message
+            .encoded_content()
+            .map_err(|e| CodecError::Decode(e.to_string()))?;
+        let Some(evidence) = JoinRequestCodec::decode(content)?.fork_evidence else {
+            return Ok(false);
+        };
+        match group.shows_fork(provider, &evidence)? {
+            Some(true) => {}
+            Some(false) => {
+                tracing::warn!(
+                    group_id = hex::encode(&request.group_id),
+                    inbox_id = request.inbox_id,
+                    "dropping rejoin request, the member's copy of the group didn't fork"
+                );
+                group.reject_join_request(&request.message_id)?;
+                return Ok(false);
+            }
+            None => return Ok(false),
+        }
+
+        group.approve_join_request(&request.message_id).await?;
+        Ok(true)
+    }
+
+    /// Send a rejoin request for `group_id` to each of its admins. Returns whether there was an
+    /// admin other than this inbox to ask.
+    async fn request_rejoin(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        group_id: &[u8],
+    ) -> Result<bool, GroupError> {
+        let group = self.group(group_id.to_vec())?;
+        let mut admins = group.super_admin_list(provider)?;
+        admins.extend(group.admin_list(provider)?);
+        admins.sort();
+        admins.dedup();
+        admins.retain(|admin| admin.as_str() != self.inbox_id());
+        if admins.is_empty() {
+            tracing::warn!(
+                group_id = hex::encode(group_id),
+                "forked group has no other admin to ask for a rejoin"
+            );
+            return Ok(false);
+        }
+
+        let evidence = group.fork_evidence(provider)?;
+        self.send_join_request(group_id, &admins, None, Some(evidence))
+            .await?;
+        provider
+            .conn_ref()
+            .set_rejoin_requested(group_id, now_ns())?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::GroupMetadataOptions,
+        storage::{
+            group_message::{GroupMessageKind, MsgQueryArgs},
+            refresh_state::EntityKind,
+        },
+        subscriptions::{GroupForkEvent, StreamMessages},
+    };
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_forked_member_is_added_back() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_provider = bo.mls_provider().unwrap();
+        bo.sync_welcomes(&bo_provider).await.unwrap();
+        let bo_group = bo.group(alix_group.group_id.clone()).unwrap();
+        let forks = bo.local_events().subscribe().stream_group_forks();
+        futures::pin_mut!(forks);
+
+        // bo skips a commit, as if it had been lost, and can't follow the group anymore
+        alix_group
+            .update_group_name("forked".to_string())
+            .await
+            .unwrap();
+        let envelopes = bo
+            .query_group_messages(&alix_group.group_id, bo_provider.conn_ref())
+            .await
+            .unwrap();
+        let Some(GroupMessageVersion::V1(commit)) = &envelopes.last().unwrap().version else {
+            panic!("unexpected envelope version");
+        };
+        bo_provider
+            .conn_ref()
+            .update_cursor(&alix_group.group_id, EntityKind::Group, commit.id as i64)
+            .unwrap();
+        for text in ["one", "two", "three"] {
+            alix_group.send_message(text.as_bytes()).await.unwrap();
+        }
+        assert!(bo_group.sync().await.is_err());
+        let GroupForkEvent::Forked(fork) = forks.next().await.unwrap().unwrap() else {
+            panic!("expected the group to fork");
+        };
+        assert_eq!(fork.undecryptable_messages, FORK_DETECTION_THRESHOLD);
+        assert_eq!(bo_group.fork_status().unwrap(), Some(fork));
+
+        let recovery = bo.recover_forked_groups().await.unwrap();
+        assert_eq!(
+            recovery.rejoins_requested,
+            vec![alix_group.group_id.clone()]
+        );
+        // requested once per fork
+        assert!(bo
+            .recover_forked_groups()
+            .await
+            .unwrap()
+            .rejoins_requested
+            .is_empty());
+
+        // alix receives the request and adds bo back
+        alix.sync_all_welcomes_and_groups(&alix.mls_provider().unwrap(), None)
+            .await
+            .unwrap();
+        let conn = alix.store().conn().unwrap();
+        assert!(conn.get_rejoin_requests().unwrap().is_empty());
+
+        bo.sync_welcomes(&bo_provider).await.unwrap();
+        assert!(matches!(
+            forks.next().await.unwrap().unwrap(),
+            GroupForkEvent::Recovered(_)
+        ));
+        assert_eq!(bo_group.fork_status().unwrap(), None);
+        alix_group.send_message(b"welcome back").await.unwrap();
+        let _ = bo_group.sync().await;
+        let messages = bo_group
+            .find_messages(&MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            messages.last().unwrap().decrypted_message_bytes,
+            b"welcome back"
+        );
+
+        // a rejoin request whose evidence shows no fork is dropped
+        let evidence = bo_group.fork_evidence(&bo_provider).unwrap();
+        bo.send_join_request(
+            &alix_group.group_id,
+            &[alix.inbox_id()],
+            None,
+            Some(evidence),
+        )
+        .await
+        .unwrap();
+        alix.sync_all_welcomes_and_groups(&alix.mls_provider().unwrap(), None)
+            .await
+            .unwrap();
+        assert!(conn.get_rejoin_requests().unwrap().is_empty());
+        assert_eq!(alix_group.members().await.unwrap().len(), 2);
+    }
+}
@@ -1,18 +1,71 @@
+pub mod activity;
+pub mod announcements;
+pub mod archive;
+pub mod attachments;
+pub mod bans;
+pub mod batches;
+pub mod commands;
+pub mod commit_validator;
+pub mod contact_consent;
+pub mod content_compat;
+pub mod deletions;
 pub mod device_sync;
+pub mod disappearing_messages;
+pub mod drafts;
+pub mod duplicate;
+pub mod edits;
+pub mod forward;
 pub mod group_membership;
 pub mod group_metadata;
 pub mod group_mutable_metadata;
 pub mod group_permissions;
+pub mod guests;
+pub mod inactivity;
 pub mod intents;
+pub mod invite_links;
+pub mod join_requests;
+pub mod key_rotation;
+pub mod link_previews;
+pub mod member_capabilities;
 pub mod members;
+pub mod membership_policy;
+pub mod mentions;
+pub mod notifications;
+pub mod open;
+pub mod pagination;
+pub mod pins;
+pub mod polls;
+pub mod post_processors;
+pub mod reactions;
+pub mod read_receipts;
+pub mod reconciliation;
+pub mod replies;
+pub mod reports;
 pub mod scoped_client;
+pub mod scratch;
+pub mod send_diagnostics;
+pub mod stale_installations;
+pub mod succession;
+pub mod summaries;
+pub mod unread;
 
 pub(super) mod mls_sync;
 pub(super) mod subscriptions;
 pub mod validated_commit;
+pub mod verification;
 
+use archive::ArchiveError;
+use attachments::AttachmentError;
+use bans::BanError;
+use commit_validator::CommitValidatorError;
+use deletions::DeletionError;
 use device_sync::preference_sync::UserPreferenceUpdate;
+use duplicate::DuplicateError;
+use edits::EditError;
+use forward::ForwardError;
 use intents::SendMessageIntentData;
+use invite_links::InviteError;
+use join_requests::JoinRequestError;
 use mls_sync::GroupMessageProcessingError;
 use openmls::{
     credentials::{BasicCredential, CredentialType},
@@ -32,13 +85,22 @@ use openmls::{
     },
 };
 use openmls_traits::OpenMlsProvider;
+use pagination::PaginationError;
+use pins::PinError;
+use polls::PollError;
 use prost::Message;
+use reports::ReportError;
+use scratch::ScratchError;
+use succession::{SuccessionError, SuperAdminTransfer, SUPER_ADMIN_TRANSFER_METADATA_FIELD};
 use thiserror::Error;
 use tokio::sync::Mutex;
-use xmtp_content_types::reaction::ReactionCodec;
+use xmtp_content_types::{
+    encoded_content_to_bytes, reaction::ReactionCodec, reply::ReplyCodec, CodecError,
+};
 
 use self::device_sync::DeviceSyncError;
-pub use self::group_permissions::PreconfiguredPolicies;
+pub use self::group_permissions::{PolicySetBuilder, PreconfiguredPolicies};
+pub use self::mls_sync::fork_detection::ForkRecovery;
 use self::scoped_client::ScopedGroupClient;
 use self::{
     group_membership::GroupMembership,
@@ -49,19 +111,20 @@ use self::{
     },
     intents::{
         AdminListActionType, PermissionPolicyOption, PermissionUpdateType,
-        UpdateAdminListIntentData, UpdateMetadataIntentData, UpdatePermissionIntentData,
+        UpdateAdminListIntentData, UpdateMetadataIntentData, UpdateMetadataListIntentData,
+        UpdatePermissionIntentData,
     },
-    validated_commit::extract_group_membership,
+    validated_commit::{extract_group_membership, CommitParticipant},
 };
 use self::{
     group_metadata::{GroupMetadata, GroupMetadataError},
-    group_permissions::PolicySet,
+    group_permissions::{PolicySet, SEND_MESSAGE_POLICY_KEY},
     intents::IntentError,
     validated_commit::CommitValidationError,
 };
 use crate::storage::{
     group::DmIdExt,
-    group_message::{ContentType, StoredGroupMessageWithReactions},
+    group_message::{ContentType, StoredGroupMessageHeader, StoredGroupMessageWithReactions},
     NotFound, StorageError,
 };
 use xmtp_common::time::now_ns;
@@ -79,12 +142,15 @@ use xmtp_proto::xmtp::mls::{
 
 use crate::{
     api::WrappedApiError,
+    block_list::BlockError,
     client::{deserialize_welcome, ClientError, XmtpMlsLocalContext},
+    codec_registry::EncodableContent,
     configuration::{
         CIPHERSUITE, GROUP_MEMBERSHIP_EXTENSION_ID, GROUP_PERMISSIONS_EXTENSION_ID, MAX_GROUP_SIZE,
         MAX_PAST_EPOCHS, MUTABLE_METADATA_EXTENSION_ID,
         SEND_MESSAGE_UPDATE_INSTALLATIONS_INTERVAL_NS,
     },
+    failover::FailoverError,
     hpke::{decrypt_welcome, HpkeError},
     identity::{parse_credential, IdentityError},
     identity_updates::{load_identity_updates, InstallationDiffError},
@@ -95,10 +161,14 @@ use crate::{
         db_connection::DbConnection,
         group::{ConversationType, GroupMembershipState, StoredGroup},
         group_intent::IntentKind,
+        group_membership_change::MembershipChangeKind,
         group_message::{DeliveryStatus, GroupMessageKind, MsgQueryArgs, StoredGroupMessage},
         sql_key_store,
     },
-    subscriptions::{LocalEventError, LocalEvents},
+    subscriptions::{
+        publish_with_backpressure, ConversationRemovalReason, DeliveryStatusUpdate, EpochChange,
+        LocalEventError, LocalEvents,
+    },
     utils::id::calculate_message_id,
     Store, MLS_COMMIT_LOCK,
 };
@@ -216,6 +286,46 @@ pub enum GroupError {
     LockUnavailable,
     #[error("Failed to acquire semaphore lock")]
     LockFailedToAcquire,
+    #[error(transparent)]
+    Attachment(#[from] AttachmentError),
+    #[error(transparent)]
+    Codec(#[from] CodecError),
+    #[error(transparent)]
+    Failover(#[from] FailoverError),
+    #[error(transparent)]
+    CommitValidator(#[from] CommitValidatorError),
+    #[error(transparent)]
+    Scratch(#[from] ScratchError),
+    #[error(transparent)]
+    Forward(#[from] ForwardError),
+    #[error(transparent)]
+    Edit(#[from] EditError),
+    #[error(transparent)]
+    Deletion(#[from] DeletionError),
+    #[error(transparent)]
+    Pin(#[from] PinError),
+    #[error(transparent)]
+    Pagination(#[from] PaginationError),
+    #[error(transparent)]
+    Succession(#[from] SuccessionError),
+    #[error(transparent)]
+    Invite(#[from] InviteError),
+    #[error(transparent)]
+    JoinRequest(#[from] JoinRequestError),
+    #[error(transparent)]
+    Ban(#[from] BanError),
+    #[error(transparent)]
+    Block(#[from] BlockError),
+    #[error(transparent)]
+    Report(#[from] ReportError),
+    #[error(transparent)]
+    Poll(#[from] PollError),
+    #[error(transparent)]
+    Duplicate(#[from] DuplicateError),
+    #[error(transparent)]
+    Archive(#[from] ArchiveError),
+    #[error("the group's send message policy doesn't allow this inbox to send")]
+    SendNotAllowed,
 }
 
 impl RetryableError for GroupError {
@@ -239,9 +349,27 @@ impl RetryableError for GroupError {
             Self::CreateGroupContextExtProposalError(create) => create.is_retryable(),
             Self::CommitValidation(err) => err.is_retryable(),
             Self::WrappedApi(err) => err.is_retryable(),
+            Self::Failover(err) => err.is_retryable(),
+            Self::CommitValidator(err) => err.is_retryable(),
             Self::MessageHistory(err) => err.is_retryable(),
             Self::ProcessIntent(err) => err.is_retryable(),
             Self::LocalEvent(err) => err.is_retryable(),
+            Self::Attachment(err) => err.is_retryable(),
+            Self::Scratch(err) => err.is_retryable(),
+            Self::Forward(err) => err.is_retryable(),
+            Self::Edit(err) => err.is_retryable(),
+            Self::Deletion(err) => err.is_retryable(),
+            Self::Pin(err) => err.is_retryable(),
+            Self::Pagination(err) => err.is_retryable(),
+            Self::Succession(err) => err.is_retryable(),
+            Self::Invite(err) => err.is_retryable(),
+            Self::JoinRequest(err) => err.is_retryable(),
+            Self::Ban(err) => err.is_retryable(),
+            Self::Block(err) => err.is_retryable(),
+            Self::Report(err) => err.is_retryable(),
+            Self::Poll(err) => err.is_retryable(),
+            Self::Duplicate(err) => err.is_retryable(),
+            Self::Archive(err) => err.is_retryable(),
             Self::LockUnavailable => true,
             Self::LockFailedToAcquire => true,
             Self::SyncFailedToWait => true,
@@ -270,7 +398,9 @@ impl RetryableError for GroupError {
             | Self::AddressValidation(_)
             | Self::InvalidPublicKeys(_)
             | Self::CredentialError(_)
-            | Self::EncodeError(_) => false,
+            | Self::EncodeError(_)
+            | Self::SendNotAllowed
+            | Self::Codec(_) => false,
         }
     }
 }
@@ -287,6 +417,15 @@ pub struct ConversationListItem<C> {
     pub last_message: Option<StoredGroupMessage>,
 }
 
+/// What an inbox screen shows about a conversation
+pub struct ConversationOverview<C> {
+    pub group: MlsGroup<C>,
+    pub last_message: Option<StoredGroupMessage>,
+    /// Messages from other members that this inbox hasn't read
+    pub unread_count: u64,
+    pub consent_state: ConsentState,
+}
+
 #[derive(Default)]
 pub struct GroupMetadataOptions {
     pub name: Option<String>,
@@ -360,6 +499,10 @@ impl TryFrom<EncodedContent> for QueryableContentFields {
                 // TODO: Implement JSON deserialization for legacy reaction format
                 None
             }
+            (ReplyCodec::TYPE_ID, _) => content
+                .parameters
+                .get(ReplyCodec::REFERENCE_KEY)
+                .and_then(|reference| hex::decode(reference).ok()),
             _ => None,
         };
 
@@ -500,6 +643,11 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         );
 
         stored_group.store(provider.conn_ref())?;
+        provider.conn_ref().record_membership_changes(
+            &group_id,
+            mls_group.epoch().as_u64() as i64,
+            [(creator_inbox_id.to_string(), MembershipChangeKind::Added)],
+        )?;
         let new_group = Self::new_from_arc(client.clone(), group_id, stored_group.created_at_ns);
 
         // Consent state defaults to allowed when the user creates the group
@@ -570,6 +718,9 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         welcome_id: i64,
     ) -> Result<Self, GroupError> {
         tracing::info!("Creating from welcome");
+        if provider.conn_ref().is_inbox_blocked(&added_by_inbox)? {
+            return Err(BlockError::Blocked(added_by_inbox).into());
+        }
         let mls_welcome =
             StagedWelcome::new_from_welcome(provider, &build_group_join_config(), welcome, None)?;
 
@@ -577,6 +728,13 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         let group_id = mls_group.group_id().to_vec();
         let metadata = extract_group_metadata(&mls_group)?;
         let dm_members = metadata.dm_members;
+        let dm_peer_inbox_id = dm_members.as_ref().map(|members| {
+            if members.member_one_inbox_id == client.inbox_id() {
+                members.member_two_inbox_id.clone()
+            } else {
+                members.member_one_inbox_id.clone()
+            }
+        });
 
         let conversation_type = metadata.conversation_type;
 
@@ -617,15 +775,30 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         // in the `GroupMembership` extension.
         validate_initial_group_membership(client.as_ref(), provider.conn_ref(), &mls_group).await?;
 
+        let members = extract_group_membership(mls_group.extensions())?;
+
         // Insert or replace the group in the database.
         // Replacement can happen in the case that the user has been removed from and subsequently re-added to the group.
         let stored_group = provider.conn_ref().insert_or_replace_group(to_store)?;
+        // Everyone already in the group joined as far as this installation is concerned
+        provider.conn_ref().record_membership_changes(
+            &stored_group.id,
+            mls_group.epoch().as_u64() as i64,
+            members
+                .inbox_ids()
+                .into_iter()
+                .map(|inbox_id| (inbox_id.to_string(), MembershipChangeKind::Added)),
+        )?;
 
-        Ok(Self::new_from_arc(
-            client.clone(),
-            stored_group.id,
-            stored_group.created_at_ns,
-        ))
+        let group = Self::new_from_arc(client.clone(), stored_group.id, stored_group.created_at_ns);
+        // A forked copy of the group was just replaced
+        group.clear_fork(provider.conn_ref());
+        if let Some(peer) = dm_peer_inbox_id {
+            if let Err(e) = group.inherit_contact_consent(provider, &peer).await {
+                tracing::warn!("failed to apply the consent of {peer} to a new DM: {e}");
+            }
+        }
+        Ok(group)
     }
 
     /// Decrypt a welcome message using HPKE and then create and save a group from the stored message
@@ -713,23 +886,49 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         self.send_message_with_provider(message, &provider).await
     }
 
+    /// Encode `content` with the client's codecs and send it. Fails if the client has no codec
+    /// for the content type.
+    pub async fn send_content(
+        &self,
+        content: impl EncodableContent,
+    ) -> Result<Vec<u8>, GroupError> {
+        let encoded = self.context().codecs.encode(content)?;
+        self.send_message(&encoded_content_to_bytes(encoded)).await
+    }
+
     /// Send a message with the given [`XmtpOpenMlsProvider`]
     pub async fn send_message_with_provider(
         &self,
         message: &[u8],
         provider: &XmtpOpenMlsProvider,
     ) -> Result<Vec<u8>, GroupError> {
+        if !self.context().connectivity.is_online() {
+            return self.queue_message(message, provider);
+        }
         let update_interval_ns = Some(SEND_MESSAGE_UPDATE_INSTALLATIONS_INTERVAL_NS);
-        self.maybe_update_installations(provider, update_interval_ns)
-            .await?;
+        match self
+            .maybe_update_installations(provider, update_interval_ns)
+            .await
+        {
+            Err(e) if self.note_offline_error(&e) => return self.queue_message(message, provider),
+            result => result?,
+        }
 
         let message_id =
             self.prepare_message(message, provider, |now| Self::into_envelope(message, now))?;
 
-        self.sync_until_last_intent_resolved(provider).await?;
+        if let Err(e) = self.sync_until_last_intent_resolved(provider).await {
+            if !self.note_offline_error(&e) {
+                return Err(e);
+            }
+            // published by the outbox once the client is back online
+            self.context().outbox.wake();
+            return Ok(message_id);
+        }
 
         // implicitly set group consent state to allowed
         self.update_consent_state(ConsentState::Allowed)?;
+        self.keep_super_admin_heartbeat(provider).await;
 
         Ok(message_id)
     }
@@ -761,10 +960,21 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
     }
 
     /// Send a message, optimistically returning the ID of the message before the result of a message publish.
+    /// The message is published by [`Self::publish_messages`], or by the outbox publisher if it's running.
     pub fn send_message_optimistic(&self, message: &[u8]) -> Result<Vec<u8>, GroupError> {
         let provider = self.mls_provider()?;
+        self.queue_message(message, &provider)
+    }
+
+    /// Store `message` as unpublished and leave it to the outbox to publish
+    fn queue_message(
+        &self,
+        message: &[u8],
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<Vec<u8>, GroupError> {
         let message_id =
-            self.prepare_message(message, &provider, |now| Self::into_envelope(message, now))?;
+            self.prepare_message(message, provider, |now| Self::into_envelope(message, now))?;
+        self.context().outbox.wake();
         Ok(message_id)
     }
 
@@ -800,6 +1010,8 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
     where
         F: FnOnce(i64) -> PlaintextEnvelope,
     {
+        self.ensure_allowed_to_send(provider)?;
+
         let now = now_ns();
         let plain_envelope = envelope(now);
         let mut encoded_envelope = vec![];
@@ -827,12 +1039,96 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
             version_minor: queryable_content_fields.version_minor,
             authority_id: queryable_content_fields.authority_id,
             reference_id: queryable_content_fields.reference_id,
+            deleted_at_ns: None,
         };
         group_message.store(provider.conn_ref())?;
+        self.notify_delivery_status(
+            provider.conn_ref(),
+            message_id.clone(),
+            DeliveryStatus::Unpublished,
+        );
 
         Ok(message_id)
     }
 
+    /// Let local subscribers know the delivery status of a message sent by this installation
+    /// changed, once the transaction open on `conn` commits
+    pub(super) fn notify_delivery_status(
+        &self,
+        conn: &DbConnection,
+        message_id: Vec<u8>,
+        status: DeliveryStatus,
+    ) {
+        self.publish_after_commit(
+            conn,
+            LocalEvents::DeliveryStatusUpdate(DeliveryStatusUpdate {
+                group_id: self.group_id.clone(),
+                message_id,
+                status,
+            }),
+        );
+    }
+
+    /// Let local subscribers know a commit by `actor` moved the group to `epoch`, once the
+    /// transaction open on `conn` commits
+    pub(super) fn notify_epoch_change(
+        &self,
+        conn: &DbConnection,
+        epoch: u64,
+        actor: &CommitParticipant,
+        key_rotation: bool,
+    ) {
+        self.publish_after_commit(
+            conn,
+            LocalEvents::EpochChanged(EpochChange {
+                group_id: self.group_id.clone(),
+                epoch,
+                originator_inbox_id: actor.inbox_id.clone(),
+                originator_installation_id: actor.installation_id.clone(),
+                key_rotation,
+            }),
+        );
+    }
+
+    /// Let local subscribers know this installation is no longer a member, once the
+    /// transaction open on `conn` commits. `inbox_removed` is whether the whole inbox was.
+    pub(super) fn notify_conversation_removed(&self, conn: &DbConnection, inbox_removed: bool) {
+        let reason = if inbox_removed {
+            ConversationRemovalReason::InboxRemoved
+        } else {
+            ConversationRemovalReason::InstallationRemoved
+        };
+        self.publish_after_commit(
+            conn,
+            LocalEvents::ConversationRemoved(self.group_id.clone(), reason),
+        );
+    }
+
+    /// Publish `event` once the transaction open on `conn` commits, so subscribers don't hear
+    /// of changes that are rolled back. Without an open transaction it's published right away.
+    pub(crate) fn publish_after_commit(&self, conn: &DbConnection, event: LocalEvents<()>) {
+        conn.defer_event(event);
+        self.publish_committed_events(conn);
+    }
+
+    /// Publish `event` to local subscribers, waiting for room in the queue if backpressure is
+    /// enabled. Only for events outside of a transaction.
+    pub(crate) async fn publish_local_event(&self, event: LocalEvents<()>) {
+        let sender = self.client.local_events();
+        if let Some(event) = event.for_client() {
+            publish_with_backpressure(sender, self.context().local_event_queue, event).await;
+        }
+    }
+
+    /// Publish the events deferred by transactions on `conn` that have committed
+    pub(crate) fn publish_committed_events(&self, conn: &DbConnection) {
+        for event in conn.take_committed_events() {
+            if let Some(event) = event.for_client() {
+                let _ = self.client.local_events().send(event);
+            }
+        }
+    }
+
     fn into_envelope(encoded_msg: &[u8], idempotency_key: i64) -> PlaintextEnvelope {
         PlaintextEnvelope {
             content: Some(Content::V1(V1 {
@@ -849,10 +1145,20 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         args: &MsgQueryArgs,
     ) -> Result<Vec<StoredGroupMessage>, GroupError> {
         let conn = self.context().store().conn()?;
-        let messages = conn.get_group_messages(&self.group_id, args)?;
+        let messages = conn.get_visible_group_messages(&self.group_id, args)?;
         Ok(messages)
     }
 
+    /// Like [`Self::find_messages`], but only loads each message's metadata, not its content
+    pub fn find_message_headers(
+        &self,
+        args: &MsgQueryArgs,
+    ) -> Result<Vec<StoredGroupMessageHeader>, GroupError> {
+        let conn = self.context().store().conn()?;
+        let headers = conn.get_visible_group_message_headers(&self.group_id, args)?;
+        Ok(headers)
+    }
+
     /// Query the database for stored messages. Optionally filtered by time, kind, delivery_status
     /// and limit
     pub fn find_messages_with_reactions(
@@ -860,7 +1166,7 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         args: &MsgQueryArgs,
     ) -> Result<Vec<StoredGroupMessageWithReactions>, GroupError> {
         let conn = self.context().store().conn()?;
-        let messages = conn.get_group_messages_with_reactions(&self.group_id, args)?;
+        let messages = conn.get_visible_group_messages_with_reactions(&self.group_id, args)?;
         Ok(messages)
     }
 
@@ -917,6 +1223,7 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         inbox_ids: &[S],
     ) -> Result<(), GroupError> {
         let ids = inbox_ids.iter().map(AsRef::as_ref).collect::<Vec<&str>>();
+        self.ensure_not_banned(provider, &ids)?;
         let intent_data = self
             .get_membership_update_intent(provider, ids.as_slice(), &[])
             .await?;
@@ -1249,7 +1556,8 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
             ConsentType::ConversationId,
             state,
             hex::encode(self.group_id.clone()),
-        );
+        )
+        .with_hlc(self.context().hlc.now());
         let new_records = conn
             .insert_or_replace_consent_records(&[consent_record.clone()])?
             .into_iter()
@@ -1484,6 +1792,24 @@ pub fn build_extensions_for_metadata_update(
     Ok(extensions)
 }
 
+/// The extensions of `group` with the list change of `intent_data` applied, or `None` if it
+/// doesn't change the list
+pub fn build_extensions_for_metadata_list_update(
+    group: &OpenMlsGroup,
+    intent_data: &UpdateMetadataListIntentData,
+) -> Result<Option<Extensions>, GroupError> {
+    let existing_metadata: GroupMutableMetadata = group.try_into()?;
+    let list = existing_metadata
+        .attributes
+        .get(&intent_data.field_name)
+        .map(String::as_str)
+        .unwrap_or_default();
+    let Some(list) = intent_data.apply(list)? else {
+        return Ok(None);
+    };
+    build_extensions_for_metadata_update(group, intent_data.field_name.clone(), list).map(Some)
+}
+
 #[tracing::instrument(level = "trace", skip_all)]
 pub fn build_extensions_for_permissions_update(
     group: &OpenMlsGroup,
@@ -1543,6 +1869,21 @@ pub fn build_extensions_for_permissions_update(
                 existing_policy_set.update_permissions_policy,
             )
         }
+        PermissionUpdateType::SendMessage => {
+            let mut metadata_policy = existing_policy_set.update_metadata_policy.clone();
+            metadata_policy.insert(
+                SEND_MESSAGE_POLICY_KEY.to_string(),
+                update_permissions_intent.policy_option.into(),
+            );
+            PolicySet::new(
+                existing_policy_set.add_member_policy,
+                existing_policy_set.remove_member_policy,
+                metadata_policy,
+                existing_policy_set.add_admin_policy,
+                existing_policy_set.remove_admin_policy,
+                existing_policy_set.update_permissions_policy,
+            )
+        }
     };
     let new_group_permissions: Vec<u8> = GroupMutablePermissions::new(new_policy_set).try_into()?;
     let unknown_gc_extension = UnknownExtension(new_group_permissions);
@@ -1586,6 +1927,36 @@ pub fn build_extensions_for_admin_lists_update(
     Ok(extensions)
 }
 
+/// Accept the super admin role offered to `inbox_id`: make them super admin, demote the member
+/// who offered the role and withdraw the offer, all in the same extensions update
+pub fn build_extensions_for_super_admin_transfer_acceptance(
+    group: &OpenMlsGroup,
+    inbox_id: &str,
+) -> Result<Extensions, GroupError> {
+    let existing_metadata: GroupMutableMetadata = group.try_into()?;
+    let transfer = SuperAdminTransfer::from_metadata(&existing_metadata)
+        .filter(|transfer| transfer.to_inbox_id == inbox_id)
+        .ok_or(SuccessionError::NoTransferOffered)?;
+    let mut attributes = existing_metadata.attributes.clone();
+    attributes.insert(
+        SUPER_ADMIN_TRANSFER_METADATA_FIELD.to_string(),
+        String::new(),
+    );
+    let mut super_admin_list = existing_metadata.super_admin_list;
+    super_admin_list.retain(|x| x != &transfer.from_inbox_id);
+    if !super_admin_list.iter().any(|x| x == inbox_id) {
+        super_admin_list.push(inbox_id.to_string());
+    }
+    let new_mutable_metadata: Vec<u8> =
+        GroupMutableMetadata::new(attributes, existing_metadata.admin_list, super_admin_list)
+            .try_into()?;
+    let unknown_gc_extension = UnknownExtension(new_mutable_metadata);
+    let extension = Extension::Unknown(MUTABLE_METADATA_EXTENSION_ID, unknown_gc_extension);
+    let mut extensions = group.extensions().clone();
+    extensions.add_or_replace(extension);
+    Ok(extensions)
+}
+
 pub fn build_starting_group_membership_extension(inbox_id: &str, sequence_id: u64) -> Extension {
     let mut group_membership = GroupMembership::new();
     group_membership.add(inbox_id.to_string(), sequence_id);
@@ -2008,6 +2379,48 @@ pub(crate) mod tests {
         }
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_members_delta() {
+        let amal = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bola = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let charlie = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let amal_group = amal
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let delta = amal_group.members_delta(None).unwrap();
+        assert_eq!(delta.added, vec![amal.inbox_id().to_string()]);
+
+        amal_group
+            .add_members_by_inbox_id(&[bola.inbox_id(), charlie.inbox_id()])
+            .await
+            .unwrap();
+        let delta = amal_group.members_delta(Some(delta.epoch)).unwrap();
+        assert_eq!(delta.added.len(), 2);
+        assert!(delta.removed.is_empty());
+
+        amal_group
+            .remove_members_by_inbox_id(&[charlie.inbox_id()])
+            .await
+            .unwrap();
+        let next = amal_group.members_delta(Some(delta.epoch)).unwrap();
+        assert!(next.added.is_empty());
+        assert_eq!(next.removed, vec![charlie.inbox_id().to_string()]);
+
+        // A member that joined later sees everyone present when it joined as added
+        let bola_group = bola
+            .sync_welcomes(&bola.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+        bola_group.sync().await.unwrap();
+        let mut added = bola_group.members_delta(None).unwrap().added;
+        added.sort();
+        let mut expected = vec![amal.inbox_id().to_string(), bola.inbox_id().to_string()];
+        expected.sort();
+        assert_eq!(added, expected);
+    }
+
     // Amal and Bola will both try and add Charlie from the same epoch.
     // The group should resolve to a consistent state
     #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
@@ -0,0 +1,113 @@
+//! Per-conversation notification settings. They're local to the user, not shared with the group,
+//! and synced to the user's other installations through the sync group, so muting a conversation
+//! on one device mutes it on all of them. Notification layers can list muted conversations with
+//! [`GroupQueryArgs::muted`](crate::storage::group::GroupQueryArgs::muted) before waking the UI.
+//! Concurrent changes on different installations are ordered by their
+//! [hybrid logical clock](crate::hlc) timestamp, so every installation keeps the same settings.
+use xmtp_common::time::now_ns;
+
+use super::{
+    device_sync::preference_sync::UserPreferenceUpdate, GroupError, MlsGroup, ScopedGroupClient,
+};
+use crate::{
+    hlc::Hlc, storage::notification_settings::StoredNotificationSettings,
+    subscriptions::LocalEvents,
+};
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Silence notifications for the conversation until `mute_until_ns`, or unmute it with
+    /// `None`. With `mentions_only`, only messages that mention the user notify.
+    pub fn set_notification_settings(
+        &self,
+        mute_until_ns: Option<i64>,
+        mentions_only: bool,
+    ) -> Result<(), GroupError> {
+        let conn = self.context().store().conn()?;
+        let settings = StoredNotificationSettings::new(
+            self.group_id.clone(),
+            mute_until_ns,
+            mentions_only,
+            self.context().hlc.now(),
+        );
+        conn.set_notification_settings(&settings)?;
+
+        if self.client.history_sync_url().is_some() {
+            // Dispatch an update event so it can be synced across devices
+            let _ = self
+                .client
+                .local_events()
+                .send(LocalEvents::OutgoingPreferenceUpdates(vec![
+                    UserPreferenceUpdate::NotificationSettingsUpdate(settings),
+                ]));
+        }
+        Ok(())
+    }
+
+    /// The conversation's notification settings, which are unmuted if they were never changed
+    pub fn notification_settings(&self) -> Result<StoredNotificationSettings, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn
+            .get_notification_settings(&self.group_id)?
+            .unwrap_or_else(|| {
+                StoredNotificationSettings::new(self.group_id.clone(), None, false, Hlc::default())
+            }))
+    }
+
+    pub fn is_muted(&self) -> Result<bool, GroupError> {
+        Ok(self.notification_settings()?.is_muted(now_ns()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions, storage::group::GroupQueryArgs,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_common::time::now_ns;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_mute_conversation() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let muted = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let other = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        assert!(!muted.is_muted().unwrap());
+
+        muted
+            .set_notification_settings(Some(now_ns() + 60_000_000_000), true)
+            .unwrap();
+        assert!(muted.is_muted().unwrap());
+        assert!(muted.notification_settings().unwrap().mentions_only);
+
+        let muted_groups = alix
+            .find_groups(GroupQueryArgs::default().muted(true))
+            .unwrap();
+        assert_eq!(muted_groups.len(), 1);
+        assert_eq!(muted_groups[0].group_id, muted.group_id);
+        let unmuted_groups = alix
+            .find_groups(GroupQueryArgs::default().muted(false))
+            .unwrap();
+        assert_eq!(unmuted_groups.len(), 1);
+        assert_eq!(unmuted_groups[0].group_id, other.group_id);
+
+        // a mute that has run out no longer counts
+        muted
+            .set_notification_settings(Some(now_ns() - 1), false)
+            .unwrap();
+        assert!(!muted.is_muted().unwrap());
+        assert_eq!(
+            alix.find_groups(GroupQueryArgs::default().muted(false))
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
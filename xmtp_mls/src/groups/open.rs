@@ -0,0 +1,269 @@
+//! Opening a conversation in the UI. An open conversation is synced ahead of the others, and the
+//! page of older messages the user is likely to scroll to next is loaded in the background, along
+//! with the thumbnails of its attachments when the app has a [`BlobStore`].
+//!
+//! The prefetched page grows the further the user scrolls, up to [`MAX_PREFETCH_PAGES`] pages,
+//! and is dropped after [`PREFETCHED_PAGE_TTL`] so edits and deletions don't go unseen for long.
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::{Mutex, RwLock};
+use xmtp_common::time::{Duration, Instant};
+use xmtp_content_types::remote_attachment::RemoteAttachmentCodec;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use super::{
+    attachments::BlobStore, pagination::MessagePage, GroupError, MlsGroup, ScopedGroupClient,
+};
+use crate::{
+    configuration::{DEFAULT_MESSAGE_PAGE_SIZE, MAX_PREFETCH_PAGES},
+    storage::group_message::{
+        ContentType, GroupMessageKind, MsgQueryArgs, SortDirection, StoredGroupMessage,
+    },
+    Client, XmtpApi,
+};
+
+/// How long a prefetched page may be handed out
+pub const PREFETCHED_PAGE_TTL: Duration = Duration::from_secs(30);
+
+struct PrefetchedPage {
+    /// The cursor the page continues from
+    cursor: String,
+    page: MessagePage<StoredGroupMessage>,
+    fetched_at: Instant,
+}
+
+#[derive(Default)]
+struct OpenConversation {
+    /// Pages of older messages handed out since the conversation was opened
+    pages_served: i64,
+    prefetched: Option<PrefetchedPage>,
+}
+
+/// The conversations open in the UI, by group id
+#[derive(Default)]
+pub struct OpenConversations {
+    entries: Mutex<HashMap<Vec<u8>, OpenConversation>>,
+    blob_store: RwLock<Option<Arc<dyn BlobStore>>>,
+}
+
+impl OpenConversations {
+    pub fn is_open(&self, group_id: &[u8]) -> bool {
+        self.entries.lock().contains_key(group_id)
+    }
+
+    /// Take the page prefetched for `cursor` if it's still fresh, and count a page as served
+    fn serve(&self, group_id: &[u8], cursor: &str) -> Option<MessagePage<StoredGroupMessage>> {
+        let mut entries = self.entries.lock();
+        let entry = entries.get_mut(group_id)?;
+        entry.pages_served += 1;
+        entry
+            .prefetched
+            .take()
+            .filter(|prefetched| {
+                prefetched.cursor == cursor && prefetched.fetched_at.elapsed() < PREFETCHED_PAGE_TTL
+            })
+            .map(|prefetched| prefetched.page)
+    }
+
+    /// The size of the next page to prefetch for `group_id`
+    fn prefetch_size(&self, group_id: &[u8]) -> i64 {
+        let pages_served = self
+            .entries
+            .lock()
+            .get(group_id)
+            .map_or(0, |entry| entry.pages_served);
+        DEFAULT_MESSAGE_PAGE_SIZE * (pages_served + 1).min(MAX_PREFETCH_PAGES)
+    }
+
+    fn store_prefetched(
+        &self,
+        group_id: &[u8],
+        cursor: String,
+        page: MessagePage<StoredGroupMessage>,
+    ) {
+        // the conversation may have been closed in the meantime
+        if let Some(entry) = self.entries.lock().get_mut(group_id) {
+            entry.prefetched = Some(PrefetchedPage {
+                cursor,
+                page,
+                fetched_at: Instant::now(),
+            });
+        }
+    }
+}
+
+/// The application messages of a conversation, newest first
+fn open_query(limit: i64) -> MsgQueryArgs {
+    MsgQueryArgs {
+        kind: Some(GroupMessageKind::Application),
+        direction: Some(SortDirection::Descending),
+        limit: Some(limit),
+        ..Default::default()
+    }
+}
+
+/// The URLs of the remote attachments among `messages`
+fn attachment_urls(messages: &[StoredGroupMessage]) -> Vec<String> {
+    messages
+        .iter()
+        .filter(|message| message.content_type == ContentType::RemoteAttachment)
+        .filter_map(|message| message.encoded_content().ok())
+        .filter(|content| {
+            content
+                .r#type
+                .as_ref()
+                .is_some_and(|id| id.type_id == RemoteAttachmentCodec::TYPE_ID)
+        })
+        .filter_map(|content| String::from_utf8(content.content).ok())
+        .collect()
+}
+
+impl<ScopedClient> MlsGroup<ScopedClient>
+where
+    ScopedClient: ScopedGroupClient + 'static,
+{
+    /// Mark the conversation as open in the UI and return its newest page of messages, newest
+    /// first. Call [`Self::close`] once the conversation is no longer on screen.
+    pub fn open(&self) -> Result<MessagePage<StoredGroupMessage>, GroupError> {
+        self.context()
+            .open_conversations
+            .entries
+            .lock()
+            .insert(self.group_id.clone(), OpenConversation::default());
+        let page = self.find_messages_page(&open_query(DEFAULT_MESSAGE_PAGE_SIZE), None)?;
+        self.prefetch_after(&page);
+        Ok(page)
+    }
+
+    /// The page of messages older than `cursor`, from a page returned by [`Self::open`] or a
+    /// previous call
+    pub fn older_messages(
+        &self,
+        cursor: &str,
+    ) -> Result<MessagePage<StoredGroupMessage>, GroupError> {
+        let open = &self.context().open_conversations;
+        let page = match open.serve(&self.group_id, cursor) {
+            Some(page) => page,
+            None => {
+                self.find_messages_page(&open_query(DEFAULT_MESSAGE_PAGE_SIZE), Some(cursor))?
+            }
+        };
+        self.prefetch_after(&page);
+        Ok(page)
+    }
+
+    /// Stop treating the conversation as open and drop its prefetched messages
+    pub fn close(&self) {
+        self.context()
+            .open_conversations
+            .entries
+            .lock()
+            .remove(&self.group_id);
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.context().open_conversations.is_open(&self.group_id)
+    }
+
+    /// Load the page after `page` and warm the thumbnails of both in the background
+    fn prefetch_after(&self, page: &MessagePage<StoredGroupMessage>) {
+        let open = &self.context().open_conversations;
+        let blob_store = open.blob_store.read().clone();
+        let mut urls = attachment_urls(&page.items);
+        let next = page
+            .next_cursor
+            .clone()
+            .map(|cursor| (cursor, open.prefetch_size(&self.group_id)));
+        if next.is_none() && (urls.is_empty() || blob_store.is_none()) {
+            return;
+        }
+
+        let group = self.clone();
+        crate::spawn(None, async move {
+            if let Some((cursor, size)) = next {
+                match group.find_messages_page(&open_query(size), Some(&cursor)) {
+                    Ok(page) => {
+                        urls.extend(attachment_urls(&page.items));
+                        group.context().open_conversations.store_prefetched(
+                            &group.group_id,
+                            cursor,
+                            page,
+                        );
+                    }
+                    Err(e) => tracing::warn!(
+                        group_id = hex::encode(&group.group_id),
+                        "failed to prefetch messages: {e}"
+                    ),
+                }
+            }
+            let Some(blob_store) = blob_store else {
+                return;
+            };
+            for url in urls {
+                if let Err(e) = blob_store.warm_thumbnail(&url).await {
+                    tracing::debug!("failed to warm attachment thumbnail: {e}");
+                }
+            }
+        });
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Warm attachment thumbnails of open conversations into `blob_store`, or stop with `None`
+    pub fn set_blob_store(&self, blob_store: Option<Arc<dyn BlobStore>>) {
+        *self.context.open_conversations.blob_store.write() = blob_store;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_open_prefetches_older_messages() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        for i in 0..DEFAULT_MESSAGE_PAGE_SIZE + 10 {
+            group
+                .send_message_optimistic(format!("{i}").as_bytes())
+                .unwrap();
+        }
+
+        let first = group.open().unwrap();
+        assert!(group.is_open());
+        assert_eq!(first.items.len() as i64, DEFAULT_MESSAGE_PAGE_SIZE);
+        assert_eq!(first.items[0].decrypted_message_bytes, b"59");
+        let cursor = first.next_cursor.unwrap();
+
+        // wait for the background prefetch
+        let open = &alix.context.open_conversations;
+        xmtp_common::wait_for_some(|| async {
+            open.entries
+                .lock()
+                .get(&group.group_id)
+                .and_then(|entry| entry.prefetched.as_ref().map(|_| ()))
+        })
+        .await
+        .unwrap();
+        let older = group.older_messages(&cursor).unwrap();
+        assert_eq!(older.items.len(), 10);
+        assert_eq!(older.items[0].decrypted_message_bytes, b"9");
+        assert!(older.next_cursor.is_none());
+
+        group.close();
+        assert!(!group.is_open());
+        assert!(open.entries.lock().is_empty());
+    }
+}
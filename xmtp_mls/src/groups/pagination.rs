@@ -0,0 +1,385 @@
+//! Paging through a group's messages with cursors. A page's cursors hold the position of the
+//! messages at its edges rather than an offset, so paging stays consistent for infinite scroll
+//! UIs while new messages arrive or old ones are deleted.
+//!
+//! [`MlsGroup::find_messages_page`] pages through one query in its sort direction and back.
+//! [`MlsGroup::find_messages_paged`] pages through history by time instead, loading the messages
+//! just before or just after a position, e.g. older messages while scrolling up and newer ones
+//! as they arrive.
+use thiserror::Error;
+use xmtp_common::RetryableError;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    configuration::DEFAULT_MESSAGE_PAGE_SIZE,
+    storage::{
+        db_connection::DbConnection,
+        group_message::{MessageCursor, MsgQueryArgs, SortDirection, StoredGroupMessage},
+    },
+};
+
+#[derive(Debug, Error)]
+pub enum PaginationError {
+    #[error("invalid page cursor")]
+    InvalidCursor,
+}
+
+impl RetryableError for PaginationError {
+    fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+/// One page of a message query, in the query's sort direction
+#[derive(Debug, Clone)]
+pub struct MessagePage<T> {
+    pub items: Vec<T>,
+    /// Continues the query past the last item. `None` once there are no more messages.
+    pub next_cursor: Option<String>,
+    /// Goes back from the first item, towards the start of the query. `None` on the first page.
+    pub prev_cursor: Option<String>,
+    /// How many messages match the query's filters overall, as of this page
+    pub total_estimate: u64,
+}
+
+/// The decoded form of the opaque cursors handed out with a [`MessagePage`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PageCursor {
+    position: MessageCursor,
+    /// Whether the cursor pages against the query's sort direction
+    backward: bool,
+}
+
+impl PageCursor {
+    fn encode(&self) -> String {
+        format!(
+            "{}:{}",
+            if self.backward { "b" } else { "f" },
+            self.position.to_token()
+        )
+    }
+
+    fn decode(cursor: &str) -> Result<Self, PaginationError> {
+        let backward = match cursor.split_once(':') {
+            Some(("f", _)) => false,
+            Some(("b", _)) => true,
+            _ => return Err(PaginationError::InvalidCursor),
+        };
+        Ok(Self {
+            position: decode_token(&cursor[2..])?,
+            backward,
+        })
+    }
+}
+
+fn decode_token(token: &str) -> Result<MessageCursor, PaginationError> {
+    MessageCursor::from_token(token).ok_or(PaginationError::InvalidCursor)
+}
+
+/// Messages loaded by [`MlsGroup::find_messages_paged`], in the query's sort direction
+#[derive(Debug, Clone)]
+pub struct PagedMessages<T> {
+    pub items: Vec<T>,
+    /// Loads the messages sent before these ones. `None` once the start of the history is
+    /// reached.
+    pub before_cursor: Option<String>,
+    /// Loads the messages sent after these ones. Messages keep arriving, so this is only `None`
+    /// when no messages have been loaded at all.
+    pub after_cursor: Option<String>,
+}
+
+fn reversed(direction: &SortDirection) -> SortDirection {
+    match direction {
+        SortDirection::Ascending => SortDirection::Descending,
+        SortDirection::Descending => SortDirection::Ascending,
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Load a page of the messages matching `args`, starting at `cursor` from a previous page or
+    /// at the start of the query without one. `args.limit` sets the page size, and its cursors
+    /// are ignored.
+    pub fn find_messages_page(
+        &self,
+        args: &MsgQueryArgs,
+        cursor: Option<&str>,
+    ) -> Result<MessagePage<StoredGroupMessage>, GroupError> {
+        let cursor = cursor.map(PageCursor::decode).transpose()?;
+        let backward = cursor.as_ref().is_some_and(|cursor| cursor.backward);
+        let direction = args.direction.clone().unwrap_or(SortDirection::Ascending);
+        let scan = if backward {
+            reversed(&direction)
+        } else {
+            direction.clone()
+        };
+        let position = cursor.as_ref().map(|cursor| cursor.position.clone());
+        let (before, after) = match scan {
+            SortDirection::Ascending => (None, position),
+            SortDirection::Descending => (position, None),
+        };
+
+        let conn = self.context().store().conn()?;
+        let (items, has_more) = self.load_page(&conn, args, before, after, scan, direction)?;
+
+        // Empty pages keep the position they were loaded from
+        let start = items
+            .first()
+            .map(MessageCursor::at)
+            .or_else(|| cursor.as_ref().map(|cursor| cursor.position.clone()));
+        let end = items
+            .last()
+            .map(MessageCursor::at)
+            .or_else(|| cursor.as_ref().map(|cursor| cursor.position.clone()));
+        let (has_next, has_prev) = if backward {
+            (true, has_more)
+        } else {
+            (has_more, cursor.is_some())
+        };
+        let next_cursor = end.filter(|_| has_next).map(|position| {
+            PageCursor {
+                position,
+                backward: false,
+            }
+            .encode()
+        });
+        let prev_cursor = start.filter(|_| has_prev).map(|position| {
+            PageCursor {
+                position,
+                backward: true,
+            }
+            .encode()
+        });
+
+        Ok(MessagePage {
+            items,
+            next_cursor,
+            prev_cursor,
+            total_estimate: conn.count_visible_group_messages(&self.group_id, args)? as u64,
+        })
+    }
+
+    /// Load up to `args.limit` of the messages matching `args` that were sent just before
+    /// `before_cursor`, just after `after_cursor`, or between the two. Without a cursor the
+    /// first messages in `args.direction` are loaded. The cursors come from a previous
+    /// [`PagedMessages`], and the cursors in `args` are ignored.
+    pub fn find_messages_paged(
+        &self,
+        args: &MsgQueryArgs,
+        before_cursor: Option<&str>,
+        after_cursor: Option<&str>,
+    ) -> Result<PagedMessages<StoredGroupMessage>, GroupError> {
+        let before = before_cursor.map(decode_token).transpose()?;
+        let after = after_cursor.map(decode_token).transpose()?;
+        let direction = args.direction.clone().unwrap_or(SortDirection::Ascending);
+        // Load the messages closest to the cursor the page starts from
+        let query_direction = match (&before, &after) {
+            (Some(_), None) => SortDirection::Descending,
+            (None, Some(_)) => SortDirection::Ascending,
+            _ => direction.clone(),
+        };
+
+        let conn = self.context().store().conn()?;
+        let (items, has_more) = self.load_page(
+            &conn,
+            args,
+            before.clone(),
+            after.clone(),
+            query_direction.clone(),
+            direction.clone(),
+        )?;
+        let has_older = match query_direction {
+            SortDirection::Ascending => after.is_some(),
+            SortDirection::Descending => has_more,
+        };
+
+        let (oldest, newest) = match direction {
+            SortDirection::Ascending => (items.first(), items.last()),
+            SortDirection::Descending => (items.last(), items.first()),
+        };
+        let before_cursor = oldest
+            .map(MessageCursor::at)
+            .or_else(|| after.clone())
+            .filter(|_| has_older)
+            .map(|position| position.to_token());
+        let after_cursor = newest
+            .map(MessageCursor::at)
+            .or(before)
+            .or(after)
+            .map(|position| position.to_token());
+
+        Ok(PagedMessages {
+            items,
+            before_cursor,
+            after_cursor,
+        })
+    }
+
+    /// Up to `args.limit` of the messages matching `args` that were sent before `before` and
+    /// after `after`, and whether there are more past them. The messages closest to the cursor
+    /// in the `scan` direction are loaded, and returned in `direction`.
+    fn load_page(
+        &self,
+        conn: &DbConnection,
+        args: &MsgQueryArgs,
+        before: Option<MessageCursor>,
+        after: Option<MessageCursor>,
+        scan: SortDirection,
+        direction: SortDirection,
+    ) -> Result<(Vec<StoredGroupMessage>, bool), GroupError> {
+        let page_size = args.limit.unwrap_or(DEFAULT_MESSAGE_PAGE_SIZE).max(1);
+        let query = MsgQueryArgs {
+            cursor: None,
+            before_cursor: before,
+            after_cursor: after,
+            direction: Some(scan.clone()),
+            limit: Some(page_size + 1),
+            ..args.clone()
+        };
+        let mut items = conn.get_visible_group_messages(&self.group_id, &query)?;
+        let has_more = items.len() as i64 > page_size;
+        items.truncate(page_size as usize);
+        if scan != direction {
+            items.reverse();
+        }
+        Ok((items, has_more))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions,
+        storage::group_message::GroupMessageKind,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_message_pages_survive_new_messages() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        for i in 0..5 {
+            group.send_message(format!("{i}").as_bytes()).await.unwrap();
+        }
+        let args = MsgQueryArgs {
+            kind: Some(GroupMessageKind::Application),
+            direction: Some(SortDirection::Descending),
+            limit: Some(2),
+            ..Default::default()
+        };
+
+        let first = group.find_messages_page(&args, None).unwrap();
+        assert_eq!(first.items.len(), 2);
+        assert_eq!(first.total_estimate, 5);
+        assert!(first.prev_cursor.is_none());
+
+        // a message arriving mid scroll doesn't shift the following pages
+        let newest = group.send_message(b"new").await.unwrap();
+        let mut seen: Vec<Vec<u8>> = first.items.iter().map(|m| m.id.clone()).collect();
+        let mut next_cursor = first.next_cursor.clone();
+        while let Some(cursor) = next_cursor {
+            let page = group.find_messages_page(&args, Some(&cursor)).unwrap();
+            seen.extend(page.items.iter().map(|m| m.id.clone()));
+            next_cursor = page.next_cursor;
+        }
+        let expected: Vec<Vec<u8>> = group
+            .find_messages(&MsgQueryArgs {
+                limit: None,
+                ..args.clone()
+            })
+            .unwrap()
+            .into_iter()
+            .map(|m| m.id)
+            .filter(|id| *id != newest)
+            .collect();
+        assert_eq!(seen, expected);
+
+        // paging back from the first page finds the new message
+        let second = group
+            .find_messages_page(&args, first.next_cursor.as_deref())
+            .unwrap();
+        let back = group
+            .find_messages_page(&args, second.prev_cursor.as_deref())
+            .unwrap();
+        assert_eq!(back.items.len(), 2);
+        assert_eq!(back.items[0].id, first.items[0].id);
+        let newer = group
+            .find_messages_page(&args, back.prev_cursor.as_deref())
+            .unwrap();
+        assert_eq!(newer.items.len(), 1);
+        assert_eq!(newer.items[0].id, newest);
+        assert!(newer.prev_cursor.is_none());
+
+        assert!(matches!(
+            group.find_messages_page(&args, Some("garbage")),
+            Err(GroupError::Pagination(PaginationError::InvalidCursor))
+        ));
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_messages_paged_before_and_after() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let mut sent = vec![];
+        for i in 0..5 {
+            sent.push(group.send_message(format!("{i}").as_bytes()).await.unwrap());
+        }
+        let args = MsgQueryArgs {
+            kind: Some(GroupMessageKind::Application),
+            direction: Some(SortDirection::Descending),
+            limit: Some(2),
+            ..Default::default()
+        };
+        let ids = |page: &PagedMessages<StoredGroupMessage>| -> Vec<Vec<u8>> {
+            page.items.iter().map(|m| m.id.clone()).collect()
+        };
+
+        // the newest messages, then older ones while scrolling up
+        let latest = group.find_messages_paged(&args, None, None).unwrap();
+        assert_eq!(ids(&latest), vec![sent[4].clone(), sent[3].clone()]);
+        let older = group
+            .find_messages_paged(&args, latest.before_cursor.as_deref(), None)
+            .unwrap();
+        assert_eq!(ids(&older), vec![sent[2].clone(), sent[1].clone()]);
+        let oldest = group
+            .find_messages_paged(&args, older.before_cursor.as_deref(), None)
+            .unwrap();
+        assert_eq!(ids(&oldest), vec![sent[0].clone()]);
+        assert!(oldest.before_cursor.is_none());
+
+        // messages arriving after the newest page
+        let empty = group
+            .find_messages_paged(&args, None, latest.after_cursor.as_deref())
+            .unwrap();
+        assert!(empty.items.is_empty());
+        assert_eq!(empty.after_cursor, latest.after_cursor);
+        let newest = group.send_message(b"new").await.unwrap();
+        let newer = group
+            .find_messages_paged(&args, None, latest.after_cursor.as_deref())
+            .unwrap();
+        assert_eq!(ids(&newer), vec![newest]);
+
+        // between two cursors
+        let between = group
+            .find_messages_paged(
+                &args,
+                latest.before_cursor.as_deref(),
+                oldest.after_cursor.as_deref(),
+            )
+            .unwrap();
+        assert_eq!(ids(&between), vec![sent[2].clone(), sent[1].clone()]);
+
+        assert!(matches!(
+            group.find_messages_paged(&args, Some("garbage"), None),
+            Err(GroupError::Pagination(PaginationError::InvalidCursor))
+        ));
+    }
+}
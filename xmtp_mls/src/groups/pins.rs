@@ -0,0 +1,250 @@
+//! Pinned messages. A group's pins live in its mutable metadata, so every member sees the same
+//! ones and who may change them is governed by the group's metadata permission policy, like any
+//! other metadata field. Groups created before pins existed have no policy for the field, so
+//! only their admins can pin messages.
+//!
+//! The field holds the hex encoded ids of the pinned messages, most recently pinned first,
+//! separated by commas. Each pin or unpin adds or removes its own entry of the list as it is
+//! when the change is published, so members pinning messages at the same time don't undo each
+//! other's pins.
+use thiserror::Error;
+use xmtp_common::RetryableError;
+
+use super::{
+    group_mutable_metadata::{GroupMutableMetadata, MetadataField},
+    intents::{MetadataListAction, UpdateMetadataListIntentData},
+    validated_commit::ValidatedCommit,
+    GroupError, MlsGroup, ScopedGroupClient,
+};
+use crate::{
+    configuration::MAX_PINNED_MESSAGES,
+    storage::{
+        group_intent::IntentKind,
+        group_message::{ContentType, GroupMessageKind, StoredGroupMessage},
+        NotFound, StorageError,
+    },
+    subscriptions::{LocalEvents, MessagePin},
+};
+
+#[derive(Debug, Error)]
+pub enum PinError {
+    #[error("message is already pinned")]
+    AlreadyPinned,
+    #[error("message is not pinned")]
+    NotPinned,
+    #[error("a group can have at most {0} pinned messages")]
+    TooManyPinned(usize),
+    #[error("{0} messages can't be pinned")]
+    NotPinnable(ContentType),
+}
+
+impl RetryableError for PinError {
+    fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+/// The ids of the messages pinned in `metadata`, most recently pinned first. Malformed entries
+/// are skipped.
+pub fn pinned_message_ids(metadata: &GroupMutableMetadata) -> Vec<Vec<u8>> {
+    metadata
+        .attributes
+        .get(MetadataField::PinnedMessages.as_str())
+        .map(|pins| parse_pins(pins))
+        .unwrap_or_default()
+}
+
+fn parse_pins(pins: &str) -> Vec<Vec<u8>> {
+    pins.split(',')
+        .filter(|pin| !pin.is_empty())
+        .filter_map(|pin| hex::decode(pin).ok())
+        .collect()
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Pin `message_id` for every member of the group. Requires permission to update the
+    /// pinned messages metadata.
+    pub async fn pin_message(&self, message_id: &[u8]) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        let message = provider
+            .conn_ref()
+            .get_group_message(message_id)?
+            .filter(|message| message.group_id == self.group_id)
+            .ok_or_else(|| StorageError::from(NotFound::MessageById(message_id.to_vec())))?;
+        if message.kind != GroupMessageKind::Application || message.deleted_at_ns.is_some() {
+            return Err(PinError::NotPinnable(message.content_type).into());
+        }
+
+        let pins = pinned_message_ids(&self.mutable_metadata(&provider)?);
+        if pins.iter().any(|pin| pin == message_id) {
+            return Err(PinError::AlreadyPinned.into());
+        }
+        if pins.len() >= MAX_PINNED_MESSAGES {
+            return Err(PinError::TooManyPinned(MAX_PINNED_MESSAGES).into());
+        }
+        self.update_pins(message_id, MetadataListAction::AddFirst)
+            .await
+    }
+
+    /// Unpin `message_id` for every member of the group. Requires permission to update the
+    /// pinned messages metadata.
+    pub async fn unpin_message(&self, message_id: &[u8]) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        let pins = pinned_message_ids(&self.mutable_metadata(&provider)?);
+        if !pins.iter().any(|pin| pin == message_id) {
+            return Err(PinError::NotPinned.into());
+        }
+        self.update_pins(message_id, MetadataListAction::Remove)
+            .await
+    }
+
+    async fn update_pins(
+        &self,
+        message_id: &[u8],
+        action: MetadataListAction,
+    ) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        let intent_data: Vec<u8> = UpdateMetadataListIntentData::new(
+            MetadataField::PinnedMessages,
+            hex::encode(message_id),
+            action,
+            Some(MAX_PINNED_MESSAGES),
+        )
+        .into();
+        let intent = self.queue_intent(&provider, IntentKind::UpdateMetadataList, intent_data)?;
+
+        self.sync_until_intent_resolved(&provider, intent.id).await
+    }
+
+    /// The group's pinned messages as of its last sync, most recently pinned first. Pinned
+    /// messages that aren't stored on this installation, e.g. because they were sent before it
+    /// joined, are left out.
+    pub fn pinned_messages(&self) -> Result<Vec<StoredGroupMessage>, GroupError> {
+        let provider = self.mls_provider()?;
+        let pins = pinned_message_ids(&self.mutable_metadata(&provider)?);
+        let mut messages = Vec::with_capacity(pins.len());
+        for pin in pins {
+            if let Some(message) = provider
+                .conn_ref()
+                .get_group_message(&pin)?
+                .filter(|message| message.group_id == self.group_id)
+            {
+                messages.push(message);
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Emit a [`LocalEvents::MessagePinned`] for each message pinned or unpinned by
+    /// `validated_commit`
+    pub(super) fn process_pin_changes(&self, validated_commit: &ValidatedCommit) {
+        let Some(change) = validated_commit
+            .metadata_changes
+            .metadata_field_changes
+            .iter()
+            .find(|change| change.field_name == MetadataField::PinnedMessages.as_str())
+        else {
+            return;
+        };
+        let old = change
+            .old_value
+            .as_deref()
+            .map(parse_pins)
+            .unwrap_or_default();
+        let new = change
+            .new_value
+            .as_deref()
+            .map(parse_pins)
+            .unwrap_or_default();
+        let added = new
+            .iter()
+            .filter(|pin| !old.contains(pin))
+            .map(|pin| (pin, true));
+        let removed = old
+            .iter()
+            .filter(|pin| !new.contains(pin))
+            .map(|pin| (pin, false));
+        for (message_id, pinned) in added.chain(removed) {
+            let _ = self
+                .client
+                .local_events()
+                .send(LocalEvents::MessagePinned(MessagePin {
+                    group_id: self.group_id.clone(),
+                    message_id: message_id.clone(),
+                    pinned,
+                    changed_by_inbox_id: validated_commit.actor_inbox_id(),
+                }));
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_pin_messages() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+        let message_id = bo_group.send_message(b"remember this").await.unwrap();
+        alix_group.sync().await.unwrap();
+
+        let pins = bo_group.stream_message_pins();
+        futures::pin_mut!(pins);
+        alix_group.pin_message(&message_id).await.unwrap();
+        bo_group.sync().await.unwrap();
+
+        let pin = pins.next().await.unwrap().unwrap();
+        assert_eq!(pin.message_id, message_id);
+        assert!(pin.pinned);
+        assert_eq!(pin.changed_by_inbox_id, alix.inbox_id());
+        let pinned = bo_group.pinned_messages().unwrap();
+        assert_eq!(pinned.len(), 1);
+        assert_eq!(pinned[0].id, message_id);
+        assert!(matches!(
+            bo_group.pin_message(&message_id).await,
+            Err(GroupError::Pin(PinError::AlreadyPinned))
+        ));
+
+        bo_group.unpin_message(&message_id).await.unwrap();
+        alix_group.sync().await.unwrap();
+        assert!(alix_group.pinned_messages().unwrap().is_empty());
+        assert!(matches!(
+            alix_group.unpin_message(&message_id).await,
+            Err(GroupError::Pin(PinError::NotPinned))
+        ));
+
+        // pins made at the same time are all kept
+        let other_id = alix_group.send_message(b"and this").await.unwrap();
+        bo_group.sync().await.unwrap();
+        alix_group.pin_message(&message_id).await.unwrap();
+        bo_group.pin_message(&other_id).await.unwrap();
+        alix_group.sync().await.unwrap();
+        let pinned: Vec<_> = alix_group
+            .pinned_messages()
+            .unwrap()
+            .into_iter()
+            .map(|message| message.id)
+            .collect();
+        assert_eq!(pinned, vec![other_id, message_id]);
+    }
+}
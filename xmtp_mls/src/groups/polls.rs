@@ -0,0 +1,359 @@
+//! Polls. A poll is sent as its own message and members answer it with vote messages. Votes are
+//! validated against the poll and tallied in `poll_votes` as they're processed, so every client
+//! counts them the same way and the tally is available without replaying the conversation. A
+//! vote that arrives before its poll is counted once the poll arrives.
+use thiserror::Error;
+use xmtp_common::{time::now_ns, RetryableError};
+use xmtp_content_types::{
+    encoded_content_to_bytes,
+    poll::{Poll, PollCodec, PollSettings, PollVote, PollVoteCodec},
+    CodecError, ContentCodec,
+};
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    storage::{
+        db_connection::DbConnection,
+        group_message::{ContentType, StoredGroupMessage},
+        pending_reference::StoredPendingReference,
+        poll_vote::StoredPollVote,
+        NotFound, StorageError,
+    },
+    subscriptions::{LocalEvents, PollUpdate},
+    StoreOrIgnore,
+};
+
+#[derive(Debug, Error)]
+pub enum PollError {
+    #[error("message is not a poll")]
+    NotAPoll,
+    #[error("a poll needs at least two options")]
+    TooFewOptions,
+    #[error("poll has no option {0}")]
+    UnknownOption(u32),
+    #[error("vote is for option {0} more than once")]
+    DuplicateOption(u32),
+    #[error("poll only allows voting for one option")]
+    SingleChoice,
+    #[error("poll is closed")]
+    Closed,
+}
+
+impl RetryableError for PollError {
+    fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+/// A poll and its current tally
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PollResults {
+    pub poll_id: Vec<u8>,
+    pub poll: Poll,
+    /// The number of votes for each option, in the order of the poll's options
+    pub tally: Vec<u32>,
+    /// The options this inbox voted for
+    pub own_vote: Vec<u32>,
+}
+
+/// Fail if `options` isn't a valid vote in `poll`, sent at `voted_at_ns`. No options is a
+/// valid vote, it retracts the earlier one.
+fn check_vote(poll: &Poll, options: &[u32], voted_at_ns: i64) -> Result<(), PollError> {
+    if poll
+        .settings
+        .closes_at_ns
+        .is_some_and(|closes_at_ns| voted_at_ns > closes_at_ns)
+    {
+        return Err(PollError::Closed);
+    }
+    if let Some(option) = options
+        .iter()
+        .find(|option| **option as usize >= poll.options.len())
+    {
+        return Err(PollError::UnknownOption(*option));
+    }
+    if let Some(option) = options
+        .iter()
+        .enumerate()
+        .find_map(|(i, option)| options[..i].contains(option).then_some(option))
+    {
+        return Err(PollError::DuplicateOption(*option));
+    }
+    if options.len() > 1 && !poll.settings.multiple_choice {
+        return Err(PollError::SingleChoice);
+    }
+    Ok(())
+}
+
+/// The poll `poll_id` of `group_id`
+fn load_poll(conn: &DbConnection, group_id: &[u8], poll_id: &[u8]) -> Result<Poll, GroupError> {
+    find_poll(conn, group_id, poll_id)?
+        .ok_or_else(|| StorageError::from(NotFound::MessageById(poll_id.to_vec())).into())
+}
+
+/// The poll `poll_id` of `group_id`, or `None` if it hasn't arrived
+fn find_poll(
+    conn: &DbConnection,
+    group_id: &[u8],
+    poll_id: &[u8],
+) -> Result<Option<Poll>, GroupError> {
+    let Some(message) = conn
+        .get_group_message(poll_id)?
+        .filter(|message| message.group_id == group_id)
+    else {
+        return Ok(None);
+    };
+    if message.content_type != ContentType::Poll {
+        return Err(PollError::NotAPoll.into());
+    }
+    let content = message
+        .encoded_content()
+        .map_err(|e| CodecError::Decode(e.to_string()))?;
+    Ok(Some(PollCodec::decode(content)?))
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Ask the group `question`, to be answered with one of `options`, or several of them if
+    /// `settings` allow it. Returns the id of the poll message, which identifies the poll.
+    pub async fn create_poll(
+        &self,
+        question: String,
+        options: Vec<String>,
+        settings: PollSettings,
+    ) -> Result<Vec<u8>, GroupError> {
+        if options.len() < 2 {
+            return Err(PollError::TooFewOptions.into());
+        }
+        let poll = PollCodec::encode(Poll {
+            question,
+            options,
+            settings,
+        })?;
+        self.send_message(&encoded_content_to_bytes(poll)).await
+    }
+
+    /// Vote for `option` in `poll_id`, replacing this inbox's earlier vote. Returns the id of the
+    /// vote message.
+    pub async fn vote(&self, poll_id: &[u8], option: u32) -> Result<Vec<u8>, GroupError> {
+        self.vote_for_options(poll_id, &[option]).await
+    }
+
+    /// Vote for each of `options` in `poll_id`, replacing this inbox's earlier vote. Voting for
+    /// no options retracts the vote. Returns the id of the vote message.
+    pub async fn vote_for_options(
+        &self,
+        poll_id: &[u8],
+        options: &[u32],
+    ) -> Result<Vec<u8>, GroupError> {
+        let conn = self.context().store().conn()?;
+        let poll = load_poll(&conn, &self.group_id, poll_id)?;
+        check_vote(&poll, options, now_ns())?;
+
+        let vote = PollVoteCodec::encode(PollVote {
+            reference: hex::encode(poll_id),
+            options: options.to_vec(),
+        })?;
+        self.send_message(&encoded_content_to_bytes(vote)).await
+    }
+
+    /// Retract this inbox's vote in `poll_id`
+    pub async fn retract_vote(&self, poll_id: &[u8]) -> Result<Vec<u8>, GroupError> {
+        self.vote_for_options(poll_id, &[]).await
+    }
+
+    /// `poll_id` with the votes processed so far
+    pub fn poll_results(&self, poll_id: &[u8]) -> Result<PollResults, GroupError> {
+        let conn = self.context().store().conn()?;
+        let poll = load_poll(&conn, &self.group_id, poll_id)?;
+        let tally = conn.poll_tally(poll_id, poll.options.len())?;
+        let own_vote = conn
+            .get_poll_votes(poll_id)?
+            .into_iter()
+            .find(|vote| vote.inbox_id == self.context().inbox_id())
+            .map(|vote| vote.option_indices())
+            .unwrap_or_default();
+
+        Ok(PollResults {
+            poll_id: poll_id.to_vec(),
+            poll,
+            tally,
+            own_vote,
+        })
+    }
+
+    /// Count the vote in `message`, or keep it until its poll arrives. A vote that is malformed
+    /// or not valid for its poll is logged and ignored, it must not fail message processing.
+    pub(super) fn process_poll_vote(&self, conn: &DbConnection, message: &StoredGroupMessage) {
+        match record_vote(conn, message) {
+            Ok(Some(update)) => self.publish_after_commit(conn, LocalEvents::PollUpdated(update)),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                message_id = hex::encode(&message.id),
+                "ignoring poll vote: {e}"
+            ),
+        }
+    }
+}
+
+/// Record the vote in `message`. Returns the new tally, or `None` if a newer vote from the same
+/// inbox was already recorded or the poll hasn't arrived yet, in which case the vote waits for it.
+fn record_vote(
+    conn: &DbConnection,
+    message: &StoredGroupMessage,
+) -> Result<Option<PollUpdate>, GroupError> {
+    let content = message
+        .encoded_content()
+        .map_err(|e| CodecError::Decode(e.to_string()))?;
+    let vote = PollVoteCodec::decode(content)?;
+    let poll_id = hex::decode(&vote.reference).map_err(|e| CodecError::Decode(e.to_string()))?;
+    let Some(poll) = find_poll(conn, &message.group_id, &poll_id)? else {
+        StoredPendingReference {
+            message_id: message.id.clone(),
+            group_id: message.group_id.clone(),
+            reference_id: poll_id,
+        }
+        .store_or_ignore(conn)?;
+        return Ok(None);
+    };
+    check_vote(&poll, &vote.options, message.sent_at_ns)?;
+
+    let options = vote
+        .options
+        .iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(",");
+    let recorded = conn.record_poll_vote(&StoredPollVote {
+        poll_id: poll_id.clone(),
+        inbox_id: message.sender_inbox_id.clone(),
+        group_id: message.group_id.clone(),
+        options,
+        vote_message_id: message.id.clone(),
+        voted_at_ns: message.sent_at_ns,
+    })?;
+    if !recorded {
+        return Ok(None);
+    }
+
+    Ok(Some(PollUpdate {
+        group_id: message.group_id.clone(),
+        tally: conn.poll_tally(&poll_id, poll.options.len())?,
+        poll_id,
+        voter_inbox_id: message.sender_inbox_id.clone(),
+    }))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions,
+        storage::group_message::tests::generate_message, Store,
+    };
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_poll_votes_are_tallied() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        let bo_group = bo.group(alix_group.group_id.clone()).unwrap();
+
+        let poll_id = alix_group
+            .create_poll(
+                "Where should we eat?".to_string(),
+                vec!["Tacos".to_string(), "Ramen".to_string()],
+                PollSettings::default(),
+            )
+            .await
+            .unwrap();
+        bo_group.sync().await.unwrap();
+
+        let updates = alix_group.stream_poll_updates();
+        futures::pin_mut!(updates);
+
+        assert!(matches!(
+            bo_group.vote(&poll_id, 2).await,
+            Err(GroupError::Poll(PollError::UnknownOption(2)))
+        ));
+        assert!(matches!(
+            bo_group.vote_for_options(&poll_id, &[1, 1]).await,
+            Err(GroupError::Poll(PollError::DuplicateOption(1)))
+        ));
+        assert!(matches!(
+            bo_group.vote_for_options(&poll_id, &[0, 1]).await,
+            Err(GroupError::Poll(PollError::SingleChoice))
+        ));
+        bo_group.vote(&poll_id, 1).await.unwrap();
+        alix_group.vote(&poll_id, 1).await.unwrap();
+        alix_group.sync().await.unwrap();
+
+        let update = updates.next().await.unwrap().unwrap();
+        assert_eq!(update.poll_id, poll_id);
+        let results = alix_group.poll_results(&poll_id).unwrap();
+        assert_eq!(results.poll.question, "Where should we eat?");
+        assert_eq!(results.tally, vec![0, 2]);
+        assert_eq!(results.own_vote, vec![1]);
+
+        // bo changes their mind
+        bo_group.vote(&poll_id, 0).await.unwrap();
+        alix_group.sync().await.unwrap();
+        assert_eq!(alix_group.poll_results(&poll_id).unwrap().tally, vec![1, 1]);
+        bo_group.sync().await.unwrap();
+        assert_eq!(bo_group.poll_results(&poll_id).unwrap().tally, vec![1, 1]);
+
+        alix_group.retract_vote(&poll_id).await.unwrap();
+        assert_eq!(alix_group.poll_results(&poll_id).unwrap().tally, vec![1, 0]);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_vote_before_poll() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let conn = group.context().store().conn().unwrap();
+        let poll = PollCodec::encode(Poll {
+            question: "Tea or coffee?".to_string(),
+            options: vec!["Tea".to_string(), "Coffee".to_string()],
+            settings: PollSettings::default(),
+        })
+        .unwrap();
+        let poll = StoredGroupMessage {
+            decrypted_message_bytes: encoded_content_to_bytes(poll),
+            content_type: ContentType::Poll,
+            ..generate_message(None, Some(&group.group_id), Some(1), None)
+        };
+        let vote = PollVoteCodec::encode(PollVote {
+            reference: hex::encode(&poll.id),
+            options: vec![1],
+        })
+        .unwrap();
+        let vote = StoredGroupMessage {
+            decrypted_message_bytes: encoded_content_to_bytes(vote),
+            content_type: ContentType::PollVote,
+            ..generate_message(None, Some(&group.group_id), Some(2), None)
+        };
+
+        // the vote arrives first and waits for the poll
+        vote.store(&conn).unwrap();
+        group.process_poll_vote(&conn, &vote);
+        poll.store(&conn).unwrap();
+        group.process_pending_references(&conn, &poll);
+
+        assert_eq!(group.poll_results(&poll.id).unwrap().tally, vec![0, 1]);
+    }
+}
@@ -0,0 +1,220 @@
+//! Plugins that run on messages after they have been decrypted and stored, attaching derived
+//! data (detected language, translation references, etc.) as annotations that can be read back
+//! alongside the message.
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use xmtp_common::time::now_ns;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    storage::{
+        group_message::{MsgQueryArgs, StoredGroupMessage},
+        message_annotation::StoredMessageAnnotation,
+        DbConnection, StorageError,
+    },
+    Client, Store, StoreOrIgnore,
+};
+
+pub type PostProcessorError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A key/value pair derived from a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageAnnotation {
+    pub key: String,
+    pub value: String,
+}
+
+impl MessageAnnotation {
+    pub fn new(key: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+}
+
+/// Runs once on every application message received from the network, after it is decrypted and
+/// stored for the first time. Messages received again, e.g. when a sync is retried, aren't
+/// processed again.
+///
+/// Processors must be cheap: they run inline with message processing. A processor that fails
+/// does not fail message processing; the error is logged and the message is left unannotated.
+pub trait MessagePostProcessor: Send + Sync {
+    /// Identifies the processor. Annotations are namespaced by this id.
+    fn id(&self) -> &str;
+
+    fn process(
+        &self,
+        message: &StoredGroupMessage,
+    ) -> Result<Vec<MessageAnnotation>, PostProcessorError>;
+}
+
+/// The processors registered on a client
+#[derive(Default)]
+pub struct PostProcessors(RwLock<Vec<Arc<dyn MessagePostProcessor>>>);
+
+impl PostProcessors {
+    pub fn register(&self, processor: Arc<dyn MessagePostProcessor>) {
+        let mut processors = self.0.write();
+        processors.retain(|p| p.id() != processor.id());
+        processors.push(processor);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.read().is_empty()
+    }
+
+    /// Run every registered processor on `message`, storing the resulting annotations
+    pub(crate) fn run(&self, conn: &DbConnection, message: &StoredGroupMessage) {
+        // clone out of the lock so a slow processor doesn't block registration
+        let processors = self.0.read().clone();
+        for processor in processors {
+            let annotations = match processor.process(message) {
+                Ok(annotations) => annotations,
+                Err(e) => {
+                    tracing::warn!(
+                        processor = processor.id(),
+                        message_id = hex::encode(&message.id),
+                        "message post-processor failed: {e}"
+                    );
+                    continue;
+                }
+            };
+            for MessageAnnotation { key, value } in annotations {
+                let stored = StoredMessageAnnotation {
+                    message_id: message.id.clone(),
+                    processor_id: processor.id().to_string(),
+                    key,
+                    value,
+                    created_at_ns: now_ns(),
+                };
+                if let Err(e) = stored.store(conn) {
+                    tracing::warn!(
+                        processor = processor.id(),
+                        "failed to store message annotation: {e}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// A message along with the annotations post-processors attached to it
+#[derive(Debug, Clone)]
+pub struct AnnotatedMessage {
+    pub message: StoredGroupMessage,
+    pub annotations: Vec<StoredMessageAnnotation>,
+}
+
+impl<ApiClient, V> Client<ApiClient, V> {
+    /// Register a processor to run on every message received from now on.
+    /// Registering a processor with the same id replaces the previous one.
+    pub fn register_post_processor(&self, processor: impl MessagePostProcessor + 'static) {
+        self.context.post_processors.register(Arc::new(processor));
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Query the database for stored messages along with their annotations
+    pub fn find_messages_with_annotations(
+        &self,
+        args: &MsgQueryArgs,
+    ) -> Result<Vec<AnnotatedMessage>, GroupError> {
+        let conn = self.context().store().conn()?;
+        let messages = conn.get_group_messages(&self.group_id, args)?;
+        let ids: Vec<Vec<u8>> = messages.iter().map(|m| m.id.clone()).collect();
+        let mut annotations = conn.get_message_annotations_for(&ids)?;
+
+        Ok(messages
+            .into_iter()
+            .map(|message| AnnotatedMessage {
+                annotations: annotations.remove(&message.id).unwrap_or_default(),
+                message,
+            })
+            .collect())
+    }
+
+    /// Store an application message received from the network, running the post-processors on
+    /// it if it wasn't stored yet. Returns whether it's new.
+    pub(super) fn store_received_message(
+        &self,
+        conn: &DbConnection,
+        message: &StoredGroupMessage,
+    ) -> Result<bool, StorageError> {
+        let is_new = conn.get_group_message(&message.id)?.is_none();
+        message.store_or_ignore(conn)?;
+        let post_processors = &self.context().post_processors;
+        if is_new && !post_processors.is_empty() {
+            post_processors.run(conn, message);
+        }
+        Ok(is_new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+
+    struct NaiveLanguageDetector;
+
+    impl MessagePostProcessor for NaiveLanguageDetector {
+        fn id(&self) -> &str {
+            "language"
+        }
+
+        fn process(
+            &self,
+            message: &StoredGroupMessage,
+        ) -> Result<Vec<MessageAnnotation>, PostProcessorError> {
+            let text = std::str::from_utf8(&message.decrypted_message_bytes)?;
+            let lang = if text.starts_with("bonjour") {
+                "fr"
+            } else {
+                "en"
+            };
+            Ok(vec![MessageAnnotation::new("lang", lang)])
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_post_processor_annotates_received_messages() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        bo.register_post_processor(NaiveLanguageDetector);
+
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        alix_group.send_message(b"bonjour bo").await.unwrap();
+
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+        bo_group.sync().await.unwrap();
+
+        let messages = bo_group
+            .find_messages_with_annotations(&MsgQueryArgs::default())
+            .unwrap();
+        let annotated = messages
+            .iter()
+            .find(|m| m.message.decrypted_message_bytes == b"bonjour bo")
+            .unwrap();
+        assert_eq!(annotated.annotations.len(), 1);
+        assert_eq!(annotated.annotations[0].processor_id, "language");
+        assert_eq!(annotated.annotations[0].value, "fr");
+    }
+}
@@ -0,0 +1,197 @@
+//! Reactions to messages. Reaction messages are aggregated into `message_reactions` as they
+//! are processed, so reading the reactions to a message doesn't require scanning and
+//! replaying the reaction messages of the conversation.
+use std::collections::HashMap;
+
+use prost::Message;
+use xmtp_content_types::{
+    encoded_content_to_bytes, reaction::ReactionCodec, CodecError, ContentCodec,
+};
+use xmtp_proto::xmtp::mls::message_contents::{
+    content_types::{ReactionAction, ReactionSchema, ReactionV2},
+    EncodedContent,
+};
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    storage::{
+        db_connection::DbConnection, group_message::StoredGroupMessage,
+        message_reaction::StoredMessageReaction, NotFound, StorageError,
+    },
+    Store,
+};
+
+/// The reactions to a message with the same content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReactionSummary {
+    /// The emoji, or other reaction content
+    pub content: String,
+    /// The inboxes that reacted, in the order they reacted
+    pub inbox_ids: Vec<String>,
+}
+
+impl ReactionSummary {
+    pub fn count(&self) -> usize {
+        self.inbox_ids.len()
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Add or remove a reaction to `message_id`, a message of this conversation.
+    /// Returns the id of the reaction message.
+    pub async fn send_reaction(
+        &self,
+        message_id: &[u8],
+        emoji: &str,
+        action: ReactionAction,
+    ) -> Result<Vec<u8>, GroupError> {
+        let conn = self.context().store().conn()?;
+        let message = conn
+            .get_group_message(message_id)?
+            .filter(|message| message.group_id == self.group_id)
+            .ok_or_else(|| StorageError::from(NotFound::MessageById(message_id.to_vec())))?;
+
+        let reaction = ReactionCodec::encode(ReactionV2 {
+            reference: hex::encode(message_id),
+            reference_inbox_id: message.sender_inbox_id,
+            action: action as i32,
+            content: emoji.to_string(),
+            schema: ReactionSchema::Unicode as i32,
+        })?;
+        self.send_message(&encoded_content_to_bytes(reaction)).await
+    }
+
+    /// The current reactions to `message_id`, grouped by content in the order each content was
+    /// first used
+    pub fn reactions_for(&self, message_id: &[u8]) -> Result<Vec<ReactionSummary>, GroupError> {
+        let conn = self.context().store().conn()?;
+        let mut summaries: Vec<ReactionSummary> = vec![];
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for reaction in conn.get_message_reactions(&self.group_id, message_id)? {
+            let position = *positions
+                .entry(reaction.content.clone())
+                .or_insert_with(|| {
+                    summaries.push(ReactionSummary {
+                        content: reaction.content,
+                        inbox_ids: vec![],
+                    });
+                    summaries.len() - 1
+                });
+            summaries[position].inbox_ids.push(reaction.sender_inbox_id);
+        }
+        Ok(summaries)
+    }
+
+    /// Apply the reaction in `message` to `message_reactions`. A malformed reaction is logged
+    /// and ignored, it must not fail message processing.
+    pub(super) fn process_reaction(&self, conn: &DbConnection, message: &StoredGroupMessage) {
+        if let Err(e) = record_reaction(conn, message) {
+            tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                message_id = hex::encode(&message.id),
+                "failed to record reaction: {e}"
+            );
+        }
+    }
+}
+
+fn record_reaction(conn: &DbConnection, message: &StoredGroupMessage) -> Result<(), GroupError> {
+    // Legacy (v1) reactions are JSON and don't carry a decodable reference
+    if message.version_major < 2 {
+        return Ok(());
+    }
+    let content = EncodedContent::decode(message.decrypted_message_bytes.as_slice())
+        .map_err(|e| CodecError::Decode(e.to_string()))?;
+    let reaction = ReactionCodec::decode(content)?;
+    let reacted_message_id =
+        hex::decode(&reaction.reference).map_err(|e| CodecError::Decode(e.to_string()))?;
+
+    match reaction.action() {
+        ReactionAction::Added => StoredMessageReaction {
+            message_id: reacted_message_id,
+            sender_inbox_id: message.sender_inbox_id.clone(),
+            content: reaction.content,
+            group_id: message.group_id.clone(),
+            reaction_message_id: message.id.clone(),
+            reacted_at_ns: message.sent_at_ns,
+        }
+        .store(conn)?,
+        ReactionAction::Removed => {
+            conn.delete_message_reaction(
+                reacted_message_id,
+                &message.sender_inbox_id,
+                &reaction.content,
+                message.sent_at_ns,
+            )?;
+        }
+        ReactionAction::Unspecified => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_reactions_for() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let message_id = alix_group.send_message(b"hello").await.unwrap();
+
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+        bo_group.sync().await.unwrap();
+        bo_group
+            .send_reaction(&message_id, "👍", ReactionAction::Added)
+            .await
+            .unwrap();
+        bo_group
+            .send_reaction(&message_id, "🎉", ReactionAction::Added)
+            .await
+            .unwrap();
+        alix_group
+            .send_reaction(&message_id, "👍", ReactionAction::Added)
+            .await
+            .unwrap();
+        bo_group
+            .send_reaction(&message_id, "🎉", ReactionAction::Removed)
+            .await
+            .unwrap();
+
+        alix_group.sync().await.unwrap();
+        bo_group.sync().await.unwrap();
+        for group in [&alix_group, &bo_group] {
+            let reactions = group.reactions_for(&message_id).unwrap();
+            assert_eq!(
+                reactions,
+                vec![ReactionSummary {
+                    content: "👍".to_string(),
+                    inbox_ids: vec![bo.inbox_id().to_string(), alix.inbox_id().to_string()],
+                }]
+            );
+            assert_eq!(reactions[0].count(), 2);
+        }
+
+        assert!(bo_group
+            .send_reaction(b"missing", "👍", ReactionAction::Added)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Read receipts. Each member's receipts move their cursor in `read_cursors` as they are
+//! processed, so apps can show who has seen a message without replaying receipts themselves.
+use xmtp_content_types::{
+    encoded_content_to_bytes,
+    read_receipt::{ReadReceipt, ReadReceiptCodec},
+    CodecError, ContentCodec,
+};
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::storage::{
+    db_connection::DbConnection, group_message::StoredGroupMessage, read_cursor::StoredReadCursor,
+    NotFound, StorageError,
+};
+
+/// How far a member has read in a conversation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberReadState {
+    pub inbox_id: String,
+    /// Messages sent at or before this time have been read
+    pub read_until_ns: i64,
+    /// The last message read, if the member's receipt named one
+    pub message_id: Option<Vec<u8>>,
+}
+
+impl From<StoredReadCursor> for MemberReadState {
+    fn from(cursor: StoredReadCursor) -> Self {
+        Self {
+            inbox_id: cursor.inbox_id,
+            read_until_ns: cursor.read_until_ns,
+            message_id: cursor.message_id,
+        }
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Tell the other members that this inbox has read every message up to and including
+    /// `message_id`. Returns the id of the receipt message.
+    pub async fn mark_read_until(&self, message_id: &[u8]) -> Result<Vec<u8>, GroupError> {
+        let conn = self.context().store().conn()?;
+        conn.get_group_message(message_id)?
+            .filter(|message| message.group_id == self.group_id)
+            .ok_or_else(|| StorageError::from(NotFound::MessageById(message_id.to_vec())))?;
+
+        let receipt = ReadReceiptCodec::encode(ReadReceipt {
+            reference: Some(hex::encode(message_id)),
+        })?;
+        self.send_message(&encoded_content_to_bytes(receipt)).await
+    }
+
+    /// How far each member that sent a read receipt has read, furthest first
+    pub fn read_state(&self) -> Result<Vec<MemberReadState>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn
+            .get_read_cursors(&self.group_id)?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// The inboxes other than the sender that have read `message_id`
+    pub fn seen_by(&self, message_id: &[u8]) -> Result<Vec<String>, GroupError> {
+        let conn = self.context().store().conn()?;
+        let message = conn
+            .get_group_message(message_id)?
+            .filter(|message| message.group_id == self.group_id)
+            .ok_or_else(|| StorageError::from(NotFound::MessageById(message_id.to_vec())))?;
+        Ok(conn
+            .get_read_cursors(&self.group_id)?
+            .into_iter()
+            .filter(|cursor| {
+                cursor.read_until_ns >= message.sent_at_ns
+                    && cursor.inbox_id != message.sender_inbox_id
+            })
+            .map(|cursor| cursor.inbox_id)
+            .collect())
+    }
+
+    /// Move the sender's read cursor to the receipt in `message`. A receipt this inbox sent also
+    /// reads the group on this installation. A malformed receipt is logged and ignored, it must
+    /// not fail message processing.
+    pub(super) fn process_read_receipt(&self, conn: &DbConnection, message: &StoredGroupMessage) {
+        let result = record_read_receipt(conn, message).and_then(|cursor| match cursor {
+            Some(cursor) if cursor.inbox_id == self.context().inbox_id() => {
+                self.mark_read_at(conn, cursor.read_until_ns)
+            }
+            _ => Ok(()),
+        });
+        if let Err(e) = result {
+            tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                message_id = hex::encode(&message.id),
+                "failed to record read receipt: {e}"
+            );
+        }
+    }
+}
+
+/// Returns the sender's new read cursor, or `None` if they had already read further
+fn record_read_receipt(
+    conn: &DbConnection,
+    message: &StoredGroupMessage,
+) -> Result<Option<StoredReadCursor>, GroupError> {
+    let content = message
+        .encoded_content()
+        .map_err(|e| CodecError::Decode(e.to_string()))?;
+    let receipt = ReadReceiptCodec::decode(content)?;
+
+    let cursor = match receipt.reference {
+        Some(reference) => {
+            let read_id = hex::decode(&reference).map_err(|e| CodecError::Decode(e.to_string()))?;
+            let read = conn
+                .get_group_message(&read_id)?
+                .filter(|read| read.group_id == message.group_id)
+                .ok_or_else(|| StorageError::from(NotFound::MessageById(read_id)))?;
+            StoredReadCursor {
+                group_id: message.group_id.clone(),
+                inbox_id: message.sender_inbox_id.clone(),
+                read_until_ns: read.sent_at_ns,
+                message_id: Some(read.id),
+                receipt_message_id: message.id.clone(),
+            }
+        }
+        // Legacy receipts mark everything sent before them as read
+        None => StoredReadCursor {
+            group_id: message.group_id.clone(),
+            inbox_id: message.sender_inbox_id.clone(),
+            read_until_ns: message.sent_at_ns,
+            message_id: None,
+            receipt_message_id: message.id.clone(),
+        },
+    };
+    Ok(conn.advance_read_cursor(&cursor)?.then_some(cursor))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_read_state() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let first = alix_group.send_message(b"first").await.unwrap();
+        let second = alix_group.send_message(b"second").await.unwrap();
+
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+        bo_group.sync().await.unwrap();
+        bo_group.mark_read_until(&first).await.unwrap();
+        alix_group.sync().await.unwrap();
+
+        assert_eq!(alix_group.seen_by(&first).unwrap(), [bo.inbox_id()]);
+        assert!(alix_group.seen_by(&second).unwrap().is_empty());
+
+        // an older receipt doesn't move the cursor back
+        bo_group.mark_read_until(&second).await.unwrap();
+        bo_group.mark_read_until(&first).await.unwrap();
+        alix_group.sync().await.unwrap();
+        for group in [&alix_group, &bo_group] {
+            let state = group.read_state().unwrap();
+            assert_eq!(state.len(), 1);
+            assert_eq!(state[0].inbox_id, bo.inbox_id());
+            assert_eq!(state[0].message_id, Some(second.clone()));
+        }
+        assert_eq!(alix_group.seen_by(&second).unwrap(), [bo.inbox_id()]);
+    }
+}
@@ -0,0 +1,174 @@
+//! Catching a group up after a network partition, when members on either side of it kept
+//! committing.
+//!
+//! The network orders every group's messages, so a partition can't split a group for good: of the
+//! commits made on top of the same epoch, the one the network received first wins and every
+//! member merges it. A commit that lost is never merged, even by the member that made it. That
+//! member finds it behind the winner when it processes its own message, puts the intent back to
+//! be published, and rebuilds the commit on top of the new epoch the next time it publishes, so
+//! the change is applied after the winner's instead of being lost. Messages sent during the
+//! partition are kept if they were encrypted no more than [`MAX_PAST_EPOCHS`] epochs back, and
+//! re-encrypted and sent again otherwise. An intent that's no longer valid on top of the winning
+//! commits, e.g. because the sender lost the permission it needs, ends in the error state.
+//!
+//! [`MlsGroup::reconcile_after_partition`] applies those rules in order: it first receives
+//! everything the network has, so no commit is built on an epoch that's already taken, and then
+//! publishes the pending intents until each one is resolved. If receiving fails, nothing is
+//! published and the error is returned.
+//!
+//! [`MAX_PAST_EPOCHS`]: crate::configuration::MAX_PAST_EPOCHS
+use std::collections::HashSet;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::storage::{
+    group_intent::{IntentState, ID},
+    xmtp_openmls_provider::XmtpOpenMlsProvider,
+};
+
+/// What [`MlsGroup::reconcile_after_partition`] did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PartitionReconciliation {
+    pub epoch_before: u64,
+    pub epoch_after: u64,
+    /// Pending intents that were committed or sent
+    pub intents_resolved: usize,
+    /// Intents published during the partition that lost to commits from the other side, or
+    /// whose messages became too old, and were published again
+    pub intents_replayed: usize,
+    /// Intents that couldn't be applied on top of the commits from the other side
+    pub intents_failed: Vec<ID>,
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Bring the group up to date after being cut off from the network, and publish what this
+    /// installation queued or published in the meantime on top of it. See the [module
+    /// docs](self) for how conflicting commits are resolved.
+    pub async fn reconcile_after_partition(&self) -> Result<PartitionReconciliation, GroupError> {
+        let provider = self.mls_provider()?;
+        let epoch_before = self.current_epoch(&provider)?;
+        let pending_states = Some(vec![IntentState::ToPublish, IntentState::Published]);
+        let published: HashSet<ID> = provider
+            .conn_ref()
+            .find_group_intents(self.group_id.clone(), pending_states.clone(), None)?
+            .into_iter()
+            .filter(|intent| intent.state == IntentState::Published)
+            .map(|intent| intent.id)
+            .collect();
+
+        {
+            let _mutex = self.mutex.lock().await;
+            self.receive(&provider).await?;
+        }
+
+        let pending =
+            provider
+                .conn_ref()
+                .find_group_intents(self.group_id.clone(), pending_states, None)?;
+        let mut reconciliation = PartitionReconciliation {
+            epoch_before,
+            intents_replayed: pending
+                .iter()
+                .filter(|intent| {
+                    intent.state == IntentState::ToPublish && published.contains(&intent.id)
+                })
+                .count(),
+            ..Default::default()
+        };
+        for intent in pending {
+            match self.sync_until_intent_resolved(&provider, intent.id).await {
+                Ok(()) => reconciliation.intents_resolved += 1,
+                Err(e) => {
+                    tracing::warn!(
+                        group_id = hex::encode(&self.group_id),
+                        intent.id,
+                        "intent could not be applied after the partition: {e}"
+                    );
+                    reconciliation.intents_failed.push(intent.id);
+                }
+            }
+        }
+        reconciliation.epoch_after = self.current_epoch(&provider)?;
+        Ok(reconciliation)
+    }
+
+    fn current_epoch(&self, provider: &XmtpOpenMlsProvider) -> Result<u64, GroupError> {
+        self.load_mls_group_with_lock(provider, |mls_group| Ok(mls_group.epoch().as_u64()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::{intents::UpdateMetadataIntentData, GroupMetadataOptions},
+        storage::group_intent::IntentKind,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_commits_on_both_sides_of_a_partition() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        let bo_group = bo.group(alix_group.group_id.clone()).unwrap();
+        let bo_provider = bo.mls_provider().unwrap();
+
+        // alix's side of the partition commits first
+        alix_group
+            .update_group_name("alix's name".to_string())
+            .await
+            .unwrap();
+        alix_group.send_message(b"from alix").await.unwrap();
+
+        // bo's side commits on top of the epoch alix's commit already took
+        let epoch = bo_group.current_epoch(&bo_provider).unwrap();
+        bo_group
+            .queue_intent(
+                &bo_provider,
+                IntentKind::MetadataUpdate,
+                UpdateMetadataIntentData::new_update_group_description("bo's description".into())
+                    .into(),
+            )
+            .unwrap();
+        bo_group.publish_intents(&bo_provider).await.unwrap();
+        bo_group.send_message_optimistic(b"from bo").unwrap();
+
+        let reconciliation = bo_group.reconcile_after_partition().await.unwrap();
+        assert_eq!(reconciliation.epoch_before, epoch);
+        assert_eq!(reconciliation.intents_replayed, 1);
+        assert_eq!(reconciliation.intents_resolved, 2);
+        assert!(reconciliation.intents_failed.is_empty());
+        // alix's commit and then bo's replayed one
+        assert_eq!(reconciliation.epoch_after, epoch + 2);
+
+        alix_group.sync().await.unwrap();
+        let alix_provider = alix.mls_provider().unwrap();
+        for (group, provider) in [(&alix_group, &alix_provider), (&bo_group, &bo_provider)] {
+            assert_eq!(group.group_name(provider).unwrap(), "alix's name");
+            assert_eq!(
+                group.group_description(provider).unwrap(),
+                "bo's description"
+            );
+            assert_eq!(
+                group.current_epoch(provider).unwrap(),
+                reconciliation.epoch_after
+            );
+        }
+        let messages = alix_group.find_messages(&Default::default()).unwrap();
+        assert!(messages
+            .iter()
+            .any(|message| message.decrypted_message_bytes == b"from bo"));
+    }
+}
@@ -0,0 +1,126 @@
+//! Threaded replies. The parent of a reply is stored as the message's `reference_id`, so a
+//! thread can be loaded from the local database without decoding the conversation's messages.
+use xmtp_content_types::{
+    encoded_content_to_bytes,
+    reply::{Reply, ReplyCodec},
+    ContentCodec,
+};
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::storage::{
+    group_message::{MsgQueryArgs, StoredGroupMessage},
+    NotFound, StorageError,
+};
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Send `content` in reply to `parent_message_id`, a message of this conversation.
+    /// Returns the id of the reply.
+    pub async fn send_reply(
+        &self,
+        parent_message_id: &[u8],
+        content: EncodedContent,
+    ) -> Result<Vec<u8>, GroupError> {
+        let conn = self.context().store().conn()?;
+        let parent = conn
+            .get_group_message(parent_message_id)?
+            .filter(|message| message.group_id == self.group_id)
+            .ok_or_else(|| StorageError::from(NotFound::MessageById(parent_message_id.to_vec())))?;
+
+        let reply = ReplyCodec::encode(Reply {
+            reference: hex::encode(parent_message_id),
+            reference_inbox_id: Some(parent.sender_inbox_id),
+            content,
+        })?;
+        self.send_message(&encoded_content_to_bytes(reply)).await
+    }
+
+    /// The replies to `parent_message_id`. `args` pages through them the same way as
+    /// [`MlsGroup::find_messages`].
+    pub fn replies(
+        &self,
+        parent_message_id: &[u8],
+        args: &MsgQueryArgs,
+    ) -> Result<Vec<StoredGroupMessage>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn.get_replies(&self.group_id, parent_message_id, args)?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::GroupMetadataOptions,
+        storage::group_message::{ContentType, SortDirection},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::text::TextCodec;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_replies() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let text = |s: &str| TextCodec::encode(s.to_string()).unwrap();
+        let parent_id = alix_group
+            .send_message(&encoded_content_to_bytes(text("question")))
+            .await
+            .unwrap();
+        let other_id = alix_group
+            .send_message(&encoded_content_to_bytes(text("unrelated")))
+            .await
+            .unwrap();
+        alix_group
+            .send_reply(&other_id, text("not in the thread"))
+            .await
+            .unwrap();
+
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+        bo_group.sync().await.unwrap();
+        for answer in ["first", "second", "third"] {
+            bo_group.send_reply(&parent_id, text(answer)).await.unwrap();
+        }
+
+        alix_group.sync().await.unwrap();
+        let replies = alix_group
+            .replies(&parent_id, &MsgQueryArgs::default())
+            .unwrap();
+        assert_eq!(replies.len(), 3);
+        assert!(replies
+            .iter()
+            .all(|reply| reply.content_type == ContentType::Reply
+                && reply.sender_inbox_id == bo.inbox_id()));
+
+        let latest = alix_group
+            .replies(
+                &parent_id,
+                &MsgQueryArgs {
+                    direction: Some(SortDirection::Descending),
+                    limit: Some(1),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let content =
+            xmtp_content_types::bytes_to_encoded_content(latest[0].decrypted_message_bytes.clone());
+        let reply = ReplyCodec::decode(content).unwrap();
+        assert_eq!(reply.reference, hex::encode(&parent_id));
+        assert_eq!(TextCodec::decode(reply.content).unwrap(), "third");
+    }
+}
@@ -0,0 +1,319 @@
+//! Reports of abusive messages and conversations, for apps to submit to their moderation
+//! backend.
+//!
+//! A report contains the reported messages as this installation decrypted them, the identities
+//! of their senders and the context of the conversation, and is signed by the reporting
+//! installation, so none of it can be changed after the fact. The backend verifies the report
+//! with [`SignedReport::verify`], and should also check that the reporting installation belongs
+//! to the reporting inbox, and each sender installation to its inbox, against the identity
+//! updates on the network. A report can't prove that a sender sent a message: anyone in the
+//! conversation could make the same claim, which is why it's signed by the reporter.
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use xmtp_common::{time::now_ns, RetryableError};
+use xmtp_id::associations::{verify_signed_with_public_context, SignatureError};
+
+use super::{group_metadata::ConversationType, GroupError, MlsGroup, ScopedGroupClient};
+use crate::storage::{
+    group_message::{
+        ContentType, GroupMessageKind, MsgQueryArgs, SortDirection, StoredGroupMessage,
+    },
+    xmtp_openmls_provider::XmtpOpenMlsProvider,
+};
+
+/// The report version written by [`MlsGroup::report_message`]
+pub const REPORT_VERSION: u32 = 1;
+
+/// How many of the latest messages [`MlsGroup::report_conversation`] includes
+pub const REPORTED_CONVERSATION_MESSAGES: i64 = 20;
+
+#[derive(Debug, Error)]
+pub enum ReportError {
+    #[error("message {} is not in the conversation", hex::encode(.0))]
+    MessageNotFound(Vec<u8>),
+    #[error("message {} was deleted", hex::encode(.0))]
+    Deleted(Vec<u8>),
+    #[error("unsupported report version {0}")]
+    UnsupportedVersion(u32),
+    #[error("report signature or signing key is malformed")]
+    Malformed,
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+}
+
+impl RetryableError for ReportError {
+    fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReportReason {
+    Spam,
+    Harassment,
+    Impersonation,
+    Other(String),
+}
+
+/// A reported message, as the reporter decrypted it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportedMessage {
+    pub id: Vec<u8>,
+    pub sender_inbox_id: String,
+    pub sender_installation_id: Vec<u8>,
+    pub sent_at_ns: i64,
+    pub content_type: ContentType,
+    pub authority_id: String,
+    pub version_major: i32,
+    pub version_minor: i32,
+    /// The encoded content
+    pub content: Vec<u8>,
+}
+
+impl From<StoredGroupMessage> for ReportedMessage {
+    fn from(message: StoredGroupMessage) -> Self {
+        Self {
+            id: message.id,
+            sender_inbox_id: message.sender_inbox_id,
+            sender_installation_id: message.sender_installation_id,
+            sent_at_ns: message.sent_at_ns,
+            content_type: message.content_type,
+            authority_id: message.authority_id,
+            version_major: message.version_major,
+            version_minor: message.version_minor,
+            content: message.decrypted_message_bytes,
+        }
+    }
+}
+
+/// The identity of a sender, as the reporter knew it. The backend can check it against the
+/// inbox's identity updates up to `sequence_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SenderIdentity {
+    pub inbox_id: String,
+    pub sequence_id: i64,
+    pub addresses: Vec<String>,
+    pub installation_ids: Vec<Vec<u8>>,
+}
+
+/// What a report is about
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportContents {
+    pub reason: ReportReason,
+    pub group_id: Vec<u8>,
+    pub conversation_type: ConversationType,
+    pub group_name: String,
+    pub member_inbox_ids: Vec<String>,
+    pub messages: Vec<ReportedMessage>,
+    pub senders: Vec<SenderIdentity>,
+}
+
+/// A report, signed by the installation that made it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedReport {
+    pub version: u32,
+    pub reporter_inbox_id: String,
+    pub reporter_installation_id: Vec<u8>,
+    pub created_at_ns: i64,
+    /// JSON encoded [`ReportContents`]
+    pub payload: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl SignedReport {
+    /// The text the installation signs, committing to every field through a hash of the payload
+    fn signature_text(&self) -> String {
+        format!(
+            "XMTP : Report\n\nVersion: {}\nInbox ID: {}\nInstallation ID: {}\nCreated at: {}\nPayload hash: {}",
+            self.version,
+            self.reporter_inbox_id,
+            hex::encode(&self.reporter_installation_id),
+            self.created_at_ns,
+            hex::encode(Sha256::digest(&self.payload)),
+        )
+    }
+
+    /// Check the report's signature and return what it's about
+    pub fn verify(&self) -> Result<ReportContents, ReportError> {
+        if self.version != REPORT_VERSION {
+            return Err(ReportError::UnsupportedVersion(self.version));
+        }
+        let signature: [u8; 64] = self
+            .signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| ReportError::Malformed)?;
+        let public_key: [u8; 32] = self
+            .reporter_installation_id
+            .as_slice()
+            .try_into()
+            .map_err(|_| ReportError::Malformed)?;
+        verify_signed_with_public_context(self.signature_text(), &signature, &public_key)?;
+        Ok(serde_json::from_slice(&self.payload)?)
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Report the message `message_id` of this conversation
+    pub async fn report_message(
+        &self,
+        message_id: &[u8],
+        reason: ReportReason,
+    ) -> Result<SignedReport, GroupError> {
+        let provider = self.mls_provider()?;
+        let message = provider
+            .conn_ref()
+            .get_group_message(message_id)?
+            .filter(|message| message.group_id == self.group_id)
+            .ok_or_else(|| ReportError::MessageNotFound(message_id.to_vec()))?;
+        if message.deleted_at_ns.is_some() {
+            return Err(ReportError::Deleted(message_id.to_vec()).into());
+        }
+        self.sign_report(&provider, reason, vec![message]).await
+    }
+
+    /// Report the conversation, along with its latest messages
+    pub async fn report_conversation(
+        &self,
+        reason: ReportReason,
+    ) -> Result<SignedReport, GroupError> {
+        let provider = self.mls_provider()?;
+        let mut messages = provider.conn_ref().get_group_messages(
+            &self.group_id,
+            &MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                direction: Some(SortDirection::Descending),
+                limit: Some(REPORTED_CONVERSATION_MESSAGES),
+                ..Default::default()
+            },
+        )?;
+        messages.retain(|message| message.deleted_at_ns.is_none());
+        messages.reverse();
+        self.sign_report(&provider, reason, messages).await
+    }
+
+    async fn sign_report(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+        reason: ReportReason,
+        messages: Vec<StoredGroupMessage>,
+    ) -> Result<SignedReport, GroupError> {
+        let conn = provider.conn_ref();
+        let sender_inbox_ids: BTreeSet<&str> = messages
+            .iter()
+            .map(|message| message.sender_inbox_id.as_str())
+            .collect();
+        let mut senders = Vec::with_capacity(sender_inbox_ids.len());
+        for inbox_id in sender_inbox_ids {
+            let state = self
+                .client
+                .get_association_state(conn, inbox_id, None)
+                .await?;
+            senders.push(SenderIdentity {
+                inbox_id: inbox_id.to_string(),
+                sequence_id: conn.get_latest_sequence_id_for_inbox(inbox_id)?,
+                addresses: state.account_addresses(),
+                installation_ids: state.installation_ids(),
+            });
+        }
+
+        let contents = ReportContents {
+            reason,
+            group_id: self.group_id.clone(),
+            conversation_type: self.metadata(provider).await?.conversation_type,
+            // DMs have no name
+            group_name: self.group_name(provider).unwrap_or_default(),
+            member_inbox_ids: self
+                .members_with_provider(provider)
+                .await?
+                .into_iter()
+                .map(|member| member.inbox_id)
+                .collect(),
+            messages: messages.into_iter().map(Into::into).collect(),
+            senders,
+        };
+        let context = self.context();
+        let mut report = SignedReport {
+            version: REPORT_VERSION,
+            reporter_inbox_id: context.inbox_id().to_string(),
+            reporter_installation_id: context.installation_public_key().into(),
+            created_at_ns: now_ns(),
+            payload: serde_json::to_vec(&contents).map_err(ReportError::from)?,
+            signature: vec![],
+        };
+        report.signature = context.sign_with_public_context(report.signature_text())?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_reports_are_signed_by_the_reporter() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_group = bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap()[0].clone();
+        bo_group.send_message(b"buy my coin").await.unwrap();
+        group.sync().await.unwrap();
+        let message = group
+            .find_messages(&MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            })
+            .unwrap()
+            .pop()
+            .unwrap();
+
+        let report = group
+            .report_message(&message.id, ReportReason::Spam)
+            .await
+            .unwrap();
+        assert_eq!(report.reporter_inbox_id, alix.inbox_id());
+        let contents = report.verify().unwrap();
+        assert_eq!(contents.reason, ReportReason::Spam);
+        assert_eq!(contents.messages[0].content, b"buy my coin");
+        assert_eq!(contents.senders[0].inbox_id, bo.inbox_id());
+        assert_eq!(contents.member_inbox_ids.len(), 2);
+
+        // changing the report breaks the signature
+        let mut forged = report.clone();
+        forged.payload = serde_json::to_vec(&ReportContents {
+            reason: ReportReason::Harassment,
+            ..contents
+        })
+        .unwrap();
+        assert!(matches!(forged.verify(), Err(ReportError::Signature(_))));
+
+        assert!(matches!(
+            group.report_message(b"nope", ReportReason::Spam).await,
+            Err(GroupError::Report(ReportError::MessageNotFound(_)))
+        ));
+        let conversation = group
+            .report_conversation(ReportReason::Other("scam".to_string()))
+            .await
+            .unwrap()
+            .verify()
+            .unwrap();
+        assert_eq!(conversation.messages.len(), 1);
+    }
+}
@@ -0,0 +1,140 @@
+//! Scratch storage for app state tied to a conversation, such as scroll anchors or per-chat
+//! feature flags. Entries stay on this installation, are never sent to other members, and
+//! are removed when the conversation is deleted. Sizes are capped so apps don't use it as a
+//! general-purpose store.
+use thiserror::Error;
+use xmtp_common::{time::now_ns, RetryableError};
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    storage::{conversation_scratch::StoredScratchEntry, ProviderTransactions},
+    Store,
+};
+
+pub const MAX_SCRATCH_KEY_LENGTH: usize = 128;
+pub const MAX_SCRATCH_VALUE_SIZE: usize = 4 * 1024;
+/// Combined size of the keys and values of a conversation's entries
+pub const MAX_SCRATCH_SIZE_PER_CONVERSATION: usize = 64 * 1024;
+
+#[derive(Debug, Error)]
+pub enum ScratchError {
+    #[error("scratch key must be between 1 and {MAX_SCRATCH_KEY_LENGTH} bytes")]
+    InvalidKey,
+    #[error("scratch value of {0} bytes is over the limit of {MAX_SCRATCH_VALUE_SIZE} bytes")]
+    ValueTooLarge(usize),
+    #[error(
+        "conversation scratch would use {0} bytes, over the limit of {MAX_SCRATCH_SIZE_PER_CONVERSATION} bytes"
+    )]
+    QuotaExceeded(usize),
+}
+
+impl RetryableError for ScratchError {
+    fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Set `key` to `value` in this conversation's scratch storage, replacing any earlier value
+    pub fn set_scratch(&self, key: &str, value: Vec<u8>) -> Result<(), GroupError> {
+        if key.is_empty() || key.len() > MAX_SCRATCH_KEY_LENGTH {
+            return Err(ScratchError::InvalidKey.into());
+        }
+        if value.len() > MAX_SCRATCH_VALUE_SIZE {
+            return Err(ScratchError::ValueTooLarge(value.len()).into());
+        }
+
+        let entry = StoredScratchEntry {
+            group_id: self.group_id.clone(),
+            key: key.to_string(),
+            value,
+            updated_at_ns: now_ns(),
+        };
+        let provider = self.mls_provider()?;
+        provider.transaction(|provider| {
+            let conn = provider.conn_ref();
+            let size = conn
+                .get_scratch_entries(&self.group_id)?
+                .iter()
+                .filter(|existing| existing.key != key)
+                .map(StoredScratchEntry::size)
+                .sum::<usize>()
+                + entry.size();
+            if size > MAX_SCRATCH_SIZE_PER_CONVERSATION {
+                return Err(ScratchError::QuotaExceeded(size).into());
+            }
+            entry.store(conn)?;
+            Ok::<_, GroupError>(())
+        })
+    }
+
+    pub fn scratch(&self, key: &str) -> Result<Option<Vec<u8>>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn
+            .get_scratch_entry(&self.group_id, key)?
+            .map(|entry| entry.value))
+    }
+
+    /// All of this conversation's scratch entries, ordered by key
+    pub fn scratch_entries(&self) -> Result<Vec<StoredScratchEntry>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn.get_scratch_entries(&self.group_id)?)
+    }
+
+    /// Delete scratch entries, optionally only the one with `key`.
+    /// Returns the number of entries deleted.
+    pub fn clear_scratch(&self, key: Option<&str>) -> Result<usize, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn.delete_scratch_entries(&self.group_id, key)?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_scratch_size_caps() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+
+        group.set_scratch("anchor", b"42".to_vec()).unwrap();
+        assert_eq!(group.scratch("anchor").unwrap(), Some(b"42".to_vec()));
+        assert_eq!(group.scratch("missing").unwrap(), None);
+
+        assert!(matches!(
+            group.set_scratch("", vec![]),
+            Err(GroupError::Scratch(ScratchError::InvalidKey))
+        ));
+        assert!(matches!(
+            group.set_scratch("big", vec![0; MAX_SCRATCH_VALUE_SIZE + 1]),
+            Err(GroupError::Scratch(ScratchError::ValueTooLarge(_)))
+        ));
+
+        let chunk = vec![0; MAX_SCRATCH_VALUE_SIZE];
+        let mut stored = 0;
+        let err = loop {
+            match group.set_scratch(&format!("chunk-{stored}"), chunk.clone()) {
+                Ok(()) => stored += 1,
+                Err(err) => break err,
+            }
+        };
+        assert!(matches!(
+            err,
+            GroupError::Scratch(ScratchError::QuotaExceeded(_))
+        ));
+        // replacing an entry doesn't count its old value against the quota
+        group.set_scratch("chunk-0", chunk.clone()).unwrap();
+
+        assert_eq!(group.clear_scratch(Some("anchor")).unwrap(), 1);
+        assert_eq!(group.scratch_entries().unwrap().len(), stored);
+    }
+}
@@ -0,0 +1,101 @@
+//! Per-message fan-out diagnostics for senders. While enabled, each message sent records the
+//! epoch it was encrypted in, how many member installations it was encrypted for, and when it
+//! was published and read back from the network, so a report of recipients missing a message
+//! can be checked against what the sender actually did.
+use std::sync::atomic::Ordering;
+
+use openmls::group::MlsGroup as OpenMlsGroup;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    storage::{
+        db_connection::DbConnection, group_intent::StoredGroupIntent,
+        send_diagnostic::StoredSendDiagnostic,
+    },
+    Client, Fetch, XmtpApi,
+};
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Record diagnostics for messages sent from now on. Off by default.
+    pub fn set_send_diagnostics(&self, enabled: bool) {
+        self.context
+            .send_diagnostics
+            .store(enabled, Ordering::SeqCst);
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Diagnostics recorded when `message_id` was sent, if diagnostics were enabled
+    pub fn send_diagnostics(
+        &self,
+        message_id: &[u8],
+    ) -> Result<Option<StoredSendDiagnostic>, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn.fetch(&message_id.to_vec())?)
+    }
+
+    /// Record the publishing of a send message intent, if diagnostics are enabled.
+    /// Returns the id of the message recorded.
+    pub(super) fn record_send_attempt(
+        &self,
+        conn: &DbConnection,
+        mls_group: &OpenMlsGroup,
+        intent: &StoredGroupIntent,
+    ) -> Result<Option<Vec<u8>>, GroupError> {
+        if !self.context().send_diagnostics.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        let Some(message_id) = intent.message_id()? else {
+            return Ok(None);
+        };
+        conn.record_send_attempt(
+            &message_id,
+            mls_group.epoch().as_u64() as i64,
+            mls_group.members().count() as i32,
+            intent.publish_attempts + 1,
+        )?;
+        Ok(Some(message_id))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_send_diagnostics() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+
+        let undiagnosed = group.send_message(b"before").await.unwrap();
+        assert!(group.send_diagnostics(&undiagnosed).unwrap().is_none());
+
+        alix.set_send_diagnostics(true);
+        let message_id = group.send_message(b"hello").await.unwrap();
+        let diagnostic = group.send_diagnostics(&message_id).unwrap().unwrap();
+        assert_eq!(diagnostic.epoch, 1);
+        assert_eq!(diagnostic.installation_count, 2);
+        assert_eq!(diagnostic.publish_attempts, 1);
+        assert!(diagnostic.published_at_ns.is_some());
+        assert!(diagnostic.receipt_cursor.is_some());
+        assert!(diagnostic.receipt_timestamp_ns.is_some());
+    }
+}
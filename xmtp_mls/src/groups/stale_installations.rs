@@ -0,0 +1,278 @@
+//! Detects installations that stopped sending anything, a sign the device is gone for good.
+//! Such installations still take up leaves in group trees and receive every welcome and commit.
+//!
+//! An installation's last activity is the latest message or commit this client received from
+//! it in any group, by server timestamp. Key package lifetimes aren't used: they're set by the
+//! installation itself, and say nothing about whether it's still around. An installation that
+//! never sent anything counts as active since this client joined the group.
+//!
+//! Detection only reports; removing members is left to an admin to approve. Commit validation
+//! only accepts removing installations together with their inbox (or after the inbox revoked
+//! them), so a member is only removable once every one of their installations is stale. Stale
+//! installations of members with a live installation are reported but kept.
+use xmtp_common::time::now_ns;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    client::ClientError,
+    configuration::{NS_IN_DAY, STALE_INSTALLATION_THRESHOLD_NS},
+    storage::group::{ConversationType, GroupMembershipState, GroupQueryArgs},
+    subscriptions::LocalEvents,
+    Client, XmtpApi,
+};
+
+/// Decides which installations are considered stale
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaleInstallationPolicy {
+    /// Installations that haven't been active for longer than this are stale
+    pub stale_after_ns: i64,
+    /// Whether to look for stale installations at all
+    pub enabled: bool,
+}
+
+impl Default for StaleInstallationPolicy {
+    fn default() -> Self {
+        Self {
+            stale_after_ns: STALE_INSTALLATION_THRESHOLD_NS,
+            enabled: true,
+        }
+    }
+}
+
+impl StaleInstallationPolicy {
+    /// Never report or remove stale installations
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    /// Consider installations stale after `months` 30-day months without activity
+    pub fn stale_after_months(mut self, months: u32) -> Self {
+        self.stale_after_ns = i64::from(months) * 30 * NS_IN_DAY;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleInstallation {
+    pub inbox_id: String,
+    pub installation_id: Vec<u8>,
+    /// When the installation last sent a message or commit received here, or `None` if it
+    /// never did
+    pub last_active_ns: Option<i64>,
+}
+
+/// The stale installations of one group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleInstallationReport {
+    pub group_id: Vec<u8>,
+    pub stale: Vec<StaleInstallation>,
+    /// Members whose every installation is stale, which an admin can approve removing with
+    /// [`MlsGroup::remove_stale_members`]
+    pub removable_inbox_ids: Vec<String>,
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Find the installations in this group that haven't been active within the policy's
+    /// threshold. This client's own inbox is never reported as removable.
+    pub async fn stale_installations(
+        &self,
+        policy: &StaleInstallationPolicy,
+    ) -> Result<StaleInstallationReport, GroupError> {
+        let provider = self.mls_provider()?;
+        let mut report = StaleInstallationReport {
+            group_id: self.group_id.clone(),
+            stale: vec![],
+            removable_inbox_ids: vec![],
+        };
+        if !policy.enabled {
+            return Ok(report);
+        }
+
+        let members = self.members_with_provider(&provider).await?;
+        let installation_ids: Vec<Vec<u8>> = members
+            .iter()
+            .flat_map(|member| member.installation_ids.iter().cloned())
+            .collect();
+        if installation_ids.is_empty() {
+            return Ok(report);
+        }
+        let last_active = provider
+            .conn_ref()
+            .installations_last_active_ns(&installation_ids)?;
+        let stale_before = now_ns().saturating_sub(policy.stale_after_ns);
+
+        for member in members {
+            let stale: Vec<StaleInstallation> = member
+                .installation_ids
+                .iter()
+                .filter_map(|installation_id| {
+                    let last_active_ns = last_active.get(installation_id).copied();
+                    (last_active_ns.unwrap_or(self.created_at_ns) < stale_before).then(|| {
+                        StaleInstallation {
+                            inbox_id: member.inbox_id.clone(),
+                            installation_id: installation_id.clone(),
+                            last_active_ns,
+                        }
+                    })
+                })
+                .collect();
+            if !stale.is_empty()
+                && stale.len() == member.installation_ids.len()
+                && member.inbox_id != self.client.inbox_id()
+            {
+                report.removable_inbox_ids.push(member.inbox_id.clone());
+            }
+            report.stale.extend(stale);
+        }
+        Ok(report)
+    }
+
+    /// Remove the members in `inbox_ids`, typically the admin-approved subset of a report's
+    /// [`StaleInstallationReport::removable_inbox_ids`]. Members that are no longer entirely
+    /// stale, e.g. because they came back online since the report, are kept.
+    /// Returns the members that were removed.
+    pub async fn remove_stale_members(
+        &self,
+        inbox_ids: &[String],
+        policy: &StaleInstallationPolicy,
+    ) -> Result<Vec<String>, GroupError> {
+        let removable = self.stale_installations(policy).await?.removable_inbox_ids;
+        let to_remove: Vec<String> = inbox_ids
+            .iter()
+            .filter(|inbox_id| removable.contains(inbox_id))
+            .cloned()
+            .collect();
+        if !to_remove.is_empty() {
+            let ids: Vec<&str> = to_remove.iter().map(String::as_str).collect();
+            self.remove_members_by_inbox_id(&ids).await?;
+            tracing::info!(
+                group_id = hex::encode(&self.group_id),
+                "removed {} members with only stale installations",
+                to_remove.len()
+            );
+        }
+        Ok(to_remove)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Look for stale installations in every group this client administers, and publish a
+    /// [`LocalEvents::StaleInstallationsDetected`] event for the groups with removable members
+    /// so an admin can approve their removal. Returns the reports of those groups.
+    pub async fn detect_stale_installations(
+        &self,
+        policy: &StaleInstallationPolicy,
+    ) -> Result<Vec<StaleInstallationReport>, ClientError> {
+        if !policy.enabled {
+            return Ok(vec![]);
+        }
+        let provider = self.mls_provider()?;
+        let inbox_id = self.inbox_id().to_string();
+        let groups = self.find_groups(
+            GroupQueryArgs::default()
+                .conversation_type(ConversationType::Group)
+                .allowed_states(vec![GroupMembershipState::Allowed]),
+        )?;
+
+        let mut reports = vec![];
+        for group in groups {
+            if !group.is_admin(inbox_id.clone(), &provider)?
+                && !group.is_super_admin(inbox_id.clone(), &provider)?
+            {
+                continue;
+            }
+            match group.stale_installations(policy).await {
+                Ok(report) if !report.removable_inbox_ids.is_empty() => reports.push(report),
+                Ok(_) => {}
+                Err(e) => tracing::warn!(
+                    group_id = hex::encode(&group.group_id),
+                    "failed to check for stale installations: {e}"
+                ),
+            }
+        }
+
+        if !reports.is_empty() {
+            self.publish_local_event(LocalEvents::StaleInstallationsDetected(reports.clone()))
+                .await;
+        }
+        Ok(reports)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions, subscriptions::StreamMessages,
+    };
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_stale_members_are_removed() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+
+        // everyone was just active
+        let report = group
+            .stale_installations(&StaleInstallationPolicy::default())
+            .await
+            .unwrap();
+        assert!(report.stale.is_empty());
+        assert!(alix
+            .detect_stale_installations(&StaleInstallationPolicy::disabled())
+            .await
+            .unwrap()
+            .is_empty());
+
+        let everything_stale = StaleInstallationPolicy {
+            stale_after_ns: -NS_IN_DAY,
+            ..Default::default()
+        };
+        let events = alix.local_events.subscribe().stream_stale_installations();
+        futures::pin_mut!(events);
+        let reports = alix
+            .detect_stale_installations(&everything_stale)
+            .await
+            .unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].stale.len(), 2);
+        // alix committed the add, bo never sent anything
+        for stale in &reports[0].stale {
+            assert_eq!(
+                stale.last_active_ns.is_some(),
+                stale.inbox_id == alix.inbox_id()
+            );
+        }
+        assert_eq!(reports[0].removable_inbox_ids, [bo.inbox_id().to_string()]);
+        assert_eq!(events.next().await.unwrap().unwrap(), reports);
+
+        let removed = group
+            .remove_stale_members(&reports[0].removable_inbox_ids, &everything_stale)
+            .await
+            .unwrap();
+        assert_eq!(removed, [bo.inbox_id().to_string()]);
+        let members = group.members().await.unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].inbox_id, alix.inbox_id());
+    }
+}
@@ -1,7 +1,7 @@
 use futures::{Stream, StreamExt};
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
-use tokio::sync::oneshot;
 use xmtp_proto::api_client::trait_impls::XmtpApi;
 use xmtp_proto::api_client::XmtpMlsStreams;
 
@@ -13,12 +13,18 @@ use crate::storage::group_message::StoredGroupMessage;
 use crate::storage::refresh_state::EntityKind;
 use crate::storage::ProviderTransactions;
 use crate::storage::StorageError;
+use crate::stream_handles::spawn_stream_task;
 use crate::subscriptions::MessagesStreamInfo;
 use crate::subscriptions::SubscribeError;
-use crate::XmtpOpenMlsProvider;
+use crate::subscriptions::{
+    EpochChange, MessageDeletion, MessageEdit, MessagePin, PendingJoinRequest, PollUpdate,
+    StreamMessages, UnreadCountUpdate,
+};
+use crate::{Client, StreamMetrics, XmtpOpenMlsProvider};
 use prost::Message;
 use xmtp_common::{retry_async, Retry};
-use xmtp_proto::xmtp::mls::api::v1::GroupMessage;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::xmtp::mls::api::v1::{group_message::Version as GroupMessageVersion, GroupMessage};
 
 impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
     /// Internal stream processing function
@@ -63,6 +69,7 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
                         .await
                 })
             );
+            self.publish_committed_events(provider.conn_ref());
 
             if let Err(SubscribeError::ReceiveGroup(_)) = process_result {
                 tracing::debug!(
@@ -99,6 +106,7 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
                     e
                 );
             }
+            self.keep_super_admin_heartbeat(provider).await;
         }
 
         // Load the message from the DB to handle cases where it may have been already processed in
@@ -107,6 +115,12 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
             .conn_ref()
             .get_group_message_by_timestamp(&self.group_id, created_ns as i64)?
             .ok_or(SubscribeError::GroupMessageNotFound)?;
+        if provider
+            .conn_ref()
+            .is_sender_hidden(&new_message.sender_inbox_id)?
+        {
+            return Err(SubscribeError::HiddenSender);
+        }
 
         Ok(new_message)
     }
@@ -149,7 +163,7 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
                 cursor: 0,
             },
         )]);
-        stream_messages(&*self.client, Arc::new(group_list)).await
+        stream_messages(&*self.client, Arc::new(group_list), None).await
     }
 
     pub fn stream_with_callback(
@@ -171,6 +185,144 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
         )]);
         stream_messages_with_callback(client, group_list, callback)
     }
+
+    /// Like [`Self::stream_with_callback`], but awaits the future returned by `callback`
+    /// before handling the next message
+    pub fn stream_with_async_callback<Fut>(
+        client: ScopedClient,
+        group_id: Vec<u8>,
+        created_at_ns: i64,
+        callback: impl FnMut(Result<StoredGroupMessage, SubscribeError>) -> Fut + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), crate::groups::ClientError>>
+    where
+        ScopedClient: 'static,
+        <ScopedClient as ScopedGroupClient>::ApiClient: XmtpMlsStreams + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let group_list = HashMap::from([(
+            group_id,
+            MessagesStreamInfo {
+                convo_created_at_ns: created_at_ns,
+                cursor: 0,
+            },
+        )]);
+        stream_messages_with_async_callback(client, group_list, callback)
+    }
+}
+
+impl<ApiClient, V> MlsGroup<Client<ApiClient, V>>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Stream the epochs this group moves to as commits are merged, whether sent by this
+    /// installation or received while syncing
+    pub fn stream_epoch_changes(
+        &self,
+    ) -> impl Stream<Item = Result<EpochChange, SubscribeError>> + 'static {
+        let group_id = self.group_id.clone();
+        self.client
+            .local_events
+            .subscribe()
+            .stream_epoch_changes()
+            .filter(move |change| {
+                // errors such as lag are passed along so the consumer can resync
+                futures::future::ready(!matches!(change, Ok(change) if change.group_id != group_id))
+            })
+    }
+
+    /// Stream the edits applied to this group's messages, whether sent by this installation
+    /// or received while syncing
+    pub fn stream_message_edits(
+        &self,
+    ) -> impl Stream<Item = Result<MessageEdit, SubscribeError>> + 'static {
+        let group_id = self.group_id.clone();
+        self.client
+            .local_events
+            .subscribe()
+            .stream_message_edits()
+            .filter(move |edit| {
+                futures::future::ready(!matches!(edit, Ok(edit) if edit.group_id != group_id))
+            })
+    }
+
+    /// Stream the deletions of this group's messages, whether sent by this installation or
+    /// received while syncing
+    pub fn stream_message_deletions(
+        &self,
+    ) -> impl Stream<Item = Result<MessageDeletion, SubscribeError>> + 'static {
+        let group_id = self.group_id.clone();
+        self.client
+            .local_events
+            .subscribe()
+            .stream_message_deletions()
+            .filter(move |deletion| {
+                futures::future::ready(
+                    !matches!(deletion, Ok(deletion) if deletion.group_id != group_id),
+                )
+            })
+    }
+
+    /// Stream messages pinned to or unpinned from this group, by this installation or by other
+    /// members
+    pub fn stream_message_pins(
+        &self,
+    ) -> impl Stream<Item = Result<MessagePin, SubscribeError>> + 'static {
+        let group_id = self.group_id.clone();
+        self.client
+            .local_events
+            .subscribe()
+            .stream_message_pins()
+            .filter(move |pin| {
+                futures::future::ready(!matches!(pin, Ok(pin) if pin.group_id != group_id))
+            })
+    }
+
+    /// Stream the tallies of this group's polls as votes are counted, including this
+    /// installation's own votes
+    pub fn stream_poll_updates(
+        &self,
+    ) -> impl Stream<Item = Result<PollUpdate, SubscribeError>> + 'static {
+        let group_id = self.group_id.clone();
+        self.client
+            .local_events
+            .subscribe()
+            .stream_poll_updates()
+            .filter(move |update| {
+                futures::future::ready(!matches!(update, Ok(update) if update.group_id != group_id))
+            })
+    }
+
+    /// Stream this group's unread count as messages arrive and the group is read
+    pub fn stream_unread_count(
+        &self,
+    ) -> impl Stream<Item = Result<UnreadCountUpdate, SubscribeError>> + 'static {
+        let group_id = self.group_id.clone();
+        self.client
+            .local_events
+            .subscribe()
+            .stream_unread_counts()
+            .filter(move |update| {
+                futures::future::ready(!matches!(update, Ok(update) if update.group_id != group_id))
+            })
+    }
+
+    /// Stream requests to join this group as they arrive. Only the admins the requests were sent
+    /// to receive them.
+    pub fn stream_join_requests(
+        &self,
+    ) -> impl Stream<Item = Result<PendingJoinRequest, SubscribeError>> + 'static {
+        let group_id = self.group_id.clone();
+        self.client
+            .local_events
+            .subscribe()
+            .stream_join_requests()
+            .filter(move |request| {
+                futures::future::ready(
+                    !matches!(request, Ok(request) if request.group_id != group_id),
+                )
+            })
+    }
 }
 
 /// Stream messages from groups in `group_id_to_info`
@@ -179,6 +331,7 @@ impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
 pub(crate) async fn stream_messages<'a, ScopedClient>(
     client: &'a ScopedClient,
     group_id_to_info: Arc<HashMap<Vec<u8>, MessagesStreamInfo>>,
+    metrics: Option<StreamMetrics>,
 ) -> Result<impl Stream<Item = Result<StoredGroupMessage, SubscribeError>> + 'a, ClientError>
 where
     ScopedClient: ScopedGroupClient,
@@ -194,10 +347,16 @@ where
     let stream = messages_subscription
         .then(move |res| {
             let group_id_to_info = group_id_to_info.clone();
+            let metrics = metrics.clone();
             async move {
                 let provider = client.mls_provider()?;
                 let envelope = res.map_err(GroupError::from)?;
                 let group_id = extract_group_id(&envelope)?;
+                if let (Some(metrics), Some(GroupMessageVersion::V1(v1))) =
+                    (&metrics, &envelope.version)
+                {
+                    metrics.record_cursor(v1.id);
+                }
                 tracing::info!(
                     inbox_id = client.inbox_id(),
                     group_id = hex::encode(&group_id),
@@ -220,7 +379,10 @@ where
             }
         })
         .filter(|e| {
-            futures::future::ready(!matches!(e, Err(SubscribeError::GroupMessageNotFound)))
+            futures::future::ready(!matches!(
+                e,
+                Err(SubscribeError::GroupMessageNotFound | SubscribeError::HiddenSender)
+            ))
         });
 
     Ok(stream)
@@ -237,15 +399,37 @@ where
     ScopedClient: ScopedGroupClient + 'static,
     <ScopedClient as ScopedGroupClient>::ApiClient: XmtpApi + XmtpMlsStreams + 'static,
 {
-    let (tx, rx) = oneshot::channel();
+    stream_messages_with_async_callback(client, group_id_to_info, move |message| {
+        callback(message);
+        futures::future::ready(())
+    })
+}
 
-    crate::spawn(Some(rx), async move {
-        let stream = stream_messages(&client, Arc::new(group_id_to_info)).await?;
-        futures::pin_mut!(stream);
-        let _ = tx.send(());
-        while let Some(message) = stream.next().await {
+/// Stream messages from groups in `group_id_to_info`, awaiting the future returned by
+/// `callback` for each message before handling the next one.
+pub(crate) fn stream_messages_with_async_callback<ScopedClient, Fut>(
+    client: ScopedClient,
+    group_id_to_info: HashMap<Vec<u8>, MessagesStreamInfo>,
+    mut callback: impl FnMut(Result<StoredGroupMessage, SubscribeError>) -> Fut + Send + 'static,
+) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>>
+where
+    ScopedClient: ScopedGroupClient + 'static,
+    <ScopedClient as ScopedGroupClient>::ApiClient: XmtpApi + XmtpMlsStreams + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    spawn_stream_task(move |task| async move {
+        let stream = stream_messages(
+            &client,
+            Arc::new(group_id_to_info),
+            Some(task.metrics().clone()),
+        )
+        .await?;
+        let stream = task.until_closed(stream);
+        task.run(stream, |metrics, message| {
+            metrics.record(&message);
             callback(message)
-        }
+        })
+        .await;
         tracing::debug!("`stream_messages` stream ended, dropping stream");
         Ok::<_, ClientError>(())
     })
@@ -443,4 +627,48 @@ pub(crate) mod tests {
         let second_val = stream.next().await.unwrap().unwrap();
         assert_eq!(second_val.decrypted_message_bytes, "hello".as_bytes());
     }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_stream_epoch_changes() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let alix_changes = alix_group.stream_epoch_changes();
+        futures::pin_mut!(alix_changes);
+
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let change = alix_changes.next().await.unwrap().unwrap();
+        assert_eq!(change.group_id, alix_group.group_id);
+        assert_eq!(change.epoch, 1);
+        assert_eq!(change.originator_inbox_id, alix.inbox_id());
+        assert_eq!(
+            change.originator_installation_id,
+            alix.installation_public_key()
+        );
+
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+        let bo_changes = bo_group.stream_epoch_changes();
+        futures::pin_mut!(bo_changes);
+
+        alix_group.key_update().await.unwrap();
+        let change = alix_changes.next().await.unwrap().unwrap();
+        assert_eq!(change.epoch, 2);
+        assert!(change.key_rotation);
+
+        bo_group.sync().await.unwrap();
+        let change = bo_changes.next().await.unwrap().unwrap();
+        assert_eq!(change.epoch, 2);
+        assert_eq!(change.originator_inbox_id, alix.inbox_id());
+        assert!(change.key_rotation);
+    }
 }
@@ -0,0 +1,542 @@
+//! Handing over a group's super admin role, so groups don't end up without anyone able to manage
+//! them.
+//!
+//! A super admin can offer the role to another member, who becomes super admin by accepting it.
+//! Accepting is a single commit that makes them super admin, demotes the member who offered the
+//! role and withdraws the offer. An offer lapses once the member who made it is no longer super
+//! admin.
+//!
+//! A super admin can also designate a successor who may claim the role once every super admin
+//! has gone silent for a dormancy period. While a successor is designated, super admin
+//! installations record a heartbeat in the group's metadata as they sync, send and stream, and a
+//! claim is only valid if the heartbeat is older than the dormancy period when the claiming commit
+//! reaches the network. Heartbeats are written with the writer's clock, so members reject a
+//! commit whose heartbeat is ahead of the commit's own server timestamp by more than
+//! [`MAX_HEARTBEAT_CLOCK_SKEW_NS`]: a clock running ahead can't keep the role from the successor.
+//! Members check all of this against the group's metadata and server timestamps, so they all
+//! agree on whether a member may make themselves super admin.
+//!
+//! The state lives in metadata fields only super admins can change, except that a member
+//! accepting an offer withdraws it.
+use std::time::Duration;
+
+use thiserror::Error;
+use xmtp_common::{time::now_ns, RetryableError};
+
+use super::{
+    group_mutable_metadata::GroupMutableMetadata, intents::UpdateMetadataIntentData,
+    validated_commit::MetadataFieldChange, GroupError, MlsGroup, ScopedGroupClient,
+    UpdateAdminListType,
+};
+use crate::{configuration::NS_IN_SEC, storage::group_intent::IntentKind, XmtpOpenMlsProvider};
+
+/// The metadata field holding the pending [`SuperAdminTransfer`]
+pub const SUPER_ADMIN_TRANSFER_METADATA_FIELD: &str = "_super_admin_transfer";
+/// The metadata field holding the group's [`SuperAdminSuccession`]
+pub const SUPER_ADMIN_SUCCESSION_METADATA_FIELD: &str = "_super_admin_succession";
+/// The metadata field holding when a super admin was last active, in nanoseconds
+pub const SUPER_ADMIN_HEARTBEAT_METADATA_FIELD: &str = "_super_admin_heartbeat_ns";
+
+/// How often super admins refresh their heartbeat, as a fraction of the dormancy period
+const HEARTBEATS_PER_DORMANCY_PERIOD: i64 = 4;
+
+/// How far ahead of the server timestamp of its commit a heartbeat may be
+pub const MAX_HEARTBEAT_CLOCK_SKEW_NS: i64 = 5 * 60 * NS_IN_SEC;
+
+#[derive(Debug, Error)]
+pub enum SuccessionError {
+    #[error("only super admins can hand over the super admin role")]
+    NotSuperAdmin,
+    #[error("inbox {0} is not a member of the group")]
+    NotAMember(String),
+    #[error("inbox {0} is already a super admin")]
+    AlreadySuperAdmin(String),
+    #[error("no super admin transfer was offered to this inbox")]
+    NoTransferOffered,
+    #[error("this inbox is not the designated successor")]
+    NotSuccessor,
+    #[error("the super admins of the group are still active")]
+    NotDormant,
+}
+
+impl RetryableError for SuccessionError {
+    fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+/// The super admin role offered by a super admin to another member
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuperAdminTransfer {
+    pub from_inbox_id: String,
+    pub to_inbox_id: String,
+}
+
+impl SuperAdminTransfer {
+    /// The pending offer, unless the member who made it is no longer super admin
+    pub(super) fn from_metadata(metadata: &GroupMutableMetadata) -> Option<Self> {
+        let (from, to) = metadata
+            .attributes
+            .get(SUPER_ADMIN_TRANSFER_METADATA_FIELD)?
+            .split_once(':')?;
+        if !metadata.is_super_admin(&from.to_string()) {
+            return None;
+        }
+        Some(Self {
+            from_inbox_id: from.to_string(),
+            to_inbox_id: to.to_string(),
+        })
+    }
+
+    fn encode(&self) -> String {
+        format!("{}:{}", self.from_inbox_id, self.to_inbox_id)
+    }
+}
+
+/// Who may claim the super admin role once the super admins go silent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuperAdminSuccession {
+    pub successor_inbox_id: String,
+    /// How long every super admin must have been silent before the successor can claim the role
+    pub dormancy: Duration,
+    /// When the successor was designated, which counts as a heartbeat
+    pub designated_at_ns: i64,
+}
+
+impl SuperAdminSuccession {
+    fn from_metadata(metadata: &GroupMutableMetadata) -> Option<Self> {
+        Self::decode(
+            metadata
+                .attributes
+                .get(SUPER_ADMIN_SUCCESSION_METADATA_FIELD)?,
+        )
+    }
+
+    fn decode(value: &str) -> Option<Self> {
+        let mut parts = value.splitn(3, ':');
+        Some(Self {
+            successor_inbox_id: parts.next()?.to_string(),
+            dormancy: Duration::from_nanos(parts.next()?.parse().ok()?),
+            designated_at_ns: parts.next()?.parse().ok()?,
+        })
+    }
+
+    fn encode(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.successor_inbox_id,
+            self.dormancy_ns(),
+            self.designated_at_ns
+        )
+    }
+
+    fn dormancy_ns(&self) -> i64 {
+        i64::try_from(self.dormancy.as_nanos()).unwrap_or(i64::MAX)
+    }
+}
+
+/// The latest heartbeat of the super admins: the heartbeat field, or the designation of the
+/// successor if that's later
+fn heartbeat_ns(metadata: &GroupMutableMetadata) -> Option<i64> {
+    let heartbeat_ns = metadata
+        .attributes
+        .get(SUPER_ADMIN_HEARTBEAT_METADATA_FIELD)
+        .and_then(|heartbeat_ns| heartbeat_ns.parse().ok());
+    let designated_at_ns =
+        SuperAdminSuccession::from_metadata(metadata).map(|succession| succession.designated_at_ns);
+    heartbeat_ns.max(designated_at_ns)
+}
+
+/// How a member who isn't super admin may make themselves one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SuperAdminClaim {
+    /// The role was offered to them by `from_inbox_id`, whom they may demote in the same commit
+    Transfer { from_inbox_id: String },
+    /// They are the designated successor of super admins that have been dormant long enough
+    Succession,
+}
+
+/// How `inbox_id` may make themselves super admin in a commit that reached the network at
+/// `timestamp_ns`, given the group's `metadata` before the commit, if they may at all
+pub(super) fn super_admin_claim(
+    metadata: &GroupMutableMetadata,
+    inbox_id: &str,
+    timestamp_ns: i64,
+) -> Option<SuperAdminClaim> {
+    if let Some(transfer) =
+        SuperAdminTransfer::from_metadata(metadata).filter(|t| t.to_inbox_id == inbox_id)
+    {
+        return Some(SuperAdminClaim::Transfer {
+            from_inbox_id: transfer.from_inbox_id,
+        });
+    }
+    let succession = SuperAdminSuccession::from_metadata(metadata)?;
+    let dormant = succession.successor_inbox_id == inbox_id
+        && heartbeat_ns(metadata).is_some_and(|heartbeat_ns| {
+            timestamp_ns.saturating_sub(heartbeat_ns) >= succession.dormancy_ns()
+        });
+    dormant.then_some(SuperAdminClaim::Succession)
+}
+
+/// Whether the heartbeats written by `changes`, in a commit that reached the network at
+/// `timestamp_ns`, are at most [`MAX_HEARTBEAT_CLOCK_SKEW_NS`] ahead of it
+pub(super) fn heartbeats_are_valid<'a>(
+    mut changes: impl Iterator<Item = &'a MetadataFieldChange>,
+    timestamp_ns: i64,
+) -> bool {
+    let latest_valid_ns = timestamp_ns.saturating_add(MAX_HEARTBEAT_CLOCK_SKEW_NS);
+    changes.all(|change| {
+        let Some(value) = change
+            .new_value
+            .as_deref()
+            .filter(|value| !value.is_empty())
+        else {
+            return true;
+        };
+        let written_at_ns = match change.field_name.as_str() {
+            SUPER_ADMIN_HEARTBEAT_METADATA_FIELD => value.parse().ok(),
+            SUPER_ADMIN_SUCCESSION_METADATA_FIELD => {
+                SuperAdminSuccession::decode(value).map(|succession| succession.designated_at_ns)
+            }
+            _ => return true,
+        };
+        written_at_ns.is_some_and(|written_at_ns: i64| written_at_ns <= latest_valid_ns)
+    })
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Offer the super admin role to `inbox_id`, a member of the group. The role moves once they
+    /// accept it with [`Self::accept_super_admin_transfer`]. Replaces any pending offer.
+    pub async fn transfer_super_admin(&self, inbox_id: &str) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        let metadata = self.mutable_metadata(&provider)?;
+        self.check_super_admin(&metadata)?;
+        if metadata.is_super_admin(&inbox_id.to_string()) {
+            return Err(SuccessionError::AlreadySuperAdmin(inbox_id.to_string()).into());
+        }
+        if !self
+            .members()
+            .await?
+            .iter()
+            .any(|member| member.inbox_id == inbox_id)
+        {
+            return Err(SuccessionError::NotAMember(inbox_id.to_string()).into());
+        }
+
+        let transfer = SuperAdminTransfer {
+            from_inbox_id: self.context().inbox_id().to_string(),
+            to_inbox_id: inbox_id.to_string(),
+        };
+        self.update_succession_field(SUPER_ADMIN_TRANSFER_METADATA_FIELD, transfer.encode())
+            .await
+    }
+
+    /// Withdraw the pending super admin transfer, if any
+    pub async fn cancel_super_admin_transfer(&self) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        self.check_super_admin(&self.mutable_metadata(&provider)?)?;
+        self.update_succession_field(SUPER_ADMIN_TRANSFER_METADATA_FIELD, String::new())
+            .await
+    }
+
+    /// The super admin transfer waiting to be accepted, as of the group's last sync. An offer
+    /// made by a member who has since stopped being super admin has lapsed.
+    pub fn pending_super_admin_transfer(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<Option<SuperAdminTransfer>, GroupError> {
+        Ok(SuperAdminTransfer::from_metadata(
+            &self.mutable_metadata(provider)?,
+        ))
+    }
+
+    /// Accept the super admin role offered to this inbox. In a single commit, this inbox
+    /// becomes super admin, the member who offered the role stops being one and the offer is
+    /// withdrawn.
+    pub async fn accept_super_admin_transfer(&self) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        let inbox_id = self.context().inbox_id().to_string();
+        let metadata = self.mutable_metadata(&provider)?;
+        if !SuperAdminTransfer::from_metadata(&metadata)
+            .is_some_and(|transfer| transfer.to_inbox_id == inbox_id)
+        {
+            return Err(SuccessionError::NoTransferOffered.into());
+        }
+
+        let intent = self.queue_intent(&provider, IntentKind::AcceptSuperAdminTransfer, vec![])?;
+        self.sync_until_intent_resolved(&provider, intent.id).await
+    }
+
+    /// Let `inbox_id` claim the super admin role once every super admin has been silent for
+    /// `dormancy`. Replaces any designated successor.
+    pub async fn set_super_admin_successor(
+        &self,
+        inbox_id: &str,
+        dormancy: Duration,
+    ) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        self.check_super_admin(&self.mutable_metadata(&provider)?)?;
+        if !self
+            .members()
+            .await?
+            .iter()
+            .any(|member| member.inbox_id == inbox_id)
+        {
+            return Err(SuccessionError::NotAMember(inbox_id.to_string()).into());
+        }
+
+        // The designation is a heartbeat, without one the successor could never claim the role
+        let succession = SuperAdminSuccession {
+            successor_inbox_id: inbox_id.to_string(),
+            dormancy,
+            designated_at_ns: now_ns(),
+        };
+        self.update_succession_field(SUPER_ADMIN_SUCCESSION_METADATA_FIELD, succession.encode())
+            .await
+    }
+
+    /// Stop anyone from claiming the super admin role through dormancy
+    pub async fn remove_super_admin_successor(&self) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        self.check_super_admin(&self.mutable_metadata(&provider)?)?;
+        self.update_succession_field(SUPER_ADMIN_SUCCESSION_METADATA_FIELD, String::new())
+            .await
+    }
+
+    /// The group's designated successor, as of its last sync
+    pub fn super_admin_successor(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<Option<SuperAdminSuccession>, GroupError> {
+        Ok(SuperAdminSuccession::from_metadata(
+            &self.mutable_metadata(provider)?,
+        ))
+    }
+
+    /// Make this inbox super admin as the designated successor of super admins that have gone
+    /// silent. Members only accept the change if the super admins' last heartbeat is older than
+    /// the dormancy period when the commit reaches the network.
+    pub async fn claim_super_admin(&self) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        let inbox_id = self.context().inbox_id().to_string();
+        let metadata = self.mutable_metadata(&provider)?;
+        if metadata.is_super_admin(&inbox_id) {
+            return Err(SuccessionError::AlreadySuperAdmin(inbox_id).into());
+        }
+        if !SuperAdminSuccession::from_metadata(&metadata)
+            .is_some_and(|succession| succession.successor_inbox_id == inbox_id)
+        {
+            return Err(SuccessionError::NotSuccessor.into());
+        }
+        if super_admin_claim(&metadata, &inbox_id, now_ns()) != Some(SuperAdminClaim::Succession) {
+            return Err(SuccessionError::NotDormant.into());
+        }
+
+        self.update_admin_list(UpdateAdminListType::AddSuper, inbox_id)
+            .await
+    }
+
+    /// Refresh the super admin heartbeat as part of activity in the group: syncing, sending or
+    /// streaming. Failing to refresh it is logged, it must not fail the activity.
+    pub(crate) async fn keep_super_admin_heartbeat(&self, provider: &XmtpOpenMlsProvider) {
+        if let Err(e) = self.maybe_refresh_super_admin_heartbeat(provider).await {
+            tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                "failed to refresh super admin heartbeat: {e}"
+            );
+        }
+    }
+
+    /// Refresh the super admin heartbeat if this inbox is a super admin, the group has a
+    /// designated successor and the heartbeat is getting old
+    async fn maybe_refresh_super_admin_heartbeat(
+        &self,
+        provider: &XmtpOpenMlsProvider,
+    ) -> Result<(), GroupError> {
+        let metadata = self.mutable_metadata(provider)?;
+        if !metadata.is_super_admin(&self.context().inbox_id().to_string()) {
+            return Ok(());
+        }
+        let Some(succession) = SuperAdminSuccession::from_metadata(&metadata) else {
+            return Ok(());
+        };
+        let now = now_ns();
+        let refresh_after_ns = succession.dormancy_ns() / HEARTBEATS_PER_DORMANCY_PERIOD;
+        if heartbeat_ns(&metadata)
+            .is_some_and(|heartbeat_ns| now.saturating_sub(heartbeat_ns) < refresh_after_ns)
+        {
+            return Ok(());
+        }
+        self.update_succession_field(SUPER_ADMIN_HEARTBEAT_METADATA_FIELD, now.to_string())
+            .await
+    }
+
+    fn check_super_admin(&self, metadata: &GroupMutableMetadata) -> Result<(), GroupError> {
+        if !metadata.is_super_admin(&self.context().inbox_id().to_string()) {
+            return Err(SuccessionError::NotSuperAdmin.into());
+        }
+        Ok(())
+    }
+
+    async fn update_succession_field(&self, field: &str, value: String) -> Result<(), GroupError> {
+        let provider = self.mls_provider()?;
+        let intent_data: Vec<u8> = UpdateMetadataIntentData::new(field.to_string(), value).into();
+        let intent = self.queue_intent(&provider, IntentKind::MetadataUpdate, intent_data)?;
+
+        self.sync_until_intent_resolved(&provider, intent.id).await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_super_admin_transfer_and_succession() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id(), caro.inbox_id()])
+            .await
+            .unwrap();
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+        let caro_group = caro
+            .sync_welcomes(&caro.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+
+        // members can't make themselves super admin without an offer
+        assert!(bo_group
+            .update_admin_list(UpdateAdminListType::AddSuper, bo.inbox_id().to_string())
+            .await
+            .is_err());
+        assert!(matches!(
+            bo_group.accept_super_admin_transfer().await,
+            Err(GroupError::Succession(SuccessionError::NoTransferOffered))
+        ));
+
+        alix_group
+            .transfer_super_admin(bo.inbox_id())
+            .await
+            .unwrap();
+        bo_group.sync().await.unwrap();
+        let bo_provider = bo.mls_provider().unwrap();
+        assert_eq!(
+            bo_group.pending_super_admin_transfer(&bo_provider).unwrap(),
+            Some(SuperAdminTransfer {
+                from_inbox_id: alix.inbox_id().to_string(),
+                to_inbox_id: bo.inbox_id().to_string(),
+            })
+        );
+        bo_group.accept_super_admin_transfer().await.unwrap();
+        assert_eq!(
+            bo_group.super_admin_list(&bo_provider).unwrap(),
+            vec![bo.inbox_id().to_string()]
+        );
+        assert!(bo_group
+            .pending_super_admin_transfer(&bo_provider)
+            .unwrap()
+            .is_none());
+
+        // a successor can only claim the role once the super admins go silent
+        bo_group
+            .set_super_admin_successor(caro.inbox_id(), Duration::from_secs(3600))
+            .await
+            .unwrap();
+        caro_group.sync().await.unwrap();
+        assert!(matches!(
+            caro_group.claim_super_admin().await,
+            Err(GroupError::Succession(SuccessionError::NotDormant))
+        ));
+        bo_group
+            .set_super_admin_successor(caro.inbox_id(), Duration::from_nanos(1))
+            .await
+            .unwrap();
+        caro_group.sync().await.unwrap();
+        caro_group.claim_super_admin().await.unwrap();
+        alix_group.sync().await.unwrap();
+        assert!(alix_group
+            .is_super_admin(caro.inbox_id().to_string(), &alix.mls_provider().unwrap())
+            .unwrap());
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_offers_lapse_once_the_offerer_is_demoted() {
+        let metadata = |super_admin: &str| {
+            GroupMutableMetadata::new(
+                HashMap::from([(
+                    SUPER_ADMIN_TRANSFER_METADATA_FIELD.to_string(),
+                    "alix:bo".to_string(),
+                )]),
+                vec![],
+                vec![super_admin.to_string()],
+            )
+        };
+        assert_eq!(
+            super_admin_claim(&metadata("alix"), "bo", 0),
+            Some(SuperAdminClaim::Transfer {
+                from_inbox_id: "alix".to_string()
+            })
+        );
+        assert!(SuperAdminTransfer::from_metadata(&metadata("caro")).is_none());
+        assert_eq!(super_admin_claim(&metadata("caro"), "bo", 0), None);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_heartbeats_ahead_of_their_commit_are_rejected() {
+        let commit_ns = 1_000 * NS_IN_SEC;
+        let change = |field: &str, value: String| {
+            MetadataFieldChange::new(field.to_string(), None, Some(value))
+        };
+        let heartbeat = |heartbeat_ns: i64| {
+            change(
+                SUPER_ADMIN_HEARTBEAT_METADATA_FIELD,
+                heartbeat_ns.to_string(),
+            )
+        };
+        let designation = |designated_at_ns: i64| {
+            let succession = SuperAdminSuccession {
+                successor_inbox_id: "caro".to_string(),
+                dormancy: Duration::from_secs(3600),
+                designated_at_ns,
+            };
+            change(SUPER_ADMIN_SUCCESSION_METADATA_FIELD, succession.encode())
+        };
+
+        let skewed_ns = commit_ns + MAX_HEARTBEAT_CLOCK_SKEW_NS;
+        assert!(heartbeats_are_valid(
+            [heartbeat(skewed_ns), designation(commit_ns)].iter(),
+            commit_ns
+        ));
+        assert!(!heartbeats_are_valid(
+            [heartbeat(skewed_ns + 1)].iter(),
+            commit_ns
+        ));
+        assert!(!heartbeats_are_valid(
+            [designation(skewed_ns + 1)].iter(),
+            commit_ns
+        ));
+        // clearing them is always allowed
+        assert!(heartbeats_are_valid(
+            [change(SUPER_ADMIN_SUCCESSION_METADATA_FIELD, String::new())].iter(),
+            commit_ns
+        ));
+    }
+}
@@ -0,0 +1,127 @@
+//! Storage for summaries apps generate from a range of a conversation's messages.
+//! Summaries are never sent to other members. Reading one reports whether messages have
+//! arrived since it was generated, so the app knows when to regenerate it.
+use xmtp_common::time::now_ns;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{storage::conversation_summary::StoredConversationSummary, Store};
+
+/// A stored summary, with how much the conversation changed since it was generated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversationSummary {
+    pub summary: StoredConversationSummary,
+    /// Messages sent after the end of the summarized range
+    pub messages_since: i64,
+    /// Messages were added to the summarized range after the summary was stored,
+    /// e.g. because they were synced late
+    pub range_changed: bool,
+}
+
+impl ConversationSummary {
+    /// Whether the summary no longer covers every message up to the most recent one
+    pub fn is_stale(&self) -> bool {
+        self.messages_since > 0 || self.range_changed
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Store `content` as the `key` summary of the messages sent between `start_ns` and
+    /// `end_ns`, inclusive, replacing any summary of the same range with the same key
+    pub fn save_summary(
+        &self,
+        key: &str,
+        start_ns: i64,
+        end_ns: i64,
+        content: Vec<u8>,
+    ) -> Result<StoredConversationSummary, GroupError> {
+        let conn = self.context().store().conn()?;
+        let summary = StoredConversationSummary {
+            group_id: self.group_id.clone(),
+            key: key.to_string(),
+            start_ns,
+            end_ns,
+            content,
+            message_count: conn.count_group_messages_between(&self.group_id, start_ns, end_ns)?,
+            created_at_ns: now_ns(),
+        };
+        summary.store(&conn)?;
+        Ok(summary)
+    }
+
+    /// Stored summaries, optionally only those with `key`, most recent range first
+    pub fn summaries(&self, key: Option<&str>) -> Result<Vec<ConversationSummary>, GroupError> {
+        let conn = self.context().store().conn()?;
+        conn.get_conversation_summaries(&self.group_id, key)?
+            .into_iter()
+            .map(|summary| {
+                let messages_since =
+                    conn.count_group_messages_after(&self.group_id, summary.end_ns)?;
+                let message_count = conn.count_group_messages_between(
+                    &self.group_id,
+                    summary.start_ns,
+                    summary.end_ns,
+                )?;
+                Ok(ConversationSummary {
+                    range_changed: message_count != summary.message_count,
+                    messages_since,
+                    summary,
+                })
+            })
+            .collect()
+    }
+
+    /// Delete stored summaries, optionally only those with `key`.
+    /// Returns the number of summaries deleted.
+    pub fn delete_summaries(&self, key: Option<&str>) -> Result<usize, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn.delete_conversation_summaries(&self.group_id, key)?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{
+        builder::ClientBuilder,
+        groups::GroupMetadataOptions,
+        storage::group_message::{GroupMessageKind, MsgQueryArgs},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_summaries_go_stale_when_messages_arrive() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group.send_message(b"hello").await.unwrap();
+        group.send_message(b"world").await.unwrap();
+
+        let messages = group
+            .find_messages(&MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            })
+            .unwrap();
+        let (start_ns, end_ns) = (messages[0].sent_at_ns, messages[1].sent_at_ns);
+        group
+            .save_summary("greeting", start_ns, end_ns, b"hello world".to_vec())
+            .unwrap();
+
+        let summaries = group.summaries(Some("greeting")).unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].summary.content, b"hello world");
+        assert!(!summaries[0].is_stale());
+
+        group.send_message(b"again").await.unwrap();
+        let summaries = group.summaries(None).unwrap();
+        assert_eq!(summaries[0].messages_since, 1);
+        assert!(summaries[0].is_stale());
+
+        assert_eq!(group.delete_summaries(Some("greeting")).unwrap(), 1);
+        assert!(group.summaries(None).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+//! Unread counts. A group's count goes up as messages from other members arrive, and is recounted
+//! when the group is read, either with [`MlsGroup::mark_as_read`] or by a read receipt this
+//! inbox sent from any of its installations. The hidden messages of blocked inboxes aren't
+//! counted, and deleted or disappeared messages stop counting. Each change is emitted as a
+//! [`LocalEvents::UnreadCountChanged`] event once it's committed.
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    storage::{
+        db_connection::DbConnection, group_message::StoredGroupMessage,
+        unread_count::UNREAD_CONTENT_TYPES,
+    },
+    subscriptions::{LocalEvents, UnreadCountUpdate},
+};
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Mark every message received so far as read on this installation. Other members and
+    /// installations aren't told, see [`Self::mark_read_until`] for that.
+    ///
+    /// The group is read up to the latest message's sent time rather than the local clock, so a
+    /// message sent earlier that arrives later, or a clock running ahead, doesn't read it.
+    pub fn mark_as_read(&self) -> Result<(), GroupError> {
+        let conn = self.context().store().conn()?;
+        let read_until_ns = conn
+            .latest_message_sent_at_ns(&self.group_id)?
+            .unwrap_or_default();
+        self.mark_read_at(&conn, read_until_ns)
+    }
+
+    /// The number of messages from other members that haven't been read
+    pub fn unread_count(&self) -> Result<u64, GroupError> {
+        let conn = self.context().store().conn()?;
+        Ok(conn
+            .get_unread_count(&self.group_id)?
+            .map(|count| count.unread_count as u64)
+            .unwrap_or_default())
+    }
+
+    /// Mark the messages sent up to `read_until_ns` as read
+    pub(super) fn mark_read_at(
+        &self,
+        conn: &DbConnection,
+        read_until_ns: i64,
+    ) -> Result<(), GroupError> {
+        let previous = conn
+            .get_unread_count(&self.group_id)?
+            .map(|count| count.unread_count);
+        let unread_count =
+            conn.mark_group_read(&self.group_id, read_until_ns, self.context().inbox_id())?;
+        if previous.unwrap_or_default() != unread_count {
+            self.emit_unread_count(conn, unread_count);
+        }
+        Ok(())
+    }
+
+    /// Recount the unread messages after some were deleted, keeping the read time. Failing to
+    /// recount is logged, it must not fail the deletion.
+    pub(super) fn recount_unread(&self, conn: &DbConnection) {
+        if let Err(e) = self.mark_read_at(conn, 0) {
+            tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                "failed to recount unread messages: {e}"
+            );
+        }
+    }
+
+    /// Count `message`, which was just received, if it's unread. Failing to count it is logged,
+    /// it must not fail message processing.
+    pub(super) fn process_unread_message(&self, conn: &DbConnection, message: &StoredGroupMessage) {
+        if !UNREAD_CONTENT_TYPES.contains(&message.content_type)
+            || message.sender_inbox_id == self.context().inbox_id()
+        {
+            return;
+        }
+        let counted =
+            conn.is_sender_hidden(&message.sender_inbox_id)
+                .and_then(|hidden| match hidden {
+                    true => Ok(None),
+                    false => conn.increment_unread_count(&self.group_id, message.sent_at_ns),
+                });
+        match counted {
+            Ok(Some(unread_count)) => self.emit_unread_count(conn, unread_count),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                message_id = hex::encode(&message.id),
+                "failed to count unread message: {e}"
+            ),
+        }
+    }
+
+    /// Emit the new count once the transaction it was changed in, if any, is committed
+    fn emit_unread_count(&self, conn: &DbConnection, unread_count: i64) {
+        self.publish_after_commit(
+            conn,
+            LocalEvents::UnreadCountChanged(UnreadCountUpdate {
+                group_id: self.group_id.clone(),
+                unread_count: unread_count as u64,
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::{encoded_content_to_bytes, text::TextCodec, ContentCodec};
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_unread_count() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        let bo_group = bo.group(alix_group.group_id.clone()).unwrap();
+        let updates = bo_group.stream_unread_count();
+        futures::pin_mut!(updates);
+
+        let text = |text: &str| encoded_content_to_bytes(TextCodec::encode(text.into()).unwrap());
+        let first = alix_group.send_message(&text("hi")).await.unwrap();
+        alix_group.send_message(&text("there")).await.unwrap();
+        let reply = bo_group.send_message(&text("hey")).await.unwrap();
+        bo_group.sync().await.unwrap();
+        assert_eq!(bo_group.unread_count().unwrap(), 2);
+        assert_eq!(updates.next().await.unwrap().unwrap().unread_count, 1);
+        assert_eq!(updates.next().await.unwrap().unwrap().unread_count, 2);
+
+        bo_group.mark_as_read().unwrap();
+        assert_eq!(bo_group.unread_count().unwrap(), 0);
+        assert_eq!(updates.next().await.unwrap().unwrap().unread_count, 0);
+        assert_eq!(
+            bo.list_conversations_overview(Default::default()).unwrap()[0].unread_count,
+            0
+        );
+
+        // deleted messages stop counting
+        let deleted = alix_group.send_message(&text("oops")).await.unwrap();
+        bo_group.sync().await.unwrap();
+        assert_eq!(updates.next().await.unwrap().unwrap().unread_count, 1);
+        alix_group.delete_message(&deleted).await.unwrap();
+        bo_group.sync().await.unwrap();
+        assert_eq!(bo_group.unread_count().unwrap(), 0);
+        assert_eq!(updates.next().await.unwrap().unwrap().unread_count, 0);
+
+        // this inbox's read receipts read the group too, on every installation that processes
+        // them
+        alix_group.sync().await.unwrap();
+        assert_eq!(alix_group.unread_count().unwrap(), 1);
+        alix_group.mark_read_until(&first).await.unwrap();
+        assert_eq!(alix_group.unread_count().unwrap(), 1);
+        alix_group.mark_read_until(&reply).await.unwrap();
+        assert_eq!(alix_group.unread_count().unwrap(), 0);
+    }
+}
@@ -29,6 +29,7 @@ use crate::{
 use xmtp_common::{retry::RetryableError, retryable};
 
 use super::{
+    bans::banned_inbox_ids,
     group_membership::{GroupMembership, MembershipDiff},
     group_metadata::{DmMembers, GroupMetadata, GroupMetadataError},
     group_mutable_metadata::{
@@ -37,6 +38,7 @@ use super::{
     group_permissions::{
         extract_group_permissions, GroupMutablePermissions, GroupMutablePermissionsError,
     },
+    succession::{heartbeats_are_valid, super_admin_claim, SuperAdminClaim},
     ScopedGroupClient,
 };
 
@@ -83,6 +85,10 @@ pub enum CommitValidationError {
     GroupMutablePermissions(#[from] GroupMutablePermissionsError),
     #[error("PSKs are not support")]
     NoPSKSupport,
+    #[error("Inbox {0} is banned from the group")]
+    BannedInboxAdded(String),
+    #[error("Super admin heartbeat is ahead of the commit")]
+    HeartbeatAhead,
 }
 
 impl RetryableError for CommitValidationError {
@@ -211,14 +217,19 @@ pub struct ValidatedCommit {
     pub metadata_changes: MutableMetadataChanges,
     pub permissions_changed: bool,
     pub dm_members: Option<DmMembers<String>>,
+    /// How the actor may make themselves super admin without being one, if they may, see
+    /// [`succession`](crate::groups::succession)
+    pub super_admin_claim: Option<SuperAdminClaim>,
 }
 
 impl ValidatedCommit {
+    /// `timestamp_ns` is when the commit reached the network
     pub async fn from_staged_commit(
         client: impl ScopedGroupClient,
         conn: &DbConnection,
         staged_commit: &StagedCommit,
         openmls_group: &OpenMlsGroup,
+        timestamp_ns: u64,
     ) -> Result<Self, CommitValidationError> {
         // Get the immutable and mutable metadata
         let extensions = openmls_group.extensions();
@@ -283,6 +294,31 @@ impl ValidatedCommit {
         )
         .await?;
 
+        // Banned inboxes can't be added back until they're unbanned
+        let banned = banned_inbox_ids(&mutable_metadata);
+        if let Some(inbox) = added_inboxes
+            .iter()
+            .find(|inbox| banned.contains(&inbox.inbox_id))
+        {
+            return Err(CommitValidationError::BannedInboxAdded(
+                inbox.inbox_id.clone(),
+            ));
+        }
+
+        // Installations removed and added back in the same commit keep their place in the
+        // group, it only gives them a fresh welcome. Only admins may do that.
+        let readded_installations: HashSet<Vec<u8>> = removed_installations
+            .intersection(&added_installations)
+            .cloned()
+            .collect();
+        if !readded_installations.is_empty() && !actor.is_admin && !actor.is_super_admin {
+            return Err(CommitValidationError::InsufficientPermissions);
+        }
+        let removed_installations = removed_installations
+            .difference(&readded_installations)
+            .cloned()
+            .collect();
+
         // Ensure that the expected diff matches the added/removed installations in the proposals
         expected_diff_matches_commit(
             &expected_installation_diff,
@@ -316,6 +352,14 @@ impl ValidatedCommit {
             }
         }
 
+        if !heartbeats_are_valid(
+            metadata_changes.metadata_field_changes.iter(),
+            timestamp_ns as i64,
+        ) {
+            return Err(CommitValidationError::HeartbeatAhead);
+        }
+        let super_admin_claim =
+            super_admin_claim(&mutable_metadata, &actor.inbox_id, timestamp_ns as i64);
         let verified_commit = Self {
             actor,
             added_inboxes,
@@ -323,6 +367,7 @@ impl ValidatedCommit {
             metadata_changes,
             permissions_changed,
             dm_members: immutable_metadata.dm_members,
+            super_admin_claim,
         };
 
         let policy_set = extract_group_permissions(openmls_group)?;
@@ -398,6 +443,17 @@ fn get_proposal_changes(
                 // building the expected installation diff
                 let leaf_node = add_proposal.key_package().leaf_node();
                 let installation_id = leaf_node.signature_key().as_slice().to_vec();
+                // An installation added back must still belong to the same inbox
+                if openmls_group
+                    .members()
+                    .any(|member| member.signature_key == installation_id)
+                {
+                    credentials_to_verify.push(CommitParticipant::from_leaf_node(
+                        leaf_node,
+                        immutable_metadata,
+                        mutable_metadata,
+                    )?);
+                }
                 added_installations.insert(installation_id);
             }
             // For Remove Proposals, all we need to do is validate that the installation_id is in the expected diff
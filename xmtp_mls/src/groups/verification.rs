@@ -0,0 +1,258 @@
+//! Reports on how the sender of a stored message was verified, for "verified sender" details in
+//! trust-center UIs and for localizing validation failures while debugging.
+//!
+//! Every check is made against the state at the time the message was sent: the group's
+//! membership as of then, reconstructed from the membership changes since, and the sender
+//! inbox's association state as of its last identity update before then. A sender who has since
+//! left the group or revoked the installation still passes the checks the message passed when it
+//! was sent.
+use prost::Message;
+use xmtp_content_types::{group_updated::GroupUpdatedCodec, CodecError, ContentCodec};
+use xmtp_id::associations::{AssociationState, MemberIdentifier};
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use super::{GroupError, MlsGroup, ScopedGroupClient};
+use crate::{
+    identity_updates::load_identity_updates,
+    storage::{
+        group_message::{GroupMessageKind, MsgQueryArgs, SortDirection, StoredGroupMessage},
+        DbConnection, NotFound, StorageError,
+    },
+};
+
+/// The outcome of one check of a [`VerificationReport`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationCheck {
+    Passed,
+    Failed(String),
+    /// The check could not run because a check it depends on failed
+    Skipped,
+}
+
+impl VerificationCheck {
+    pub fn passed(&self) -> bool {
+        matches!(self, Self::Passed)
+    }
+}
+
+/// Which checks the sender of a message passes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationReport {
+    pub message_id: Vec<u8>,
+    pub sender_inbox_id: String,
+    pub sender_installation_id: Vec<u8>,
+    /// The sender inbox was a member of the group when the message was sent
+    pub mls_membership: VerificationCheck,
+    /// Every association from the sender installation up to the address that created the inbox
+    /// is in the inbox's association state, so the installation's credential is vouched for by
+    /// the inbox's owner
+    pub credential_chain: VerificationCheck,
+    /// The sender inbox's association state included the sender installation when the message
+    /// was sent
+    pub installation_association: VerificationCheck,
+}
+
+impl VerificationReport {
+    /// Whether every check passed
+    pub fn is_verified(&self) -> bool {
+        self.mls_membership.passed()
+            && self.credential_chain.passed()
+            && self.installation_association.passed()
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Re-run the sender checks for the stored message `message_id`
+    pub async fn verify_message(
+        &self,
+        message_id: &[u8],
+    ) -> Result<VerificationReport, GroupError> {
+        let provider = self.mls_provider()?;
+        let conn = provider.conn_ref();
+        let message = conn
+            .get_group_message(message_id)?
+            .filter(|message| message.group_id == self.group_id)
+            .ok_or_else(|| StorageError::from(NotFound::MessageById(message_id.to_vec())))?;
+        let StoredGroupMessage {
+            id,
+            sender_inbox_id,
+            sender_installation_id,
+            sent_at_ns,
+            ..
+        } = message;
+
+        let is_member = self
+            .members_with_provider(&provider)
+            .await?
+            .iter()
+            .any(|member| member.inbox_id == sender_inbox_id);
+        let mls_membership = match self.was_member_at(conn, &sender_inbox_id, sent_at_ns, is_member)
+        {
+            Ok(true) => VerificationCheck::Passed,
+            Ok(false) => VerificationCheck::Failed(
+                "inbox was not a member of the group when the message was sent".to_string(),
+            ),
+            Err(e) => VerificationCheck::Failed(format!("no membership history: {e}")),
+        };
+
+        // fall back to the identity updates already stored when the network is unreachable
+        if let Err(e) =
+            load_identity_updates(self.client.api(), conn, &[sender_inbox_id.as_str()]).await
+        {
+            tracing::warn!(
+                inbox_id = sender_inbox_id,
+                "could not refresh identity updates for verification: {e}"
+            );
+        }
+        let sequence_id = conn
+            .get_identity_updates(&sender_inbox_id, None, None)?
+            .into_iter()
+            .rev()
+            .find(|update| update.server_timestamp_ns <= sent_at_ns)
+            .map(|update| update.sequence_id);
+        let association_state = match sequence_id {
+            Some(sequence_id) => self
+                .client
+                .get_association_state(conn, &sender_inbox_id, Some(sequence_id))
+                .await
+                .map_err(|e| format!("no association state: {e}")),
+            None => Err("inbox had no identity updates when the message was sent".to_string()),
+        };
+        let installation = MemberIdentifier::Installation(sender_installation_id.clone());
+        let (installation_association, credential_chain) = match association_state {
+            Ok(state) if state.get(&installation).is_some() => (
+                VerificationCheck::Passed,
+                match association_chain(&state, installation) {
+                    Ok(()) => VerificationCheck::Passed,
+                    Err(e) => VerificationCheck::Failed(e),
+                },
+            ),
+            Ok(_) => (
+                VerificationCheck::Failed(
+                    "installation was not associated with the inbox when the message was sent"
+                        .to_string(),
+                ),
+                VerificationCheck::Skipped,
+            ),
+            Err(e) => (VerificationCheck::Failed(e), VerificationCheck::Skipped),
+        };
+
+        Ok(VerificationReport {
+            message_id: id,
+            sender_inbox_id,
+            sender_installation_id,
+            mls_membership,
+            credential_chain,
+            installation_association,
+        })
+    }
+
+    /// Whether `inbox_id` was a member at `sent_at_ns`, given whether it's a member now. The first
+    /// membership change of the inbox after `sent_at_ns` tells: a removal means it was, an
+    /// addition means it wasn't.
+    fn was_member_at(
+        &self,
+        conn: &DbConnection,
+        inbox_id: &str,
+        sent_at_ns: i64,
+        is_member: bool,
+    ) -> Result<bool, GroupError> {
+        let changes = conn.get_group_messages(
+            &self.group_id,
+            &MsgQueryArgs {
+                sent_after_ns: Some(sent_at_ns),
+                kind: Some(GroupMessageKind::MembershipChange),
+                direction: Some(SortDirection::Ascending),
+                ..Default::default()
+            },
+        )?;
+        for change in changes {
+            let content = EncodedContent::decode(change.decrypted_message_bytes.as_slice())
+                .map_err(|e| CodecError::Decode(e.to_string()))?;
+            let update = GroupUpdatedCodec::decode(content)?;
+            if update
+                .removed_inboxes
+                .iter()
+                .any(|i| i.inbox_id == inbox_id)
+            {
+                return Ok(true);
+            }
+            if update.added_inboxes.iter().any(|i| i.inbox_id == inbox_id) {
+                return Ok(false);
+            }
+        }
+        Ok(is_member)
+    }
+}
+
+/// Follow the associations from `member` up to the address that created the inbox, the only
+/// member that wasn't added by another one
+fn association_chain(state: &AssociationState, member: MemberIdentifier) -> Result<(), String> {
+    let mut current = member;
+    // every link is a distinct member, so a longer chain has a cycle
+    for _ in 0..=state.members().len() {
+        let added_by = state
+            .get(&current)
+            .ok_or_else(|| format!("{current} is not associated with the inbox"))?
+            .added_by_entity
+            .clone();
+        match added_by {
+            Some(parent) => current = parent,
+            None if matches!(current, MemberIdentifier::Address(_)) => return Ok(()),
+            None => return Err(format!("{current} was not added by an address")),
+        }
+    }
+    Err("association chain has a cycle".to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::GroupMetadataOptions,
+        storage::group_message::{GroupMessageKind, MsgQueryArgs},
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_verify_message_sender() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_groups = bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        bo_groups[0].send_message(b"hi").await.unwrap();
+
+        group.sync().await.unwrap();
+        let messages = group
+            .find_messages(&MsgQueryArgs {
+                kind: Some(GroupMessageKind::Application),
+                ..Default::default()
+            })
+            .unwrap();
+        let report = group.verify_message(&messages[0].id).await.unwrap();
+        assert_eq!(report.sender_inbox_id, bo.inbox_id());
+        assert!(report.is_verified());
+
+        group
+            .remove_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        // bo was a member when the message was sent
+        let report = group.verify_message(&messages[0].id).await.unwrap();
+        assert!(report.is_verified());
+
+        assert!(group.verify_message(b"missing").await.is_err());
+    }
+}
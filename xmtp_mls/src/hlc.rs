@@ -0,0 +1,97 @@
+//! Hybrid logical clocks, for ordering preference writes made concurrently on different
+//! installations.
+//!
+//! A timestamp is the wall clock time of the write, a counter that orders writes within the
+//! same nanosecond, and the id of the installation that made it, which breaks the remaining
+//! ties. Every installation observes the timestamps it receives, so a write made after seeing
+//! another one is always ordered after it, even if the installation's clock is behind. Given
+//! the same writes, every installation picks the same latest one, whatever order they arrive in.
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use xmtp_common::time::now_ns;
+
+/// A hybrid logical clock timestamp. Timestamps are ordered by wall clock time, then counter,
+/// then node. The default is the zero timestamp, which orders before any real one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Hlc {
+    pub wall_ns: i64,
+    pub counter: u32,
+    /// The installation that made the write
+    pub node: Vec<u8>,
+}
+
+/// Issues timestamps for one installation
+pub struct HybridClock {
+    node: Vec<u8>,
+    /// Wall clock time and counter of the latest timestamp issued or observed
+    last: Mutex<(i64, u32)>,
+}
+
+impl HybridClock {
+    pub fn new(node: Vec<u8>) -> Self {
+        Self {
+            node,
+            last: Mutex::new((0, 0)),
+        }
+    }
+
+    /// A timestamp ordered after every timestamp issued or observed so far
+    pub fn now(&self) -> Hlc {
+        let mut last = self.last.lock();
+        let wall_ns = now_ns().max(last.0);
+        let counter = if wall_ns == last.0 { last.1 + 1 } else { 0 };
+        *last = (wall_ns, counter);
+        Hlc {
+            wall_ns,
+            counter,
+            node: self.node.clone(),
+        }
+    }
+
+    /// Move the clock past `remote`, a timestamp received from another installation
+    pub fn observe(&self, remote: &Hlc) {
+        let mut last = self.last.lock();
+        if (remote.wall_ns, remote.counter) > *last {
+            *last = (remote.wall_ns, remote.counter);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_writes_after_observing_are_ordered_after() {
+        let a = HybridClock::new(vec![1]);
+        let b = HybridClock::new(vec![2]);
+
+        let first = a.now();
+        let second = a.now();
+        assert!(second > first);
+
+        // b's wall clock is far behind a's
+        let ahead = Hlc {
+            wall_ns: now_ns() + 3_600_000_000_000,
+            counter: 7,
+            node: vec![1],
+        };
+        b.observe(&ahead);
+        let reply = b.now();
+        assert!(reply > ahead);
+        assert_eq!(reply.wall_ns, ahead.wall_ns);
+        assert_eq!(reply.counter, 8);
+
+        // the node breaks ties between otherwise equal timestamps
+        let tie = Hlc {
+            node: vec![2],
+            ..first.clone()
+        };
+        assert!(tie > first);
+        assert!(Hlc::default() < first);
+    }
+}
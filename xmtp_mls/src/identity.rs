@@ -35,7 +35,7 @@ use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
 use xmtp_id::{
     associations::{
         builder::{SignatureRequest, SignatureRequestBuilder, SignatureRequestError},
-        generate_inbox_id, sign_with_legacy_key, MemberIdentifier,
+        sign_with_legacy_key, InboxIdCompatibility, MemberIdentifier,
     },
     InboxId, InboxIdRef,
 };
@@ -62,6 +62,8 @@ pub enum IdentityStrategy {
         address: String,
         nonce: u64,
         legacy_signed_private_key: Option<Vec<u8>>,
+        /// Which forms of the inbox id are accepted for `address` and `nonce`
+        inbox_id_compatibility: InboxIdCompatibility,
     },
     /// Identity that is already in the disk store
     CachedOnly,
@@ -93,8 +95,22 @@ impl IdentityStrategy {
             address,
             nonce,
             legacy_signed_private_key,
+            inbox_id_compatibility: InboxIdCompatibility::default(),
         }
     }
+
+    /// Accept the inbox id forms in `compatibility`, e.g. both the old and new forms while
+    /// inbox id derivation is being migrated. Has no effect on [`IdentityStrategy::CachedOnly`].
+    pub fn inbox_id_compatibility(mut self, compatibility: InboxIdCompatibility) -> Self {
+        if let Self::CreateIfNotFound {
+            ref mut inbox_id_compatibility,
+            ..
+        } = self
+        {
+            *inbox_id_compatibility = compatibility;
+        }
+        self
+    }
 }
 
 impl IdentityStrategy {
@@ -130,6 +146,7 @@ impl IdentityStrategy {
                 address,
                 nonce,
                 legacy_signed_private_key,
+                inbox_id_compatibility,
             } => {
                 if let Some(stored_identity) = stored_identity {
                     tracing::debug!(
@@ -138,7 +155,13 @@ impl IdentityStrategy {
                         inbox_id = stored_identity.inbox_id,
                         "Found existing identity in store"
                     );
-                    if inbox_id != stored_identity.inbox_id {
+                    // the stored identity may use another form of the same inbox id
+                    if !inbox_id_compatibility.same_inbox(
+                        &inbox_id,
+                        &stored_identity.inbox_id,
+                        &address.to_lowercase(),
+                        &nonce,
+                    )? {
                         return Err(IdentityError::InboxIdMismatch {
                             id: inbox_id.clone(),
                             stored: stored_identity.inbox_id,
@@ -152,6 +175,7 @@ impl IdentityStrategy {
                         address,
                         nonce,
                         legacy_signed_private_key,
+                        &inbox_id_compatibility,
                         api_client,
                         provider,
                         scw_signature_verifier,
@@ -271,6 +295,7 @@ impl Identity {
         address: String,
         nonce: u64,
         legacy_signed_private_key: Option<Vec<u8>>,
+        inbox_id_compatibility: &InboxIdCompatibility,
         api_client: &ApiClientWrapper<ApiClient>,
         provider: &XmtpOpenMlsProvider,
         scw_signature_verifier: impl SmartContractSignatureVerifier,
@@ -285,7 +310,12 @@ impl Identity {
         if let Some(associated_inbox_id) = associated_inbox_id {
             // If an inbox is associated with address, we'd use it to create Identity and ignore the nonce.
             // We would need a signature from user's wallet.
-            if *associated_inbox_id != inbox_id {
+            if !inbox_id_compatibility.same_inbox(
+                associated_inbox_id,
+                &inbox_id,
+                &address,
+                &nonce,
+            )? {
                 return Err(IdentityError::NewIdentity("Inbox ID mismatch".to_string()));
             }
             let builder = SignatureRequestBuilder::new(associated_inbox_id.clone());
@@ -325,8 +355,7 @@ impl Identity {
                 ));
             }
             // If the inbox_id found on the network does not match the one generated from the address and nonce, we must error
-            let generated_inbox_id = generate_inbox_id(&address, &nonce)?;
-            if inbox_id != generated_inbox_id {
+            if !inbox_id_compatibility.accepts(&inbox_id, &address, &nonce)? {
                 return Err(IdentityError::NewIdentity(
                     "Inbox ID doesn't match nonce & address".to_string(),
                 ));
@@ -381,8 +410,7 @@ impl Identity {
 
             Ok(identity)
         } else {
-            let generated_inbox_id = generate_inbox_id(&address, &nonce)?;
-            if inbox_id != generated_inbox_id {
+            if !inbox_id_compatibility.accepts(&inbox_id, &address, &nonce)? {
                 return Err(IdentityError::NewIdentity(
                     "Inbox ID doesn't match nonce & address".to_string(),
                 ));
@@ -1,8 +1,11 @@
 use crate::storage::{
     association_state::StoredAssociationState, user_preferences::StoredUserPreferences,
 };
-use futures::future::try_join_all;
-use std::collections::{HashMap, HashSet};
+use futures::{future::try_join_all, Stream};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 use thiserror::Error;
 use xmtp_common::{retry_async, retryable, Retry, RetryableError};
 use xmtp_cryptography::CredentialSign;
@@ -25,8 +28,12 @@ use xmtp_proto::api_client::{ClientWithMetadata, XmtpIdentityClient, XmtpMlsClie
 use crate::{
     api::{ApiClientWrapper, GetIdentityUpdatesV2Filter, InboxUpdate},
     client::ClientError,
-    groups::group_membership::{GroupMembership, MembershipDiff},
+    groups::{
+        device_sync::preference_sync::UserPreferenceUpdate,
+        group_membership::{GroupMembership, MembershipDiff},
+    },
     storage::{db_connection::DbConnection, identity_update::StoredIdentityUpdate},
+    subscriptions::LocalEvents,
     Client, XmtpApi,
 };
 
@@ -352,13 +359,21 @@ where
             )
         }
 
-        // Cycle the HMAC key
-        let conn = self.store().conn()?;
-        StoredUserPreferences::new_hmac_key(&conn, &self.local_events)?;
+        self.rotate_hmac_key().await?;
 
         Ok(builder.build())
     }
 
+    /// Cycle the HMAC key, and sync the new key to the user's other installations
+    pub(crate) async fn rotate_hmac_key(&self) -> Result<Vec<u8>, ClientError> {
+        let key = StoredUserPreferences::new_hmac_key(&self.store().conn()?)?;
+        self.publish_local_event(LocalEvents::OutgoingPreferenceUpdates(vec![
+            UserPreferenceUpdate::HmacKeyUpdate { key: key.clone() },
+        ]))
+        .await;
+        Ok(key)
+    }
+
     /**
      * Apply a signature request to the client's inbox by publishing the identity update to the network.
      *
@@ -478,6 +493,99 @@ where
     }
 }
 
+/// A change to the members of an inbox
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdentityChange {
+    InstallationAdded(Vec<u8>),
+    InstallationRevoked(Vec<u8>),
+    WalletAdded(String),
+    WalletRemoved(String),
+}
+
+/// A change from the identity update with `sequence_id`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityUpdateEvent {
+    pub inbox_id: String,
+    pub sequence_id: i64,
+    pub change: IdentityChange,
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Stream changes to this client's inbox, such as a new installation being added, so apps
+    /// can show security notices. The identity update log is polled every `poll_interval`, and
+    /// only changes made after the stream starts are emitted.
+    pub fn stream_identity_updates(
+        &self,
+        poll_interval: Duration,
+    ) -> impl Stream<Item = Result<IdentityUpdateEvent, ClientError>> + '_ {
+        async_stream::stream! {
+            let mut last_sequence_id = None;
+            loop {
+                match self.identity_updates_since(last_sequence_id).await {
+                    Ok((sequence_id, events)) => {
+                        // the first poll only establishes where the log currently ends
+                        if last_sequence_id.is_some() {
+                            for event in events {
+                                yield Ok(event);
+                            }
+                        }
+                        last_sequence_id = Some(sequence_id);
+                    }
+                    Err(e) => yield Err(e),
+                }
+                xmtp_common::time::sleep(poll_interval).await;
+            }
+        }
+    }
+
+    /// Load this client's new identity updates and turn each one after `since` into events.
+    /// Returns the latest sequence id along with the events.
+    async fn identity_updates_since(
+        &self,
+        since: Option<i64>,
+    ) -> Result<(i64, Vec<IdentityUpdateEvent>), ClientError> {
+        let conn = self.store().conn()?;
+        let inbox_id = self.inbox_id();
+        load_identity_updates(&self.api_client, &conn, &[inbox_id]).await?;
+        let Some(since) = since else {
+            let latest = conn.get_latest_sequence_id(&[inbox_id])?;
+            return Ok((latest.get(inbox_id).copied().unwrap_or_default(), vec![]));
+        };
+
+        let mut events = vec![];
+        let mut previous = since;
+        for update in conn.get_identity_updates(inbox_id, Some(since), None)? {
+            let diff = self
+                .get_association_state_diff(
+                    &conn,
+                    inbox_id,
+                    Some(previous),
+                    Some(update.sequence_id),
+                )
+                .await?;
+            let added = diff.new_members.into_iter().map(|member| match member {
+                MemberIdentifier::Installation(id) => IdentityChange::InstallationAdded(id),
+                MemberIdentifier::Address(address) => IdentityChange::WalletAdded(address),
+            });
+            let removed = diff.removed_members.into_iter().map(|member| match member {
+                MemberIdentifier::Installation(id) => IdentityChange::InstallationRevoked(id),
+                MemberIdentifier::Address(address) => IdentityChange::WalletRemoved(address),
+            });
+            events.extend(added.chain(removed).map(|change| IdentityUpdateEvent {
+                inbox_id: inbox_id.to_string(),
+                sequence_id: update.sequence_id,
+                change,
+            }));
+            previous = update.sequence_id;
+        }
+        Ok((previous, events))
+    }
+}
+
 /// For the given list of `inbox_id`s get all updates from the network that are newer than the last known `sequence_id`,
 /// write them in the db, and return the updates
 #[tracing::instrument(level = "trace", skip_all)]
@@ -601,7 +709,9 @@ pub(crate) mod tests {
     };
     use xmtp_common::rand_vec;
 
-    use super::{is_member_of_association_state, load_identity_updates};
+    use super::{is_member_of_association_state, load_identity_updates, IdentityChange};
+    use futures::StreamExt;
+    use std::time::Duration;
 
     async fn get_association_state<ApiClient, Verifier>(
         client: &Client<ApiClient, Verifier>,
@@ -996,4 +1106,27 @@ pub(crate) mod tests {
         let association_state = get_association_state(&client1, client1.inbox_id()).await;
         assert_eq!(association_state.installation_ids().len(), 1);
     }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    pub async fn stream_identity_updates() {
+        let wallet = generate_local_wallet();
+        let client1: FullXmtpClient = ClientBuilder::new_test_client(&wallet).await;
+        let stream = client1.stream_identity_updates(Duration::from_millis(100));
+        futures::pin_mut!(stream);
+        // nothing changed since the stream started
+        assert!(
+            xmtp_common::time::timeout(Duration::from_millis(500), stream.next())
+                .await
+                .is_err()
+        );
+
+        let client2: FullXmtpClient = ClientBuilder::new_test_client(&wallet).await;
+        let event = stream.next().await.unwrap().unwrap();
+        assert_eq!(event.inbox_id, client1.inbox_id());
+        assert_eq!(
+            event.change,
+            IdentityChange::InstallationAdded(client2.installation_public_key().to_vec())
+        );
+    }
 }
@@ -0,0 +1,198 @@
+//! Key packages fetched ahead of time for the contacts a user is most likely to add to a group,
+//! so that "add to group" doesn't wait on the network.
+//!
+//! Likely add targets are the inboxes that sent this client the most messages recently. A cached
+//! key package is handed out at most once and only while it is fresh, so a group add never uses
+//! a key package its owner has long since rotated.
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::Mutex;
+use xmtp_common::time::{now_ns, Duration, Instant};
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use crate::{
+    client::ClientError, identity_updates::load_identity_updates,
+    verified_key_package_v2::VerifiedKeyPackageV2, CancellationToken, Client, StreamMetrics,
+    XmtpApi,
+};
+
+/// How long a prefetched key package may be used
+pub const KEY_PACKAGE_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+/// How far back messages count towards a contact's activity
+pub const RECENT_CONTACTS_WINDOW: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+struct CachedKeyPackage {
+    key_package: VerifiedKeyPackageV2,
+    fetched_at: Instant,
+}
+
+impl CachedKeyPackage {
+    fn is_fresh(&self) -> bool {
+        self.fetched_at.elapsed() < KEY_PACKAGE_CACHE_TTL
+    }
+}
+
+/// Prefetched key packages, by installation id
+#[derive(Default)]
+pub struct KeyPackageCache {
+    entries: Mutex<HashMap<Vec<u8>, CachedKeyPackage>>,
+}
+
+impl KeyPackageCache {
+    /// Number of fresh key packages in the cache
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .values()
+            .filter(|entry| entry.is_fresh())
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn contains(&self, installation_id: &[u8]) -> bool {
+        self.entries
+            .lock()
+            .get(installation_id)
+            .is_some_and(CachedKeyPackage::is_fresh)
+    }
+
+    fn insert(&self, key_packages: Vec<VerifiedKeyPackageV2>) {
+        let mut entries = self.entries.lock();
+        entries.retain(|_, entry| entry.is_fresh());
+        let fetched_at = Instant::now();
+        for key_package in key_packages {
+            entries.insert(
+                key_package.installation_id(),
+                CachedKeyPackage {
+                    key_package,
+                    fetched_at,
+                },
+            );
+        }
+    }
+
+    /// Remove and return the fresh key packages cached for `installation_ids`
+    pub(crate) fn take(&self, installation_ids: &[Vec<u8>]) -> Vec<VerifiedKeyPackageV2> {
+        let mut entries = self.entries.lock();
+        installation_ids
+            .iter()
+            .filter_map(|installation_id| entries.remove(installation_id))
+            .filter(CachedKeyPackage::is_fresh)
+            .map(|entry| entry.key_package)
+            .collect()
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Fetch and cache key packages for every installation of the `limit` contacts that were
+    /// most active recently. Returns the number of key packages fetched.
+    pub async fn prefetch_key_packages(&self, limit: usize) -> Result<usize, ClientError> {
+        let conn = self.store().conn()?;
+        let since_ns = now_ns().saturating_sub(RECENT_CONTACTS_WINDOW.as_nanos() as i64);
+        let contacts = conn.get_frequent_senders(self.inbox_id(), since_ns, limit)?;
+        if contacts.is_empty() {
+            return Ok(0);
+        }
+
+        let contacts: Vec<&str> = contacts.iter().map(String::as_str).collect();
+        load_identity_updates(&self.api_client, &conn, &contacts).await?;
+        let requests: Vec<_> = contacts.iter().map(|inbox_id| (*inbox_id, None)).collect();
+        let cache = &self.context.key_package_cache;
+        let installation_ids: Vec<Vec<u8>> = self
+            .batch_get_association_state(&conn, &requests)
+            .await?
+            .iter()
+            .flat_map(|state| state.installation_ids())
+            .filter(|installation_id| !cache.contains(installation_id))
+            .collect();
+        if installation_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let key_packages = self.fetch_key_packages(installation_ids).await?;
+        let fetched = key_packages.len();
+        cache.insert(key_packages);
+        tracing::debug!(fetched, "prefetched key packages for recent contacts");
+        Ok(fetched)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Run [`Client::prefetch_key_packages`] every `interval` until the handle is closed.
+    /// The interval should be shorter than [`KEY_PACKAGE_CACHE_TTL`] to keep the cache warm.
+    pub fn start_key_package_prefetcher(
+        client: Arc<Client<ApiClient, V>>,
+        interval: Duration,
+        limit: usize,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
+        let cancel = CancellationToken::new();
+        let stopped = cancel.clone();
+
+        crate::spawn_cancellable(None, StreamMetrics::default(), cancel, async move {
+            while !stopped.is_cancelled() {
+                if let Err(e) = client.prefetch_key_packages(limit).await {
+                    tracing::warn!("key package prefetch failed: {e}");
+                }
+                let _ = xmtp_common::time::timeout(interval, stopped.cancelled()).await;
+            }
+            Ok::<_, ClientError>(())
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_prefetched_key_packages_are_used_once() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let caro = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        // bo talks to alix, which makes bo a likely add target
+        let dm = bo
+            .create_dm_by_inbox_id(alix.inbox_id().to_string())
+            .await
+            .unwrap();
+        dm.send_message(b"hi").await.unwrap();
+        alix.sync_welcomes(&alix.mls_provider().unwrap())
+            .await
+            .unwrap();
+        alix.find_groups(Default::default()).unwrap()[0]
+            .sync()
+            .await
+            .unwrap();
+
+        assert_eq!(alix.prefetch_key_packages(10).await.unwrap(), 1);
+        assert_eq!(alix.context.key_package_cache.len(), 1);
+        // already cached
+        assert_eq!(alix.prefetch_key_packages(10).await.unwrap(), 0);
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id(), caro.inbox_id()])
+            .await
+            .unwrap();
+        assert!(alix.context.key_package_cache.is_empty());
+        assert_eq!(group.members().await.unwrap().len(), 3);
+    }
+}
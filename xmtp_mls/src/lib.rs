@@ -2,21 +2,40 @@
 #![warn(clippy::unwrap_used)]
 
 pub mod api;
+pub mod backup;
+pub mod block_list;
 pub mod builder;
 pub mod client;
+pub mod codec_registry;
+pub mod commit_scheduling;
 pub mod configuration;
+pub mod connectivity;
+pub mod consent_shard;
+pub mod decoded_message;
+pub mod deferred_startup;
+pub mod diagnostics;
+pub mod export_envelope;
+pub mod failover;
 pub mod groups;
+pub mod hlc;
 mod hpke;
 pub mod identity;
 pub mod identity_updates;
 mod intents;
+pub mod key_package_cache;
+pub mod local_events;
+pub mod multiplexer;
 mod mutex_registry;
+pub mod outbox;
+pub mod profiles;
+pub mod rate_limiting;
 pub mod storage;
 mod stream_handles;
 pub mod subscriptions;
 pub mod types;
 pub mod utils;
 pub mod verified_key_package_v2;
+pub mod wipe;
 
 pub use client::{Client, Network};
 use std::collections::HashMap;
@@ -152,8 +171,11 @@ pub trait Delete<Model> {
 
 use crate::groups::GroupError;
 pub use stream_handles::{
-    spawn, AbortHandle, GenericStreamHandle, StreamHandle, StreamHandleError,
+    spawn, spawn_cancellable, spawn_pausable, spawn_with_metrics, until_closed, AbortHandle,
+    GenericStreamHandle, PauseToken, StreamControls, StreamHandle, StreamHandleError,
+    StreamMetrics,
 };
+pub use tokio_util::sync::CancellationToken;
 
 #[cfg(test)]
 pub(crate) mod tests {
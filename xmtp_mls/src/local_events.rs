@@ -0,0 +1,197 @@
+//! Public access to the client's local event queue, for bindings that route events
+//! themselves instead of using the purpose-built streams.
+use std::collections::HashSet;
+
+use futures::{Stream, StreamExt};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use crate::{subscriptions::LocalEvents, Client, XmtpApi};
+
+/// The kind of a [`LocalEvents`] event. Events the client only uses internally, like the
+/// steps of device sync, have no kind and are never yielded by a [`LocalEventReceiver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LocalEventKind {
+    NewGroup,
+    IncomingPreferenceUpdate,
+    ReconsentRequested,
+    GuestsExpiring,
+    ConversationRemoved,
+    DeliveryStatusUpdate,
+    EpochChanged,
+    GroupForked,
+    GroupRecovered,
+    MessageEdited,
+    MessageDeleted,
+    MessagePinned,
+    PollUpdated,
+    UnreadCountChanged,
+    JoinRequest,
+    StaleInstallationsDetected,
+    LocalDataWiped,
+    ConnectivityChanged,
+    SyncProgress,
+    RateLimited,
+}
+
+impl<C> LocalEvents<C> {
+    /// The kind of this event, `None` for events the client only uses internally
+    pub fn kind(&self) -> Option<LocalEventKind> {
+        Some(match self {
+            Self::NewGroup(_) => LocalEventKind::NewGroup,
+            Self::SyncMessage(_) | Self::OutgoingPreferenceUpdates(_) => return None,
+            Self::IncomingPreferenceUpdate(_) => LocalEventKind::IncomingPreferenceUpdate,
+            Self::ReconsentRequested(_) => LocalEventKind::ReconsentRequested,
+            Self::GuestsExpiring(_) => LocalEventKind::GuestsExpiring,
+            Self::ConversationRemoved(..) => LocalEventKind::ConversationRemoved,
+            Self::DeliveryStatusUpdate(_) => LocalEventKind::DeliveryStatusUpdate,
+            Self::EpochChanged(_) => LocalEventKind::EpochChanged,
+            Self::GroupForked(_) => LocalEventKind::GroupForked,
+            Self::GroupRecovered(_) => LocalEventKind::GroupRecovered,
+            Self::MessageEdited(_) => LocalEventKind::MessageEdited,
+            Self::MessageDeleted(_) => LocalEventKind::MessageDeleted,
+            Self::MessagePinned(_) => LocalEventKind::MessagePinned,
+            Self::PollUpdated(_) => LocalEventKind::PollUpdated,
+            Self::UnreadCountChanged(_) => LocalEventKind::UnreadCountChanged,
+            Self::JoinRequest(_) => LocalEventKind::JoinRequest,
+            Self::StaleInstallationsDetected(_) => LocalEventKind::StaleInstallationsDetected,
+            Self::LocalDataWiped(_) => LocalEventKind::LocalDataWiped,
+            Self::ConnectivityChanged(_) => LocalEventKind::ConnectivityChanged,
+            Self::SyncProgress(_) => LocalEventKind::SyncProgress,
+            Self::RateLimited(_) => LocalEventKind::RateLimited,
+        })
+    }
+}
+
+/// Which events a [`LocalEventReceiver`] yields. The default yields every event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocalEventFilter {
+    kinds: Option<HashSet<LocalEventKind>>,
+}
+
+impl LocalEventFilter {
+    /// Also yield events of `kind`. Once any kind is added, only the added kinds are yielded.
+    pub fn kind(mut self, kind: LocalEventKind) -> Self {
+        self.kinds.get_or_insert_with(HashSet::new).insert(kind);
+        self
+    }
+
+    /// Also yield events of each of `kinds`
+    pub fn kinds(self, kinds: impl IntoIterator<Item = LocalEventKind>) -> Self {
+        kinds.into_iter().fold(self, Self::kind)
+    }
+
+    pub fn matches<C>(&self, event: &LocalEvents<C>) -> bool {
+        match (&self.kinds, event.kind()) {
+            (_, None) => false,
+            (Some(kinds), Some(kind)) => kinds.contains(&kind),
+            (None, Some(_)) => true,
+        }
+    }
+}
+
+/// An item received from a [`LocalEventReceiver`]
+#[derive(Debug, Clone)]
+pub enum ReceivedLocalEvent<C> {
+    Event(LocalEvents<C>),
+    /// The receiver fell behind the queue and this many events were skipped. Receiving carries
+    /// on from the oldest event still queued.
+    Lagged(u64),
+}
+
+/// A subscription to the client's local events, yielding those that match its filter
+pub struct LocalEventReceiver<C> {
+    receiver: broadcast::Receiver<LocalEvents<C>>,
+    filter: LocalEventFilter,
+}
+
+impl<C> LocalEventReceiver<C>
+where
+    C: Clone + Send + Sync + 'static,
+{
+    /// Wait for the next matching event, or for [`ReceivedLocalEvent::Lagged`] if the receiver
+    /// fell behind. Returns `None` once the client is dropped.
+    pub async fn recv(&mut self) -> Option<ReceivedLocalEvent<C>> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if self.filter.matches(&event) => {
+                    return Some(ReceivedLocalEvent::Event(event))
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => return Some(ReceivedLocalEvent::Lagged(missed)),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    pub fn into_stream(self) -> impl Stream<Item = ReceivedLocalEvent<C>> {
+        let filter = self.filter;
+        BroadcastStream::new(self.receiver).filter_map(move |event| {
+            let event = match event {
+                Ok(event) if filter.matches(&event) => Some(ReceivedLocalEvent::Event(event)),
+                Ok(_) => None,
+                Err(BroadcastStreamRecvError::Lagged(missed)) => {
+                    Some(ReceivedLocalEvent::Lagged(missed))
+                }
+            };
+            futures::future::ready(event)
+        })
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Subscribe to the local events matching `filter`, from now on
+    pub fn subscribe_local_events(&self, filter: LocalEventFilter) -> LocalEventReceiver<Self> {
+        LocalEventReceiver {
+            receiver: self.local_events.subscribe(),
+            filter,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions,
+        storage::group_message::DeliveryStatus, subscriptions::SyncMessage,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_subscribe_local_events_by_kind() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let mut receiver = alix.subscribe_local_events(
+            LocalEventFilter::default().kind(LocalEventKind::DeliveryStatusUpdate),
+        );
+
+        // the new group event is filtered out
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group.send_message(b"hello").await.unwrap();
+
+        let event = receiver.recv().await.unwrap();
+        let ReceivedLocalEvent::Event(LocalEvents::DeliveryStatusUpdate(update)) = event else {
+            panic!("expected a delivery status update");
+        };
+        assert_eq!(update.group_id, group.group_id);
+        assert_eq!(update.status, DeliveryStatus::Unpublished);
+
+        // device sync's own events are never handed out
+        let sync_message = LocalEvents::<()>::SyncMessage(SyncMessage::Request {
+            message_id: vec![1],
+        });
+        assert!(!LocalEventFilter::default().matches(&sync_message));
+    }
+}
@@ -0,0 +1,324 @@
+//! Shares a single `subscribe_group_messages` connection between every multiplexed group
+//! stream of a client, instead of opening one server subscription per stream.
+//!
+//! Envelopes are fanned out to a broadcast channel per group. Subscribing to a group that
+//! isn't part of the connection yet reconnects with the new set of groups, resuming every
+//! other group from the last envelope it received so nothing is missed across the switch.
+//! A connection that fails is reopened the same way, so the streams following it carry on.
+use std::{collections::HashMap, sync::Arc};
+
+use futures::{Stream, StreamExt};
+use parking_lot::Mutex;
+use tokio::sync::{broadcast, oneshot};
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use xmtp_common::time::Duration;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+use xmtp_proto::{
+    api_client::XmtpMlsStreams,
+    xmtp::mls::api::v1::{group_message::Version as GroupMessageVersion, GroupMessage},
+};
+
+use crate::{
+    api::{ApiClientWrapper, GroupFilter},
+    client::ClientError,
+    groups::MlsGroup,
+    storage::group_message::StoredGroupMessage,
+    subscriptions::SubscribeError,
+    AbortHandle, Client, StreamHandle, XmtpApi,
+};
+
+/// Envelopes buffered per group for the slowest stream of that group
+const GROUP_CHANNEL_CAPACITY: usize = 256;
+
+/// How long to wait before reopening a connection that failed
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+struct GroupChannel {
+    sender: broadcast::Sender<GroupMessage>,
+    /// The id of the last envelope received for the group
+    cursor: u64,
+}
+
+#[derive(Default)]
+struct MultiplexerState {
+    groups: HashMap<Vec<u8>, GroupChannel>,
+    /// Incremented on every reconnect, so a replaced connection stops forwarding
+    generation: u64,
+    /// The task of the current connection, from the moment it's opened
+    connection: Option<Box<dyn AbortHandle>>,
+}
+
+impl MultiplexerState {
+    fn is_connected(&self) -> bool {
+        self.connection
+            .as_ref()
+            .is_some_and(|connection| !connection.is_finished())
+    }
+
+    /// Drop the groups without streams left, and resume the others from their own cursor.
+    /// `None` if no group is streamed anymore.
+    fn resume_filters(&mut self) -> Option<Vec<GroupFilter>> {
+        self.groups
+            .retain(|_, channel| channel.sender.receiver_count() > 0);
+        if self.groups.is_empty() {
+            return None;
+        }
+        Some(
+            self.groups
+                .iter()
+                .map(|(group_id, channel)| GroupFilter::new(group_id.clone(), Some(channel.cursor)))
+                .collect(),
+        )
+    }
+}
+
+/// One network subscription fanned out to per-group channels
+pub struct SubscriptionMultiplexer<ApiClient> {
+    api_client: Arc<ApiClientWrapper<ApiClient>>,
+    state: Arc<Mutex<MultiplexerState>>,
+}
+
+impl<ApiClient> SubscriptionMultiplexer<ApiClient> {
+    pub(crate) fn new(api_client: Arc<ApiClientWrapper<ApiClient>>) -> Self {
+        Self {
+            api_client,
+            state: Arc::default(),
+        }
+    }
+
+    /// Number of groups included in the shared connection
+    pub fn group_count(&self) -> usize {
+        self.state.lock().groups.len()
+    }
+
+    /// Whether the shared connection is currently open
+    pub fn is_connected(&self) -> bool {
+        self.state.lock().is_connected()
+    }
+
+    /// Forward `envelope` to its group's channel.
+    /// Returns false if the connection was replaced and should stop.
+    fn forward(state: &Mutex<MultiplexerState>, generation: u64, envelope: GroupMessage) -> bool {
+        let mut state = state.lock();
+        if state.generation != generation {
+            return false;
+        }
+        let Some(GroupMessageVersion::V1(v1)) = &envelope.version else {
+            return true;
+        };
+        let (id, group_id) = (v1.id, v1.group_id.clone());
+        if let Some(channel) = state.groups.get_mut(&group_id) {
+            channel.cursor = channel.cursor.max(id);
+            // a group without streams left is dropped on the next reconnect
+            let _ = channel.sender.send(envelope);
+        }
+        true
+    }
+
+    /// The filters to reopen the connection of `generation` with, unless it was replaced or
+    /// nothing is streamed through it anymore
+    fn reconnect_filters(
+        state: &Mutex<MultiplexerState>,
+        generation: u64,
+    ) -> Option<Vec<GroupFilter>> {
+        let mut state = state.lock();
+        if state.generation != generation {
+            return None;
+        }
+        state.resume_filters()
+    }
+}
+
+impl<ApiClient> SubscriptionMultiplexer<ApiClient>
+where
+    ApiClient: XmtpApi + XmtpMlsStreams + Send + Sync + 'static,
+{
+    /// Receive envelopes for `group_id` newer than `cursor` through the shared connection
+    pub async fn subscribe(
+        &self,
+        group_id: Vec<u8>,
+        cursor: u64,
+    ) -> Result<broadcast::Receiver<GroupMessage>, ClientError> {
+        let (receiver, ready) = {
+            let mut state = self.state.lock();
+            // a connection that's still opening already includes its groups
+            if state.is_connected() {
+                if let Some(channel) = state.groups.get(&group_id) {
+                    return Ok(channel.sender.subscribe());
+                }
+            }
+
+            let (sender, receiver) = broadcast::channel(GROUP_CHANNEL_CAPACITY);
+            state
+                .groups
+                .insert(group_id, GroupChannel { sender, cursor });
+            let filters = state.resume_filters().unwrap_or_default();
+
+            if let Some(connection) = state.connection.take() {
+                connection.end();
+            }
+            state.generation += 1;
+            tracing::debug!(
+                groups = filters.len(),
+                "reconnecting multiplexed group message subscription"
+            );
+            let (handle, ready) = self.connect(state.generation, filters);
+            state.connection = Some(handle);
+            (receiver, ready)
+        };
+
+        // a closed channel means the connection was replaced before it opened, by one that
+        // includes this group as well
+        if let Ok(result) = ready.await {
+            result?;
+        }
+        Ok(receiver)
+    }
+
+    /// Open the connection of `generation`, reopening it whenever it fails for as long as it's
+    /// current and groups are streamed through it. The receiver resolves once it first opened.
+    fn connect(
+        &self,
+        generation: u64,
+        mut filters: Vec<GroupFilter>,
+    ) -> (
+        Box<dyn AbortHandle>,
+        oneshot::Receiver<Result<(), xmtp_proto::Error>>,
+    ) {
+        let (ready_tx, ready_rx) = oneshot::channel();
+        let api_client = self.api_client.clone();
+        let state = self.state.clone();
+
+        let handle = crate::spawn(None, async move {
+            let mut ready_tx = Some(ready_tx);
+            loop {
+                match api_client.subscribe_group_messages(filters).await {
+                    Ok(subscription) => {
+                        if let Some(ready_tx) = ready_tx.take() {
+                            let _ = ready_tx.send(Ok(()));
+                        }
+                        futures::pin_mut!(subscription);
+                        while let Some(envelope) = subscription.next().await {
+                            match envelope {
+                                Ok(envelope) => {
+                                    if !Self::forward(&state, generation, envelope) {
+                                        return;
+                                    }
+                                }
+                                Err(e) => {
+                                    tracing::warn!(
+                                        "multiplexed group message subscription failed: {e}"
+                                    );
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    // the group that was being added fails to subscribe, the others carry on
+                    Err(e) => match ready_tx.take() {
+                        Some(ready_tx) => {
+                            let _ = ready_tx.send(Err(e));
+                        }
+                        None => tracing::warn!(
+                            "reopening the multiplexed group message subscription failed: {e}"
+                        ),
+                    },
+                }
+                xmtp_common::time::sleep(RECONNECT_INTERVAL).await;
+                match Self::reconnect_filters(&state, generation) {
+                    Some(resumed) => filters = resumed,
+                    None => return,
+                }
+            }
+        });
+
+        (handle.abort_handle(), ready_rx)
+    }
+}
+
+impl<ApiClient, V> MlsGroup<Client<ApiClient, V>>
+where
+    ApiClient: XmtpApi + XmtpMlsStreams + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Like [`MlsGroup::stream`], but shares the client's single multiplexed connection with
+    /// every other group streamed this way
+    pub async fn stream_multiplexed(
+        &self,
+    ) -> Result<impl Stream<Item = Result<StoredGroupMessage, SubscribeError>> + '_, ClientError>
+    {
+        let receiver = self
+            .client
+            .multiplexer
+            .subscribe(self.group_id.clone(), 0)
+            .await?;
+
+        let stream = BroadcastStream::new(receiver)
+            .then(move |envelope| async move {
+                let envelope = envelope.map_err(|BroadcastStreamRecvError::Lagged(missed)| {
+                    SubscribeError::LaggedEvents(missed)
+                })?;
+                let provider = self.client.mls_provider()?;
+                self.process_stream_entry(&provider, envelope).await
+            })
+            .filter(|e| {
+                futures::future::ready(!matches!(
+                    e,
+                    Err(SubscribeError::GroupMessageNotFound | SubscribeError::HiddenSender)
+                ))
+            });
+        Ok(stream)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_streams_share_one_subscription() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let mut groups = vec![];
+        for _ in 0..2 {
+            let group = alix
+                .create_group(None, GroupMetadataOptions::default())
+                .unwrap();
+            group
+                .add_members_by_inbox_id(&[bo.inbox_id()])
+                .await
+                .unwrap();
+            groups.push(group);
+        }
+        let bo_groups = bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        assert_eq!(bo_groups.len(), 2);
+
+        // subscribing concurrently replaces the connection while it's opening
+        let (first, second) = futures::join!(
+            bo_groups[0].stream_multiplexed(),
+            bo_groups[1].stream_multiplexed()
+        );
+        let (first, second) = (first.unwrap(), second.unwrap());
+        futures::pin_mut!(first, second);
+        assert_eq!(bo.multiplexer.group_count(), 2);
+        assert!(bo.multiplexer.is_connected());
+
+        for group in &groups {
+            group.send_message(group.group_id.as_slice()).await.unwrap();
+        }
+
+        let message = first.next().await.unwrap().unwrap();
+        assert_eq!(message.group_id, bo_groups[0].group_id);
+        assert_eq!(message.decrypted_message_bytes, bo_groups[0].group_id);
+        let message = second.next().await.unwrap().unwrap();
+        assert_eq!(message.group_id, bo_groups[1].group_id);
+        assert_eq!(message.decrypted_message_bytes, bo_groups[1].group_id);
+    }
+}
@@ -0,0 +1,177 @@
+//! Optimistic sending through a persistent outbox.
+//!
+//! [`MlsGroup::send_message_optimistic`](crate::groups::MlsGroup::send_message_optimistic)
+//! stores the message as unpublished along with its send intent and returns right away. The
+//! outbox publisher started by [`Client::start_outbox_publisher`] pushes unpublished messages in
+//! the background, backing off while the network is unavailable. Both the messages and their
+//! intents are in the database, so messages queued before the app was killed are published once
+//! the publisher runs again. Delivery status changes are emitted as usual, as
+//! [`LocalEvents::DeliveryStatusUpdate`](crate::subscriptions::LocalEvents::DeliveryStatusUpdate).
+use tokio::sync::Notify;
+use xmtp_common::time::Duration;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use crate::{client::ClientError, CancellationToken, Client, StreamHandle, StreamMetrics, XmtpApi};
+
+/// How the outbox publisher retries while messages can't be published
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutboxRetryPolicy {
+    /// How long to wait after the first failed attempt
+    pub initial_backoff: Duration,
+    /// The wait doubles after each failed attempt, up to this long
+    pub max_backoff: Duration,
+}
+
+impl Default for OutboxRetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Wakes the outbox publisher when a message is queued
+#[derive(Default)]
+pub struct Outbox {
+    queued: Notify,
+}
+
+impl Outbox {
+    pub(crate) fn wake(&self) {
+        self.queued.notify_one();
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Publish the unpublished messages of every group. Returns the number of groups that still
+    /// have unpublished messages, e.g. because the network is unavailable.
+    pub async fn publish_outbox(&self) -> Result<usize, ClientError> {
+        let group_ids = self.store().conn()?.groups_with_unpublished_messages()?;
+        let mut pending = 0;
+        for group_id in group_ids {
+            let group = self.group(group_id)?;
+            if let Err(e) = group.publish_messages().await {
+                group.note_offline_error(&e);
+                tracing::debug!(
+                    group_id = hex::encode(&group.group_id),
+                    "failed to publish queued messages: {e}"
+                );
+                pending += 1;
+            }
+        }
+        Ok(pending)
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi + Send + Sync + 'static,
+    V: SmartContractSignatureVerifier + Send + Sync + 'static,
+{
+    /// Publish queued messages in the background: right away, whenever a message is sent
+    /// optimistically, and with exponential backoff while publishing fails. Runs until the
+    /// returned handle is ended.
+    pub fn start_outbox_publisher(
+        &self,
+        policy: OutboxRetryPolicy,
+    ) -> impl StreamHandle<StreamOutput = Result<(), ClientError>> {
+        let client = self.clone();
+        let cancel = CancellationToken::new();
+        let stopped = cancel.clone();
+
+        crate::spawn_cancellable(None, StreamMetrics::default(), cancel, async move {
+            let mut backoff = policy.initial_backoff;
+            while !stopped.is_cancelled() {
+                let wait = match client.publish_outbox().await {
+                    Ok(0) => {
+                        backoff = policy.initial_backoff;
+                        policy.max_backoff
+                    }
+                    Ok(_) => backoff,
+                    Err(e) => {
+                        tracing::warn!("failed to check the outbox: {e}");
+                        backoff
+                    }
+                };
+                let woken = async {
+                    tokio::select! {
+                        _ = client.context.outbox.queued.notified() => {}
+                        _ = stopped.cancelled() => {}
+                    }
+                };
+                // a queued message is published right away, retries still back off
+                if xmtp_common::time::timeout(wait, woken).await.is_err() && wait == backoff {
+                    backoff = std::cmp::min(backoff * 2, policy.max_backoff);
+                }
+            }
+            Ok::<_, ClientError>(())
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions,
+        storage::group_message::DeliveryStatus,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_outbox_publishes_queued_messages() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+
+        // queued while the publisher wasn't running, e.g. before a restart
+        let queued = group.send_message_optimistic(b"queued").unwrap();
+        let conn = alix.store().conn().unwrap();
+        assert_eq!(
+            conn.groups_with_unpublished_messages().unwrap(),
+            vec![group.group_id.clone()]
+        );
+
+        let mut publisher = alix.start_outbox_publisher(OutboxRetryPolicy::default());
+        let sent = group.send_message_optimistic(b"sent").unwrap();
+        xmtp_common::time::timeout(Duration::from_secs(10), async {
+            while !conn.groups_with_unpublished_messages().unwrap().is_empty() {
+                xmtp_common::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap();
+        for message_id in [queued, sent] {
+            let message = conn.get_group_message(&message_id).unwrap().unwrap();
+            assert_eq!(message.delivery_status, DeliveryStatus::Published);
+        }
+        let _ = publisher.end_and_wait().await;
+
+        bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        let bo_group = bo.group(group.group_id.clone()).unwrap();
+        bo_group.sync().await.unwrap();
+        let received: Vec<_> = bo_group
+            .find_messages(&Default::default())
+            .unwrap()
+            .into_iter()
+            .map(|message| message.decrypted_message_bytes)
+            .collect();
+        assert!(received.contains(&b"queued".to_vec()));
+        assert!(received.contains(&b"sent".to_vec()));
+    }
+}
@@ -0,0 +1,288 @@
+//! Profiles: display name, avatar and ENS name claims an inbox makes about itself.
+//!
+//! A profile is signed by one of the inbox's installations and shared with the conversations
+//! the user has allowed. Receivers check the signature and that it was sent by the installation
+//! that signed it before caching it, so a member can't publish a profile for someone else.
+//! Profiles are ordered by when they were signed, but no later than when the server received
+//! them, so a profile dated in the future doesn't shadow the ones published after it.
+//!
+//! The claims themselves are not checked when caching: an ENS name is what the inbox says it
+//! is until [`Client::verified_ens_name`] resolves it, with an [`EnsResolver`] the app
+//! supplies, to one of the inbox's addresses.
+use thiserror::Error;
+use xmtp_common::time::now_ns;
+use xmtp_content_types::{
+    encoded_content_to_bytes,
+    profile::{ProfileClaims, ProfileCodec, SignedProfile},
+    CodecError, ContentCodec,
+};
+use xmtp_id::{
+    associations::{verify_signed_with_public_context, SignatureError},
+    scw_verifier::SmartContractSignatureVerifier,
+};
+
+use crate::{
+    client::ClientError,
+    groups::{scoped_client::ScopedGroupClient, MlsGroup},
+    storage::{
+        consent_record::ConsentState, db_connection::DbConnection, group::GroupQueryArgs,
+        group_message::StoredGroupMessage, inbox_profile::StoredInboxProfile, StorageError,
+    },
+    Client, XmtpApi,
+};
+
+#[derive(Debug, Error)]
+pub enum ProfileError {
+    #[error("profile for inbox {0} was not sent by the installation that signed it")]
+    SenderMismatch(String),
+    #[error("profile signature or signing key is malformed")]
+    Malformed,
+    #[error(transparent)]
+    Signature(#[from] SignatureError),
+    #[error(transparent)]
+    Codec(#[from] CodecError),
+    #[error(transparent)]
+    Decode(#[from] prost::DecodeError),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+    #[error("resolving the ENS name failed: {0}")]
+    EnsResolution(String),
+    #[error(transparent)]
+    Client(#[from] ClientError),
+}
+
+/// Resolves ENS names, e.g. through an Ethereum RPC endpoint the app trusts
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+pub trait EnsResolver {
+    /// The address `name` resolves to, if it resolves at all
+    async fn resolve(&self, name: &str) -> Result<Option<String>, ProfileError>;
+}
+
+/// Check that `profile` was signed by the installation it names
+pub fn verify_profile(profile: &SignedProfile) -> Result<(), ProfileError> {
+    let signature: [u8; 64] = profile
+        .signature
+        .as_slice()
+        .try_into()
+        .map_err(|_| ProfileError::Malformed)?;
+    let public_key: [u8; 32] = profile
+        .installation_id
+        .as_slice()
+        .try_into()
+        .map_err(|_| ProfileError::Malformed)?;
+    let text = SignedProfile::signature_text(
+        &profile.inbox_id,
+        &profile.installation_id,
+        &profile.claims,
+        profile.signed_at_ns,
+    );
+    verify_signed_with_public_context(text, &signature, &public_key)?;
+    Ok(())
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Sign `claims` as this inbox's profile and share it with every allowed conversation.
+    /// Conversations the profile couldn't be sent to are skipped; it is sent to them the next
+    /// time a profile is published.
+    pub async fn publish_profile(
+        &self,
+        claims: ProfileClaims,
+    ) -> Result<SignedProfile, ClientError> {
+        let inbox_id = self.inbox_id().to_string();
+        let installation_id: Vec<u8> = self.installation_public_key().into();
+        let signed_at_ns = now_ns();
+        let text =
+            SignedProfile::signature_text(&inbox_id, &installation_id, &claims, signed_at_ns);
+        let profile = SignedProfile {
+            signature: self.identity().sign_with_public_context(text)?,
+            inbox_id,
+            installation_id,
+            claims,
+            signed_at_ns,
+        };
+        // Our own messages are never processed as incoming, so cache our profile directly
+        self.store()
+            .conn()?
+            .upsert_inbox_profile(&StoredInboxProfile::new(profile.clone(), signed_at_ns))?;
+
+        let encoded = encoded_content_to_bytes(
+            ProfileCodec::encode(profile.clone()).map_err(crate::groups::GroupError::from)?,
+        );
+        let groups = self
+            .find_groups(GroupQueryArgs::default().consent_states(vec![ConsentState::Allowed]))?;
+        for group in groups {
+            if let Err(e) = group.send_message(&encoded).await {
+                tracing::warn!(
+                    group_id = hex::encode(&group.group_id),
+                    "failed to share profile: {e}"
+                );
+            }
+        }
+
+        Ok(profile)
+    }
+
+    /// The most recent verified profile of `inbox_id` received in any conversation, or this
+    /// inbox's own profile
+    pub fn profile(&self, inbox_id: &str) -> Result<Option<SignedProfile>, ClientError> {
+        Ok(self
+            .store()
+            .conn()?
+            .get_inbox_profile(inbox_id)?
+            .map(Into::into))
+    }
+
+    /// The ENS name in the profile of `inbox_id`, if `resolver` resolves it to one of the
+    /// inbox's addresses. `None` if the profile has no ENS name or it isn't the inbox's.
+    pub async fn verified_ens_name<R>(
+        &self,
+        inbox_id: &str,
+        resolver: &R,
+    ) -> Result<Option<String>, ProfileError>
+    where
+        R: EnsResolver + ?Sized,
+    {
+        let Some(ens_name) = self.profile(inbox_id)?.and_then(|p| p.claims.ens_name) else {
+            return Ok(None);
+        };
+        let Some(address) = resolver.resolve(&ens_name).await? else {
+            return Ok(None);
+        };
+        let conn = self.store().conn()?;
+        let state = self.get_latest_association_state(&conn, inbox_id).await?;
+        let owned = state
+            .account_addresses()
+            .iter()
+            .any(|owned| owned.eq_ignore_ascii_case(&address));
+        Ok(owned.then_some(ens_name))
+    }
+}
+
+impl<ScopedClient: ScopedGroupClient> MlsGroup<ScopedClient> {
+    /// Cache the profile in `message` if it verifies. An invalid profile is logged and
+    /// ignored, it must not fail message processing.
+    pub(crate) fn process_profile(&self, conn: &DbConnection, message: &StoredGroupMessage) {
+        if let Err(e) = record_profile(conn, message) {
+            tracing::warn!(
+                group_id = hex::encode(&self.group_id),
+                sender_inbox_id = message.sender_inbox_id.as_str(),
+                "ignoring profile: {e}"
+            );
+        }
+    }
+}
+
+fn record_profile(conn: &DbConnection, message: &StoredGroupMessage) -> Result<(), ProfileError> {
+    let profile = ProfileCodec::decode(message.encoded_content()?)?;
+    if profile.inbox_id != message.sender_inbox_id
+        || profile.installation_id != message.sender_installation_id
+    {
+        return Err(ProfileError::SenderMismatch(profile.inbox_id));
+    }
+    verify_profile(&profile)?;
+    conn.upsert_inbox_profile(&StoredInboxProfile::new(profile, message.sent_at_ns))?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{builder::ClientBuilder, groups::GroupMetadataOptions};
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+    use xmtp_id::InboxOwner;
+
+    /// Resolves every name to the same address
+    struct StaticResolver(String);
+
+    #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+    #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+    impl EnsResolver for StaticResolver {
+        async fn resolve(&self, _name: &str) -> Result<Option<String>, ProfileError> {
+            Ok(Some(self.0.clone()))
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_profiles_are_verified() {
+        let alix_wallet = generate_local_wallet();
+        let alix = ClientBuilder::new_test_client(&alix_wallet).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+        bo_group
+            .update_consent_state(ConsentState::Allowed)
+            .unwrap();
+
+        let claims = ProfileClaims {
+            display_name: Some("Alix".to_string()),
+            avatar_url: None,
+            ens_name: Some("alix.eth".to_string()),
+        };
+        let published = alix.publish_profile(claims).await.unwrap();
+        assert!(verify_profile(&published).is_ok());
+        assert_eq!(
+            alix.profile(alix.inbox_id()).unwrap(),
+            Some(published.clone())
+        );
+
+        bo_group.sync().await.unwrap();
+        assert_eq!(
+            bo.profile(alix.inbox_id()).unwrap(),
+            Some(published.clone())
+        );
+
+        // the ENS name is only verified if it resolves to one of alix's addresses
+        let resolver = StaticResolver(alix_wallet.get_address().to_uppercase());
+        assert_eq!(
+            bo.verified_ens_name(alix.inbox_id(), &resolver)
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("alix.eth")
+        );
+        let resolver = StaticResolver(generate_local_wallet().get_address());
+        assert!(bo
+            .verified_ens_name(alix.inbox_id(), &resolver)
+            .await
+            .unwrap()
+            .is_none());
+
+        // bo can't pass off a profile as alix's, even by replaying alix's signature
+        let forged = SignedProfile {
+            claims: ProfileClaims {
+                display_name: Some("Not Alix".to_string()),
+                ..Default::default()
+            },
+            signed_at_ns: published.signed_at_ns + 1,
+            ..published.clone()
+        };
+        assert!(verify_profile(&forged).is_err());
+        bo_group
+            .send_message(&encoded_content_to_bytes(
+                ProfileCodec::encode(forged).unwrap(),
+            ))
+            .await
+            .unwrap();
+        alix_group.sync().await.unwrap();
+        assert_eq!(alix.profile(alix.inbox_id()).unwrap(), Some(published));
+    }
+}
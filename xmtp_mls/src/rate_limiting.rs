@@ -0,0 +1,156 @@
+//! Per-group rate limiting of inbound message processing.
+//!
+//! A group that receives thousands of messages at once could otherwise hold up the sync of every
+//! other group. Apps that want to guard against this opt in with
+//! [`Client::set_inbound_rate_limit`]; there's no limit by default. Each group then gets a token
+//! bucket, and processing a message takes a token; once a group runs out, the rest of its
+//! messages are deferred, and a [`LocalEvents::RateLimited`] event is emitted. Deferred messages
+//! aren't lost: the group's cursor stays on the last message processed, so they're fetched
+//! again by the next sync of the group, which the app schedules on receiving the event.
+//!
+//! [`LocalEvents::RateLimited`]: crate::subscriptions::LocalEvents::RateLimited
+use std::collections::HashMap;
+
+use parking_lot::Mutex;
+use xmtp_common::time::Instant;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use crate::{Client, XmtpApi};
+
+/// How fast a single group's messages are processed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InboundRateLimit {
+    /// The most messages processed in a burst
+    pub burst: u32,
+    /// Messages processed per second once the burst is used up
+    pub per_second: f64,
+}
+
+impl Default for InboundRateLimit {
+    fn default() -> Self {
+        Self {
+            burst: 1000,
+            per_second: 100.0,
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+#[derive(Default)]
+pub struct InboundRateLimiter {
+    /// No limit when unset
+    limit: Mutex<Option<InboundRateLimit>>,
+    buckets: Mutex<HashMap<Vec<u8>, Bucket>>,
+}
+
+impl InboundRateLimiter {
+    /// Take a token to process a message of `group_id`. Returns false if the group is out of
+    /// tokens and the message should be deferred.
+    pub(crate) fn try_acquire(&self, group_id: &[u8]) -> bool {
+        let Some(limit) = *self.limit.lock() else {
+            return true;
+        };
+        let now = Instant::now();
+        let burst = f64::from(limit.burst);
+        let mut buckets = self.buckets.lock();
+        let bucket = buckets.entry(group_id.to_vec()).or_insert(Bucket {
+            tokens: burst,
+            refilled_at: now,
+        });
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.per_second).min(burst);
+        bucket.refilled_at = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    fn set_limit(&self, limit: Option<InboundRateLimit>) {
+        *self.limit.lock() = limit;
+        // start over with full buckets under the new limit
+        self.buckets.lock().clear();
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Limit how fast each group's inbound messages are processed, or lift the limit with `None`
+    pub fn set_inbound_rate_limit(&self, limit: Option<InboundRateLimit>) {
+        self.context.inbound_rate_limiter.set_limit(limit)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder,
+        groups::{scoped_client::ScopedGroupClient, GroupMetadataOptions, MlsGroup},
+        storage::group_message::{GroupMessageKind, MsgQueryArgs},
+        subscriptions::StreamMessages,
+    };
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_flooding_group_is_deferred() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_group = bo
+            .sync_welcomes(&bo.mls_provider().unwrap())
+            .await
+            .unwrap()
+            .remove(0);
+        // nothing is limited unless the app opts in
+        assert!((0..2000).all(|_| bo.context.inbound_rate_limiter.try_acquire(&group.group_id)));
+        for i in 0..5 {
+            group.send_message(format!("{i}").as_bytes()).await.unwrap();
+        }
+
+        // no refill, so only the burst gets through
+        bo.set_inbound_rate_limit(Some(InboundRateLimit {
+            burst: 3,
+            per_second: 0.0,
+        }));
+        let rate_limited = bo.local_events().subscribe().stream_rate_limited();
+        futures::pin_mut!(rate_limited);
+        bo_group.sync().await.unwrap();
+        let received = |group: &MlsGroup<_>| {
+            group
+                .find_messages(&MsgQueryArgs {
+                    kind: Some(GroupMessageKind::Application),
+                    ..Default::default()
+                })
+                .unwrap()
+                .len()
+        };
+        assert_eq!(received(&bo_group), 3);
+        assert_eq!(rate_limited.next().await.unwrap().unwrap(), group.group_id);
+
+        // the deferred messages are picked up by the next sync
+        bo.set_inbound_rate_limit(None);
+        bo_group.sync().await.unwrap();
+        assert_eq!(received(&bo_group), 5);
+    }
+}
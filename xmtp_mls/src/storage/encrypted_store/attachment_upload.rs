@@ -0,0 +1,269 @@
+//! Persisted progress for chunked attachment uploads, so an interrupted upload can resume from
+//! the last acknowledged chunk instead of starting over.
+use super::{
+    db_connection::DbConnection,
+    schema::attachment_uploads::{self, dsl},
+    Sqlite,
+};
+use crate::{impl_fetch, impl_store_or_ignore, storage::StorageError};
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql, FromSqlRow},
+    expression::AsExpression,
+    prelude::*,
+    serialize::{self, IsNull, Output, ToSql},
+    sql_types::Integer,
+};
+use xmtp_common::time::now_ns;
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Integer)]
+pub enum AttachmentUploadState {
+    /// No bytes have been acknowledged by the uploader yet
+    Pending = 1,
+    /// Some chunks have been uploaded, the upload can be resumed from `bytes_uploaded`
+    Uploading = 2,
+    /// The upload finished and the attachment is available at `url`
+    Uploaded = 3,
+    /// The message referencing the attachment has been queued for sending
+    Sent = 4,
+}
+
+impl ToSql<Integer, Sqlite> for AttachmentUploadState
+where
+    i32: ToSql<Integer, Sqlite>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(*self as i32);
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Integer, Sqlite> for AttachmentUploadState
+where
+    i32: FromSql<Integer, Sqlite>,
+{
+    fn from_sql(bytes: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            1 => Ok(AttachmentUploadState::Pending),
+            2 => Ok(AttachmentUploadState::Uploading),
+            3 => Ok(AttachmentUploadState::Uploaded),
+            4 => Ok(AttachmentUploadState::Sent),
+            x => Err(format!("Unrecognized variant {}", x).into()),
+        }
+    }
+}
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = attachment_uploads)]
+#[diesel(primary_key(id))]
+pub struct StoredAttachmentUpload {
+    /// Derived from the group id and the digest of the attachment payload
+    pub id: Vec<u8>,
+    /// The group the attachment message will be sent to
+    pub group_id: Vec<u8>,
+    /// sha256 digest of the full attachment payload
+    pub content_digest: Vec<u8>,
+    /// Total size of the payload in bytes
+    pub total_size: i64,
+    /// Size of each chunk handed to the uploader
+    pub chunk_size: i64,
+    /// Number of bytes acknowledged by the uploader
+    pub bytes_uploaded: i64,
+    /// Uploader-specific session identifier used to resume the upload
+    pub upload_session: Option<String>,
+    /// Location of the attachment once the upload has completed
+    pub url: Option<String>,
+    /// Enum, [`AttachmentUploadState`]
+    pub state: AttachmentUploadState,
+    /// The id of the message sent once the upload completed
+    pub message_id: Option<Vec<u8>>,
+    pub created_at_ns: i64,
+    pub updated_at_ns: i64,
+}
+
+impl_fetch!(StoredAttachmentUpload, attachment_uploads, Vec<u8>);
+impl_store_or_ignore!(StoredAttachmentUpload, attachment_uploads);
+
+impl StoredAttachmentUpload {
+    pub fn new(
+        id: Vec<u8>,
+        group_id: Vec<u8>,
+        content_digest: Vec<u8>,
+        total_size: i64,
+        chunk_size: i64,
+    ) -> Self {
+        let now = now_ns();
+        Self {
+            id,
+            group_id,
+            content_digest,
+            total_size,
+            chunk_size,
+            bytes_uploaded: 0,
+            upload_session: None,
+            url: None,
+            state: AttachmentUploadState::Pending,
+            message_id: None,
+            created_at_ns: now,
+            updated_at_ns: now,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.bytes_uploaded >= self.total_size
+    }
+}
+
+impl DbConnection {
+    pub fn find_attachment_upload<Id: AsRef<[u8]>>(
+        &self,
+        id: Id,
+    ) -> Result<Option<StoredAttachmentUpload>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::attachment_uploads
+                .find(id.as_ref())
+                .first(conn)
+                .optional()
+        })?)
+    }
+
+    /// Uploads that have not yet resulted in a sent message
+    pub fn unfinished_attachment_uploads(
+        &self,
+    ) -> Result<Vec<StoredAttachmentUpload>, StorageError> {
+        let query = dsl::attachment_uploads
+            .filter(dsl::state.ne(AttachmentUploadState::Sent))
+            .order(dsl::created_at_ns.asc());
+
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    pub fn set_attachment_upload_session<Id: AsRef<[u8]>>(
+        &self,
+        id: Id,
+        session: &str,
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::update(dsl::attachment_uploads.find(id.as_ref()))
+                .set((
+                    dsl::upload_session.eq(session),
+                    dsl::updated_at_ns.eq(now_ns()),
+                ))
+                .execute(conn)
+        })?;
+
+        Ok(())
+    }
+
+    /// Record that the uploader acknowledged every byte up to `bytes_uploaded`.
+    /// Progress only moves forward, so a stale write can never rewind a resumed upload.
+    pub fn update_attachment_upload_progress<Id: AsRef<[u8]>>(
+        &self,
+        id: Id,
+        bytes_uploaded: i64,
+    ) -> Result<bool, StorageError> {
+        let num_updated = self.raw_query(|conn| {
+            diesel::update(dsl::attachment_uploads.find(id.as_ref()))
+                .filter(dsl::bytes_uploaded.lt(bytes_uploaded))
+                .set((
+                    dsl::bytes_uploaded.eq(bytes_uploaded),
+                    dsl::state.eq(AttachmentUploadState::Uploading),
+                    dsl::updated_at_ns.eq(now_ns()),
+                ))
+                .execute(conn)
+        })?;
+
+        Ok(num_updated == 1)
+    }
+
+    pub fn set_attachment_upload_complete<Id: AsRef<[u8]>>(
+        &self,
+        id: Id,
+        url: &str,
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::update(dsl::attachment_uploads.find(id.as_ref()))
+                .set((
+                    dsl::url.eq(url),
+                    dsl::state.eq(AttachmentUploadState::Uploaded),
+                    dsl::updated_at_ns.eq(now_ns()),
+                ))
+                .execute(conn)
+        })?;
+
+        Ok(())
+    }
+
+    pub fn set_attachment_upload_sent<Id: AsRef<[u8]>>(
+        &self,
+        id: Id,
+        message_id: &[u8],
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::update(dsl::attachment_uploads.find(id.as_ref()))
+                .set((
+                    dsl::message_id.eq(message_id),
+                    dsl::state.eq(AttachmentUploadState::Sent),
+                    dsl::updated_at_ns.eq(now_ns()),
+                ))
+                .execute(conn)
+        })?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store, StoreOrIgnore,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_common::rand_vec;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_tracks_upload_progress() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+
+            let upload =
+                StoredAttachmentUpload::new(rand_vec::<24>(), group.id, rand_vec::<32>(), 100, 40);
+            upload.store_or_ignore(conn).unwrap();
+
+            assert!(conn
+                .update_attachment_upload_progress(&upload.id, 40)
+                .unwrap());
+            assert!(conn
+                .update_attachment_upload_progress(&upload.id, 80)
+                .unwrap());
+            // Progress never moves backwards
+            assert!(!conn
+                .update_attachment_upload_progress(&upload.id, 40)
+                .unwrap());
+
+            let stored = conn.find_attachment_upload(&upload.id).unwrap().unwrap();
+            assert_eq!(stored.bytes_uploaded, 80);
+            assert_eq!(stored.state, AttachmentUploadState::Uploading);
+            assert_eq!(conn.unfinished_attachment_uploads().unwrap().len(), 1);
+
+            conn.set_attachment_upload_complete(&upload.id, "https://example.com/a")
+                .unwrap();
+            conn.set_attachment_upload_sent(&upload.id, &[1, 2, 3])
+                .unwrap();
+
+            let stored = conn.find_attachment_upload(&upload.id).unwrap().unwrap();
+            assert_eq!(stored.url.as_deref(), Some("https://example.com/a"));
+            assert_eq!(stored.message_id, Some(vec![1, 2, 3]));
+            assert!(conn.unfinished_attachment_uploads().unwrap().is_empty());
+        })
+        .await
+    }
+}
@@ -0,0 +1,105 @@
+//! The inboxes the user blocked. The block list is synced to the user's other installations, so
+//! each entry is kept with the [hybrid logical clock](crate::hlc) timestamp of its write and an
+//! update only replaces a later write. Unblocking keeps the entry, unset, for the same reason.
+use super::{
+    db_connection::DbConnection,
+    schema::blocked_inboxes::{self, dsl},
+    Sqlite,
+};
+use crate::{hlc::Hlc, storage::StorageError};
+use diesel::{prelude::*, sql_types::Text};
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[diesel(table_name = blocked_inboxes)]
+#[diesel(primary_key(inbox_id))]
+pub struct StoredBlockedInbox {
+    pub inbox_id: String,
+    /// Unset once the inbox is unblocked
+    pub blocked: bool,
+    /// Hide the inbox's messages in groups the user shares with it
+    pub hide_messages: bool,
+    /// Hybrid logical clock timestamp of the write, see [`Self::hlc`]
+    pub hlc_wall_ns: i64,
+    pub hlc_counter: i32,
+    pub hlc_node: Vec<u8>,
+}
+
+impl StoredBlockedInbox {
+    pub fn new(inbox_id: String, blocked: bool, hide_messages: bool, hlc: Hlc) -> Self {
+        Self {
+            inbox_id,
+            blocked,
+            hide_messages,
+            hlc_wall_ns: hlc.wall_ns,
+            hlc_counter: hlc.counter as i32,
+            hlc_node: hlc.node,
+        }
+    }
+
+    /// When the entry was written, ordering concurrent writes from different installations
+    pub fn hlc(&self) -> Hlc {
+        Hlc {
+            wall_ns: self.hlc_wall_ns,
+            counter: self.hlc_counter as u32,
+            node: self.hlc_node.clone(),
+        }
+    }
+}
+
+impl DbConnection {
+    pub fn is_inbox_blocked(&self, inbox_id: &str) -> Result<bool, StorageError> {
+        let query = dsl::blocked_inboxes.find(inbox_id).select(dsl::blocked);
+        let blocked: Option<bool> = self.raw_query(|conn| query.first(conn).optional())?;
+        Ok(blocked.unwrap_or(false))
+    }
+
+    /// The inboxes that are blocked
+    pub fn blocked_inboxes(&self) -> Result<Vec<StoredBlockedInbox>, StorageError> {
+        let query = dsl::blocked_inboxes.filter(dsl::blocked.eq(true));
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// Every entry of the block list, including unblocked inboxes
+    pub fn all_blocked_inboxes(&self) -> Result<Vec<StoredBlockedInbox>, StorageError> {
+        Ok(self.raw_query(|conn| dsl::blocked_inboxes.load(conn))?)
+    }
+
+    /// Whether `inbox_id` is blocked and its messages are hidden
+    pub fn is_sender_hidden(&self, inbox_id: &str) -> Result<bool, StorageError> {
+        let query = Self::hidden_senders()
+            .filter(dsl::inbox_id.eq(inbox_id))
+            .count();
+        let count: i64 = self.raw_query(|conn| query.get_result(conn))?;
+        Ok(count > 0)
+    }
+
+    /// The blocked inboxes whose messages are hidden, as a subquery for leaving their messages
+    /// out of message queries
+    pub(super) fn hidden_senders<'a>() -> blocked_inboxes::BoxedQuery<'a, Sqlite, Text> {
+        dsl::blocked_inboxes
+            .filter(dsl::blocked.eq(true))
+            .filter(dsl::hide_messages.eq(true))
+            .select(dsl::inbox_id)
+            .into_boxed()
+    }
+
+    /// Store `entry` unless the stored one was written later. Returns whether it was stored.
+    pub fn set_blocked_inbox(&self, entry: &StoredBlockedInbox) -> Result<bool, StorageError> {
+        Ok(self.raw_query(|conn| -> diesel::QueryResult<bool> {
+            let current: Option<StoredBlockedInbox> = dsl::blocked_inboxes
+                .find(&entry.inbox_id)
+                .first(conn)
+                .optional()?;
+            if current.is_some_and(|current| current.hlc() >= entry.hlc()) {
+                return Ok(false);
+            }
+            diesel::replace_into(dsl::blocked_inboxes)
+                .values(entry)
+                .execute(conn)?;
+            Ok(true)
+        })?)
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{impl_store, storage::StorageError};
+use crate::{hlc::Hlc, impl_store, storage::StorageError};
 
 use super::Sqlite;
 use super::{
@@ -16,6 +16,8 @@ use diesel::{
 };
 use serde::{Deserialize, Serialize};
 
+diesel::define_sql_function!(fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
+
 /// StoredConsentRecord holds a serialized ConsentRecord
 #[derive(Insertable, Queryable, Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[diesel(table_name = consent_records)]
@@ -27,6 +29,14 @@ pub struct StoredConsentRecord {
     pub state: ConsentState,
     /// The entity of what was consented (0x00 etc..)
     pub entity: String,
+    /// Hybrid logical clock timestamp of the write that set the record, see [`Self::hlc`].
+    /// Defaults for updates from installations that don't stamp their writes.
+    #[serde(default)]
+    pub hlc_wall_ns: i64,
+    #[serde(default)]
+    pub hlc_counter: i32,
+    #[serde(default)]
+    pub hlc_node: Vec<u8>,
 }
 
 impl StoredConsentRecord {
@@ -35,10 +45,46 @@ impl StoredConsentRecord {
             entity_type,
             state,
             entity,
+            hlc_wall_ns: 0,
+            hlc_counter: 0,
+            hlc_node: vec![],
+        }
+    }
+
+    /// When the record was written, ordering concurrent writes from different installations
+    pub fn hlc(&self) -> Hlc {
+        Hlc {
+            wall_ns: self.hlc_wall_ns,
+            counter: self.hlc_counter as u32,
+            node: self.hlc_node.clone(),
+        }
+    }
+
+    pub fn with_hlc(self, hlc: Hlc) -> Self {
+        Self {
+            hlc_wall_ns: hlc.wall_ns,
+            hlc_counter: hlc.counter as i32,
+            hlc_node: hlc.node,
+            ..self
         }
     }
 }
 
+/// How an incoming consent record was reconciled with the one stored locally
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsentMerge {
+    /// There was no record yet, or the incoming one was written later
+    Applied {
+        record: StoredConsentRecord,
+        replaced: Option<StoredConsentRecord>,
+    },
+    /// The local record was written later than the incoming one and was kept
+    Ignored {
+        record: StoredConsentRecord,
+        kept: StoredConsentRecord,
+    },
+}
+
 impl_store!(StoredConsentRecord, consent_records);
 
 impl DbConnection {
@@ -57,50 +103,122 @@ impl DbConnection {
         })?)
     }
 
-    /// Insert consent_records, and replace existing entries, returns records that are new or changed
+    /// Insert consent_records, and replace existing entries whose state differs, returns records
+    /// that are new or changed. Records whose state is unchanged aren't written, so they keep the
+    /// timestamp of the write that set the state. All of the records are written in one
+    /// transaction, so a bulk update, e.g. allowing every contact the user imported, is applied
+    /// entirely or not at all.
     pub fn insert_or_replace_consent_records(
         &self,
         records: &[StoredConsentRecord],
     ) -> Result<Vec<StoredConsentRecord>, StorageError> {
-        let mut query = consent_records::table
-            .into_boxed()
-            .filter(false.into_sql::<diesel::sql_types::Bool>());
-        let primary_keys: Vec<_> = records
-            .iter()
-            .map(|r| (&r.entity, &r.entity_type))
-            .collect();
-        for (entity, entity_type) in primary_keys {
-            query = query.or_filter(
-                consent_records::entity_type
-                    .eq(entity_type)
-                    .and(consent_records::entity.eq(entity)),
-            );
-        }
-
-        let changed = self.raw_query(|conn| -> diesel::QueryResult<_> {
-            let existing: Vec<StoredConsentRecord> = query.load(conn)?;
-            let changed: Vec<_> = records
-                .iter()
-                .filter(|r| !existing.contains(r))
-                .cloned()
-                .collect();
-
+        Ok(self.raw_query(|conn| {
             conn.transaction::<_, diesel::result::Error, _>(|conn| {
-                for record in records.iter() {
+                let mut changed = Vec::with_capacity(records.len());
+                for record in records {
+                    let existing: Option<ConsentState> = dsl::consent_records
+                        .find((&record.entity_type, &record.entity))
+                        .select(dsl::state)
+                        .first(conn)
+                        .optional()?;
+                    if existing == Some(record.state) {
+                        continue;
+                    }
+                    changed.push(record.clone());
                     diesel::insert_into(dsl::consent_records)
                         .values(record)
                         .on_conflict((dsl::entity_type, dsl::entity))
                         .do_update()
-                        .set(dsl::state.eq(excluded(dsl::state)))
+                        .set((
+                            dsl::state.eq(excluded(dsl::state)),
+                            dsl::hlc_wall_ns.eq(excluded(dsl::hlc_wall_ns)),
+                            dsl::hlc_counter.eq(excluded(dsl::hlc_counter)),
+                            dsl::hlc_node.eq(excluded(dsl::hlc_node)),
+                        ))
                         .execute(conn)?;
                 }
-                Ok(())
-            })?;
+                Ok(changed)
+            })
+        })?)
+    }
 
-            Ok(changed)
+    /// The latest timestamp among the stored consent records
+    pub fn latest_consent_hlc(&self) -> Result<Option<Hlc>, StorageError> {
+        let latest: Option<StoredConsentRecord> = self.raw_query(|conn| {
+            dsl::consent_records
+                .order((dsl::hlc_wall_ns.desc(), dsl::hlc_counter.desc()))
+                .first(conn)
+                .optional()
         })?;
+        Ok(latest.map(|record| record.hlc()))
+    }
 
-        Ok(changed)
+    /// Reconcile consent records received from other installations with the stored ones.
+    /// A record replaces the stored one only if it was written later, so every installation
+    /// ends up with the same records whatever order they arrive in.
+    pub fn merge_consent_records(
+        &self,
+        records: &[StoredConsentRecord],
+    ) -> Result<Vec<ConsentMerge>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            conn.transaction::<_, diesel::result::Error, _>(|conn| {
+                let mut merges = Vec::with_capacity(records.len());
+                for record in records {
+                    let existing: Option<StoredConsentRecord> = dsl::consent_records
+                        .find((&record.entity_type, &record.entity))
+                        .first(conn)
+                        .optional()?;
+                    if let Some(kept) = existing.clone().filter(|e| e.hlc() >= record.hlc()) {
+                        merges.push(ConsentMerge::Ignored {
+                            record: record.clone(),
+                            kept,
+                        });
+                        continue;
+                    }
+                    diesel::replace_into(dsl::consent_records)
+                        .values(record)
+                        .execute(conn)?;
+                    merges.push(ConsentMerge::Applied {
+                        record: record.clone(),
+                        replaced: existing,
+                    });
+                }
+                Ok(merges)
+            })
+        })?)
+    }
+
+    /// The consent given to `inbox_id`, directly or through one of its `addresses`, e.g. before
+    /// a conversation with it existed. A denial of any of them wins over the others.
+    pub fn contact_consent_state(
+        &self,
+        inbox_id: &str,
+        addresses: &[String],
+    ) -> Result<Option<ConsentState>, StorageError> {
+        let addresses: Vec<String> = addresses.iter().map(|a| a.to_lowercase()).collect();
+        let states: Vec<ConsentState> = self.raw_query(|conn| {
+            dsl::consent_records
+                .filter(
+                    dsl::entity_type
+                        .eq(ConsentType::InboxId)
+                        .and(dsl::entity.eq(inbox_id)),
+                )
+                .or_filter(
+                    dsl::entity_type
+                        .eq(ConsentType::Address)
+                        .and(lower(dsl::entity).eq_any(&addresses)),
+                )
+                .select(dsl::state)
+                .load(conn)
+        })?;
+
+        Ok(states
+            .into_iter()
+            .filter(|state| *state != ConsentState::Unknown)
+            .reduce(|acc, state| match acc {
+                ConsentState::Denied => acc,
+                _ => state,
+            }))
     }
 
     pub fn maybe_insert_consent_record_return_existing(
@@ -215,11 +333,7 @@ mod tests {
         state: ConsentState,
         entity: String,
     ) -> StoredConsentRecord {
-        StoredConsentRecord {
-            entity_type,
-            state,
-            entity,
-        }
+        StoredConsentRecord::new(entity_type, state, entity)
     }
 
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
@@ -287,4 +401,100 @@ mod tests {
         })
         .await;
     }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn insert_many_at_once() {
+        with_connection(|conn| {
+            let records: Vec<_> = (0..2_000)
+                .map(|i| {
+                    generate_consent_record(
+                        ConsentType::InboxId,
+                        ConsentState::Allowed,
+                        format!("inbox_{i}"),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                conn.insert_or_replace_consent_records(&records)
+                    .unwrap()
+                    .len(),
+                2_000
+            );
+
+            // only the records whose state differs are reported
+            let mut update = records.clone();
+            update[7].state = ConsentState::Denied;
+            let update: Vec<_> = update
+                .into_iter()
+                .map(|record| {
+                    record.with_hlc(Hlc {
+                        wall_ns: 1,
+                        counter: 0,
+                        node: vec![1],
+                    })
+                })
+                .collect();
+            let changed = conn.insert_or_replace_consent_records(&update).unwrap();
+            assert_eq!(changed, vec![update[7].clone()]);
+            // unchanged records keep their timestamp
+            let unchanged = conn
+                .get_consent_record("inbox_8".to_string(), ConsentType::InboxId)
+                .unwrap()
+                .unwrap();
+            assert_eq!(unchanged.hlc(), records[8].hlc());
+        })
+        .await;
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), tokio::test)]
+    async fn merge_keeps_the_latest_write() {
+        with_connection(|conn| {
+            let record = |state: ConsentState, wall_ns: i64, node: u8| {
+                generate_consent_record(ConsentType::InboxId, state, "inbox_1".to_string())
+                    .with_hlc(Hlc {
+                        wall_ns,
+                        counter: 0,
+                        node: vec![node],
+                    })
+            };
+            let allowed = record(ConsentState::Allowed, 100, 1);
+            let denied = record(ConsentState::Denied, 200, 2);
+
+            // the later write wins whichever arrives first
+            let merges = conn
+                .merge_consent_records(&[denied.clone(), allowed.clone()])
+                .unwrap();
+            assert!(matches!(
+                &merges[0],
+                ConsentMerge::Applied { replaced: None, .. }
+            ));
+            assert_eq!(
+                merges[1],
+                ConsentMerge::Ignored {
+                    record: allowed.clone(),
+                    kept: denied.clone(),
+                }
+            );
+            let stored = conn
+                .get_consent_record("inbox_1".to_string(), ConsentType::InboxId)
+                .unwrap();
+            assert_eq!(stored, Some(denied.clone()));
+
+            // the node breaks ties between writes made at the same time
+            let tied = record(ConsentState::Allowed, 200, 3);
+            let merges = conn.merge_consent_records(&[tied.clone()]).unwrap();
+            assert_eq!(
+                merges,
+                vec![ConsentMerge::Applied {
+                    record: tied.clone(),
+                    replaced: Some(denied),
+                }]
+            );
+            // the clock is seeded with the latest stored write on startup
+            assert_eq!(conn.latest_consent_hlc().unwrap(), Some(tied.hlc()));
+        })
+        .await;
+    }
 }
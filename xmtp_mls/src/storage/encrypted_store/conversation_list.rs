@@ -1,11 +1,16 @@
+use super::notification_settings::muted_filter;
 use super::schema::conversation_list::dsl::conversation_list;
-use crate::storage::consent_record::ConsentState;
+use super::Sqlite;
+use crate::storage::consent_record::{ConsentState, ConsentType};
 use crate::storage::group::{ConversationType, GroupMembershipState, GroupQueryArgs};
 use crate::storage::group_message::{ContentType, DeliveryStatus, GroupMessageKind};
+use crate::storage::schema::conversation_list::BoxedQuery;
 use crate::storage::{DbConnection, StorageError};
 use diesel::dsl::sql;
+use diesel::sql_types::{BigInt, Text};
 use diesel::{
-    BoolExpressionMethods, ExpressionMethods, JoinOnDsl, QueryDsl, Queryable, RunQueryDsl, Table,
+    BoolExpressionMethods, ExpressionMethods, JoinOnDsl, NullableExpressionMethods, QueryDsl,
+    Queryable, RunQueryDsl, Table,
 };
 use serde::{Deserialize, Serialize};
 
@@ -56,6 +61,17 @@ pub struct ConversationListItem {
     pub authority_id: Option<String>,
 }
 
+/// A conversation with what an inbox screen shows about it, loaded by
+/// [`DbConnection::fetch_conversation_overview`]
+#[derive(Debug, Clone)]
+pub struct ConversationOverviewItem {
+    pub conversation: ConversationListItem,
+    /// Messages from other members that haven't been read, see [`super::unread_count`]
+    pub unread_count: i64,
+    /// `None` if no consent was recorded for the conversation
+    pub consent_state: Option<ConsentState>,
+}
+
 impl DbConnection {
     pub fn fetch_conversation_list<A: AsRef<GroupQueryArgs>>(
         &self,
@@ -65,51 +81,12 @@ impl DbConnection {
         use crate::storage::schema::conversation_list::dsl as conversation_list_dsl;
 
         let GroupQueryArgs {
-            allowed_states,
-            created_after_ns,
-            created_before_ns,
-            limit,
             conversation_type,
             consent_states,
             include_sync_groups,
-            include_duplicate_dms,
+            ..
         } = args.as_ref();
-        let mut query = conversation_list
-            .select(conversation_list::all_columns())
-            .filter(conversation_list_dsl::conversation_type.ne(ConversationType::Sync))
-            .into_boxed();
-
-        if !include_duplicate_dms {
-            // Group by dm_id and grab the latest group (conversation stitching)
-            query = query.filter(sql::<diesel::sql_types::Bool>(
-                "id IN (
-                    SELECT id
-                    FROM groups
-                    GROUP BY CASE WHEN dm_id IS NULL THEN id ELSE dm_id END
-                    ORDER BY last_message_ns DESC
-                )",
-            ));
-        }
-
-        if let Some(limit) = limit {
-            query = query.limit(*limit);
-        }
-
-        if let Some(allowed_states) = allowed_states {
-            query = query.filter(conversation_list_dsl::membership_state.eq_any(allowed_states));
-        }
-
-        if let Some(created_after_ns) = created_after_ns {
-            query = query.filter(conversation_list_dsl::created_at_ns.gt(created_after_ns));
-        }
-
-        if let Some(created_before_ns) = created_before_ns {
-            query = query.filter(conversation_list_dsl::created_at_ns.lt(created_before_ns));
-        }
-
-        if let Some(conversation_type) = conversation_type {
-            query = query.filter(conversation_list_dsl::conversation_type.eq(conversation_type));
-        }
+        let query = Self::conversation_list_query(args.as_ref());
 
         let mut conversations = if let Some(consent_states) = consent_states {
             if consent_states
@@ -171,6 +148,129 @@ impl DbConnection {
 
         Ok(conversations)
     }
+
+    /// The conversations matching `args` with their last message, unread count and consent
+    /// state, most recently active first, in a single query. Sync groups are left out.
+    pub fn fetch_conversation_overview<A: AsRef<GroupQueryArgs>>(
+        &self,
+        args: A,
+    ) -> Result<Vec<ConversationOverviewItem>, StorageError> {
+        use crate::storage::schema::consent_records::dsl as consent_dsl;
+
+        let unread_count = sql::<BigInt>(
+            "COALESCE((SELECT unread_count FROM unread_counts
+                WHERE unread_counts.group_id = conversation_list.id), 0)",
+        );
+        let selection = (
+            conversation_list::all_columns(),
+            unread_count,
+            consent_dsl::state.nullable(),
+        );
+        let last_active = sql::<BigInt>(
+            "COALESCE(conversation_list.sent_at_ns, conversation_list.created_at_ns)",
+        );
+
+        // inbox and address entities are hex too, only conversation consent applies
+        let query = Self::conversation_list_query(args.as_ref()).left_join(
+            consent_dsl::consent_records.on(sql::<Text>("lower(hex(conversation_list.id))")
+                .eq(consent_dsl::entity)
+                .and(consent_dsl::entity_type.eq(ConsentType::ConversationId))),
+        );
+        let rows: Vec<(ConversationListItem, i64, Option<ConsentState>)> =
+            match &args.as_ref().consent_states {
+                Some(consent_states) if consent_states.contains(&ConsentState::Unknown) => {
+                    let query = query
+                        .filter(
+                            consent_dsl::state
+                                .is_null()
+                                .or(consent_dsl::state.eq(ConsentState::Unknown))
+                                .or(consent_dsl::state.eq_any(consent_states.clone())),
+                        )
+                        .select(selection)
+                        .order(last_active.desc());
+                    self.raw_query(|conn| query.load(conn))?
+                }
+                Some(consent_states) => {
+                    let query = query
+                        .filter(consent_dsl::state.eq_any(consent_states.clone()))
+                        .select(selection)
+                        .order(last_active.desc());
+                    self.raw_query(|conn| query.load(conn))?
+                }
+                None => {
+                    let query = query.select(selection).order(last_active.desc());
+                    self.raw_query(|conn| query.load(conn))?
+                }
+            };
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(conversation, unread_count, consent_state)| ConversationOverviewItem {
+                    conversation,
+                    unread_count,
+                    consent_state,
+                },
+            )
+            .collect())
+    }
+
+    /// The conversations matching `args`, leaving out sync groups and ignoring its consent states
+    fn conversation_list_query(args: &GroupQueryArgs) -> BoxedQuery<'_, Sqlite> {
+        use crate::storage::schema::conversation_list::dsl as conversation_list_dsl;
+
+        let GroupQueryArgs {
+            allowed_states,
+            created_after_ns,
+            created_before_ns,
+            limit,
+            conversation_type,
+            include_duplicate_dms,
+            muted,
+            ..
+        } = args;
+        let mut query = conversation_list
+            .filter(conversation_list_dsl::conversation_type.ne(ConversationType::Sync))
+            .into_boxed();
+
+        if !include_duplicate_dms {
+            // Group by dm_id and grab the latest group (conversation stitching)
+            query = query.filter(sql::<diesel::sql_types::Bool>(
+                "id IN (
+                    SELECT id
+                    FROM groups
+                    GROUP BY CASE WHEN dm_id IS NULL THEN id ELSE dm_id END
+                    ORDER BY last_message_ns DESC
+                )",
+            ));
+        }
+
+        if let Some(limit) = limit {
+            query = query.limit(*limit);
+        }
+
+        if let Some(allowed_states) = allowed_states {
+            query = query.filter(conversation_list_dsl::membership_state.eq_any(allowed_states));
+        }
+
+        if let Some(created_after_ns) = created_after_ns {
+            query = query.filter(conversation_list_dsl::created_at_ns.gt(created_after_ns));
+        }
+
+        if let Some(created_before_ns) = created_before_ns {
+            query = query.filter(conversation_list_dsl::created_at_ns.lt(created_before_ns));
+        }
+
+        if let Some(conversation_type) = conversation_type {
+            query = query.filter(conversation_list_dsl::conversation_type.eq(conversation_type));
+        }
+
+        if let Some(muted) = muted {
+            query = query.filter(muted_filter(*muted));
+        }
+
+        query
+    }
 }
 
 #[cfg(test)]
@@ -180,7 +280,7 @@ pub(crate) mod tests {
         generate_consent_record, generate_dm, generate_group, generate_group_with_created_at,
     };
     use crate::storage::group::{GroupMembershipState, GroupQueryArgs};
-    use crate::storage::group_message::ContentType;
+    use crate::storage::group_message::{tests::generate_message, ContentType};
     use crate::storage::tests::with_connection;
     use crate::Store;
     use wasm_bindgen_test::wasm_bindgen_test;
@@ -383,4 +483,66 @@ pub(crate) mod tests {
         })
         .await
     }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_conversation_overview() {
+        with_connection(|conn| {
+            let read = generate_group_with_created_at(None, 100);
+            read.store(conn).unwrap();
+            let quiet = generate_group_with_created_at(None, 500);
+            quiet.store(conn).unwrap();
+            generate_consent_record(
+                ConsentType::ConversationId,
+                ConsentState::Allowed,
+                hex::encode(&read.id),
+            )
+            .store(conn)
+            .unwrap();
+            // consent for another kind of entity that happens to have the same hex
+            generate_consent_record(
+                ConsentType::InboxId,
+                ConsentState::Denied,
+                hex::encode(&quiet.id),
+            )
+            .store(conn)
+            .unwrap();
+
+            for sent_at_ns in [1000, 2000, 3000, 4000] {
+                generate_message(
+                    None,
+                    Some(&read.id),
+                    Some(sent_at_ns),
+                    Some(ContentType::Text),
+                )
+                .store(conn)
+                .unwrap();
+            }
+            let mut own =
+                generate_message(None, Some(&read.id), Some(5000), Some(ContentType::Text));
+            own.sender_inbox_id = "alix".to_string();
+            own.store(conn).unwrap();
+            conn.mark_group_read(&read.id, 2000, "alix").unwrap();
+
+            let overview = conn
+                .fetch_conversation_overview(GroupQueryArgs::default())
+                .unwrap();
+            assert_eq!(overview.len(), 2);
+            assert_eq!(overview[0].conversation.id, read.id);
+            assert_eq!(overview[0].conversation.message_id, Some(own.id.clone()));
+            assert_eq!(overview[0].unread_count, 2);
+            assert_eq!(overview[0].consent_state, Some(ConsentState::Allowed));
+            assert_eq!(overview[1].conversation.id, quiet.id);
+            assert_eq!(overview[1].unread_count, 0);
+            assert_eq!(overview[1].consent_state, None);
+
+            let allowed = conn
+                .fetch_conversation_overview(
+                    GroupQueryArgs::default().consent_states(vec![ConsentState::Allowed]),
+                )
+                .unwrap();
+            assert_eq!(allowed.len(), 1);
+            assert_eq!(allowed[0].conversation.id, read.id);
+        })
+        .await
+    }
 }
@@ -0,0 +1,124 @@
+//! Per-conversation key-value state kept by apps on this installation, e.g. scroll anchors.
+//! Entries are removed along with their conversation.
+use super::{
+    db_connection::DbConnection,
+    schema::conversation_scratch::{self, dsl},
+};
+use crate::{storage::StorageError, Store};
+use diesel::prelude::*;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = conversation_scratch)]
+#[diesel(primary_key(group_id, key))]
+pub struct StoredScratchEntry {
+    pub group_id: Vec<u8>,
+    pub key: String,
+    pub value: Vec<u8>,
+    pub updated_at_ns: i64,
+}
+
+impl StoredScratchEntry {
+    /// Bytes the entry counts against its conversation's quota
+    pub fn size(&self) -> usize {
+        self.key.len() + self.value.len()
+    }
+}
+
+impl Store<DbConnection> for StoredScratchEntry {
+    // Setting a key again replaces its value
+    fn store(&self, into: &DbConnection) -> Result<(), StorageError> {
+        into.raw_query(|conn| {
+            diesel::replace_into(dsl::conversation_scratch)
+                .values(self)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+}
+
+impl DbConnection {
+    pub fn get_scratch_entry<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        key: &str,
+    ) -> Result<Option<StoredScratchEntry>, StorageError> {
+        let query = dsl::conversation_scratch.find((group_id.as_ref(), key));
+        Ok(self.raw_query(|conn| query.first(conn).optional())?)
+    }
+
+    /// All entries of `group_id`, ordered by key
+    pub fn get_scratch_entries<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<Vec<StoredScratchEntry>, StorageError> {
+        let query = dsl::conversation_scratch
+            .filter(dsl::group_id.eq(group_id.as_ref()))
+            .order(dsl::key.asc());
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// Delete the entries of `group_id`, optionally only the one with `key`.
+    /// Returns the number of entries deleted.
+    pub fn delete_scratch_entries<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        key: Option<&str>,
+    ) -> Result<usize, StorageError> {
+        let mut query = diesel::delete(dsl::conversation_scratch)
+            .filter(dsl::group_id.eq(group_id.as_ref()))
+            .into_boxed();
+        if let Some(key) = key {
+            query = query.filter(dsl::key.eq(key));
+        }
+
+        Ok(self.raw_query(|conn| query.execute(conn))?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::{
+        group::tests::generate_group, schema::groups::dsl as groups_dsl, tests::with_connection,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn entry(group_id: &[u8], key: &str, value: &[u8]) -> StoredScratchEntry {
+        StoredScratchEntry {
+            group_id: group_id.to_vec(),
+            key: key.to_string(),
+            value: value.to_vec(),
+            updated_at_ns: 0,
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_removes_scratch_entries_with_the_group() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let other_group = generate_group(None);
+            other_group.store(conn).unwrap();
+
+            entry(&group.id, "anchor", b"1").store(conn).unwrap();
+            entry(&group.id, "anchor", b"2").store(conn).unwrap();
+            entry(&group.id, "flag", b"on").store(conn).unwrap();
+            entry(&other_group.id, "anchor", b"3").store(conn).unwrap();
+
+            let anchor = conn.get_scratch_entry(&group.id, "anchor").unwrap();
+            assert_eq!(anchor.unwrap().value, b"2");
+            assert_eq!(conn.get_scratch_entries(&group.id).unwrap().len(), 2);
+
+            conn.raw_query(|conn| {
+                diesel::delete(groups_dsl::groups.find(group.id.as_slice())).execute(conn)
+            })
+            .unwrap();
+            assert!(conn.get_scratch_entries(&group.id).unwrap().is_empty());
+            assert_eq!(conn.get_scratch_entries(&other_group.id).unwrap().len(), 1);
+        })
+        .await
+    }
+}
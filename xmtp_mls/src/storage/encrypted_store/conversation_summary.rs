@@ -0,0 +1,196 @@
+//! Summaries of a range of a conversation's messages, generated by the app (e.g. by an
+//! assistant feature) and stored alongside the conversation.
+use super::{
+    db_connection::DbConnection,
+    schema::{
+        conversation_summaries::{self, dsl},
+        group_messages::dsl as messages_dsl,
+    },
+};
+use crate::{storage::StorageError, Store};
+use diesel::prelude::*;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = conversation_summaries)]
+#[diesel(primary_key(group_id, key, start_ns, end_ns))]
+pub struct StoredConversationSummary {
+    pub group_id: Vec<u8>,
+    /// Set by the app to tell summaries of different kinds apart, e.g. `"daily"`
+    pub key: String,
+    /// `sent_at_ns` of the first message summarized
+    pub start_ns: i64,
+    /// `sent_at_ns` of the last message summarized
+    pub end_ns: i64,
+    pub content: Vec<u8>,
+    /// Number of messages in the range when the summary was stored
+    pub message_count: i64,
+    pub created_at_ns: i64,
+}
+
+impl Store<DbConnection> for StoredConversationSummary {
+    // Summarizing the same range again replaces the earlier summary
+    fn store(&self, into: &DbConnection) -> Result<(), StorageError> {
+        into.raw_query(|conn| {
+            diesel::replace_into(dsl::conversation_summaries)
+                .values(self)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+}
+
+impl DbConnection {
+    /// Summaries of `group_id`, optionally only those with `key`, most recent range first
+    pub fn get_conversation_summaries<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        key: Option<&str>,
+    ) -> Result<Vec<StoredConversationSummary>, StorageError> {
+        let mut query = dsl::conversation_summaries
+            .filter(dsl::group_id.eq(group_id.as_ref()))
+            .order((dsl::end_ns.desc(), dsl::start_ns.desc()))
+            .into_boxed();
+        if let Some(key) = key {
+            query = query.filter(dsl::key.eq(key));
+        }
+
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// Delete the summaries of `group_id`, optionally only those with `key`.
+    /// Returns the number of summaries deleted.
+    pub fn delete_conversation_summaries<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        key: Option<&str>,
+    ) -> Result<usize, StorageError> {
+        let mut query = diesel::delete(dsl::conversation_summaries)
+            .filter(dsl::group_id.eq(group_id.as_ref()))
+            .into_boxed();
+        if let Some(key) = key {
+            query = query.filter(dsl::key.eq(key));
+        }
+
+        Ok(self.raw_query(|conn| query.execute(conn))?)
+    }
+
+    /// Number of messages in `group_id` sent between `start_ns` and `end_ns`, inclusive
+    pub fn count_group_messages_between<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        start_ns: i64,
+        end_ns: i64,
+    ) -> Result<i64, StorageError> {
+        let query = messages_dsl::group_messages
+            .filter(messages_dsl::group_id.eq(group_id.as_ref()))
+            .filter(messages_dsl::sent_at_ns.between(start_ns, end_ns))
+            .count();
+
+        Ok(self.raw_query(|conn| query.get_result(conn))?)
+    }
+
+    /// Number of messages in `group_id` sent after `sent_after_ns`
+    pub fn count_group_messages_after<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        sent_after_ns: i64,
+    ) -> Result<i64, StorageError> {
+        let query = messages_dsl::group_messages
+            .filter(messages_dsl::group_id.eq(group_id.as_ref()))
+            .filter(messages_dsl::sent_at_ns.gt(sent_after_ns))
+            .count();
+
+        Ok(self.raw_query(|conn| query.get_result(conn))?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::{
+        group::tests::generate_group, group_message::tests::generate_message,
+        tests::with_connection,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn summary(
+        group_id: &[u8],
+        key: &str,
+        start_ns: i64,
+        end_ns: i64,
+    ) -> StoredConversationSummary {
+        StoredConversationSummary {
+            group_id: group_id.to_vec(),
+            key: key.to_string(),
+            start_ns,
+            end_ns,
+            content: b"summary".to_vec(),
+            message_count: 0,
+            created_at_ns: 0,
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_stores_summaries_by_key_and_range() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            for sent_at_ns in [1_000, 2_000, 3_000] {
+                generate_message(None, Some(&group.id), Some(sent_at_ns), None)
+                    .store(conn)
+                    .unwrap();
+            }
+            assert_eq!(
+                conn.count_group_messages_between(&group.id, 1_000, 2_000)
+                    .unwrap(),
+                2
+            );
+            assert_eq!(
+                conn.count_group_messages_after(&group.id, 2_000).unwrap(),
+                1
+            );
+
+            summary(&group.id, "daily", 1_000, 2_000)
+                .store(conn)
+                .unwrap();
+            summary(&group.id, "daily", 2_000, 3_000)
+                .store(conn)
+                .unwrap();
+            summary(&group.id, "topics", 1_000, 3_000)
+                .store(conn)
+                .unwrap();
+            // replaces the first summary
+            summary(&group.id, "daily", 1_000, 2_000)
+                .store(conn)
+                .unwrap();
+
+            let daily = conn
+                .get_conversation_summaries(&group.id, Some("daily"))
+                .unwrap();
+            assert_eq!(daily.len(), 2);
+            assert_eq!(daily[0].end_ns, 3_000);
+            assert_eq!(
+                conn.get_conversation_summaries(&group.id, None)
+                    .unwrap()
+                    .len(),
+                3
+            );
+
+            assert_eq!(
+                conn.delete_conversation_summaries(&group.id, Some("daily"))
+                    .unwrap(),
+                2
+            );
+            assert_eq!(
+                conn.get_conversation_summaries(&group.id, None)
+                    .unwrap()
+                    .len(),
+                1
+            );
+        })
+        .await
+    }
+}
@@ -0,0 +1,129 @@
+//! Preferences defined by the app, such as a theme, stored as opaque payloads under the namespace
+//! the app registered. They're synced to the user's other installations, so each is kept with the
+//! [hybrid logical clock](crate::hlc) timestamp of its write and an update only replaces a later
+//! write. Removing a preference keeps it without a payload for the same reason.
+use super::{
+    db_connection::DbConnection,
+    schema::custom_preferences::{self, dsl},
+};
+use crate::{hlc::Hlc, storage::StorageError};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[diesel(table_name = custom_preferences)]
+#[diesel(primary_key(namespace))]
+pub struct StoredCustomPreference {
+    pub namespace: String,
+    /// The preference, or `None` if it was removed
+    pub payload: Option<Vec<u8>>,
+    /// Hybrid logical clock timestamp of the write, see [`Self::hlc`]
+    pub hlc_wall_ns: i64,
+    pub hlc_counter: i32,
+    pub hlc_node: Vec<u8>,
+}
+
+impl StoredCustomPreference {
+    pub fn new(namespace: String, payload: Option<Vec<u8>>, hlc: Hlc) -> Self {
+        Self {
+            namespace,
+            payload,
+            hlc_wall_ns: hlc.wall_ns,
+            hlc_counter: hlc.counter as i32,
+            hlc_node: hlc.node,
+        }
+    }
+
+    /// When the preference was written, ordering concurrent writes from different installations
+    pub fn hlc(&self) -> Hlc {
+        Hlc {
+            wall_ns: self.hlc_wall_ns,
+            counter: self.hlc_counter as u32,
+            node: self.hlc_node.clone(),
+        }
+    }
+}
+
+impl DbConnection {
+    pub fn get_custom_preference(
+        &self,
+        namespace: &str,
+    ) -> Result<Option<StoredCustomPreference>, StorageError> {
+        let query = dsl::custom_preferences.find(namespace);
+
+        Ok(self.raw_query(|conn| query.first(conn).optional())?)
+    }
+
+    /// Every custom preference, including removed ones
+    pub fn all_custom_preferences(&self) -> Result<Vec<StoredCustomPreference>, StorageError> {
+        Ok(self.raw_query(|conn| dsl::custom_preferences.load(conn))?)
+    }
+
+    /// Store `preference` unless the stored one was written later. Returns whether it was stored.
+    pub fn set_custom_preference(
+        &self,
+        preference: &StoredCustomPreference,
+    ) -> Result<bool, StorageError> {
+        Ok(self.raw_query(|conn| -> diesel::QueryResult<bool> {
+            let current: Option<StoredCustomPreference> = dsl::custom_preferences
+                .find(&preference.namespace)
+                .first(conn)
+                .optional()?;
+            if current.is_some_and(|current| current.hlc() >= preference.hlc()) {
+                return Ok(false);
+            }
+            diesel::replace_into(dsl::custom_preferences)
+                .values(preference)
+                .execute(conn)?;
+            Ok(true)
+        })?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::tests::with_connection;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_keeps_the_latest_write() {
+        with_connection(|conn| {
+            let at = |wall_ns, node: u8| Hlc {
+                wall_ns,
+                counter: 0,
+                node: vec![node],
+            };
+            let theme = StoredCustomPreference::new(
+                "com.example.theme".to_string(),
+                Some(b"dark".to_vec()),
+                at(10, 1),
+            );
+            assert!(conn.set_custom_preference(&theme).unwrap());
+
+            let stale = StoredCustomPreference::new(
+                theme.namespace.clone(),
+                Some(b"light".to_vec()),
+                at(5, 2),
+            );
+            assert!(!conn.set_custom_preference(&stale).unwrap());
+            // writing the same preference again is a no-op
+            assert!(!conn.set_custom_preference(&theme).unwrap());
+            assert_eq!(
+                conn.get_custom_preference(&theme.namespace).unwrap(),
+                Some(theme.clone())
+            );
+
+            // a later removal wins over the preference
+            let removed = StoredCustomPreference::new(theme.namespace.clone(), None, at(10, 2));
+            assert!(conn.set_custom_preference(&removed).unwrap());
+            assert_eq!(conn.all_custom_preferences().unwrap(), vec![removed]);
+        })
+        .await
+    }
+}
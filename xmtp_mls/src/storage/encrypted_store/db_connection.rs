@@ -1,8 +1,9 @@
+use diesel::connection::TransactionManager;
 use parking_lot::Mutex;
 use std::fmt;
 use std::sync::Arc;
 
-use crate::storage::xmtp_openmls_provider::XmtpOpenMlsProvider;
+use crate::{storage::xmtp_openmls_provider::XmtpOpenMlsProvider, subscriptions::LocalEvents};
 
 #[cfg(not(target_arch = "wasm32"))]
 pub type DbConnection = DbConnectionPrivate<super::RawDbConnection>;
@@ -20,13 +21,33 @@ pub type DbConnection = DbConnectionPrivate<sqlite_web::connection::WasmSqliteCo
 #[doc(hidden)]
 pub struct DbConnectionPrivate<C> {
     inner: Arc<Mutex<C>>,
+    /// Events to publish once the open transaction commits
+    deferred_events: Mutex<Vec<LocalEvents<()>>>,
 }
 
 /// Owned DBConnection Methods
 impl<C> DbConnectionPrivate<C> {
     /// Create a new [`DbConnectionPrivate`] from an existing Arc<Mutex<C>>
     pub(super) fn from_arc_mutex(conn: Arc<Mutex<C>>) -> Self {
-        Self { inner: conn }
+        Self {
+            inner: conn,
+            deferred_events: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Queue `event` until the open transaction commits, see
+    /// [`MlsGroup::publish_after_commit`](crate::groups::MlsGroup::publish_after_commit)
+    pub(crate) fn defer_event(&self, event: LocalEvents<()>) {
+        self.deferred_events.lock().push(event);
+    }
+
+    pub(super) fn deferred_event_count(&self) -> usize {
+        self.deferred_events.lock().len()
+    }
+
+    /// Drop the events deferred after the first `count`, as their transaction rolled back
+    pub(super) fn discard_deferred_events(&self, count: usize) {
+        self.deferred_events.lock().truncate(count);
     }
 }
 
@@ -44,6 +65,24 @@ where
         fun(&mut lock)
     }
 
+    /// Whether a transaction is open on this connection
+    pub(crate) fn in_transaction(&self) -> bool {
+        self.raw_query(|conn| {
+            <C as diesel::Connection>::TransactionManager::transaction_manager_status_mut(conn)
+                .transaction_depth()
+                .map(|depth| depth.is_some())
+        })
+        .unwrap_or(true)
+    }
+
+    /// The deferred events, once the transactions that queued them have committed
+    pub(crate) fn take_committed_events(&self) -> Vec<LocalEvents<()>> {
+        if self.in_transaction() {
+            return Vec::new();
+        }
+        std::mem::take(&mut *self.deferred_events.lock())
+    }
+
     /// Internal-only API to get the underlying `diesel::Connection` reference
     /// without a scope
     /// Must be used with care. holding this reference while calling `raw_query`
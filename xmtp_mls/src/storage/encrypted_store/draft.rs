@@ -0,0 +1,122 @@
+//! The user's unfinished message in each conversation. Drafts are synced to the user's other
+//! installations, so they're kept with the [hybrid logical clock](crate::hlc) timestamp of their
+//! write and an update only replaces an earlier write. Clearing a draft keeps it without content
+//! for the same reason.
+use super::{
+    db_connection::DbConnection,
+    schema::drafts::{self, dsl},
+};
+use crate::{hlc::Hlc, storage::StorageError};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[diesel(table_name = drafts)]
+#[diesel(primary_key(group_id))]
+pub struct StoredDraft {
+    pub group_id: Vec<u8>,
+    /// The draft, or `None` if it was cleared
+    pub content: Option<Vec<u8>>,
+    /// Hybrid logical clock timestamp of the write, see [`Self::hlc`]
+    pub hlc_wall_ns: i64,
+    pub hlc_counter: i32,
+    pub hlc_node: Vec<u8>,
+}
+
+impl StoredDraft {
+    pub fn new(group_id: Vec<u8>, content: Option<Vec<u8>>, hlc: Hlc) -> Self {
+        Self {
+            group_id,
+            content,
+            hlc_wall_ns: hlc.wall_ns,
+            hlc_counter: hlc.counter as i32,
+            hlc_node: hlc.node,
+        }
+    }
+
+    /// When the draft was written, ordering concurrent writes from different installations
+    pub fn hlc(&self) -> Hlc {
+        Hlc {
+            wall_ns: self.hlc_wall_ns,
+            counter: self.hlc_counter as u32,
+            node: self.hlc_node.clone(),
+        }
+    }
+}
+
+impl DbConnection {
+    pub fn get_draft<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<Option<StoredDraft>, StorageError> {
+        let query = dsl::drafts.find(group_id.as_ref());
+
+        Ok(self.raw_query(|conn| query.first(conn).optional())?)
+    }
+
+    /// Every draft, including cleared ones
+    pub fn all_drafts(&self) -> Result<Vec<StoredDraft>, StorageError> {
+        Ok(self.raw_query(|conn| dsl::drafts.load(conn))?)
+    }
+
+    /// Store `draft` unless the stored draft was written later. Returns whether it was stored.
+    pub fn set_draft(&self, draft: &StoredDraft) -> Result<bool, StorageError> {
+        Ok(self.raw_query(|conn| -> diesel::QueryResult<bool> {
+            let current: Option<StoredDraft> =
+                dsl::drafts.find(&draft.group_id).first(conn).optional()?;
+            if current.is_some_and(|current| current.hlc() >= draft.hlc()) {
+                return Ok(false);
+            }
+            diesel::replace_into(dsl::drafts)
+                .values(draft)
+                .execute(conn)?;
+            Ok(true)
+        })?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_keeps_the_newest_draft() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+
+            let hlc = |wall_ns, node: u8| Hlc {
+                wall_ns,
+                counter: 0,
+                node: vec![node],
+            };
+            let draft =
+                StoredDraft::new(group.id.clone(), Some(b"see you at".to_vec()), hlc(10, 1));
+            assert!(conn.set_draft(&draft).unwrap());
+            let stale = StoredDraft::new(group.id.clone(), Some(b"see".to_vec()), hlc(5, 2));
+            assert!(!conn.set_draft(&stale).unwrap());
+            // the same write again, e.g. synced back, changes nothing
+            assert!(!conn.set_draft(&draft).unwrap());
+            assert_eq!(conn.get_draft(&group.id).unwrap(), Some(draft.clone()));
+
+            // a later clear wins over the draft, and concurrent writes are ordered by installation
+            let concurrent = StoredDraft::new(group.id.clone(), Some(b"see".to_vec()), hlc(20, 1));
+            assert!(conn.set_draft(&concurrent).unwrap());
+            let cleared = StoredDraft::new(group.id.clone(), None, hlc(20, 2));
+            assert!(conn.set_draft(&cleared).unwrap());
+            assert!(!conn.set_draft(&concurrent).unwrap());
+            assert_eq!(conn.get_draft(&group.id).unwrap(), Some(cleared));
+        })
+        .await
+    }
+}
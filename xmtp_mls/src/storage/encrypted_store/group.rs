@@ -2,6 +2,7 @@
 use super::{
     consent_record::{ConsentState, StoredConsentRecord},
     db_connection::DbConnection,
+    notification_settings::muted_filter,
     schema::groups::{self, dsl},
     Sqlite,
 };
@@ -138,6 +139,8 @@ pub struct GroupQueryArgs {
     pub consent_states: Option<Vec<ConsentState>>,
     pub include_sync_groups: bool,
     pub include_duplicate_dms: bool,
+    /// Only groups whose notifications are muted, or only those that aren't
+    pub muted: Option<bool>,
 }
 
 impl AsRef<GroupQueryArgs> for GroupQueryArgs {
@@ -207,6 +210,15 @@ impl GroupQueryArgs {
         self.include_sync_groups = true;
         self
     }
+
+    pub fn muted(self, muted: bool) -> Self {
+        self.maybe_muted(Some(muted))
+    }
+
+    pub fn maybe_muted(mut self, muted: Option<bool>) -> Self {
+        self.muted = muted;
+        self
+    }
 }
 
 impl DbConnection {
@@ -226,6 +238,7 @@ impl DbConnection {
             consent_states,
             include_sync_groups,
             include_duplicate_dms,
+            muted,
         } = args.as_ref();
 
         let mut query = groups_dsl::groups
@@ -265,6 +278,10 @@ impl DbConnection {
             query = query.filter(groups_dsl::conversation_type.eq(conversation_type));
         }
 
+        if let Some(muted) = muted {
+            query = query.filter(muted_filter(*muted));
+        }
+
         let mut groups = if let Some(consent_states) = consent_states {
             if consent_states
                 .iter()
@@ -475,6 +492,17 @@ impl DbConnection {
                     return Err(StorageError::Duplicate(DuplicateItem::WelcomeId(
                         existing_group.welcome_id,
                     )));
+                } else if existing_group.membership_state == GroupMembershipState::Restored {
+                    // Read-only history restored from an archive or backup is taken over by the
+                    // group this installation joined
+                    tracing::info!("Restored group is joined");
+                    return Ok(diesel::update(dsl::groups.find(&group.id))
+                        .set((
+                            dsl::membership_state.eq(group.membership_state),
+                            dsl::added_by_inbox_id.eq(&group.added_by_inbox_id),
+                            dsl::welcome_id.eq(group.welcome_id),
+                        ))
+                        .get_result(conn)?);
                 } else {
                     tracing::info!("Group already exists");
                     return Ok(existing_group);
@@ -504,6 +532,9 @@ pub enum GroupMembershipState {
     Rejected = 2,
     /// User is Pending acceptance to the Group
     Pending = 3,
+    /// Read-only history restored from an archive or backup. There is no MLS state for the
+    /// group, so it isn't synced and nothing can be sent to it.
+    Restored = 4,
 }
 
 impl ToSql<Integer, Sqlite> for GroupMembershipState
@@ -525,6 +556,7 @@ where
             1 => Ok(GroupMembershipState::Allowed),
             2 => Ok(GroupMembershipState::Rejected),
             3 => Ok(GroupMembershipState::Pending),
+            4 => Ok(GroupMembershipState::Restored),
             x => Err(format!("Unrecognized variant {}", x).into()),
         }
     }
@@ -638,11 +670,7 @@ pub(crate) mod tests {
         state: ConsentState,
         entity: String,
     ) -> StoredConsentRecord {
-        StoredConsentRecord {
-            entity_type,
-            state,
-            entity,
-        }
+        StoredConsentRecord::new(entity_type, state, entity)
     }
 
     static TARGET_INBOX_ID: AtomicU16 = AtomicU16::new(2);
@@ -0,0 +1,79 @@
+//! When each group last moved to a new epoch. Every commit replaces the group's epoch secrets,
+//! so this is when the keys messages are encrypted with were last changed.
+use super::{
+    db_connection::DbConnection,
+    schema::group_epochs::{self, dsl},
+};
+use crate::storage::StorageError;
+use diesel::prelude::*;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = group_epochs)]
+#[diesel(primary_key(group_id))]
+pub struct StoredGroupEpoch {
+    pub group_id: Vec<u8>,
+    pub epoch: i64,
+    /// The server timestamp of the commit that moved the group to `epoch`
+    pub changed_at_ns: i64,
+}
+
+impl DbConnection {
+    /// Record that a commit sent at `changed_at_ns` moved `group_id` to `epoch`
+    pub fn record_epoch_change(
+        &self,
+        group_id: &[u8],
+        epoch: i64,
+        changed_at_ns: i64,
+    ) -> Result<(), StorageError> {
+        let record = StoredGroupEpoch {
+            group_id: group_id.to_vec(),
+            epoch,
+            changed_at_ns,
+        };
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::group_epochs)
+                .values(&record)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// When `group_id` last moved to a new epoch, or `None` if it hasn't since this installation
+    /// joined it
+    pub fn get_epoch_changed_at_ns(&self, group_id: &[u8]) -> Result<Option<i64>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::group_epochs
+                .find(group_id)
+                .select(dsl::changed_at_ns)
+                .first(conn)
+                .optional()
+        })?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_keeps_the_latest_epoch_change() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            assert_eq!(conn.get_epoch_changed_at_ns(&group.id).unwrap(), None);
+
+            conn.record_epoch_change(&group.id, 2, 10).unwrap();
+            conn.record_epoch_change(&group.id, 3, 20).unwrap();
+            assert_eq!(conn.get_epoch_changed_at_ns(&group.id).unwrap(), Some(20));
+        })
+        .await
+    }
+}
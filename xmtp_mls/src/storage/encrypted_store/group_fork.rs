@@ -0,0 +1,169 @@
+//! Undecryptable messages in a row, per group. A group whose messages keep failing to decrypt has
+//! most likely forked: this installation missed or rejected a commit the other members merged,
+//! and can't follow them anymore. The row is removed as soon as a message is processed again.
+use super::{
+    db_connection::DbConnection,
+    schema::group_forks::{self, dsl},
+};
+use crate::storage::StorageError;
+use diesel::prelude::*;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = group_forks)]
+#[diesel(primary_key(group_id))]
+pub struct StoredGroupFork {
+    pub group_id: Vec<u8>,
+    /// The number of messages in a row that couldn't be decrypted
+    pub undecryptable_messages: i32,
+    /// The cursor of the latest of them
+    pub last_failed_cursor: i64,
+    pub first_failed_at_ns: i64,
+    /// When the group was considered forked
+    pub forked_at_ns: Option<i64>,
+    /// When this installation asked an admin to add it back
+    pub rejoin_requested_at_ns: Option<i64>,
+}
+
+impl DbConnection {
+    pub fn get_group_fork<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<Option<StoredGroupFork>, StorageError> {
+        let query = dsl::group_forks.find(group_id.as_ref());
+
+        Ok(self.raw_query(|conn| query.first(conn).optional())?)
+    }
+
+    /// The groups considered forked, oldest first
+    pub fn forked_groups(&self) -> Result<Vec<StoredGroupFork>, StorageError> {
+        let query = dsl::group_forks
+            .filter(dsl::forked_at_ns.is_not_null())
+            .order(dsl::forked_at_ns.asc());
+
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// Count the message at `cursor` as undecryptable, unless it or a later message was already
+    /// counted. Returns the updated record.
+    pub fn record_undecryptable_message<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        cursor: i64,
+        failed_at_ns: i64,
+    ) -> Result<StoredGroupFork, StorageError> {
+        let group_id = group_id.as_ref();
+        Ok(
+            self.raw_query(|conn| -> diesel::QueryResult<StoredGroupFork> {
+                let current: Option<StoredGroupFork> =
+                    dsl::group_forks.find(group_id).first(conn).optional()?;
+                let fork = match current {
+                    Some(fork) if fork.last_failed_cursor >= cursor => return Ok(fork),
+                    Some(fork) => StoredGroupFork {
+                        undecryptable_messages: fork.undecryptable_messages + 1,
+                        last_failed_cursor: cursor,
+                        ..fork
+                    },
+                    None => StoredGroupFork {
+                        group_id: group_id.to_vec(),
+                        undecryptable_messages: 1,
+                        last_failed_cursor: cursor,
+                        first_failed_at_ns: failed_at_ns,
+                        forked_at_ns: None,
+                        rejoin_requested_at_ns: None,
+                    },
+                };
+                diesel::replace_into(dsl::group_forks)
+                    .values(&fork)
+                    .execute(conn)?;
+                Ok(fork)
+            })?,
+        )
+    }
+
+    pub fn set_group_forked<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        forked_at_ns: i64,
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::update(dsl::group_forks.find(group_id.as_ref()))
+                .set(dsl::forked_at_ns.eq(forked_at_ns))
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    pub fn set_rejoin_requested<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        requested_at_ns: i64,
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::update(dsl::group_forks.find(group_id.as_ref()))
+                .set(dsl::rejoin_requested_at_ns.eq(requested_at_ns))
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// Forget the undecryptable messages of `group_id`, once a message was processed again.
+    /// Returns the removed record, if there was one.
+    pub fn clear_group_fork<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<Option<StoredGroupFork>, StorageError> {
+        let group_id = group_id.as_ref();
+        Ok(
+            self.raw_query(|conn| -> diesel::QueryResult<Option<StoredGroupFork>> {
+                let current: Option<StoredGroupFork> =
+                    dsl::group_forks.find(group_id).first(conn).optional()?;
+                if current.is_some() {
+                    diesel::delete(dsl::group_forks.find(group_id)).execute(conn)?;
+                }
+                Ok(current)
+            })?,
+        )
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_counts_each_undecryptable_message_once() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+
+            conn.record_undecryptable_message(&group.id, 10, 100)
+                .unwrap();
+            // retried on the next sync
+            conn.record_undecryptable_message(&group.id, 10, 200)
+                .unwrap();
+            let fork = conn
+                .record_undecryptable_message(&group.id, 11, 300)
+                .unwrap();
+            assert_eq!(fork.undecryptable_messages, 2);
+            assert_eq!(fork.first_failed_at_ns, 100);
+            assert!(conn.forked_groups().unwrap().is_empty());
+
+            conn.set_group_forked(&group.id, 300).unwrap();
+            assert_eq!(conn.forked_groups().unwrap().len(), 1);
+
+            let cleared = conn.clear_group_fork(&group.id).unwrap().unwrap();
+            assert_eq!(cleared.forked_at_ns, Some(300));
+            assert!(conn.get_group_fork(&group.id).unwrap().is_none());
+            assert!(conn.clear_group_fork(&group.id).unwrap().is_none());
+        })
+        .await
+    }
+}
@@ -37,6 +37,13 @@ pub enum IntentKind {
     UpdateGroupMembership = 4,
     UpdateAdminList = 5,
     UpdatePermission = 6,
+    /// Remove installations and add them back in the same commit, so they receive a fresh
+    /// welcome
+    ReaddInstallations = 7,
+    /// Accept the super admin role offered to this inbox, demoting the member who offered it
+    AcceptSuperAdminTransfer = 8,
+    /// Add or remove one entry of a metadata field holding a list
+    UpdateMetadataList = 9,
 }
 
 impl std::fmt::Display for IntentKind {
@@ -48,6 +55,9 @@ impl std::fmt::Display for IntentKind {
             IntentKind::UpdateGroupMembership => "UpdateGroupMembership",
             IntentKind::UpdateAdminList => "UpdateAdminList",
             IntentKind::UpdatePermission => "UpdatePermission",
+            IntentKind::ReaddInstallations => "ReaddInstallations",
+            IntentKind::AcceptSuperAdminTransfer => "AcceptSuperAdminTransfer",
+            IntentKind::UpdateMetadataList => "UpdateMetadataList",
         };
         write!(f, "{}", description)
     }
@@ -356,6 +366,9 @@ where
             4 => Ok(IntentKind::UpdateGroupMembership),
             5 => Ok(IntentKind::UpdateAdminList),
             6 => Ok(IntentKind::UpdatePermission),
+            7 => Ok(IntentKind::ReaddInstallations),
+            8 => Ok(IntentKind::AcceptSuperAdminTransfer),
+            9 => Ok(IntentKind::UpdateMetadataList),
             x => Err(format!("Unrecognized variant {}", x).into()),
         }
     }
@@ -0,0 +1,219 @@
+//! Inboxes added to and removed from a group, keyed by the epoch each change took effect in,
+//! so membership can be synced incrementally instead of reloading the full member list.
+use super::{
+    db_connection::DbConnection,
+    schema::group_membership_changes::{self, dsl},
+    Sqlite,
+};
+use crate::storage::StorageError;
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql, FromSqlRow},
+    expression::AsExpression,
+    prelude::*,
+    serialize::{self, IsNull, Output, ToSql},
+    sql_types::Integer,
+};
+use std::collections::HashMap;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = group_membership_changes)]
+#[diesel(primary_key(group_id, epoch, inbox_id))]
+pub struct StoredGroupMembershipChange {
+    pub group_id: Vec<u8>,
+    /// The epoch the change took effect in
+    pub epoch: i64,
+    pub inbox_id: String,
+    pub kind: MembershipChangeKind,
+}
+
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Integer)]
+pub enum MembershipChangeKind {
+    Added = 1,
+    Removed = 2,
+}
+
+impl ToSql<Integer, Sqlite> for MembershipChangeKind
+where
+    i32: ToSql<Integer, Sqlite>,
+{
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(*self as i32);
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Integer, Sqlite> for MembershipChangeKind
+where
+    i32: FromSql<Integer, Sqlite>,
+{
+    fn from_sql(bytes: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        match i32::from_sql(bytes)? {
+            1 => Ok(MembershipChangeKind::Added),
+            2 => Ok(MembershipChangeKind::Removed),
+            x => Err(format!("Unrecognized variant {}", x).into()),
+        }
+    }
+}
+
+/// The net effect of the membership changes after an epoch
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MembershipDelta {
+    /// Inboxes that are members now but were not at the epoch
+    pub added: Vec<String>,
+    /// Inboxes that were members at the epoch but are not anymore
+    pub removed: Vec<String>,
+}
+
+impl DbConnection {
+    /// Record inboxes added to or removed from `group_id` in `epoch`
+    pub fn record_membership_changes<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        epoch: i64,
+        changes: impl IntoIterator<Item = (String, MembershipChangeKind)>,
+    ) -> Result<(), StorageError> {
+        let records: Vec<StoredGroupMembershipChange> = changes
+            .into_iter()
+            .map(|(inbox_id, kind)| StoredGroupMembershipChange {
+                group_id: group_id.as_ref().to_vec(),
+                epoch,
+                inbox_id,
+                kind,
+            })
+            .collect();
+        if records.is_empty() {
+            return Ok(());
+        }
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::group_membership_changes)
+                .values(&records)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// Whether any membership change was ever recorded for `group_id`
+    pub fn has_membership_changes<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<bool, StorageError> {
+        let query = dsl::group_membership_changes
+            .filter(dsl::group_id.eq(group_id.as_ref()))
+            .select(dsl::epoch);
+        let epoch: Option<i64> = self.raw_query(|conn| query.first(conn).optional())?;
+        Ok(epoch.is_some())
+    }
+
+    /// Every membership change recorded for `group_id`, oldest epoch first
+    pub fn get_membership_changes<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<Vec<StoredGroupMembershipChange>, StorageError> {
+        let query = dsl::group_membership_changes
+            .filter(dsl::group_id.eq(group_id.as_ref()))
+            .order(dsl::epoch.asc());
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// The net membership changes to `group_id` in epochs after `since_epoch`.
+    /// An inbox removed and re-added (or added and removed) in that range is in neither list.
+    pub fn get_membership_delta<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        since_epoch: i64,
+    ) -> Result<MembershipDelta, StorageError> {
+        let query = dsl::group_membership_changes
+            .filter(dsl::group_id.eq(group_id.as_ref()))
+            .filter(dsl::epoch.gt(since_epoch))
+            .order(dsl::epoch.asc());
+        let changes: Vec<StoredGroupMembershipChange> = self.raw_query(|conn| query.load(conn))?;
+
+        // first and last change of each inbox, in the order inboxes were first changed
+        let mut order = vec![];
+        let mut by_inbox: HashMap<String, (MembershipChangeKind, MembershipChangeKind)> =
+            HashMap::new();
+        for change in changes {
+            by_inbox
+                .entry(change.inbox_id.clone())
+                .and_modify(|(_, last)| *last = change.kind)
+                .or_insert_with(|| {
+                    order.push(change.inbox_id);
+                    (change.kind, change.kind)
+                });
+        }
+
+        let mut delta = MembershipDelta::default();
+        for inbox_id in order {
+            match by_inbox[&inbox_id] {
+                (MembershipChangeKind::Added, MembershipChangeKind::Added) => {
+                    delta.added.push(inbox_id)
+                }
+                (MembershipChangeKind::Removed, MembershipChangeKind::Removed) => {
+                    delta.removed.push(inbox_id)
+                }
+                _ => {}
+            }
+        }
+        Ok(delta)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use MembershipChangeKind::*;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_computes_net_membership_changes() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            assert!(!conn.has_membership_changes(&group.id).unwrap());
+
+            let change = |inbox_id: &str, kind| (inbox_id.to_string(), kind);
+            conn.record_membership_changes(&group.id, 0, [change("alix", Added)])
+                .unwrap();
+            conn.record_membership_changes(
+                &group.id,
+                1,
+                [change("bo", Added), change("caro", Added)],
+            )
+            .unwrap();
+            conn.record_membership_changes(&group.id, 2, [change("bo", Removed)])
+                .unwrap();
+            conn.record_membership_changes(
+                &group.id,
+                3,
+                [change("alix", Removed), change("bo", Added)],
+            )
+            .unwrap();
+            assert!(conn.has_membership_changes(&group.id).unwrap());
+
+            let delta = conn.get_membership_delta(&group.id, 0).unwrap();
+            assert_eq!(delta.added, vec!["bo", "caro"]);
+            assert_eq!(delta.removed, vec!["alix"]);
+
+            // bo was a member at epoch 1, and is again after being removed and re-added
+            let delta = conn.get_membership_delta(&group.id, 1).unwrap();
+            assert!(delta.added.is_empty());
+            assert_eq!(delta.removed, vec!["alix"]);
+
+            assert_eq!(
+                conn.get_membership_delta(&group.id, 3).unwrap(),
+                MembershipDelta::default()
+            );
+        })
+        .await
+    }
+}
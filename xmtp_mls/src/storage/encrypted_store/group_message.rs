@@ -11,8 +11,9 @@ use diesel::{
 
 use serde::{Deserialize, Serialize};
 use xmtp_content_types::{
-    attachment, group_updated, membership_change, reaction, read_receipt, remote_attachment, reply,
-    text, transaction_reference,
+    attachment, capabilities, delete_message, edit, group_updated, invite_redemption, join_request,
+    link_preview, membership_change, poll, profile, reaction, read_receipt, remote_attachment,
+    reply, text, transaction_reference,
 };
 
 use super::{
@@ -20,6 +21,7 @@ use super::{
     schema::{
         group_messages::{self, dsl},
         groups::dsl as groups_dsl,
+        message_edits, message_reactions,
     },
     Sqlite,
 };
@@ -58,6 +60,36 @@ pub struct StoredGroupMessage {
     pub authority_id: String,
     /// The ID of a referenced message
     pub reference_id: Option<Vec<u8>>,
+    /// Time in nanoseconds the message was deleted. The content of deleted messages is erased.
+    #[serde(default)]
+    pub deleted_at_ns: Option<i64>,
+}
+
+/// The metadata of a stored message, loaded without its content
+#[derive(Debug, Clone, Queryable, Selectable, Eq, PartialEq)]
+#[diesel(table_name = group_messages)]
+pub struct StoredGroupMessageHeader {
+    pub id: Vec<u8>,
+    pub group_id: Vec<u8>,
+    pub sent_at_ns: i64,
+    pub kind: GroupMessageKind,
+    pub sender_inbox_id: String,
+    pub delivery_status: DeliveryStatus,
+    pub content_type: ContentType,
+}
+
+impl From<&StoredGroupMessage> for StoredGroupMessageHeader {
+    fn from(message: &StoredGroupMessage) -> Self {
+        Self {
+            id: message.id.clone(),
+            group_id: message.group_id.clone(),
+            sent_at_ns: message.sent_at_ns,
+            kind: message.kind,
+            sender_inbox_id: message.sender_inbox_id.clone(),
+            delivery_status: message.delivery_status,
+            content_type: message.content_type,
+        }
+    }
 }
 
 pub struct StoredGroupMessageWithReactions {
@@ -118,6 +150,15 @@ pub enum ContentType {
     Attachment = 7,
     RemoteAttachment = 8,
     TransactionReference = 9,
+    Capabilities = 10,
+    Profile = 11,
+    Edit = 12,
+    DeleteMessage = 13,
+    InviteRedemption = 14,
+    JoinRequest = 15,
+    Poll = 16,
+    PollVote = 17,
+    LinkPreview = 18,
 }
 
 impl std::fmt::Display for ContentType {
@@ -133,6 +174,15 @@ impl std::fmt::Display for ContentType {
             Self::RemoteAttachment => remote_attachment::RemoteAttachmentCodec::TYPE_ID,
             Self::Reply => reply::ReplyCodec::TYPE_ID,
             Self::TransactionReference => transaction_reference::TransactionReferenceCodec::TYPE_ID,
+            Self::Capabilities => capabilities::CapabilitiesCodec::TYPE_ID,
+            Self::Profile => profile::ProfileCodec::TYPE_ID,
+            Self::Edit => edit::EditCodec::TYPE_ID,
+            Self::DeleteMessage => delete_message::DeleteMessageCodec::TYPE_ID,
+            Self::InviteRedemption => invite_redemption::InviteRedemptionCodec::TYPE_ID,
+            Self::JoinRequest => join_request::JoinRequestCodec::TYPE_ID,
+            Self::Poll => poll::PollCodec::TYPE_ID,
+            Self::PollVote => poll::PollVoteCodec::TYPE_ID,
+            Self::LinkPreview => link_preview::LinkPreviewCodec::TYPE_ID,
         };
 
         write!(f, "{}", as_string)
@@ -151,6 +201,15 @@ impl From<String> for ContentType {
             attachment::AttachmentCodec::TYPE_ID => Self::Attachment,
             remote_attachment::RemoteAttachmentCodec::TYPE_ID => Self::RemoteAttachment,
             transaction_reference::TransactionReferenceCodec::TYPE_ID => Self::TransactionReference,
+            capabilities::CapabilitiesCodec::TYPE_ID => Self::Capabilities,
+            profile::ProfileCodec::TYPE_ID => Self::Profile,
+            edit::EditCodec::TYPE_ID => Self::Edit,
+            delete_message::DeleteMessageCodec::TYPE_ID => Self::DeleteMessage,
+            invite_redemption::InviteRedemptionCodec::TYPE_ID => Self::InviteRedemption,
+            join_request::JoinRequestCodec::TYPE_ID => Self::JoinRequest,
+            poll::PollCodec::TYPE_ID => Self::Poll,
+            poll::PollVoteCodec::TYPE_ID => Self::PollVote,
+            link_preview::LinkPreviewCodec::TYPE_ID => Self::LinkPreview,
             _ => Self::Unknown,
         }
     }
@@ -182,6 +241,15 @@ where
             7 => Ok(ContentType::Attachment),
             8 => Ok(ContentType::RemoteAttachment),
             9 => Ok(ContentType::TransactionReference),
+            10 => Ok(ContentType::Capabilities),
+            11 => Ok(ContentType::Profile),
+            12 => Ok(ContentType::Edit),
+            13 => Ok(ContentType::DeleteMessage),
+            14 => Ok(ContentType::InviteRedemption),
+            15 => Ok(ContentType::JoinRequest),
+            16 => Ok(ContentType::Poll),
+            17 => Ok(ContentType::PollVote),
+            18 => Ok(ContentType::LinkPreview),
             x => Err(format!("Unrecognized variant {}", x).into()),
         }
     }
@@ -224,6 +292,39 @@ impl_fetch!(StoredGroupMessage, group_messages, Vec<u8>);
 impl_store!(StoredGroupMessage, group_messages);
 impl_store_or_ignore!(StoredGroupMessage, group_messages);
 
+/// A position between two messages of a message query. Messages are ordered by when they were
+/// sent with ties broken by id, so a cursor keeps its place as messages are inserted or deleted
+/// around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageCursor {
+    pub sent_at_ns: i64,
+    pub message_id: Vec<u8>,
+}
+
+impl MessageCursor {
+    /// The position of `message`
+    pub fn at(message: &StoredGroupMessage) -> Self {
+        Self {
+            sent_at_ns: message.sent_at_ns,
+            message_id: message.id.clone(),
+        }
+    }
+
+    /// The cursor as an opaque token to hand out to apps
+    pub fn to_token(&self) -> String {
+        format!("{}:{}", self.sent_at_ns, hex::encode(&self.message_id))
+    }
+
+    /// The cursor in `token`, or `None` if it isn't a token made by [`Self::to_token`]
+    pub fn from_token(token: &str) -> Option<Self> {
+        let (sent_at_ns, message_id) = token.split_once(':')?;
+        Some(Self {
+            sent_at_ns: sent_at_ns.parse().ok()?,
+            message_id: hex::decode(message_id).ok()?,
+        })
+    }
+}
+
 #[derive(Default, Clone)]
 pub struct MsgQueryArgs {
     pub sent_after_ns: Option<i64>,
@@ -233,6 +334,12 @@ pub struct MsgQueryArgs {
     pub limit: Option<i64>,
     pub direction: Option<SortDirection>,
     pub content_types: Option<Vec<ContentType>>,
+    /// Only messages past the cursor in `direction`
+    pub cursor: Option<MessageCursor>,
+    /// Only messages ordered before the cursor, whatever the `direction`
+    pub before_cursor: Option<MessageCursor>,
+    /// Only messages ordered after the cursor, whatever the `direction`
+    pub after_cursor: Option<MessageCursor>,
 }
 
 impl DbConnection {
@@ -242,6 +349,123 @@ impl DbConnection {
         group_id: &[u8],
         args: &MsgQueryArgs,
     ) -> Result<Vec<StoredGroupMessage>, StorageError> {
+        let query = Self::group_messages_query(group_id, args, false);
+        Ok(self.raw_query(|conn| query.load::<StoredGroupMessage>(conn))?)
+    }
+
+    /// Like [`Self::get_group_messages`], skipping the messages of blocked inboxes that are
+    /// hidden, see [`super::blocked_inbox`]
+    pub(crate) fn get_visible_group_messages(
+        &self,
+        group_id: &[u8],
+        args: &MsgQueryArgs,
+    ) -> Result<Vec<StoredGroupMessage>, StorageError> {
+        let query = Self::group_messages_query(group_id, args, true);
+        Ok(self.raw_query(|conn| query.load::<StoredGroupMessage>(conn))?)
+    }
+
+    /// Query for the headers of group messages, skipping the message content
+    pub fn get_group_message_headers(
+        &self,
+        group_id: &[u8],
+        args: &MsgQueryArgs,
+    ) -> Result<Vec<StoredGroupMessageHeader>, StorageError> {
+        let query = Self::group_messages_query(group_id, args, false)
+            .select(StoredGroupMessageHeader::as_select());
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// Like [`Self::get_group_message_headers`], skipping the messages of hidden senders
+    pub(crate) fn get_visible_group_message_headers(
+        &self,
+        group_id: &[u8],
+        args: &MsgQueryArgs,
+    ) -> Result<Vec<StoredGroupMessageHeader>, StorageError> {
+        let query = Self::group_messages_query(group_id, args, true)
+            .select(StoredGroupMessageHeader::as_select());
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// Count the group messages matching `args`, ignoring its cursors and limit
+    pub fn count_group_messages(
+        &self,
+        group_id: &[u8],
+        args: &MsgQueryArgs,
+    ) -> Result<i64, StorageError> {
+        let query = Self::group_messages_filter(group_id, args, false).count();
+        Ok(self.raw_query(|conn| query.get_result::<i64>(conn))?)
+    }
+
+    /// Like [`Self::count_group_messages`], skipping the messages of hidden senders
+    pub(crate) fn count_visible_group_messages(
+        &self,
+        group_id: &[u8],
+        args: &MsgQueryArgs,
+    ) -> Result<i64, StorageError> {
+        let query = Self::group_messages_filter(group_id, args, true).count();
+        Ok(self.raw_query(|conn| query.get_result::<i64>(conn))?)
+    }
+
+    fn group_messages_query<'a>(
+        group_id: &'a [u8],
+        args: &'a MsgQueryArgs,
+        visible_only: bool,
+    ) -> group_messages::BoxedQuery<'a, Sqlite> {
+        let mut query = Self::group_messages_filter(group_id, args, visible_only);
+        let direction = args.direction.as_ref().unwrap_or(&SortDirection::Ascending);
+
+        if let Some(cursor) = &args.cursor {
+            let at = cursor.sent_at_ns;
+            query = match direction {
+                SortDirection::Ascending => query.filter(
+                    dsl::sent_at_ns.gt(at).or(dsl::sent_at_ns
+                        .eq(at)
+                        .and(dsl::id.gt(cursor.message_id.as_slice()))),
+                ),
+                SortDirection::Descending => query.filter(
+                    dsl::sent_at_ns.lt(at).or(dsl::sent_at_ns
+                        .eq(at)
+                        .and(dsl::id.lt(cursor.message_id.as_slice()))),
+                ),
+            };
+        }
+
+        if let Some(cursor) = &args.before_cursor {
+            let at = cursor.sent_at_ns;
+            query = query.filter(
+                dsl::sent_at_ns.lt(at).or(dsl::sent_at_ns
+                    .eq(at)
+                    .and(dsl::id.lt(cursor.message_id.as_slice()))),
+            );
+        }
+        if let Some(cursor) = &args.after_cursor {
+            let at = cursor.sent_at_ns;
+            query = query.filter(
+                dsl::sent_at_ns.gt(at).or(dsl::sent_at_ns
+                    .eq(at)
+                    .and(dsl::id.gt(cursor.message_id.as_slice()))),
+            );
+        }
+
+        query = match direction {
+            SortDirection::Ascending => query.order((dsl::sent_at_ns.asc(), dsl::id.asc())),
+            SortDirection::Descending => query.order((dsl::sent_at_ns.desc(), dsl::id.desc())),
+        };
+
+        if let Some(limit) = args.limit {
+            query = query.limit(limit);
+        }
+
+        query
+    }
+
+    /// The messages of `group_id` matching `args`. With `visible_only`, the messages of blocked
+    /// inboxes that are hidden are skipped.
+    fn group_messages_filter<'a>(
+        group_id: &'a [u8],
+        args: &'a MsgQueryArgs,
+        visible_only: bool,
+    ) -> group_messages::BoxedQuery<'a, Sqlite> {
         // Get all messages that have a group with an id equal the provided id,
         // or a dm_id equal to the dm_id that belongs to the loaded group with the provided id.
         let mut query = dsl::group_messages
@@ -281,16 +505,11 @@ impl DbConnection {
             query = query.filter(dsl::content_type.eq_any(content_types));
         }
 
-        query = match args.direction.as_ref().unwrap_or(&SortDirection::Ascending) {
-            SortDirection::Ascending => query.order(dsl::sent_at_ns.asc()),
-            SortDirection::Descending => query.order(dsl::sent_at_ns.desc()),
-        };
-
-        if let Some(limit) = args.limit {
-            query = query.limit(limit);
+        if visible_only {
+            query = query.filter(dsl::sender_inbox_id.ne_all(Self::hidden_senders()));
         }
 
-        Ok(self.raw_query(|conn| query.load::<StoredGroupMessage>(conn))?)
+        query
     }
 
     /// Query for group messages with their reactions
@@ -299,6 +518,24 @@ impl DbConnection {
         &self,
         group_id: &[u8],
         args: &MsgQueryArgs,
+    ) -> Result<Vec<StoredGroupMessageWithReactions>, StorageError> {
+        self.group_messages_with_reactions(group_id, args, false)
+    }
+
+    /// Like [`Self::get_group_messages_with_reactions`], skipping the messages of hidden senders
+    pub(crate) fn get_visible_group_messages_with_reactions(
+        &self,
+        group_id: &[u8],
+        args: &MsgQueryArgs,
+    ) -> Result<Vec<StoredGroupMessageWithReactions>, StorageError> {
+        self.group_messages_with_reactions(group_id, args, true)
+    }
+
+    fn group_messages_with_reactions(
+        &self,
+        group_id: &[u8],
+        args: &MsgQueryArgs,
+        visible_only: bool,
     ) -> Result<Vec<StoredGroupMessageWithReactions>, StorageError> {
         // First get all the main messages
         let mut modified_args = args.clone();
@@ -323,13 +560,15 @@ impl DbConnection {
         };
 
         modified_args.content_types = content_types;
-        let messages = self.get_group_messages(group_id, &modified_args)?;
+        let query = Self::group_messages_query(group_id, &modified_args, visible_only);
+        let messages: Vec<StoredGroupMessage> = self.raw_query(|conn| query.load(conn))?;
 
         // Then get all reactions for these messages in a single query
         let message_ids: Vec<&[u8]> = messages.iter().map(|m| m.id.as_slice()).collect();
 
         let mut reactions_query = dsl::group_messages
             .filter(dsl::group_id.eq(group_id))
+            .filter(dsl::content_type.eq(ContentType::Reaction))
             .filter(dsl::reference_id.is_not_null())
             .filter(dsl::reference_id.eq_any(message_ids))
             .into_boxed();
@@ -372,6 +611,19 @@ impl DbConnection {
         Ok(messages_with_reactions)
     }
 
+    /// Query for the replies to `parent_id`
+    pub fn get_replies(
+        &self,
+        group_id: &[u8],
+        parent_id: &[u8],
+        args: &MsgQueryArgs,
+    ) -> Result<Vec<StoredGroupMessage>, StorageError> {
+        let query = Self::group_messages_query(group_id, args, false)
+            .filter(dsl::content_type.eq(ContentType::Reply))
+            .filter(dsl::reference_id.eq(parent_id));
+        Ok(self.raw_query(|conn| query.load::<StoredGroupMessage>(conn))?)
+    }
+
     /// Get a particular group message
     pub fn get_group_message<MessageId: AsRef<[u8]>>(
         &self,
@@ -385,6 +637,53 @@ impl DbConnection {
         })?)
     }
 
+    /// The inboxes other than `own_inbox_id` that sent the most application messages at or
+    /// after `since_ns`, most active first
+    pub fn get_frequent_senders(
+        &self,
+        own_inbox_id: &str,
+        since_ns: i64,
+        limit: usize,
+    ) -> Result<Vec<String>, StorageError> {
+        let mut counts: Vec<(String, i64)> = self.raw_query(|conn| {
+            dsl::group_messages
+                .filter(dsl::kind.eq(GroupMessageKind::Application))
+                .filter(dsl::sent_at_ns.ge(since_ns))
+                .filter(dsl::sender_inbox_id.ne(own_inbox_id))
+                .group_by(dsl::sender_inbox_id)
+                .select((dsl::sender_inbox_id, diesel::dsl::count_star()))
+                .load(conn)
+        })?;
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(counts
+            .into_iter()
+            .take(limit)
+            .map(|(inbox_id, _)| inbox_id)
+            .collect())
+    }
+
+    /// When each of `installation_ids` last sent a message or commit received here, in any
+    /// group. Installations that never did are left out.
+    pub fn installations_last_active_ns(
+        &self,
+        installation_ids: &[Vec<u8>],
+    ) -> Result<HashMap<Vec<u8>, i64>, StorageError> {
+        let last_active: Vec<(Vec<u8>, Option<i64>)> = self.raw_query(|conn| {
+            dsl::group_messages
+                .filter(dsl::sender_installation_id.eq_any(installation_ids))
+                .group_by(dsl::sender_installation_id)
+                .select((
+                    dsl::sender_installation_id,
+                    diesel::dsl::max(dsl::sent_at_ns),
+                ))
+                .load(conn)
+        })?;
+        Ok(last_active
+            .into_iter()
+            .filter_map(|(installation_id, sent_at_ns)| Some((installation_id, sent_at_ns?)))
+            .collect())
+    }
+
     pub fn get_group_message_by_timestamp<GroupId: AsRef<[u8]>>(
         &self,
         group_id: GroupId,
@@ -399,6 +698,18 @@ impl DbConnection {
         })?)
     }
 
+    /// When the latest message of a group was sent, if it has any
+    pub fn latest_message_sent_at_ns<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<Option<i64>, StorageError> {
+        let query = dsl::group_messages
+            .filter(dsl::group_id.eq(group_id.as_ref()))
+            .select(diesel::dsl::max(dsl::sent_at_ns));
+
+        Ok(self.raw_query(|conn| query.first(conn))?)
+    }
+
     pub fn set_delivery_status_to_published<MessageId: AsRef<[u8]>>(
         &self,
         msg_id: &MessageId,
@@ -426,6 +737,86 @@ impl DbConnection {
                 .execute(conn)
         })?)
     }
+
+    /// The groups with messages from this installation that haven't been published yet
+    pub fn groups_with_unpublished_messages(&self) -> Result<Vec<Vec<u8>>, StorageError> {
+        let query = dsl::group_messages
+            .filter(dsl::delivery_status.eq(DeliveryStatus::Unpublished))
+            .select(dsl::group_id)
+            .distinct();
+
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// Mark a message as deleted, erasing its content, its edit history and the content of the
+    /// edit messages that changed it. Returns false if the message doesn't exist or was already
+    /// deleted.
+    pub fn mark_message_deleted<MessageId: AsRef<[u8]>>(
+        &self,
+        msg_id: &MessageId,
+        deleted_at_ns: i64,
+    ) -> Result<bool, StorageError> {
+        let updated = self.raw_query(|conn| {
+            conn.transaction::<_, diesel::result::Error, _>(|conn| {
+                let edit_message_ids: Vec<Vec<u8>> = message_edits::table
+                    .filter(message_edits::message_id.eq(msg_id.as_ref()))
+                    .select(message_edits::edit_message_id)
+                    .load(conn)?;
+                diesel::delete(
+                    message_edits::table.filter(message_edits::message_id.eq(msg_id.as_ref())),
+                )
+                .execute(conn)?;
+                let updated = diesel::update(dsl::group_messages)
+                    .filter(dsl::id.eq(msg_id.as_ref()))
+                    .filter(dsl::deleted_at_ns.is_null())
+                    .set((
+                        dsl::decrypted_message_bytes.eq(Vec::<u8>::new()),
+                        dsl::deleted_at_ns.eq(deleted_at_ns),
+                    ))
+                    .execute(conn)?;
+                diesel::update(dsl::group_messages)
+                    .filter(dsl::id.eq_any(&edit_message_ids))
+                    .filter(dsl::deleted_at_ns.is_null())
+                    .set((
+                        dsl::decrypted_message_bytes.eq(Vec::<u8>::new()),
+                        dsl::deleted_at_ns.eq(deleted_at_ns),
+                    ))
+                    .execute(conn)?;
+                Ok(updated)
+            })
+        })?;
+        Ok(updated > 0)
+    }
+
+    /// Delete the application messages of a group sent at or after `from_ns` and before
+    /// `sent_before_ns`, along with their edit history and reactions.
+    /// Returns the number of messages deleted.
+    pub fn delete_expired_messages<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        from_ns: i64,
+        sent_before_ns: i64,
+    ) -> Result<usize, StorageError> {
+        let expired = dsl::group_messages
+            .filter(dsl::group_id.eq(group_id.as_ref()))
+            .filter(dsl::kind.eq(GroupMessageKind::Application))
+            .filter(dsl::sent_at_ns.ge(from_ns))
+            .filter(dsl::sent_at_ns.lt(sent_before_ns));
+
+        Ok(self.raw_query(|conn| {
+            let ids: Vec<Vec<u8>> = expired.select(dsl::id).load(conn)?;
+            if ids.is_empty() {
+                return Ok(0);
+            }
+            diesel::delete(message_edits::table.filter(message_edits::message_id.eq_any(&ids)))
+                .execute(conn)?;
+            diesel::delete(
+                message_reactions::table.filter(message_reactions::message_id.eq_any(&ids)),
+            )
+            .execute(conn)?;
+            diesel::delete(dsl::group_messages.filter(dsl::id.eq_any(&ids))).execute(conn)
+        })?)
+    }
 }
 
 #[cfg(test)]
@@ -461,6 +852,7 @@ pub(crate) mod tests {
             version_minor: 0,
             authority_id: "unknown".to_string(),
             reference_id: None,
+            deleted_at_ns: None,
         }
     }
 
@@ -644,6 +1036,39 @@ pub(crate) mod tests {
         .await
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_gets_frequent_senders() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+
+            let senders = ["me", "bo", "bo", "alix", "bo", "caro", "caro"];
+            for (i, sender) in senders.into_iter().enumerate() {
+                let mut msg = generate_message(
+                    Some(GroupMessageKind::Application),
+                    Some(&group.id),
+                    Some(1_000 + i as i64),
+                    Some(ContentType::Text),
+                );
+                msg.sender_inbox_id = sender.to_string();
+                msg.store(conn).unwrap();
+            }
+            // too old to count
+            let mut msg = generate_message(
+                Some(GroupMessageKind::Application),
+                Some(&group.id),
+                Some(10),
+                Some(ContentType::Text),
+            );
+            msg.sender_inbox_id = "alix".to_string();
+            msg.store(conn).unwrap();
+
+            let frequent = conn.get_frequent_senders("me", 1_000, 2).unwrap();
+            assert_eq!(frequent, vec!["bo".to_string(), "caro".to_string()]);
+        })
+        .await
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test)]
     async fn it_orders_messages_by_sent() {
         with_connection(|conn| {
@@ -762,4 +1187,90 @@ pub(crate) mod tests {
         })
         .await
     }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_gets_message_headers() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+
+            let messages = vec![
+                generate_message(None, Some(&group.id), Some(1_000), Some(ContentType::Text)),
+                generate_message(None, Some(&group.id), Some(2_000), Some(ContentType::Text)),
+                generate_message(None, Some(&group.id), Some(3_000), None),
+            ];
+            assert_ok!(messages.store(conn));
+
+            let args = MsgQueryArgs {
+                content_types: Some(vec![ContentType::Text]),
+                direction: Some(SortDirection::Descending),
+                ..Default::default()
+            };
+            let headers = conn.get_group_message_headers(&group.id, &args).unwrap();
+            let expected: Vec<StoredGroupMessageHeader> = conn
+                .get_group_messages(&group.id, &args)
+                .unwrap()
+                .iter()
+                .map(Into::into)
+                .collect();
+            assert_eq!(headers, expected);
+            assert_eq!(headers[0].sent_at_ns, 2_000);
+        })
+        .await
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_marks_messages_deleted() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let message = generate_message(None, Some(&group.id), Some(1_000), None);
+            message.store(conn).unwrap();
+
+            assert!(conn.mark_message_deleted(&message.id, 2_000).unwrap());
+            assert!(!conn.mark_message_deleted(&message.id, 3_000).unwrap());
+            assert!(!conn.mark_message_deleted(&vec![0], 3_000).unwrap());
+
+            let deleted = conn.get_group_message(&message.id).unwrap().unwrap();
+            assert_eq!(deleted.deleted_at_ns, Some(2_000));
+            assert!(deleted.decrypted_message_bytes.is_empty());
+        })
+        .await
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_deletes_expired_messages() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let messages = vec![
+                generate_message(None, Some(&group.id), Some(1_000), None),
+                generate_message(None, Some(&group.id), Some(2_000), None),
+                generate_message(None, Some(&group.id), Some(3_000), None),
+                generate_message(
+                    Some(GroupMessageKind::MembershipChange),
+                    Some(&group.id),
+                    Some(2_000),
+                    None,
+                ),
+            ];
+            assert_ok!(messages.store(conn));
+
+            // sent before the settings applied, expired, not yet expired, not an application message
+            assert_eq!(
+                conn.delete_expired_messages(&group.id, 1_500, 3_000)
+                    .unwrap(),
+                1
+            );
+            let remaining: Vec<i64> = conn
+                .get_group_messages(&group.id, &MsgQueryArgs::default())
+                .unwrap()
+                .iter()
+                .map(|message| message.sent_at_ns)
+                .collect();
+            assert_eq!(remaining, [1_000, 2_000, 3_000]);
+            assert!(conn.get_group_message(&messages[1].id).unwrap().is_none());
+        })
+        .await
+    }
 }
@@ -0,0 +1,89 @@
+//! Groups restored from an archive, along with the state the group was in when it was exported.
+//! This installation usually isn't a member of these groups, so the archive is the only source
+//! for their metadata and members.
+use super::{
+    db_connection::DbConnection,
+    schema::imported_archives::{self, dsl},
+};
+use crate::storage::StorageError;
+use diesel::prelude::*;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = imported_archives)]
+#[diesel(primary_key(group_id))]
+pub struct StoredImportedArchive {
+    pub group_id: Vec<u8>,
+    /// JSON encoded metadata, admins and members of the group when it was exported
+    pub metadata: Vec<u8>,
+    pub exported_at_ns: i64,
+    pub imported_at_ns: i64,
+}
+
+impl DbConnection {
+    pub fn get_imported_archive<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<Option<StoredImportedArchive>, StorageError> {
+        let query = dsl::imported_archives.find(group_id.as_ref());
+
+        Ok(self.raw_query(|conn| query.first(conn).optional())?)
+    }
+
+    /// Store `archive`, unless an archive exported later was already imported for the group.
+    /// Returns whether it was stored.
+    pub fn set_imported_archive(
+        &self,
+        archive: &StoredImportedArchive,
+    ) -> Result<bool, StorageError> {
+        Ok(self.raw_query(|conn| -> diesel::QueryResult<bool> {
+            let current: Option<i64> = dsl::imported_archives
+                .find(&archive.group_id)
+                .select(dsl::exported_at_ns)
+                .first(conn)
+                .optional()?;
+            if current.is_some_and(|exported_at_ns| exported_at_ns > archive.exported_at_ns) {
+                return Ok(false);
+            }
+            diesel::replace_into(dsl::imported_archives)
+                .values(archive)
+                .execute(conn)?;
+            Ok(true)
+        })?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_keeps_the_latest_export() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let archive = |exported_at_ns: i64| StoredImportedArchive {
+                group_id: group.id.clone(),
+                metadata: exported_at_ns.to_string().into_bytes(),
+                exported_at_ns,
+                imported_at_ns: 1_000,
+            };
+
+            assert!(conn.set_imported_archive(&archive(200)).unwrap());
+            assert!(!conn.set_imported_archive(&archive(100)).unwrap());
+            assert!(conn.set_imported_archive(&archive(300)).unwrap());
+            assert_eq!(
+                conn.get_imported_archive(&group.id).unwrap(),
+                Some(archive(300))
+            );
+        })
+        .await
+    }
+}
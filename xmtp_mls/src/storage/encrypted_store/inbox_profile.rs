@@ -0,0 +1,129 @@
+//! The most recent verified [`SignedProfile`] of each inbox.
+use super::{
+    db_connection::DbConnection,
+    schema::inbox_profiles::{self, dsl},
+};
+use crate::storage::StorageError;
+use diesel::prelude::*;
+use xmtp_content_types::profile::{ProfileClaims, SignedProfile};
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = inbox_profiles)]
+#[diesel(primary_key(inbox_id))]
+pub struct StoredInboxProfile {
+    pub inbox_id: String,
+    /// The installation that signed the profile
+    pub installation_id: Vec<u8>,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub ens_name: Option<String>,
+    pub signed_at_ns: i64,
+    pub signature: Vec<u8>,
+    /// `signed_at_ns`, but no later than the server timestamp of the message that carried the
+    /// profile. Profiles are ordered by it, since the signer's clock can't be trusted.
+    pub sent_at_ns: i64,
+}
+
+impl StoredInboxProfile {
+    /// `profile`, carried by a message the server received at `sent_at_ns`
+    pub fn new(profile: SignedProfile, sent_at_ns: i64) -> Self {
+        Self {
+            sent_at_ns: profile.signed_at_ns.min(sent_at_ns),
+            inbox_id: profile.inbox_id,
+            installation_id: profile.installation_id,
+            display_name: profile.claims.display_name,
+            avatar_url: profile.claims.avatar_url,
+            ens_name: profile.claims.ens_name,
+            signed_at_ns: profile.signed_at_ns,
+            signature: profile.signature,
+        }
+    }
+}
+
+impl From<StoredInboxProfile> for SignedProfile {
+    fn from(profile: StoredInboxProfile) -> Self {
+        Self {
+            inbox_id: profile.inbox_id,
+            installation_id: profile.installation_id,
+            claims: ProfileClaims {
+                display_name: profile.display_name,
+                avatar_url: profile.avatar_url,
+                ens_name: profile.ens_name,
+            },
+            signed_at_ns: profile.signed_at_ns,
+            signature: profile.signature,
+        }
+    }
+}
+
+impl DbConnection {
+    /// Store a profile, unless a newer one for the same inbox is already stored.
+    /// Returns whether the stored profile changed.
+    pub fn upsert_inbox_profile(&self, profile: &StoredInboxProfile) -> Result<bool, StorageError> {
+        let existing = self.get_inbox_profile(&profile.inbox_id)?;
+        if existing.is_some_and(|e| e.sent_at_ns >= profile.sent_at_ns) {
+            return Ok(false);
+        }
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::inbox_profiles)
+                .values(profile)
+                .execute(conn)
+        })?;
+        Ok(true)
+    }
+
+    pub fn get_inbox_profile(
+        &self,
+        inbox_id: &str,
+    ) -> Result<Option<StoredInboxProfile>, StorageError> {
+        Ok(self.raw_query(|conn| dsl::inbox_profiles.find(inbox_id).first(conn).optional())?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::tests::with_connection;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn profile(display_name: &str, signed_at_ns: i64, sent_at_ns: i64) -> StoredInboxProfile {
+        StoredInboxProfile::new(
+            SignedProfile {
+                inbox_id: "alix".to_string(),
+                installation_id: vec![1],
+                claims: ProfileClaims {
+                    display_name: Some(display_name.to_string()),
+                    ..Default::default()
+                },
+                signed_at_ns,
+                signature: vec![2],
+            },
+            sent_at_ns,
+        )
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_keeps_the_newest_profile() {
+        with_connection(|conn| {
+            assert!(conn.upsert_inbox_profile(&profile("Alix", 10, 10)).unwrap());
+            assert!(!conn
+                .upsert_inbox_profile(&profile("Old Alix", 5, 5))
+                .unwrap());
+            // a profile claiming to be signed in the future counts from when it was sent
+            assert!(!conn
+                .upsert_inbox_profile(&profile("Future Alix", 1000, 8))
+                .unwrap());
+            assert!(conn
+                .upsert_inbox_profile(&profile("New Alix", 20, 20))
+                .unwrap());
+
+            let stored = conn.get_inbox_profile("alix").unwrap().unwrap();
+            assert_eq!(stored.display_name.as_deref(), Some("New Alix"));
+            assert!(conn.get_inbox_profile("bo").unwrap().is_none());
+        })
+        .await
+    }
+}
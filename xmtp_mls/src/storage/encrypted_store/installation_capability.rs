@@ -0,0 +1,157 @@
+//! The most recent [`Capabilities`] advertised by each installation we share a conversation with.
+use super::{
+    db_connection::DbConnection,
+    schema::installation_capabilities::{self, dsl},
+};
+use crate::storage::StorageError;
+use diesel::prelude::*;
+use std::collections::HashMap;
+use xmtp_content_types::capabilities::Capabilities;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = installation_capabilities)]
+#[diesel(primary_key(installation_id))]
+pub struct StoredInstallationCapabilities {
+    pub installation_id: Vec<u8>,
+    pub inbox_id: String,
+    /// JSON array of content type ids
+    pub content_types: String,
+    /// JSON array of feature names
+    pub features: String,
+    pub advertised_at_ns: i64,
+}
+
+impl StoredInstallationCapabilities {
+    pub fn new(
+        installation_id: Vec<u8>,
+        inbox_id: String,
+        capabilities: &Capabilities,
+        advertised_at_ns: i64,
+    ) -> Result<Self, StorageError> {
+        let to_json = |v: &Vec<String>| {
+            serde_json::to_string(v).map_err(|e| StorageError::Serialization(e.to_string()))
+        };
+        Ok(Self {
+            installation_id,
+            inbox_id,
+            content_types: to_json(&capabilities.content_types)?,
+            features: to_json(&capabilities.features)?,
+            advertised_at_ns,
+        })
+    }
+
+    pub fn capabilities(&self) -> Result<Capabilities, StorageError> {
+        let from_json = |s: &str| {
+            serde_json::from_str(s).map_err(|e| StorageError::Deserialization(e.to_string()))
+        };
+        Ok(Capabilities {
+            content_types: from_json(&self.content_types)?,
+            features: from_json(&self.features)?,
+        })
+    }
+}
+
+impl DbConnection {
+    /// Store an advertisement, unless a newer one from the same installation is already stored.
+    /// Returns whether the stored capabilities changed.
+    pub fn upsert_installation_capabilities(
+        &self,
+        record: &StoredInstallationCapabilities,
+    ) -> Result<bool, StorageError> {
+        let existing = self.get_installation_capabilities(&record.installation_id)?;
+        if existing.is_some_and(|e| e.advertised_at_ns >= record.advertised_at_ns) {
+            return Ok(false);
+        }
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::installation_capabilities)
+                .values(record)
+                .execute(conn)
+        })?;
+        Ok(true)
+    }
+
+    pub fn get_installation_capabilities<InstallationId: AsRef<[u8]>>(
+        &self,
+        installation_id: InstallationId,
+    ) -> Result<Option<StoredInstallationCapabilities>, StorageError> {
+        Ok(self.raw_query(|conn| {
+            dsl::installation_capabilities
+                .find(installation_id.as_ref())
+                .first(conn)
+                .optional()
+        })?)
+    }
+
+    /// Capabilities for many installations at once, keyed by installation id.
+    /// Installations that never advertised are absent from the map.
+    pub fn get_installation_capabilities_for(
+        &self,
+        installation_ids: &[Vec<u8>],
+    ) -> Result<HashMap<Vec<u8>, StoredInstallationCapabilities>, StorageError> {
+        let query =
+            dsl::installation_capabilities.filter(dsl::installation_id.eq_any(installation_ids));
+        let records: Vec<StoredInstallationCapabilities> =
+            self.raw_query(|conn| query.load(conn))?;
+
+        Ok(records
+            .into_iter()
+            .map(|r| (r.installation_id.clone(), r))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::tests::with_connection;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_common::rand_vec;
+
+    fn advertisement(
+        installation_id: &[u8],
+        feature: &str,
+        at: i64,
+    ) -> StoredInstallationCapabilities {
+        let capabilities = Capabilities {
+            content_types: vec!["xmtp.org/text:1.0".to_string()],
+            features: vec![feature.to_string()],
+        };
+        StoredInstallationCapabilities::new(
+            installation_id.to_vec(),
+            "inbox".into(),
+            &capabilities,
+            at,
+        )
+        .unwrap()
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_keeps_the_newest_advertisement() {
+        with_connection(|conn| {
+            let installation_id = rand_vec::<32>();
+
+            assert!(conn
+                .upsert_installation_capabilities(&advertisement(&installation_id, "polls", 2))
+                .unwrap());
+            // An older advertisement delivered late is ignored
+            assert!(!conn
+                .upsert_installation_capabilities(&advertisement(&installation_id, "editing", 1))
+                .unwrap());
+
+            let stored = conn
+                .get_installation_capabilities(&installation_id)
+                .unwrap()
+                .unwrap();
+            assert_eq!(stored.capabilities().unwrap().features, vec!["polls"]);
+
+            let by_installation = conn
+                .get_installation_capabilities_for(&[installation_id.clone(), rand_vec::<32>()])
+                .unwrap();
+            assert_eq!(by_installation.len(), 1);
+        })
+        .await
+    }
+}
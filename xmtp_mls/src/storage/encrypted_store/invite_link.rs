@@ -0,0 +1,212 @@
+//! Invite links created by the user and the redemptions received for them. Links are synced to
+//! the user's other installations, which merge them: a link counts the most uses any
+//! installation saw, and stays revoked once any installation revoked it.
+use super::{
+    db_connection::DbConnection,
+    schema::{
+        invite_links::{self, dsl},
+        invite_redemptions::{self, dsl as redemptions_dsl},
+    },
+};
+use crate::{impl_fetch, impl_store, impl_store_or_ignore, storage::StorageError};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[diesel(table_name = invite_links)]
+#[diesel(primary_key(id))]
+pub struct StoredInviteLink {
+    pub id: Vec<u8>,
+    pub group_id: Vec<u8>,
+    pub created_at_ns: i64,
+    /// The link can't be redeemed from this time on
+    pub expires_at_ns: Option<i64>,
+    /// How many inboxes can join through the link, unlimited if unset
+    pub max_uses: Option<i32>,
+    /// How many inboxes joined through the link
+    pub uses: i32,
+    pub revoked_at_ns: Option<i64>,
+}
+
+impl_fetch!(StoredInviteLink, invite_links, Vec<u8>);
+impl_store!(StoredInviteLink, invite_links);
+
+impl StoredInviteLink {
+    /// Whether another inbox can join through the link at `now_ns`
+    pub fn is_redeemable(&self, now_ns: i64) -> bool {
+        self.revoked_at_ns.is_none()
+            && self
+                .expires_at_ns
+                .is_none_or(|expires_at_ns| now_ns < expires_at_ns)
+            && self.max_uses.is_none_or(|max_uses| self.uses < max_uses)
+    }
+}
+
+/// A request to join through an invite link, waiting for the sender to be added
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = invite_redemptions)]
+#[diesel(primary_key(message_id))]
+pub struct StoredInviteRedemption {
+    /// The message carrying the redemption
+    pub message_id: Vec<u8>,
+    pub link_id: Vec<u8>,
+    pub inbox_id: String,
+    pub received_at_ns: i64,
+}
+
+impl_store_or_ignore!(StoredInviteRedemption, invite_redemptions);
+
+impl DbConnection {
+    /// The invite links of `group_id`, newest first
+    pub fn get_invite_links<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<Vec<StoredInviteLink>, StorageError> {
+        let query = dsl::invite_links
+            .filter(dsl::group_id.eq(group_id.as_ref()))
+            .order(dsl::created_at_ns.desc());
+
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// Revoke `link_id`. Returns whether the link existed and wasn't revoked yet.
+    pub fn revoke_invite_link(&self, link_id: &[u8], now_ns: i64) -> Result<bool, StorageError> {
+        let updated = self.raw_query(|conn| {
+            diesel::update(dsl::invite_links.find(link_id))
+                .filter(dsl::revoked_at_ns.is_null())
+                .set(dsl::revoked_at_ns.eq(now_ns))
+                .execute(conn)
+        })?;
+        Ok(updated > 0)
+    }
+
+    /// Count an inbox joining through `link_id`
+    pub fn record_invite_link_use(&self, link_id: &[u8]) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::update(dsl::invite_links.find(link_id))
+                .set(dsl::uses.eq(dsl::uses + 1))
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// Merge `link` as received from another installation. Returns whether it changed the
+    /// stored link.
+    pub fn merge_invite_link(&self, link: &StoredInviteLink) -> Result<bool, StorageError> {
+        Ok(self.raw_query(|conn| {
+            conn.transaction::<_, diesel::result::Error, _>(|conn| {
+                let existing: Option<StoredInviteLink> =
+                    dsl::invite_links.find(&link.id).first(conn).optional()?;
+                let Some(existing) = existing else {
+                    diesel::insert_into(dsl::invite_links)
+                        .values(link)
+                        .execute(conn)?;
+                    return Ok(true);
+                };
+                let merged = StoredInviteLink {
+                    uses: existing.uses.max(link.uses),
+                    revoked_at_ns: match (existing.revoked_at_ns, link.revoked_at_ns) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    },
+                    ..existing.clone()
+                };
+                if merged == existing {
+                    return Ok(false);
+                }
+                diesel::update(dsl::invite_links.find(&link.id))
+                    .set((
+                        dsl::uses.eq(merged.uses),
+                        dsl::revoked_at_ns.eq(merged.revoked_at_ns),
+                    ))
+                    .execute(conn)?;
+                Ok(true)
+            })
+        })?)
+    }
+
+    /// Every redemption waiting to be admitted, oldest first
+    pub fn get_pending_invite_redemptions(
+        &self,
+    ) -> Result<Vec<StoredInviteRedemption>, StorageError> {
+        let query =
+            redemptions_dsl::invite_redemptions.order(redemptions_dsl::received_at_ns.asc());
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    pub fn delete_invite_redemption(&self, message_id: &[u8]) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::delete(redemptions_dsl::invite_redemptions.find(message_id)).execute(conn)
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Fetch, Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_tracks_invite_link_uses() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let link = StoredInviteLink {
+                id: vec![1, 2, 3],
+                group_id: group.id.clone(),
+                created_at_ns: 0,
+                expires_at_ns: Some(100),
+                max_uses: Some(1),
+                uses: 0,
+                revoked_at_ns: None,
+            };
+            link.store(conn).unwrap();
+            assert!(link.is_redeemable(50));
+            assert!(!link.is_redeemable(100));
+
+            conn.record_invite_link_use(&link.id).unwrap();
+            let used: StoredInviteLink = conn.fetch(&link.id).unwrap().unwrap();
+            assert_eq!(used.uses, 1);
+            assert!(!used.is_redeemable(50));
+
+            assert!(conn.revoke_invite_link(&link.id, 10).unwrap());
+            assert!(!conn.revoke_invite_link(&link.id, 20).unwrap());
+            assert_eq!(conn.get_invite_links(&group.id).unwrap().len(), 1);
+
+            // another installation's copy only adds uses and revocations
+            let stale = StoredInviteLink {
+                uses: 0,
+                revoked_at_ns: Some(30),
+                ..link.clone()
+            };
+            assert!(!conn.merge_invite_link(&stale).unwrap());
+            let newer = StoredInviteLink {
+                uses: 3,
+                revoked_at_ns: Some(5),
+                ..link.clone()
+            };
+            assert!(conn.merge_invite_link(&newer).unwrap());
+            let merged: StoredInviteLink = conn.fetch(&link.id).unwrap().unwrap();
+            assert_eq!(merged.uses, 3);
+            assert_eq!(merged.revoked_at_ns, Some(5));
+
+            let unknown = StoredInviteLink {
+                id: vec![4, 5, 6],
+                ..link
+            };
+            assert!(conn.merge_invite_link(&unknown).unwrap());
+            assert_eq!(conn.get_invite_links(&group.id).unwrap().len(), 2);
+        })
+        .await
+    }
+}
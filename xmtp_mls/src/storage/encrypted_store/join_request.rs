@@ -0,0 +1,107 @@
+//! Requests from non-members to join a group, received by the admins they were sent to. A
+//! request stays pending until an admin approves or rejects it.
+use super::{
+    db_connection::DbConnection,
+    schema::join_requests::{self, dsl},
+};
+use crate::{impl_fetch, impl_store_or_ignore, storage::StorageError};
+use diesel::prelude::*;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = join_requests)]
+#[diesel(primary_key(message_id))]
+pub struct StoredJoinRequest {
+    /// The message carrying the request
+    pub message_id: Vec<u8>,
+    pub group_id: Vec<u8>,
+    /// The inbox asking to join
+    pub inbox_id: String,
+    pub note: Option<String>,
+    pub requested_at_ns: i64,
+    /// The inbox is a member whose copy of the group forked, and asks to be added back
+    pub rejoin: bool,
+}
+
+impl_fetch!(StoredJoinRequest, join_requests, Vec<u8>);
+impl_store_or_ignore!(StoredJoinRequest, join_requests);
+
+impl DbConnection {
+    /// The pending requests to join `group_id`, oldest first
+    pub fn get_join_requests<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<Vec<StoredJoinRequest>, StorageError> {
+        let query = dsl::join_requests
+            .filter(dsl::group_id.eq(group_id.as_ref()))
+            .order(dsl::requested_at_ns.asc());
+
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// The pending rejoin requests to every group, oldest first
+    pub fn get_rejoin_requests(&self) -> Result<Vec<StoredJoinRequest>, StorageError> {
+        let query = dsl::join_requests
+            .filter(dsl::rejoin.eq(true))
+            .order(dsl::requested_at_ns.asc());
+
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// Drop every pending request from `inbox_id` to join `group_id`, once it's been decided
+    pub fn delete_join_requests<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        inbox_id: &str,
+    ) -> Result<usize, StorageError> {
+        Ok(self.raw_query(|conn| {
+            diesel::delete(
+                dsl::join_requests
+                    .filter(dsl::group_id.eq(group_id.as_ref()))
+                    .filter(dsl::inbox_id.eq(inbox_id)),
+            )
+            .execute(conn)
+        })?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Fetch, Store, StoreOrIgnore,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_deletes_decided_join_requests() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            for (i, inbox_id) in ["bo", "bo", "caro"].into_iter().enumerate() {
+                StoredJoinRequest {
+                    message_id: vec![i as u8],
+                    group_id: group.id.clone(),
+                    inbox_id: inbox_id.to_string(),
+                    note: None,
+                    requested_at_ns: i as i64,
+                    rejoin: false,
+                }
+                .store_or_ignore(conn)
+                .unwrap();
+            }
+            assert_eq!(conn.get_join_requests(&group.id).unwrap().len(), 3);
+
+            assert_eq!(conn.delete_join_requests(&group.id, "bo").unwrap(), 2);
+            let pending = conn.get_join_requests(&group.id).unwrap();
+            assert_eq!(pending.len(), 1);
+            assert_eq!(pending[0].inbox_id, "caro");
+            let fetched: Option<StoredJoinRequest> = conn.fetch(&vec![0]).unwrap();
+            assert!(fetched.is_none());
+        })
+        .await
+    }
+}
@@ -0,0 +1,199 @@
+//! Aggregations over a conversation's stored messages, computed in SQL so that activity
+//! statistics don't require loading every message.
+use diesel::{
+    prelude::*,
+    sql_query,
+    sql_types::{BigInt, Binary, Integer, Text},
+};
+
+use super::{
+    db_connection::DbConnection,
+    group_message::{ContentType, GroupMessageKind},
+};
+use crate::{
+    configuration::{NS_IN_DAY, NS_IN_HOUR},
+    storage::StorageError,
+};
+
+/// Application messages a member sent on one UTC day
+#[derive(QueryableByName, Debug, Clone, PartialEq, Eq)]
+pub struct MemberDayCount {
+    #[diesel(sql_type = Text)]
+    pub sender_inbox_id: String,
+    /// Start of the day, in ns since the epoch
+    #[diesel(sql_type = BigInt)]
+    pub day_start_ns: i64,
+    #[diesel(sql_type = BigInt)]
+    pub message_count: i64,
+}
+
+#[derive(QueryableByName)]
+struct HourCount {
+    #[diesel(sql_type = BigInt)]
+    hour: i64,
+    #[diesel(sql_type = BigInt)]
+    message_count: i64,
+}
+
+#[derive(QueryableByName)]
+struct AttachmentTotals {
+    #[diesel(sql_type = BigInt)]
+    attachment_count: i64,
+    #[diesel(sql_type = BigInt)]
+    attachment_bytes: i64,
+}
+
+/// Activity in a conversation since `since_ns`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageActivity {
+    pub since_ns: i64,
+    /// Ordered by day, then sender
+    pub member_days: Vec<MemberDayCount>,
+    /// Application messages sent during each UTC hour of the day
+    pub active_hours: [i64; 24],
+    /// Attachments and remote attachments sent
+    pub attachment_count: i64,
+    /// Size of the stored attachment messages. For remote attachments this is the size of
+    /// the reference, not of the remote payload.
+    pub attachment_bytes: i64,
+}
+
+impl DbConnection {
+    /// Aggregate the application messages of `group_id` sent at or after `since_ns`
+    pub fn get_message_activity<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        since_ns: i64,
+    ) -> Result<MessageActivity, StorageError> {
+        let group_id = group_id.as_ref();
+        let application = GroupMessageKind::Application as i32;
+
+        let member_days = self.raw_query(|conn| {
+            sql_query(
+                "SELECT sender_inbox_id, (sent_at_ns / ?) * ? AS day_start_ns, \
+                 COUNT(*) AS message_count \
+                 FROM group_messages \
+                 WHERE group_id = ? AND kind = ? AND sent_at_ns >= ? \
+                 GROUP BY sender_inbox_id, day_start_ns \
+                 ORDER BY day_start_ns, sender_inbox_id",
+            )
+            .bind::<BigInt, _>(NS_IN_DAY)
+            .bind::<BigInt, _>(NS_IN_DAY)
+            .bind::<Binary, _>(group_id)
+            .bind::<Integer, _>(application)
+            .bind::<BigInt, _>(since_ns)
+            .load::<MemberDayCount>(conn)
+        })?;
+
+        let hours = self.raw_query(|conn| {
+            sql_query(
+                "SELECT (sent_at_ns / ?) % 24 AS hour, COUNT(*) AS message_count \
+                 FROM group_messages \
+                 WHERE group_id = ? AND kind = ? AND sent_at_ns >= ? \
+                 GROUP BY hour",
+            )
+            .bind::<BigInt, _>(NS_IN_HOUR)
+            .bind::<Binary, _>(group_id)
+            .bind::<Integer, _>(application)
+            .bind::<BigInt, _>(since_ns)
+            .load::<HourCount>(conn)
+        })?;
+        let mut active_hours = [0; 24];
+        for HourCount {
+            hour,
+            message_count,
+        } in hours
+        {
+            active_hours[hour.rem_euclid(24) as usize] += message_count;
+        }
+
+        let totals = self.raw_query(|conn| {
+            sql_query(
+                "SELECT COUNT(*) AS attachment_count, \
+                 COALESCE(SUM(LENGTH(decrypted_message_bytes)), 0) AS attachment_bytes \
+                 FROM group_messages \
+                 WHERE group_id = ? AND kind = ? AND sent_at_ns >= ? \
+                 AND content_type IN (?, ?)",
+            )
+            .bind::<Binary, _>(group_id)
+            .bind::<Integer, _>(application)
+            .bind::<BigInt, _>(since_ns)
+            .bind::<Integer, _>(ContentType::Attachment as i32)
+            .bind::<Integer, _>(ContentType::RemoteAttachment as i32)
+            .get_result::<AttachmentTotals>(conn)
+        })?;
+
+        Ok(MessageActivity {
+            since_ns,
+            member_days,
+            active_hours,
+            attachment_count: totals.attachment_count,
+            attachment_bytes: totals.attachment_bytes,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::tests::generate_group, group_message::tests::generate_message,
+            tests::with_connection,
+        },
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_aggregates_message_activity() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+
+            let day = 10 * NS_IN_DAY;
+            let messages = [
+                ("alix", day + 9 * NS_IN_HOUR, ContentType::Text),
+                ("alix", day + 9 * NS_IN_HOUR + 1, ContentType::Attachment),
+                ("bo", day + 17 * NS_IN_HOUR, ContentType::Text),
+                ("bo", day + NS_IN_DAY + 9 * NS_IN_HOUR, ContentType::Text),
+                // before the window
+                ("bo", day - 1, ContentType::Text),
+            ];
+            for (sender, sent_at_ns, content_type) in messages {
+                let mut message =
+                    generate_message(None, Some(&group.id), Some(sent_at_ns), Some(content_type));
+                message.sender_inbox_id = sender.to_string();
+                message.store(conn).unwrap();
+            }
+            generate_message(
+                Some(GroupMessageKind::MembershipChange),
+                Some(&group.id),
+                Some(day),
+                None,
+            )
+            .store(conn)
+            .unwrap();
+
+            let activity = conn.get_message_activity(&group.id, day).unwrap();
+            let days: Vec<(&str, i64, i64)> = activity
+                .member_days
+                .iter()
+                .map(|d| (d.sender_inbox_id.as_str(), d.day_start_ns, d.message_count))
+                .collect();
+            assert_eq!(
+                days,
+                vec![("alix", day, 2), ("bo", day, 1), ("bo", day + NS_IN_DAY, 1)]
+            );
+            assert_eq!(activity.active_hours[9], 3);
+            assert_eq!(activity.active_hours[17], 1);
+            assert_eq!(activity.active_hours.iter().sum::<i64>(), 4);
+            assert_eq!(activity.attachment_count, 1);
+            assert_eq!(activity.attachment_bytes, 24);
+        })
+        .await
+    }
+}
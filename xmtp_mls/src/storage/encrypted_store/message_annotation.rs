@@ -0,0 +1,118 @@
+//! Derived data attached to a message by a [`MessagePostProcessor`](crate::groups::post_processors::MessagePostProcessor),
+//! such as a detected language or a reference to a translation.
+use super::{
+    db_connection::DbConnection,
+    schema::message_annotations::{self, dsl},
+};
+use crate::{storage::StorageError, Store};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Insertable, Queryable, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[diesel(table_name = message_annotations)]
+#[diesel(primary_key(message_id, processor_id, key))]
+pub struct StoredMessageAnnotation {
+    /// The message this annotation belongs to
+    pub message_id: Vec<u8>,
+    /// Id of the post-processor that produced the annotation
+    pub processor_id: String,
+    pub key: String,
+    pub value: String,
+    pub created_at_ns: i64,
+}
+
+impl Store<DbConnection> for StoredMessageAnnotation {
+    // An annotation is replaced when a processor runs again on the same message
+    fn store(&self, into: &DbConnection) -> Result<(), StorageError> {
+        into.raw_query(|conn| {
+            diesel::replace_into(dsl::message_annotations)
+                .values(self)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+}
+
+impl DbConnection {
+    pub fn get_message_annotations<MessageId: AsRef<[u8]>>(
+        &self,
+        message_id: MessageId,
+    ) -> Result<Vec<StoredMessageAnnotation>, StorageError> {
+        let query = dsl::message_annotations
+            .filter(dsl::message_id.eq(message_id.as_ref()))
+            .order((dsl::processor_id.asc(), dsl::key.asc()));
+
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// Annotations for many messages at once, keyed by message id
+    pub fn get_message_annotations_for(
+        &self,
+        message_ids: &[Vec<u8>],
+    ) -> Result<HashMap<Vec<u8>, Vec<StoredMessageAnnotation>>, StorageError> {
+        let query = dsl::message_annotations
+            .filter(dsl::message_id.eq_any(message_ids))
+            .order((dsl::processor_id.asc(), dsl::key.asc()));
+        let annotations: Vec<StoredMessageAnnotation> = self.raw_query(|conn| query.load(conn))?;
+
+        let mut by_message: HashMap<Vec<u8>, Vec<StoredMessageAnnotation>> = HashMap::new();
+        for annotation in annotations {
+            by_message
+                .entry(annotation.message_id.clone())
+                .or_default()
+                .push(annotation);
+        }
+        Ok(by_message)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::{
+        group::tests::generate_group, group_message::tests::generate_message,
+        tests::with_connection,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn annotation(message_id: &[u8], key: &str, value: &str) -> StoredMessageAnnotation {
+        StoredMessageAnnotation {
+            message_id: message_id.to_vec(),
+            processor_id: "language".to_string(),
+            key: key.to_string(),
+            value: value.to_string(),
+            created_at_ns: 0,
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_replaces_annotations() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let message = generate_message(None, Some(&group.id), None, None);
+            message.store(conn).unwrap();
+
+            annotation(&message.id, "lang", "en").store(conn).unwrap();
+            annotation(&message.id, "lang", "fr").store(conn).unwrap();
+            annotation(&message.id, "confidence", "0.9")
+                .store(conn)
+                .unwrap();
+
+            let annotations = conn.get_message_annotations(&message.id).unwrap();
+            assert_eq!(annotations.len(), 2);
+            assert_eq!(annotations[1].key, "lang");
+            assert_eq!(annotations[1].value, "fr");
+
+            let by_message = conn
+                .get_message_annotations_for(&[message.id.clone()])
+                .unwrap();
+            assert_eq!(by_message[&message.id].len(), 2);
+        })
+        .await
+    }
+}
@@ -0,0 +1,135 @@
+//! Earlier versions of edited messages. Applying an edit moves the message's content here
+//! before replacing it in `group_messages`, so the full edit history stays available.
+use super::{
+    db_connection::DbConnection,
+    group_message::StoredGroupMessage,
+    schema::{
+        group_messages::dsl as messages_dsl,
+        message_edits::{self, dsl},
+    },
+};
+use crate::storage::StorageError;
+use diesel::prelude::*;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = message_edits)]
+#[diesel(primary_key(message_id, version))]
+pub struct StoredMessageEdit {
+    pub message_id: Vec<u8>,
+    /// 0 for the content the message was sent with, counting up with each edit
+    pub version: i32,
+    pub group_id: Vec<u8>,
+    pub decrypted_message_bytes: Vec<u8>,
+    /// The edit that replaced this version
+    pub edit_message_id: Vec<u8>,
+    pub replaced_at_ns: i64,
+}
+
+impl DbConnection {
+    /// Earlier versions of `message_id`, oldest first
+    pub fn get_message_edits<MessageId: AsRef<[u8]>>(
+        &self,
+        message_id: MessageId,
+    ) -> Result<Vec<StoredMessageEdit>, StorageError> {
+        let query = dsl::message_edits
+            .filter(dsl::message_id.eq(message_id.as_ref()))
+            .order(dsl::version.asc());
+
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// Replace the content of `message` with that of `edited`, keeping the current content as
+    /// the next version in the edit history. Returns the version number the edit created.
+    pub fn apply_message_edit(
+        &self,
+        message: &StoredGroupMessage,
+        edited: &StoredGroupMessage,
+        edit_message_id: &[u8],
+        edited_at_ns: i64,
+    ) -> Result<i32, StorageError> {
+        let previous_versions = self.raw_query(|conn| {
+            dsl::message_edits
+                .filter(dsl::message_id.eq(message.id.as_slice()))
+                .count()
+                .get_result::<i64>(conn)
+        })?;
+        let previous = StoredMessageEdit {
+            message_id: message.id.clone(),
+            version: previous_versions as i32,
+            group_id: message.group_id.clone(),
+            decrypted_message_bytes: message.decrypted_message_bytes.clone(),
+            edit_message_id: edit_message_id.to_vec(),
+            replaced_at_ns: edited_at_ns,
+        };
+
+        self.raw_query(|conn| {
+            diesel::insert_into(dsl::message_edits)
+                .values(&previous)
+                .execute(conn)?;
+            diesel::update(messages_dsl::group_messages.find(message.id.as_slice()))
+                .set((
+                    messages_dsl::decrypted_message_bytes.eq(&edited.decrypted_message_bytes),
+                    messages_dsl::content_type.eq(edited.content_type),
+                    messages_dsl::version_major.eq(edited.version_major),
+                    messages_dsl::version_minor.eq(edited.version_minor),
+                    messages_dsl::authority_id.eq(&edited.authority_id),
+                    messages_dsl::reference_id.eq(&edited.reference_id),
+                ))
+                .execute(conn)
+        })?;
+        Ok(previous.version + 1)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::tests::generate_group, group_message::tests::generate_message,
+            tests::with_connection,
+        },
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_keeps_earlier_versions() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let message = generate_message(None, Some(&group.id), Some(1), None);
+            message.store(conn).unwrap();
+
+            let mut edited = message.clone();
+            for (i, content) in [b"second".to_vec(), b"third".to_vec()]
+                .into_iter()
+                .enumerate()
+            {
+                let current = conn.get_group_message(&message.id).unwrap().unwrap();
+                edited.decrypted_message_bytes = content;
+                let version = conn
+                    .apply_message_edit(&current, &edited, &[i as u8], 10 + i as i64)
+                    .unwrap();
+                assert_eq!(version, i as i32 + 1);
+            }
+
+            let current = conn.get_group_message(&message.id).unwrap().unwrap();
+            assert_eq!(current.decrypted_message_bytes, b"third");
+            let history: Vec<_> = conn
+                .get_message_edits(&message.id)
+                .unwrap()
+                .into_iter()
+                .map(|edit| edit.decrypted_message_bytes)
+                .collect();
+            assert_eq!(
+                history,
+                [message.decrypted_message_bytes.clone(), b"second".to_vec()]
+            );
+        })
+        .await
+    }
+}
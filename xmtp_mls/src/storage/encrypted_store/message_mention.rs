@@ -0,0 +1,118 @@
+//! The inboxes mentioned by each message, indexed so that an inbox's mentions can be found
+//! without decoding messages.
+use super::{
+    db_connection::DbConnection,
+    group_message::StoredGroupMessage,
+    schema::{
+        group_messages::dsl as messages_dsl,
+        message_mentions::{self, dsl},
+        read_cursors::dsl as cursors_dsl,
+    },
+};
+use crate::storage::StorageError;
+use diesel::prelude::*;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = message_mentions)]
+#[diesel(primary_key(message_id, inbox_id))]
+pub struct StoredMessageMention {
+    pub message_id: Vec<u8>,
+    pub inbox_id: String,
+    pub group_id: Vec<u8>,
+    pub sent_at_ns: i64,
+}
+
+impl DbConnection {
+    pub fn insert_message_mentions(
+        &self,
+        mentions: &[StoredMessageMention],
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::insert_or_ignore_into(dsl::message_mentions)
+                .values(mentions)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// The messages mentioning `inbox_id` that were sent after its read cursor in their group,
+    /// newest first. Deleted messages are left out.
+    pub fn unread_mentions(&self, inbox_id: &str) -> Result<Vec<StoredGroupMessage>, StorageError> {
+        let query = messages_dsl::group_messages
+            .inner_join(dsl::message_mentions)
+            .left_join(
+                cursors_dsl::read_cursors.on(cursors_dsl::group_id
+                    .eq(dsl::group_id)
+                    .and(cursors_dsl::inbox_id.eq(dsl::inbox_id))),
+            )
+            .filter(dsl::inbox_id.eq(inbox_id))
+            .filter(messages_dsl::deleted_at_ns.is_null())
+            .filter(
+                cursors_dsl::read_until_ns
+                    .is_null()
+                    .or(cursors_dsl::read_until_ns.lt(dsl::sent_at_ns)),
+            )
+            .select(messages_dsl::group_messages::all_columns())
+            .order(messages_dsl::sent_at_ns.desc());
+
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::tests::generate_group, group_message::tests::generate_message,
+            read_cursor::StoredReadCursor, tests::with_connection,
+        },
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_finds_mentions_after_the_read_cursor() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let messages: Vec<_> = [10, 20, 30]
+                .into_iter()
+                .map(|sent_at_ns| generate_message(None, Some(&group.id), Some(sent_at_ns), None))
+                .collect();
+            for message in &messages {
+                message.store(conn).unwrap();
+            }
+            let mentions: Vec<_> = messages
+                .iter()
+                .map(|message| StoredMessageMention {
+                    message_id: message.id.clone(),
+                    inbox_id: "alix".to_string(),
+                    group_id: group.id.clone(),
+                    sent_at_ns: message.sent_at_ns,
+                })
+                .collect();
+            conn.insert_message_mentions(&mentions).unwrap();
+            // mentions are only recorded once
+            conn.insert_message_mentions(&mentions[..1]).unwrap();
+            assert_eq!(conn.unread_mentions("alix").unwrap().len(), 3);
+            assert!(conn.unread_mentions("bo").unwrap().is_empty());
+
+            conn.advance_read_cursor(&StoredReadCursor {
+                group_id: group.id.clone(),
+                inbox_id: "alix".to_string(),
+                read_until_ns: 20,
+                message_id: Some(messages[1].id.clone()),
+                receipt_message_id: vec![1],
+            })
+            .unwrap();
+            let unread = conn.unread_mentions("alix").unwrap();
+            assert_eq!(unread.len(), 1);
+            assert_eq!(unread[0].id, messages[2].id);
+        })
+        .await
+    }
+}
@@ -0,0 +1,126 @@
+//! The current reactions to each message, maintained while reaction messages are processed.
+//! A row exists for every inbox that currently has a reaction with a given emoji on a message;
+//! removing the reaction deletes the row.
+use super::{
+    db_connection::DbConnection,
+    schema::message_reactions::{self, dsl},
+};
+use crate::{storage::StorageError, Store};
+use diesel::prelude::*;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = message_reactions)]
+#[diesel(primary_key(message_id, sender_inbox_id, content))]
+pub struct StoredMessageReaction {
+    /// The message reacted to
+    pub message_id: Vec<u8>,
+    pub sender_inbox_id: String,
+    /// The emoji, or other reaction content
+    pub content: String,
+    pub group_id: Vec<u8>,
+    /// The message carrying the reaction
+    pub reaction_message_id: Vec<u8>,
+    pub reacted_at_ns: i64,
+}
+
+impl Store<DbConnection> for StoredMessageReaction {
+    // Reacting again with the same content replaces the earlier reaction
+    fn store(&self, into: &DbConnection) -> Result<(), StorageError> {
+        into.raw_query(|conn| {
+            diesel::replace_into(dsl::message_reactions)
+                .values(self)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+}
+
+impl DbConnection {
+    /// Reactions to `message_id` in `group_id`, oldest first
+    pub fn get_message_reactions<GroupId: AsRef<[u8]>, MessageId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        message_id: MessageId,
+    ) -> Result<Vec<StoredMessageReaction>, StorageError> {
+        let query = dsl::message_reactions
+            .filter(dsl::group_id.eq(group_id.as_ref()))
+            .filter(dsl::message_id.eq(message_id.as_ref()))
+            .order((dsl::reacted_at_ns.asc(), dsl::sender_inbox_id.asc()));
+
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// Remove the reaction `content` of `sender_inbox_id` to `message_id`, unless it was made
+    /// after `removed_at_ns`. Returns whether a reaction was removed.
+    pub fn delete_message_reaction<MessageId: AsRef<[u8]>>(
+        &self,
+        message_id: MessageId,
+        sender_inbox_id: &str,
+        content: &str,
+        removed_at_ns: i64,
+    ) -> Result<bool, StorageError> {
+        let query = diesel::delete(dsl::message_reactions)
+            .filter(dsl::message_id.eq(message_id.as_ref()))
+            .filter(dsl::sender_inbox_id.eq(sender_inbox_id))
+            .filter(dsl::content.eq(content))
+            .filter(dsl::reacted_at_ns.le(removed_at_ns));
+
+        Ok(self.raw_query(|conn| query.execute(conn))? > 0)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::storage::encrypted_store::{group::tests::generate_group, tests::with_connection};
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn reaction(group_id: &[u8], sender: &str, content: &str, at: i64) -> StoredMessageReaction {
+        StoredMessageReaction {
+            message_id: vec![1, 2, 3],
+            sender_inbox_id: sender.to_string(),
+            content: content.to_string(),
+            group_id: group_id.to_vec(),
+            reaction_message_id: vec![at as u8],
+            reacted_at_ns: at,
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_adds_and_removes_reactions() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+
+            reaction(&group.id, "alix", "👍", 10).store(conn).unwrap();
+            reaction(&group.id, "bo", "👍", 5).store(conn).unwrap();
+            reaction(&group.id, "alix", "👍", 20).store(conn).unwrap();
+            reaction(&group.id, "alix", "🎉", 30).store(conn).unwrap();
+
+            let reactions = conn.get_message_reactions(&group.id, [1, 2, 3]).unwrap();
+            let order: Vec<_> = reactions
+                .iter()
+                .map(|r| (r.sender_inbox_id.as_str(), r.content.as_str()))
+                .collect();
+            assert_eq!(order, [("bo", "👍"), ("alix", "👍"), ("alix", "🎉")]);
+
+            // a removal older than the reaction doesn't undo it
+            assert!(!conn
+                .delete_message_reaction([1, 2, 3], "alix", "👍", 15)
+                .unwrap());
+            assert!(conn
+                .delete_message_reaction([1, 2, 3], "alix", "👍", 25)
+                .unwrap());
+            assert_eq!(
+                conn.get_message_reactions(&group.id, [1, 2, 3])
+                    .unwrap()
+                    .len(),
+                2
+            );
+        })
+        .await
+    }
+}
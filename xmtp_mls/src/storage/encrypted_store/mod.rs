@@ -11,27 +11,58 @@
 //! `diesel print-schema` or use `cargo run update-schema` which will update the files for you.
 
 pub mod association_state;
+pub mod attachment_upload;
+pub mod blocked_inbox;
 pub mod consent_record;
-mod conversation_list;
+pub mod conversation_list;
+pub mod conversation_scratch;
+pub mod conversation_summary;
+pub mod custom_preference;
 pub mod db_connection;
+pub mod draft;
 pub mod group;
+pub mod group_epoch;
+pub mod group_fork;
 pub mod group_intent;
+pub mod group_membership_change;
 pub mod group_message;
 pub mod identity;
 pub mod identity_update;
+pub mod imported_archive;
+pub mod inbox_profile;
+pub mod installation_capability;
+pub mod invite_link;
+pub mod join_request;
 pub mod key_package_history;
 pub mod key_store_entry;
+pub mod message_activity;
+pub mod message_annotation;
+pub mod message_edit;
+pub mod message_mention;
+pub mod message_reaction;
 #[cfg(not(target_arch = "wasm32"))]
 pub(super) mod native;
+pub mod notification_settings;
+pub mod pending_reference;
+pub mod poll_vote;
+pub mod processing_checkpoint;
+pub mod read_cursor;
+pub mod reconsent_prompt;
 pub mod refresh_state;
 pub mod schema;
 mod schema_gen;
+pub mod send_diagnostic;
+#[cfg(all(feature = "slow-query-log", not(target_arch = "wasm32")))]
+pub mod slow_query_log;
 #[cfg(not(target_arch = "wasm32"))]
 mod sqlcipher_connection;
+pub mod unknown_content_type;
+pub mod unread_count;
 pub mod user_preferences;
 pub mod wallet_addresses;
 #[cfg(target_arch = "wasm32")]
 pub(super) mod wasm;
+pub mod wipe;
 
 pub use self::db_connection::DbConnection;
 #[cfg(not(target_arch = "wasm32"))]
@@ -130,6 +161,12 @@ impl EncryptedMessageStore {
         store.init_db()?;
         Ok(store)
     }
+
+    /// The log of slow statements run on this store's connections
+    #[cfg(all(feature = "slow-query-log", not(target_arch = "wasm32")))]
+    pub fn slow_query_log(&self) -> &slow_query_log::SlowQueryLog {
+        &self.db.slow_query_log
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -398,6 +435,7 @@ where
         }
 
         let conn = self.conn_ref();
+        let deferred_events = conn.deferred_event_count();
 
         match fun(self) {
             Ok(value) => {
@@ -409,6 +447,7 @@ where
             }
             Err(err) => {
                 tracing::debug!("Transaction being rolled back");
+                conn.discard_deferred_events(deferred_events);
                 match conn.raw_query(|conn| {
                     <Db as XmtpDb>::TransactionManager::rollback_transaction(&mut *conn)
                 }) {
@@ -447,9 +486,15 @@ where
             let mut connection = connection.inner_mut_ref();
             <Db as XmtpDb>::TransactionManager::begin_transaction(&mut *connection)?;
         }
+        let deferred_events = self.conn_ref().deferred_event_count();
+        let mut abandoned = RollbackOnDrop {
+            provider: Some(self),
+            deferred_events,
+        };
 
         // ensuring we have only one strong reference
         let result = fun(self).await;
+        abandoned.provider = None;
         let local_connection = self.conn_ref().inner_ref();
         if Arc::strong_count(&local_connection) > 1 {
             tracing::warn!(
@@ -474,6 +519,7 @@ where
             }
             Err(err) => {
                 tracing::debug!("Transaction async being rolled back");
+                self.conn_ref().discard_deferred_events(deferred_events);
                 match local_connection.raw_query(|conn| {
                     <Db as XmtpDb>::TransactionManager::rollback_transaction(&mut *conn)
                 }) {
@@ -486,6 +532,30 @@ where
     }
 }
 
+/// Rolls back an async transaction whose future is dropped before it finishes, e.g. when the
+/// stream or sync running it is ended, so the pooled connection isn't handed out again still
+/// inside the transaction
+struct RollbackOnDrop<'a, Db: XmtpDb> {
+    provider: Option<&'a XmtpOpenMlsProviderPrivate<Db, <Db as XmtpDb>::Connection>>,
+    deferred_events: usize,
+}
+
+impl<Db: XmtpDb> Drop for RollbackOnDrop<'_, Db> {
+    fn drop(&mut self) {
+        let Some(provider) = self.provider else {
+            return;
+        };
+        tracing::warn!("Transaction async abandoned, rolling back");
+        let conn = provider.conn_ref();
+        conn.discard_deferred_events(self.deferred_events);
+        if let Err(e) = conn
+            .raw_query(|conn| <Db as XmtpDb>::TransactionManager::rollback_transaction(&mut *conn))
+        {
+            tracing::error!("failed to roll back an abandoned transaction: {e}");
+        }
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     #[cfg(target_arch = "wasm32")]
@@ -95,6 +95,8 @@ pub struct NativeDb {
     pub(super) pool: Arc<RwLock<Option<Pool>>>,
     customizer: Option<Box<dyn XmtpConnection>>,
     opts: StorageOption,
+    #[cfg(all(feature = "slow-query-log", not(target_arch = "wasm32")))]
+    pub(super) slow_query_log: super::slow_query_log::SlowQueryLog,
 }
 
 impl NativeDb {
@@ -129,6 +131,8 @@ impl NativeDb {
             pool: Arc::new(Some(pool).into()),
             customizer,
             opts: opts.clone(),
+            #[cfg(all(feature = "slow-query-log", not(target_arch = "wasm32")))]
+            slow_query_log: Default::default(),
         })
     }
 
@@ -155,7 +159,11 @@ impl XmtpDb for NativeDb {
 
     /// Returns the Wrapped [`super::db_connection::DbConnection`] Connection implementation for this Database
     fn conn(&self) -> Result<DbConnectionPrivate<Self::Connection>, StorageError> {
-        let conn = self.raw_conn()?;
+        #[allow(unused_mut)]
+        let mut conn = self.raw_conn()?;
+        // Pooled connections are reused, so reinstall on every checkout
+        #[cfg(all(feature = "slow-query-log", not(target_arch = "wasm32")))]
+        conn.set_instrumentation(self.slow_query_log.instrumentation());
         Ok(DbConnectionPrivate::from_arc_mutex(Arc::new(
             parking_lot::Mutex::new(conn),
         )))
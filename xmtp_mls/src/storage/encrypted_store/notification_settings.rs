@@ -0,0 +1,182 @@
+//! The user's notification settings for each conversation. Settings are synced to the user's
+//! other installations, so they're kept with the [hybrid logical clock](crate::hlc) timestamp of
+//! their write and an update only replaces an earlier write.
+use super::{
+    db_connection::DbConnection,
+    schema::notification_settings::{self, dsl},
+    Sqlite,
+};
+use crate::{hlc::Hlc, storage::StorageError};
+use diesel::{
+    dsl::sql,
+    prelude::*,
+    sql_types::{BigInt, Bool},
+};
+use serde::{Deserialize, Serialize};
+use xmtp_common::time::now_ns;
+
+#[derive(
+    Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[diesel(table_name = notification_settings)]
+#[diesel(primary_key(group_id))]
+pub struct StoredNotificationSettings {
+    pub group_id: Vec<u8>,
+    /// Notifications are silenced until this time
+    pub mute_until_ns: Option<i64>,
+    /// Only notify about messages that mention the user
+    pub mentions_only: bool,
+    /// Hybrid logical clock timestamp of the write, see [`Self::hlc`]
+    pub hlc_wall_ns: i64,
+    pub hlc_counter: i32,
+    pub hlc_node: Vec<u8>,
+}
+
+impl StoredNotificationSettings {
+    pub fn new(
+        group_id: Vec<u8>,
+        mute_until_ns: Option<i64>,
+        mentions_only: bool,
+        hlc: Hlc,
+    ) -> Self {
+        Self {
+            group_id,
+            mute_until_ns,
+            mentions_only,
+            hlc_wall_ns: hlc.wall_ns,
+            hlc_counter: hlc.counter as i32,
+            hlc_node: hlc.node,
+        }
+    }
+
+    /// When the settings were written, ordering concurrent writes from different installations
+    pub fn hlc(&self) -> Hlc {
+        Hlc {
+            wall_ns: self.hlc_wall_ns,
+            counter: self.hlc_counter as u32,
+            node: self.hlc_node.clone(),
+        }
+    }
+
+    pub fn is_muted(&self, now_ns: i64) -> bool {
+        self.mute_until_ns
+            .is_some_and(|mute_until_ns| mute_until_ns > now_ns)
+    }
+}
+
+impl DbConnection {
+    pub fn get_notification_settings<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<Option<StoredNotificationSettings>, StorageError> {
+        let query = dsl::notification_settings.find(group_id.as_ref());
+
+        Ok(self.raw_query(|conn| query.first(conn).optional())?)
+    }
+
+    /// Store `settings` unless the stored settings were written later. Returns whether they were
+    /// stored.
+    pub fn set_notification_settings(
+        &self,
+        settings: &StoredNotificationSettings,
+    ) -> Result<bool, StorageError> {
+        Ok(self.raw_query(|conn| -> diesel::QueryResult<bool> {
+            let current: Option<StoredNotificationSettings> = dsl::notification_settings
+                .find(&settings.group_id)
+                .first(conn)
+                .optional()?;
+            if current.is_some_and(|current| current.hlc() >= settings.hlc()) {
+                return Ok(false);
+            }
+            diesel::replace_into(dsl::notification_settings)
+                .values(settings)
+                .execute(conn)?;
+            Ok(true)
+        })?)
+    }
+
+    pub fn all_notification_settings(
+        &self,
+    ) -> Result<Vec<StoredNotificationSettings>, StorageError> {
+        Ok(self.raw_query(|conn| dsl::notification_settings.load(conn))?)
+    }
+}
+
+/// Filters a query of conversations by `id` to those that are muted right now, or to those that
+/// aren't
+pub(super) fn muted_filter<QS>(
+    muted: bool,
+) -> Box<dyn BoxableExpression<QS, Sqlite, SqlType = Bool>> {
+    let muted_groups =
+        sql::<Bool>("id IN (SELECT group_id FROM notification_settings WHERE mute_until_ns > ")
+            .bind::<BigInt, _>(now_ns())
+            .sql(")");
+    if muted {
+        Box::new(muted_groups)
+    } else {
+        Box::new(diesel::dsl::not(muted_groups))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::{tests::generate_group, GroupQueryArgs},
+            tests::with_connection,
+        },
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_keeps_the_newest_settings() {
+        with_connection(|conn| {
+            let muted = generate_group(None);
+            let unmuted = generate_group(None);
+            muted.store(conn).unwrap();
+            unmuted.store(conn).unwrap();
+            let now = xmtp_common::time::now_ns();
+
+            let hlc = |wall_ns, node: u8| Hlc {
+                wall_ns,
+                counter: 0,
+                node: vec![node],
+            };
+
+            let settings = StoredNotificationSettings::new(
+                muted.id.clone(),
+                Some(now + 1_000_000_000_000),
+                false,
+                hlc(10, 1),
+            );
+            assert!(conn.set_notification_settings(&settings).unwrap());
+            let stale = StoredNotificationSettings::new(muted.id.clone(), None, false, hlc(5, 2));
+            assert!(!conn.set_notification_settings(&stale).unwrap());
+            // a concurrent write from an installation ordered before this one loses too
+            let concurrent =
+                StoredNotificationSettings::new(muted.id.clone(), None, false, hlc(10, 0));
+            assert!(!conn.set_notification_settings(&concurrent).unwrap());
+            assert_eq!(
+                conn.get_notification_settings(&muted.id).unwrap(),
+                Some(settings)
+            );
+
+            let muted_groups = conn
+                .find_groups(GroupQueryArgs::default().muted(true))
+                .unwrap();
+            assert_eq!(muted_groups.len(), 1);
+            assert_eq!(muted_groups[0].id, muted.id);
+            let unmuted_groups = conn
+                .find_groups(GroupQueryArgs::default().muted(false))
+                .unwrap();
+            assert_eq!(unmuted_groups.len(), 1);
+            assert_eq!(unmuted_groups[0].id, unmuted.id);
+        })
+        .await
+    }
+}
@@ -0,0 +1,89 @@
+//! Messages that reference a message this installation hasn't received yet, e.g. an edit that
+//! arrived before the message it edits. Messages from different installations can arrive in
+//! any order, so they wait here until the referenced message is stored.
+use super::{
+    db_connection::DbConnection,
+    group_message::StoredGroupMessage,
+    schema::{
+        group_messages,
+        pending_references::{self, dsl},
+    },
+};
+use crate::{impl_store_or_ignore, storage::StorageError};
+use diesel::prelude::*;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = pending_references)]
+#[diesel(primary_key(message_id))]
+pub struct StoredPendingReference {
+    /// The message waiting, e.g. the edit
+    pub message_id: Vec<u8>,
+    pub group_id: Vec<u8>,
+    /// The message it references
+    pub reference_id: Vec<u8>,
+}
+
+impl_store_or_ignore!(StoredPendingReference, pending_references);
+
+impl DbConnection {
+    /// Remove the messages waiting for `reference_id` and return them, oldest first
+    pub fn take_pending_references(
+        &self,
+        reference_id: &[u8],
+    ) -> Result<Vec<StoredGroupMessage>, StorageError> {
+        let query = dsl::pending_references
+            .inner_join(group_messages::table)
+            .filter(dsl::reference_id.eq(reference_id))
+            .order(group_messages::sent_at_ns.asc())
+            .select(group_messages::all_columns);
+
+        Ok(self.raw_query(|conn| {
+            let messages = query.load::<StoredGroupMessage>(conn)?;
+            diesel::delete(dsl::pending_references.filter(dsl::reference_id.eq(reference_id)))
+                .execute(conn)?;
+            Ok::<_, diesel::result::Error>(messages)
+        })?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::tests::generate_group, group_message::tests::generate_message,
+            tests::with_connection,
+        },
+        Store, StoreOrIgnore,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_returns_waiting_messages_once() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let later = generate_message(None, Some(&group.id), Some(20), None);
+            let earlier = generate_message(None, Some(&group.id), Some(10), None);
+            for message in [&later, &earlier] {
+                message.store(conn).unwrap();
+                StoredPendingReference {
+                    message_id: message.id.clone(),
+                    group_id: group.id.clone(),
+                    reference_id: vec![1],
+                }
+                .store_or_ignore(conn)
+                .unwrap();
+            }
+
+            assert!(conn.take_pending_references(&[2]).unwrap().is_empty());
+            let waiting = conn.take_pending_references(&[1]).unwrap();
+            assert_eq!(waiting, vec![earlier, later]);
+            assert!(conn.take_pending_references(&[1]).unwrap().is_empty());
+        })
+        .await
+    }
+}
@@ -0,0 +1,141 @@
+//! The current vote of each inbox in each poll, maintained while vote messages are processed so
+//! that tallies don't have to replay the conversation.
+use super::{
+    db_connection::DbConnection,
+    schema::poll_votes::{self, dsl},
+};
+use crate::storage::StorageError;
+use diesel::prelude::*;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = poll_votes)]
+#[diesel(primary_key(poll_id, inbox_id))]
+pub struct StoredPollVote {
+    /// The message that created the poll
+    pub poll_id: Vec<u8>,
+    pub inbox_id: String,
+    pub group_id: Vec<u8>,
+    /// Indices of the chosen options, separated by commas. Empty if the vote was retracted.
+    pub options: String,
+    /// The message carrying the vote
+    pub vote_message_id: Vec<u8>,
+    pub voted_at_ns: i64,
+}
+
+impl StoredPollVote {
+    /// Indices of the chosen options
+    pub fn option_indices(&self) -> Vec<u32> {
+        self.options
+            .split(',')
+            .filter_map(|option| option.parse().ok())
+            .collect()
+    }
+}
+
+impl DbConnection {
+    /// Store `vote` unless its inbox voted more recently in the same poll. Returns whether it was
+    /// stored.
+    pub fn record_poll_vote(&self, vote: &StoredPollVote) -> Result<bool, StorageError> {
+        Ok(self.raw_query(|conn| -> diesel::QueryResult<bool> {
+            let current: Option<i64> = dsl::poll_votes
+                .find((&vote.poll_id, &vote.inbox_id))
+                .select(dsl::voted_at_ns)
+                .first(conn)
+                .optional()?;
+            if current.is_some_and(|voted_at_ns| voted_at_ns > vote.voted_at_ns) {
+                return Ok(false);
+            }
+            diesel::replace_into(dsl::poll_votes)
+                .values(vote)
+                .execute(conn)?;
+            Ok(true)
+        })?)
+    }
+
+    /// The votes in `poll_id`, oldest first, leaving out retracted ones
+    pub fn get_poll_votes<PollId: AsRef<[u8]>>(
+        &self,
+        poll_id: PollId,
+    ) -> Result<Vec<StoredPollVote>, StorageError> {
+        let query = dsl::poll_votes
+            .filter(dsl::poll_id.eq(poll_id.as_ref()))
+            .filter(dsl::options.ne(""))
+            .order((dsl::voted_at_ns.asc(), dsl::inbox_id.asc()));
+
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// The number of votes for each of the `option_count` options of `poll_id`
+    pub fn poll_tally<PollId: AsRef<[u8]>>(
+        &self,
+        poll_id: PollId,
+        option_count: usize,
+    ) -> Result<Vec<u32>, StorageError> {
+        let mut tally = vec![0; option_count];
+        for vote in self.get_poll_votes(poll_id)? {
+            for option in vote.option_indices() {
+                if let Some(count) = tally.get_mut(option as usize) {
+                    *count += 1;
+                }
+            }
+        }
+        Ok(tally)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn vote(group_id: &[u8], inbox_id: &str, options: &str, at: i64) -> StoredPollVote {
+        StoredPollVote {
+            poll_id: vec![1, 2, 3],
+            inbox_id: inbox_id.to_string(),
+            group_id: group_id.to_vec(),
+            options: options.to_string(),
+            vote_message_id: vec![at as u8],
+            voted_at_ns: at,
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_tallies_the_latest_votes() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+
+            assert!(conn
+                .record_poll_vote(&vote(&group.id, "alix", "0", 10))
+                .unwrap());
+            assert!(conn
+                .record_poll_vote(&vote(&group.id, "bo", "0,2", 10))
+                .unwrap());
+            assert_eq!(conn.poll_tally([1, 2, 3], 3).unwrap(), vec![2, 0, 1]);
+
+            // alix changes their vote, then an older vote arrives late
+            assert!(conn
+                .record_poll_vote(&vote(&group.id, "alix", "1", 30))
+                .unwrap());
+            assert!(!conn
+                .record_poll_vote(&vote(&group.id, "alix", "2", 20))
+                .unwrap());
+            assert_eq!(conn.poll_tally([1, 2, 3], 3).unwrap(), vec![1, 1, 1]);
+
+            // bo retracts
+            assert!(conn
+                .record_poll_vote(&vote(&group.id, "bo", "", 40))
+                .unwrap());
+            assert_eq!(conn.poll_tally([1, 2, 3], 3).unwrap(), vec![0, 1, 0]);
+            assert_eq!(conn.get_poll_votes([1, 2, 3]).unwrap().len(), 1);
+        })
+        .await
+    }
+}
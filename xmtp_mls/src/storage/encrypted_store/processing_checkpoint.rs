@@ -0,0 +1,147 @@
+//! Marks an envelope as in-flight while it is processed, so processing interrupted by a crash
+//! can be detected and resolved on the next startup.
+//!
+//! The checkpoint is written before the processing transaction starts and deleted inside it,
+//! so it commits or rolls back together with the envelope's message, cursor and intent
+//! writes. A checkpoint left behind means processing stopped before committing.
+use super::{
+    db_connection::DbConnection,
+    refresh_state::EntityKind,
+    schema::processing_checkpoints::{self, dsl},
+};
+use crate::storage::StorageError;
+use diesel::prelude::*;
+use xmtp_common::time::now_ns;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = processing_checkpoints)]
+#[diesel(primary_key(group_id))]
+pub struct StoredProcessingCheckpoint {
+    pub group_id: Vec<u8>,
+    /// Cursor of the envelope being processed
+    pub cursor: i64,
+    /// Timestamp of the envelope being processed
+    pub envelope_timestamp_ns: i64,
+    pub started_at_ns: i64,
+}
+
+/// How an interrupted checkpoint was resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointResolution {
+    /// The envelope was processed after the interruption, by a later sync that left its own
+    /// checkpoint behind. Nothing to do.
+    Committed,
+    /// The processing transaction never committed. The envelope will be processed again
+    /// on the next sync of the group.
+    RolledBack,
+}
+
+impl DbConnection {
+    /// Record that processing of the envelope at `cursor` is starting for `group_id`.
+    /// Must be written outside of the processing transaction so it survives a rollback, and
+    /// cleared inside it.
+    pub fn start_processing_checkpoint<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+        cursor: i64,
+        envelope_timestamp_ns: i64,
+    ) -> Result<(), StorageError> {
+        let checkpoint = StoredProcessingCheckpoint {
+            group_id: group_id.as_ref().to_vec(),
+            cursor,
+            envelope_timestamp_ns,
+            started_at_ns: now_ns(),
+        };
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::processing_checkpoints)
+                .values(&checkpoint)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    pub fn clear_processing_checkpoint<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::delete(dsl::processing_checkpoints.find(group_id.as_ref())).execute(conn)
+        })?;
+        Ok(())
+    }
+
+    pub fn processing_checkpoints(&self) -> Result<Vec<StoredProcessingCheckpoint>, StorageError> {
+        Ok(self.raw_query(|conn| dsl::processing_checkpoints.load(conn))?)
+    }
+
+    /// Resolve a checkpoint left behind by interrupted processing and remove it.
+    /// Run it in a transaction, so the checkpoint isn't removed from under a sync.
+    pub fn resolve_processing_checkpoint(
+        &self,
+        checkpoint: &StoredProcessingCheckpoint,
+    ) -> Result<CheckpointResolution, StorageError> {
+        let cursor = self.get_last_cursor_for_id(&checkpoint.group_id, EntityKind::Group)?;
+        let resolution = if cursor >= checkpoint.cursor {
+            CheckpointResolution::Committed
+        } else {
+            CheckpointResolution::RolledBack
+        };
+        self.clear_processing_checkpoint(&checkpoint.group_id)?;
+
+        Ok(resolution)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn checkpoint(conn: &DbConnection, group_id: &[u8]) -> StoredProcessingCheckpoint {
+        conn.processing_checkpoints()
+            .unwrap()
+            .into_iter()
+            .find(|c| c.group_id == group_id)
+            .unwrap()
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_resolves_interrupted_checkpoints() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            conn.get_last_cursor_for_id(&group.id, EntityKind::Group)
+                .unwrap();
+
+            // Interrupted, then processed by a later sync
+            conn.start_processing_checkpoint(&group.id, 5, 500).unwrap();
+            conn.update_cursor(&group.id, EntityKind::Group, 5).unwrap();
+            let resolution = conn
+                .resolve_processing_checkpoint(&checkpoint(conn, &group.id))
+                .unwrap();
+            assert_eq!(resolution, CheckpointResolution::Committed);
+
+            // Interrupted before the transaction committed
+            conn.start_processing_checkpoint(&group.id, 7, 700).unwrap();
+            let resolution = conn
+                .resolve_processing_checkpoint(&checkpoint(conn, &group.id))
+                .unwrap();
+            assert_eq!(resolution, CheckpointResolution::RolledBack);
+            assert_eq!(
+                conn.get_last_cursor_for_id(&group.id, EntityKind::Group)
+                    .unwrap(),
+                5
+            );
+
+            assert!(conn.processing_checkpoints().unwrap().is_empty());
+        })
+        .await
+    }
+}
@@ -0,0 +1,105 @@
+//! How far each member has read in each group, maintained while read receipts are processed.
+//! A cursor only ever moves forward, so a receipt processed late can't mark messages unread.
+use super::{
+    db_connection::DbConnection,
+    schema::read_cursors::{self, dsl},
+};
+use crate::storage::StorageError;
+use diesel::prelude::*;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = read_cursors)]
+#[diesel(primary_key(group_id, inbox_id))]
+pub struct StoredReadCursor {
+    pub group_id: Vec<u8>,
+    pub inbox_id: String,
+    /// Messages sent at or before this time have been read
+    pub read_until_ns: i64,
+    /// The last message read, if the receipt named one
+    pub message_id: Option<Vec<u8>>,
+    /// The read receipt that moved the cursor here
+    pub receipt_message_id: Vec<u8>,
+}
+
+impl DbConnection {
+    /// The read cursors of every member of `group_id` that sent a read receipt
+    pub fn get_read_cursors<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<Vec<StoredReadCursor>, StorageError> {
+        let query = dsl::read_cursors
+            .filter(dsl::group_id.eq(group_id.as_ref()))
+            .order(dsl::read_until_ns.desc());
+
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+
+    /// Move the cursor of `cursor.inbox_id` forward to `cursor`. Returns whether it moved.
+    pub fn advance_read_cursor(&self, cursor: &StoredReadCursor) -> Result<bool, StorageError> {
+        Ok(self.raw_query(|conn| -> diesel::QueryResult<bool> {
+            let current: Option<i64> = dsl::read_cursors
+                .find((&cursor.group_id, &cursor.inbox_id))
+                .select(dsl::read_until_ns)
+                .first(conn)
+                .optional()?;
+            if current.is_some_and(|read_until_ns| read_until_ns >= cursor.read_until_ns) {
+                return Ok(false);
+            }
+            diesel::replace_into(dsl::read_cursors)
+                .values(cursor)
+                .execute(conn)?;
+            Ok(true)
+        })?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn cursor(group_id: &[u8], inbox_id: &str, read_until_ns: i64) -> StoredReadCursor {
+        StoredReadCursor {
+            group_id: group_id.to_vec(),
+            inbox_id: inbox_id.to_string(),
+            read_until_ns,
+            message_id: Some(vec![read_until_ns as u8]),
+            receipt_message_id: vec![read_until_ns as u8, 0],
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_only_moves_cursors_forward() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+
+            assert!(conn
+                .advance_read_cursor(&cursor(&group.id, "alix", 10))
+                .unwrap());
+            assert!(conn
+                .advance_read_cursor(&cursor(&group.id, "bo", 5))
+                .unwrap());
+            assert!(!conn
+                .advance_read_cursor(&cursor(&group.id, "alix", 7))
+                .unwrap());
+            assert!(conn
+                .advance_read_cursor(&cursor(&group.id, "bo", 20))
+                .unwrap());
+
+            let cursors = conn.get_read_cursors(&group.id).unwrap();
+            assert_eq!(
+                cursors,
+                vec![cursor(&group.id, "bo", 20), cursor(&group.id, "alix", 10)]
+            );
+        })
+        .await
+    }
+}
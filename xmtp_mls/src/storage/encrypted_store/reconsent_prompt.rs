@@ -0,0 +1,84 @@
+//! When inactive conversations were last flagged for re-consent, used to rate limit
+//! how often the same conversation is flagged.
+use super::{
+    db_connection::DbConnection,
+    schema::reconsent_prompts::{self, dsl},
+};
+use crate::storage::StorageError;
+use diesel::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = reconsent_prompts)]
+#[diesel(primary_key(group_id))]
+pub struct StoredReconsentPrompt {
+    pub group_id: Vec<u8>,
+    pub prompted_at_ns: i64,
+}
+
+impl DbConnection {
+    /// Record that the conversations in `group_ids` were flagged for re-consent at `prompted_at_ns`
+    pub fn record_reconsent_prompts(
+        &self,
+        group_ids: &[Vec<u8>],
+        prompted_at_ns: i64,
+    ) -> Result<(), StorageError> {
+        let records: Vec<StoredReconsentPrompt> = group_ids
+            .iter()
+            .map(|group_id| StoredReconsentPrompt {
+                group_id: group_id.clone(),
+                prompted_at_ns,
+            })
+            .collect();
+        if records.is_empty() {
+            return Ok(());
+        }
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::reconsent_prompts)
+                .values(&records)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// When each conversation that was ever flagged for re-consent was last flagged, by group id
+    pub fn reconsent_prompt_times(&self) -> Result<HashMap<Vec<u8>, i64>, StorageError> {
+        let prompts: Vec<StoredReconsentPrompt> =
+            self.raw_query(|conn| dsl::reconsent_prompts.load(conn))?;
+        Ok(prompts
+            .into_iter()
+            .map(|prompt| (prompt.group_id, prompt.prompted_at_ns))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{
+        storage::encrypted_store::{group::tests::generate_group, tests::with_connection},
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_keeps_the_latest_prompt_time() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            assert!(conn.reconsent_prompt_times().unwrap().is_empty());
+
+            conn.record_reconsent_prompts(&[group.id.clone()], 1_000)
+                .unwrap();
+            conn.record_reconsent_prompts(&[group.id.clone()], 2_000)
+                .unwrap();
+
+            let times = conn.reconsent_prompt_times().unwrap();
+            assert_eq!(times.len(), 1);
+            assert_eq!(times[&group.id], 2_000);
+        })
+        .await
+    }
+}
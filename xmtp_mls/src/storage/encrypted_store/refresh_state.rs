@@ -20,6 +20,10 @@ use crate::{
 pub enum EntityKind {
     Welcome = 1,
     Group = 2,
+    /// Per-group position of the allowed consent shard stream, as a `sent_at_ns` watermark
+    AllowedShard = 3,
+    /// Per-group position of the message requests consent shard stream
+    RequestsShard = 4,
 }
 
 impl std::fmt::Display for EntityKind {
@@ -28,6 +32,8 @@ impl std::fmt::Display for EntityKind {
         match self {
             Welcome => write!(f, "welcome"),
             Group => write!(f, "group"),
+            AllowedShard => write!(f, "allowed_shard"),
+            RequestsShard => write!(f, "requests_shard"),
         }
     }
 }
@@ -50,6 +56,8 @@ where
         match i32::from_sql(bytes)? {
             1 => Ok(EntityKind::Welcome),
             2 => Ok(EntityKind::Group),
+            3 => Ok(EntityKind::AllowedShard),
+            4 => Ok(EntityKind::RequestsShard),
             x => Err(format!("Unrecognized variant {}", x).into()),
         }
     }
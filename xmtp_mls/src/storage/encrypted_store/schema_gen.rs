@@ -10,11 +10,102 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    attachment_uploads (id) {
+        id -> Binary,
+        group_id -> Binary,
+        content_digest -> Binary,
+        total_size -> BigInt,
+        chunk_size -> BigInt,
+        bytes_uploaded -> BigInt,
+        upload_session -> Nullable<Text>,
+        url -> Nullable<Text>,
+        state -> Integer,
+        message_id -> Nullable<Binary>,
+        created_at_ns -> BigInt,
+        updated_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    blocked_inboxes (inbox_id) {
+        inbox_id -> Text,
+        blocked -> Bool,
+        hide_messages -> Bool,
+        hlc_wall_ns -> BigInt,
+        hlc_counter -> Integer,
+        hlc_node -> Binary,
+    }
+}
+
 diesel::table! {
     consent_records (entity_type, entity) {
         entity_type -> Integer,
         state -> Integer,
         entity -> Text,
+        hlc_wall_ns -> BigInt,
+        hlc_counter -> Integer,
+        hlc_node -> Binary,
+    }
+}
+
+diesel::table! {
+    conversation_scratch (group_id, key) {
+        group_id -> Binary,
+        key -> Text,
+        value -> Binary,
+        updated_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    conversation_summaries (group_id, key, start_ns, end_ns) {
+        group_id -> Binary,
+        key -> Text,
+        start_ns -> BigInt,
+        end_ns -> BigInt,
+        content -> Binary,
+        message_count -> BigInt,
+        created_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    custom_preferences (namespace) {
+        namespace -> Text,
+        payload -> Nullable<Binary>,
+        hlc_wall_ns -> BigInt,
+        hlc_counter -> Integer,
+        hlc_node -> Binary,
+    }
+}
+
+diesel::table! {
+    drafts (group_id) {
+        group_id -> Binary,
+        content -> Nullable<Binary>,
+        hlc_wall_ns -> BigInt,
+        hlc_counter -> Integer,
+        hlc_node -> Binary,
+    }
+}
+
+diesel::table! {
+    group_epochs (group_id) {
+        group_id -> Binary,
+        epoch -> BigInt,
+        changed_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    group_forks (group_id) {
+        group_id -> Binary,
+        undecryptable_messages -> Integer,
+        last_failed_cursor -> BigInt,
+        first_failed_at_ns -> BigInt,
+        forked_at_ns -> Nullable<BigInt>,
+        rejoin_requested_at_ns -> Nullable<BigInt>,
     }
 }
 
@@ -33,6 +124,15 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    group_membership_changes (group_id, epoch, inbox_id) {
+        group_id -> Binary,
+        epoch -> BigInt,
+        inbox_id -> Text,
+        kind -> Integer,
+    }
+}
+
 diesel::table! {
     group_messages (id) {
         id -> Binary,
@@ -48,6 +148,7 @@ diesel::table! {
         version_major -> Integer,
         authority_id -> Text,
         reference_id -> Nullable<Binary>,
+        deleted_at_ns -> Nullable<BigInt>,
     }
 }
 
@@ -84,6 +185,70 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    imported_archives (group_id) {
+        group_id -> Binary,
+        metadata -> Binary,
+        exported_at_ns -> BigInt,
+        imported_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    inbox_profiles (inbox_id) {
+        inbox_id -> Text,
+        installation_id -> Binary,
+        display_name -> Nullable<Text>,
+        avatar_url -> Nullable<Text>,
+        ens_name -> Nullable<Text>,
+        signed_at_ns -> BigInt,
+        signature -> Binary,
+        sent_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    installation_capabilities (installation_id) {
+        installation_id -> Binary,
+        inbox_id -> Text,
+        content_types -> Text,
+        features -> Text,
+        advertised_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    invite_links (id) {
+        id -> Binary,
+        group_id -> Binary,
+        created_at_ns -> BigInt,
+        expires_at_ns -> Nullable<BigInt>,
+        max_uses -> Nullable<Integer>,
+        uses -> Integer,
+        revoked_at_ns -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    invite_redemptions (message_id) {
+        message_id -> Binary,
+        link_id -> Binary,
+        inbox_id -> Text,
+        received_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    join_requests (message_id) {
+        message_id -> Binary,
+        group_id -> Binary,
+        inbox_id -> Text,
+        note -> Nullable<Text>,
+        requested_at_ns -> BigInt,
+        rejoin -> Bool,
+    }
+}
+
 diesel::table! {
     key_package_history (id) {
         id -> Integer,
@@ -92,6 +257,58 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    message_annotations (message_id, processor_id, key) {
+        message_id -> Binary,
+        processor_id -> Text,
+        key -> Text,
+        value -> Text,
+        created_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    message_edits (message_id, version) {
+        message_id -> Binary,
+        version -> Integer,
+        group_id -> Binary,
+        decrypted_message_bytes -> Binary,
+        edit_message_id -> Binary,
+        replaced_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    message_mentions (message_id, inbox_id) {
+        message_id -> Binary,
+        inbox_id -> Text,
+        group_id -> Binary,
+        sent_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    message_reactions (message_id, sender_inbox_id, content) {
+        message_id -> Binary,
+        sender_inbox_id -> Text,
+        content -> Text,
+        group_id -> Binary,
+        reaction_message_id -> Binary,
+        reacted_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    notification_settings (group_id) {
+        group_id -> Binary,
+        mute_until_ns -> Nullable<BigInt>,
+        mentions_only -> Bool,
+        hlc_wall_ns -> BigInt,
+        hlc_counter -> Integer,
+        hlc_node -> Binary,
+    }
+}
+
 diesel::table! {
     openmls_key_store (key_bytes) {
         key_bytes -> Binary,
@@ -107,6 +324,51 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    pending_references (message_id) {
+        message_id -> Binary,
+        group_id -> Binary,
+        reference_id -> Binary,
+    }
+}
+
+diesel::table! {
+    poll_votes (poll_id, inbox_id) {
+        poll_id -> Binary,
+        inbox_id -> Text,
+        group_id -> Binary,
+        options -> Text,
+        vote_message_id -> Binary,
+        voted_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    processing_checkpoints (group_id) {
+        group_id -> Binary,
+        cursor -> BigInt,
+        envelope_timestamp_ns -> BigInt,
+        started_at_ns -> BigInt,
+    }
+}
+
+diesel::table! {
+    read_cursors (group_id, inbox_id) {
+        group_id -> Binary,
+        inbox_id -> Text,
+        read_until_ns -> BigInt,
+        message_id -> Nullable<Binary>,
+        receipt_message_id -> Binary,
+    }
+}
+
+diesel::table! {
+    reconsent_prompts (group_id) {
+        group_id -> Binary,
+        prompted_at_ns -> BigInt,
+    }
+}
+
 diesel::table! {
     refresh_state (entity_id, entity_kind) {
         entity_id -> Binary,
@@ -115,6 +377,38 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    send_diagnostics (message_id) {
+        message_id -> Binary,
+        epoch -> BigInt,
+        installation_count -> Integer,
+        publish_attempts -> Integer,
+        published_at_ns -> Nullable<BigInt>,
+        receipt_cursor -> Nullable<BigInt>,
+        receipt_timestamp_ns -> Nullable<BigInt>,
+    }
+}
+
+diesel::table! {
+    unknown_content_types (authority_id, type_id, version_major, version_minor) {
+        authority_id -> Text,
+        type_id -> Text,
+        version_major -> Integer,
+        version_minor -> Integer,
+        first_seen_ns -> BigInt,
+        last_seen_ns -> BigInt,
+        message_count -> BigInt,
+    }
+}
+
+diesel::table! {
+    unread_counts (group_id) {
+        group_id -> Binary,
+        last_read_ns -> BigInt,
+        unread_count -> BigInt,
+    }
+}
+
 diesel::table! {
     user_preferences (id) {
         id -> Integer,
@@ -129,21 +423,72 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(attachment_uploads -> groups (group_id));
+diesel::joinable!(conversation_scratch -> groups (group_id));
+diesel::joinable!(conversation_summaries -> groups (group_id));
+diesel::joinable!(drafts -> groups (group_id));
+diesel::joinable!(group_epochs -> groups (group_id));
+diesel::joinable!(group_forks -> groups (group_id));
 diesel::joinable!(group_intents -> groups (group_id));
+diesel::joinable!(group_membership_changes -> groups (group_id));
 diesel::joinable!(group_messages -> groups (group_id));
+diesel::joinable!(imported_archives -> groups (group_id));
+diesel::joinable!(invite_links -> groups (group_id));
+diesel::joinable!(invite_redemptions -> invite_links (link_id));
+diesel::joinable!(join_requests -> groups (group_id));
+diesel::joinable!(message_annotations -> group_messages (message_id));
+diesel::joinable!(message_edits -> group_messages (message_id));
+diesel::joinable!(message_mentions -> group_messages (message_id));
+diesel::joinable!(message_reactions -> groups (group_id));
+diesel::joinable!(notification_settings -> groups (group_id));
+diesel::joinable!(pending_references -> group_messages (message_id));
+diesel::joinable!(poll_votes -> groups (group_id));
+diesel::joinable!(processing_checkpoints -> groups (group_id));
+diesel::joinable!(read_cursors -> groups (group_id));
+diesel::joinable!(reconsent_prompts -> groups (group_id));
+diesel::joinable!(send_diagnostics -> group_messages (message_id));
+diesel::joinable!(unread_counts -> groups (group_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     association_state,
+    attachment_uploads,
+    blocked_inboxes,
     consent_records,
+    conversation_scratch,
+    conversation_summaries,
+    custom_preferences,
+    drafts,
+    group_epochs,
+    group_forks,
     group_intents,
+    group_membership_changes,
     group_messages,
     groups,
     identity,
     identity_updates,
+    imported_archives,
+    inbox_profiles,
+    installation_capabilities,
+    invite_links,
+    invite_redemptions,
+    join_requests,
     key_package_history,
+    message_annotations,
+    message_edits,
+    message_mentions,
+    message_reactions,
+    notification_settings,
     openmls_key_store,
     openmls_key_value,
+    pending_references,
+    poll_votes,
+    processing_checkpoints,
+    read_cursors,
+    reconsent_prompts,
     refresh_state,
+    send_diagnostics,
+    unknown_content_types,
+    unread_counts,
     user_preferences,
     wallet_addresses,
     conversation_list
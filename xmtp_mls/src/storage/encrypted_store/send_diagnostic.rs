@@ -0,0 +1,140 @@
+//! Fan-out diagnostics for messages sent by this installation, to investigate reports of
+//! recipients missing messages.
+use super::{
+    db_connection::DbConnection,
+    schema::send_diagnostics::{self, dsl},
+};
+use crate::{impl_fetch, storage::StorageError};
+use diesel::prelude::*;
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = send_diagnostics)]
+#[diesel(primary_key(message_id))]
+pub struct StoredSendDiagnostic {
+    pub message_id: Vec<u8>,
+    /// Epoch the message was encrypted in
+    pub epoch: i64,
+    /// Number of member installations when the message was encrypted
+    pub installation_count: i32,
+    pub publish_attempts: i32,
+    /// When the API accepted the message
+    pub published_at_ns: Option<i64>,
+    /// Cursor of the message when it was read back from the network
+    pub receipt_cursor: Option<i64>,
+    /// Server timestamp of the message when it was read back from the network
+    pub receipt_timestamp_ns: Option<i64>,
+}
+
+impl_fetch!(StoredSendDiagnostic, send_diagnostics, Vec<u8>);
+
+impl DbConnection {
+    /// Record that the message is being published. Publishing again, e.g. after the epoch
+    /// moved on, replaces the earlier record.
+    pub fn record_send_attempt(
+        &self,
+        message_id: &[u8],
+        epoch: i64,
+        installation_count: i32,
+        publish_attempts: i32,
+    ) -> Result<(), StorageError> {
+        let diagnostic = StoredSendDiagnostic {
+            message_id: message_id.to_vec(),
+            epoch,
+            installation_count,
+            publish_attempts,
+            published_at_ns: None,
+            receipt_cursor: None,
+            receipt_timestamp_ns: None,
+        };
+        self.raw_query(|conn| {
+            diesel::replace_into(dsl::send_diagnostics)
+                .values(&diagnostic)
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    pub fn set_send_published(
+        &self,
+        message_id: &[u8],
+        published_at_ns: i64,
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::update(dsl::send_diagnostics.find(message_id))
+                .set(dsl::published_at_ns.eq(published_at_ns))
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// Record the receipt of the message, if diagnostics were recorded when it was sent
+    pub fn set_send_receipt(
+        &self,
+        message_id: &[u8],
+        cursor: i64,
+        timestamp_ns: i64,
+    ) -> Result<(), StorageError> {
+        self.raw_query(|conn| {
+            diesel::update(dsl::send_diagnostics.find(message_id))
+                .set((
+                    dsl::receipt_cursor.eq(cursor),
+                    dsl::receipt_timestamp_ns.eq(timestamp_ns),
+                ))
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::tests::generate_group, group_message::tests::generate_message,
+            tests::with_connection,
+        },
+        Fetch, Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_send_diagnostic_lifecycle() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+            let message = generate_message(None, Some(&group.id), None, None);
+            message.store(conn).unwrap();
+
+            conn.record_send_attempt(&message.id, 3, 4, 1).unwrap();
+            // a receipt for a message sent without diagnostics is ignored
+            conn.set_send_receipt(b"other", 1, 1).unwrap();
+            conn.set_send_published(&message.id, 100).unwrap();
+            conn.set_send_receipt(&message.id, 42, 101).unwrap();
+
+            let diagnostic: StoredSendDiagnostic = conn.fetch(&message.id).unwrap().unwrap();
+            assert_eq!(
+                diagnostic,
+                StoredSendDiagnostic {
+                    message_id: message.id.clone(),
+                    epoch: 3,
+                    installation_count: 4,
+                    publish_attempts: 1,
+                    published_at_ns: Some(100),
+                    receipt_cursor: Some(42),
+                    receipt_timestamp_ns: Some(101),
+                }
+            );
+
+            // republishing in a later epoch starts over
+            conn.record_send_attempt(&message.id, 4, 4, 2).unwrap();
+            let diagnostic: StoredSendDiagnostic = conn.fetch(&message.id).unwrap().unwrap();
+            assert_eq!(diagnostic.epoch, 4);
+            assert_eq!(diagnostic.receipt_cursor, None);
+        })
+        .await
+    }
+}
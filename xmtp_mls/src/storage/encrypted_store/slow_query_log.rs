@@ -0,0 +1,192 @@
+//! An opt-in log of statements that take longer than a threshold, to diagnose storage
+//! regressions on large mailboxes in the field.
+//!
+//! Every pooled connection reports its statements to the shared [`SlowQueryLog`] through
+//! diesel's instrumentation. Query plans are computed when the log is read rather than while
+//! the slow statement holds the connection, with parameters left unbound, which doesn't change
+//! which indexes SQLite picks.
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use diesel::{
+    connection::{Instrumentation, InstrumentationEvent},
+    prelude::*,
+    sql_query,
+    sql_types::Text,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+use xmtp_common::time::now_ns;
+
+use super::db_connection::DbConnection;
+use crate::{configuration::SLOW_QUERY_LOG_CAPACITY, storage::StorageError};
+
+/// A statement that took longer than the log's threshold
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SlowQuery {
+    /// The statement, without its bound values
+    pub sql: String,
+    pub duration: Duration,
+    pub recorded_at_ns: i64,
+    /// `EXPLAIN QUERY PLAN` output, one line per step. Empty until read through
+    /// [`DbConnection::slow_queries_with_plans`].
+    pub query_plan: Vec<String>,
+}
+
+/// The slow statements of every connection of a store. Disabled until
+/// [`SlowQueryLog::enable`] is called.
+#[derive(Debug, Clone, Default)]
+pub struct SlowQueryLog {
+    /// The threshold in nanoseconds, 0 when disabled
+    threshold_ns: Arc<AtomicU64>,
+    queries: Arc<Mutex<VecDeque<SlowQuery>>>,
+}
+
+impl SlowQueryLog {
+    /// Record statements that take `threshold` or longer
+    pub fn enable(&self, threshold: Duration) {
+        let threshold_ns = u64::try_from(threshold.as_nanos()).unwrap_or(u64::MAX);
+        self.threshold_ns
+            .store(threshold_ns.max(1), Ordering::Relaxed);
+    }
+
+    /// Stop recording statements. Statements already recorded are kept.
+    pub fn disable(&self) {
+        self.threshold_ns.store(0, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.threshold_ns.load(Ordering::Relaxed) > 0
+    }
+
+    /// The recorded statements, oldest first, without query plans
+    pub fn queries(&self) -> Vec<SlowQuery> {
+        self.queries.lock().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.queries.lock().clear();
+    }
+
+    /// The instrumentation to install on a connection so it reports to this log
+    pub(super) fn instrumentation(&self) -> SlowQueryInstrumentation {
+        SlowQueryInstrumentation {
+            log: self.clone(),
+            started: None,
+        }
+    }
+
+    fn record(&self, sql: String, duration: Duration) {
+        tracing::warn!(?duration, "slow query: {sql}");
+        let mut queries = self.queries.lock();
+        if queries.len() >= SLOW_QUERY_LOG_CAPACITY {
+            queries.pop_front();
+        }
+        queries.push_back(SlowQuery {
+            sql,
+            duration,
+            recorded_at_ns: now_ns(),
+            query_plan: vec![],
+        });
+    }
+}
+
+pub(super) struct SlowQueryInstrumentation {
+    log: SlowQueryLog,
+    started: Option<Instant>,
+}
+
+impl Instrumentation for SlowQueryInstrumentation {
+    fn on_connection_event(&mut self, event: InstrumentationEvent<'_>) {
+        match event {
+            InstrumentationEvent::StartQuery { .. } if self.log.is_enabled() => {
+                self.started = Some(Instant::now());
+            }
+            InstrumentationEvent::FinishQuery { query, .. } => {
+                let Some(started) = self.started.take() else {
+                    return;
+                };
+                let duration = started.elapsed();
+                let threshold_ns = self.log.threshold_ns.load(Ordering::Relaxed);
+                if threshold_ns == 0 || duration < Duration::from_nanos(threshold_ns) {
+                    return;
+                }
+                // Diesel appends the bound values, which may hold message contents
+                let query = query.to_string();
+                let sql = query.split(" -- binds: ").next().unwrap_or_default();
+                // Computing the plans of the log must not fill it
+                if !sql.starts_with("EXPLAIN QUERY PLAN") {
+                    self.log.record(sql.to_string(), duration);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct QueryPlanStep {
+    #[diesel(sql_type = Text)]
+    detail: String,
+}
+
+impl DbConnection {
+    /// The `EXPLAIN QUERY PLAN` output of `sql`, one line per step
+    pub fn explain_query_plan(&self, sql: &str) -> Result<Vec<String>, StorageError> {
+        let steps: Vec<QueryPlanStep> =
+            self.raw_query(|conn| sql_query(format!("EXPLAIN QUERY PLAN {sql}")).load(conn))?;
+        Ok(steps.into_iter().map(|step| step.detail).collect())
+    }
+
+    /// The statements recorded in `log` with their query plans. A plan that can't be computed
+    /// is left empty.
+    pub fn slow_queries_with_plans(&self, log: &SlowQueryLog) -> Vec<SlowQuery> {
+        log.queries()
+            .into_iter()
+            .map(|mut query| {
+                match self.explain_query_plan(&query.sql) {
+                    Ok(plan) => query.query_plan = plan,
+                    Err(e) => tracing::debug!("failed to explain slow query: {e}"),
+                }
+                query
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::storage::encrypted_store::{group::GroupQueryArgs, tests::with_connection};
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_records_slow_queries_with_plans() {
+        with_connection(|conn| {
+            let log = SlowQueryLog::default();
+            conn.raw_query(|c| {
+                c.set_instrumentation(log.instrumentation());
+                Ok::<_, StorageError>(())
+            })
+            .unwrap();
+
+            conn.find_groups(GroupQueryArgs::default()).unwrap();
+            assert!(log.queries().is_empty());
+
+            log.enable(Duration::from_nanos(1));
+            conn.find_groups(GroupQueryArgs::default()).unwrap();
+            let queries = conn.slow_queries_with_plans(&log);
+            assert!(!queries.is_empty());
+            assert!(queries[0].sql.contains("groups"));
+            assert!(!queries[0].sql.contains("binds"));
+            assert!(!queries[0].query_plan.is_empty());
+        })
+        .await
+    }
+}
@@ -0,0 +1,102 @@
+//! Content types of received messages that this installation couldn't decode, with how often and
+//! when they were seen.
+use super::{
+    db_connection::DbConnection,
+    schema::unknown_content_types::{self, dsl},
+};
+use crate::storage::StorageError;
+use diesel::{dsl::sql, prelude::*, sql_types::BigInt};
+
+#[derive(Insertable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = unknown_content_types)]
+pub struct StoredUnknownContentType {
+    pub authority_id: String,
+    pub type_id: String,
+    pub version_major: i32,
+    pub version_minor: i32,
+    pub first_seen_ns: i64,
+    pub last_seen_ns: i64,
+    /// How many messages of the content type were received
+    pub message_count: i64,
+}
+
+impl DbConnection {
+    /// Count a message of an unknown content type, sent at `seen_at_ns`. Messages may be
+    /// processed out of order, so the first and last times seen only ever widen.
+    pub fn record_unknown_content_type(
+        &self,
+        authority_id: &str,
+        type_id: &str,
+        version_major: i32,
+        version_minor: i32,
+        seen_at_ns: i64,
+    ) -> Result<(), StorageError> {
+        let entry = StoredUnknownContentType {
+            authority_id: authority_id.to_string(),
+            type_id: type_id.to_string(),
+            version_major,
+            version_minor,
+            first_seen_ns: seen_at_ns,
+            last_seen_ns: seen_at_ns,
+            message_count: 1,
+        };
+        self.raw_query(|conn| {
+            diesel::insert_into(dsl::unknown_content_types)
+                .values(&entry)
+                .on_conflict((
+                    dsl::authority_id,
+                    dsl::type_id,
+                    dsl::version_major,
+                    dsl::version_minor,
+                ))
+                .do_update()
+                .set((
+                    dsl::first_seen_ns
+                        .eq(sql::<BigInt>("MIN(first_seen_ns, excluded.first_seen_ns)")),
+                    dsl::last_seen_ns.eq(sql::<BigInt>("MAX(last_seen_ns, excluded.last_seen_ns)")),
+                    dsl::message_count.eq(dsl::message_count + 1),
+                ))
+                .execute(conn)
+        })?;
+        Ok(())
+    }
+
+    /// The unknown content types seen, most recently seen first
+    pub fn unknown_content_types(&self) -> Result<Vec<StoredUnknownContentType>, StorageError> {
+        let query = dsl::unknown_content_types.order(dsl::last_seen_ns.desc());
+        Ok(self.raw_query(|conn| query.load(conn))?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::storage::encrypted_store::tests::with_connection;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_counts_messages_per_content_type() {
+        with_connection(|conn| {
+            conn.record_unknown_content_type("example.com", "sticker", 1, 0, 10)
+                .unwrap();
+            conn.record_unknown_content_type("example.com", "sticker", 1, 0, 20)
+                .unwrap();
+            // an older message processed late
+            conn.record_unknown_content_type("example.com", "sticker", 1, 0, 5)
+                .unwrap();
+            conn.record_unknown_content_type("xmtp.org", "text", 2, 0, 15)
+                .unwrap();
+
+            let seen = conn.unknown_content_types().unwrap();
+            assert_eq!(seen.len(), 2);
+            assert_eq!(seen[0].type_id, "sticker");
+            assert_eq!(seen[0].message_count, 3);
+            assert_eq!(seen[0].first_seen_ns, 5);
+            assert_eq!(seen[0].last_seen_ns, 20);
+            assert_eq!(seen[1].version_major, 2);
+        })
+        .await
+    }
+}
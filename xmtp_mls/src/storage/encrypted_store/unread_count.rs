@@ -0,0 +1,213 @@
+//! How many messages of each group this inbox hasn't read. Counts go up as messages arrive, so
+//! badges don't have to count messages, and are recounted when the group is read.
+use super::{
+    db_connection::DbConnection,
+    group_message::{ContentType, GroupMessageKind},
+    schema::{
+        group_messages::{self, dsl as messages_dsl},
+        groups::dsl as groups_dsl,
+        unread_counts::{self, dsl},
+    },
+    Sqlite,
+};
+use crate::storage::StorageError;
+use diesel::prelude::*;
+
+/// The content types counted as unread messages: the ones apps show in the message list
+pub const UNREAD_CONTENT_TYPES: [ContentType; 7] = [
+    ContentType::Text,
+    ContentType::Reply,
+    ContentType::Attachment,
+    ContentType::RemoteAttachment,
+    ContentType::TransactionReference,
+    ContentType::Poll,
+    ContentType::LinkPreview,
+];
+
+#[derive(Insertable, Identifiable, Queryable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = unread_counts)]
+#[diesel(primary_key(group_id))]
+pub struct StoredUnreadCount {
+    pub group_id: Vec<u8>,
+    /// Messages sent at or before this time have been read
+    pub last_read_ns: i64,
+    pub unread_count: i64,
+}
+
+impl DbConnection {
+    pub fn get_unread_count<GroupId: AsRef<[u8]>>(
+        &self,
+        group_id: GroupId,
+    ) -> Result<Option<StoredUnreadCount>, StorageError> {
+        let query = dsl::unread_counts.find(group_id.as_ref());
+        Ok(self.raw_query(|conn| query.first(conn).optional())?)
+    }
+
+    /// Count a message of `group_id` sent at `sent_at_ns` as unread, unless the group was read
+    /// after it was sent. Returns the new count if it changed.
+    pub fn increment_unread_count(
+        &self,
+        group_id: &[u8],
+        sent_at_ns: i64,
+    ) -> Result<Option<i64>, StorageError> {
+        Ok(self.raw_query(|conn| -> diesel::QueryResult<Option<i64>> {
+            let current: Option<StoredUnreadCount> =
+                dsl::unread_counts.find(group_id).first(conn).optional()?;
+            let (last_read_ns, unread_count) = current
+                .map(|current| (current.last_read_ns, current.unread_count))
+                .unwrap_or_default();
+            if sent_at_ns <= last_read_ns {
+                return Ok(None);
+            }
+            diesel::replace_into(dsl::unread_counts)
+                .values(StoredUnreadCount {
+                    group_id: group_id.to_vec(),
+                    last_read_ns,
+                    unread_count: unread_count + 1,
+                })
+                .execute(conn)?;
+            Ok(Some(unread_count + 1))
+        })?)
+    }
+
+    /// Mark the messages of `group_id` sent up to `read_until_ns` as read, and recount the
+    /// unread messages sent by inboxes other than `inbox_id`. The group never goes back to an
+    /// earlier read time. Returns the new count.
+    pub fn mark_group_read(
+        &self,
+        group_id: &[u8],
+        read_until_ns: i64,
+        inbox_id: &str,
+    ) -> Result<i64, StorageError> {
+        Ok(self.raw_query(|conn| -> diesel::QueryResult<i64> {
+            let last_read_ns: Option<i64> = dsl::unread_counts
+                .find(group_id)
+                .select(dsl::last_read_ns)
+                .first(conn)
+                .optional()?;
+            let last_read_ns = last_read_ns.unwrap_or_default().max(read_until_ns);
+            let unread_count = Self::unread_messages(group_id, last_read_ns, inbox_id)
+                .count()
+                .get_result(conn)?;
+            diesel::replace_into(dsl::unread_counts)
+                .values(StoredUnreadCount {
+                    group_id: group_id.to_vec(),
+                    last_read_ns,
+                    unread_count,
+                })
+                .execute(conn)?;
+            Ok(unread_count)
+        })?)
+    }
+
+    /// Recount the unread messages of every group, e.g. once the senders whose messages are
+    /// hidden changed. Returns the groups whose count changed, with their new count.
+    pub fn recount_unread_counts(
+        &self,
+        inbox_id: &str,
+    ) -> Result<Vec<(Vec<u8>, i64)>, StorageError> {
+        Ok(
+            self.raw_query(|conn| -> diesel::QueryResult<Vec<(Vec<u8>, i64)>> {
+                let group_ids: Vec<Vec<u8>> =
+                    groups_dsl::groups.select(groups_dsl::id).load(conn)?;
+                let mut changed = vec![];
+                for group_id in group_ids {
+                    let current: Option<StoredUnreadCount> =
+                        dsl::unread_counts.find(&group_id).first(conn).optional()?;
+                    let (last_read_ns, previous) = current
+                        .map(|current| (current.last_read_ns, current.unread_count))
+                        .unwrap_or_default();
+                    let unread_count = Self::unread_messages(&group_id, last_read_ns, inbox_id)
+                        .count()
+                        .get_result(conn)?;
+                    if unread_count == previous {
+                        continue;
+                    }
+                    diesel::replace_into(dsl::unread_counts)
+                        .values(StoredUnreadCount {
+                            group_id: group_id.clone(),
+                            last_read_ns,
+                            unread_count,
+                        })
+                        .execute(conn)?;
+                    changed.push((group_id, unread_count));
+                }
+                Ok(changed)
+            })?,
+        )
+    }
+
+    /// The messages of `group_id` sent after `last_read_ns` that count as unread for `inbox_id`:
+    /// the ones other inboxes sent, leaving out the hidden messages of blocked inboxes
+    fn unread_messages<'a>(
+        group_id: &'a [u8],
+        last_read_ns: i64,
+        inbox_id: &'a str,
+    ) -> group_messages::BoxedQuery<'a, Sqlite> {
+        messages_dsl::group_messages
+            .filter(messages_dsl::group_id.eq(group_id))
+            .filter(messages_dsl::kind.eq(GroupMessageKind::Application))
+            .filter(messages_dsl::content_type.eq_any(UNREAD_CONTENT_TYPES.to_vec()))
+            .filter(messages_dsl::deleted_at_ns.is_null())
+            .filter(messages_dsl::sender_inbox_id.ne(inbox_id))
+            .filter(messages_dsl::sender_inbox_id.ne_all(Self::hidden_senders()))
+            .filter(messages_dsl::sent_at_ns.gt(last_read_ns))
+            .into_boxed()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        storage::encrypted_store::{
+            group::tests::generate_group, group_message::tests::generate_message,
+            tests::with_connection,
+        },
+        Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_counts_messages_after_the_last_read() {
+        with_connection(|conn| {
+            let group = generate_group(None);
+            group.store(conn).unwrap();
+
+            assert_eq!(conn.increment_unread_count(&group.id, 10).unwrap(), Some(1));
+            assert_eq!(conn.increment_unread_count(&group.id, 20).unwrap(), Some(2));
+
+            for sent_at_ns in [10, 20, 30] {
+                generate_message(
+                    None,
+                    Some(&group.id),
+                    Some(sent_at_ns),
+                    Some(ContentType::Text),
+                )
+                .store(conn)
+                .unwrap();
+            }
+            // our own messages and reactions aren't unread
+            let mut own =
+                generate_message(None, Some(&group.id), Some(40), Some(ContentType::Text));
+            own.sender_inbox_id = "alix".to_string();
+            own.store(conn).unwrap();
+            generate_message(None, Some(&group.id), Some(50), Some(ContentType::Reaction))
+                .store(conn)
+                .unwrap();
+            assert_eq!(conn.mark_group_read(&group.id, 15, "alix").unwrap(), 2);
+
+            // the read time never moves back, and older messages arriving late stay read
+            assert_eq!(conn.mark_group_read(&group.id, 5, "alix").unwrap(), 2);
+            assert_eq!(conn.increment_unread_count(&group.id, 12).unwrap(), None);
+            assert_eq!(conn.increment_unread_count(&group.id, 60).unwrap(), Some(3));
+            let stored = conn.get_unread_count(&group.id).unwrap().unwrap();
+            assert_eq!(stored.last_read_ns, 15);
+            assert_eq!(stored.unread_count, 3);
+        })
+        .await
+    }
+}
@@ -1,7 +1,4 @@
-use crate::{
-    groups::device_sync::preference_sync::UserPreferenceUpdate, storage::StorageError,
-    subscriptions::LocalEvents, Store,
-};
+use crate::{storage::StorageError, Store};
 
 use super::{
     schema::user_preferences::{self, dsl},
@@ -9,7 +6,6 @@ use super::{
 };
 use diesel::prelude::*;
 use rand::{rngs::OsRng, RngCore};
-use tokio::sync::broadcast::Sender;
 
 #[derive(Identifiable, Queryable, AsChangeset, Debug, Clone, PartialEq, Eq, Default)]
 #[diesel(table_name = user_preferences)]
@@ -55,23 +51,15 @@ impl StoredUserPreferences {
         Ok(result.pop().unwrap_or_default())
     }
 
-    pub fn new_hmac_key<C>(
-        conn: &DbConnection,
-        local_events: &Sender<LocalEvents<C>>,
-    ) -> Result<Vec<u8>, StorageError> {
+    /// Generate and store a new hmac key root. It's up to the caller to sync it to the user's
+    /// other installations with a `UserPreferenceUpdate::HmacKeyUpdate`.
+    pub fn new_hmac_key(conn: &DbConnection) -> Result<Vec<u8>, StorageError> {
         let mut preferences = Self::load(conn)?;
 
         let mut hmac_key = vec![0; 32];
         OsRng.fill_bytes(&mut hmac_key);
         preferences.hmac_key = Some(hmac_key.clone());
 
-        // Sync the new key to other devices
-        let _ = local_events.send(LocalEvents::OutgoingPreferenceUpdates(vec![
-            UserPreferenceUpdate::HmacKeyUpdate {
-                key: hmac_key.clone(),
-            },
-        ]));
-
         let to_insert: NewStoredUserPreferences = (&preferences).into();
         conn.raw_query(|conn| {
             diesel::insert_into(dsl::user_preferences)
@@ -107,7 +95,7 @@ mod tests {
         assert!(pref.hmac_key.is_none());
 
         // set an hmac key
-        let hmac_key = StoredUserPreferences::new_hmac_key(&conn, &client.local_events).unwrap();
+        let hmac_key = StoredUserPreferences::new_hmac_key(&conn).unwrap();
         let pref = StoredUserPreferences::load(&conn).unwrap();
         // Make sure it saved
         assert_eq!(hmac_key, pref.hmac_key.unwrap());
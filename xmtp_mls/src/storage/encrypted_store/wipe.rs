@@ -0,0 +1,196 @@
+//! Deleting local data at the user's request. Deletes run with `secure_delete` on, so SQLite
+//! overwrites the freed content instead of leaving it recoverable from the database file.
+use diesel::{
+    connection::SimpleConnection,
+    prelude::*,
+    sql_query,
+    sql_types::{Binary, Nullable},
+};
+
+use super::db_connection::DbConnection;
+use crate::storage::StorageError;
+
+/// Clear what's derived from the messages of the group bound to `?1`, or of every group if it's
+/// NULL. The messages themselves are deleted last, see [`DELETE_MESSAGES`].
+const MESSAGE_STATEMENTS: &[&str] = &[
+    "DELETE FROM message_annotations WHERE message_id IN \
+     (SELECT id FROM group_messages WHERE ?1 IS NULL OR group_id = ?1)",
+    "DELETE FROM send_diagnostics WHERE message_id IN \
+     (SELECT id FROM group_messages WHERE ?1 IS NULL OR group_id = ?1)",
+    "DELETE FROM message_edits WHERE ?1 IS NULL OR group_id = ?1",
+    "DELETE FROM pending_references WHERE ?1 IS NULL OR group_id = ?1",
+    "DELETE FROM message_reactions WHERE ?1 IS NULL OR group_id = ?1",
+    "DELETE FROM message_mentions WHERE ?1 IS NULL OR group_id = ?1",
+    "DELETE FROM poll_votes WHERE ?1 IS NULL OR group_id = ?1",
+    "DELETE FROM read_cursors WHERE ?1 IS NULL OR group_id = ?1",
+    "DELETE FROM unread_counts WHERE ?1 IS NULL OR group_id = ?1",
+    "DELETE FROM conversation_summaries WHERE ?1 IS NULL OR group_id = ?1",
+];
+const DELETE_MESSAGES: &str = "DELETE FROM group_messages WHERE ?1 IS NULL OR group_id = ?1";
+
+/// Clear the rest of the records of the group bound to `?1`, and the group itself. Foreign keys
+/// aren't enforced on every connection, so nothing is left to the cascades.
+const CONVERSATION_STATEMENTS: &[&str] = &[
+    "DELETE FROM group_intents WHERE group_id = ?1",
+    "DELETE FROM group_membership_changes WHERE group_id = ?1",
+    "DELETE FROM processing_checkpoints WHERE group_id = ?1",
+    "DELETE FROM reconsent_prompts WHERE group_id = ?1",
+    "DELETE FROM conversation_scratch WHERE group_id = ?1",
+    "DELETE FROM invite_redemptions WHERE link_id IN \
+     (SELECT id FROM invite_links WHERE group_id = ?1)",
+    "DELETE FROM invite_links WHERE group_id = ?1",
+    "DELETE FROM join_requests WHERE group_id = ?1",
+    "DELETE FROM group_epochs WHERE group_id = ?1",
+    "DELETE FROM group_forks WHERE group_id = ?1",
+    "DELETE FROM attachment_uploads WHERE group_id = ?1",
+    "DELETE FROM notification_settings WHERE group_id = ?1",
+    "DELETE FROM drafts WHERE group_id = ?1",
+    "DELETE FROM imported_archives WHERE group_id = ?1",
+    // the group's message and consent shard cursors, the welcome cursor is keyed by installation
+    "DELETE FROM refresh_state WHERE entity_id = ?1",
+    "DELETE FROM groups WHERE id = ?1",
+];
+
+/// Records that aren't tied to a conversation: about other inboxes, the app's preferences, and
+/// the content types seen that couldn't be decoded
+const USER_RECORD_STATEMENTS: &str = "\
+    PRAGMA secure_delete = ON; \
+    DELETE FROM consent_records; \
+    DELETE FROM inbox_profiles; \
+    DELETE FROM installation_capabilities; \
+    DELETE FROM custom_preferences; \
+    DELETE FROM blocked_inboxes; \
+    DELETE FROM unknown_content_types;";
+
+impl DbConnection {
+    /// Delete the messages of `group_id`, or of every group with `None`, along with their edits,
+    /// reactions, annotations, send diagnostics, read cursors and summaries. Sync cursors are
+    /// kept, so the messages aren't fetched again. Returns the number of messages deleted.
+    pub fn wipe_messages(&self, group_id: Option<&[u8]>) -> Result<usize, StorageError> {
+        Ok(self.raw_query(|conn| {
+            conn.batch_execute("PRAGMA secure_delete = ON;")?;
+            // all or nothing, so no records are left pointing at deleted messages
+            conn.transaction::<_, diesel::result::Error, _>(|conn| {
+                for statement in MESSAGE_STATEMENTS {
+                    sql_query(*statement)
+                        .bind::<Nullable<Binary>, _>(group_id)
+                        .execute(conn)?;
+                }
+                sql_query(DELETE_MESSAGES)
+                    .bind::<Nullable<Binary>, _>(group_id)
+                    .execute(conn)
+            })
+        })?)
+    }
+
+    /// Delete every local record of `group_id`, including the group itself. The group's MLS
+    /// state is kept in the key store and has to be deleted separately. Returns the number of
+    /// messages deleted.
+    pub fn wipe_conversation_records(&self, group_id: &[u8]) -> Result<usize, StorageError> {
+        let deleted = self.wipe_messages(Some(group_id))?;
+        self.raw_query(|conn| {
+            for statement in CONVERSATION_STATEMENTS {
+                sql_query(*statement)
+                    .bind::<Binary, _>(group_id)
+                    .execute(conn)?;
+            }
+            Ok::<_, diesel::result::Error>(())
+        })?;
+        Ok(deleted)
+    }
+
+    /// Delete the consent records and cached profiles of other inboxes, the capabilities their
+    /// installations advertised, the block list, the app's custom preferences, and the record of
+    /// unknown content types
+    pub fn wipe_user_records(&self) -> Result<(), StorageError> {
+        Ok(self.raw_query(|conn| conn.batch_execute(USER_RECORD_STATEMENTS))?)
+    }
+
+    /// Move the write-ahead log into the database and truncate it, so deleted content doesn't
+    /// linger in the log. Has no effect inside a transaction.
+    pub fn truncate_wal(&self) -> Result<(), StorageError> {
+        Ok(self.raw_query(|conn| conn.batch_execute("PRAGMA wal_checkpoint(TRUNCATE);"))?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{
+        hlc::Hlc,
+        storage::encrypted_store::{
+            blocked_inbox::StoredBlockedInbox,
+            group::{tests::generate_group, StoredGroup},
+            group_message::tests::generate_message,
+            installation_capability::StoredInstallationCapabilities,
+            tests::with_connection,
+        },
+        Fetch, Store,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::capabilities::Capabilities;
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn it_wipes_messages_and_conversations() {
+        with_connection(|conn| {
+            let kept = generate_group(None);
+            let wiped = generate_group(None);
+            for group in [&kept, &wiped] {
+                group.store(conn).unwrap();
+                for _ in 0..2 {
+                    generate_message(None, Some(&group.id), None, None)
+                        .store(conn)
+                        .unwrap();
+                }
+            }
+
+            assert_eq!(conn.wipe_messages(Some(&wiped.id)).unwrap(), 2);
+            assert!(conn
+                .get_group_messages(&wiped.id, &Default::default())
+                .unwrap()
+                .is_empty());
+            assert_eq!(
+                conn.get_group_messages(&kept.id, &Default::default())
+                    .unwrap()
+                    .len(),
+                2
+            );
+
+            assert_eq!(conn.wipe_conversation_records(&kept.id).unwrap(), 2);
+            let group: Option<StoredGroup> = conn.fetch(&kept.id).unwrap();
+            assert!(group.is_none());
+            let group: Option<StoredGroup> = conn.fetch(&wiped.id).unwrap();
+            assert!(group.is_some());
+
+            assert_eq!(conn.wipe_messages(None).unwrap(), 0);
+            let capabilities = StoredInstallationCapabilities::new(
+                vec![1],
+                "inbox".to_string(),
+                &Capabilities::default(),
+                1,
+            )
+            .unwrap();
+            conn.upsert_installation_capabilities(&capabilities)
+                .unwrap();
+            let hlc = Hlc {
+                wall_ns: 1,
+                counter: 0,
+                node: vec![1],
+            };
+            conn.set_blocked_inbox(&StoredBlockedInbox::new("bo".to_string(), true, true, hlc))
+                .unwrap();
+            conn.record_unknown_content_type("example.com", "sticker", 1, 0, 10)
+                .unwrap();
+            conn.wipe_user_records().unwrap();
+            assert!(conn
+                .get_installation_capabilities(&capabilities.installation_id)
+                .unwrap()
+                .is_none());
+            assert!(conn.all_blocked_inboxes().unwrap().is_empty());
+            assert!(conn.unknown_content_types().unwrap().is_empty());
+        })
+        .await
+    }
+}
@@ -79,6 +79,8 @@ pub enum NotFound {
     RefreshStateByIdAndKind(Vec<u8>, EntityKind),
     #[error("Cipher salt for db at [`{0}`] not found")]
     CipherSalt(String),
+    #[error("attachment upload with id {id} not found", id = hex::encode(_0))]
+    AttachmentUpload(Vec<u8>),
 }
 
 #[derive(Error, Debug)]
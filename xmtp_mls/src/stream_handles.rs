@@ -1,6 +1,13 @@
 //! Consistent Stream behavior between WebAssembly and Native utilizing `tokio::task::spawn` in native and
 //! `wasm_bindgen_futures::spawn` for web.
-use futures::FutureExt;
+use crate::subscriptions::SubscribeError;
+use futures::{FutureExt, Stream, StreamExt};
+use std::sync::{
+    atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
+    Arc,
+};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
 
 #[cfg(target_arch = "wasm32")]
 pub type GenericStreamHandle<O> = dyn StreamHandle<StreamOutput = O>;
@@ -21,6 +28,225 @@ pub enum StreamHandleError {
     #[error("Stream Panicked With {0}")]
     Panicked(String),
 }
+/// Counters describing the health of a spawned stream, updated by the stream as it runs.
+/// Cloning is cheap; every clone reads the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct StreamMetrics(Arc<StreamMetricsInner>);
+
+#[derive(Debug, Default)]
+struct StreamMetricsInner {
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+    cursor: AtomicU64,
+    last_item_ns: AtomicI64,
+}
+
+impl StreamMetrics {
+    /// Items and errors passed to the stream's consumer
+    pub fn items_delivered(&self) -> u64 {
+        self.0.delivered.load(Ordering::Relaxed)
+    }
+
+    /// Items the stream skipped because it fell behind the local event queue
+    pub fn items_dropped(&self) -> u64 {
+        self.0.dropped.load(Ordering::Relaxed)
+    }
+
+    /// The highest network cursor the stream has processed, if it follows one
+    pub fn cursor(&self) -> Option<u64> {
+        Some(self.0.cursor.load(Ordering::Relaxed)).filter(|c| *c > 0)
+    }
+
+    /// When the last item was delivered
+    pub fn last_item_ns(&self) -> Option<i64> {
+        Some(self.0.last_item_ns.load(Ordering::Relaxed)).filter(|ns| *ns > 0)
+    }
+
+    /// Record an item about to be passed to the stream's consumer
+    pub(crate) fn record<T>(&self, item: &Result<T, SubscribeError>) {
+        match item {
+            Err(SubscribeError::LaggedEvents(missed)) => self.record_dropped(*missed),
+            _ => self.record_delivered(),
+        }
+    }
+
+    pub(crate) fn record_delivered(&self) {
+        self.0.delivered.fetch_add(1, Ordering::Relaxed);
+        self.0
+            .last_item_ns
+            .store(xmtp_common::time::now_ns(), Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_dropped(&self, count: u64) {
+        self.0.dropped.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_cursor(&self, cursor: u64) {
+        self.0.cursor.fetch_max(cursor, Ordering::Relaxed);
+    }
+}
+
+/// Shared pause state of a stream that can be suspended, e.g. while the app is in the
+/// background. Cloning is cheap; every clone controls the same stream.
+#[derive(Debug, Clone, Default)]
+pub struct PauseToken(Arc<PauseTokenInner>);
+
+#[derive(Debug, Default)]
+struct PauseTokenInner {
+    paused: AtomicBool,
+    changed: Notify,
+}
+
+impl PauseToken {
+    pub fn pause(&self) {
+        self.set_paused(true)
+    }
+
+    pub fn resume(&self) {
+        self.set_paused(false)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.paused.load(Ordering::SeqCst)
+    }
+
+    fn set_paused(&self, paused: bool) {
+        if self.0.paused.swap(paused, Ordering::SeqCst) != paused {
+            self.0.changed.notify_waiters();
+        }
+    }
+
+    /// Resolves once the stream is paused
+    pub async fn paused(self) {
+        self.wait_for(true).await
+    }
+
+    /// Resolves once the stream is resumed
+    pub async fn resumed(self) {
+        self.wait_for(false).await
+    }
+
+    async fn wait_for(&self, paused: bool) {
+        loop {
+            // created before checking, so a change in between can't be missed
+            let changed = self.0.changed.notified();
+            if self.is_paused() == paused {
+                return;
+            }
+            changed.await;
+        }
+    }
+}
+
+/// What a spawned stream shares with its handle besides ending it: its metrics, and the
+/// tokens to close it gracefully and to pause it if it supports that
+#[derive(Debug, Clone, Default)]
+pub struct StreamControls {
+    metrics: StreamMetrics,
+    cancel: Option<CancellationToken>,
+    pause: Option<PauseToken>,
+}
+
+/// Yield the items of `stream` until `closing` is cancelled. Unlike `take_until`, the items
+/// `stream` already has ready when it's cancelled are still yielded, so adapters that buffer
+/// items, e.g. [`crate::subscriptions::with_batching`], flush them once `stream` ends.
+pub fn until_closed<S: Stream>(
+    stream: S,
+    closing: CancellationToken,
+) -> impl Stream<Item = S::Item> {
+    async_stream::stream! {
+        let stream = stream.fuse();
+        futures::pin_mut!(stream);
+        loop {
+            let next = tokio::select! {
+                biased;
+                next = stream.next() => next,
+                _ = closing.cancelled() => break,
+            };
+            match next {
+                Some(item) => yield item,
+                None => break,
+            }
+        }
+        while let Some(Some(item)) = stream.next().now_or_never() {
+            yield item;
+        }
+    }
+}
+
+/// The task side of a stream spawned with [`spawn_stream_task`]
+pub(crate) struct StreamTask {
+    ready: Option<tokio::sync::oneshot::Sender<()>>,
+    metrics: StreamMetrics,
+    closing: CancellationToken,
+    pause: PauseToken,
+}
+
+impl StreamTask {
+    fn new() -> (Self, tokio::sync::oneshot::Receiver<()>, StreamControls) {
+        let (ready, ready_rx) = tokio::sync::oneshot::channel();
+        let task = Self {
+            ready: Some(ready),
+            metrics: StreamMetrics::default(),
+            closing: CancellationToken::new(),
+            pause: PauseToken::default(),
+        };
+        let controls = StreamControls {
+            metrics: task.metrics.clone(),
+            cancel: Some(task.closing.clone()),
+            pause: Some(task.pause.clone()),
+        };
+        (task, ready_rx, controls)
+    }
+
+    pub(crate) fn metrics(&self) -> &StreamMetrics {
+        &self.metrics
+    }
+
+    /// Stop pulling from `stream` once the handle closes the stream gracefully
+    pub(crate) fn until_closed<S: Stream>(&self, stream: S) -> impl Stream<Item = S::Item> {
+        until_closed(stream, self.closing.clone())
+    }
+
+    /// Mark the stream as ready, then pass each item of `stream` to `callback` until it ends.
+    /// `stream` should be limited with [`Self::until_closed`].
+    ///
+    /// While the handle has the stream paused, `stream` isn't polled, but it isn't dropped
+    /// either, so its subscriptions and their cursors are kept and resuming picks up where
+    /// it left off.
+    pub(crate) async fn run<S, Fut>(
+        mut self,
+        stream: S,
+        mut callback: impl FnMut(&StreamMetrics, S::Item) -> Fut,
+    ) where
+        S: Stream,
+        Fut: std::future::Future<Output = ()>,
+    {
+        futures::pin_mut!(stream);
+        if let Some(ready) = self.ready.take() {
+            let _ = ready.send(());
+        }
+        loop {
+            let next = tokio::select! {
+                biased;
+                // once closing, `stream` is drained instead
+                _ = self.pause.clone().paused(), if !self.closing.is_cancelled() => {
+                    tokio::select! {
+                        _ = self.pause.clone().resumed() => {}
+                        _ = self.closing.cancelled() => {}
+                    }
+                    continue;
+                }
+                next = stream.next() => next,
+            };
+            let Some(item) = next else {
+                break;
+            };
+            callback(&self.metrics, item).await
+        }
+    }
+}
+
 /// A handle to a spawned Stream
 /// the spawned stream can be 'joined` by awaiting its Future implementation.
 /// All spawned tasks are detached, so waiting the handle is not required.
@@ -37,6 +263,12 @@ pub trait StreamHandle {
     /// Does not wait for the stream to end, so will not receive the result of stream.
     fn end(&self);
 
+    /// Wait for the stream to end on its own, getting the result of its execution.
+    async fn wait_for_end(&mut self) -> Result<Self::StreamOutput, StreamHandleError>;
+
+    /// The metrics and tokens the stream was spawned with
+    fn controls(&self) -> &StreamControls;
+
     // Its better to:
     // `StreamHandle: Future<Output = Result<Self::StreamOutput,StreamHandleError>>`
     // but then crate::spawn` generates `Unused future must be used` since
@@ -49,11 +281,62 @@ pub trait StreamHandle {
 
     /// End the stream and asynchronously wait for it to shutdown, getting the result of its
     /// execution.
-    async fn end_and_wait(&mut self) -> Result<Self::StreamOutput, StreamHandleError>;
+    async fn end_and_wait(&mut self) -> Result<Self::StreamOutput, StreamHandleError> {
+        self.end();
+        self.wait_for_end().await
+    }
     /// Get an Abort Handle to the stream.
     /// This handle may be cloned/sent/etc easily
     /// and many handles may exist at once.
     fn abort_handle(&self) -> Box<dyn AbortHandle>;
+
+    /// Statistics about the items the stream has delivered so far,
+    /// useful to detect an unhealthy stream that should be restarted.
+    fn metrics(&self) -> StreamMetrics {
+        self.controls().metrics.clone()
+    }
+
+    /// A token that closes the stream gracefully when cancelled, like
+    /// [`close_gracefully`](Self::close_gracefully). Clones can be handed to every owner
+    /// that may need to shut the stream down. `None` if the stream can only be ended.
+    fn cancellation_token(&self) -> Option<CancellationToken> {
+        self.controls().cancel.clone()
+    }
+
+    /// Stop pulling new items from the network, let the item currently being handled finish,
+    /// then wait for the stream to end. Unlike [`end`](Self::end), an item that was already
+    /// received is never dropped halfway through its callback. Streams without a
+    /// cancellation token are ended instead.
+    async fn close_gracefully(&mut self) -> Result<Self::StreamOutput, StreamHandleError> {
+        match self.cancellation_token() {
+            Some(cancel) => {
+                cancel.cancel();
+                self.wait_for_end().await
+            }
+            None => self.end_and_wait().await,
+        }
+    }
+
+    /// Suspend pulling from the network, keeping the stream's position, e.g. while the app is
+    /// in the background. Returns `false` if the stream can't be paused.
+    fn pause(&self) -> bool {
+        self.controls()
+            .pause
+            .as_ref()
+            .map(PauseToken::pause)
+            .is_some()
+    }
+
+    /// Resume a paused stream. What arrived on its subscriptions while paused is delivered
+    /// first, then live delivery carries on. A subscription whose connection dropped in the
+    /// meantime resumes from its own cursor. Returns `false` if the stream can't be paused.
+    fn resume(&self) -> bool {
+        self.controls()
+            .pause
+            .as_ref()
+            .map(PauseToken::resume)
+            .is_some()
+    }
 }
 
 /// A handle that can be moved/cloned/sent, but can only close the stream.
@@ -88,6 +371,7 @@ mod wasm {
         // so we use mpsc here to keep the `&self` on `end`.
         closer: tokio::sync::mpsc::Sender<()>,
         ready: Option<tokio::sync::oneshot::Receiver<()>>,
+        controls: StreamControls,
     }
 
     impl<T> Future for WasmStreamHandle<Result<T, StreamHandleError>> {
@@ -114,8 +398,7 @@ mod wasm {
             }
         }
 
-        async fn end_and_wait(&mut self) -> Result<Self::StreamOutput, StreamHandleError> {
-            self.end();
+        async fn wait_for_end(&mut self) -> Result<Self::StreamOutput, StreamHandleError> {
             self.await
         }
 
@@ -127,6 +410,10 @@ mod wasm {
             Box::new(CloseHandle(self.closer.clone()))
         }
 
+        fn controls(&self) -> &StreamControls {
+            &self.controls
+        }
+
         async fn join(self) -> Result<Self::StreamOutput, StreamHandleError> {
             self.await
         }
@@ -151,6 +438,89 @@ mod wasm {
         ready: Option<tokio::sync::oneshot::Receiver<()>>,
         future: F,
     ) -> impl StreamHandle<StreamOutput = F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        spawn_with_metrics(ready, StreamMetrics::default(), future)
+    }
+
+    /// [`spawn`], reporting `metrics` from the returned handle
+    pub fn spawn_with_metrics<F>(
+        ready: Option<tokio::sync::oneshot::Receiver<()>>,
+        metrics: StreamMetrics,
+        future: F,
+    ) -> impl StreamHandle<StreamOutput = F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let controls = StreamControls {
+            metrics,
+            ..Default::default()
+        };
+        spawn_inner(ready, controls, future)
+    }
+
+    /// [`spawn_with_metrics`] for a future that stops pulling new items and finishes
+    /// once `cancel` is cancelled, which the returned handle uses to close gracefully
+    pub fn spawn_cancellable<F>(
+        ready: Option<tokio::sync::oneshot::Receiver<()>>,
+        metrics: StreamMetrics,
+        cancel: CancellationToken,
+        future: F,
+    ) -> impl StreamHandle<StreamOutput = F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let controls = StreamControls {
+            metrics,
+            cancel: Some(cancel),
+            pause: None,
+        };
+        spawn_inner(ready, controls, future)
+    }
+
+    /// [`spawn_cancellable`] for a future that suspends its network pulls while `pause` is
+    /// paused, which the returned handle uses to pause and resume
+    pub fn spawn_pausable<F>(
+        ready: Option<tokio::sync::oneshot::Receiver<()>>,
+        metrics: StreamMetrics,
+        cancel: CancellationToken,
+        pause: PauseToken,
+        future: F,
+    ) -> impl StreamHandle<StreamOutput = F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let controls = StreamControls {
+            metrics,
+            cancel: Some(cancel),
+            pause: Some(pause),
+        };
+        spawn_inner(ready, controls, future)
+    }
+
+    /// Spawn the task `task` builds from a [`StreamTask`], closed gracefully by the handle
+    pub(crate) fn spawn_stream_task<F, Fut>(
+        task: F,
+    ) -> impl StreamHandle<StreamOutput = Fut::Output>
+    where
+        F: FnOnce(StreamTask) -> Fut,
+        Fut: Future + 'static,
+        Fut::Output: 'static,
+    {
+        let (stream_task, ready, controls) = StreamTask::new();
+        spawn_inner(Some(ready), controls, task(stream_task))
+    }
+
+    fn spawn_inner<F>(
+        ready: Option<tokio::sync::oneshot::Receiver<()>>,
+        controls: StreamControls,
+        future: F,
+    ) -> impl StreamHandle<StreamOutput = F::Output>
     where
         F: Future + 'static,
         F::Output: 'static,
@@ -163,6 +533,7 @@ mod wasm {
             result: res_rx,
             closer: closer_tx,
             ready,
+            controls,
         };
         tracing::info!("Spawning local task on web executor");
         wasm_bindgen_futures::spawn_local(async move {
@@ -217,6 +588,7 @@ mod native {
     pub struct TokioStreamHandle<T> {
         inner: JoinHandle<T>,
         ready: Option<tokio::sync::oneshot::Receiver<()>>,
+        controls: StreamControls,
     }
 
     impl<T> Future for TokioStreamHandle<T> {
@@ -244,10 +616,9 @@ mod native {
             self.inner.abort();
         }
 
-        async fn end_and_wait(&mut self) -> Result<Self::StreamOutput, StreamHandleError> {
+        async fn wait_for_end(&mut self) -> Result<Self::StreamOutput, StreamHandleError> {
             use crate::StreamHandleError::*;
 
-            self.end();
             match self.await {
                 Err(JoinHandleError(e)) if e.is_panic() => Err(Panicked(e.to_string())),
                 Err(JoinHandleError(e)) if e.is_cancelled() => Err(Cancelled),
@@ -260,6 +631,10 @@ mod native {
             Box::new(self.inner.abort_handle())
         }
 
+        fn controls(&self) -> &StreamControls {
+            &self.controls
+        }
+
         async fn join(self) -> Result<Self::StreamOutput, StreamHandleError> {
             self.await
         }
@@ -279,6 +654,89 @@ mod native {
         ready: Option<tokio::sync::oneshot::Receiver<()>>,
         future: F,
     ) -> impl StreamHandle<StreamOutput = F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        spawn_with_metrics(ready, StreamMetrics::default(), future)
+    }
+
+    /// [`spawn`], reporting `metrics` from the returned handle
+    pub fn spawn_with_metrics<F>(
+        ready: Option<tokio::sync::oneshot::Receiver<()>>,
+        metrics: StreamMetrics,
+        future: F,
+    ) -> impl StreamHandle<StreamOutput = F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let controls = StreamControls {
+            metrics,
+            ..Default::default()
+        };
+        spawn_inner(ready, controls, future)
+    }
+
+    /// [`spawn_with_metrics`] for a future that stops pulling new items and finishes
+    /// once `cancel` is cancelled, which the returned handle uses to close gracefully
+    pub fn spawn_cancellable<F>(
+        ready: Option<tokio::sync::oneshot::Receiver<()>>,
+        metrics: StreamMetrics,
+        cancel: CancellationToken,
+        future: F,
+    ) -> impl StreamHandle<StreamOutput = F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let controls = StreamControls {
+            metrics,
+            cancel: Some(cancel),
+            pause: None,
+        };
+        spawn_inner(ready, controls, future)
+    }
+
+    /// [`spawn_cancellable`] for a future that suspends its network pulls while `pause` is
+    /// paused, which the returned handle uses to pause and resume
+    pub fn spawn_pausable<F>(
+        ready: Option<tokio::sync::oneshot::Receiver<()>>,
+        metrics: StreamMetrics,
+        cancel: CancellationToken,
+        pause: PauseToken,
+        future: F,
+    ) -> impl StreamHandle<StreamOutput = F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let controls = StreamControls {
+            metrics,
+            cancel: Some(cancel),
+            pause: Some(pause),
+        };
+        spawn_inner(ready, controls, future)
+    }
+
+    /// Spawn the task `task` builds from a [`StreamTask`], closed gracefully by the handle
+    pub(crate) fn spawn_stream_task<F, Fut>(
+        task: F,
+    ) -> impl StreamHandle<StreamOutput = Fut::Output>
+    where
+        F: FnOnce(StreamTask) -> Fut,
+        Fut: Future + Send + 'static,
+        Fut::Output: Send + 'static,
+    {
+        let (stream_task, ready, controls) = StreamTask::new();
+        spawn_inner(Some(ready), controls, task(stream_task))
+    }
+
+    fn spawn_inner<F>(
+        ready: Option<tokio::sync::oneshot::Receiver<()>>,
+        controls: StreamControls,
+        future: F,
+    ) -> TokioStreamHandle<F::Output>
     where
         F: Future + Send + 'static,
         F::Output: Send + 'static,
@@ -286,6 +744,7 @@ mod native {
         TokioStreamHandle {
             inner: tokio::task::spawn(future),
             ready,
+            controls,
         }
     }
 }
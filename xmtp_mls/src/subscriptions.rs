@@ -1,32 +1,52 @@
-use futures::{FutureExt, Stream, StreamExt};
+use futures::{Stream, StreamExt};
 use prost::Message;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    sync::Arc,
+    time::Duration,
+};
 use tokio::{
     sync::{broadcast, oneshot},
     task::JoinHandle,
 };
-use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
 use tracing::instrument;
 use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
 use xmtp_proto::{api_client::XmtpMlsStreams, xmtp::mls::api::v1::WelcomeMessage};
 
 use crate::{
     client::{extract_welcome_message, ClientError},
+    connectivity::ConnectivityState,
     groups::{
-        device_sync::preference_sync::UserPreferenceUpdate, group_metadata::GroupMetadata,
-        mls_sync::GroupMessageProcessingError, scoped_client::ScopedGroupClient as _,
+        device_sync::{preference_sync::UserPreferenceUpdate, progress::SyncProgress},
+        group_metadata::GroupMetadata,
+        guests::GuestExpiration,
+        inactivity::ReconsentRequest,
+        mentions::mentions_inbox,
+        mls_sync::GroupMessageProcessingError,
+        scoped_client::ScopedGroupClient as _,
+        stale_installations::StaleInstallationReport,
         subscriptions, GroupError, MlsGroup,
     },
     storage::{
-        consent_record::StoredConsentRecord,
+        consent_record::{ConsentState, ConsentType, StoredConsentRecord},
         group::{ConversationType, GroupQueryArgs, StoredGroup},
-        group_message::StoredGroupMessage,
+        group_fork::StoredGroupFork,
+        group_message::{ContentType, DeliveryStatus, MsgQueryArgs, StoredGroupMessage},
+        join_request::StoredJoinRequest,
+        refresh_state::EntityKind,
         ProviderTransactions, StorageError,
     },
-    Client, XmtpApi, XmtpOpenMlsProvider,
+    stream_handles::spawn_stream_task,
+    Client, StreamMetrics, XmtpApi, XmtpOpenMlsProvider,
 };
 use thiserror::Error;
-use xmtp_common::{retry_async, retryable, Retry, RetryableError};
+use xmtp_common::{
+    retry_async, retryable,
+    time::{now_ns, Instant},
+    Retry, RetryableError,
+};
 
 #[derive(Debug, Error)]
 pub enum LocalEventError {
@@ -40,6 +60,47 @@ impl RetryableError for LocalEventError {
     }
 }
 
+/// Default number of events the local event queue holds for its slowest consumer
+pub const DEFAULT_LOCAL_EVENT_CAPACITY: usize = 32;
+
+/// How often a producer re-checks a full queue while applying backpressure
+const BACKPRESSURE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Configures the queue [`LocalEvents`] are broadcast on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalEventQueueOptions {
+    /// Number of events held for the slowest consumer, at least 1. A consumer that falls
+    /// further behind skips the oldest events and receives [`SubscribeError::LaggedEvents`],
+    /// or [`ReceivedLocalEvent::Lagged`] from a [`LocalEventReceiver`], instead.
+    ///
+    /// [`ReceivedLocalEvent::Lagged`]: crate::local_events::ReceivedLocalEvent::Lagged
+    /// [`LocalEventReceiver`]: crate::local_events::LocalEventReceiver
+    pub capacity: usize,
+    /// When set, producers hold off publishing while the queue is full, for at most this long,
+    /// giving slow consumers a chance to catch up before they lag. Synchronous APIs, like
+    /// [`Client::create_group`], can't wait and publish right away.
+    pub backpressure: Option<Duration>,
+}
+
+impl Default for LocalEventQueueOptions {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_LOCAL_EVENT_CAPACITY,
+            backpressure: None,
+        }
+    }
+}
+
+impl LocalEventQueueOptions {
+    /// A queue of `capacity` events, at least 1, where producers wait up to `max_wait` for room
+    pub fn bounded(capacity: usize, max_wait: Duration) -> Self {
+        Self {
+            capacity,
+            backpressure: Some(max_wait),
+        }
+    }
+}
+
 #[derive(Debug)]
 /// Wrapper around a [`tokio::task::JoinHandle`] but with a oneshot receiver
 /// which allows waiting for a `with_callback` stream fn to be ready for stream items.
@@ -51,15 +112,288 @@ pub struct StreamHandle<T> {
 /// Events local to this client
 /// are broadcast across all senders/receivers of streams
 #[derive(Clone)]
+#[non_exhaustive]
 pub enum LocalEvents<C> {
     // a new group was created
     NewGroup(MlsGroup<C>),
+    // internal to device sync, never yielded by `LocalEventReceiver`
+    #[doc(hidden)]
     SyncMessage(SyncMessage),
+    #[doc(hidden)]
     OutgoingPreferenceUpdates(Vec<UserPreferenceUpdate>),
     IncomingPreferenceUpdate(Vec<UserPreferenceUpdate>),
+    // inactive conversations were flagged for re-consent
+    ReconsentRequested(Vec<ReconsentRequest>),
+    // guests whose membership of a group ends soon
+    GuestsExpiring(Vec<GuestExpiration>),
+    // this installation is no longer a member of the group
+    ConversationRemoved(Vec<u8>, ConversationRemovalReason),
+    // an outgoing message was queued, published or failed to send
+    DeliveryStatusUpdate(DeliveryStatusUpdate),
+    // a commit advanced the epoch of a group
+    EpochChanged(EpochChange),
+    // this installation can no longer decrypt a group's messages
+    GroupForked(GroupFork),
+    // a forked group was added back and its messages can be decrypted again
+    GroupRecovered(GroupFork),
+    // the content of a message was replaced by an edit
+    MessageEdited(MessageEdit),
+    // a message was deleted and its content erased
+    MessageDeleted(MessageDeletion),
+    // a message was pinned or unpinned
+    MessagePinned(MessagePin),
+    // a vote in a poll was counted
+    PollUpdated(PollUpdate),
+    // the number of unread messages in a group changed
+    UnreadCountChanged(UnreadCountUpdate),
+    // a non-member asked to join a group this client is a member of
+    JoinRequest(PendingJoinRequest),
+    // groups this client administers have members with only stale installations
+    StaleInstallationsDetected(Vec<StaleInstallationReport>),
+    // local data was deleted at the user's request
+    LocalDataWiped(WipeScope),
+    // the client went offline or came back online
+    ConnectivityChanged(ConnectivityState),
+    // device sync moved on to a new step or item
+    SyncProgress(SyncProgress),
+    // processing of a group's messages was deferred to keep it from flooding the sync; syncing
+    // the group again picks up the rest
+    RateLimited(Vec<u8>),
 }
 
-#[derive(Clone)]
+impl LocalEvents<()> {
+    /// This event for subscribers of client `C`. Events are deferred until their transaction
+    /// commits without a client, which only a new group carries, so it has no equivalent.
+    pub(crate) fn for_client<C>(self) -> Option<LocalEvents<C>> {
+        Some(match self {
+            Self::NewGroup(_) => return None,
+            Self::SyncMessage(message) => LocalEvents::SyncMessage(message),
+            Self::OutgoingPreferenceUpdates(updates) => {
+                LocalEvents::OutgoingPreferenceUpdates(updates)
+            }
+            Self::IncomingPreferenceUpdate(updates) => {
+                LocalEvents::IncomingPreferenceUpdate(updates)
+            }
+            Self::ReconsentRequested(requests) => LocalEvents::ReconsentRequested(requests),
+            Self::GuestsExpiring(guests) => LocalEvents::GuestsExpiring(guests),
+            Self::ConversationRemoved(group_id, reason) => {
+                LocalEvents::ConversationRemoved(group_id, reason)
+            }
+            Self::DeliveryStatusUpdate(update) => LocalEvents::DeliveryStatusUpdate(update),
+            Self::EpochChanged(change) => LocalEvents::EpochChanged(change),
+            Self::GroupForked(fork) => LocalEvents::GroupForked(fork),
+            Self::GroupRecovered(fork) => LocalEvents::GroupRecovered(fork),
+            Self::MessageEdited(edit) => LocalEvents::MessageEdited(edit),
+            Self::MessageDeleted(deletion) => LocalEvents::MessageDeleted(deletion),
+            Self::MessagePinned(pin) => LocalEvents::MessagePinned(pin),
+            Self::PollUpdated(update) => LocalEvents::PollUpdated(update),
+            Self::UnreadCountChanged(update) => LocalEvents::UnreadCountChanged(update),
+            Self::JoinRequest(request) => LocalEvents::JoinRequest(request),
+            Self::StaleInstallationsDetected(reports) => {
+                LocalEvents::StaleInstallationsDetected(reports)
+            }
+            Self::LocalDataWiped(scope) => LocalEvents::LocalDataWiped(scope),
+            Self::ConnectivityChanged(state) => LocalEvents::ConnectivityChanged(state),
+            Self::SyncProgress(progress) => LocalEvents::SyncProgress(progress),
+            Self::RateLimited(group_id) => LocalEvents::RateLimited(group_id),
+        })
+    }
+}
+
+/// A commit merged into a group, moving it to a new epoch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpochChange {
+    pub group_id: Vec<u8>,
+    /// The epoch the group is in after the commit
+    pub epoch: u64,
+    pub originator_inbox_id: String,
+    pub originator_installation_id: Vec<u8>,
+    /// Whether the commit carried an update path, replacing the committer's leaf key and the
+    /// path secrets, as needed for forward secrecy and post-compromise security
+    pub key_rotation: bool,
+}
+
+/// A group whose copy on this installation diverged from the other members'
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupFork {
+    pub group_id: Vec<u8>,
+    /// The number of messages in a row that couldn't be decrypted
+    pub undecryptable_messages: u32,
+    pub forked_at_ns: i64,
+}
+
+impl From<StoredGroupFork> for GroupFork {
+    fn from(fork: StoredGroupFork) -> Self {
+        Self {
+            group_id: fork.group_id,
+            undecryptable_messages: fork.undecryptable_messages as u32,
+            forked_at_ns: fork.forked_at_ns.unwrap_or(fork.first_failed_at_ns),
+        }
+    }
+}
+
+/// A change in whether a group is forked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupForkEvent {
+    Forked(GroupFork),
+    Recovered(GroupFork),
+}
+
+/// An edit applied to a stored message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageEdit {
+    pub group_id: Vec<u8>,
+    /// The edited message, which now has the new content
+    pub message_id: Vec<u8>,
+    /// The message carrying the edit
+    pub edit_message_id: Vec<u8>,
+    /// The version the edit created, starting at 1 for the first edit
+    pub version: i32,
+}
+
+/// A message deleted by its sender or an admin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageDeletion {
+    pub group_id: Vec<u8>,
+    /// The deleted message, which no longer has any content
+    pub message_id: Vec<u8>,
+    /// The message carrying the deletion
+    pub delete_message_id: Vec<u8>,
+    pub deleted_by_inbox_id: String,
+}
+
+/// A message pinned to or unpinned from a group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessagePin {
+    pub group_id: Vec<u8>,
+    pub message_id: Vec<u8>,
+    /// `false` if the message was unpinned
+    pub pinned: bool,
+    pub changed_by_inbox_id: String,
+}
+
+/// The tally of a poll after a vote was counted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PollUpdate {
+    pub group_id: Vec<u8>,
+    /// The message that created the poll
+    pub poll_id: Vec<u8>,
+    /// The number of votes for each option, in the order of the poll's options
+    pub tally: Vec<u32>,
+    pub voter_inbox_id: String,
+}
+
+/// The number of messages in a group this inbox hasn't read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnreadCountUpdate {
+    pub group_id: Vec<u8>,
+    pub unread_count: u64,
+}
+
+/// A request to join a group, waiting for an admin to approve or reject it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingJoinRequest {
+    pub group_id: Vec<u8>,
+    /// Identifies the request when approving or rejecting it
+    pub request_id: Vec<u8>,
+    pub inbox_id: String,
+    pub note: Option<String>,
+    pub requested_at_ns: i64,
+    /// The sender is a member whose copy of the group forked, and asks to be added back
+    pub rejoin: bool,
+}
+
+impl From<StoredJoinRequest> for PendingJoinRequest {
+    fn from(request: StoredJoinRequest) -> Self {
+        Self {
+            group_id: request.group_id,
+            request_id: request.message_id,
+            inbox_id: request.inbox_id,
+            note: request.note,
+            requested_at_ns: request.requested_at_ns,
+            rejoin: request.rejoin,
+        }
+    }
+}
+
+/// A change in the delivery status of a message sent by this installation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeliveryStatusUpdate {
+    pub group_id: Vec<u8>,
+    pub message_id: Vec<u8>,
+    pub status: DeliveryStatus,
+}
+
+/// Why this installation stopped being a member of a conversation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConversationRemovalReason {
+    /// This client's inbox was removed from the group
+    InboxRemoved,
+    /// Only this installation was removed, e.g. because it was revoked
+    InstallationRemoved,
+}
+
+/// What a wipe of local data deleted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WipeScope {
+    /// The messages of every conversation
+    Messages,
+    /// One conversation
+    Conversation {
+        group_id: Vec<u8>,
+        /// Whether the group itself and its MLS state were deleted, or only its messages
+        mls_state: bool,
+    },
+    /// Every conversation and the records of other inboxes
+    All,
+}
+
+/// An item of [`Client::stream_conversation_updates`]
+pub enum ConversationUpdate<C> {
+    /// A conversation was created or joined
+    Added(MlsGroup<C>),
+    /// This installation was removed from a conversation
+    Removed {
+        group_id: Vec<u8>,
+        reason: ConversationRemovalReason,
+    },
+}
+
+/// A single event from [`Client::stream_events`], covering every kind of
+/// event a client can stream.
+pub enum ClientEvent<C> {
+    /// A new message in any conversation
+    Message(StoredGroupMessage),
+    /// A new conversation was created or joined
+    Conversation(MlsGroup<C>),
+    /// Consent records changed locally or were synced from another installation
+    Consent(Vec<StoredConsentRecord>),
+    /// Preferences (other than consent) changed locally or were synced from another installation
+    Preferences(Vec<UserPreferenceUpdate>),
+    /// This installation was removed from a conversation
+    ConversationRemoved {
+        group_id: Vec<u8>,
+        reason: ConversationRemovalReason,
+    },
+}
+
+impl<C> ClientEvent<C> {
+    /// Events that don't need to reach the user right away: read receipts, reactions and
+    /// preference updates. Held back during [`QuietHours`].
+    pub fn is_low_priority(&self) -> bool {
+        match self {
+            ClientEvent::Message(message) => matches!(
+                message.content_type,
+                ContentType::ReadReceipt | ContentType::Reaction
+            ),
+            ClientEvent::Consent(_) | ClientEvent::Preferences(_) => true,
+            ClientEvent::Conversation(_) | ClientEvent::ConversationRemoved { .. } => false,
+        }
+    }
+}
+
+#[doc(hidden)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SyncMessage {
     Request { message_id: Vec<u8> },
     Reply { message_id: Vec<u8> },
@@ -86,6 +420,21 @@ impl<C> LocalEvents<C> {
         }
     }
 
+    /// Apply `filter` to an event received from the queue, surfacing lag to the consumer
+    /// as [`SubscribeError::LaggedEvents`] so it can resync instead of silently missing events.
+    fn filter_received<T>(
+        event: Result<Self, BroadcastStreamRecvError>,
+        filter: impl FnOnce(Self) -> Option<T>,
+    ) -> Option<Result<T, SubscribeError>> {
+        match event {
+            Ok(event) => filter(event).map(Ok),
+            Err(BroadcastStreamRecvError::Lagged(missed)) => {
+                tracing::warn!("Missed {missed} local events due to event queue lag");
+                Some(Err(SubscribeError::LaggedEvents(missed)))
+            }
+        }
+    }
+
     fn consent_filter(self) -> Option<Vec<StoredConsentRecord>> {
         use LocalEvents::*;
 
@@ -114,6 +463,126 @@ impl<C> LocalEvents<C> {
         }
     }
 
+    fn reconsent_filter(self) -> Option<Vec<ReconsentRequest>> {
+        match self {
+            LocalEvents::ReconsentRequested(requests) => Some(requests),
+            _ => None,
+        }
+    }
+
+    fn removal_filter(self) -> Option<(Vec<u8>, ConversationRemovalReason)> {
+        match self {
+            LocalEvents::ConversationRemoved(group_id, reason) => Some((group_id, reason)),
+            _ => None,
+        }
+    }
+
+    fn delivery_status_filter(self) -> Option<DeliveryStatusUpdate> {
+        match self {
+            LocalEvents::DeliveryStatusUpdate(update) => Some(update),
+            _ => None,
+        }
+    }
+
+    fn epoch_change_filter(self) -> Option<EpochChange> {
+        match self {
+            LocalEvents::EpochChanged(change) => Some(change),
+            _ => None,
+        }
+    }
+
+    fn group_fork_filter(self) -> Option<GroupForkEvent> {
+        match self {
+            LocalEvents::GroupForked(fork) => Some(GroupForkEvent::Forked(fork)),
+            LocalEvents::GroupRecovered(fork) => Some(GroupForkEvent::Recovered(fork)),
+            _ => None,
+        }
+    }
+
+    fn message_edit_filter(self) -> Option<MessageEdit> {
+        match self {
+            LocalEvents::MessageEdited(edit) => Some(edit),
+            _ => None,
+        }
+    }
+
+    fn message_deletion_filter(self) -> Option<MessageDeletion> {
+        match self {
+            LocalEvents::MessageDeleted(deletion) => Some(deletion),
+            _ => None,
+        }
+    }
+
+    fn message_pin_filter(self) -> Option<MessagePin> {
+        match self {
+            LocalEvents::MessagePinned(pin) => Some(pin),
+            _ => None,
+        }
+    }
+
+    fn poll_update_filter(self) -> Option<PollUpdate> {
+        match self {
+            LocalEvents::PollUpdated(update) => Some(update),
+            _ => None,
+        }
+    }
+
+    fn unread_count_filter(self) -> Option<UnreadCountUpdate> {
+        match self {
+            LocalEvents::UnreadCountChanged(update) => Some(update),
+            _ => None,
+        }
+    }
+
+    fn join_request_filter(self) -> Option<PendingJoinRequest> {
+        match self {
+            LocalEvents::JoinRequest(request) => Some(request),
+            _ => None,
+        }
+    }
+
+    fn local_data_wiped_filter(self) -> Option<WipeScope> {
+        match self {
+            LocalEvents::LocalDataWiped(scope) => Some(scope),
+            _ => None,
+        }
+    }
+
+    fn sync_progress_filter(self) -> Option<SyncProgress> {
+        match self {
+            LocalEvents::SyncProgress(progress) => Some(progress),
+            _ => None,
+        }
+    }
+
+    fn connectivity_filter(self) -> Option<ConnectivityState> {
+        match self {
+            LocalEvents::ConnectivityChanged(state) => Some(state),
+            _ => None,
+        }
+    }
+
+    fn stale_installations_filter(self) -> Option<Vec<StaleInstallationReport>> {
+        match self {
+            LocalEvents::StaleInstallationsDetected(reports) => Some(reports),
+            _ => None,
+        }
+    }
+
+    fn guests_expiring_filter(self) -> Option<Vec<GuestExpiration>> {
+        match self {
+            LocalEvents::GuestsExpiring(guests) => Some(guests),
+            _ => None,
+        }
+    }
+
+    fn rate_limited_filter(self) -> Option<Vec<u8>> {
+        match self {
+            LocalEvents::RateLimited(group_id) => Some(group_id),
+            _ => None,
+        }
+    }
+
     fn preference_filter(self) -> Option<Vec<UserPreferenceUpdate>> {
         use LocalEvents::*;
 
@@ -148,9 +617,46 @@ pub(crate) trait StreamMessages<C> {
     fn stream_consent_updates(
         self,
     ) -> impl Stream<Item = Result<Vec<StoredConsentRecord>, SubscribeError>>;
+    fn stream_consent_updates_for(
+        self,
+        entity_type: ConsentType,
+        entity: String,
+    ) -> impl Stream<Item = Result<Vec<StoredConsentRecord>, SubscribeError>>;
     fn stream_preference_updates(
         self,
     ) -> impl Stream<Item = Result<Vec<UserPreferenceUpdate>, SubscribeError>>;
+    fn stream_reconsent_requests(
+        self,
+    ) -> impl Stream<Item = Result<Vec<ReconsentRequest>, SubscribeError>>;
+    fn stream_guests_expiring(
+        self,
+    ) -> impl Stream<Item = Result<Vec<GuestExpiration>, SubscribeError>>;
+    fn stream_conversation_removals(
+        self,
+    ) -> impl Stream<Item = Result<(Vec<u8>, ConversationRemovalReason), SubscribeError>>;
+    fn stream_delivery_status(
+        self,
+    ) -> impl Stream<Item = Result<DeliveryStatusUpdate, SubscribeError>>;
+    fn stream_epoch_changes(self) -> impl Stream<Item = Result<EpochChange, SubscribeError>>;
+    fn stream_group_forks(self) -> impl Stream<Item = Result<GroupForkEvent, SubscribeError>>;
+    fn stream_message_edits(self) -> impl Stream<Item = Result<MessageEdit, SubscribeError>>;
+    fn stream_message_deletions(
+        self,
+    ) -> impl Stream<Item = Result<MessageDeletion, SubscribeError>>;
+    fn stream_message_pins(self) -> impl Stream<Item = Result<MessagePin, SubscribeError>>;
+    fn stream_poll_updates(self) -> impl Stream<Item = Result<PollUpdate, SubscribeError>>;
+    fn stream_unread_counts(self) -> impl Stream<Item = Result<UnreadCountUpdate, SubscribeError>>;
+    fn stream_join_requests(self)
+        -> impl Stream<Item = Result<PendingJoinRequest, SubscribeError>>;
+    fn stream_stale_installations(
+        self,
+    ) -> impl Stream<Item = Result<Vec<StaleInstallationReport>, SubscribeError>>;
+    fn stream_local_data_wipes(self) -> impl Stream<Item = Result<WipeScope, SubscribeError>>;
+    fn stream_connectivity_changes(
+        self,
+    ) -> impl Stream<Item = Result<ConnectivityState, SubscribeError>>;
+    fn stream_sync_progress(self) -> impl Stream<Item = Result<SyncProgress, SubscribeError>>;
+    fn stream_rate_limited(self) -> impl Stream<Item = Result<Vec<u8>, SubscribeError>>;
 }
 
 impl<C> StreamMessages<C> for broadcast::Receiver<LocalEvents<C>>
@@ -160,9 +666,7 @@ where
     #[instrument(level = "trace", skip_all)]
     fn stream_sync_messages(self) -> impl Stream<Item = Result<LocalEvents<C>, SubscribeError>> {
         BroadcastStream::new(self).filter_map(|event| async {
-            xmtp_common::optify!(event, "Missed message due to event queue lag")
-                .and_then(LocalEvents::sync_filter)
-                .map(Result::Ok)
+            LocalEvents::filter_received(event, LocalEvents::sync_filter)
         })
     }
 
@@ -170,9 +674,27 @@ where
         self,
     ) -> impl Stream<Item = Result<Vec<StoredConsentRecord>, SubscribeError>> {
         BroadcastStream::new(self).filter_map(|event| async {
-            xmtp_common::optify!(event, "Missed message due to event queue lag")
-                .and_then(LocalEvents::consent_filter)
-                .map(Result::Ok)
+            LocalEvents::filter_received(event, LocalEvents::consent_filter)
+        })
+    }
+
+    fn stream_consent_updates_for(
+        self,
+        entity_type: ConsentType,
+        entity: String,
+    ) -> impl Stream<Item = Result<Vec<StoredConsentRecord>, SubscribeError>> {
+        self.stream_consent_updates().filter_map(move |records| {
+            let records = records.map(|records| {
+                records
+                    .into_iter()
+                    .filter(|record| record.entity_type == entity_type && record.entity == entity)
+                    .collect::<Vec<_>>()
+            });
+            // skip updates that only concern other entities
+            futures::future::ready(match records {
+                Ok(records) if records.is_empty() => None,
+                records => Some(records),
+            })
         })
     }
 
@@ -180,9 +702,125 @@ where
         self,
     ) -> impl Stream<Item = Result<Vec<UserPreferenceUpdate>, SubscribeError>> {
         BroadcastStream::new(self).filter_map(|event| async {
-            xmtp_common::optify!(event, "Missed message due to event queue lag")
-                .and_then(LocalEvents::preference_filter)
-                .map(Result::Ok)
+            LocalEvents::filter_received(event, LocalEvents::preference_filter)
+        })
+    }
+
+    fn stream_reconsent_requests(
+        self,
+    ) -> impl Stream<Item = Result<Vec<ReconsentRequest>, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::reconsent_filter)
+        })
+    }
+
+    fn stream_guests_expiring(
+        self,
+    ) -> impl Stream<Item = Result<Vec<GuestExpiration>, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::guests_expiring_filter)
+        })
+    }
+
+    fn stream_conversation_removals(
+        self,
+    ) -> impl Stream<Item = Result<(Vec<u8>, ConversationRemovalReason), SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::removal_filter)
+        })
+    }
+
+    fn stream_delivery_status(
+        self,
+    ) -> impl Stream<Item = Result<DeliveryStatusUpdate, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::delivery_status_filter)
+        })
+    }
+
+    fn stream_epoch_changes(self) -> impl Stream<Item = Result<EpochChange, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::epoch_change_filter)
+        })
+    }
+
+    fn stream_group_forks(self) -> impl Stream<Item = Result<GroupForkEvent, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::group_fork_filter)
+        })
+    }
+
+    fn stream_message_edits(self) -> impl Stream<Item = Result<MessageEdit, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::message_edit_filter)
+        })
+    }
+
+    fn stream_message_deletions(
+        self,
+    ) -> impl Stream<Item = Result<MessageDeletion, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::message_deletion_filter)
+        })
+    }
+
+    fn stream_message_pins(self) -> impl Stream<Item = Result<MessagePin, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::message_pin_filter)
+        })
+    }
+
+    fn stream_poll_updates(self) -> impl Stream<Item = Result<PollUpdate, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::poll_update_filter)
+        })
+    }
+
+    fn stream_unread_counts(self) -> impl Stream<Item = Result<UnreadCountUpdate, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::unread_count_filter)
+        })
+    }
+
+    fn stream_join_requests(
+        self,
+    ) -> impl Stream<Item = Result<PendingJoinRequest, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::join_request_filter)
+        })
+    }
+
+    fn stream_stale_installations(
+        self,
+    ) -> impl Stream<Item = Result<Vec<StaleInstallationReport>, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::stale_installations_filter)
+        })
+    }
+
+    fn stream_local_data_wipes(self) -> impl Stream<Item = Result<WipeScope, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::local_data_wiped_filter)
+        })
+    }
+
+    fn stream_connectivity_changes(
+        self,
+    ) -> impl Stream<Item = Result<ConnectivityState, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::connectivity_filter)
+        })
+    }
+
+    fn stream_sync_progress(self) -> impl Stream<Item = Result<SyncProgress, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::sync_progress_filter)
+        })
+    }
+
+    fn stream_rate_limited(self) -> impl Stream<Item = Result<Vec<u8>, SubscribeError>> {
+        BroadcastStream::new(self).filter_map(|event| async {
+            LocalEvents::filter_received(event, LocalEvents::rate_limited_filter)
         })
     }
 }
@@ -220,16 +858,284 @@ impl From<StoredGroup> for (Vec<u8>, MessagesStreamInfo) {
     }
 }
 
-#[derive(thiserror::Error, Debug)]
-pub enum SubscribeError {
-    #[error("failed to start new messages stream {0}")]
-    FailedToStartNewMessagesStream(ClientError),
-    #[error(transparent)]
-    Client(#[from] ClientError),
-    #[error(transparent)]
-    Group(#[from] GroupError),
-    #[error("group message expected in database but is missing")]
+/// Restricts the messages yielded by [`Client::stream_all_messages`] by content type, or to
+/// those mentioning the streaming inbox. The default filter lets every message through.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageStreamFilter {
+    allowed: Option<Vec<ContentType>>,
+    denied: Vec<ContentType>,
+    only_mentions: bool,
+}
+
+impl MessageStreamFilter {
+    /// Only yield messages with one of these content types
+    pub fn allow(mut self, content_types: impl IntoIterator<Item = ContentType>) -> Self {
+        self.allowed
+            .get_or_insert_with(Vec::new)
+            .extend(content_types);
+        self
+    }
+
+    /// Never yield messages with one of these content types, even if they are allowed
+    pub fn deny(mut self, content_types: impl IntoIterator<Item = ContentType>) -> Self {
+        self.denied.extend(content_types);
+        self
+    }
+
+    /// Only yield messages that mention the streaming inbox, e.g. for mention badges
+    pub fn only_mentions(mut self, only_mentions: bool) -> Self {
+        self.only_mentions = only_mentions;
+        self
+    }
+
+    /// Whether `message` passes the filter when streamed to `inbox_id`
+    pub fn matches(&self, message: &StoredGroupMessage, inbox_id: &str) -> bool {
+        let content_type = message.content_type;
+        self.allowed
+            .as_ref()
+            .map_or(true, |allowed| allowed.contains(&content_type))
+            && !self.denied.contains(&content_type)
+            && (!self.only_mentions || mentions_inbox(message, inbox_id))
+    }
+}
+
+/// What [`Client::stream_all_messages`] and its variants stream
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamAllMessagesOptions {
+    /// Only stream conversations of this type
+    pub conversation_type: Option<ConversationType>,
+    /// Only subscribe to conversations in one of these consent states when they are added to
+    /// the stream. Messages from other conversations are never fetched or decrypted.
+    pub consent_states: Option<Vec<ConsentState>>,
+    /// Skip the messages that don't match
+    pub filter: MessageStreamFilter,
+    /// First yield the stored messages sent after this time, oldest first, then switch to
+    /// live messages
+    pub sent_after_ns: Option<i64>,
+}
+
+/// An item of a stream with an idle timeout, see [`with_idle_timeout`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamItem<T> {
+    Item(T),
+    /// Nothing arrived for a whole idle timeout, but the stream is still open
+    Idle,
+}
+
+impl<T> StreamItem<T> {
+    pub fn into_item(self) -> Option<T> {
+        match self {
+            Self::Item(item) => Some(item),
+            Self::Idle => None,
+        }
+    }
+}
+
+/// Wrap `stream` so it yields a [`StreamItem::Idle`] every `timeout` it stays quiet. This is a
+/// local timer, nothing is sent to the server: an idle stream may be quiet or its connection
+/// may be dead, and consumers that expect traffic can resubscribe when it goes idle.
+pub fn with_idle_timeout<S: Stream>(
+    stream: S,
+    timeout: Duration,
+) -> impl Stream<Item = StreamItem<S::Item>> {
+    async_stream::stream! {
+        futures::pin_mut!(stream);
+        loop {
+            match xmtp_common::time::timeout(timeout, stream.next()).await {
+                Ok(Some(item)) => yield StreamItem::Item(item),
+                Ok(None) => break,
+                Err(_) => yield StreamItem::Idle,
+            }
+        }
+    }
+}
+
+/// Wrap a message stream so each message is yielded at most once, even if it is delivered
+/// again, e.g. by a subscription added for a conversation that replays it from the start.
+/// Remembers the id of every message yielded for as long as the stream lives.
+pub fn deduplicate_messages<S>(
+    stream: S,
+) -> impl Stream<Item = Result<StoredGroupMessage, SubscribeError>>
+where
+    S: Stream<Item = Result<StoredGroupMessage, SubscribeError>>,
+{
+    let mut delivered = HashSet::new();
+    stream.filter(move |message| {
+        let first_delivery = match message {
+            Ok(message) => delivered.insert(message.id.clone()),
+            Err(_) => true,
+        };
+        futures::future::ready(first_delivery)
+    })
+}
+
+/// How [`with_batching`] groups stream items
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamBatchOptions {
+    /// A batch is yielded as soon as it holds this many items
+    pub max_batch_size: usize,
+    /// How long the first item of a batch waits for more to arrive before the batch is yielded
+    pub max_latency: Duration,
+}
+
+impl Default for StreamBatchOptions {
+    fn default() -> Self {
+        Self {
+            max_batch_size: 50,
+            max_latency: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Wrap `stream` so its items are yielded in batches, amortizing per-item overhead for
+/// consumers such as callbacks across the FFI boundary. An error ends the current batch early
+/// and is yielded on its own.
+pub fn with_batching<S, T, E>(
+    stream: S,
+    options: StreamBatchOptions,
+) -> impl Stream<Item = Result<Vec<T>, E>>
+where
+    S: Stream<Item = Result<T, E>>,
+{
+    let max_batch_size = options.max_batch_size.max(1);
+    async_stream::stream! {
+        futures::pin_mut!(stream);
+        let mut batch = Vec::with_capacity(max_batch_size);
+        let mut deadline = None;
+        loop {
+            let next = match deadline {
+                None => stream.next().await,
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    match xmtp_common::time::timeout(remaining, stream.next()).await {
+                        Ok(next) => next,
+                        Err(_) => {
+                            deadline = None;
+                            yield Ok(std::mem::take(&mut batch));
+                            continue;
+                        }
+                    }
+                }
+            };
+            match next {
+                Some(Ok(item)) => {
+                    if batch.is_empty() {
+                        deadline = Some(Instant::now() + options.max_latency);
+                    }
+                    batch.push(item);
+                    if batch.len() >= max_batch_size {
+                        deadline = None;
+                        yield Ok(std::mem::take(&mut batch));
+                    }
+                }
+                Some(Err(e)) => {
+                    deadline = None;
+                    if !batch.is_empty() {
+                        yield Ok(std::mem::take(&mut batch));
+                    }
+                    yield Err(e);
+                }
+                None => {
+                    if !batch.is_empty() {
+                        yield Ok(batch);
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A daily window, in UTC, during which low-priority events are held back.
+/// The window may span midnight, e.g. 22:00 to 07:00. Apps convert from the user's local time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    /// Time since midnight UTC at which quiet hours start
+    pub start: Duration,
+    /// Time since midnight UTC at which quiet hours end
+    pub end: Duration,
+}
+
+impl QuietHours {
+    /// How long quiet hours last from `now_ns`, or `None` if they are not in effect then.
+    /// Quiet hours that start and end at the same time are never in effect.
+    pub fn remaining_at(&self, now_ns: i64) -> Option<Duration> {
+        let now = Duration::from_nanos(now_ns.max(0) as u64 % DAY.as_nanos() as u64);
+        let (start, end) = (self.start.min(DAY), self.end.min(DAY));
+        if start <= end {
+            (start <= now && now < end).then(|| end - now)
+        } else if now >= start {
+            Some(DAY - now + end)
+        } else {
+            (now < end).then(|| end - now)
+        }
+    }
+}
+
+/// Wrap `stream` so items for which `is_low_priority` returns true are held while `quiet_hours`
+/// are in effect and yielded together as one batch when they end. Every other item, and any
+/// item outside quiet hours, is yielded right away as a batch of one. Held items are also
+/// yielded if the stream ends.
+pub fn with_quiet_hours<S, T, E>(
+    stream: S,
+    quiet_hours: QuietHours,
+    is_low_priority: impl Fn(&T) -> bool,
+) -> impl Stream<Item = Result<Vec<T>, E>>
+where
+    S: Stream<Item = Result<T, E>>,
+{
+    async_stream::stream! {
+        futures::pin_mut!(stream);
+        let mut held = Vec::new();
+        loop {
+            let next = if held.is_empty() {
+                stream.next().await
+            } else {
+                let remaining = quiet_hours.remaining_at(now_ns()).unwrap_or_default();
+                match xmtp_common::time::timeout(remaining, stream.next()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        yield Ok(std::mem::take(&mut held));
+                        continue;
+                    }
+                }
+            };
+            match next {
+                Some(Ok(item)) => {
+                    if is_low_priority(&item) && quiet_hours.remaining_at(now_ns()).is_some() {
+                        held.push(item);
+                    } else {
+                        yield Ok(vec![item]);
+                    }
+                }
+                Some(Err(e)) => yield Err(e),
+                None => {
+                    if !held.is_empty() {
+                        yield Ok(held);
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SubscribeError {
+    #[error("failed to start new messages stream {0}")]
+    FailedToStartNewMessagesStream(ClientError),
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    #[error(transparent)]
+    Group(#[from] GroupError),
+    #[error("group message expected in database but is missing")]
     GroupMessageNotFound,
+    /// The message was sent by a blocked inbox whose messages are hidden, see
+    /// [`crate::block_list`]. Message streams skip it.
+    #[error("message sent by a blocked inbox whose messages are hidden")]
+    HiddenSender,
     #[error("processing group message in stream: {0}")]
     ReceiveGroup(#[from] GroupMessageProcessingError),
     #[error(transparent)]
@@ -240,6 +1146,12 @@ pub enum SubscribeError {
     Api(#[from] xmtp_proto::Error),
     #[error(transparent)]
     Decode(#[from] prost::DecodeError),
+    #[error(transparent)]
+    Codec(#[from] xmtp_content_types::CodecError),
+    /// The consumer fell behind the local event queue and missed this many events.
+    /// The stream continues, but state derived from it should be fully resynced.
+    #[error("missed {0} local events due to event queue lag")]
+    LaggedEvents(u64),
 }
 
 impl RetryableError for SubscribeError {
@@ -250,13 +1162,60 @@ impl RetryableError for SubscribeError {
             Client(e) => retryable!(e),
             Group(e) => retryable!(e),
             GroupMessageNotFound => true,
+            HiddenSender => false,
             ReceiveGroup(e) => retryable!(e),
             Database(e) => retryable!(e),
             Storage(e) => retryable!(e),
             Api(e) => retryable!(e),
             Decode(_) => false,
+            Codec(_) => false,
+            LaggedEvents(_) => false,
+        }
+    }
+}
+
+impl<ApiClient, V> Client<ApiClient, V> {
+    /// Publish an event to local subscribers. If backpressure is enabled, first waits for the
+    /// slowest subscriber to make room in the queue, up to the configured maximum wait.
+    pub(crate) async fn publish_local_event(&self, event: LocalEvents<Self>) {
+        publish_with_backpressure(&self.local_events, self.context.local_event_queue, event).await
+    }
+}
+
+/// Publish `event` on `sender`, applying the backpressure configured in `options`
+pub(crate) async fn publish_with_backpressure<C>(
+    sender: &broadcast::Sender<LocalEvents<C>>,
+    options: LocalEventQueueOptions,
+    event: LocalEvents<C>,
+) {
+    let LocalEventQueueOptions {
+        capacity,
+        backpressure,
+    } = options;
+    if let Some(max_wait) = backpressure {
+        let room = async {
+            while sender.len() >= capacity {
+                xmtp_common::time::sleep(BACKPRESSURE_POLL_INTERVAL).await;
+            }
+        };
+        if xmtp_common::time::timeout(max_wait, room).await.is_err() {
+            tracing::warn!(
+                "local event queue still full after {max_wait:?}, slow subscribers will lag"
+            );
         }
     }
+    // an error only means there are no subscribers
+    let _ = sender.send(event);
+}
+
+/// Publish `event` on `sender` from code that can't wait for room in the queue, skipping
+/// backpressure
+pub(crate) fn publish_without_waiting<C>(
+    sender: &broadcast::Sender<LocalEvents<C>>,
+    event: LocalEvents<C>,
+) {
+    // an error only means there are no subscribers
+    let _ = sender.send(event);
 }
 
 impl<ApiClient, V> Client<ApiClient, V>
@@ -293,6 +1252,10 @@ where
             })
         );
 
+        // a welcome from a blocked inbox was never stored, so there's no group to fall back to
+        if let Err(err @ GroupError::Block(_)) = creation_result {
+            return Err(err.into());
+        }
         if let Some(err) = creation_result.as_ref().err() {
             let conn = provider.conn_ref();
             let result = conn.find_group_by_welcome_id(welcome_v1.id as i64);
@@ -348,9 +1311,7 @@ where
         let event_queue =
             tokio_stream::wrappers::BroadcastStream::new(self.local_events.subscribe())
                 .filter_map(|event| async {
-                    xmtp_common::optify!(event, "Missed messages due to event queue lag")
-                        .and_then(LocalEvents::group_filter)
-                        .map(Result::Ok)
+                    LocalEvents::filter_received(event, LocalEvents::group_filter)
                 })
                 .map(WelcomeOrGroup::<ApiClient, V>::Group);
 
@@ -361,7 +1322,15 @@ where
                 installation_id = %self.installation_id(),
                 "Received conversation streaming payload"
             );
-            let filtered = self.process_streamed_convo(group_or_welcome).await;
+            let filtered = match self.process_streamed_convo(group_or_welcome).await {
+                // welcomes from blocked inboxes are dropped
+                Err(SubscribeError::Client(ClientError::Group(err)))
+                    if matches!(*err, GroupError::Block(_)) =>
+                {
+                    return None;
+                }
+                filtered => filtered,
+            };
             let filtered = filtered.map(|(metadata, group)| {
                 conversation_type
                     .map_or(true, |ct| ct == metadata.conversation_type)
@@ -373,6 +1342,67 @@ where
         Ok(stream)
     }
 
+    /// Like [`Client::stream_conversations`], but yields a [`StreamItem::Idle`] when no
+    /// conversation has arrived for `idle_timeout`
+    pub async fn stream_conversations_with_idle_timeout<'a>(
+        &'a self,
+        conversation_type: Option<ConversationType>,
+        idle_timeout: Duration,
+    ) -> Result<
+        impl Stream<Item = StreamItem<Result<MlsGroup<Self>, SubscribeError>>> + 'a,
+        ClientError,
+    >
+    where
+        ApiClient: XmtpMlsStreams,
+    {
+        let stream = self.stream_conversations(conversation_type).await?;
+        Ok(with_idle_timeout(stream, idle_timeout))
+    }
+
+    /// Like [`Client::stream_conversations`], but also yields a [`ConversationUpdate::Removed`]
+    /// when this installation is removed from a conversation, so it can be closed right away
+    pub async fn stream_conversation_updates<'a>(
+        &'a self,
+        conversation_type: Option<ConversationType>,
+    ) -> Result<
+        impl Stream<Item = Result<ConversationUpdate<Self>, SubscribeError>> + 'a,
+        ClientError,
+    >
+    where
+        ApiClient: XmtpMlsStreams,
+    {
+        let removals = self.stream_conversation_removals(conversation_type);
+        let added = self
+            .stream_conversations(conversation_type)
+            .await?
+            .map(|r| r.map(ConversationUpdate::Added));
+        let removals = removals
+            .map(|r| r.map(|(group_id, reason)| ConversationUpdate::Removed { group_id, reason }));
+        Ok(futures::stream::select(added, removals))
+    }
+
+    /// Removals of this installation from conversations of `conversation_type`
+    fn stream_conversation_removals(
+        &self,
+        conversation_type: Option<ConversationType>,
+    ) -> impl Stream<Item = Result<(Vec<u8>, ConversationRemovalReason), SubscribeError>> + '_ {
+        self.local_events
+            .subscribe()
+            .stream_conversation_removals()
+            .filter(move |removal| {
+                let matches = match (removal, conversation_type) {
+                    (Ok((group_id, _)), Some(conversation_type)) => self
+                        .store()
+                        .conn()
+                        .ok()
+                        .and_then(|conn| conn.find_group(group_id.clone()).ok().flatten())
+                        .is_some_and(|group| group.conversation_type == conversation_type),
+                    _ => true,
+                };
+                futures::future::ready(matches)
+            })
+    }
+
     async fn process_streamed_convo(
         &self,
         welcome_or_group: WelcomeOrGroup<ApiClient, V>,
@@ -404,60 +1434,198 @@ where
         conversation_type: Option<ConversationType>,
         mut convo_callback: impl FnMut(Result<MlsGroup<Self>, SubscribeError>) + Send + 'static,
     ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
-        let (tx, rx) = oneshot::channel();
+        Self::stream_conversations_with_async_callback(client, conversation_type, move |convo| {
+            convo_callback(convo);
+            futures::future::ready(())
+        })
+    }
 
-        crate::spawn(Some(rx), async move {
+    /// Like [`Self::stream_conversations_with_callback`], but awaits the future returned by
+    /// `convo_callback` before handling the next conversation
+    pub fn stream_conversations_with_async_callback<Fut>(
+        client: Arc<Client<ApiClient, V>>,
+        conversation_type: Option<ConversationType>,
+        mut convo_callback: impl FnMut(Result<MlsGroup<Self>, SubscribeError>) -> Fut + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>>
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        spawn_stream_task(move |task| async move {
             let stream = client.stream_conversations(conversation_type).await?;
-            futures::pin_mut!(stream);
-            let _ = tx.send(());
-            while let Some(convo) = stream.next().await {
+            let stream = task.until_closed(stream);
+            task.run(stream, |metrics, convo| {
+                metrics.record(&convo);
                 tracing::info!("Trigger conversation callback");
                 convo_callback(convo)
-            }
+            })
+            .await;
+            tracing::debug!("`stream_conversations` stream ended, dropping stream");
+            Ok::<_, ClientError>(())
+        })
+    }
+
+    pub fn stream_conversations_with_idle_timeout_callback(
+        client: Arc<Client<ApiClient, V>>,
+        conversation_type: Option<ConversationType>,
+        idle_timeout: Duration,
+        mut convo_callback: impl FnMut(StreamItem<Result<MlsGroup<Self>, SubscribeError>>)
+            + Send
+            + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
+        spawn_stream_task(move |task| async move {
+            let stream = client
+                .stream_conversations_with_idle_timeout(conversation_type, idle_timeout)
+                .await?;
+            let stream = task.until_closed(stream);
+            task.run(stream, |metrics, item| {
+                if let StreamItem::Item(convo) = &item {
+                    metrics.record(convo);
+                }
+                futures::future::ready(convo_callback(item))
+            })
+            .await;
             tracing::debug!("`stream_conversations` stream ended, dropping stream");
             Ok::<_, ClientError>(())
         })
     }
 
+    /// Stream new messages from every conversation, including conversations joined after the
+    /// stream started, as configured by `opts`. Errors are always yielded.
     #[tracing::instrument(level = "debug", skip_all)]
     pub async fn stream_all_messages(
         &self,
-        conversation_type: Option<ConversationType>,
+        opts: StreamAllMessagesOptions,
     ) -> Result<impl Stream<Item = Result<StoredGroupMessage, SubscribeError>> + '_, ClientError>
     {
-        tracing::debug!(
-            inbox_id = self.inbox_id(),
-            conversation_type = ?conversation_type,
-            "stream all messages"
-        );
-        let mut group_id_to_info = async {
-            let provider = self.mls_provider()?;
-            self.sync_welcomes(&provider).await?;
-
-            let group_id_to_info = provider
-                .conn_ref()
-                .find_groups(GroupQueryArgs::default().maybe_conversation_type(conversation_type))?
-                .into_iter()
-                .map(Into::into)
-                .collect::<HashMap<Vec<u8>, MessagesStreamInfo>>();
-            Ok::<_, ClientError>(group_id_to_info)
+        self.stream_all_messages_with_metrics(opts, None).await
+    }
+
+    async fn stream_all_messages_with_metrics(
+        &self,
+        opts: StreamAllMessagesOptions,
+        metrics: Option<StreamMetrics>,
+    ) -> Result<impl Stream<Item = Result<StoredGroupMessage, SubscribeError>> + '_, ClientError>
+    {
+        tracing::debug!(inbox_id = self.inbox_id(), opts = ?opts, "stream all messages");
+        let (stored, group_id_to_info) = self.streamed_groups(&opts).await?;
+        Ok(self.stream_messages_from(stored, group_id_to_info, opts, metrics))
+    }
+
+    /// Sync welcomes, then return the stored conversations a message stream starts with, and
+    /// the stored messages it replays first, oldest first. When replaying, each conversation's
+    /// subscription resumes from the last envelope stored locally, so nothing sent while
+    /// catching up is missed.
+    async fn streamed_groups(
+        &self,
+        opts: &StreamAllMessagesOptions,
+    ) -> Result<
+        (
+            Vec<StoredGroupMessage>,
+            HashMap<Vec<u8>, MessagesStreamInfo>,
+        ),
+        ClientError,
+    > {
+        let provider = self.mls_provider()?;
+        self.sync_welcomes(&provider).await?;
+        let conn = provider.conn_ref();
+
+        let groups = conn.find_groups(
+            GroupQueryArgs::default()
+                .maybe_conversation_type(opts.conversation_type)
+                .maybe_consent_states(opts.consent_states.clone()),
+        )?;
+        let Some(sent_after_ns) = opts.sent_after_ns else {
+            return Ok((vec![], groups.into_iter().map(Into::into).collect()));
+        };
+        let query = MsgQueryArgs {
+            sent_after_ns: Some(sent_after_ns),
+            ..Default::default()
+        };
+        let mut group_id_to_info = HashMap::new();
+        let mut stored = vec![];
+        for group in groups {
+            let cursor = conn.get_last_cursor_for_id(&group.id, EntityKind::Group)?;
+            stored.extend(
+                conn.get_group_messages(&group.id, &query)?
+                    .into_iter()
+                    .filter(|message| opts.filter.matches(message, self.inbox_id())),
+            );
+            group_id_to_info.insert(
+                group.id,
+                MessagesStreamInfo {
+                    convo_created_at_ns: group.created_at_ns,
+                    cursor: cursor as u64,
+                },
+            );
         }
-        .await?;
+        stored.sort_by_key(|message| message.sent_at_ns);
+        Ok((stored, group_id_to_info))
+    }
 
-        let stream = async_stream::stream! {
-            let messages_stream = subscriptions::stream_messages(
-                self,
-                Arc::new(group_id_to_info.clone())
-            )
-            .await?;
-            futures::pin_mut!(messages_stream);
+    /// Yield the `stored` messages, then stream messages from the conversations in
+    /// `group_id_to_info`, adding conversations that match `opts` as they are created or
+    /// joined. Each message is yielded at most once per stream.
+    pub(crate) fn stream_messages_from(
+        &self,
+        stored: Vec<StoredGroupMessage>,
+        group_id_to_info: HashMap<Vec<u8>, MessagesStreamInfo>,
+        opts: StreamAllMessagesOptions,
+        metrics: Option<StreamMetrics>,
+    ) -> impl Stream<Item = Result<StoredGroupMessage, SubscribeError>> + '_ {
+        self.stream_events_from(stored, group_id_to_info, opts, metrics, false)
+            .filter_map(|event| {
+                futures::future::ready(match event {
+                    Ok(ClientEvent::Message(message)) => Some(Ok(message)),
+                    Ok(_) => None,
+                    Err(e) => Some(Err(e)),
+                })
+            })
+    }
+
+    /// Like [`Self::stream_messages_from`], and with `yield_conversations` also yields every
+    /// conversation of the requested type created or joined, from the same welcome
+    /// subscription that adds them to the stream.
+    fn stream_events_from(
+        &self,
+        stored: Vec<StoredGroupMessage>,
+        mut group_id_to_info: HashMap<Vec<u8>, MessagesStreamInfo>,
+        opts: StreamAllMessagesOptions,
+        metrics: Option<StreamMetrics>,
+        yield_conversations: bool,
+    ) -> impl Stream<Item = Result<ClientEvent<Self>, SubscribeError>> + '_ {
+        let StreamAllMessagesOptions {
+            conversation_type,
+            consent_states,
+            filter,
+            ..
+        } = opts;
+        async_stream::stream! {
+            // the subscriptions can deliver stored messages again, and a subscription added
+            // for a conversation can replay messages already yielded
+            let mut delivered = HashSet::new();
+            for message in stored {
+                delivered.insert(message.id.clone());
+                yield Ok(ClientEvent::Message(message));
+            }
+
+            // Each conversation discovered after the stream started gets its own
+            // subscription, multiplexed with the others. Live subscriptions are never torn
+            // down, so no message in flight on them can be lost while switching.
+            let mut messages_stream = futures::stream::SelectAll::new();
+            if !group_id_to_info.is_empty() {
+                let initial = subscriptions::stream_messages(
+                    self,
+                    Arc::new(group_id_to_info.clone()),
+                    metrics.clone(),
+                )
+                .await?;
+                messages_stream.push(Box::pin(initial));
+            }
 
             let convo_stream = self.stream_conversations(conversation_type).await?;
 
             futures::pin_mut!(convo_stream);
 
-            let mut extra_messages = Vec::new();
-
             loop {
                 tokio::select! {
                     // biased enforces an order to select!. If a message and a group are both ready
@@ -465,36 +1633,48 @@ where
                     // group.
                     biased;
 
-                    messages = futures::future::ready(&mut extra_messages), if !extra_messages.is_empty() => {
-                        for message in messages.drain(0..) {
-                            yield message;
+                    Some(message) = messages_stream.next(), if !messages_stream.is_empty() => {
+                        match message {
+                            Ok(message) => {
+                                if !filter.matches(&message, self.inbox_id())
+                                    || !delivered.insert(message.id.clone())
+                                {
+                                    continue;
+                                }
+                                yield Ok(ClientEvent::Message(message));
+                            }
+                            Err(e) => yield Err(e),
                         }
-                    },
-                    Some(message) = messages_stream.next() => {
-                        // an error can only mean the receiver has been dropped or closed so we're
-                        // safe to end the stream
-                        yield message;
                     }
                     Some(new_group) = convo_stream.next() => {
                         match new_group {
                             Ok(new_group) => {
                                 tracing::info!("Received new conversation inside streamAllMessages");
+                                if yield_conversations {
+                                    yield Ok(ClientEvent::Conversation(new_group.clone()));
+                                }
                                 if group_id_to_info.contains_key(&new_group.group_id) {
                                     continue;
                                 }
-                                for info in group_id_to_info.values_mut() {
-                                    info.cursor = 0;
+                                if let Some(consent_states) = &consent_states {
+                                    match new_group.consent_state() {
+                                        Ok(state) if !consent_states.contains(&state) => continue,
+                                        Ok(_) => {}
+                                        Err(e) => {
+                                            yield Err(e.into());
+                                            continue;
+                                        }
+                                    }
                                 }
-                                group_id_to_info.insert(
-                                    new_group.group_id,
-                                    MessagesStreamInfo {
-                                        convo_created_at_ns: new_group.created_at_ns,
-                                        cursor: 1, // For the new group, stream all messages since the group was created
-                                    },
-                                );
-                                let new_messages_stream = match subscriptions::stream_messages(
+                                let info = MessagesStreamInfo {
+                                    convo_created_at_ns: new_group.created_at_ns,
+                                    cursor: 1, // For the new group, stream all messages since the group was created
+                                };
+                                group_id_to_info.insert(new_group.group_id.clone(), info.clone());
+                                let new_group_stream = match subscriptions::stream_messages(
                                     self,
-                                    Arc::new(group_id_to_info.clone())
+                                    Arc::new(HashMap::from([(new_group.group_id, info)])),
+                                    metrics.clone(),
                                 ).await {
                                     Ok(s) => s,
                                     Err(e) => {
@@ -503,13 +1683,8 @@ where
                                     },
                                 };
 
-                                tracing::debug!("switching streams");
-                                // attempt to drain all ready messages from existing stream
-                                while let Some(Some(message)) = messages_stream.next().now_or_never() {
-                                    extra_messages.push(message);
-                                }
-                                messages_stream.set(new_messages_stream);
-                                continue;
+                                tracing::debug!("adding new conversation to message stream");
+                                messages_stream.push(Box::pin(new_group_stream));
                             },
                             Err(e) => {
                                 yield Err(e)
@@ -518,93 +1693,376 @@ where
                     },
                 }
             }
-        };
+        }
+    }
 
-        Ok(stream)
+    /// Like [`Client::stream_all_messages`], but yields a [`StreamItem::Idle`] when no
+    /// message has arrived for `idle_timeout`
+    pub async fn stream_all_messages_with_idle_timeout(
+        &self,
+        opts: StreamAllMessagesOptions,
+        idle_timeout: Duration,
+    ) -> Result<
+        impl Stream<Item = StreamItem<Result<StoredGroupMessage, SubscribeError>>> + '_,
+        ClientError,
+    > {
+        let stream = self.stream_all_messages(opts).await?;
+        Ok(with_idle_timeout(stream, idle_timeout))
+    }
+
+    /// Like [`Client::stream_all_messages`], but yields messages in batches as configured by
+    /// `batching`, for high-volume consumers
+    pub async fn stream_all_messages_batched(
+        &self,
+        opts: StreamAllMessagesOptions,
+        batching: StreamBatchOptions,
+    ) -> Result<impl Stream<Item = Result<Vec<StoredGroupMessage>, SubscribeError>> + '_, ClientError>
+    {
+        let stream = self.stream_all_messages(opts).await?;
+        Ok(with_batching(stream, batching))
     }
 
     pub fn stream_all_messages_with_callback(
         client: Arc<Client<ApiClient, V>>,
-        conversation_type: Option<ConversationType>,
+        opts: StreamAllMessagesOptions,
         mut callback: impl FnMut(Result<StoredGroupMessage, SubscribeError>) + Send + 'static,
     ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
-        let (tx, rx) = oneshot::channel();
+        Self::stream_all_messages_with_async_callback(client, opts, move |message| {
+            callback(message);
+            futures::future::ready(())
+        })
+    }
 
-        crate::spawn(Some(rx), async move {
-            let stream = client.stream_all_messages(conversation_type).await?;
-            futures::pin_mut!(stream);
-            let _ = tx.send(());
-            while let Some(message) = stream.next().await {
+    /// Like [`Self::stream_all_messages_with_callback`], but awaits the future returned by
+    /// `callback` before handling the next message, so slow consumers apply backpressure
+    /// instead of blocking the stream task.
+    pub fn stream_all_messages_with_async_callback<Fut>(
+        client: Arc<Client<ApiClient, V>>,
+        opts: StreamAllMessagesOptions,
+        mut callback: impl FnMut(Result<StoredGroupMessage, SubscribeError>) -> Fut + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>>
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        spawn_stream_task(move |task| async move {
+            let stream = client
+                .stream_all_messages_with_metrics(opts, Some(task.metrics().clone()))
+                .await?;
+            let stream = task.until_closed(stream);
+            task.run(stream, |metrics, message| {
+                metrics.record(&message);
                 callback(message)
-            }
+            })
+            .await;
             tracing::debug!("`stream_all_messages` stream ended, dropping stream");
             Ok::<_, ClientError>(())
         })
     }
 
-    pub fn stream_consent_with_callback(
+    pub fn stream_all_messages_with_idle_timeout_callback(
         client: Arc<Client<ApiClient, V>>,
-        mut callback: impl FnMut(Result<Vec<StoredConsentRecord>, SubscribeError>) + Send + 'static,
+        opts: StreamAllMessagesOptions,
+        idle_timeout: Duration,
+        mut callback: impl FnMut(StreamItem<Result<StoredGroupMessage, SubscribeError>>)
+            + Send
+            + 'static,
     ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
-        let (tx, rx) = oneshot::channel();
-
-        crate::spawn(Some(rx), async move {
-            let receiver = client.local_events.subscribe();
-            let stream = receiver.stream_consent_updates();
-
-            futures::pin_mut!(stream);
-            let _ = tx.send(());
-            while let Some(message) = stream.next().await {
-                callback(message)
-            }
-            tracing::debug!("`stream_consent` stream ended, dropping stream");
+        spawn_stream_task(move |task| async move {
+            let stream = client
+                .stream_all_messages_with_metrics(opts, Some(task.metrics().clone()))
+                .await?;
+            let stream = with_idle_timeout(task.until_closed(stream), idle_timeout);
+            task.run(stream, |metrics, item| {
+                if let StreamItem::Item(message) = &item {
+                    metrics.record(message);
+                }
+                futures::future::ready(callback(item))
+            })
+            .await;
+            tracing::debug!("`stream_all_messages` stream ended, dropping stream");
             Ok::<_, ClientError>(())
         })
     }
 
-    pub fn stream_preferences_with_callback(
+    pub fn stream_all_messages_batched_with_callback(
         client: Arc<Client<ApiClient, V>>,
-        mut callback: impl FnMut(Result<Vec<UserPreferenceUpdate>, SubscribeError>) + Send + 'static,
+        opts: StreamAllMessagesOptions,
+        batching: StreamBatchOptions,
+        mut callback: impl FnMut(Result<Vec<StoredGroupMessage>, SubscribeError>) + Send + 'static,
     ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
-        let (tx, rx) = oneshot::channel();
+        spawn_stream_task(move |task| async move {
+            let stream = client
+                .stream_all_messages_with_metrics(opts, Some(task.metrics().clone()))
+                .await?;
+            // closed before batching, so the pending batch is flushed on close
+            let stream = with_batching(task.until_closed(stream), batching);
+            task.run(stream, |metrics, batch| {
+                match &batch {
+                    Ok(messages) => {
+                        for _ in messages {
+                            metrics.record_delivered();
+                        }
+                    }
+                    Err(_) => metrics.record(&batch),
+                }
+                futures::future::ready(callback(batch))
+            })
+            .await;
+            tracing::debug!("`stream_all_messages` stream ended, dropping stream");
+            Ok::<_, ClientError>(())
+        })
+    }
 
-        crate::spawn(Some(rx), async move {
-            let receiver = client.local_events.subscribe();
-            let stream = receiver.stream_preference_updates();
+    /// Stream messages, conversations, consent and preference updates as a single stream,
+    /// so callers only need to manage one handle.
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn stream_events(
+        &self,
+        conversation_type: Option<ConversationType>,
+    ) -> Result<impl Stream<Item = Result<ClientEvent<Self>, SubscribeError>> + '_, ClientError>
+    {
+        // subscribe to local events first so no update is missed while the
+        // network streams are being set up
+        let consent = self
+            .local_events
+            .subscribe()
+            .stream_consent_updates()
+            .filter(|r| futures::future::ready(!matches!(r, Ok(records) if records.is_empty())))
+            .map(|r| r.map(ClientEvent::Consent));
+        let preferences = self
+            .local_events
+            .subscribe()
+            .stream_preference_updates()
+            .filter(|r| futures::future::ready(!matches!(r, Ok(updates) if updates.is_empty())))
+            .map(|r| r.map(ClientEvent::Preferences));
+
+        let removals = self
+            .stream_conversation_removals(conversation_type)
+            .map(|r| {
+                r.map(|(group_id, reason)| ClientEvent::ConversationRemoved { group_id, reason })
+            });
 
-            futures::pin_mut!(stream);
-            let _ = tx.send(());
-            while let Some(message) = stream.next().await {
-                callback(message)
-            }
-            tracing::debug!("`stream_consent` stream ended, dropping stream");
+        // messages and conversations share a single welcome subscription
+        let opts = StreamAllMessagesOptions {
+            conversation_type,
+            ..Default::default()
+        };
+        let (stored, group_id_to_info) = self.streamed_groups(&opts).await?;
+        let messages_and_conversations =
+            self.stream_events_from(stored, group_id_to_info, opts, None, true);
+
+        Ok(futures::stream::select(
+            messages_and_conversations,
+            futures::stream::select(futures::stream::select(consent, preferences), removals),
+        ))
+    }
+
+    pub fn stream_events_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        conversation_type: Option<ConversationType>,
+        mut callback: impl FnMut(Result<ClientEvent<Self>, SubscribeError>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
+        spawn_stream_task(move |task| async move {
+            let stream = client.stream_events(conversation_type).await?;
+            let stream = task.until_closed(stream);
+            task.run(stream, |metrics, event| {
+                metrics.record(&event);
+                futures::future::ready(callback(event))
+            })
+            .await;
+            tracing::debug!("`stream_events` stream ended, dropping stream");
             Ok::<_, ClientError>(())
         })
     }
-}
 
-#[cfg(test)]
-pub(crate) mod tests {
-    #[cfg(target_arch = "wasm32")]
-    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+    /// Like [`Client::stream_events`], but low-priority events (see
+    /// [`ClientEvent::is_low_priority`]) that arrive during `quiet_hours` are delivered as a
+    /// single batch once they end, reducing background wakeups. Everything else is still
+    /// delivered as it arrives, in a batch of one.
+    pub async fn stream_events_with_quiet_hours(
+        &self,
+        conversation_type: Option<ConversationType>,
+        quiet_hours: QuietHours,
+    ) -> Result<impl Stream<Item = Result<Vec<ClientEvent<Self>>, SubscribeError>> + '_, ClientError>
+    {
+        let stream = self.stream_events(conversation_type).await?;
+        Ok(with_quiet_hours(
+            stream,
+            quiet_hours,
+            ClientEvent::is_low_priority,
+        ))
+    }
 
-    use crate::{
-        builder::ClientBuilder,
-        groups::GroupMetadataOptions,
-        storage::{
-            group::{ConversationType, GroupQueryArgs},
-            group_message::StoredGroupMessage,
-        },
-        utils::test::{Delivery, FullXmtpClient, TestClient},
-        Client, StreamHandle,
-    };
-    use futures::StreamExt;
-    use parking_lot::Mutex;
-    use std::sync::{
-        atomic::{AtomicU64, Ordering},
-        Arc,
-    };
-    use wasm_bindgen_test::wasm_bindgen_test;
+    pub fn stream_events_with_quiet_hours_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        conversation_type: Option<ConversationType>,
+        quiet_hours: QuietHours,
+        mut callback: impl FnMut(Result<Vec<ClientEvent<Self>>, SubscribeError>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
+        spawn_stream_task(move |task| async move {
+            let stream = client.stream_events(conversation_type).await?;
+            // closed before holding events back, so held events are flushed on close
+            let stream = with_quiet_hours(
+                task.until_closed(stream),
+                quiet_hours,
+                ClientEvent::is_low_priority,
+            );
+            task.run(stream, |metrics, batch| {
+                match &batch {
+                    Ok(events) => {
+                        for _ in events {
+                            metrics.record_delivered();
+                        }
+                    }
+                    Err(_) => metrics.record(&batch),
+                }
+                futures::future::ready(callback(batch))
+            })
+            .await;
+            tracing::debug!("`stream_events` stream ended, dropping stream");
+            Ok::<_, ClientError>(())
+        })
+    }
+
+    pub fn stream_consent_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<Vec<StoredConsentRecord>, SubscribeError>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
+        spawn_stream_task(move |task| async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_consent_updates();
+
+            let stream = task.until_closed(stream);
+            task.run(stream, |metrics, message| {
+                metrics.record(&message);
+                futures::future::ready(callback(message))
+            })
+            .await;
+            tracing::debug!("`stream_consent` stream ended, dropping stream");
+            Ok::<_, ClientError>(())
+        })
+    }
+
+    /// Like [`Client::stream_consent_with_callback`], but only for the consent of one inbox
+    /// or conversation. For a conversation, `entity` is the hex encoded group id.
+    pub fn stream_consent_for_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        entity_type: ConsentType,
+        entity: String,
+        mut callback: impl FnMut(Result<Vec<StoredConsentRecord>, SubscribeError>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
+        spawn_stream_task(move |task| async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_consent_updates_for(entity_type, entity);
+
+            let stream = task.until_closed(stream);
+            task.run(stream, |metrics, message| {
+                metrics.record(&message);
+                futures::future::ready(callback(message))
+            })
+            .await;
+            tracing::debug!("`stream_consent_for` stream ended, dropping stream");
+            Ok::<_, ClientError>(())
+        })
+    }
+
+    pub fn stream_preferences_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<Vec<UserPreferenceUpdate>, SubscribeError>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
+        spawn_stream_task(move |task| async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_preference_updates();
+
+            let stream = task.until_closed(stream);
+            task.run(stream, |metrics, message| {
+                metrics.record(&message);
+                futures::future::ready(callback(message))
+            })
+            .await;
+            tracing::debug!("`stream_consent` stream ended, dropping stream");
+            Ok::<_, ClientError>(())
+        })
+    }
+
+    /// Get notified when inactive conversations are flagged by
+    /// [`Client::apply_inactivity_policy`], so the user can be asked to re-consent
+    pub fn stream_reconsent_requests_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<Vec<ReconsentRequest>, SubscribeError>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
+        spawn_stream_task(move |task| async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_reconsent_requests();
+
+            let stream = task.until_closed(stream);
+            task.run(stream, |metrics, requests| {
+                metrics.record(&requests);
+                futures::future::ready(callback(requests))
+            })
+            .await;
+            tracing::debug!("`stream_reconsent_requests` stream ended, dropping stream");
+            Ok::<_, ClientError>(())
+        })
+    }
+
+    /// Get notified as messages sent by this installation are queued, published or fail,
+    /// e.g. to render per-message checkmarks
+    pub fn stream_delivery_status_with_callback(
+        client: Arc<Client<ApiClient, V>>,
+        mut callback: impl FnMut(Result<DeliveryStatusUpdate, SubscribeError>) + Send + 'static,
+    ) -> impl crate::StreamHandle<StreamOutput = Result<(), ClientError>> {
+        spawn_stream_task(move |task| async move {
+            let receiver = client.local_events.subscribe();
+            let stream = receiver.stream_delivery_status();
+
+            let stream = task.until_closed(stream);
+            task.run(stream, |metrics, update| {
+                metrics.record(&update);
+                futures::future::ready(callback(update))
+            })
+            .await;
+            tracing::debug!("`stream_delivery_status` stream ended, dropping stream");
+            Ok::<_, ClientError>(())
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use crate::{
+        builder::ClientBuilder,
+        groups::{device_sync::preference_sync::UserPreferenceUpdate, GroupMetadataOptions},
+        storage::{
+            consent_record::{ConsentState, ConsentType, StoredConsentRecord},
+            group::{ConversationType, GroupQueryArgs},
+            group_message::{
+                tests::generate_message, ContentType, DeliveryStatus, GroupMessageKind,
+                StoredGroupMessage,
+            },
+        },
+        subscriptions::{
+            deduplicate_messages, ClientEvent, ConversationRemovalReason, ConversationUpdate,
+            LocalEvents, MessageStreamFilter, StreamAllMessagesOptions, StreamBatchOptions,
+            StreamItem, StreamMessages, SubscribeError,
+        },
+        utils::test::{Delivery, FullXmtpClient, TestClient},
+        Client, StreamHandle,
+    };
+    use futures::StreamExt;
+    use parking_lot::Mutex;
+    use std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_content_types::{encoded_content_to_bytes, text::TextCodec, ContentCodec};
     use xmtp_cryptography::utils::generate_local_wallet;
     use xmtp_id::InboxOwner;
 
@@ -643,6 +2101,60 @@ pub(crate) mod tests {
         assert_eq!(bob_received_groups.group_id, group_id);
     }
 
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_stream_delivery_status() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+
+        let updates = alix.local_events.subscribe().stream_delivery_status();
+        futures::pin_mut!(updates);
+        let message_id = group.send_message(b"hi").await.unwrap();
+
+        let queued = updates.next().await.unwrap().unwrap();
+        assert_eq!(queued.message_id, message_id);
+        assert_eq!(queued.group_id, group.group_id);
+        assert_eq!(queued.status, DeliveryStatus::Unpublished);
+        let published = updates.next().await.unwrap().unwrap();
+        assert_eq!(published.message_id, message_id);
+        assert_eq!(published.status, DeliveryStatus::Published);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_stream_conversation_removals() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let bo_groups = bo.sync_welcomes(&bo.mls_provider().unwrap()).await.unwrap();
+        let bo_group = &bo_groups[0];
+
+        let stream = bo.stream_conversation_updates(None).await.unwrap();
+        futures::pin_mut!(stream);
+
+        group
+            .remove_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        bo_group.sync().await.unwrap();
+
+        let update = stream.next().await.unwrap().unwrap();
+        assert!(matches!(
+            update,
+            ConversationUpdate::Removed {
+                group_id,
+                reason: ConversationRemovalReason::InboxRemoved,
+            } if group_id == group.group_id
+        ));
+    }
+
     #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
     async fn test_stream_messages() {
         xmtp_common::logger();
@@ -722,7 +2234,7 @@ pub(crate) mod tests {
         let notify_pointer = notify.clone();
         let mut handle = Client::<TestClient, _>::stream_all_messages_with_callback(
             Arc::new(caro),
-            None,
+            StreamAllMessagesOptions::default(),
             move |message| {
                 (*messages_clone.lock()).push(message.unwrap());
                 notify_pointer.notify_one();
@@ -770,7 +2282,7 @@ pub(crate) mod tests {
         let delivery_pointer = delivery.clone();
         let mut handle = Client::<TestClient, _>::stream_all_messages_with_callback(
             caro.clone(),
-            None,
+            StreamAllMessagesOptions::default(),
             move |message| {
                 delivery_pointer.notify_one();
                 (*messages_clone.lock()).push(message.unwrap());
@@ -839,7 +2351,6 @@ pub(crate) mod tests {
         assert_eq!(messages.len(), 5);
     }
 
-    #[ignore]
     #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread"))]
     async fn test_stream_all_messages_does_not_lose_messages() {
         let alix = Arc::new(ClientBuilder::new_test_client(&generate_local_wallet()).await);
@@ -861,7 +2372,7 @@ pub(crate) mod tests {
         let blocked_pointer = blocked.clone();
         let mut handle = Client::<TestClient, _>::stream_all_messages_with_callback(
             caro.clone(),
-            None,
+            StreamAllMessagesOptions::default(),
             move |message| {
                 (*messages_clone.lock()).push(message.unwrap());
                 blocked_pointer.fetch_sub(1, Ordering::SeqCst);
@@ -1126,7 +2637,10 @@ pub(crate) mod tests {
 
         let mut closer = Client::<TestClient, _>::stream_all_messages_with_callback(
             bo.clone(),
-            Some(ConversationType::Group),
+            StreamAllMessagesOptions {
+                conversation_type: Some(ConversationType::Group),
+                ..Default::default()
+            },
             move |message| {
                 let mut messages: parking_lot::lock_api::MutexGuard<
                     '_,
@@ -1162,7 +2676,10 @@ pub(crate) mod tests {
 
         let mut closer = Client::<TestClient, _>::stream_all_messages_with_callback(
             bo.clone(),
-            Some(ConversationType::Dm),
+            StreamAllMessagesOptions {
+                conversation_type: Some(ConversationType::Dm),
+                ..Default::default()
+            },
             move |message| {
                 let mut messages: parking_lot::lock_api::MutexGuard<
                     '_,
@@ -1201,7 +2718,7 @@ pub(crate) mod tests {
 
         let mut closer = Client::<TestClient, _>::stream_all_messages_with_callback(
             bo.clone(),
-            None,
+            StreamAllMessagesOptions::default(),
             move |message| {
                 let mut messages = messages_pointer.lock();
                 messages.push(message.unwrap());
@@ -1228,4 +2745,619 @@ pub(crate) mod tests {
 
         closer.end();
     }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_stream_all_messages_filters_content_types() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = Arc::new(ClientBuilder::new_test_client(&generate_local_wallet()).await);
+
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+
+        let messages: Arc<Mutex<Vec<StoredGroupMessage>>> = Arc::new(Mutex::new(Vec::new()));
+        let notify = Delivery::new(Some(1));
+        let (notify_pointer, messages_pointer) = (notify.clone(), messages.clone());
+        let mut closer = Client::<TestClient, _>::stream_all_messages_with_callback(
+            bo.clone(),
+            StreamAllMessagesOptions {
+                filter: MessageStreamFilter::default().allow([ContentType::Text]),
+                ..Default::default()
+            },
+            move |message| {
+                messages_pointer.lock().push(message.unwrap());
+                notify_pointer.notify_one();
+            },
+        );
+        closer.wait_for_ready().await;
+
+        // raw bytes are stored with an unknown content type
+        alix_group.send_message(b"unknown").await.unwrap();
+        let result = notify.wait_for_delivery().await;
+        assert!(
+            result.is_err(),
+            "Stream unexpectedly received an unknown message"
+        );
+
+        let text = TextCodec::encode("text".to_string()).unwrap();
+        alix_group
+            .send_message(&encoded_content_to_bytes(text))
+            .await
+            .unwrap();
+        notify.wait_for_delivery().await.unwrap();
+        {
+            let msgs = messages.lock();
+            assert_eq!(msgs.len(), 1);
+            assert_eq!(msgs[0].content_type, ContentType::Text);
+        }
+        // filtered messages are not delivered, but the stream still moves past them
+        let metrics = closer.metrics();
+        assert_eq!(metrics.items_delivered(), 1);
+        assert_eq!(metrics.items_dropped(), 0);
+        assert!(metrics.cursor().is_some());
+        assert!(metrics.last_item_ns().is_some());
+
+        closer.end();
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_stream_all_messages_filters_consent_states() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = Arc::new(ClientBuilder::new_test_client(&generate_local_wallet()).await);
+
+        let allowed_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        allowed_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        let request_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        request_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+
+        let bo_provider = bo.mls_provider().unwrap();
+        bo.sync_welcomes(&bo_provider).await.unwrap();
+        bo.group(allowed_group.group_id.clone())
+            .unwrap()
+            .update_consent_state(ConsentState::Allowed)
+            .unwrap();
+
+        let messages: Arc<Mutex<Vec<StoredGroupMessage>>> = Arc::new(Mutex::new(Vec::new()));
+        let notify = Delivery::new(Some(1));
+        let (notify_pointer, messages_pointer) = (notify.clone(), messages.clone());
+        let mut closer = Client::<TestClient, _>::stream_all_messages_with_callback(
+            bo.clone(),
+            StreamAllMessagesOptions {
+                consent_states: Some(vec![ConsentState::Allowed]),
+                ..Default::default()
+            },
+            move |message| {
+                messages_pointer.lock().push(message.unwrap());
+                notify_pointer.notify_one();
+            },
+        );
+        closer.wait_for_ready().await;
+
+        request_group.send_message(b"request").await.unwrap();
+        let result = notify.wait_for_delivery().await;
+        assert!(
+            result.is_err(),
+            "Stream unexpectedly received a message request"
+        );
+
+        allowed_group.send_message(b"allowed").await.unwrap();
+        notify.wait_for_delivery().await.unwrap();
+        {
+            let msgs = messages.lock();
+            assert_eq!(msgs.len(), 1);
+            assert_eq!(msgs[0].group_id, allowed_group.group_id);
+        }
+
+        closer.end();
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_stream_all_messages_since_replays_stored_messages() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+
+        let alix_group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        alix_group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        alix_group.send_message(b"before").await.unwrap();
+        let since = xmtp_common::time::now_ns();
+        alix_group.send_message(b"first").await.unwrap();
+        alix_group.send_message(b"second").await.unwrap();
+
+        let bo_provider = bo.mls_provider().unwrap();
+        bo.sync_welcomes(&bo_provider).await.unwrap();
+        let bo_group = bo.group(alix_group.group_id.clone()).unwrap();
+        bo_group.sync().await.unwrap();
+
+        let stream = bo
+            .stream_all_messages(StreamAllMessagesOptions {
+                sent_after_ns: Some(since),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        futures::pin_mut!(stream);
+
+        // sent before the stream polls its subscription, so it has to be replayed from the cursor
+        alix_group.send_message(b"third").await.unwrap();
+
+        let mut received = vec![];
+        tokio::time::timeout(core::time::Duration::from_secs(20), async {
+            while received.len() < 3 {
+                let message = stream.next().await.unwrap().unwrap();
+                if message.kind == GroupMessageKind::Application {
+                    received.push(message.decrypted_message_bytes);
+                }
+            }
+        })
+        .await
+        .expect("timed out waiting for messages");
+        assert_eq!(
+            received,
+            vec![b"first".to_vec(), b"second".to_vec(), b"third".to_vec()]
+        );
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_message_stream_filter_denies_over_allows() {
+        let mut message = crate::storage::group_message::tests::generate_message(
+            None,
+            None,
+            None,
+            Some(ContentType::Reaction),
+        );
+        assert!(MessageStreamFilter::default().matches(&message, "alix"));
+
+        let filter = MessageStreamFilter::default()
+            .allow([ContentType::Text, ContentType::Reaction])
+            .deny([ContentType::Reaction]);
+        assert!(!filter.matches(&message, "alix"));
+        message.content_type = ContentType::Text;
+        assert!(filter.matches(&message, "alix"));
+        message.content_type = ContentType::ReadReceipt;
+        assert!(!filter.matches(&message, "alix"));
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread"))]
+    #[cfg_attr(target_family = "wasm", ignore)]
+    async fn test_stream_events() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = Arc::new(
+            ClientBuilder::new_test_client_with_history(
+                &generate_local_wallet(),
+                crate::utils::test::HISTORY_SYNC_URL,
+            )
+            .await,
+        );
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let notify = Delivery::new(None);
+        let (notify_pointer, events_pointer) = (notify.clone(), events.clone());
+        let mut handle = FullXmtpClient::stream_events_with_callback(bo.clone(), None, move |e| {
+            let kind = match e.unwrap() {
+                ClientEvent::Message(_) => "message",
+                ClientEvent::Conversation(_) => "conversation",
+                ClientEvent::Consent(_) => "consent",
+                ClientEvent::Preferences(_) => "preferences",
+                ClientEvent::ConversationRemoved { .. } => "conversation removed",
+            };
+            events_pointer.lock().push(kind);
+            notify_pointer.notify_one();
+        });
+        handle.wait_for_ready().await;
+
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        group
+            .add_members_by_inbox_id(&[bo.inbox_id()])
+            .await
+            .unwrap();
+        notify.wait_for_delivery().await.unwrap();
+
+        group.send_message(b"hello").await.unwrap();
+        notify.wait_for_delivery().await.unwrap();
+
+        bo.set_consent_states(&[StoredConsentRecord::new(
+            ConsentType::InboxId,
+            ConsentState::Denied,
+            alix.inbox_id().to_string(),
+        )])
+        .await
+        .unwrap();
+        notify.wait_for_delivery().await.unwrap();
+
+        {
+            let events = events.lock();
+            assert!(events.contains(&"conversation"));
+            assert!(events.contains(&"message"));
+            assert!(events.contains(&"consent"));
+        }
+
+        handle.end();
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_lagging_consent_stream_reports_missed_events() {
+        let (tx, rx) = tokio::sync::broadcast::channel::<LocalEvents<()>>(2);
+        let stream = rx.stream_consent_updates();
+        futures::pin_mut!(stream);
+
+        for i in 0..3 {
+            let record = StoredConsentRecord::new(
+                ConsentType::Address,
+                ConsentState::Allowed,
+                format!("0x{i}"),
+            );
+            tx.send(LocalEvents::OutgoingPreferenceUpdates(vec![
+                UserPreferenceUpdate::ConsentUpdate(record),
+            ]))
+            .unwrap();
+        }
+
+        // The oldest update was pushed out of the queue before it was read
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(SubscribeError::LaggedEvents(1)))
+        ));
+        // and the stream carries on from the oldest update still queued
+        let records = stream.next().await.unwrap().unwrap();
+        assert_eq!(records[0].entity, "0x1");
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "current_thread"))]
+    async fn test_consent_stream_filtered_by_entity() {
+        let (tx, rx) = tokio::sync::broadcast::channel::<LocalEvents<()>>(10);
+        let stream = rx.stream_consent_updates_for(ConsentType::InboxId, "bo".to_string());
+        futures::pin_mut!(stream);
+
+        let record = |entity_type, entity: &str| {
+            UserPreferenceUpdate::ConsentUpdate(StoredConsentRecord::new(
+                entity_type,
+                ConsentState::Allowed,
+                entity.to_string(),
+            ))
+        };
+        tx.send(LocalEvents::OutgoingPreferenceUpdates(vec![record(
+            ConsentType::InboxId,
+            "alix",
+        )]))
+        .unwrap();
+        tx.send(LocalEvents::IncomingPreferenceUpdate(vec![
+            record(ConsentType::ConversationId, "bo"),
+            record(ConsentType::InboxId, "bo"),
+        ]))
+        .unwrap();
+
+        // the update for alix alone is skipped, and the other record is filtered out
+        let records = stream.next().await.unwrap().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].entity_type, ConsentType::InboxId);
+        assert_eq!(records[0].entity, "bo");
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_stream_all_messages_with_idle_timeout() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+
+        let stream = alix
+            .stream_all_messages_with_idle_timeout(
+                StreamAllMessagesOptions::default(),
+                Duration::from_millis(100),
+            )
+            .await
+            .unwrap();
+        futures::pin_mut!(stream);
+        assert!(matches!(stream.next().await, Some(StreamItem::Idle)));
+
+        group.send_message(b"hi").await.unwrap();
+        let message = loop {
+            if let StreamItem::Item(message) = stream.next().await.unwrap() {
+                break message.unwrap();
+            }
+        };
+        assert_eq!(message.decrypted_message_bytes, b"hi");
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_deduplicate_messages() {
+        let first = generate_message(None, None, Some(1), None);
+        let second = generate_message(None, None, Some(2), None);
+        let redelivered = futures::stream::iter(vec![
+            Ok(first.clone()),
+            Ok(second.clone()),
+            Err(SubscribeError::GroupMessageNotFound),
+            Ok(first.clone()),
+            Ok(second.clone()),
+        ]);
+
+        let delivered: Vec<_> = deduplicate_messages(redelivered).collect().await;
+        assert_eq!(delivered.len(), 3);
+        assert_eq!(delivered[0].as_ref().unwrap(), &first);
+        assert_eq!(delivered[1].as_ref().unwrap(), &second);
+        assert!(matches!(
+            delivered[2],
+            Err(SubscribeError::GroupMessageNotFound)
+        ));
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test)]
+    async fn test_quiet_hours() {
+        let hours = |h: u64| Duration::from_secs(h * 60 * 60);
+        let at = |h: u64| (DAY * 3 + hours(h)).as_nanos() as i64;
+        let overnight = QuietHours {
+            start: hours(22),
+            end: hours(7),
+        };
+        assert_eq!(overnight.remaining_at(at(23)), Some(hours(8)));
+        assert_eq!(overnight.remaining_at(at(3)), Some(hours(4)));
+        assert_eq!(overnight.remaining_at(at(12)), None);
+
+        // the whole day is quiet, so low-priority events are held until the stream ends
+        let always = QuietHours {
+            start: Duration::ZERO,
+            end: DAY,
+        };
+        let events = futures::stream::iter(vec![
+            Ok::<_, SubscribeError>(("receipt", true)),
+            Ok(("message", false)),
+            Err(SubscribeError::GroupMessageNotFound),
+            Ok(("reaction", true)),
+        ]);
+        let delivered: Vec<_> = with_quiet_hours(events, always, |(_, low)| *low)
+            .map(|batch| batch.map(|events| events.into_iter().map(|(e, _)| e).collect::<Vec<_>>()))
+            .collect()
+            .await;
+        assert_eq!(delivered.len(), 3);
+        assert_eq!(delivered[0].as_ref().unwrap(), &["message"]);
+        assert!(delivered[1].is_err());
+        assert_eq!(delivered[2].as_ref().unwrap(), &["receipt", "reaction"]);
+
+        let never = QuietHours {
+            start: hours(1),
+            end: hours(1),
+        };
+        let events = futures::stream::iter(vec![
+            Ok::<_, SubscribeError>(("receipt", true)),
+            Ok(("message", false)),
+        ]);
+        let delivered: Vec<_> = with_quiet_hours(events, never, |(_, low)| *low)
+            .collect()
+            .await;
+        assert_eq!(delivered.len(), 2);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_stream_all_messages_batched() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+
+        let stream = alix
+            .stream_all_messages_batched(
+                StreamAllMessagesOptions::default(),
+                StreamBatchOptions {
+                    max_batch_size: 2,
+                    max_latency: Duration::from_millis(500),
+                },
+            )
+            .await
+            .unwrap();
+        futures::pin_mut!(stream);
+
+        for body in [&b"one"[..], b"two", b"three"] {
+            group.send_message(body).await.unwrap();
+        }
+
+        // a full batch is yielded right away
+        let batch = stream.next().await.unwrap().unwrap();
+        let bodies: Vec<_> = batch
+            .iter()
+            .map(|m| m.decrypted_message_bytes.clone())
+            .collect();
+        assert_eq!(bodies, vec![b"one".to_vec(), b"two".to_vec()]);
+
+        // a partial batch is yielded once the latency budget runs out
+        let batch = stream.next().await.unwrap().unwrap();
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].decrypted_message_bytes, b"three");
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_stream_all_messages_with_async_callback() {
+        let alix = Arc::new(ClientBuilder::new_test_client(&generate_local_wallet()).await);
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+
+        // a bounded channel, so the callback has to wait for the receiver
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let mut closer = Client::<TestClient, _>::stream_all_messages_with_async_callback(
+            alix.clone(),
+            StreamAllMessagesOptions::default(),
+            move |message| {
+                let tx = tx.clone();
+                async move {
+                    tx.send(message.unwrap()).await.unwrap();
+                }
+            },
+        );
+        closer.wait_for_ready().await;
+
+        group.send_message(b"first").await.unwrap();
+        group.send_message(b"second").await.unwrap();
+
+        let first = rx.recv().await.unwrap();
+        let second = rx.recv().await.unwrap();
+        assert_eq!(first.decrypted_message_bytes, b"first");
+        assert_eq!(second.decrypted_message_bytes, b"second");
+        closer.end();
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_pause_and_resume_stream_all_messages() {
+        let alix = Arc::new(ClientBuilder::new_test_client(&generate_local_wallet()).await);
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut handle = Client::<TestClient, _>::stream_all_messages_with_async_callback(
+            alix.clone(),
+            StreamAllMessagesOptions::default(),
+            move |message| {
+                let _ = tx.send(message.unwrap().decrypted_message_bytes);
+                futures::future::ready(())
+            },
+        );
+        handle.wait_for_ready().await;
+
+        group.send_message(b"live").await.unwrap();
+        assert_eq!(rx.recv().await.unwrap(), b"live");
+
+        assert!(handle.pause());
+        group.send_message(b"while paused").await.unwrap();
+        assert!(
+            xmtp_common::time::timeout(Duration::from_millis(500), rx.recv())
+                .await
+                .is_err()
+        );
+
+        // catches up on what was sent while paused, without repeating what was delivered
+        assert!(handle.resume());
+        assert_eq!(rx.recv().await.unwrap(), b"while paused");
+        group.send_message(b"after").await.unwrap();
+        assert_eq!(rx.recv().await.unwrap(), b"after");
+        handle.end();
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_streams_pause_independently() {
+        let alix = Arc::new(ClientBuilder::new_test_client(&generate_local_wallet()).await);
+
+        let (convo_tx, mut convo_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut conversations = Client::<TestClient, _>::stream_conversations_with_callback(
+            alix.clone(),
+            None,
+            move |convo| {
+                let _ = convo_tx.send(convo.unwrap().group_id);
+            },
+        );
+        let (message_tx, mut message_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut messages = Client::<TestClient, _>::stream_all_messages_with_callback(
+            alix.clone(),
+            StreamAllMessagesOptions::default(),
+            move |message| {
+                let _ = message_tx.send(message.unwrap().decrypted_message_bytes);
+            },
+        );
+        conversations.wait_for_ready().await;
+        messages.wait_for_ready().await;
+
+        assert!(conversations.pause());
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        assert!(
+            xmtp_common::time::timeout(Duration::from_millis(500), convo_rx.recv())
+                .await
+                .is_err()
+        );
+        // the message stream isn't paused along with it
+        group.send_message(b"live").await.unwrap();
+        assert_eq!(message_rx.recv().await.unwrap(), b"live");
+
+        assert!(conversations.resume());
+        assert_eq!(convo_rx.recv().await.unwrap(), group.group_id);
+        conversations.end();
+        messages.end();
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_close_gracefully_drains_in_flight_message() {
+        let alix = Arc::new(ClientBuilder::new_test_client(&generate_local_wallet()).await);
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+
+        let (started_tx, mut started_rx) = tokio::sync::mpsc::channel(1);
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        let handled_clone = handled.clone();
+        let mut closer = Client::<TestClient, _>::stream_all_messages_with_async_callback(
+            alix.clone(),
+            StreamAllMessagesOptions::default(),
+            move |message| {
+                let started_tx = started_tx.clone();
+                let handled = handled_clone.clone();
+                async move {
+                    started_tx.send(()).await.unwrap();
+                    xmtp_common::time::sleep(Duration::from_millis(200)).await;
+                    handled
+                        .lock()
+                        .push(message.unwrap().decrypted_message_bytes);
+                }
+            },
+        );
+        closer.wait_for_ready().await;
+
+        group.send_message(b"in flight").await.unwrap();
+        started_rx.recv().await.unwrap();
+
+        let result = closer.close_gracefully().await;
+        assert!(matches!(result, Ok(Ok(()))));
+        assert_eq!(*handled.lock(), vec![b"in flight".to_vec()]);
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 10))]
+    async fn test_close_gracefully_flushes_pending_batch() {
+        let alix = Arc::new(ClientBuilder::new_test_client(&generate_local_wallet()).await);
+        let group = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut handle = Client::<TestClient, _>::stream_all_messages_batched_with_callback(
+            alix.clone(),
+            StreamAllMessagesOptions::default(),
+            StreamBatchOptions {
+                max_batch_size: 10,
+                max_latency: Duration::from_secs(60),
+            },
+            move |batch| {
+                let _ = tx.send(batch.unwrap().len());
+            },
+        );
+        handle.wait_for_ready().await;
+
+        group.send_message(b"pending").await.unwrap();
+        // received, but waiting for the batch to fill up
+        xmtp_common::time::sleep(Duration::from_millis(500)).await;
+        assert!(rx.try_recv().is_err());
+
+        let result = handle.close_gracefully().await;
+        assert!(matches!(result, Ok(Ok(()))));
+        assert_eq!(rx.recv().await, Some(1));
+    }
 }
@@ -1,5 +1,7 @@
 #![allow(clippy::unwrap_used)]
 
+pub mod sync_simulator;
+
 use std::{
     future::Future,
     sync::{
@@ -305,6 +307,15 @@ impl<ApiClient, V> Client<ApiClient, V> {
     pub(crate) fn set_sync_worker_handle(&self, handle: Arc<WorkerHandle>) {
         *self.sync_worker_handle.lock() = Some(handle);
     }
+
+    /// Simulate a crash while the next envelope is processed: after its writes, before the
+    /// processing transaction commits, processing stops and never resumes. The caller
+    /// abandons the sync, as a dying process would.
+    pub fn crash_next_processing(&self) {
+        self.context
+            .processing_crash
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+    }
 }
 
 pub async fn register_client<T: XmtpApi, V: SmartContractSignatureVerifier>(
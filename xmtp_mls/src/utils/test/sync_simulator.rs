@@ -0,0 +1,323 @@
+//! Drives two installations of the same inbox through interleaved consent, preference and
+//! membership operations with a controlled delivery order, then checks that both installations
+//! converge on the same state.
+//!
+//! Operations are applied locally on one installation. Nothing is received by the other
+//! installation until a [`SimOp::Deliver`] step (or [`SyncSimulator::settle`]) pulls from the
+//! network, so the order in which updates reach each side is under the test's control.
+use xmtp_cryptography::utils::generate_local_wallet;
+
+use super::{FullXmtpClient, HISTORY_SYNC_URL};
+use crate::{
+    builder::ClientBuilder,
+    groups::{GroupMetadataOptions, MlsGroup},
+    storage::{
+        consent_record::StoredConsentRecord, group::GroupQueryArgs,
+        user_preferences::StoredUserPreferences,
+    },
+};
+
+/// One of the two installations of the simulated inbox
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Installation {
+    A,
+    B,
+}
+
+/// A single step of a simulation
+#[derive(Debug, Clone)]
+pub enum SimOp {
+    /// Set a consent record on an installation
+    SetConsent(Installation, StoredConsentRecord),
+    /// Rotate the hmac key on an installation
+    RotateHmacKey(Installation),
+    /// Create a new group on an installation. Groups are referred to by creation index.
+    CreateGroup(Installation),
+    /// Add the peer at `peer` (see [`SyncSimulator::add_peer`]) to the group at `group`
+    AddMember {
+        on: Installation,
+        group: usize,
+        peer: usize,
+    },
+    /// Remove the peer at `peer` from the group at `group`
+    RemoveMember {
+        on: Installation,
+        group: usize,
+        peer: usize,
+    },
+    /// Pull welcomes, the sync group and all groups from the network on an installation
+    Deliver(Installation),
+}
+
+pub struct SyncSimulator {
+    pub a: FullXmtpClient,
+    pub b: FullXmtpClient,
+    pub peers: Vec<FullXmtpClient>,
+    /// Ids of the groups created during the simulation, by creation index
+    pub groups: Vec<Vec<u8>>,
+}
+
+impl SyncSimulator {
+    /// Create two installations of the same inbox that share a sync group
+    pub async fn new() -> Self {
+        let wallet = generate_local_wallet();
+        let a = ClientBuilder::new_test_client_with_history(&wallet, HISTORY_SYNC_URL).await;
+        a.sync_worker_handle()
+            .unwrap()
+            .wait_for_processed_count(1)
+            .await
+            .unwrap();
+        let b = ClientBuilder::new_test_client_with_history(&wallet, HISTORY_SYNC_URL).await;
+        b.sync_worker_handle()
+            .unwrap()
+            .wait_for_processed_count(1)
+            .await
+            .unwrap();
+
+        // b creates a new sync group and invites a, make sure a joined it
+        let a_provider = a.mls_provider().unwrap();
+        a.sync_welcomes(&a_provider).await.unwrap();
+        let a_sync = a.get_sync_group(a_provider.conn_ref()).unwrap();
+        let b_sync = b.get_sync_group(&b.store().conn().unwrap()).unwrap();
+        assert_eq!(a_sync.group_id, b_sync.group_id);
+
+        Self {
+            a,
+            b,
+            peers: vec![],
+            groups: vec![],
+        }
+    }
+
+    pub fn client(&self, installation: Installation) -> &FullXmtpClient {
+        match installation {
+            Installation::A => &self.a,
+            Installation::B => &self.b,
+        }
+    }
+
+    /// Register another inbox that can be added to groups. Returns its index.
+    pub async fn add_peer(&mut self) -> usize {
+        let peer = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        self.peers.push(peer);
+        self.peers.len() - 1
+    }
+
+    fn group(&self, installation: Installation, index: usize) -> MlsGroup<FullXmtpClient> {
+        self.client(installation)
+            .group(self.groups[index].clone())
+            .unwrap()
+    }
+
+    pub async fn apply(&mut self, op: SimOp) {
+        tracing::info!("sync simulator: {op:?}");
+        match op {
+            SimOp::SetConsent(on, record) => {
+                let client = self.client(on);
+                let worker = client.sync_worker_handle().unwrap();
+                let processed = worker.processed_count();
+                // Only records that change something locally are published to the sync group
+                let changes = client
+                    .get_consent_state(record.entity_type, record.entity.clone())
+                    .await
+                    .unwrap()
+                    != record.state;
+                client.set_consent_states(&[record]).await.unwrap();
+                if changes {
+                    worker
+                        .wait_for_processed_count(processed + 1)
+                        .await
+                        .unwrap();
+                }
+            }
+            SimOp::RotateHmacKey(on) => {
+                let client = self.client(on);
+                let worker = client.sync_worker_handle().unwrap();
+                let processed = worker.processed_count();
+                client.rotate_hmac_key().await.unwrap();
+                worker
+                    .wait_for_processed_count(processed + 1)
+                    .await
+                    .unwrap();
+            }
+            SimOp::CreateGroup(on) => {
+                let group = self
+                    .client(on)
+                    .create_group(None, GroupMetadataOptions::default())
+                    .unwrap();
+                // Pull in our other installation
+                group.update_installations().await.unwrap();
+                self.groups.push(group.group_id);
+            }
+            SimOp::AddMember { on, group, peer } => {
+                let inbox_id = self.peers[peer].inbox_id().to_string();
+                self.group(on, group)
+                    .add_members_by_inbox_id(&[inbox_id])
+                    .await
+                    .unwrap();
+            }
+            SimOp::RemoveMember { on, group, peer } => {
+                let inbox_id = self.peers[peer].inbox_id().to_string();
+                self.group(on, group)
+                    .remove_members_by_inbox_id(&[inbox_id.as_str()])
+                    .await
+                    .unwrap();
+            }
+            SimOp::Deliver(on) => {
+                let client = self.client(on);
+                let provider = client.mls_provider().unwrap();
+                client
+                    .sync_all_welcomes_and_groups(&provider, None)
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+
+    pub async fn run(&mut self, ops: impl IntoIterator<Item = SimOp>) {
+        for op in ops {
+            self.apply(op).await;
+        }
+    }
+
+    /// Deliver everything outstanding to both installations
+    pub async fn settle(&mut self) {
+        for on in [Installation::A, Installation::B, Installation::A] {
+            self.apply(SimOp::Deliver(on)).await;
+        }
+    }
+
+    fn consent_records(&self, installation: Installation) -> Vec<StoredConsentRecord> {
+        let conn = self.client(installation).store().conn().unwrap();
+        let mut records = conn.consent_records().unwrap();
+        records.sort_by(|l, r| {
+            (l.entity_type as i32, &l.entity).cmp(&(r.entity_type as i32, &r.entity))
+        });
+        records
+    }
+
+    fn group_ids(&self, installation: Installation) -> Vec<Vec<u8>> {
+        let mut ids: Vec<_> = self
+            .client(installation)
+            .find_groups(GroupQueryArgs::default())
+            .unwrap()
+            .into_iter()
+            .map(|g| g.group_id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Assert that both installations have converged:
+    /// * the same consent records
+    /// * the same hmac key
+    /// * the same set of groups, with the same members
+    pub async fn assert_converged(&self) {
+        assert_eq!(
+            self.consent_records(Installation::A),
+            self.consent_records(Installation::B),
+            "consent records diverged"
+        );
+
+        let hmac_a = StoredUserPreferences::load(&self.a.store().conn().unwrap()).unwrap();
+        let hmac_b = StoredUserPreferences::load(&self.b.store().conn().unwrap()).unwrap();
+        assert_eq!(hmac_a.hmac_key, hmac_b.hmac_key, "hmac keys diverged");
+
+        let group_ids = self.group_ids(Installation::A);
+        assert_eq!(
+            group_ids,
+            self.group_ids(Installation::B),
+            "group lists diverged"
+        );
+
+        for group_id in group_ids {
+            let mut members = vec![];
+            for client in [&self.a, &self.b] {
+                let mut inbox_ids: Vec<_> = client
+                    .group(group_id.clone())
+                    .unwrap()
+                    .members()
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|m| m.inbox_id)
+                    .collect();
+                inbox_ids.sort();
+                members.push(inbox_ids);
+            }
+            assert_eq!(
+                members[0],
+                members[1],
+                "members of group {} diverged",
+                hex::encode(&group_id)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_id::InboxOwner;
+
+    use super::{Installation::*, *};
+    use crate::storage::consent_record::{ConsentState, ConsentType};
+
+    fn consent(entity: &str, state: ConsentState) -> StoredConsentRecord {
+        StoredConsentRecord::new(ConsentType::Address, state, entity.to_string())
+    }
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    #[cfg_attr(target_family = "wasm", ignore)]
+    async fn test_interleaved_operations_converge() {
+        let mut sim = SyncSimulator::new().await;
+        let peer = sim.add_peer().await;
+        let bo = generate_local_wallet().get_address();
+        let caro = generate_local_wallet().get_address();
+
+        sim.run([
+            SimOp::SetConsent(A, consent(&bo, ConsentState::Allowed)),
+            SimOp::CreateGroup(B),
+            SimOp::Deliver(A),
+            SimOp::AddMember {
+                on: A,
+                group: 0,
+                peer,
+            },
+            SimOp::SetConsent(B, consent(&caro, ConsentState::Denied)),
+            SimOp::Deliver(B),
+            SimOp::RotateHmacKey(B),
+            SimOp::SetConsent(A, consent(&bo, ConsentState::Denied)),
+            SimOp::RemoveMember {
+                on: B,
+                group: 0,
+                peer,
+            },
+        ])
+        .await;
+        sim.settle().await;
+
+        sim.assert_converged().await;
+    }
+
+    /// Both installations write the same record before seeing the other's update.
+    /// The later write wins on both sides, whatever order the updates arrive in.
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    #[cfg_attr(target_family = "wasm", ignore)]
+    async fn test_concurrent_consent_writes_converge() {
+        let mut sim = SyncSimulator::new().await;
+        let bo = generate_local_wallet().get_address();
+
+        sim.run([
+            SimOp::SetConsent(A, consent(&bo, ConsentState::Allowed)),
+            SimOp::SetConsent(B, consent(&bo, ConsentState::Denied)),
+        ])
+        .await;
+        sim.settle().await;
+
+        sim.assert_converged().await;
+    }
+}
@@ -0,0 +1,187 @@
+//! Deleting local data at the user's request, e.g. to free up space or before handing a device
+//! on. The client stays usable afterwards: the identity, key packages and device sync groups are
+//! kept, and sync cursors are only dropped along with the conversation they belong to, so deleted
+//! messages aren't fetched again.
+use openmls::prelude::{GroupId, MlsGroup as OpenMlsGroup};
+use openmls_traits::OpenMlsProvider;
+use xmtp_id::scw_verifier::SmartContractSignatureVerifier;
+
+use crate::{
+    client::ClientError,
+    groups::GroupError,
+    storage::{group::GroupQueryArgs, NotFound, ProviderTransactions},
+    subscriptions::{LocalEvents, WipeScope},
+    Client, XmtpApi, XmtpOpenMlsProvider, MLS_COMMIT_LOCK,
+};
+
+impl<ApiClient, V> Client<ApiClient, V>
+where
+    ApiClient: XmtpApi,
+    V: SmartContractSignatureVerifier,
+{
+    /// Delete the messages of every conversation, along with their reactions, edits and the other
+    /// records derived from them. Returns the number of messages deleted.
+    pub async fn wipe_messages(&self) -> Result<usize, ClientError> {
+        let provider = self.mls_provider()?;
+        let deleted = provider.conn_ref().wipe_messages(None)?;
+        self.finish_wipe(&provider, WipeScope::Messages).await;
+        Ok(deleted)
+    }
+
+    /// Delete the messages of `group_id`. With `mls_state`, the conversation is deleted entirely,
+    /// including its MLS state, and is no longer listed or synced. This installation remains a
+    /// member of the group for everyone else until it's removed. Returns the number of messages
+    /// deleted.
+    pub async fn wipe_conversation(
+        &self,
+        group_id: &[u8],
+        mls_state: bool,
+    ) -> Result<usize, ClientError> {
+        let provider = self.mls_provider()?;
+        if provider.conn_ref().find_group(group_id.to_vec())?.is_none() {
+            return Err(NotFound::GroupById(group_id.to_vec()).into());
+        }
+        let deleted = if mls_state {
+            provider.transaction(|provider| wipe_group(provider, group_id))?
+        } else {
+            provider.conn_ref().wipe_messages(Some(group_id))?
+        };
+        self.finish_wipe(
+            &provider,
+            WipeScope::Conversation {
+                group_id: group_id.to_vec(),
+                mls_state,
+            },
+        )
+        .await;
+        Ok(deleted)
+    }
+
+    /// Delete every conversation with its MLS state, the consent records, profiles and
+    /// installation capabilities of other inboxes, the block list, custom preferences, and the
+    /// record of unknown content types. What's left is a client that's just been registered.
+    /// Returns the number of messages deleted.
+    pub async fn wipe_all_local_data(&self) -> Result<usize, ClientError> {
+        let provider = self.mls_provider()?;
+        let groups = provider.conn_ref().find_groups(GroupQueryArgs {
+            include_duplicate_dms: true,
+            ..Default::default()
+        })?;
+        let deleted = provider.transaction(|provider| {
+            let mut deleted = 0;
+            for group in &groups {
+                deleted += wipe_group(provider, &group.id)?;
+            }
+            provider.conn_ref().wipe_user_records()?;
+            Ok::<_, ClientError>(deleted)
+        })?;
+        self.finish_wipe(&provider, WipeScope::All).await;
+        Ok(deleted)
+    }
+
+    async fn finish_wipe(&self, provider: &XmtpOpenMlsProvider, scope: WipeScope) {
+        if let Err(e) = provider.conn_ref().truncate_wal() {
+            tracing::warn!("failed to truncate the write-ahead log after a wipe: {e}");
+        }
+        self.publish_local_event(LocalEvents::LocalDataWiped(scope))
+            .await;
+    }
+}
+
+/// Delete the MLS state of `group_id` from the key store, and every local record of the group
+pub(crate) fn wipe_group(
+    provider: &XmtpOpenMlsProvider,
+    group_id: &[u8],
+) -> Result<usize, ClientError> {
+    let _lock = MLS_COMMIT_LOCK.get_lock_sync(group_id.to_vec())?;
+    let mls_group = OpenMlsGroup::load(provider.storage(), &GroupId::from_slice(group_id))
+        .map_err(GroupError::from)?;
+    // a group whose welcome failed to process has no MLS state
+    if let Some(mut mls_group) = mls_group {
+        mls_group
+            .delete(provider.storage())
+            .map_err(GroupError::from)?;
+    }
+    Ok(provider.conn_ref().wipe_conversation_records(group_id)?)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+    use super::*;
+    use crate::{
+        builder::ClientBuilder, groups::GroupMetadataOptions, storage::group_message::MsgQueryArgs,
+        subscriptions::StreamMessages,
+    };
+    use futures::StreamExt;
+    use wasm_bindgen_test::wasm_bindgen_test;
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    #[wasm_bindgen_test(unsupported = tokio::test(flavor = "multi_thread", worker_threads = 1))]
+    async fn test_wipe_local_data() {
+        let alix = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let bo = ClientBuilder::new_test_client(&generate_local_wallet()).await;
+        let kept = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        let wiped = alix
+            .create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+        for group in [&kept, &wiped] {
+            group
+                .add_members_by_inbox_id(&[bo.inbox_id()])
+                .await
+                .unwrap();
+            group.send_message(b"hello").await.unwrap();
+        }
+
+        let wipes = alix.local_events.subscribe().stream_local_data_wipes();
+        futures::pin_mut!(wipes);
+
+        alix.wipe_conversation(&wiped.group_id, true).await.unwrap();
+        assert_eq!(
+            wipes.next().await.unwrap().unwrap(),
+            WipeScope::Conversation {
+                group_id: wiped.group_id.clone(),
+                mls_state: true,
+            }
+        );
+        assert!(alix.group(wiped.group_id.clone()).is_err());
+        let provider = alix.mls_provider().unwrap();
+        assert!(
+            OpenMlsGroup::load(provider.storage(), &GroupId::from_slice(&wiped.group_id))
+                .unwrap()
+                .is_none()
+        );
+
+        assert!(alix.wipe_messages().await.unwrap() > 0);
+        assert_eq!(wipes.next().await.unwrap().unwrap(), WipeScope::Messages);
+        assert!(kept
+            .find_messages(&MsgQueryArgs::default())
+            .unwrap()
+            .is_empty());
+
+        // the remaining group still works and wiped messages aren't fetched again
+        kept.sync().await.unwrap();
+        assert!(kept
+            .find_messages(&MsgQueryArgs::default())
+            .unwrap()
+            .is_empty());
+        kept.send_message(b"still here").await.unwrap();
+        assert_eq!(
+            kept.find_messages(&MsgQueryArgs::default()).unwrap().len(),
+            1
+        );
+
+        alix.wipe_all_local_data().await.unwrap();
+        assert_eq!(wipes.next().await.unwrap().unwrap(), WipeScope::All);
+        assert!(alix
+            .find_groups(GroupQueryArgs::default())
+            .unwrap()
+            .is_empty());
+        alix.create_group(None, GroupMetadataOptions::default())
+            .unwrap();
+    }
+}
@@ -51,6 +51,44 @@ impl Error {
         self.source = Some(source.into());
         self
     }
+
+    /// Whether the request failed because the API couldn't be reached, e.g. no connection or a
+    /// timeout, rather than being rejected by the API
+    pub fn is_connection_error(&self) -> bool {
+        if matches!(
+            self.kind,
+            ErrorKind::SetupCreateChannelError | ErrorKind::SetupConnectionError
+        ) {
+            return true;
+        }
+        let mut source = self
+            .source
+            .as_ref()
+            .map(|source| &**source as &(dyn StdError + 'static));
+        while let Some(err) = source {
+            if is_transport_error(err) {
+                return true;
+            }
+            source = err.source();
+        }
+        false
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn is_transport_error(err: &(dyn StdError + 'static)) -> bool {
+    if let Some(status) = err.downcast_ref::<tonic::Status>() {
+        return matches!(
+            status.code(),
+            tonic::Code::Unavailable | tonic::Code::DeadlineExceeded
+        );
+    }
+    err.is::<std::io::Error>()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn is_transport_error(_err: &(dyn StdError + 'static)) -> bool {
+    false
 }
 
 impl From<hex::FromHexError> for Error {
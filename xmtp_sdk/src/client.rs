@@ -0,0 +1,415 @@
+//! Creating a client, and the conversations, streams and consent it manages.
+use std::{collections::HashMap, sync::Arc};
+
+use futures::{Stream, StreamExt};
+use xmtp_common::retry::Retry;
+use xmtp_id::{
+    associations::{
+        generate_inbox_id,
+        unverified::{UnverifiedRecoverableEcdsaSignature, UnverifiedSignature},
+    },
+    InboxOwner,
+};
+use xmtp_mls::{
+    api::ApiClientWrapper,
+    groups::{device_sync::MessageHistoryUrls, GroupMetadataOptions},
+    identity::IdentityStrategy,
+    storage::{
+        consent_record::StoredConsentRecord,
+        group::{ConversationType, GroupQueryArgs},
+        EncryptedMessageStore, StorageOption,
+    },
+    subscriptions::{ClientEvent, StreamAllMessagesOptions},
+};
+
+use crate::{
+    ConsentEntity, ConsentRecord, ConsentState, Conversation, ConversationKind, Error, Message,
+};
+
+pub(crate) type ApiClient = xmtp_api_grpc::grpc_api_helper::Client;
+pub(crate) type MlsClient = xmtp_mls::Client<ApiClient>;
+
+/// Nonce used to derive the inbox id of an address that has no inbox yet
+const INBOX_NONCE: u64 = 0;
+
+/// The XMTP network to connect to
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Env {
+    /// A node running on this machine, for development
+    Local,
+    Dev,
+    Production,
+    /// A self-hosted node
+    Custom {
+        host: String,
+        is_secure: bool,
+    },
+}
+
+impl Env {
+    fn host(&self) -> (&str, bool) {
+        match self {
+            Self::Local => (xmtp_api_grpc::LOCALHOST_ADDRESS, false),
+            Self::Dev => (xmtp_api_grpc::DEV_ADDRESS, true),
+            Self::Production => ("https://grpc.production.xmtp.network:443", true),
+            Self::Custom { host, is_secure } => (host, *is_secure),
+        }
+    }
+
+    fn history_sync_url(&self) -> Option<&'static str> {
+        match self {
+            Self::Local => Some(MessageHistoryUrls::LOCAL_ADDRESS),
+            Self::Dev => Some(MessageHistoryUrls::DEV_ADDRESS),
+            Self::Production => Some(MessageHistoryUrls::PRODUCTION_ADDRESS),
+            Self::Custom { .. } => None,
+        }
+    }
+}
+
+/// Configures and creates a [`Client`]
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    env: Env,
+    db_path: Option<String>,
+    encryption_key: Option<[u8; 32]>,
+    history_sync_url: Option<String>,
+}
+
+impl ClientBuilder {
+    /// Where to store the local database. Without a path, the database is kept in memory and
+    /// lost when the client is dropped.
+    pub fn db_path(mut self, path: impl Into<String>) -> Self {
+        self.db_path = Some(path.into());
+        self
+    }
+
+    /// Encrypt the local database with `key`
+    pub fn encryption_key(mut self, key: [u8; 32]) -> Self {
+        self.encryption_key = Some(key);
+        self
+    }
+
+    /// Sync history between installations through `url`, instead of the environment's default
+    pub fn history_sync_url(mut self, url: impl Into<String>) -> Self {
+        self.history_sync_url = Some(url.into());
+        self
+    }
+
+    /// Connect to the network and open the local database.
+    ///
+    /// If `owner` has no inbox on this installation yet, one is created or the installation is
+    /// added to the owner's existing inbox, and `owner` is asked to sign the registration.
+    pub async fn build(self, owner: &impl InboxOwner) -> Result<Client, Error> {
+        let (host, is_secure) = self.env.host();
+        let api_client = ApiClient::create(host, is_secure).await?;
+
+        let address = owner.get_address().to_lowercase();
+        let inbox_id = match ApiClientWrapper::new(Arc::new(api_client.clone()), Retry::default())
+            .get_inbox_ids(vec![address.clone()])
+            .await?
+            .remove(&address)
+        {
+            Some(inbox_id) => inbox_id,
+            None => generate_inbox_id(&address, &INBOX_NONCE)?,
+        };
+
+        let storage = match self.db_path {
+            Some(path) => StorageOption::Persistent(path),
+            None => StorageOption::Ephemeral,
+        };
+        let store = match self.encryption_key {
+            Some(key) => EncryptedMessageStore::new(storage, key).await?,
+            None => EncryptedMessageStore::new_unencrypted(storage).await?,
+        };
+
+        let mut builder = xmtp_mls::builder::ClientBuilder::new(IdentityStrategy::new(
+            inbox_id,
+            address,
+            INBOX_NONCE,
+            None,
+        ))
+        .api_client(api_client)
+        .store(store);
+        if let Some(url) = self
+            .history_sync_url
+            .as_deref()
+            .or(self.env.history_sync_url())
+        {
+            builder = builder.history_sync_url(url);
+        }
+        let inner = builder.build().await?;
+
+        if let Some(mut signature_request) = inner.identity().signature_request() {
+            tracing::info!(inbox_id = inner.inbox_id(), "registering installation");
+            let signature = owner.sign(&signature_request.signature_text())?;
+            signature_request
+                .add_signature(
+                    UnverifiedSignature::RecoverableEcdsa(
+                        UnverifiedRecoverableEcdsaSignature::new(signature.into()),
+                    ),
+                    inner.scw_verifier(),
+                )
+                .await?;
+            inner.register_identity(signature_request).await?;
+        }
+
+        Ok(Client { inner })
+    }
+}
+
+/// Filters for [`Client::list_conversations`]
+#[derive(Debug, Clone, Default)]
+pub struct ConversationQuery {
+    kind: Option<ConversationKind>,
+    consent_states: Option<Vec<ConsentState>>,
+    created_after_ns: Option<i64>,
+    created_before_ns: Option<i64>,
+    limit: Option<i64>,
+}
+
+impl ConversationQuery {
+    pub fn kind(mut self, kind: ConversationKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn consent_states(mut self, consent_states: Vec<ConsentState>) -> Self {
+        self.consent_states = Some(consent_states);
+        self
+    }
+
+    pub fn created_after_ns(mut self, created_after_ns: i64) -> Self {
+        self.created_after_ns = Some(created_after_ns);
+        self
+    }
+
+    pub fn created_before_ns(mut self, created_before_ns: i64) -> Self {
+        self.created_before_ns = Some(created_before_ns);
+        self
+    }
+
+    pub fn limit(mut self, limit: i64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+impl From<ConversationQuery> for GroupQueryArgs {
+    fn from(query: ConversationQuery) -> Self {
+        GroupQueryArgs {
+            conversation_type: query.kind.map(conversation_type),
+            consent_states: query
+                .consent_states
+                .map(|states| states.into_iter().map(Into::into).collect()),
+            created_after_ns: query.created_after_ns,
+            created_before_ns: query.created_before_ns,
+            limit: query.limit,
+            ..Default::default()
+        }
+    }
+}
+
+fn conversation_type(kind: ConversationKind) -> ConversationType {
+    match kind {
+        ConversationKind::Group => ConversationType::Group,
+        ConversationKind::Dm => ConversationType::Dm,
+    }
+}
+
+/// Something that happened on any of the client's conversations
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Event {
+    Message(Message),
+    /// The client created or was added to a conversation
+    Conversation(Conversation),
+    /// Consent changed on this or another of the inbox's installations
+    Consent(Vec<ConsentRecord>),
+}
+
+/// A client for one installation of an inbox
+#[derive(Clone)]
+pub struct Client {
+    inner: MlsClient,
+}
+
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+            .field("inbox_id", &self.inbox_id())
+            .field("installation_id", &hex::encode(self.installation_id()))
+            .finish()
+    }
+}
+
+impl Client {
+    pub fn builder(env: Env) -> ClientBuilder {
+        ClientBuilder {
+            env,
+            db_path: None,
+            encryption_key: None,
+            history_sync_url: None,
+        }
+    }
+
+    pub fn inbox_id(&self) -> &str {
+        self.inner.inbox_id()
+    }
+
+    pub fn installation_id(&self) -> Vec<u8> {
+        self.inner.installation_public_key().into()
+    }
+
+    /// Whether each of `account_addresses` can be messaged
+    pub async fn can_message(
+        &self,
+        account_addresses: &[String],
+    ) -> Result<HashMap<String, bool>, Error> {
+        Ok(self.inner.can_message(account_addresses).await?)
+    }
+
+    /// Fetch conversations the client was added to and sync every conversation.
+    /// Returns the number of conversations synced.
+    pub async fn sync_conversations(&self) -> Result<usize, Error> {
+        let provider = self.inner.mls_provider()?;
+        Ok(self
+            .inner
+            .sync_all_welcomes_and_groups(&provider, None)
+            .await?)
+    }
+
+    /// Create a group chat with the given members
+    pub async fn create_group(&self, account_addresses: &[String]) -> Result<Conversation, Error> {
+        let group = self
+            .inner
+            .create_group_with_members(account_addresses, None, GroupMetadataOptions::default())
+            .await?;
+        Ok(Conversation::new(group))
+    }
+
+    /// Create a direct message with `account_address`, or return the existing one
+    pub async fn create_dm(&self, account_address: &str) -> Result<Conversation, Error> {
+        let dm = self.inner.create_dm(account_address.to_string()).await?;
+        Ok(Conversation::new(dm))
+    }
+
+    pub fn conversation(&self, id: &[u8]) -> Result<Conversation, Error> {
+        Ok(Conversation::new(self.inner.group(id.to_vec())?))
+    }
+
+    /// Conversations stored on this installation. Call [`Client::sync_conversations`] first to
+    /// fetch new ones.
+    pub fn list_conversations(&self, query: ConversationQuery) -> Result<Vec<Conversation>, Error> {
+        Ok(self
+            .inner
+            .find_groups(query.into())?
+            .into_iter()
+            .map(Conversation::new)
+            .collect())
+    }
+
+    /// Conversations as they are created or joined
+    pub async fn stream_conversations(
+        &self,
+        kind: Option<ConversationKind>,
+    ) -> Result<impl Stream<Item = Result<Conversation, Error>> + '_, Error> {
+        let stream = self
+            .inner
+            .stream_conversations(kind.map(conversation_type))
+            .await?;
+        Ok(stream.map(|group| Ok(Conversation::new(group?))))
+    }
+
+    /// Messages from every conversation as they arrive
+    pub async fn stream_all_messages(
+        &self,
+        kind: Option<ConversationKind>,
+    ) -> Result<impl Stream<Item = Result<Message, Error>> + '_, Error> {
+        let stream = self
+            .inner
+            .stream_all_messages(StreamAllMessagesOptions {
+                conversation_type: kind.map(conversation_type),
+                ..Default::default()
+            })
+            .await?;
+        Ok(stream.map(|message| Ok(message?.into())))
+    }
+
+    /// Messages, conversations and consent changes as a single stream
+    pub async fn stream_events(
+        &self,
+        kind: Option<ConversationKind>,
+    ) -> Result<impl Stream<Item = Result<Event, Error>> + '_, Error> {
+        let stream = self
+            .inner
+            .stream_events(kind.map(conversation_type))
+            .await?;
+        Ok(stream.filter_map(|event| async move {
+            let event = match event {
+                Ok(ClientEvent::Message(message)) => Event::Message(message.into()),
+                Ok(ClientEvent::Conversation(group)) => {
+                    Event::Conversation(Conversation::new(group))
+                }
+                Ok(ClientEvent::Consent(records)) => {
+                    let records: Result<Vec<ConsentRecord>, Error> =
+                        records.into_iter().map(TryInto::try_into).collect();
+                    match records {
+                        Ok(records) => Event::Consent(records),
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+                Ok(_) => return None,
+                Err(e) => return Some(Err(e.into())),
+            };
+            Some(Ok(event))
+        }))
+    }
+
+    pub async fn set_consent_states(&self, records: Vec<ConsentRecord>) -> Result<(), Error> {
+        let records: Vec<StoredConsentRecord> = records.into_iter().map(Into::into).collect();
+        Ok(self.inner.set_consent_states(&records).await?)
+    }
+
+    pub async fn consent_state(&self, entity: ConsentEntity) -> Result<ConsentState, Error> {
+        let (entity_type, entity) = entity.into_parts();
+        Ok(self
+            .inner
+            .get_consent_state(entity_type, entity)
+            .await?
+            .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xmtp_cryptography::utils::generate_local_wallet;
+
+    use super::*;
+    use crate::MessageQuery;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_send_and_receive() {
+        let alix_wallet = generate_local_wallet();
+        let bo_wallet = generate_local_wallet();
+        let alix = Client::builder(Env::Local)
+            .build(&alix_wallet)
+            .await
+            .unwrap();
+        let bo = Client::builder(Env::Local).build(&bo_wallet).await.unwrap();
+
+        let dm = alix.create_dm(&bo_wallet.get_address()).await.unwrap();
+        dm.send_text("gm").await.unwrap();
+
+        bo.sync_conversations().await.unwrap();
+        let conversations = bo
+            .list_conversations(ConversationQuery::default().kind(ConversationKind::Dm))
+            .unwrap();
+        assert_eq!(conversations.len(), 1);
+        let messages = conversations[0]
+            .messages(MessageQuery::default().kind(crate::MessageKind::Application))
+            .unwrap();
+        assert_eq!(messages[0].text().unwrap().as_deref(), Some("gm"));
+        assert_eq!(messages[0].sender_inbox_id, alix.inbox_id());
+    }
+}
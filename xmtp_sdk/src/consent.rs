@@ -0,0 +1,105 @@
+//! Whether the user wants to hear from a conversation, inbox or address.
+use xmtp_mls::storage::consent_record::{
+    ConsentState as MlsConsentState, ConsentType, StoredConsentRecord,
+};
+
+/// The user's decision about an entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum ConsentState {
+    /// No decision has been made
+    #[default]
+    Unknown,
+    Allowed,
+    Denied,
+}
+
+/// What a consent decision applies to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ConsentEntity {
+    /// A conversation, by id
+    Conversation(Vec<u8>),
+    InboxId(String),
+    /// An account address. Consent set on an address also applies to its inbox.
+    Address(String),
+}
+
+/// A consent decision about an entity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsentRecord {
+    pub entity: ConsentEntity,
+    pub state: ConsentState,
+}
+
+impl From<MlsConsentState> for ConsentState {
+    fn from(state: MlsConsentState) -> Self {
+        match state {
+            MlsConsentState::Unknown => Self::Unknown,
+            MlsConsentState::Allowed => Self::Allowed,
+            MlsConsentState::Denied => Self::Denied,
+        }
+    }
+}
+
+impl From<ConsentState> for MlsConsentState {
+    fn from(state: ConsentState) -> Self {
+        match state {
+            ConsentState::Unknown => Self::Unknown,
+            ConsentState::Allowed => Self::Allowed,
+            ConsentState::Denied => Self::Denied,
+        }
+    }
+}
+
+impl ConsentEntity {
+    pub(crate) fn into_parts(self) -> (ConsentType, String) {
+        match self {
+            Self::Conversation(id) => (ConsentType::ConversationId, hex::encode(id)),
+            Self::InboxId(inbox_id) => (ConsentType::InboxId, inbox_id),
+            Self::Address(address) => (ConsentType::Address, address),
+        }
+    }
+}
+
+impl From<ConsentRecord> for StoredConsentRecord {
+    fn from(record: ConsentRecord) -> Self {
+        let (entity_type, entity) = record.entity.into_parts();
+        StoredConsentRecord::new(entity_type, record.state.into(), entity)
+    }
+}
+
+impl TryFrom<StoredConsentRecord> for ConsentRecord {
+    type Error = crate::Error;
+
+    fn try_from(record: StoredConsentRecord) -> Result<Self, Self::Error> {
+        let entity = match record.entity_type {
+            ConsentType::ConversationId => ConsentEntity::Conversation(
+                hex::decode(&record.entity).map_err(|e| crate::Error::Content(e.into()))?,
+            ),
+            ConsentType::InboxId => ConsentEntity::InboxId(record.entity),
+            ConsentType::Address => ConsentEntity::Address(record.entity),
+        };
+        Ok(Self {
+            entity,
+            state: record.state.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversation_consent_round_trips() {
+        let record = ConsentRecord {
+            entity: ConsentEntity::Conversation(vec![0xab, 0xcd]),
+            state: ConsentState::Denied,
+        };
+        let stored: StoredConsentRecord = record.clone().into();
+        assert_eq!(stored.entity_type, ConsentType::ConversationId);
+        assert_eq!(stored.entity, "abcd");
+        assert_eq!(ConsentRecord::try_from(stored).unwrap(), record);
+    }
+}
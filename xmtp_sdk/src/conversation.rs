@@ -0,0 +1,202 @@
+//! Group chats and direct messages.
+use futures::{Stream, StreamExt};
+use xmtp_content_types::{encoded_content_to_bytes, text::TextCodec, ContentCodec};
+use xmtp_mls::{
+    groups::{members::PermissionLevel, MlsGroup},
+    storage::{
+        group::ConversationType,
+        group_message::{GroupMessageKind, MsgQueryArgs, SortDirection},
+    },
+};
+
+use crate::{client::MlsClient, ConsentState, Error, Message, MessageKind};
+
+/// Whether a conversation is a group chat or a direct message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConversationKind {
+    Group,
+    Dm,
+}
+
+/// A member of a conversation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub inbox_id: String,
+    pub account_addresses: Vec<String>,
+    pub installation_ids: Vec<Vec<u8>>,
+    pub is_admin: bool,
+    pub consent_state: ConsentState,
+}
+
+/// Filters for [`Conversation::messages`]
+#[derive(Debug, Clone, Default)]
+pub struct MessageQuery {
+    sent_after_ns: Option<i64>,
+    sent_before_ns: Option<i64>,
+    kind: Option<MessageKind>,
+    limit: Option<i64>,
+    newest_first: bool,
+}
+
+impl MessageQuery {
+    pub fn sent_after_ns(mut self, sent_after_ns: i64) -> Self {
+        self.sent_after_ns = Some(sent_after_ns);
+        self
+    }
+
+    pub fn sent_before_ns(mut self, sent_before_ns: i64) -> Self {
+        self.sent_before_ns = Some(sent_before_ns);
+        self
+    }
+
+    pub fn kind(mut self, kind: MessageKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    pub fn limit(mut self, limit: i64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Return the most recent messages first. Oldest first by default.
+    pub fn newest_first(mut self) -> Self {
+        self.newest_first = true;
+        self
+    }
+}
+
+impl From<MessageQuery> for MsgQueryArgs {
+    fn from(query: MessageQuery) -> Self {
+        MsgQueryArgs {
+            sent_after_ns: query.sent_after_ns,
+            sent_before_ns: query.sent_before_ns,
+            kind: query.kind.map(|kind| match kind {
+                MessageKind::Application => GroupMessageKind::Application,
+                MessageKind::MembershipChange => GroupMessageKind::MembershipChange,
+            }),
+            limit: query.limit,
+            direction: Some(if query.newest_first {
+                SortDirection::Descending
+            } else {
+                SortDirection::Ascending
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+/// A group chat or direct message the client is a member of
+#[derive(Clone)]
+pub struct Conversation {
+    inner: MlsGroup<MlsClient>,
+}
+
+impl std::fmt::Debug for Conversation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Conversation")
+            .field("id", &hex::encode(self.id()))
+            .field("created_at_ns", &self.created_at_ns())
+            .finish()
+    }
+}
+
+impl Conversation {
+    pub(crate) fn new(inner: MlsGroup<MlsClient>) -> Self {
+        Self { inner }
+    }
+
+    pub fn id(&self) -> &[u8] {
+        &self.inner.group_id
+    }
+
+    pub fn created_at_ns(&self) -> i64 {
+        self.inner.created_at_ns
+    }
+
+    pub async fn kind(&self) -> Result<ConversationKind, Error> {
+        let provider = self.inner.mls_provider()?;
+        Ok(match self.inner.conversation_type(&provider).await? {
+            ConversationType::Dm => ConversationKind::Dm,
+            _ => ConversationKind::Group,
+        })
+    }
+
+    /// Fetch and process new messages and membership changes from the network
+    pub async fn sync(&self) -> Result<(), Error> {
+        Ok(self.inner.sync().await?)
+    }
+
+    /// Send encoded content, returning the id of the new message
+    pub async fn send(&self, content: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(self.inner.send_message(content).await?)
+    }
+
+    /// Send a text message, returning the id of the new message
+    pub async fn send_text(&self, text: &str) -> Result<Vec<u8>, Error> {
+        let encoded = encoded_content_to_bytes(TextCodec::encode(text.to_string())?);
+        self.send(&encoded).await
+    }
+
+    /// Messages stored on this installation. Call [`Conversation::sync`] first to fetch new ones.
+    pub fn messages(&self, query: MessageQuery) -> Result<Vec<Message>, Error> {
+        Ok(self
+            .inner
+            .find_messages(&query.into())?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// New messages as they arrive
+    pub async fn stream(&self) -> Result<impl Stream<Item = Result<Message, Error>> + '_, Error> {
+        let stream = self.inner.stream().await?;
+        Ok(stream.map(|message| Ok(message?.into())))
+    }
+
+    pub async fn members(&self) -> Result<Vec<Member>, Error> {
+        Ok(self
+            .inner
+            .members()
+            .await?
+            .into_iter()
+            .map(|member| Member {
+                is_admin: member.permission_level != PermissionLevel::Member,
+                inbox_id: member.inbox_id,
+                account_addresses: member.account_addresses,
+                installation_ids: member.installation_ids,
+                consent_state: member.consent_state.into(),
+            })
+            .collect())
+    }
+
+    pub async fn add_members(&self, account_addresses: &[String]) -> Result<(), Error> {
+        Ok(self.inner.add_members(account_addresses).await?)
+    }
+
+    pub async fn add_members_by_inbox_id(&self, inbox_ids: &[String]) -> Result<(), Error> {
+        Ok(self.inner.add_members_by_inbox_id(inbox_ids).await?)
+    }
+
+    pub async fn remove_members(&self, account_addresses: &[String]) -> Result<(), Error> {
+        Ok(self.inner.remove_members(account_addresses).await?)
+    }
+
+    pub fn name(&self) -> Result<String, Error> {
+        let provider = self.inner.mls_provider()?;
+        Ok(self.inner.group_name(&provider)?)
+    }
+
+    pub async fn set_name(&self, name: &str) -> Result<(), Error> {
+        Ok(self.inner.update_group_name(name.to_string()).await?)
+    }
+
+    pub fn consent_state(&self) -> Result<ConsentState, Error> {
+        Ok(self.inner.consent_state()?.into())
+    }
+
+    pub fn set_consent_state(&self, state: ConsentState) -> Result<(), Error> {
+        Ok(self.inner.update_consent_state(state.into())?)
+    }
+}
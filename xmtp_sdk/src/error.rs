@@ -0,0 +1,172 @@
+use thiserror::Error;
+use xmtp_common::RetryableError;
+
+type Source = Box<dyn std::error::Error + Send + Sync>;
+
+/// Errors returned by the SDK.
+///
+/// Errors from the underlying implementation are grouped by the area that failed. Their
+/// messages are informative only, the wrapped types are not part of the public API.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Connecting to the network failed
+    #[error("connection error: {0}")]
+    Connection(Source),
+    /// Opening, reading or writing the local database failed
+    #[error("storage error: {source}")]
+    Storage { source: Source, retryable: bool },
+    /// Creating, registering or signing for the inbox failed
+    #[error("identity error: {0}")]
+    Identity(Source),
+    #[error("client error: {source}")]
+    Client { source: Source, retryable: bool },
+    #[error("conversation error: {source}")]
+    Conversation { source: Source, retryable: bool },
+    #[error("stream error: {source}")]
+    Stream { source: Source, retryable: bool },
+    #[error("content error: {0}")]
+    Content(Source),
+    /// What was looked up doesn't exist, e.g. a conversation that isn't stored locally
+    #[error("{0}")]
+    NotFound(String),
+}
+
+impl Error {
+    /// Whether the operation may succeed if it is attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Storage { retryable, .. }
+            | Self::Client { retryable, .. }
+            | Self::Conversation { retryable, .. }
+            | Self::Stream { retryable, .. } => *retryable,
+            Self::Connection(_) => true,
+            Self::Identity(_) | Self::Content(_) | Self::NotFound(_) => false,
+        }
+    }
+}
+
+impl From<xmtp_mls::builder::ClientBuilderError> for Error {
+    fn from(err: xmtp_mls::builder::ClientBuilderError) -> Self {
+        use xmtp_mls::builder::ClientBuilderError::*;
+        match err {
+            StorageError(err) => err.into(),
+            ApiError(err) => err.into(),
+            WrappedApiError(err) => err.into(),
+            ClientError(err) => err.into(),
+            GroupError(err) => err.into(),
+            err @ (Identity(_) | StoredIdentityMismatch | AddressValidation(_)) => {
+                Self::Identity(err.into())
+            }
+            err => Self::Client {
+                retryable: false,
+                source: err.into(),
+            },
+        }
+    }
+}
+
+impl From<xmtp_proto::Error> for Error {
+    fn from(err: xmtp_proto::Error) -> Self {
+        Self::Connection(err.into())
+    }
+}
+
+impl From<xmtp_mls::api::WrappedApiError> for Error {
+    fn from(err: xmtp_mls::api::WrappedApiError) -> Self {
+        Self::Connection(err.into())
+    }
+}
+
+impl From<xmtp_mls::storage::StorageError> for Error {
+    fn from(err: xmtp_mls::storage::StorageError) -> Self {
+        match err {
+            xmtp_mls::storage::StorageError::NotFound(not_found) => {
+                Self::NotFound(not_found.to_string())
+            }
+            err => Self::Storage {
+                retryable: err.is_retryable(),
+                source: err.into(),
+            },
+        }
+    }
+}
+
+impl From<xmtp_id::associations::AssociationError> for Error {
+    fn from(err: xmtp_id::associations::AssociationError) -> Self {
+        Self::Identity(err.into())
+    }
+}
+
+impl From<xmtp_id::associations::builder::SignatureRequestError> for Error {
+    fn from(err: xmtp_id::associations::builder::SignatureRequestError) -> Self {
+        Self::Identity(err.into())
+    }
+}
+
+impl From<xmtp_cryptography::signature::SignatureError> for Error {
+    fn from(err: xmtp_cryptography::signature::SignatureError) -> Self {
+        Self::Identity(err.into())
+    }
+}
+
+impl From<xmtp_mls::client::ClientError> for Error {
+    fn from(err: xmtp_mls::client::ClientError) -> Self {
+        if let xmtp_mls::client::ClientError::Storage(err) = err {
+            return err.into();
+        }
+        Self::Client {
+            retryable: err.is_retryable(),
+            source: err.into(),
+        }
+    }
+}
+
+impl From<xmtp_mls::groups::GroupError> for Error {
+    fn from(err: xmtp_mls::groups::GroupError) -> Self {
+        if let xmtp_mls::groups::GroupError::Storage(err) = err {
+            return err.into();
+        }
+        Self::Conversation {
+            retryable: err.is_retryable(),
+            source: err.into(),
+        }
+    }
+}
+
+impl From<xmtp_mls::subscriptions::SubscribeError> for Error {
+    fn from(err: xmtp_mls::subscriptions::SubscribeError) -> Self {
+        Self::Stream {
+            retryable: err.is_retryable(),
+            source: err.into(),
+        }
+    }
+}
+
+impl From<xmtp_content_types::CodecError> for Error {
+    fn from(err: xmtp_content_types::CodecError) -> Self {
+        Self::Content(err.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xmtp_mls::storage::{NotFound, StorageError};
+
+    #[test]
+    fn storage_errors_are_classified_apart_from_connection_errors() {
+        let missing: Error = StorageError::NotFound(NotFound::GroupById(vec![1])).into();
+        assert!(matches!(missing, Error::NotFound(_)));
+        assert!(!missing.is_retryable());
+
+        let reconnecting: Error = StorageError::PoolNeedsConnection.into();
+        assert!(matches!(reconnecting, Error::Storage { .. }));
+        assert!(reconnecting.is_retryable());
+
+        let wrong_key: Error =
+            xmtp_mls::client::ClientError::Storage(StorageError::SqlCipherKeyIncorrect).into();
+        assert!(matches!(wrong_key, Error::Storage { .. }));
+        assert!(!wrong_key.is_retryable());
+    }
+}
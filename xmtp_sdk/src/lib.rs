@@ -0,0 +1,34 @@
+//! A stable Rust API for building applications on XMTP.
+//!
+//! `xmtp_sdk` wraps the internal `xmtp_mls` crate behind a small set of types that follow
+//! semver: client lifecycle, conversations, messages, streams and consent. Internal types are
+//! never exposed, so applications can upgrade without tracking refactors of the protocol
+//! implementation.
+//!
+//! ```no_run
+//! # async fn example(wallet: impl xmtp_sdk::InboxOwner) -> Result<(), xmtp_sdk::Error> {
+//! use xmtp_sdk::{Client, Env};
+//!
+//! let client = Client::builder(Env::Dev)
+//!     .db_path("xmtp.db3")
+//!     .build(&wallet)
+//!     .await?;
+//! let dm = client.create_dm("0x...").await?;
+//! dm.send_text("gm").await?;
+//! # Ok(())
+//! # }
+//! ```
+#![warn(clippy::unwrap_used)]
+
+pub mod client;
+pub mod consent;
+pub mod conversation;
+pub mod error;
+pub mod message;
+
+pub use client::{Client, ClientBuilder, ConversationQuery, Env, Event};
+pub use consent::{ConsentEntity, ConsentRecord, ConsentState};
+pub use conversation::{Conversation, ConversationKind, Member, MessageQuery};
+pub use error::Error;
+pub use message::{Message, MessageKind};
+pub use xmtp_id::InboxOwner;
@@ -0,0 +1,103 @@
+//! Messages sent in a conversation.
+use prost::Message as _;
+use xmtp_content_types::{text::TextCodec, ContentCodec};
+use xmtp_mls::storage::group_message::{GroupMessageKind, StoredGroupMessage};
+use xmtp_proto::xmtp::mls::message_contents::EncodedContent;
+
+use crate::Error;
+
+/// What a message records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MessageKind {
+    /// Content sent by a member
+    Application,
+    /// Members were added or removed, or the conversation's metadata changed
+    MembershipChange,
+}
+
+/// A message stored on this installation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub id: Vec<u8>,
+    pub conversation_id: Vec<u8>,
+    pub sender_inbox_id: String,
+    pub sender_installation_id: Vec<u8>,
+    pub sent_at_ns: i64,
+    pub kind: MessageKind,
+    /// Encoded content, as sent. Decode it with the codec for [`Message::content_type`].
+    pub content: Vec<u8>,
+    /// The content type, formatted as `authority/type:major.minor`
+    pub content_type: String,
+}
+
+impl Message {
+    /// The content decoded as text, if the message is a text message
+    pub fn text(&self) -> Result<Option<String>, Error> {
+        let content = EncodedContent::decode(self.content.as_slice())
+            .map_err(|e| Error::Content(e.into()))?;
+        if content
+            .r#type
+            .as_ref()
+            .is_none_or(|t| t.type_id != TextCodec::TYPE_ID)
+        {
+            return Ok(None);
+        }
+        Ok(Some(TextCodec::decode(content)?))
+    }
+}
+
+impl From<StoredGroupMessage> for Message {
+    fn from(message: StoredGroupMessage) -> Self {
+        Self {
+            content_type: format!(
+                "{}/{}:{}.{}",
+                message.authority_id,
+                message.content_type,
+                message.version_major,
+                message.version_minor
+            ),
+            id: message.id,
+            conversation_id: message.group_id,
+            sender_inbox_id: message.sender_inbox_id,
+            sender_installation_id: message.sender_installation_id,
+            sent_at_ns: message.sent_at_ns,
+            kind: match message.kind {
+                GroupMessageKind::Application => MessageKind::Application,
+                GroupMessageKind::MembershipChange => MessageKind::MembershipChange,
+            },
+            content: message.decrypted_message_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xmtp_content_types::{encoded_content_to_bytes, reaction::ReactionCodec};
+
+    fn message(content: EncodedContent) -> Message {
+        Message {
+            id: vec![1],
+            conversation_id: vec![2],
+            sender_inbox_id: "inbox".to_string(),
+            sender_installation_id: vec![3],
+            sent_at_ns: 0,
+            kind: MessageKind::Application,
+            content: encoded_content_to_bytes(content),
+            content_type: String::new(),
+        }
+    }
+
+    #[test]
+    fn decodes_text_messages_only() {
+        let text = message(TextCodec::encode("gm".to_string()).unwrap());
+        assert_eq!(text.text().unwrap(), Some("gm".to_string()));
+
+        let reaction = EncodedContent {
+            r#type: Some(ReactionCodec::content_type()),
+            ..Default::default()
+        };
+        assert_eq!(message(reaction).text().unwrap(), None);
+    }
+}